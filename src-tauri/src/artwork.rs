@@ -0,0 +1,184 @@
+// Embedded cover art writing
+// Replaces the front cover in a file's tags (ID3 APIC for MP3, a PICTURE
+// block for FLAC). Images that are too large, or in a format players handle
+// poorly, are scaled down and re-encoded as JPEG before embedding.
+
+use id3::TagLike;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::Serialize;
+use std::path::Path;
+use thiserror::Error;
+
+/// Longest side of embedded artwork; larger images are scaled down
+pub const MAX_ARTWORK_DIMENSION: u32 = 1200;
+
+/// Images above this size are re-encoded even when their dimensions are fine
+pub const MAX_ARTWORK_BYTES: usize = 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Error, Debug)]
+pub enum ArtworkError {
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Embedded artwork is not supported for .{0} files")]
+    UnsupportedFormat(String),
+    #[error("Unsupported image type: {0}")]
+    UnsupportedImage(String),
+    #[error("Failed to decode image: {0}")]
+    InvalidImage(String),
+    #[error("Failed to write artwork tag: {0}")]
+    Tag(String),
+}
+
+/// The image that ended up in the file
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct EmbeddedArtwork {
+    pub mime: String,
+    pub width: u32,
+    pub height: u32,
+    pub size_bytes: usize,
+    /// True when the image was scaled down or re-encoded before embedding
+    pub converted: bool,
+}
+
+/// Embed `image_bytes` as the front cover of the file, replacing any existing cover
+pub fn set_artwork(file_path: &Path, image_bytes: &[u8], mime: &str) -> Result<EmbeddedArtwork, ArtworkError> {
+    if !file_path.exists() {
+        return Err(ArtworkError::FileNotFound(file_path.to_string_lossy().to_string()));
+    }
+    let extension = file_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    if !matches!(extension.as_str(), "mp3" | "flac") {
+        return Err(ArtworkError::UnsupportedFormat(extension));
+    }
+    let (data, artwork) = prepare_artwork(image_bytes, mime)?;
+    match extension.as_str() {
+        "mp3" => {
+            let mut tag = match id3::Tag::read_from_path(file_path) {
+                Ok(tag) => tag,
+                Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+                Err(e) => return Err(ArtworkError::Tag(e.to_string())),
+            };
+            // "Other" pictures are often the only cover in files tagged by older tools
+            tag.remove_picture_by_type(id3::frame::PictureType::CoverFront);
+            tag.remove_picture_by_type(id3::frame::PictureType::Other);
+            tag.add_frame(id3::frame::Picture {
+                mime_type: artwork.mime.clone(),
+                picture_type: id3::frame::PictureType::CoverFront,
+                description: String::new(),
+                data,
+            });
+            tag.write_to_path(file_path, id3::Version::Id3v24)
+                .map_err(|e| ArtworkError::Tag(e.to_string()))?;
+        }
+        _ => {
+            let mut tag = metaflac::Tag::read_from_path(file_path).map_err(|e| ArtworkError::Tag(e.to_string()))?;
+            tag.remove_picture_type(metaflac::block::PictureType::Other);
+            tag.add_picture(artwork.mime.clone(), metaflac::block::PictureType::CoverFront, data);
+            tag.save().map_err(|e| ArtworkError::Tag(e.to_string()))?;
+        }
+    }
+    Ok(artwork)
+}
+
+/// Validate the image and shrink or convert it when needed
+///
+/// JPEG and PNG images within `MAX_ARTWORK_DIMENSION` and `MAX_ARTWORK_BYTES`
+/// are embedded as-is; anything else is scaled to fit and re-encoded as JPEG.
+pub fn prepare_artwork(image_bytes: &[u8], mime: &str) -> Result<(Vec<u8>, EmbeddedArtwork), ArtworkError> {
+    let mime = mime.trim().to_lowercase();
+    let format = ImageFormat::from_mime_type(&mime).ok_or_else(|| ArtworkError::UnsupportedImage(mime.clone()))?;
+    let image = image::load_from_memory_with_format(image_bytes, format)
+        .map_err(|e| ArtworkError::InvalidImage(e.to_string()))?;
+    let (width, height) = image.dimensions();
+    let fits = width <= MAX_ARTWORK_DIMENSION && height <= MAX_ARTWORK_DIMENSION && image_bytes.len() <= MAX_ARTWORK_BYTES;
+    if fits && matches!(format, ImageFormat::Jpeg | ImageFormat::Png) {
+        let artwork = EmbeddedArtwork {
+            mime: format.to_mime_type().to_string(),
+            width,
+            height,
+            size_bytes: image_bytes.len(),
+            converted: false,
+        };
+        return Ok((image_bytes.to_vec(), artwork));
+    }
+
+    let image = if width > MAX_ARTWORK_DIMENSION || height > MAX_ARTWORK_DIMENSION {
+        image.resize(MAX_ARTWORK_DIMENSION, MAX_ARTWORK_DIMENSION, image::imageops::FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let data = encode_jpeg(&image)?;
+    let (width, height) = image.dimensions();
+    let artwork = EmbeddedArtwork {
+        mime: ImageFormat::Jpeg.to_mime_type().to_string(),
+        width,
+        height,
+        size_bytes: data.len(),
+        converted: true,
+    };
+    Ok((data, artwork))
+}
+
+fn encode_jpeg(image: &DynamicImage) -> Result<Vec<u8>, ArtworkError> {
+    let mut data = Vec::new();
+    JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+        .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+        .map_err(|e| ArtworkError::InvalidImage(e.to_string()))?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_prepare_artwork() {
+        let small = png(300, 300);
+        let (data, artwork) = prepare_artwork(&small, "image/png").unwrap();
+        assert_eq!(data, small);
+        assert!(!artwork.converted);
+
+        let (data, artwork) = prepare_artwork(&png(2400, 1200), "image/png").unwrap();
+        assert!(artwork.converted);
+        assert_eq!((artwork.width, artwork.height), (1200, 600));
+        assert_eq!(artwork.mime, "image/jpeg");
+        assert_eq!(image::guess_format(&data).unwrap(), ImageFormat::Jpeg);
+
+        assert!(matches!(prepare_artwork(&small, "text/plain"), Err(ArtworkError::UnsupportedImage(_))));
+        assert!(matches!(prepare_artwork(b"not an image", "image/jpeg"), Err(ArtworkError::InvalidImage(_))));
+    }
+
+    #[test]
+    fn test_replace_id3_cover() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.mp3");
+        std::fs::write(&path, b"").unwrap();
+        set_artwork(&path, &png(64, 64), "image/png").unwrap();
+        let cover = png(32, 32);
+        set_artwork(&path, &cover, "image/png").unwrap();
+
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        let pictures: Vec<_> = tag.pictures().collect();
+        assert_eq!(pictures.len(), 1);
+        assert_eq!(pictures[0].data, cover);
+        assert!(matches!(
+            set_artwork(&dir.path().join("missing.mp3"), &cover, "image/png"),
+            Err(ArtworkError::FileNotFound(_))
+        ));
+    }
+}
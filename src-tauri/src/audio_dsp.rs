@@ -0,0 +1,327 @@
+// Spectrum analysis and beat detection for visualizer data
+// Turns mono sample blocks from system audio capture or the native player into
+// compact visualizer frames
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+/// Samples per analysis block; must be a power of two
+pub const FFT_SIZE: usize = 2048;
+
+/// Number of magnitude bins sent to the frontend
+pub const DISPLAY_BIN_COUNT: usize = 256;
+
+/// Number of log-spaced spectrum bands
+pub const BAND_COUNT: usize = 16;
+
+/// Per-frame decay applied to falling band values (rises are immediate)
+const BAND_DECAY: f32 = 0.8;
+
+/// Frames of history used for the adaptive beat and onset thresholds (~1s at 48kHz)
+const HISTORY_FRAMES: usize = 43;
+
+/// Bass energy must exceed its recent average by this factor to count as a beat
+const BEAT_SENSITIVITY: f32 = 1.4;
+
+/// Spectral flux must exceed its recent average by this factor to count as an onset
+const ONSET_SENSITIVITY: f32 = 1.5;
+
+/// Ignore beats closer together than this (caps detection at 240 BPM)
+const MIN_BEAT_INTERVAL_SECS: f32 = 0.25;
+
+/// Upper edge of the bass range used for beat detection
+const BASS_CUTOFF_HZ: f32 = 150.0;
+
+const MIN_BAND_HZ: f32 = 20.0;
+const MAX_BAND_HZ: f32 = 20_000.0;
+
+/// Payload of the `visualizer-frame` event
+#[derive(Debug, Clone, Serialize)]
+pub struct VisualizerFrame {
+    /// Linear magnitudes, 0.0-1.0 for a full-scale sine, spanning 0 Hz to Nyquist
+    pub bins: Vec<f32>,
+    /// Smoothed log-spaced band levels
+    pub bands: Vec<f32>,
+    /// RMS level of the block
+    pub volume: f32,
+    pub beat: bool,
+    pub onset: bool,
+    /// Bass energy relative to its recent average (1.0 = average)
+    pub beat_strength: f32,
+    pub sample_rate: u32,
+}
+
+/// Stateful analyzer; keeps smoothing and beat history between blocks
+pub struct SpectrumAnalyzer {
+    sample_rate: u32,
+    window: Vec<f32>,
+    band_edges: Vec<usize>,
+    bands: Vec<f32>,
+    previous_magnitudes: Vec<f32>,
+    bass_history: VecDeque<f32>,
+    flux_history: VecDeque<f32>,
+    secs_since_beat: f32,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(sample_rate: u32) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / (FFT_SIZE - 1) as f32).cos())
+            .collect();
+
+        Self {
+            sample_rate,
+            window,
+            band_edges: band_edges(sample_rate),
+            bands: vec![0.0; BAND_COUNT],
+            previous_magnitudes: vec![0.0; FFT_SIZE / 2],
+            bass_history: VecDeque::with_capacity(HISTORY_FRAMES),
+            flux_history: VecDeque::with_capacity(HISTORY_FRAMES),
+            secs_since_beat: f32::MAX,
+        }
+    }
+
+    /// Analyze one block of mono samples
+    ///
+    /// Blocks shorter than `FFT_SIZE` are zero-padded; longer ones are truncated.
+    pub fn process(&mut self, samples: &[f32]) -> VisualizerFrame {
+        let magnitudes = self.magnitudes(samples);
+        let volume = rms(samples);
+
+        // Bands rise immediately and fall off gradually
+        for (band, level) in self.bands.iter_mut().enumerate() {
+            let start = self.band_edges[band];
+            let end = self.band_edges[band + 1].max(start + 1);
+            let value = magnitudes[start..end].iter().sum::<f32>() / (end - start) as f32;
+            *level = value.max(*level * BAND_DECAY);
+        }
+
+        let flux: f32 = magnitudes
+            .iter()
+            .zip(&self.previous_magnitudes)
+            .map(|(current, previous)| (current - previous).max(0.0))
+            .sum();
+        let bass_bins = ((BASS_CUTOFF_HZ / self.bin_hz()) as usize).clamp(1, magnitudes.len());
+        let bass: f32 = magnitudes[1..bass_bins.max(2)].iter().map(|m| m * m).sum();
+
+        let bass_average = average(&self.bass_history);
+        let flux_average = average(&self.flux_history);
+        let beat_strength = if bass_average > f32::EPSILON { bass / bass_average } else { 0.0 };
+
+        let block_secs = FFT_SIZE as f32 / self.sample_rate as f32;
+        self.secs_since_beat += block_secs;
+        let history_ready = self.bass_history.len() >= HISTORY_FRAMES / 4;
+        let beat = history_ready
+            && beat_strength > BEAT_SENSITIVITY
+            && self.secs_since_beat >= MIN_BEAT_INTERVAL_SECS;
+        if beat {
+            self.secs_since_beat = 0.0;
+        }
+        let onset = history_ready && flux > flux_average * ONSET_SENSITIVITY && flux > f32::EPSILON;
+
+        push_history(&mut self.bass_history, bass);
+        push_history(&mut self.flux_history, flux);
+
+        let frame = VisualizerFrame {
+            bins: downsample(&magnitudes, DISPLAY_BIN_COUNT),
+            bands: self.bands.clone(),
+            volume,
+            beat,
+            onset,
+            beat_strength,
+            sample_rate: self.sample_rate,
+        };
+        self.previous_magnitudes = magnitudes;
+        frame
+    }
+
+    fn bin_hz(&self) -> f32 {
+        self.sample_rate as f32 / FFT_SIZE as f32
+    }
+
+    /// Windowed FFT magnitudes for bins 0..FFT_SIZE/2, normalized to a full-scale sine
+    fn magnitudes(&self, samples: &[f32]) -> Vec<f32> {
+        let mut re: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| samples.get(i).copied().unwrap_or(0.0) * self.window[i])
+            .collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+
+        // The Hann window halves the coherent gain
+        let scale = 4.0 / FFT_SIZE as f32;
+        re.iter()
+            .zip(&im)
+            .take(FFT_SIZE / 2)
+            .map(|(r, i)| (r * r + i * i).sqrt() * scale)
+            .collect()
+    }
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// FFT bin boundaries of log-spaced bands between 20 Hz and min(20 kHz, Nyquist)
+fn band_edges(sample_rate: u32) -> Vec<usize> {
+    let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+    let max_bin = FFT_SIZE / 2;
+    let top = MAX_BAND_HZ.min(sample_rate as f32 / 2.0);
+    let ratio = (top / MIN_BAND_HZ).powf(1.0 / BAND_COUNT as f32);
+
+    let mut edges: Vec<usize> = (0..=BAND_COUNT)
+        .map(|i| ((MIN_BAND_HZ * ratio.powi(i as i32) / bin_hz).round() as usize).clamp(1, max_bin))
+        .collect();
+    // Low bands can round to the same bin; keep every band at least one bin wide
+    for i in 1..edges.len() {
+        if edges[i] <= edges[i - 1] {
+            edges[i] = (edges[i - 1] + 1).min(max_bin);
+        }
+    }
+    edges
+}
+
+/// Average adjacent bins down to `count` values
+fn downsample(magnitudes: &[f32], count: usize) -> Vec<f32> {
+    let group = (magnitudes.len() / count).max(1);
+    magnitudes
+        .chunks(group)
+        .take(count)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+fn average(history: &VecDeque<f32>) -> f32 {
+    if history.is_empty() {
+        0.0
+    } else {
+        history.iter().sum::<f32>() / history.len() as f32
+    }
+}
+
+fn push_history(history: &mut VecDeque<f32>, value: f32) {
+    if history.len() == HISTORY_FRAMES {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn sine(freq: f32, amplitude: f32) -> Vec<f32> {
+        (0..FFT_SIZE)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_peaks_at_expected_bin() {
+        let analyzer = SpectrumAnalyzer::new(SAMPLE_RATE);
+        // Center the tone on bin 100
+        let freq = 100.0 * SAMPLE_RATE as f32 / FFT_SIZE as f32;
+        let magnitudes = analyzer.magnitudes(&sine(freq, 1.0));
+
+        let peak = magnitudes
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap();
+        assert_eq!(peak.0, 100);
+        assert!((peak.1 - 1.0).abs() < 0.05, "full-scale sine magnitude was {}", peak.1);
+    }
+
+    #[test]
+    fn test_frame_shape() {
+        let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE);
+        let frame = analyzer.process(&sine(1000.0, 0.5));
+        assert_eq!(frame.bins.len(), DISPLAY_BIN_COUNT);
+        assert_eq!(frame.bands.len(), BAND_COUNT);
+        assert!((frame.volume - 0.5 / 2f32.sqrt()).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_band_edges_are_increasing() {
+        for rate in [22_050, 44_100, 48_000, 96_000] {
+            let edges = band_edges(rate);
+            assert_eq!(edges.len(), BAND_COUNT + 1);
+            assert!(edges.windows(2).all(|w| w[0] < w[1]), "edges not increasing at {} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn test_bands_decay_after_silence() {
+        let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE);
+        let loud = analyzer.process(&sine(1000.0, 1.0));
+        let quiet = analyzer.process(&[0.0; FFT_SIZE]);
+        let peak_band = loud
+            .bands
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .unwrap()
+            .0;
+        assert!(quiet.bands[peak_band] > 0.0);
+        assert!(quiet.bands[peak_band] < loud.bands[peak_band]);
+    }
+
+    #[test]
+    fn test_bass_hit_after_quiet_is_a_beat() {
+        let mut analyzer = SpectrumAnalyzer::new(SAMPLE_RATE);
+        for _ in 0..HISTORY_FRAMES {
+            assert!(!analyzer.process(&sine(60.0, 0.05)).beat);
+        }
+        let frame = analyzer.process(&sine(60.0, 1.0));
+        assert!(frame.beat);
+        assert!(frame.onset);
+        assert!(frame.beat_strength > BEAT_SENSITIVITY);
+
+        // A second hit inside the refractory window is ignored
+        assert!(!analyzer.process(&sine(60.0, 1.0)).beat);
+    }
+}
@@ -0,0 +1,357 @@
+// Delivery options for captured audio
+// Throttling and downsampling for capture events, plus a ring buffer the
+// frontend can poll and a lock-free one for handing samples out of (or into)
+// real-time audio callbacks
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default ring buffer size: a little over one second of audio at 48kHz
+pub const DEFAULT_RING_CAPACITY: usize = 65_536;
+
+/// How raw samples reach the frontend
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureTransport {
+    /// Push every block as a `system-audio-data` event
+    Events,
+    /// Keep samples in a ring buffer read with `read_capture_buffer`
+    RingBuffer,
+}
+
+/// Tuning for latency versus IPC overhead
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// Upper bound on events per second; None emits every block
+    #[serde(default)]
+    pub max_fps: Option<u32>,
+    /// Keep one averaged sample out of every N (1 = full rate)
+    #[serde(default = "default_downsample_factor")]
+    pub downsample_factor: u32,
+    #[serde(default = "default_transport")]
+    pub transport: CaptureTransport,
+    /// Ring buffer capacity in (downsampled) samples
+    #[serde(default = "default_ring_capacity")]
+    pub ring_capacity: usize,
+}
+
+fn default_downsample_factor() -> u32 {
+    1
+}
+
+fn default_transport() -> CaptureTransport {
+    CaptureTransport::Events
+}
+
+fn default_ring_capacity() -> usize {
+    DEFAULT_RING_CAPACITY
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            max_fps: None,
+            downsample_factor: default_downsample_factor(),
+            transport: default_transport(),
+            ring_capacity: default_ring_capacity(),
+        }
+    }
+}
+
+impl CaptureOptions {
+    /// Reject settings that would stall or starve the capture pipeline
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_fps == Some(0) {
+            return Err("max_fps must be at least 1".to_string());
+        }
+        if !(1..=16).contains(&self.downsample_factor) {
+            return Err("downsample_factor must be between 1 and 16".to_string());
+        }
+        if !(1024..=1 << 20).contains(&self.ring_capacity) {
+            return Err("ring_capacity must be between 1024 and 1048576 samples".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Average every `factor` samples into one
+pub fn downsample(samples: &[f32], factor: u32) -> Vec<f32> {
+    if factor <= 1 {
+        return samples.to_vec();
+    }
+    samples
+        .chunks(factor as usize)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect()
+}
+
+/// Limits how often capture events are emitted
+#[derive(Debug, Default)]
+pub struct FrameThrottle {
+    last_emit: Option<Instant>,
+}
+
+impl FrameThrottle {
+    /// Whether a frame may be emitted at `now`; records the emission if so
+    pub fn ready(&mut self, max_fps: Option<u32>, now: Instant) -> bool {
+        let Some(fps) = max_fps.filter(|fps| *fps > 0) else {
+            self.last_emit = Some(now);
+            return true;
+        };
+        let interval = Duration::from_secs_f64(1.0 / fps as f64);
+        match self.last_emit {
+            Some(last) if now.duration_since(last) < interval => false,
+            _ => {
+                self.last_emit = Some(now);
+                true
+            }
+        }
+    }
+}
+
+/// Samples returned from a ring buffer read
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptureBufferRead {
+    pub samples: Vec<f32>,
+    /// Pass back as `cursor` on the next read
+    pub cursor: u64,
+    /// Samples overwritten before they could be read
+    pub dropped: u64,
+    pub sample_rate: u32,
+}
+
+/// Fixed-capacity sample ring addressed by a monotonically increasing cursor
+#[derive(Debug)]
+pub struct CaptureRingBuffer {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    /// Total samples ever written
+    written: u64,
+    sample_rate: u32,
+}
+
+impl CaptureRingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            written: 0,
+            sample_rate: 0,
+        }
+    }
+
+    /// Change capacity, keeping the most recent samples
+    pub fn resize(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.samples.len() > capacity {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32], sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        for &sample in samples {
+            if self.samples.len() == self.capacity {
+                self.samples.pop_front();
+            }
+            self.samples.push_back(sample);
+        }
+        self.written += samples.len() as u64;
+    }
+
+    /// Everything written after `cursor` that is still buffered
+    pub fn read_since(&self, cursor: u64) -> CaptureBufferRead {
+        let oldest = self.written - self.samples.len() as u64;
+        let start = cursor.clamp(oldest, self.written);
+        CaptureBufferRead {
+            samples: self.samples.iter().skip((start - oldest) as usize).copied().collect(),
+            cursor: self.written,
+            dropped: start - cursor.min(start),
+            sample_rate: self.sample_rate,
+        }
+    }
+}
+
+/// Single-producer, single-consumer sample queue that never locks or allocates
+///
+/// One thread pushes and one pops; either may be an audio callback. Samples
+/// are stored as `f32` bits in atomics, and the positions count every sample
+/// ever pushed or popped.
+pub struct SampleRing {
+    slots: Box<[AtomicU32]>,
+    written: AtomicU64,
+    read: AtomicU64,
+    /// Samples before this position are skipped by the consumer
+    discard_before: AtomicU64,
+}
+
+impl SampleRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicU64::new(0),
+            read: AtomicU64::new(0),
+            discard_before: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Where the consumer continues from, past anything discarded
+    fn read_position(&self) -> u64 {
+        self.read.load(Ordering::Acquire).max(self.discard_before.load(Ordering::Acquire))
+    }
+
+    /// Samples waiting to be popped
+    pub fn len(&self) -> usize {
+        self.written.load(Ordering::Acquire).saturating_sub(self.read_position()) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Producer side: queue as many samples as fit, returning how many did
+    pub fn push(&self, samples: &[f32]) -> usize {
+        let written = self.written.load(Ordering::Relaxed);
+        let free = self.capacity() - written.saturating_sub(self.read_position()) as usize;
+        let count = samples.len().min(free);
+        for (i, sample) in samples[..count].iter().enumerate() {
+            self.slot(written + i as u64).store(sample.to_bits(), Ordering::Relaxed);
+        }
+        self.written.store(written + count as u64, Ordering::Release);
+        count
+    }
+
+    /// Consumer side: fill the front of `out`, returning how many samples were popped
+    pub fn pop(&self, out: &mut [f32]) -> usize {
+        let read = self.read_position();
+        let available = self.written.load(Ordering::Acquire).saturating_sub(read) as usize;
+        let count = out.len().min(available);
+        for (i, out) in out[..count].iter_mut().enumerate() {
+            *out = f32::from_bits(self.slot(read + i as u64).load(Ordering::Relaxed));
+        }
+        self.read.store(read + count as u64, Ordering::Release);
+        count
+    }
+
+    /// Drop everything queued so far
+    ///
+    /// Must not race a push; the producer is expected to be stopped or
+    /// serialized with the caller. The consumer skips the dropped samples on
+    /// its next pop.
+    pub fn clear(&self) {
+        self.discard_before.store(self.written.load(Ordering::Acquire), Ordering::Release);
+    }
+
+    fn slot(&self, position: u64) -> &AtomicU32 {
+        &self.slots[(position % self.slots.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_ring_wraps_and_clears() {
+        let ring = SampleRing::new(4);
+        assert_eq!(ring.push(&[1.0, 2.0, 3.0]), 3);
+        let mut out = [0.0; 2];
+        assert_eq!(ring.pop(&mut out), 2);
+        assert_eq!(out, [1.0, 2.0]);
+        // Full after wrapping around; the rest is refused rather than overwritten
+        assert_eq!(ring.push(&[4.0, 5.0, 6.0, 7.0]), 3);
+        assert_eq!(ring.len(), 4);
+        let mut out = [0.0; 8];
+        assert_eq!(ring.pop(&mut out), 4);
+        assert_eq!(out[..4], [3.0, 4.0, 5.0, 6.0]);
+
+        ring.push(&[8.0, 9.0]);
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(ring.push(&[10.0]), 1);
+        assert_eq!(ring.pop(&mut out), 1);
+        assert_eq!(out[0], 10.0);
+    }
+
+    #[test]
+    fn test_sample_ring_across_threads() {
+        let ring = std::sync::Arc::new(SampleRing::new(64));
+        let producer = {
+            let ring = ring.clone();
+            std::thread::spawn(move || {
+                let mut next = 0;
+                while next < 10_000 {
+                    next += ring.push(&[next as f32]);
+                }
+            })
+        };
+        let mut expected = 0;
+        let mut out = [0.0; 16];
+        while expected < 10_000 {
+            let popped = ring.pop(&mut out);
+            for sample in &out[..popped] {
+                assert_eq!(*sample, expected as f32);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn test_downsample_averages_groups() {
+        assert_eq!(downsample(&[1.0, 3.0, 5.0, 7.0, 9.0], 2), vec![2.0, 6.0, 9.0]);
+        assert_eq!(downsample(&[1.0, 2.0], 1), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_throttle_limits_rate() {
+        let mut throttle = FrameThrottle::default();
+        let start = Instant::now();
+        assert!(throttle.ready(Some(10), start));
+        assert!(!throttle.ready(Some(10), start + Duration::from_millis(50)));
+        assert!(throttle.ready(Some(10), start + Duration::from_millis(100)));
+        // Unthrottled always emits
+        assert!(throttle.ready(None, start + Duration::from_millis(101)));
+    }
+
+    #[test]
+    fn test_ring_buffer_reads_incrementally() {
+        let mut ring = CaptureRingBuffer::new(8);
+        ring.push(&[1.0, 2.0, 3.0], 44_100);
+
+        let first = ring.read_since(0);
+        assert_eq!(first.samples, vec![1.0, 2.0, 3.0]);
+        assert_eq!(first.dropped, 0);
+
+        ring.push(&[4.0], 44_100);
+        let second = ring.read_since(first.cursor);
+        assert_eq!(second.samples, vec![4.0]);
+        assert_eq!(second.cursor, 4);
+    }
+
+    #[test]
+    fn test_ring_buffer_reports_overwritten_samples() {
+        let mut ring = CaptureRingBuffer::new(4);
+        ring.push(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0], 48_000);
+
+        let read = ring.read_since(0);
+        assert_eq!(read.samples, vec![3.0, 4.0, 5.0, 6.0]);
+        assert_eq!(read.dropped, 2);
+        assert_eq!(read.sample_rate, 48_000);
+    }
+
+    #[test]
+    fn test_options_validation() {
+        assert!(CaptureOptions::default().validate().is_ok());
+        let options = CaptureOptions { max_fps: Some(0), ..Default::default() };
+        assert!(options.validate().is_err());
+        let options = CaptureOptions { downsample_factor: 0, ..Default::default() };
+        assert!(options.validate().is_err());
+    }
+}
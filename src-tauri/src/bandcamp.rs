@@ -0,0 +1,583 @@
+// Bandcamp collection
+// Bandcamp has no public API for fans, so this uses the endpoints its own
+// collection pages call. Signing in takes the `identity` cookie of a browser
+// session (the login form is behind a captcha); it is kept in secure storage
+// and sent only to Bandcamp's own hosts, never to artists' custom domains.
+// Purchases can be streamed from their album
+// pages or downloaded in any format Bandcamp offers: albums arrive as a zip
+// that is unpacked into `<library>/<artist>/<album>`, single tracks as one file.
+
+use crate::api_client::{self, ApiClient, RateBudget};
+use crate::error_recovery::ErrorRecovery;
+use crate::secure_storage::{PlatformSecureStorage, SecureStorage};
+use crate::spotify::ApiError;
+use crate::tag_pattern::sanitize;
+use reqwest::header::{HeaderValue, CONTENT_DISPOSITION, COOKIE};
+use reqwest::{RequestBuilder, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+const BANDCAMP_BASE: &str = "https://bandcamp.com";
+const IDENTITY_KEY: &str = "bandcamp_identity";
+
+/// Pages are fetched one at a time as the user scrolls
+const RATE_BUDGET: RateBudget = RateBudget::new(3, 1.0);
+
+/// Lossless albums run to hundreds of megabytes
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Largest page `list_purchases` asks for
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Error, Debug)]
+pub enum BandcampError {
+    #[error("Not signed in to Bandcamp")]
+    NotSignedIn,
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    #[error("Unexpected response from Bandcamp: {0}")]
+    Parse(String),
+    #[error("'{0}' is not offered for this purchase")]
+    FormatUnavailable(String),
+    #[error("Not enough space in the library folder for {0}")]
+    DiskFull(String),
+    #[error("Download failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid album archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+/// Download formats, named as Bandcamp names them
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum DownloadFormat {
+    #[serde(rename = "mp3-v0")]
+    Mp3V0,
+    #[serde(rename = "mp3-320")]
+    Mp3_320,
+    #[serde(rename = "flac")]
+    Flac,
+    #[serde(rename = "aac-hi")]
+    Aac,
+    #[serde(rename = "vorbis")]
+    Vorbis,
+    #[serde(rename = "alac")]
+    Alac,
+    #[serde(rename = "wav")]
+    Wav,
+    #[serde(rename = "aiff-lossless")]
+    Aiff,
+}
+
+impl DownloadFormat {
+    fn key(self) -> String {
+        serde_json::to_value(self).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+    }
+}
+
+/// The signed-in fan
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BandcampFan {
+    pub fan_id: u64,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PurchaseKind {
+    Album,
+    Track,
+}
+
+/// An album or track in the fan's collection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Purchase {
+    pub id: u64,
+    pub kind: PurchaseKind,
+    pub title: String,
+    pub artist: String,
+    /// Album or track page, where it streams from
+    pub url: String,
+    pub artwork_url: Option<String>,
+    /// Download page; missing for items that can't be downloaded, e.g. subscriptions
+    pub download_page: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PurchasePage {
+    pub items: Vec<Purchase>,
+    /// Pass to `list_purchases` for the next page; `None` on the last page
+    pub next_token: Option<String>,
+}
+
+/// A streamable track from an album or track page
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BandcampTrack {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration_sec: f64,
+    /// 128 kbps MP3; these URLs expire, so fetch them again before playing later
+    pub stream_url: String,
+}
+
+/// Where a download ended up
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DownloadedPurchase {
+    pub folder: PathBuf,
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+pub struct BandcampClient {
+    client: ApiClient,
+    storage: Box<dyn SecureStorage + Send + Sync>,
+    fan: Mutex<Option<BandcampFan>>,
+}
+
+impl BandcampClient {
+    pub fn new() -> Self {
+        Self::with_storage(PlatformSecureStorage::new())
+    }
+
+    /// Create a client that keeps the session cookie in `storage` instead of the OS keyring
+    pub fn with_storage(storage: impl SecureStorage + Send + Sync + 'static) -> Self {
+        BandcampClient {
+            client: ApiClient::new("bandcamp", RATE_BUDGET),
+            storage: Box::new(storage),
+            fan: Mutex::new(None),
+        }
+    }
+
+    /// Check `identity` against Bandcamp and keep it for later requests
+    pub async fn sign_in(&self, identity: &str) -> Result<BandcampFan, BandcampError> {
+        let identity = identity.trim().trim_start_matches("identity=");
+        let fan = self.fetch_fan(identity).await?;
+        self.storage
+            .store(IDENTITY_KEY, identity)
+            .map_err(|e| ApiError::StorageError(e.to_string()))?;
+        *self.fan.lock().unwrap() = Some(fan.clone());
+        Ok(fan)
+    }
+
+    pub fn sign_out(&self) -> Result<(), BandcampError> {
+        *self.fan.lock().unwrap() = None;
+        self.storage.delete(IDENTITY_KEY).map_err(|e| ApiError::StorageError(e.to_string()))?;
+        Ok(())
+    }
+
+    fn identity(&self) -> Result<String, BandcampError> {
+        self.storage
+            .retrieve(IDENTITY_KEY)
+            .map_err(|e| ApiError::StorageError(e.to_string()))?
+            .ok_or(BandcampError::NotSignedIn)
+    }
+
+    /// The signed-in fan, looked up once per session
+    pub async fn fan(&self) -> Result<BandcampFan, BandcampError> {
+        if let Some(fan) = self.fan.lock().unwrap().clone() {
+            return Ok(fan);
+        }
+        let fan = self.fetch_fan(&self.identity()?).await?;
+        *self.fan.lock().unwrap() = Some(fan.clone());
+        Ok(fan)
+    }
+
+    async fn fetch_fan(&self, identity: &str) -> Result<BandcampFan, BandcampError> {
+        let request = self.client.get(format!("{}/api/fan/2/collection_summary", BANDCAMP_BASE));
+        let summary = self.send_json(request, identity).await?;
+        let fan_id = summary["fan_id"]
+            .as_u64()
+            .ok_or_else(|| ApiError::AuthenticationError("Bandcamp session cookie was not accepted".to_string()))?;
+        Ok(BandcampFan {
+            fan_id,
+            username: summary["collection_summary"]["username"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    /// A page of the collection, newest purchases first
+    pub async fn list_purchases(&self, token: Option<String>, limit: u32) -> Result<PurchasePage, BandcampError> {
+        let fan = self.fan().await?;
+        // Tokens are "<unix time>::<type>::"; one far in the future starts at the newest purchase
+        let token = token.unwrap_or_else(|| "9999999999::a::".to_string());
+        let request = self.client.post(format!("{}/api/fancollection/1/collection_items", BANDCAMP_BASE)).json(&serde_json::json!({
+            "fan_id": fan.fan_id,
+            "older_than_token": token,
+            "count": limit.clamp(1, MAX_PAGE_SIZE),
+        }));
+        let page = self.send_json(request, &self.identity()?).await?;
+        parse_collection(&page)
+    }
+
+    /// Tracks of an album or track page, with their stream URLs
+    pub async fn stream_tracks(&self, url: &str) -> Result<Vec<BandcampTrack>, BandcampError> {
+        let html = self.send_text(self.client.get(url), &self.identity()?).await?;
+        let tralbum = html_attribute(&html, "data-tralbum").ok_or_else(|| BandcampError::Parse("no track data on page".to_string()))?;
+        parse_tralbum(&serde_json::from_str(&tralbum).map_err(|e| BandcampError::Parse(e.to_string()))?)
+    }
+
+    /// Download `purchase` in `format` into `library`, reporting (bytes received, total)
+    pub async fn download(
+        &self,
+        purchase: &Purchase,
+        format: DownloadFormat,
+        library: &Path,
+        mut progress: impl FnMut(u64, Option<u64>),
+    ) -> Result<DownloadedPurchase, BandcampError> {
+        let page = purchase
+            .download_page
+            .as_deref()
+            .ok_or_else(|| BandcampError::FormatUnavailable(format.key()))?;
+        let identity = self.identity()?;
+        let html = self.send_text(self.client.get(page), &identity).await?;
+        let blob = html_attribute(&html, "data-blob").ok_or_else(|| BandcampError::Parse("no download data on page".to_string()))?;
+        let downloads = parse_downloads(&serde_json::from_str(&blob).map_err(|e| BandcampError::Parse(e.to_string()))?);
+        let url = downloads.get(&format.key()).ok_or_else(|| BandcampError::FormatUnavailable(format.key()))?;
+        let url = self.resolve_download(url, &identity).await.unwrap_or_else(|| url.clone());
+
+        let folder = library.join(sanitize(&purchase.artist)).join(sanitize(&purchase.title));
+        fs::create_dir_all(&folder)?;
+        let mut response = with_identity(api_client::http_client().get(&url).timeout(DOWNLOAD_TIMEOUT), &identity)?
+            .send()
+            .await
+            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(ApiError::NetworkError(format!("Status {}", response.status())).into());
+        }
+        let total = response.content_length();
+        // Zips are unpacked next to their download, so they need room twice
+        let needed = total.unwrap_or(0) * if purchase.kind == PurchaseKind::Album { 2 } else { 1 };
+        if !ErrorRecovery::has_free_space(&folder, needed) {
+            return Err(BandcampError::DiskFull(purchase.title.clone()));
+        }
+        let name = response
+            .headers()
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(disposition_filename)
+            .unwrap_or_else(|| format!("{} - {}", purchase.artist, purchase.title));
+
+        let partial = folder.join(format!(".{}.part", sanitize(&name)));
+        let mut file = File::create(&partial)?;
+        let mut received = 0;
+        let written: Result<(), BandcampError> = async {
+            while let Some(chunk) = response.chunk().await.map_err(|e| ApiError::NetworkError(e.to_string()))? {
+                file.write_all(&chunk)?;
+                received += chunk.len() as u64;
+                progress(received, total);
+            }
+            file.flush()?;
+            Ok(())
+        }
+        .await;
+        drop(file);
+        let files = written.and_then(|()| {
+            if name.to_lowercase().ends_with(".zip") {
+                extract_album(&partial, &folder)
+            } else {
+                let path = folder.join(sanitize(&name));
+                fs::rename(&partial, &path)?;
+                Ok(vec![path])
+            }
+        });
+        let _ = fs::remove_file(&partial);
+        Ok(DownloadedPurchase { folder, files: files?, bytes: received })
+    }
+
+    /// The signed file URL behind a download link; Bandcamp prepares the file on request
+    async fn resolve_download(&self, url: &str, identity: &str) -> Option<String> {
+        let status_url = url.replacen("/download/", "/statdownload/", 1) + "&.vrs=1";
+        let body = self.send_text(self.client.get(status_url), identity).await.ok()?;
+        // The response is JSON, possibly wrapped in a JavaScript callback
+        let json = &body[body.find('{')?..=body.rfind('}')?];
+        let status: Value = serde_json::from_str(json).ok()?;
+        status["download_url"].as_str().or(status["retry_url"].as_str()).map(str::to_string)
+    }
+
+    async fn send_text(&self, request: RequestBuilder, identity: &str) -> Result<String, BandcampError> {
+        let response = self.client.send(with_identity(request, identity)?).await?;
+        let status = response.status();
+        if status == 401 || status == 403 {
+            return Err(ApiError::AuthenticationError("Bandcamp session expired; sign in again".to_string()).into());
+        }
+        if status == 404 {
+            return Err(ApiError::NotFound(response.url().to_string()).into());
+        }
+        if !status.is_success() {
+            return Err(ApiError::NetworkError(format!("Status {}", status)).into());
+        }
+        response.text().await.map_err(|e| ApiError::NetworkError(e.to_string()).into())
+    }
+
+    async fn send_json(&self, request: RequestBuilder, identity: &str) -> Result<Value, BandcampError> {
+        let body = self.send_text(request, identity).await?;
+        let value: Value = serde_json::from_str(&body).map_err(|e| BandcampError::Parse(e.to_string()))?;
+        if value["error"].as_bool() == Some(true) {
+            let message = value["error_message"].as_str().unwrap_or("request rejected");
+            return Err(ApiError::AuthenticationError(message.to_string()).into());
+        }
+        Ok(value)
+    }
+}
+
+/// Bandcamp itself and its file CDN; albums on custom domains are other people's servers
+fn is_bandcamp_host(url: &Url) -> bool {
+    url.scheme() == "https"
+        && url.host_str().is_some_and(|host| {
+            ["bandcamp.com", "bcbits.com"]
+                .iter()
+                .any(|domain| host == *domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')))
+        })
+}
+
+/// Attach the session cookie if the request goes to Bandcamp
+fn with_identity(request: RequestBuilder, identity: &str) -> Result<RequestBuilder, BandcampError> {
+    let (client, request) = request.build_split();
+    let mut request = request.map_err(|e| ApiError::NetworkError(e.to_string()))?;
+    if is_bandcamp_host(request.url()) {
+        let cookie = HeaderValue::from_str(&format!("identity={}", identity))
+            .map_err(|_| ApiError::AuthenticationError("Bandcamp session cookie is malformed".to_string()))?;
+        request.headers_mut().insert(COOKIE, cookie);
+    }
+    Ok(RequestBuilder::from_parts(client, request))
+}
+
+fn parse_collection(page: &Value) -> Result<PurchasePage, BandcampError> {
+    let items = page["items"].as_array().ok_or_else(|| BandcampError::Parse("missing 'items'".to_string()))?;
+    let redownload = &page["redownload_urls"];
+    let purchases = items
+        .iter()
+        .filter_map(|item| {
+            let kind = match item["tralbum_type"].as_str()? {
+                "a" => PurchaseKind::Album,
+                "t" => PurchaseKind::Track,
+                _ => return None,
+            };
+            let sale = format!("{}{}", item["sale_item_type"].as_str().unwrap_or_default(), item["sale_item_id"]);
+            Some(Purchase {
+                id: item["tralbum_id"].as_u64()?,
+                kind,
+                title: item["item_title"].as_str().or(item["album_title"].as_str())?.to_string(),
+                artist: item["band_name"].as_str().unwrap_or_default().to_string(),
+                url: item["item_url"].as_str()?.to_string(),
+                artwork_url: item["item_art_id"].as_u64().map(|id| format!("https://f4.bcbits.com/img/a{}_10.jpg", id)),
+                download_page: redownload[&sale].as_str().map(str::to_string),
+            })
+        })
+        .collect();
+    let next_token = page["last_token"]
+        .as_str()
+        .filter(|_| page["more_available"].as_bool() == Some(true))
+        .map(str::to_string);
+    Ok(PurchasePage { items: purchases, next_token })
+}
+
+fn parse_tralbum(tralbum: &Value) -> Result<Vec<BandcampTrack>, BandcampError> {
+    let tracks = tralbum["trackinfo"].as_array().ok_or_else(|| BandcampError::Parse("missing 'trackinfo'".to_string()))?;
+    let artist = tralbum["artist"].as_str().unwrap_or_default();
+    let album = tralbum["current"]["title"].as_str().filter(|_| tralbum["item_type"].as_str() == Some("album"));
+    Ok(tracks
+        .iter()
+        .filter_map(|track| {
+            Some(BandcampTrack {
+                title: track["title"].as_str()?.to_string(),
+                artist: track["artist"].as_str().unwrap_or(artist).to_string(),
+                album: album.map(str::to_string),
+                track_number: track["track_num"].as_u64().map(|n| n as u32),
+                duration_sec: track["duration"].as_f64().unwrap_or(0.0),
+                stream_url: track["file"]["mp3-128"].as_str()?.to_string(),
+            })
+        })
+        .collect())
+}
+
+/// Download URLs by format key from a download page's data blob
+fn parse_downloads(blob: &Value) -> BTreeMap<String, String> {
+    blob["digital_items"]
+        .as_array()
+        .and_then(|items| items.first())
+        .and_then(|item| item["downloads"].as_object())
+        .map(|downloads| {
+            downloads
+                .iter()
+                .filter_map(|(format, download)| Some((format.clone(), download["url"].as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The HTML-unescaped value of the first `name="..."` attribute in `html`
+fn html_attribute(html: &str, name: &str) -> Option<String> {
+    let start = html.find(&format!("{}=\"", name))? + name.len() + 2;
+    let end = start + html[start..].find('"')?;
+    let mut value = String::with_capacity(end - start);
+    let mut rest = &html[start..end];
+    while let Some(amp) = rest.find('&') {
+        value.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else { break };
+        let decoded = match &rest[1..semi] {
+            "quot" => Some('"'),
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                value.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                value.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    value.push_str(rest);
+    Some(value)
+}
+
+/// `filename` from a Content-Disposition header, preferring the UTF-8 `filename*`
+fn disposition_filename(header: &str) -> Option<String> {
+    let mut plain = None;
+    for part in header.split(';').map(str::trim) {
+        if let Some(encoded) = part.strip_prefix("filename*=") {
+            let encoded = encoded.trim_matches('"');
+            let encoded = encoded.split_once("''").map_or(encoded, |(_, name)| name);
+            return Some(percent_encoding::percent_decode_str(encoded).decode_utf8_lossy().to_string());
+        }
+        if let Some(name) = part.strip_prefix("filename=") {
+            plain = Some(name.trim_matches('"').to_string());
+        }
+    }
+    plain.filter(|name| !name.is_empty())
+}
+
+/// Unpack an album zip into `folder`, flattening any folders inside it
+fn extract_album(zip_path: &Path, folder: &Path) -> Result<Vec<PathBuf>, BandcampError> {
+    let mut archive = zip::ZipArchive::new(File::open(zip_path)?)?;
+    let mut files = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().rsplit(['/', '\\']).next().unwrap_or_default().to_string();
+        if name.is_empty() {
+            continue;
+        }
+        let path = folder.join(sanitize(&name));
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+        files.push(path);
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure_storage::InMemorySecureStorage;
+    use serde_json::json;
+    use zip::write::{FileOptions, ZipWriter};
+
+    #[test]
+    fn test_parse_collection() {
+        let page = json!({
+            "items": [
+                {
+                    "tralbum_type": "a", "tralbum_id": 11, "item_title": "Tomorrow's Harvest", "band_name": "Boards of Canada",
+                    "item_url": "https://boc.bandcamp.com/album/th", "item_art_id": 42, "sale_item_type": "p", "sale_item_id": 7
+                },
+                {
+                    "tralbum_type": "t", "tralbum_id": 12, "item_title": "Single", "band_name": "Someone",
+                    "item_url": "https://someone.bandcamp.com/track/single", "sale_item_type": "r", "sale_item_id": 8
+                },
+                { "tralbum_type": "s", "tralbum_id": 13, "item_title": "Subscription" }
+            ],
+            "more_available": true,
+            "last_token": "1700000000:8:t::",
+            "redownload_urls": { "p7": "https://bandcamp.com/download?id=7" }
+        });
+        let page = parse_collection(&page).unwrap();
+        assert_eq!(page.next_token.as_deref(), Some("1700000000:8:t::"));
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.items[0].kind, PurchaseKind::Album);
+        assert_eq!(page.items[0].artwork_url.as_deref(), Some("https://f4.bcbits.com/img/a42_10.jpg"));
+        assert_eq!(page.items[0].download_page.as_deref(), Some("https://bandcamp.com/download?id=7"));
+        assert_eq!(page.items[1].download_page, None);
+
+        let last = parse_collection(&json!({ "items": [], "more_available": false, "last_token": "x" })).unwrap();
+        assert_eq!(last.next_token, None);
+    }
+
+    #[test]
+    fn test_page_data() {
+        let html = r#"<script data-tralbum="{&quot;artist&quot;:&quot;Boards of Canada&quot;,&quot;item_type&quot;:&quot;album&quot;,&quot;current&quot;:{&quot;title&quot;:&quot;Geogaddi &amp; more&quot;},&quot;trackinfo&quot;:[{&quot;title&quot;:&quot;Ready Lets Go&quot;,&quot;track_num&quot;:1,&quot;duration&quot;:59.5,&quot;file&quot;:{&quot;mp3-128&quot;:&quot;https://t4.bcbits.com/stream/1&quot;}},{&quot;title&quot;:&quot;Unreleased&quot;,&quot;file&quot;:null}]}" data-other="1"></script>"#;
+        let tralbum: Value = serde_json::from_str(&html_attribute(html, "data-tralbum").unwrap()).unwrap();
+        let tracks = parse_tralbum(&tralbum).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].album.as_deref(), Some("Geogaddi & more"));
+        assert_eq!((tracks[0].track_number, tracks[0].duration_sec), (Some(1), 59.5));
+        assert_eq!(html_attribute("<a title=\"R&#39;n&#x42; &nbsp\">", "title").as_deref(), Some("R'nB &nbsp"));
+
+        let blob = json!({ "digital_items": [{ "downloads": { "flac": { "url": "https://p4.bcbits.com/download/album?enc=flac" } } }] });
+        let downloads = parse_downloads(&blob);
+        assert_eq!(downloads.get(&DownloadFormat::Flac.key()).map(String::as_str), Some("https://p4.bcbits.com/download/album?enc=flac"));
+        assert!(!downloads.contains_key(&DownloadFormat::Mp3V0.key()));
+
+        assert_eq!(disposition_filename("attachment; filename=\"a.zip\"; filename*=UTF-8''Sigur%20R%C3%B3s.zip").as_deref(), Some("Sigur Rós.zip"));
+        assert_eq!(disposition_filename("attachment; filename=\"b.flac\"").as_deref(), Some("b.flac"));
+    }
+
+    #[test]
+    fn test_extract_album_flattens_entries() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let zip_path = dir.path().join("album.zip");
+        let mut zip = ZipWriter::new(File::create(&zip_path).unwrap());
+        for name in ["01 Intro.flac", "../../escape.flac", "art/cover.jpg"] {
+            zip.start_file::<_, ()>(name, FileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let folder = dir.path().join("Artist").join("Album");
+        fs::create_dir_all(&folder).unwrap();
+        let files = extract_album(&zip_path, &folder).unwrap();
+        let names: Vec<_> = files.iter().map(|f| f.strip_prefix(&folder).unwrap().to_string_lossy().to_string()).collect();
+        assert_eq!(names, ["01 Intro.flac", "escape.flac", "cover.jpg"]);
+    }
+
+    #[test]
+    fn test_identity_only_sent_to_bandcamp() {
+        let client = reqwest::Client::new();
+        let cookie = |url: &str| with_identity(client.get(url), "secret").unwrap().build().unwrap().headers().get(COOKIE).cloned();
+        assert!(cookie("https://bandcamp.com/api/fan/2/collection_summary").is_some());
+        assert!(cookie("https://artist.bandcamp.com/album/x").is_some());
+        assert!(cookie("https://p4.bcbits.com/download/album?enc=flac").is_some());
+        assert!(cookie("https://music.artist-domain.com/album/x").is_none());
+        assert!(cookie("https://evilbandcamp.com/album/x").is_none());
+        assert!(cookie("http://artist.bandcamp.com/album/x").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requires_sign_in() {
+        let client = BandcampClient::with_storage(InMemorySecureStorage::new());
+        assert!(matches!(client.list_purchases(None, 20).await, Err(BandcampError::NotSignedIn)));
+        client.storage.store(IDENTITY_KEY, "cookie").unwrap();
+        client.sign_out().unwrap();
+        assert!(matches!(client.identity(), Err(BandcampError::NotSignedIn)));
+    }
+}
@@ -0,0 +1,180 @@
+// Bookmarks and resume positions
+// Bookmarks are labelled positions in a track, kept per file path in the KV
+// store. Resume positions are saved on their own for long files (audiobooks,
+// DJ mixes, podcasts) as the player reports its position, and dropped once the
+// file has been played to the end.
+
+use crate::kv_store::{KvError, KvStore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Key prefix for per-file bookmark lists in the KV store
+pub const BOOKMARK_KEY_PREFIX: &str = "bookmarks.";
+
+/// Key prefix for per-file resume positions in the KV store
+pub const RESUME_KEY_PREFIX: &str = "resume.";
+
+/// A saved resume position is only rewritten once playback has moved this far
+const RESUME_SAVE_STEP_SECS: f64 = 10.0;
+
+/// Positions this close to the end count as finished
+const FINISHED_MARGIN_SECS: f64 = 30.0;
+
+#[derive(Error, Debug)]
+pub enum BookmarkError {
+    #[error("Invalid bookmark position: {0}")]
+    InvalidPosition(f64),
+    #[error(transparent)]
+    Store(#[from] KvError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Bookmark {
+    pub id: String,
+    pub position_secs: f64,
+    pub label: Option<String>,
+    /// Unix milliseconds
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ResumePosition {
+    pub position_secs: f64,
+    pub duration_secs: f64,
+    /// Unix milliseconds of the last save
+    pub updated_at: i64,
+}
+
+fn bookmark_key(file_path: &str) -> String {
+    format!("{}{}", BOOKMARK_KEY_PREFIX, file_path)
+}
+
+fn resume_key(file_path: &str) -> String {
+    format!("{}{}", RESUME_KEY_PREFIX, file_path)
+}
+
+/// Bookmarks of a track, in position order
+pub fn list_bookmarks(store: &KvStore, file_path: &str) -> Vec<Bookmark> {
+    store.get(&bookmark_key(file_path)).unwrap_or_default()
+}
+
+/// Bookmark `position_secs` in a track
+pub fn add_bookmark(
+    store: &KvStore,
+    file_path: &str,
+    position_secs: f64,
+    label: Option<String>,
+    created_at: DateTime<Utc>,
+) -> Result<Bookmark, BookmarkError> {
+    if !position_secs.is_finite() || position_secs < 0.0 {
+        return Err(BookmarkError::InvalidPosition(position_secs));
+    }
+    let bookmark = Bookmark {
+        id: uuid::Uuid::new_v4().to_string(),
+        position_secs,
+        label: label.filter(|label| !label.trim().is_empty()),
+        created_at: created_at.timestamp_millis(),
+    };
+    let mut bookmarks = list_bookmarks(store, file_path);
+    let index = bookmarks.partition_point(|b| b.position_secs <= position_secs);
+    bookmarks.insert(index, bookmark.clone());
+    store.set(&bookmark_key(file_path), &bookmarks)?;
+    Ok(bookmark)
+}
+
+/// Remove one bookmark; false if the track had no such bookmark
+pub fn remove_bookmark(store: &KvStore, file_path: &str, bookmark_id: &str) -> Result<bool, KvError> {
+    let mut bookmarks = list_bookmarks(store, file_path);
+    let before = bookmarks.len();
+    bookmarks.retain(|b| b.id != bookmark_id);
+    if bookmarks.len() == before {
+        return Ok(false);
+    }
+    if bookmarks.is_empty() {
+        store.remove(&bookmark_key(file_path))?;
+    } else {
+        store.set(&bookmark_key(file_path), &bookmarks)?;
+    }
+    Ok(true)
+}
+
+pub fn get_resume_position(store: &KvStore, file_path: &str) -> Option<ResumePosition> {
+    store.get(&resume_key(file_path))
+}
+
+pub fn clear_resume_position(store: &KvStore, file_path: &str) -> Result<bool, KvError> {
+    store.remove(&resume_key(file_path))
+}
+
+/// Remember where playback of a long file is
+///
+/// Files shorter than `min_duration_secs` are never saved. The stored position
+/// is only rewritten every `RESUME_SAVE_STEP_SECS`, since the player reports
+/// its position every second, and is cleared near the end of the file.
+pub fn record_position(
+    store: &KvStore,
+    file_path: &str,
+    position_secs: f64,
+    duration_secs: f64,
+    min_duration_secs: f64,
+    now: DateTime<Utc>,
+) -> Result<(), KvError> {
+    if !duration_secs.is_finite() || duration_secs < min_duration_secs || !position_secs.is_finite() {
+        return Ok(());
+    }
+    let saved = get_resume_position(store, file_path);
+    if position_secs >= duration_secs - FINISHED_MARGIN_SECS {
+        if saved.is_some() {
+            clear_resume_position(store, file_path)?;
+        }
+        return Ok(());
+    }
+    let last = saved.map_or(0.0, |saved| saved.position_secs);
+    if (position_secs - last).abs() < RESUME_SAVE_STEP_SECS {
+        return Ok(());
+    }
+    let position = ResumePosition { position_secs, duration_secs, updated_at: now.timestamp_millis() };
+    store.set(&resume_key(file_path), &position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks() {
+        let store = KvStore::in_memory();
+        let now = Utc::now();
+        let late = add_bookmark(&store, "/books/a.m4b", 3600.0, Some("Chapter 4".to_string()), now).unwrap();
+        let early = add_bookmark(&store, "/books/a.m4b", 90.5, Some(" ".to_string()), now).unwrap();
+        assert_eq!(early.label, None);
+        assert_eq!(list_bookmarks(&store, "/books/a.m4b"), vec![early.clone(), late.clone()]);
+        assert!(matches!(add_bookmark(&store, "/books/a.m4b", -1.0, None, now), Err(BookmarkError::InvalidPosition(_))));
+
+        assert!(remove_bookmark(&store, "/books/a.m4b", &early.id).unwrap());
+        assert!(!remove_bookmark(&store, "/books/a.m4b", &early.id).unwrap());
+        assert_eq!(list_bookmarks(&store, "/books/a.m4b"), vec![late]);
+        assert!(list_bookmarks(&store, "/books/b.m4b").is_empty());
+    }
+
+    #[test]
+    fn test_resume_positions() {
+        let store = KvStore::in_memory();
+        let now = Utc::now();
+        // Too short to resume
+        record_position(&store, "/music/song.mp3", 120.0, 240.0, 1200.0, now).unwrap();
+        assert_eq!(get_resume_position(&store, "/music/song.mp3"), None);
+
+        let mix = "/mixes/set.mp3";
+        record_position(&store, mix, 4.0, 3600.0, 1200.0, now).unwrap();
+        assert_eq!(get_resume_position(&store, mix), None);
+        record_position(&store, mix, 600.0, 3600.0, 1200.0, now).unwrap();
+        record_position(&store, mix, 605.0, 3600.0, 1200.0, now).unwrap();
+        assert_eq!(get_resume_position(&store, mix).unwrap().position_secs, 600.0);
+
+        // Finishing the file forgets the position
+        record_position(&store, mix, 3590.0, 3600.0, 1200.0, now).unwrap();
+        assert_eq!(get_resume_position(&store, mix), None);
+    }
+}
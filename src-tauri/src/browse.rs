@@ -0,0 +1,225 @@
+// Artist/album browse hierarchy
+// Groups the library index by artist and album so the frontend can show a
+// browse view without loading the flat track list.
+
+use crate::library::Track;
+use crate::metadata::{MetadataExtractor, TrackMetadata};
+use crate::search::SearchIndex;
+use crate::collation::Collator;
+use crate::sorting::compare_disc_track;
+use image::ImageFormat;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+use std::sync::Mutex;
+
+/// Longest edge of album thumbnails, in pixels
+pub const THUMBNAIL_SIZE: u32 = 96;
+
+pub const UNKNOWN_ARTIST: &str = "Unknown Artist";
+pub const UNKNOWN_ALBUM: &str = "Unknown Album";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ArtistSummary {
+    pub name: String,
+    pub album_count: usize,
+    pub track_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AlbumSummary {
+    /// Stable id derived from artist and album name
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    pub year: Option<u32>,
+    pub track_count: usize,
+    /// PNG thumbnail of the first embedded cover found in the album
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AlbumTrack {
+    pub track: Track,
+    pub metadata: Option<TrackMetadata>,
+}
+
+/// Thumbnails by album id, so artwork is decoded once per album
+pub struct ThumbnailCache(Mutex<BTreeMap<String, Option<Vec<u8>>>>);
+
+impl ThumbnailCache {
+    pub const fn new() -> Self {
+        Self(Mutex::new(BTreeMap::new()))
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap().clear();
+    }
+}
+
+struct Album<'a> {
+    title: &'a str,
+    artist: &'a str,
+    tracks: Vec<(&'a Track, Option<&'a TrackMetadata>)>,
+}
+
+fn artist_of(metadata: Option<&TrackMetadata>) -> &str {
+    metadata.and_then(|m| m.artist.as_deref()).unwrap_or(UNKNOWN_ARTIST)
+}
+
+fn album_of(metadata: Option<&TrackMetadata>) -> &str {
+    metadata.and_then(|m| m.album.as_deref()).unwrap_or(UNKNOWN_ALBUM)
+}
+
+/// Album id for an artist/album pair; case-insensitive so tag spelling variants merge
+///
+/// A SHA-256 prefix rather than `DefaultHasher`, whose output may change
+/// between Rust releases, so ids the frontend keeps stay valid.
+pub fn album_id(artist: &str, album: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(artist.to_lowercase())
+        .chain_update([0])
+        .chain_update(album.to_lowercase())
+        .finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("album_{}", hex)
+}
+
+fn group_albums(index: &SearchIndex) -> HashMap<String, Album<'_>> {
+    let mut albums: HashMap<String, Album> = HashMap::new();
+    for (track, metadata) in index.entries() {
+        let (artist, title) = (artist_of(metadata), album_of(metadata));
+        albums
+            .entry(album_id(artist, title))
+            .or_insert_with(|| Album { title, artist, tracks: Vec::new() })
+            .tracks
+            .push((track, metadata));
+    }
+    albums
+}
+
+/// Artists sorted by name
+pub fn artists(index: &SearchIndex) -> Vec<ArtistSummary> {
+    let mut by_artist: BTreeMap<String, ArtistSummary> = BTreeMap::new();
+    for album in group_albums(index).values() {
+        let summary = by_artist.entry(album.artist.to_lowercase()).or_insert_with(|| ArtistSummary {
+            name: album.artist.to_string(),
+            album_count: 0,
+            track_count: 0,
+        });
+        summary.album_count += 1;
+        summary.track_count += album.tracks.len();
+    }
+    let mut artists: Vec<ArtistSummary> = by_artist.into_values().collect();
+    let collator = Collator::shared(None, true);
+    artists.sort_by(|a, b| collator.compare(&a.name, &b.name));
+    artists
+}
+
+/// Albums sorted by artist, year and title; `artist` matches case-insensitively
+pub fn albums(
+    index: &SearchIndex,
+    artist: Option<&str>,
+    extractor: &MetadataExtractor,
+    thumbnails: &ThumbnailCache,
+) -> Vec<AlbumSummary> {
+    let mut albums: Vec<AlbumSummary> = group_albums(index)
+        .into_iter()
+        .filter(|(_, album)| artist.is_none_or(|artist| album.artist.eq_ignore_ascii_case(artist)))
+        .map(|(id, album)| {
+            let thumbnail = cached_thumbnail(&id, &album, extractor, thumbnails);
+            AlbumSummary {
+                year: album.tracks.iter().filter_map(|(_, m)| m.and_then(|m| m.year)).min(),
+                track_count: album.tracks.len(),
+                title: album.title.to_string(),
+                artist: album.artist.to_string(),
+                id,
+                thumbnail,
+            }
+        })
+        .collect();
+    let collator = Collator::shared(None, true);
+    albums.sort_by(|a, b| {
+        collator
+            .compare(&a.artist, &b.artist)
+            .then_with(|| a.year.cmp(&b.year))
+            .then_with(|| collator.compare(&a.title, &b.title))
+    });
+    albums
+}
+
+/// Tracks of an album in disc/track order, or `None` for an unknown id
+pub fn album_tracks(index: &SearchIndex, album_id: &str) -> Option<Vec<AlbumTrack>> {
+    let mut albums = group_albums(index);
+    let album = albums.remove(album_id)?;
+    let mut tracks: Vec<AlbumTrack> = album
+        .tracks
+        .into_iter()
+        .map(|(track, metadata)| AlbumTrack { track: track.clone(), metadata: metadata.cloned() })
+        .collect();
+    let collator = Collator::shared(None, true);
+    tracks.sort_by(|a, b| {
+        let key = |t: &AlbumTrack| (t.metadata.as_ref().and_then(|m| m.disc_number), t.metadata.as_ref().and_then(|m| m.track_number));
+        compare_disc_track(key(a), key(b)).then_with(|| collator.compare(&a.track.file_name, &b.track.file_name))
+    });
+    Some(tracks)
+}
+
+fn cached_thumbnail(id: &str, album: &Album, extractor: &MetadataExtractor, thumbnails: &ThumbnailCache) -> Option<Vec<u8>> {
+    if let Some(cached) = thumbnails.0.lock().unwrap().get(id) {
+        return cached.clone();
+    }
+    let thumbnail = album
+        .tracks
+        .iter()
+        .find_map(|(track, _)| extractor.extract_artwork(&track.file_path).ok().flatten())
+        .and_then(|artwork| thumbnail_png(&artwork));
+    thumbnails.0.lock().unwrap().insert(id.to_string(), thumbnail.clone());
+    thumbnail
+}
+
+/// Downscale cover art and re-encode it as PNG
+fn thumbnail_png(artwork: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(artwork).ok()?;
+    let mut png = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .ok()?;
+    Some(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::test_index as index;
+
+    #[test]
+    fn test_artists_and_albums() {
+        let (_dir, index) = index(&["Boards - Roygbiv.wav", "boards - Olson.wav", "Air - Sexy Boy.wav"]);
+
+        let artists = artists(&index);
+        assert_eq!(artists.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), vec!["Air", "Boards"]);
+        assert_eq!(artists[1].track_count, 2);
+
+        let albums = albums(&index, Some("BOARDS"), &MetadataExtractor::new(), &ThumbnailCache::new());
+        assert_eq!(albums.len(), 1);
+        assert_eq!(albums[0].track_count, 2);
+        assert!(albums[0].thumbnail.is_none());
+
+        let tracks = album_tracks(&index, &albums[0].id).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert!(album_tracks(&index, "album_missing").is_none());
+    }
+
+    #[test]
+    fn test_thumbnail_is_downscaled() {
+        let cover = image::RgbImage::from_pixel(400, 200, image::Rgb([200, 10, 10]));
+        let mut bytes = Vec::new();
+        cover.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+
+        let thumbnail = image::load_from_memory(&thumbnail_png(&bytes).unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2));
+    }
+}
@@ -0,0 +1,196 @@
+// Chapter markers for audiobooks and long recordings
+// MP3 chapters come from ID3 CHAP frames, in the order of the top-level CTOC
+// frame when there is one. MP4 audio (M4B/M4A) keeps chapters in a QuickTime
+// chapter track or a Nero `chpl` atom, which FFprobe reads for us.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ChapterError {
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Failed to read chapters: {0}")]
+    Read(String),
+    #[error("Chapter {index} out of range; the track has {count} chapters")]
+    OutOfRange { index: usize, count: usize },
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Chapter {
+    pub index: usize,
+    /// The chapter's title, or "Chapter N" when it has none
+    pub title: String,
+    pub start_secs: f64,
+    pub end_secs: f64,
+}
+
+/// Chapters of the file at `path`, in playback order; empty for formats without chapters
+pub fn read_chapters(path: &Path) -> Result<Vec<Chapter>, ChapterError> {
+    if !path.exists() {
+        return Err(ChapterError::FileNotFound(path.to_string_lossy().to_string()));
+    }
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let markers = match extension.as_str() {
+        "mp3" => read_id3_chapters(path)?,
+        "m4b" | "m4a" | "mp4" => read_mp4_chapters(path)?,
+        _ => Vec::new(),
+    };
+    Ok(number(markers))
+}
+
+/// The chapter at `index`
+pub fn chapter_at(chapters: &[Chapter], index: usize) -> Result<&Chapter, ChapterError> {
+    chapters.get(index).ok_or(ChapterError::OutOfRange { index, count: chapters.len() })
+}
+
+/// (title, start, end) of each chapter in the tag
+fn read_id3_chapters(path: &Path) -> Result<Vec<(Option<String>, f64, f64)>, ChapterError> {
+    let tag = match id3::Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => return Ok(Vec::new()),
+        Err(e) => return Err(ChapterError::Read(e.to_string())),
+    };
+    let mut chapters: Vec<&id3::frame::Chapter> = tag.chapters().collect();
+    let order = tag.tables_of_contents().find(|toc| toc.top_level && toc.ordered).map(|toc| &toc.elements);
+    match order {
+        Some(order) => chapters.sort_by_key(|c| order.iter().position(|id| *id == c.element_id).unwrap_or(usize::MAX)),
+        None => chapters.sort_by_key(|c| c.start_time),
+    }
+    Ok(chapters
+        .into_iter()
+        .map(|chapter| {
+            let title = chapter
+                .frames
+                .iter()
+                .find(|frame| frame.id() == "TIT2")
+                .and_then(|frame| frame.content().text())
+                .map(str::to_string);
+            (title, chapter.start_time as f64 / 1000.0, chapter.end_time as f64 / 1000.0)
+        })
+        .collect())
+}
+
+fn read_mp4_chapters(path: &Path) -> Result<Vec<(Option<String>, f64, f64)>, ChapterError> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_chapters", "-of", "json"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| ChapterError::Read(format!("failed to execute FFprobe: {}", e)))?;
+    if !output.status.success() {
+        return Err(ChapterError::Read(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    let json: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| ChapterError::Read(format!("failed to parse FFprobe output: {}", e)))?;
+    Ok(parse_ffprobe_chapters(&json))
+}
+
+fn parse_ffprobe_chapters(json: &Value) -> Vec<(Option<String>, f64, f64)> {
+    // FFprobe reports times as strings
+    let seconds = |value: &Value| value.as_str().and_then(|s| s.parse::<f64>().ok()).unwrap_or(0.0);
+    json["chapters"]
+        .as_array()
+        .map(|chapters| {
+            chapters
+                .iter()
+                .map(|chapter| {
+                    let title = chapter["tags"]["title"].as_str().map(str::to_string);
+                    (title, seconds(&chapter["start_time"]), seconds(&chapter["end_time"]))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Index and name the markers; a missing end runs to the next chapter
+fn number(markers: Vec<(Option<String>, f64, f64)>) -> Vec<Chapter> {
+    let starts: Vec<f64> = markers.iter().map(|(_, start, _)| *start).collect();
+    markers
+        .into_iter()
+        .enumerate()
+        .map(|(index, (title, start_secs, end_secs))| {
+            let end_secs = if end_secs > start_secs {
+                end_secs
+            } else {
+                starts.get(index + 1).copied().filter(|next| *next > start_secs).unwrap_or(start_secs)
+            };
+            Chapter {
+                index,
+                title: title.filter(|t| !t.trim().is_empty()).unwrap_or_else(|| format!("Chapter {}", index + 1)),
+                start_secs,
+                end_secs,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::frame::{Chapter as Id3Chapter, Content, Frame, TableOfContents};
+    use id3::TagLike;
+
+    fn chap(id: &str, start_ms: u32, end_ms: u32, title: Option<&str>) -> Frame {
+        Frame::with_content(
+            "CHAP",
+            Content::Chapter(Id3Chapter {
+                element_id: id.to_string(),
+                start_time: start_ms,
+                end_time: end_ms,
+                start_offset: u32::MAX,
+                end_offset: u32::MAX,
+                frames: title.map(|t| vec![Frame::text("TIT2", t)]).unwrap_or_default(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_id3_chapters() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.mp3");
+        std::fs::write(&path, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.add_frame(chap("intro", 0, 65_000, Some("Introduction")));
+        tag.add_frame(chap("ch2", 65_000, 300_500, None));
+        tag.add_frame(Frame::with_content(
+            "CTOC",
+            Content::TableOfContents(TableOfContents {
+                element_id: "toc".to_string(),
+                top_level: true,
+                ordered: true,
+                elements: vec!["intro".to_string(), "ch2".to_string()],
+                frames: Vec::new(),
+            }),
+        ));
+        tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+        let chapters = read_chapters(&path).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title, "Introduction");
+        assert_eq!(chapters[1], Chapter { index: 1, title: "Chapter 2".to_string(), start_secs: 65.0, end_secs: 300.5 });
+        assert!(matches!(chapter_at(&chapters, 2), Err(ChapterError::OutOfRange { index: 2, count: 2 })));
+
+        let untagged = dir.path().join("plain.mp3");
+        std::fs::write(&untagged, b"").unwrap();
+        assert!(read_chapters(&untagged).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_ffprobe_chapters() {
+        let json = serde_json::json!({
+            "chapters": [
+                {"id": 0, "start_time": "0.000000", "end_time": "0.000000", "tags": {"title": "Opening Credits"}},
+                {"id": 1, "start_time": "12.500000", "end_time": "1800.000000", "tags": {}}
+            ]
+        });
+        let chapters = number(parse_ffprobe_chapters(&json));
+        assert_eq!(chapters[0].end_secs, 12.5);
+        assert_eq!(chapters[0].title, "Opening Credits");
+        assert_eq!(chapters[1].title, "Chapter 2");
+        assert!(parse_ffprobe_chapters(&serde_json::json!({})).is_empty());
+    }
+}
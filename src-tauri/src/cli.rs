@@ -0,0 +1,155 @@
+// Command-line and file-association launch handling
+// Turns `milkline song.mp3`, `milkline --enqueue folder/` and playlist or skin
+// files into a request for the frontend. Used for the first launch and for
+// arguments a second instance forwards to the running one.
+
+use crate::file_drop::{classify, DroppedKind};
+use crate::metadata::MetadataExtractor;
+use crate::playlist::Track;
+use crate::queue_import::{expand_folder, read_playlist_file, track_for_file, EnqueueMode};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// What to do with the tracks from the command line
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LaunchMode {
+    /// Replace the queue and start playing (double-clicking a file)
+    Play,
+    /// Append to the queue without interrupting playback
+    Enqueue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LaunchArgs {
+    pub mode: LaunchMode,
+    pub paths: Vec<PathBuf>,
+    /// Run as a daemon without a window
+    pub headless: bool,
+    /// Print the remote API token on startup (headless only)
+    pub print_token: bool,
+}
+
+/// Parse arguments (without the program name), resolving paths against `cwd`
+///
+/// Files are handled as `default_mode` says: playing them on first launch,
+/// enqueueing when forwarded to a running instance so its queue is kept.
+/// `--enqueue`/`-e` and Winamp's `/ADD` switch to enqueueing; `--play`/`-p`
+/// to playing; `--headless` starts the daemon and `--print-token` has it
+/// print the remote API token. Unknown flags are ignored since the OS may add
+/// its own.
+pub fn parse_args(args: &[String], cwd: &Path, default_mode: LaunchMode) -> LaunchArgs {
+    let mut mode = default_mode;
+    let mut paths = Vec::new();
+    let mut headless = false;
+    let mut print_token = false;
+    for arg in args {
+        match arg.as_str() {
+            "--headless" => headless = true,
+            "--print-token" => print_token = true,
+            "--enqueue" | "-e" => mode = LaunchMode::Enqueue,
+            "--play" | "-p" => mode = LaunchMode::Play,
+            _ if arg.eq_ignore_ascii_case("/add") => mode = LaunchMode::Enqueue,
+            _ if arg.starts_with('-') => {}
+            _ => paths.push(cwd.join(arg)),
+        }
+    }
+    LaunchArgs { mode, paths, headless, print_token }
+}
+
+/// Payload of the `open-files` event
+#[derive(Debug, Clone, Serialize)]
+pub struct LaunchRequest {
+    pub mode: LaunchMode,
+    /// Tracks in the order given, folders expanded recursively
+    pub tracks: Vec<Track>,
+    /// Skin files to load
+    pub skins: Vec<String>,
+    /// Arguments that were not a supported file, folder or playlist
+    pub skipped: Vec<String>,
+}
+
+/// Expand parsed arguments into tracks and skins
+pub fn resolve(args: &LaunchArgs, extractor: &MetadataExtractor) -> LaunchRequest {
+    let mut request = LaunchRequest {
+        mode: args.mode,
+        tracks: Vec::new(),
+        skins: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for path in &args.paths {
+        let display = path.to_string_lossy().to_string();
+        match classify(path) {
+            DroppedKind::Skin => request.skins.push(display),
+            DroppedKind::Folder => match expand_folder(path, EnqueueMode::Recursive, extractor) {
+                Ok(summary) => request.tracks.extend(summary.tracks),
+                Err(_) => request.skipped.push(display),
+            },
+            DroppedKind::Playlist => match read_playlist_file(path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        if classify(&entry) == DroppedKind::Audio {
+                            request.tracks.push(track_for_file(&entry, extractor));
+                        } else {
+                            request.skipped.push(entry.to_string_lossy().to_string());
+                        }
+                    }
+                }
+                Err(_) => request.skipped.push(display),
+            },
+            DroppedKind::Audio => request.tracks.push(track_for_file(path, extractor)),
+            DroppedKind::Unsupported => request.skipped.push(display),
+        }
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let cwd = Path::new("/home/user");
+        let parsed = parse_args(&args(&["song.mp3"]), cwd, LaunchMode::Play);
+        assert_eq!(parsed, LaunchArgs { mode: LaunchMode::Play, paths: vec![cwd.join("song.mp3")], headless: false, print_token: false });
+
+        let parsed = parse_args(&args(&["--enqueue", "folder/", "/abs/b.flac", "-psn_0_123"]), cwd, LaunchMode::Play);
+        assert_eq!(parsed.mode, LaunchMode::Enqueue);
+        assert_eq!(parsed.paths, vec![cwd.join("folder/"), PathBuf::from("/abs/b.flac")]);
+        assert_eq!(parse_args(&args(&["/ADD", "a.mp3"]), cwd, LaunchMode::Play).mode, LaunchMode::Enqueue);
+        assert!(parse_args(&args(&["--headless", "music/"]), cwd, LaunchMode::Play).headless);
+        assert!(parse_args(&args(&["--headless", "--print-token"]), cwd, LaunchMode::Play).print_token);
+
+        // Forwarded arguments enqueue unless asked to play
+        assert_eq!(parse_args(&args(&["song.mp3"]), cwd, LaunchMode::Enqueue).mode, LaunchMode::Enqueue);
+        assert_eq!(parse_args(&args(&["-p", "song.mp3"]), cwd, LaunchMode::Enqueue).mode, LaunchMode::Play);
+    }
+
+    #[test]
+    fn test_resolve_mixed_arguments() {
+        let dir = TempDir::new().unwrap();
+        let album = dir.path().join("album");
+        fs::create_dir_all(&album).unwrap();
+        for name in ["2 - two.wav", "1 - one.wav"] {
+            fs::write(album.join(name), b"").unwrap();
+        }
+        fs::write(dir.path().join("single.wav"), b"").unwrap();
+        fs::write(dir.path().join("list.m3u"), "single.wav\nmissing.wav\n").unwrap();
+        fs::write(dir.path().join("classic.wsz"), b"").unwrap();
+
+        let parsed = parse_args(&args(&["album", "list.m3u", "classic.wsz", "notes.txt"]), dir.path(), LaunchMode::Play);
+        let request = resolve(&parsed, &MetadataExtractor::new());
+
+        let titles: Vec<_> = request.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["one", "two", "single"]);
+        assert_eq!(request.skins.len(), 1);
+        assert_eq!(request.skipped.len(), 2);
+    }
+}
@@ -0,0 +1,673 @@
+// Cloud backup
+// Keeps playlists, ratings and settings in a single JSON snapshot on a WebDAV
+// server or an S3-compatible bucket, so several machines can share them. Each
+// backup is a three-way merge between this machine, the remote snapshot and
+// the snapshot this machine last synced: changes made on only one side win,
+// and when both sides changed the same playlist the newer edit is kept.
+// Credentials never leave the machine: the WebDAV password or S3 secret key
+// lives in secure storage, and the backup and proxy settings are not uploaded.
+
+use crate::api_client::{ApiClient, RateBudget};
+use crate::config::{self, parse_config, BackupProvider, BackupSettings, ConfigError, ConfigManager, FileConfigManager};
+use crate::connectivity;
+use crate::kv_store::{KvError, KvStore};
+use crate::playlist::{Playlist, PlaylistError, PlaylistManager};
+use crate::ratings::{self, RatingError, TrackRating, RATING_KEY_PREFIX};
+use crate::spotify::ApiError;
+use chrono::{DateTime, Utc};
+use reqwest::{Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+/// Bumped when the snapshot layout changes incompatibly
+pub const SNAPSHOT_FORMAT: u32 = 1;
+
+/// File name of the snapshot in the backup folder
+pub const SNAPSHOT_NAME: &str = "milk-backup.json";
+
+/// Secure storage key of the WebDAV password or S3 secret key
+pub const BACKUP_SECRET_KEY: &str = "backup_secret";
+
+/// KV store key of the snapshot last synced, the base of the next merge
+const BASE_KEY: &str = "backup.base";
+
+/// Config sections that stay on this machine: its own paths, window layout,
+/// audio output and network exposure, plus the backup settings themselves
+const LOCAL_SETTINGS: &[&str] = &[
+    "backup",
+    "proxy",
+    "revision",
+    "library_path",
+    "window_position",
+    "window_size",
+    "windows",
+    "output",
+    "remote_api",
+];
+
+/// Backups are small and infrequent
+const BACKUP_BUDGET: RateBudget = RateBudget::new(4, 1.0);
+
+/// How often the scheduler checks whether an automatic backup is due
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+// One backup at a time, so a manual backup can't race the scheduled one
+static BACKUP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Error, Debug)]
+pub enum BackupError {
+    #[error("Cloud backup is not configured")]
+    NotConfigured,
+    #[error("Backup server rejected the credentials ({0})")]
+    Unauthorized(u16),
+    #[error("Backup server returned {0}")]
+    Status(u16),
+    #[error("Invalid backup snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Backup snapshot format {0} is newer than this version of milk supports")]
+    UnsupportedFormat(u32),
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    #[error("Backup failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Playlist(#[from] PlaylistError),
+    #[error(transparent)]
+    Store(#[from] KvError),
+    #[error(transparent)]
+    Rating(#[from] RatingError),
+}
+
+/// Everything that is backed up
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub format: u32,
+    pub app_version: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    /// Settings without the sections in `LOCAL_SETTINGS`
+    pub config: Option<Value>,
+    /// Saved playlists by id, as JSON so fields this build doesn't know survive
+    pub playlists: BTreeMap<String, Value>,
+    /// By file path
+    pub ratings: BTreeMap<String, TrackRating>,
+}
+
+impl Snapshot {
+    fn parse(data: &[u8]) -> Result<Self, BackupError> {
+        let snapshot: Snapshot = serde_json::from_slice(data).map_err(|e| BackupError::InvalidSnapshot(e.to_string()))?;
+        if snapshot.format > SNAPSHOT_FORMAT {
+            return Err(BackupError::UnsupportedFormat(snapshot.format));
+        }
+        for (id, playlist) in &snapshot.playlists {
+            if !crate::data_bundle::is_safe_id(id) || serde_json::from_value::<Playlist>(playlist.clone()).is_err() {
+                return Err(BackupError::InvalidSnapshot(format!("invalid playlist '{}'", id)));
+            }
+        }
+        Ok(snapshot)
+    }
+
+    fn same_data(&self, other: &Snapshot) -> bool {
+        self.config == other.config && self.playlists == other.playlists && self.ratings == other.ratings
+    }
+}
+
+/// What a backup did
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub provider: BackupProvider,
+    /// Whether a new snapshot was written to the server
+    pub uploaded: bool,
+    /// Playlists added, changed or removed here from another machine's backup
+    pub playlists_pulled: usize,
+    pub ratings_pulled: usize,
+    pub config_pulled: bool,
+    /// Items changed on both sides since the last backup
+    pub conflicts: usize,
+    pub playlists: usize,
+    pub ratings: usize,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub completed_at: DateTime<Utc>,
+}
+
+/// Where the snapshot is stored
+pub enum Remote {
+    WebDav {
+        folder: Url,
+        username: Option<String>,
+        password: Option<String>,
+    },
+    S3 {
+        endpoint: Url,
+        bucket: String,
+        folder: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+impl Remote {
+    /// `secret` is the WebDAV password or S3 secret key from secure storage
+    pub fn from_settings(settings: &BackupSettings, secret: Option<String>) -> Result<Self, BackupError> {
+        let endpoint = |url: &str| {
+            Url::parse(url.trim()).map_err(|e| BackupError::Config(ConfigError::InvalidSetting {
+                key: "backup.endpoint".to_string(),
+                reason: e.to_string(),
+            }))
+        };
+        let folder = settings.folder.trim_matches('/').to_string();
+        match settings.provider {
+            BackupProvider::None => Err(BackupError::NotConfigured),
+            BackupProvider::WebDav => {
+                let mut url = endpoint(&settings.endpoint)?;
+                let path = format!("{}/{}", url.path().trim_end_matches('/'), folder);
+                url.set_path(&format!("{}/", path.trim_end_matches('/')));
+                Ok(Remote::WebDav {
+                    folder: url,
+                    username: settings.username.clone().filter(|u| !u.is_empty()),
+                    password: secret,
+                })
+            }
+            BackupProvider::S3 => Ok(Remote::S3 {
+                endpoint: endpoint(&settings.endpoint)?,
+                bucket: settings.bucket.trim().to_string(),
+                folder,
+                region: settings.region.trim().to_string(),
+                access_key: settings.username.clone().filter(|u| !u.is_empty()).ok_or(BackupError::NotConfigured)?,
+                secret_key: secret.ok_or(BackupError::NotConfigured)?,
+            }),
+        }
+    }
+
+    /// Path-style for S3, which every S3-compatible store accepts
+    fn snapshot_url(&self) -> Url {
+        let (mut url, parts) = match self {
+            Remote::WebDav { folder, .. } => (folder.clone(), vec![folder.path()]),
+            Remote::S3 { endpoint, bucket, folder, .. } => (endpoint.clone(), vec![endpoint.path(), bucket, folder]),
+        };
+        let path = parts
+            .into_iter()
+            .map(|part| part.trim_matches('/'))
+            .filter(|part| !part.is_empty())
+            .chain([SNAPSHOT_NAME])
+            .collect::<Vec<_>>()
+            .join("/");
+        url.set_path(&path);
+        url
+    }
+
+    fn request(&self, method: Method, url: Url, body: Vec<u8>) -> reqwest::RequestBuilder {
+        let client = crate::api_client::http_client();
+        match self {
+            Remote::WebDav { username, password, .. } => {
+                let request = client.request(method, url).body(body);
+                match username {
+                    Some(username) => request.basic_auth(username, password.as_deref()),
+                    None => request,
+                }
+            }
+            Remote::S3 { region, access_key, secret_key, .. } => {
+                let headers = sign_s3(method.as_str(), &url, region, access_key, secret_key, &body, Utc::now());
+                headers
+                    .into_iter()
+                    .fold(client.request(method, url).body(body), |request, (name, value)| request.header(name, value))
+            }
+        }
+    }
+
+    /// The stored snapshot, or `None` before the first backup
+    pub async fn fetch(&self, client: &ApiClient) -> Result<Option<Vec<u8>>, BackupError> {
+        let response = client.send(self.request(Method::GET, self.snapshot_url(), Vec::new())).await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => {
+                let body = response.bytes().await.map_err(|e| ApiError::NetworkError(e.to_string()))?;
+                Ok(Some(body.to_vec()))
+            }
+            status => Err(status_error(status)),
+        }
+    }
+
+    /// Replace the stored snapshot, creating the WebDAV folder on first use
+    pub async fn upload(&self, client: &ApiClient, data: Vec<u8>) -> Result<(), BackupError> {
+        let url = self.snapshot_url();
+        let mut status = client.send(self.request(Method::PUT, url.clone(), data.clone())).await?.status();
+        if let Remote::WebDav { folder, .. } = self {
+            // Servers answer 409 when the parent collection is missing
+            if status == StatusCode::CONFLICT || status == StatusCode::NOT_FOUND {
+                let mkcol = Method::from_bytes(b"MKCOL").expect("MKCOL is a valid method");
+                client.send(self.request(mkcol, folder.clone(), Vec::new())).await?;
+                status = client.send(self.request(Method::PUT, url, data)).await?.status();
+            }
+        }
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(status_error(status))
+        }
+    }
+}
+
+fn status_error(status: StatusCode) -> BackupError {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => BackupError::Unauthorized(status.as_u16()),
+        status => BackupError::Status(status.as_u16()),
+    }
+}
+
+/// Headers signing an S3 request with AWS Signature Version 4
+fn sign_s3(
+    method: &str,
+    url: &Url,
+    region: &str,
+    access_key: &str,
+    secret_key: &str,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> Vec<(&'static str, String)> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = &amz_date[..8];
+    let payload_hash = hex(&Sha256::digest(body));
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        method,
+        url.path(),
+        url.query().unwrap_or_default(),
+        host,
+        payload_hash,
+        amz_date,
+        signed_headers,
+        payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+    let key = [date, region, "s3", "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", secret_key).into_bytes(), |key, part| hmac_sha256(&key, part.as_bytes()).to_vec());
+    let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+    vec![
+        ("x-amz-date", amz_date.clone()),
+        ("x-amz-content-sha256", payload_hash),
+        (
+            "authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key, scope, signed_headers, signature
+            ),
+        ),
+    ]
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| block.iter().map(move |b| b ^ byte).collect::<Vec<u8>>();
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether an automatic backup is due
+pub fn is_due(settings: &BackupSettings, last: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    settings.provider != BackupProvider::None
+        && settings.interval_hours > 0
+        && last.is_none_or(|last| now - last >= chrono::Duration::hours(settings.interval_hours as i64))
+}
+
+/// When this machine last completed a backup
+pub fn last_backup(store: &KvStore) -> Option<DateTime<Utc>> {
+    store.get::<Snapshot>(BASE_KEY).map(|base| base.created_at)
+}
+
+/// Run `backup` whenever the configured interval has passed since the last backup
+///
+/// Settings are re-read on every check, so changes apply without a restart.
+/// A backup that falls due while offline waits in the connectivity queue and
+/// runs once the connection is back.
+pub fn start<F, Fut>(store: &'static KvStore, backup: F)
+where
+    F: Fn() -> Fut + Clone + Send + 'static,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let due = FileConfigManager::load()
+                .map(|config| is_due(&config.backup, last_backup(store), Utc::now()))
+                .unwrap_or(false);
+            if due && connectivity::is_online() {
+                backup().await;
+            } else if due {
+                connectivity::run_when_online("cloud-backup", backup.clone());
+            }
+            tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Snapshot of this machine's playlists, ratings and settings
+async fn local_snapshot(manager: &PlaylistManager, store: &KvStore, config_path: &Path) -> Result<Snapshot, BackupError> {
+    let config = match fs::read_to_string(config_path) {
+        Ok(contents) => Some(shareable_config(serde_json::to_value(parse_config(&contents)?).map_err(ConfigError::from)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut playlists = BTreeMap::new();
+    for playlist in manager.list_playlists().await?.into_iter().filter(|p| !p.system) {
+        playlists.insert(playlist.id.clone(), serde_json::to_value(&playlist).map_err(PlaylistError::from)?);
+    }
+    Ok(Snapshot {
+        format: SNAPSHOT_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        config,
+        playlists,
+        ratings: store.entries_with_prefix(RATING_KEY_PREFIX).into_iter().collect(),
+    })
+}
+
+fn shareable_config(mut config: Value) -> Value {
+    if let Some(settings) = config.as_object_mut() {
+        for key in LOCAL_SETTINGS {
+            settings.remove(*key);
+        }
+    }
+    config
+}
+
+/// Top-level settings of a snapshot's config that are shared between machines
+///
+/// Snapshots from older builds may still carry sections that are now local.
+fn shared_settings(config: Option<&Value>) -> BTreeMap<String, Value> {
+    config
+        .and_then(Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !LOCAL_SETTINGS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// Three-way merge of the shared settings, one top-level section at a time,
+/// so a volume change here and a skin change elsewhere both survive
+fn merge_settings(base: Option<&Value>, local: Option<&Value>, remote: Option<&Value>) -> Merged<Value> {
+    // Settings carry no timestamps, so a section changed on both sides keeps this machine's
+    merge_maps(&shared_settings(base), &shared_settings(local), &shared_settings(remote), |_, _| false)
+}
+
+/// The merged maps, and how many entries came from the remote side or conflicted
+#[derive(Debug, PartialEq)]
+struct Merged<T> {
+    entries: BTreeMap<String, T>,
+    pulled: BTreeSet<String>,
+    conflicts: usize,
+}
+
+/// Three-way merge of `local` and `remote` against `base`
+///
+/// An entry changed on one side only takes that side, including removals.
+/// When both changed it differently, `remote_wins` decides; an entry edited
+/// on one side and removed on the other is kept.
+fn merge_maps<T: Clone + PartialEq>(
+    base: &BTreeMap<String, T>,
+    local: &BTreeMap<String, T>,
+    remote: &BTreeMap<String, T>,
+    remote_wins: impl Fn(&T, &T) -> bool,
+) -> Merged<T> {
+    let mut merged = Merged { entries: BTreeMap::new(), pulled: BTreeSet::new(), conflicts: 0 };
+    let keys: BTreeSet<&String> = base.keys().chain(local.keys()).chain(remote.keys()).collect();
+    for key in keys {
+        let (base, local, remote) = (base.get(key), local.get(key), remote.get(key));
+        let take_remote = if local == remote || remote == base {
+            false
+        } else if local == base {
+            true
+        } else {
+            merged.conflicts += 1;
+            match (local, remote) {
+                (Some(local), Some(remote)) => remote_wins(local, remote),
+                (local, _) => local.is_none(),
+            }
+        };
+        let chosen = if take_remote {
+            merged.pulled.insert(key.clone());
+            remote
+        } else {
+            local
+        };
+        if let Some(value) = chosen {
+            merged.entries.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+fn modified_at(playlist: &Value) -> i64 {
+    playlist.get("modified_at").and_then(Value::as_i64).unwrap_or(0)
+}
+
+/// Merge this machine with the stored snapshot, apply what came from other
+/// machines, and upload the result if it differs from what is stored
+pub async fn backup_now(
+    remote: &Remote,
+    provider: BackupProvider,
+    manager: &PlaylistManager,
+    store: &KvStore,
+    config_path: &Path,
+) -> Result<BackupReport, BackupError> {
+    let _running = BACKUP_LOCK.lock().await;
+    let client = ApiClient::new("backup", BACKUP_BUDGET);
+    let local = local_snapshot(manager, store, config_path).await?;
+    let stored = remote.fetch(&client).await?.map(|data| Snapshot::parse(&data)).transpose()?;
+    let base = store.get::<Snapshot>(BASE_KEY);
+    let empty = BTreeMap::new();
+
+    let (merged, mut report) = match &stored {
+        None => (local.clone(), BackupReport::new(provider)),
+        Some(stored) => {
+            let base_playlists = base.as_ref().map_or(&empty, |base| &base.playlists);
+            let playlists = merge_maps(base_playlists, &local.playlists, &stored.playlists, |local, remote| {
+                modified_at(remote) > modified_at(local)
+            });
+            let empty_ratings = BTreeMap::new();
+            let base_ratings = base.as_ref().map_or(&empty_ratings, |base| &base.ratings);
+            // Ratings carry no timestamps, so a rating changed on both sides keeps this machine's
+            let ratings = merge_maps(base_ratings, &local.ratings, &stored.ratings, |_, _| false);
+            let config = merge_settings(base.as_ref().and_then(|b| b.config.as_ref()), local.config.as_ref(), stored.config.as_ref());
+
+            apply_playlists(manager, &playlists).await?;
+            for path in &ratings.pulled {
+                ratings::set_rating(store, path, ratings.entries.get(path).copied().unwrap_or_default())?;
+            }
+            let config_pulled = !config.pulled.is_empty();
+            let shared_config = (!config.entries.is_empty()).then(|| Value::Object(config.entries.into_iter().collect()));
+            if let Some(shared) = shared_config.as_ref().filter(|_| config_pulled) {
+                apply_config(config_path, shared)?;
+            }
+
+            let report = BackupReport {
+                playlists_pulled: playlists.pulled.len(),
+                ratings_pulled: ratings.pulled.len(),
+                config_pulled,
+                conflicts: playlists.conflicts + ratings.conflicts + config.conflicts,
+                ..BackupReport::new(provider)
+            };
+            let merged = Snapshot {
+                playlists: playlists.entries,
+                ratings: ratings.entries,
+                config: shared_config,
+                ..local.clone()
+            };
+            (merged, report)
+        }
+    };
+
+    if stored.as_ref().is_none_or(|stored| !stored.same_data(&merged)) {
+        let data = serde_json::to_vec_pretty(&merged).map_err(|e| BackupError::InvalidSnapshot(e.to_string()))?;
+        remote.upload(&client, data).await?;
+        report.uploaded = true;
+    }
+    store.set(BASE_KEY, &merged)?;
+    report.playlists = merged.playlists.len();
+    report.ratings = merged.ratings.len();
+    Ok(report)
+}
+
+impl BackupReport {
+    fn new(provider: BackupProvider) -> Self {
+        Self {
+            provider,
+            uploaded: false,
+            playlists_pulled: 0,
+            ratings_pulled: 0,
+            config_pulled: false,
+            conflicts: 0,
+            playlists: 0,
+            ratings: 0,
+            completed_at: Utc::now(),
+        }
+    }
+}
+
+async fn apply_playlists(manager: &PlaylistManager, merged: &Merged<Value>) -> Result<(), BackupError> {
+    for id in &merged.pulled {
+        match merged.entries.get(id) {
+            Some(playlist) => {
+                let playlist: Playlist = serde_json::from_value(playlist.clone()).map_err(PlaylistError::from)?;
+                manager.save_playlist(&playlist).await?;
+            }
+            None => match manager.delete_playlist(id).await {
+                Ok(()) | Err(PlaylistError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Overlay the shared settings from `shared` on this machine's config,
+/// leaving its `LOCAL_SETTINGS` and any section `shared` lacks untouched
+fn apply_config(config_path: &Path, shared: &Value) -> Result<(), BackupError> {
+    let current = fs::read_to_string(config_path).ok().and_then(|contents| parse_config(&contents).ok());
+    let mut updated = serde_json::to_value(current.clone().unwrap_or_else(FileConfigManager::get_default)).map_err(ConfigError::from)?;
+    if let Some(updated) = updated.as_object_mut() {
+        for (key, value) in shared_settings(Some(shared)) {
+            updated.insert(key, value);
+        }
+    }
+    let updated = parse_config(&updated.to_string())?;
+    config::save_with_revision(config_path, current.as_ref(), &updated)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map<T: Clone>(entries: &[(&str, T)]) -> BTreeMap<String, T> {
+        entries.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    #[test]
+    fn test_three_way_merge() {
+        let base = map(&[("kept", 1), ("edited_here", 1), ("edited_there", 1), ("both", 1), ("removed_there", 1), ("removed_here", 1)]);
+        let local = map(&[("kept", 1), ("edited_here", 2), ("edited_there", 1), ("both", 2), ("removed_there", 1), ("added_here", 1)]);
+        let remote = map(&[("kept", 1), ("edited_here", 1), ("edited_there", 3), ("both", 3), ("removed_here", 1), ("added_there", 1)]);
+
+        let merged = merge_maps(&base, &local, &remote, |local, remote| remote > local);
+        assert_eq!(
+            merged.entries,
+            map(&[("kept", 1), ("edited_here", 2), ("edited_there", 3), ("both", 3), ("added_here", 1), ("added_there", 1)])
+        );
+        assert_eq!(
+            merged.pulled.iter().map(String::as_str).collect::<Vec<_>>(),
+            ["added_there", "both", "edited_there", "removed_there"]
+        );
+        assert_eq!(merged.conflicts, 1);
+
+        // Without a base, both sides are kept and differences are conflicts
+        let merged = merge_maps(&BTreeMap::new(), &local, &remote, |_, _| false);
+        assert_eq!(merged.entries.get("both"), Some(&2));
+        assert_eq!(merged.entries.get("removed_here"), Some(&1));
+    }
+
+    #[test]
+    fn test_settings_merge_per_section() {
+        use serde_json::json;
+        let base = json!({ "volume": 0.5, "visualizer_style": "bars", "spotify_enabled": false });
+        let local = json!({ "volume": 0.8, "visualizer_style": "bars", "spotify_enabled": false, "library_path": "/home/jo/Music" });
+        // An older build's snapshot still carrying a machine-local section
+        let remote = json!({ "volume": 0.5, "visualizer_style": "waveform", "spotify_enabled": false, "library_path": "D:\\Music" });
+
+        let merged = merge_settings(Some(&base), Some(&local), Some(&remote));
+        assert_eq!(merged.entries.get("volume"), Some(&json!(0.8)));
+        assert_eq!(merged.entries.get("visualizer_style"), Some(&json!("waveform")));
+        assert!(!merged.entries.contains_key("library_path"));
+        assert_eq!(merged.pulled, BTreeSet::from(["visualizer_style".to_string()]));
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn test_sign_s3() {
+        let url = Url::parse("https://s3.eu-west-1.amazonaws.com/backups/milk/milk-backup.json").unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:30:00Z").unwrap().with_timezone(&Utc);
+        let headers = sign_s3("PUT", &url, "eu-west-1", "AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", b"{}", now);
+        assert_eq!(headers[0], ("x-amz-date", "20240501T123000Z".to_string()));
+        assert_eq!(headers[1].1, "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a");
+        assert_eq!(
+            headers[2].1,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240501/eu-west-1/s3/aws4_request, \
+             SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+             Signature=31f1ba8ce64a6cf2fc97bb8fae162374219360a79f48a3d6418b572f0ade0f8a"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_settings() {
+        let mut config = serde_json::to_value(crate::config::FileConfigManager::get_default()).unwrap();
+        config["proxy"]["username"] = "jo".into();
+        let shared = shareable_config(config);
+        assert!(shared.get("backup").is_none() && shared.get("proxy").is_none() && shared.get("revision").is_none());
+        assert!(["library_path", "window_position", "window_size", "windows", "output", "remote_api"].iter().all(|key| shared.get(key).is_none()));
+        assert!(shared.get("volume").is_some());
+
+        let mut settings = BackupSettings { provider: BackupProvider::WebDav, endpoint: "https://dav.example.com/files/".into(), ..Default::default() };
+        let now = Utc::now();
+        assert!(is_due(&settings, None, now));
+        assert!(!is_due(&settings, Some(now - chrono::Duration::hours(23)), now));
+        assert!(is_due(&settings, Some(now - chrono::Duration::hours(24)), now));
+        settings.interval_hours = 0;
+        assert!(!is_due(&settings, None, now));
+
+        let remote = Remote::from_settings(&BackupSettings { folder: "/milk/".into(), ..settings }, None).unwrap();
+        assert_eq!(remote.snapshot_url().as_str(), "https://dav.example.com/files/milk/milk-backup.json");
+        let s3 = BackupSettings { provider: BackupProvider::S3, endpoint: "https://s3.example.com".into(), bucket: "b".into(), ..Default::default() };
+        assert!(matches!(Remote::from_settings(&s3, None), Err(BackupError::NotConfigured)));
+    }
+}
@@ -0,0 +1,163 @@
+// Locale-aware text ordering and matching
+// Library browse, search and playlist sorting all order names through a
+// `Collator`: the CLDR collation for the user's locale (so "Ångström" sorts
+// after "Z" in Swedish but with the A's in English), with digit runs compared
+// as numbers and a leading "The"/"A"/"Le"/"Die"... ignored, as most players do.
+// `fold` is the matching side: it normalizes, drops accents and case-folds so
+// search finds "Beyoncé" from "beyonce".
+
+use icu_collator::{CollatorOptions, Numeric};
+use icu_locid::Locale;
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Built collators by (locale, natural); building one loads its tailoring
+static COLLATORS: Mutex<Vec<(String, bool, Arc<Collator>)>> = Mutex::new(Vec::new());
+
+/// Articles ignored everywhere, since most libraries mix in English names
+const ENGLISH_ARTICLES: [&str; 3] = ["the", "a", "an"];
+
+/// Leading articles ignored for a language, besides the English ones
+///
+/// Entries ending in an apostrophe are elided forms that join the next word.
+fn articles(language: &str) -> &'static [&'static str] {
+    match language {
+        "fr" => &["le", "la", "les", "l'", "un", "une"],
+        "de" => &["der", "die", "das", "ein", "eine"],
+        "es" => &["el", "la", "los", "las", "un", "una"],
+        "it" => &["il", "lo", "la", "i", "gli", "le", "l'", "un", "una"],
+        "pt" => &["o", "a", "os", "as", "um", "uma"],
+        "nl" => &["de", "het", "een"],
+        _ => &[],
+    }
+}
+
+/// Orders display names for one locale
+pub struct Collator {
+    collator: icu_collator::Collator,
+    language: String,
+}
+
+impl Collator {
+    /// Collator for a BCP 47 `locale` like "sv-SE", or the system locale
+    ///
+    /// Unknown locales fall back to the root collation. With `natural`,
+    /// "Track 2" sorts before "Track 10".
+    pub fn new(locale: Option<&str>, natural: bool) -> Self {
+        let locale = locale
+            .map(str::to_string)
+            .or_else(sys_locale::get_locale)
+            .and_then(|tag| tag.replace('_', "-").parse::<Locale>().ok())
+            .unwrap_or_default();
+        let mut options = CollatorOptions::new();
+        if natural {
+            options.numeric = Some(Numeric::On);
+        }
+        let collator = icu_collator::Collator::try_new(&(&locale).into(), options)
+            .or_else(|_| icu_collator::Collator::try_new(&Default::default(), options))
+            .expect("root collation data is compiled in");
+        Collator { collator, language: locale.id.language.as_str().to_string() }
+    }
+
+    /// Shared collator for `locale`, built on first use
+    pub fn shared(locale: Option<&str>, natural: bool) -> Arc<Collator> {
+        let key = locale.unwrap_or_default().to_string();
+        let mut collators = COLLATORS.lock().unwrap();
+        if let Some((_, _, collator)) = collators.iter().find(|(l, n, _)| *l == key && *n == natural) {
+            return collator.clone();
+        }
+        let collator = Arc::new(Collator::new(locale, natural));
+        collators.push((key, natural, collator.clone()));
+        collator
+    }
+
+    /// Compare two names, ignoring a leading article
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        self.collator
+            .compare(self.strip_article(a), self.strip_article(b))
+            // Keep "The Band" and "Band" apart, with the bare name first
+            .then_with(|| self.collator.compare(a, b))
+    }
+
+    /// `text` without a leading article of this collator's language or English
+    pub fn strip_article<'a>(&self, text: &'a str) -> &'a str {
+        let trimmed = text.trim_start();
+        for article in ENGLISH_ARTICLES.iter().chain(articles(&self.language)) {
+            let Some(head) = trimmed.get(..article.len()) else {
+                continue;
+            };
+            if !head.eq_ignore_ascii_case(article) {
+                continue;
+            }
+            let rest = &trimmed[article.len()..];
+            let rest = if article.ends_with('\'') {
+                rest
+            } else if rest.starts_with(char::is_whitespace) {
+                rest.trim_start()
+            } else {
+                continue;
+            };
+            // Never strip a name down to nothing, like the band "The The"'s second word
+            if !rest.is_empty() {
+                return rest;
+            }
+        }
+        trimmed
+    }
+}
+
+/// Normalize `text` for matching: compatibility decomposition, accents removed, case folded
+pub fn fold(text: &str) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for c in text.nfkd().filter(|c| !is_combining_mark(*c)) {
+        match c {
+            // Letters whose full case folding differs from lowercasing
+            'ß' | 'ẞ' => folded.push_str("ss"),
+            'ς' => folded.push('σ'),
+            c => folded.extend(c.to_lowercase()),
+        }
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(locale: &str, names: &[&str]) -> Vec<String> {
+        let collator = Collator::new(Some(locale), true);
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| collator.compare(a, b));
+        names
+    }
+
+    #[test]
+    fn test_locale_ordering() {
+        let names = ["Zebra", "Ångström", "apple", "Öl"];
+        assert_eq!(sorted("en-US", &names), ["Ångström", "apple", "Öl", "Zebra"]);
+        assert_eq!(sorted("sv_SE", &names), ["apple", "Zebra", "Ångström", "Öl"]);
+        assert_eq!(sorted("not a locale", &["b", "A"]), ["A", "b"]);
+    }
+
+    #[test]
+    fn test_articles_and_numbers() {
+        let names = ["The Zombies", "Track 10", "A Tribe Called Quest", "Track 2", "The The", "Beatles"];
+        assert_eq!(
+            sorted("en", &names),
+            ["Beatles", "The The", "Track 2", "Track 10", "A Tribe Called Quest", "The Zombies"]
+        );
+        let french = Collator::new(Some("fr"), true);
+        assert_eq!(french.strip_article("L'Impératrice"), "Impératrice");
+        assert_eq!(french.strip_article("Les Négresses Vertes"), "Négresses Vertes");
+        assert_eq!(french.strip_article("Theory"), "Theory");
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Beyoncé"), "beyonce");
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("ﬁve"), "five");
+    }
+}
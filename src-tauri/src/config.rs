@@ -1,18 +1,378 @@
+use crate::dsp_chain::DspChain;
+use crate::logging::{log_warn, LogFormat, LogLevel, LoggerConfig};
+use crate::now_playing::{NowPlayingSource, DEFAULT_SOURCE_PRIORITY};
+use crate::skin::SkinAssetOptions;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::io;
+use std::sync::Mutex;
+
+// Serializes read-check-write cycles so concurrent saves from several windows
+// cannot interleave between the revision check and the write
+static SAVE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Schema version written by this build
+pub const CONFIG_VERSION: u32 = 2;
+
+/// A step upgrading the raw config JSON by one version
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[n - 1]` upgrades version n to n + 1
+const MIGRATIONS: &[Migration] = &[migrate_v1_to_v2];
+
+/// Settings managed by the backend that `set_setting` may not touch
+const READ_ONLY_SETTINGS: &[&str] = &["version", "revision"];
+
+/// Map-valued settings whose entries may be added, not just replaced
+const MAP_SETTINGS: &[&str] = &["hotkeys.bindings", "logging.category_levels", "windows"];
+
+/// Smallest accepted log rotation size (64 KB)
+const MIN_LOG_FILE_SIZE: u64 = 64 * 1024;
+
+/// Most rotated log files that may be kept
+const MAX_LOG_FILES: usize = 50;
+
+/// Lowest remote API port; privileged ports would need elevated rights
+const MIN_REMOTE_API_PORT: u16 = 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Config {
+    /// Schema version; configs written before versioning are version 1
+    #[serde(default = "unversioned")]
+    pub version: u32,
     pub library_path: Option<String>,
     pub last_skin: Option<String>,
+    /// How skin bitmaps are prepared for the webview
+    #[serde(default)]
+    pub skin_assets: SkinAssetOptions,
     pub volume: f32,
     pub visualizer_style: String,
     pub spotify_enabled: bool,
     pub youtube_enabled: bool,
     pub window_position: WindowPosition,
     pub window_size: WindowSize,
+    /// Position, size and shade state of each window by label, in physical pixels
+    #[serde(default)]
+    pub windows: BTreeMap<String, WindowGeometry>,
+    /// User color overrides applied on top of skin and artwork colors
+    #[serde(default)]
+    pub theme_overrides: ThemeOverrides,
+    /// System-wide transport shortcuts
+    #[serde(default)]
+    pub hotkeys: HotkeyConfig,
+    /// Log level, format and rotation
+    #[serde(default)]
+    pub logging: LoggingSettings,
+    /// Embedded HTTP remote control
+    #[serde(default)]
+    pub remote_api: RemoteApiSettings,
+    /// Notification area icon and hide-to-tray behavior
+    #[serde(default)]
+    pub tray: TraySettings,
+    /// Proxy for the streaming services and the skin museum
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    /// Files and folders kept out of the library
+    #[serde(default)]
+    pub library_filters: LibraryFilterSettings,
+    /// Player behavior
+    #[serde(default)]
+    pub playback: PlaybackSettings,
+    /// Where the headless player sends audio
+    #[serde(default)]
+    pub output: OutputSettings,
+    /// Effects applied to the headless player's output
+    #[serde(default)]
+    pub dsp: DspChain,
+    /// Where and how often playlists, ratings and settings are backed up
+    #[serde(default)]
+    pub backup: BackupSettings,
+    /// Incremented on every successful save; used to detect stale writes
+    #[serde(default)]
+    pub revision: u64,
+    /// Fields this build does not know about, kept so they survive a save
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn unversioned() -> u32 {
+    1
+}
+
+/// User-chosen colors as `#rrggbb` strings; unset fields fall through to the skin
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ThemeOverrides {
+    pub accent: Option<String>,
+    pub visualizer: Option<Vec<String>>,
+    pub playlist_text: Option<String>,
+    pub playlist_background: Option<String>,
+}
+
+/// Action triggered by a global hotkey
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    ToggleWindow,
+}
+
+/// Global hotkey bindings as accelerator strings such as `Ctrl+Alt+Home`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HotkeyConfig {
+    /// Off by default so milk never grabs keys other apps rely on
+    pub enabled: bool,
+    /// Actions without a binding (or with an empty one) are not registered
+    pub bindings: BTreeMap<HotkeyAction, String>,
+}
+
+impl Default for HotkeyConfig {
+    /// Classic Winamp global hotkey layout
+    fn default() -> Self {
+        let bindings = [
+            (HotkeyAction::PlayPause, "Ctrl+Alt+Home"),
+            (HotkeyAction::Stop, "Ctrl+Alt+End"),
+            (HotkeyAction::Next, "Ctrl+Alt+PageDown"),
+            (HotkeyAction::Previous, "Ctrl+Alt+PageUp"),
+            (HotkeyAction::VolumeUp, "Ctrl+Alt+Up"),
+            (HotkeyAction::VolumeDown, "Ctrl+Alt+Down"),
+            (HotkeyAction::ToggleWindow, "Ctrl+Alt+W"),
+        ]
+        .into_iter()
+        .map(|(action, accelerator)| (action, accelerator.to_string()))
+        .collect();
+
+        Self {
+            enabled: false,
+            bindings,
+        }
+    }
+}
+
+/// Persisted logger settings, applied at startup and whenever they change
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LoggingSettings {
+    pub level: LogLevel,
+    pub format: LogFormat,
+    /// Size in bytes at which the log file is rotated
+    pub max_file_size: u64,
+    /// Number of rotated log files kept
+    pub max_files: usize,
+    /// Per-category level overrides, e.g. `{"SystemAudio": "debug"}`
+    pub category_levels: BTreeMap<String, LogLevel>,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        let logger = LoggerConfig::default();
+        Self {
+            level: logger.min_level,
+            format: logger.format,
+            max_file_size: logger.max_file_size,
+            max_files: logger.max_files,
+            category_levels: BTreeMap::new(),
+        }
+    }
+}
+
+impl LoggingSettings {
+    pub fn to_logger_config(&self) -> LoggerConfig {
+        LoggerConfig {
+            max_file_size: self.max_file_size,
+            max_files: self.max_files,
+            min_level: self.level,
+            format: self.format,
+            category_levels: self.category_levels.clone().into_iter().collect(),
+        }
+    }
+}
+
+/// Settings for the HTTP remote-control API
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RemoteApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Listen on all interfaces instead of only localhost
+    pub allow_lan: bool,
+}
+
+impl Default for RemoteApiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // httpQ's default port
+            port: 4800,
+            allow_lan: false,
+        }
+    }
+}
+
+/// Settings for the system tray icon
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct TraySettings {
+    pub enabled: bool,
+    /// Minimizing the main window hides it to the tray
+    pub minimize_to_tray: bool,
+    /// Closing the main window hides it to the tray instead of quitting
+    pub close_to_tray: bool,
+}
+
+impl Default for TraySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            minimize_to_tray: false,
+            close_to_tray: false,
+        }
+    }
+}
+
+/// How outgoing HTTP requests reach the network
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyMode {
+    /// Follow the HTTP_PROXY/HTTPS_PROXY/ALL_PROXY environment variables
+    #[default]
+    System,
+    /// Connect directly, ignoring the environment
+    None,
+    Http,
+    Socks5,
+}
+
+/// Proxy settings; the password lives in secure storage, not here
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct ProxySettings {
+    pub mode: ProxyMode,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupProvider {
+    #[default]
+    None,
+    WebDav,
+    /// Any S3-compatible object store
+    S3,
+}
+
+/// Cloud backup target; the WebDAV password or S3 secret key lives in secure storage
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub provider: BackupProvider,
+    /// WebDAV folder URL, or the S3 endpoint, e.g. `https://s3.eu-west-1.amazonaws.com`
+    pub endpoint: String,
+    /// S3 bucket; unused for WebDAV
+    pub bucket: String,
+    /// S3 signing region
+    pub region: String,
+    /// Folder (WebDAV) or key prefix (S3) the backup is stored under
+    pub folder: String,
+    /// WebDAV user name or S3 access key id
+    pub username: Option<String>,
+    /// Hours between automatic backups; 0 only backs up on request
+    pub interval_hours: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            provider: BackupProvider::None,
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            folder: "milk".to_string(),
+            username: None,
+            interval_hours: 24,
+        }
+    }
+}
+
+/// Library exclusion rules, applied when scanning
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct LibraryFilterSettings {
+    /// Glob patterns; see `library_filter::LibraryFilter`
+    pub exclusions: Vec<String>,
+    /// Skip tracks tagged as explicit
+    pub hide_explicit: bool,
+}
+
+/// Playback options applied by the player
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PlaybackSettings {
+    /// Seek past leading silence and move on at trailing silence, as found by `analyze_silence`
+    pub skip_silence: bool,
+    /// Files at least this long (audiobooks, mixes, podcasts) resume where they were left
+    pub resume_min_duration_secs: u32,
+    /// Now playing sources, highest priority first; sources left out are ignored
+    pub source_priority: Vec<NowPlayingSource>,
+}
+
+impl Default for PlaybackSettings {
+    fn default() -> Self {
+        Self {
+            skip_silence: false,
+            resume_min_duration_secs: 20 * 60,
+            source_priority: DEFAULT_SOURCE_PRIORITY.to_vec(),
+        }
+    }
+}
+
+/// Where the headless player sends audio; the windowed player always plays
+/// through the webview to the system output
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// The default output device
+    #[default]
+    Local,
+    /// A Snapcast server's TCP source, for synchronized multi-room playback
+    Snapcast,
+}
+
+/// Audio output of the headless player
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct OutputSettings {
+    pub mode: OutputMode,
+    /// Output device by name; the system default when unset or missing
+    pub device: Option<String>,
+    /// Devices played bit-perfect: held exclusively (WASAPI exclusive mode on
+    /// Windows, hog mode on macOS) at each file's own sample rate, bypassing
+    /// the OS mixer, with the volume at full and no DSP
+    pub bit_perfect_devices: Vec<String>,
+    pub snapcast_host: String,
+    /// Port of the server's `tcp://` source
+    pub snapcast_port: u16,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            mode: OutputMode::Local,
+            device: None,
+            bit_perfect_devices: Vec::new(),
+            snapcast_host: "127.0.0.1".to_string(),
+            snapcast_port: 4953,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -27,11 +387,42 @@ pub struct WindowSize {
     pub height: u32,
 }
 
+/// Saved placement of one window
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// Collapsed to the skin's title bar strip
+    #[serde(default)]
+    pub shaded: bool,
+    /// Height to restore when leaving shade mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_height: Option<u32>,
+    #[serde(default)]
+    pub always_on_top: bool,
+}
+
+/// Payload of the `config-conflict` event, sent when a save is rejected
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigConflict {
+    /// Fields changed both on disk and by the rejected save
+    pub fields: Vec<String>,
+    /// The configuration currently on disk, for the UI to reload
+    pub current: Config,
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     IoError(io::Error),
     SerializationError(serde_json::Error),
     InvalidPath,
+    Conflict(Box<ConfigConflict>),
+    /// An upgrade step could not convert the stored config
+    Migration { from: u32, reason: String },
+    /// A single setting was unknown, read-only or given a bad value
+    InvalidSetting { key: String, reason: String },
 }
 
 impl From<io::Error> for ConfigError {
@@ -52,6 +443,13 @@ impl std::fmt::Display for ConfigError {
             ConfigError::IoError(e) => write!(f, "IO error: {}", e),
             ConfigError::SerializationError(e) => write!(f, "Serialization error: {}", e),
             ConfigError::InvalidPath => write!(f, "Invalid configuration path"),
+            ConfigError::Conflict(c) => {
+                write!(f, "Configuration changed elsewhere (revision {}): {}", c.current.revision, c.fields.join(", "))
+            }
+            ConfigError::Migration { from, reason } => {
+                write!(f, "Failed to upgrade configuration from version {}: {}", from, reason)
+            }
+            ConfigError::InvalidSetting { key, reason } => write!(f, "Invalid setting {}: {}", key, reason),
         }
     }
 }
@@ -60,7 +458,6 @@ impl std::error::Error for ConfigError {}
 
 pub trait ConfigManager {
     fn load() -> Result<Config, ConfigError>;
-    fn save(&self, config: &Config) -> Result<(), ConfigError>;
     fn get_default() -> Config;
 }
 
@@ -79,46 +476,329 @@ impl FileConfigManager {
         
         Ok(milk_dir.join("config.json"))
     }
-}
 
-impl ConfigManager for FileConfigManager {
-    fn load() -> Result<Config, ConfigError> {
+    /// Save with optimistic concurrency, merging against `base` when stale
+    ///
+    /// `base` is the configuration the caller originally loaded. Returns the
+    /// configuration as written, including its new revision.
+    pub fn save_merged(&self, base: Option<&Config>, config: &Config) -> Result<Config, ConfigError> {
         let config_path = Self::get_config_path()?;
-        
-        if !config_path.exists() {
-            // Return default config if file doesn't exist
-            return Ok(Self::get_default());
+        save_with_revision(&config_path, base, config)
+    }
+
+    /// Change one setting on top of the current on-disk config
+    ///
+    /// Never conflicts, since only the named setting is written.
+    pub fn update_setting(&self, key: &str, value: Value) -> Result<Config, ConfigError> {
+        self.update_settings(vec![(key.to_string(), value)])
+    }
+
+    /// Change several settings in one write; nothing is saved if any is invalid
+    pub fn update_settings(&self, changes: Vec<(String, Value)>) -> Result<Config, ConfigError> {
+        let config_path = Self::get_config_path()?;
+        update_settings_at(&config_path, changes)
+    }
+
+    /// Rewrite the current on-disk config with `change`, holding off other saves meanwhile
+    ///
+    /// For read-modify-write updates that depend on the value being replaced.
+    pub fn update_with(&self, change: impl FnOnce(&Config) -> Result<Config, ConfigError>) -> Result<Config, ConfigError> {
+        let config_path = Self::get_config_path()?;
+        update_at(&config_path, change)
+    }
+}
+
+/// Read a setting by dotted key, e.g. `volume` or `window_size.width`
+pub fn get_setting(config: &Config, key: &str) -> Result<Value, ConfigError> {
+    let invalid = |reason: &str| ConfigError::InvalidSetting {
+        key: key.to_string(),
+        reason: reason.to_string(),
+    };
+    let mut value = serde_json::to_value(config)?;
+    for part in key.split('.') {
+        value = value
+            .as_object_mut()
+            .and_then(|map| map.remove(part))
+            .ok_or_else(|| invalid("unknown setting"))?;
+    }
+    Ok(value)
+}
+
+/// Return `config` with one setting replaced, validating the new value
+///
+/// Values are type-checked against the `Config` schema and then range-checked
+/// by `validate_settings`.
+pub fn set_setting(config: &Config, key: &str, value: Value) -> Result<Config, ConfigError> {
+    let invalid = |reason: String| ConfigError::InvalidSetting {
+        key: key.to_string(),
+        reason,
+    };
+    if READ_ONLY_SETTINGS.contains(&key) {
+        return Err(invalid("setting is read-only".to_string()));
+    }
+
+    let mut root = serde_json::to_value(config)?;
+    let (parent_key, field) = match key.rsplit_once('.') {
+        Some((parent, field)) => (Some(parent), field),
+        None => (None, key),
+    };
+    let mut parent = &mut root;
+    for part in parent_key.into_iter().flat_map(|p| p.split('.')) {
+        parent = parent
+            .get_mut(part)
+            .filter(|v| v.is_object())
+            .ok_or_else(|| invalid("unknown setting".to_string()))?;
+    }
+    let map = parent
+        .as_object_mut()
+        .ok_or_else(|| invalid("unknown setting".to_string()))?;
+    let insertable = parent_key.is_some_and(|p| MAP_SETTINGS.contains(&p));
+    if !insertable && !map.contains_key(field) {
+        return Err(invalid("unknown setting".to_string()));
+    }
+    map.insert(field.to_string(), value);
+
+    let updated: Config = serde_json::from_value(root).map_err(|e| invalid(e.to_string()))?;
+    validate_settings(&updated).map_err(invalid)?;
+    Ok(updated)
+}
+
+/// Range checks that the type system does not cover
+fn validate_settings(config: &Config) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&config.volume) {
+        return Err("volume must be between 0.0 and 1.0".to_string());
+    }
+    if config.visualizer_style.trim().is_empty() {
+        return Err("visualizer style cannot be empty".to_string());
+    }
+    if config.window_size.width == 0 || config.window_size.height == 0 {
+        return Err("window size must be non-zero".to_string());
+    }
+    if config.logging.max_file_size < MIN_LOG_FILE_SIZE {
+        return Err(format!("log file size must be at least {} bytes", MIN_LOG_FILE_SIZE));
+    }
+    if !(1..=MAX_LOG_FILES).contains(&config.logging.max_files) {
+        return Err(format!("log file count must be between 1 and {}", MAX_LOG_FILES));
+    }
+    if config.remote_api.port < MIN_REMOTE_API_PORT {
+        return Err(format!("remote API port must be at least {}", MIN_REMOTE_API_PORT));
+    }
+    if matches!(config.proxy.mode, ProxyMode::Http | ProxyMode::Socks5)
+        && (config.proxy.host.trim().is_empty() || config.proxy.port == 0)
+    {
+        return Err("proxy host and port are required".to_string());
+    }
+    config.dsp.validate()?;
+    let backup = &config.backup;
+    if backup.provider != BackupProvider::None {
+        match url::Url::parse(backup.endpoint.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err("backup endpoint must be an http or https URL".to_string()),
         }
-        
-        let contents = fs::read_to_string(&config_path)?;
-        
-        // Try to parse the config, return default if corrupted
-        match serde_json::from_str::<Config>(&contents) {
-            Ok(config) => Ok(config),
-            Err(_) => {
-                // Config is corrupted, return default
-                Ok(Self::get_default())
+    }
+    if backup.provider == BackupProvider::S3 && (backup.bucket.trim().is_empty() || backup.region.trim().is_empty()) {
+        return Err("S3 backups need a bucket and a region".to_string());
+    }
+    for pattern in &config.library_filters.exclusions {
+        glob::Pattern::new(pattern).map_err(|e| format!("'{}' is not a valid pattern: {}", pattern, e))?;
+    }
+    let overrides = &config.theme_overrides;
+    let colors = [&overrides.accent, &overrides.playlist_text, &overrides.playlist_background]
+        .into_iter()
+        .flatten()
+        .chain(overrides.visualizer.iter().flatten());
+    for color in colors {
+        if !is_hex_color(color) {
+            return Err(format!("'{}' is not a #rrggbb color", color));
+        }
+    }
+    Ok(())
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Parse stored config JSON, upgrading it to `CONFIG_VERSION` first
+///
+/// Configs from a newer build are read as-is; their unknown fields are kept
+/// in `extra`.
+pub fn parse_config(contents: &str) -> Result<Config, ConfigError> {
+    let mut map = match serde_json::from_str::<Value>(contents)? {
+        Value::Object(map) => map,
+        _ => {
+            return Err(ConfigError::Migration {
+                from: 0,
+                reason: "configuration is not a JSON object".to_string(),
+            })
+        }
+    };
+
+    let stored_version = map
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |v| v.clamp(1, u32::MAX as u64) as u32);
+    for version in stored_version..CONFIG_VERSION {
+        MIGRATIONS[(version - 1) as usize](&mut map).map_err(|reason| ConfigError::Migration { from: version, reason })?;
+        map.insert("version".to_string(), Value::from(version + 1));
+    }
+
+    Ok(serde_json::from_value(Value::Object(map))?)
+}
+
+/// Version 1 predates theme overrides, hotkeys and revisions; write their defaults out
+fn migrate_v1_to_v2(map: &mut Map<String, Value>) -> Result<(), String> {
+    let defaults: [(&str, Value); 3] = [
+        ("theme_overrides", serde_json::to_value(ThemeOverrides::default()).map_err(|e| e.to_string())?),
+        ("hotkeys", serde_json::to_value(HotkeyConfig::default()).map_err(|e| e.to_string())?),
+        ("revision", Value::from(0)),
+    ];
+    for (key, value) in defaults {
+        if map.get(key).is_none_or(Value::is_null) {
+            map.insert(key.to_string(), value);
+        }
+    }
+    Ok(())
+}
+
+/// Read the config at `path`, falling back to defaults if missing or unreadable
+///
+/// A config that cannot be parsed or migrated is copied aside to
+/// `config.json.bak` before defaults are used, so the user's settings can be
+/// recovered by hand.
+fn read_or_default(path: &Path) -> Result<Config, ConfigError> {
+    if !path.exists() {
+        return Ok(FileConfigManager::get_default());
+    }
+    let contents = fs::read_to_string(path)?;
+    match parse_config(&contents) {
+        Ok(config) => Ok(config),
+        Err(e) => {
+            let backup = path.with_extension("json.bak");
+            log_warn("Config", &format!("{}; using defaults (previous file kept at {})", e, backup.display()));
+            if let Err(copy_err) = fs::copy(path, &backup) {
+                log_warn("Config", &format!("Failed to back up unreadable config: {}", copy_err));
             }
+            Ok(FileConfigManager::get_default())
         }
     }
-    
-    fn save(&self, config: &Config) -> Result<(), ConfigError> {
+}
+
+/// Write `config` to `path` if its revision is current, otherwise try a merge
+///
+/// A stale save without a `base` cannot be merged and is rejected outright.
+pub(crate) fn save_with_revision(path: &Path, base: Option<&Config>, config: &Config) -> Result<Config, ConfigError> {
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let current = read_or_default(path)?;
+
+    let mut to_write = if config.revision == current.revision {
+        config.clone()
+    } else {
+        let conflict = |fields| ConfigError::Conflict(Box::new(ConfigConflict {
+            fields,
+            current: current.clone(),
+        }));
+        match base {
+            Some(base) => merge_config(base, config, &current).map_err(conflict)?,
+            None => return Err(conflict(Vec::new())),
+        }
+    };
+
+    to_write.revision = current.revision + 1;
+    let json = serde_json::to_string_pretty(&to_write)?;
+    fs::write(path, json)?;
+    Ok(to_write)
+}
+
+/// Apply settings to the config at `path` and write it with a new revision
+pub(crate) fn update_settings_at(path: &Path, changes: Vec<(String, Value)>) -> Result<Config, ConfigError> {
+    update_at(path, |current| {
+        changes
+            .into_iter()
+            .try_fold(current.clone(), |config, (key, value)| set_setting(&config, &key, value))
+    })
+}
+
+/// Write `change` applied to the config at `path` with a new revision
+pub(crate) fn update_at(path: &Path, change: impl FnOnce(&Config) -> Result<Config, ConfigError>) -> Result<Config, ConfigError> {
+    let _guard = SAVE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let current = read_or_default(path)?;
+
+    let mut to_write = change(&current)?;
+    to_write.revision = current.revision + 1;
+    let json = serde_json::to_string_pretty(&to_write)?;
+    fs::write(path, json)?;
+    Ok(to_write)
+}
+
+/// Three-way merge of a local edit into the current on-disk config
+///
+/// Fields the caller changed relative to `base` are applied on top of
+/// `current`. If another writer changed the same field to a different value,
+/// the merge fails with the list of conflicting field names.
+pub fn merge_config(base: &Config, local: &Config, current: &Config) -> Result<Config, Vec<String>> {
+    let to_map = |c: &Config| match serde_json::to_value(c) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let base_map = to_map(base);
+    let local_map = to_map(local);
+    let mut merged = to_map(current);
+    let mut conflicts = Vec::new();
+
+    for (key, local_value) in &local_map {
+        if key == "revision" || base_map.get(key) == Some(local_value) {
+            continue;
+        }
+        let current_value = merged.get(key);
+        if current_value != base_map.get(key) && current_value != Some(local_value) {
+            conflicts.push(key.clone());
+            continue;
+        }
+        merged.insert(key.clone(), local_value.clone());
+    }
+
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+    serde_json::from_value(serde_json::Value::Object(merged)).map_err(|_| vec!["config".to_string()])
+}
+
+impl ConfigManager for FileConfigManager {
+    fn load() -> Result<Config, ConfigError> {
         let config_path = Self::get_config_path()?;
-        let json = serde_json::to_string_pretty(config)?;
-        fs::write(&config_path, json)?;
-        Ok(())
+        
+        // Missing files give defaults; old versions are migrated, and only
+        // configs that cannot be upgraded fall back to defaults
+        read_or_default(&config_path)
     }
     
     fn get_default() -> Config {
         Config {
+            version: CONFIG_VERSION,
             library_path: None,
             last_skin: None,
+            skin_assets: SkinAssetOptions::default(),
             volume: 0.7,
             visualizer_style: "bars".to_string(),
             spotify_enabled: false,
             youtube_enabled: false,
             window_position: WindowPosition { x: 100, y: 100 },
             window_size: WindowSize { width: 800, height: 600 },
+            windows: BTreeMap::new(),
+            theme_overrides: ThemeOverrides::default(),
+            hotkeys: HotkeyConfig::default(),
+            logging: LoggingSettings::default(),
+            remote_api: RemoteApiSettings::default(),
+            tray: TraySettings::default(),
+            proxy: ProxySettings::default(),
+            library_filters: LibraryFilterSettings::default(),
+            playback: PlaybackSettings::default(),
+            output: OutputSettings::default(),
+            dsp: DspChain::default(),
+            backup: BackupSettings::default(),
+            revision: 0,
+            extra: Map::new(),
         }
     }
 }
@@ -143,6 +823,227 @@ mod tests {
         let deserialized: Config = serde_json::from_str(&json).unwrap();
         assert_eq!(config, deserialized);
     }
+
+    #[test]
+    fn test_missing_revision_defaults_to_zero() {
+        let mut value = serde_json::to_value(FileConfigManager::get_default()).unwrap();
+        value.as_object_mut().unwrap().remove("revision");
+        let config: Config = serde_json::from_value(value).unwrap();
+        assert_eq!(config.revision, 0);
+    }
+
+    fn v1_config_json() -> Value {
+        serde_json::json!({
+            "library_path": "/music",
+            "last_skin": null,
+            "volume": 0.4,
+            "visualizer_style": "spectrum",
+            "spotify_enabled": true,
+            "youtube_enabled": false,
+            "window_position": { "x": 10, "y": 20 },
+            "window_size": { "width": 640, "height": 480 }
+        })
+    }
+
+    #[test]
+    fn test_v1_config_is_migrated() {
+        let config = parse_config(&v1_config_json().to_string()).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.library_path.as_deref(), Some("/music"));
+        assert_eq!(config.volume, 0.4);
+        assert_eq!(config.hotkeys, HotkeyConfig::default());
+    }
+
+    #[test]
+    fn test_unknown_fields_survive_round_trip() {
+        let mut value = v1_config_json();
+        value["future_setting"] = serde_json::json!({ "enabled": true });
+        let config = parse_config(&value.to_string()).unwrap();
+        assert_eq!(config.extra.get("future_setting"), Some(&serde_json::json!({ "enabled": true })));
+
+        let reparsed = parse_config(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(reparsed, config);
+    }
+
+    #[test]
+    fn test_newer_version_is_not_migrated() {
+        let mut value = serde_json::to_value(FileConfigManager::get_default()).unwrap();
+        value["version"] = Value::from(CONFIG_VERSION + 1);
+        let config = parse_config(&value.to_string()).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION + 1);
+    }
+
+    #[test]
+    fn test_unreadable_config_is_backed_up() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        fs::write(&path, r#"{"volume": "loud"}"#).unwrap();
+
+        let config = read_or_default(&path).unwrap();
+        assert_eq!(config, FileConfigManager::get_default());
+        let backup = fs::read_to_string(temp_dir.path().join("config.json.bak")).unwrap();
+        assert_eq!(backup, r#"{"volume": "loud"}"#);
+    }
+
+    #[test]
+    fn test_get_setting_by_dotted_key() {
+        let config = FileConfigManager::get_default();
+        assert_eq!(get_setting(&config, "window_size.width").unwrap(), serde_json::json!(800));
+        assert!(matches!(get_setting(&config, "window_size.depth"), Err(ConfigError::InvalidSetting { .. })));
+    }
+
+    #[test]
+    fn test_set_setting_validates_type_and_range() {
+        let config = FileConfigManager::get_default();
+        assert_eq!(set_setting(&config, "volume", serde_json::json!(0.5)).unwrap().volume, 0.5);
+        assert!(set_setting(&config, "volume", serde_json::json!("loud")).is_err());
+        assert!(set_setting(&config, "volume", serde_json::json!(1.5)).is_err());
+        assert!(set_setting(&config, "theme_overrides.accent", serde_json::json!("red")).is_err());
+    }
+
+    #[test]
+    fn test_set_setting_rejects_unknown_and_read_only_keys() {
+        let config = FileConfigManager::get_default();
+        assert!(set_setting(&config, "volum", serde_json::json!(0.5)).is_err());
+        assert!(set_setting(&config, "revision", serde_json::json!(99)).is_err());
+        let updated = set_setting(&config, "hotkeys.bindings.next", serde_json::json!("Ctrl+N")).unwrap();
+        assert_eq!(updated.hotkeys.bindings[&HotkeyAction::Next], "Ctrl+N");
+    }
+
+    #[test]
+    fn test_logging_settings_are_validated() {
+        let config = FileConfigManager::get_default();
+        let updated = set_setting(&config, "logging.level", serde_json::json!("debug")).unwrap();
+        assert_eq!(updated.logging.level, LogLevel::Debug);
+        assert!(set_setting(&config, "logging.level", serde_json::json!("chatty")).is_err());
+        assert!(set_setting(&config, "logging.max_files", serde_json::json!(0)).is_err());
+        assert!(set_setting(&config, "logging.max_file_size", serde_json::json!(10)).is_err());
+    }
+
+    #[test]
+    fn test_proxy_settings_are_validated() {
+        let config = FileConfigManager::get_default();
+        assert_eq!(config.proxy.mode, ProxyMode::System);
+        assert!(set_setting(&config, "proxy.mode", serde_json::json!("socks5")).is_err());
+
+        let updated = set_setting(&config, "proxy.host", serde_json::json!("proxy.corp")).unwrap();
+        let updated = set_setting(&updated, "proxy.port", serde_json::json!(3128)).unwrap();
+        let updated = set_setting(&updated, "proxy.mode", serde_json::json!("http")).unwrap();
+        assert_eq!(updated.proxy.mode, ProxyMode::Http);
+        assert!(set_setting(&updated, "proxy.mode", serde_json::json!("ftp")).is_err());
+    }
+
+    #[test]
+    fn test_update_setting_keeps_concurrent_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let mut other = FileConfigManager::get_default();
+        other.spotify_enabled = true;
+        save_with_revision(&path, None, &other).unwrap();
+
+        let updated = update_settings_at(&path, vec![("volume".to_string(), serde_json::json!(0.2))]).unwrap();
+        assert_eq!(updated.revision, 2);
+        assert!(updated.spotify_enabled);
+        assert_eq!(read_or_default(&path).unwrap(), updated);
+    }
+
+    #[test]
+    fn test_concurrent_updates_are_not_lost() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    update_at(&path, |current| {
+                        let mut config = current.clone();
+                        config.library_filters.exclusions.push(format!("pattern{}", i));
+                        Ok(config)
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let saved = read_or_default(&path).unwrap();
+        assert_eq!(saved.library_filters.exclusions.len(), 8);
+        assert_eq!(saved.revision, 8);
+    }
+
+    #[test]
+    fn test_merge_non_conflicting_changes() {
+        let base = FileConfigManager::get_default();
+        let mut local = base.clone();
+        local.volume = 0.3;
+        let mut current = base.clone();
+        current.visualizer_style = "spectrum".to_string();
+        current.revision = 4;
+
+        let merged = merge_config(&base, &local, &current).unwrap();
+        assert_eq!(merged.volume, 0.3);
+        assert_eq!(merged.visualizer_style, "spectrum");
+        assert_eq!(merged.revision, 4);
+    }
+
+    #[test]
+    fn test_merge_reports_conflicting_fields() {
+        let base = FileConfigManager::get_default();
+        let mut local = base.clone();
+        local.volume = 0.3;
+        let mut current = base.clone();
+        current.volume = 0.9;
+
+        let conflicts = merge_config(&base, &local, &current).unwrap_err();
+        assert_eq!(conflicts, vec!["volume".to_string()]);
+    }
+
+    #[test]
+    fn test_merge_same_change_is_not_a_conflict() {
+        let base = FileConfigManager::get_default();
+        let mut local = base.clone();
+        local.spotify_enabled = true;
+        let current = local.clone();
+
+        assert!(merge_config(&base, &local, &current).is_ok());
+    }
+
+    #[test]
+    fn test_save_with_revision_bumps_and_rejects_stale() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let base = FileConfigManager::get_default();
+
+        // First window saves against revision 0
+        let mut first = base.clone();
+        first.volume = 0.2;
+        let saved = save_with_revision(&path, Some(&base), &first).unwrap();
+        assert_eq!(saved.revision, 1);
+
+        // Second window is stale but touches a different field: merged
+        let mut second = base.clone();
+        second.youtube_enabled = true;
+        let merged = save_with_revision(&path, Some(&base), &second).unwrap();
+        assert_eq!(merged.revision, 2);
+        assert_eq!(merged.volume, 0.2);
+        assert!(merged.youtube_enabled);
+
+        // Stale write to the same field is rejected
+        let mut third = base.clone();
+        third.volume = 0.5;
+        match save_with_revision(&path, Some(&base), &third) {
+            Err(ConfigError::Conflict(c)) => {
+                assert_eq!(c.fields, vec!["volume".to_string()]);
+                assert_eq!(c.current.revision, 2);
+            }
+            other => panic!("expected conflict, got {:?}", other),
+        }
+
+        // Stale write without a base cannot be merged
+        assert!(matches!(save_with_revision(&path, None, &third), Err(ConfigError::Conflict(_))));
+    }
 }
 
 #[cfg(test)]
@@ -207,14 +1108,30 @@ mod property_tests {
         )
             .prop_map(|(library_path, last_skin, volume, visualizer_style, spotify_enabled, youtube_enabled, x, y, width, height)| {
                 Config {
+                    version: CONFIG_VERSION,
                     library_path,
                     last_skin,
+                    skin_assets: SkinAssetOptions::default(),
                     volume,
                     visualizer_style,
                     spotify_enabled,
                     youtube_enabled,
                     window_position: WindowPosition { x, y },
                     window_size: WindowSize { width, height },
+                    windows: BTreeMap::new(),
+                    theme_overrides: ThemeOverrides::default(),
+                    hotkeys: HotkeyConfig::default(),
+                    logging: LoggingSettings::default(),
+                    remote_api: RemoteApiSettings::default(),
+                    tray: TraySettings::default(),
+                    proxy: ProxySettings::default(),
+                    library_filters: LibraryFilterSettings::default(),
+                    playback: PlaybackSettings::default(),
+                    output: OutputSettings::default(),
+                    dsp: DspChain::default(),
+                    backup: BackupSettings::default(),
+                    revision: 0,
+                    extra: Map::new(),
                 }
             })
     }
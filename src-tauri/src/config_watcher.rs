@@ -0,0 +1,146 @@
+// Live reload of config.json
+// Polls the config file for external edits and broadcasts the revalidated config
+
+use crate::api_client;
+use crate::config::{parse_config, Config, ConfigError, ConfigManager, FileConfigManager};
+use crate::hotkeys;
+use crate::logging::{self, log_info, log_warn};
+use crate::secure_storage::PlatformSecureStorage;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How often the config file is checked for changes
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Tracks the last config seen on disk
+pub struct ConfigWatcher {
+    path: PathBuf,
+    contents: Option<String>,
+    config: Option<Config>,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let contents = fs::read_to_string(&path).ok();
+        let config = contents.as_deref().and_then(|c| parse_config(c).ok());
+        Self { path, contents, config }
+    }
+
+    /// Re-read the file, returning the new config if it changed
+    ///
+    /// Edits that leave the parsed config unchanged (formatting, touching the
+    /// file) are ignored. An invalid file is reported once per edit and the
+    /// last good config is kept.
+    pub fn poll(&mut self) -> Option<Result<Config, ConfigError>> {
+        let contents = fs::read_to_string(&self.path).ok();
+        if contents == self.contents {
+            return None;
+        }
+        self.contents = contents;
+
+        // A deleted file means the defaults apply again
+        let parsed = match self.contents.as_deref() {
+            Some(contents) => parse_config(contents),
+            None => Ok(FileConfigManager::get_default()),
+        };
+        match parsed {
+            Ok(config) if self.config.as_ref() == Some(&config) => None,
+            Ok(config) => {
+                self.config = Some(config.clone());
+                Some(Ok(config))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Watch the config file for the lifetime of the app
+///
+/// Changes are emitted as `config-changed` with the new config, whether they
+/// come from another window's save or a hand edit. Global hotkeys, the logger
+/// and the network proxy are re-applied so the backend follows the file too.
+pub fn spawn(app: AppHandle) -> Result<(), ConfigError> {
+    let path = FileConfigManager::get_config_path()?;
+    let mut watcher = ConfigWatcher::new(path);
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            match watcher.poll() {
+                None => {}
+                Some(Ok(config)) => {
+                    log_info("Config", &format!("Configuration changed on disk (revision {})", config.revision));
+                    if let Err(e) = hotkeys::apply(&app, &config.hotkeys, false) {
+                        log_warn("Hotkeys", &format!("Global hotkeys not updated: {}", e));
+                    }
+                    logging::reconfigure_logger(config.logging.to_logger_config());
+                    if let Err(e) = api_client::configure_proxy(&config.proxy, &PlatformSecureStorage::new()) {
+                        log_warn("ApiClient", &format!("Proxy settings not applied: {}", e));
+                    }
+                    if let Err(e) = app.emit("config-changed", &config) {
+                        log_warn("Config", &format!("Failed to emit config-changed event: {}", e));
+                    }
+                }
+                Some(Err(e)) => log_warn("Config", &format!("Ignoring invalid config edit: {}", e)),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(path: &PathBuf, config: &Config) {
+        fs::write(path, serde_json::to_string_pretty(config).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_detects_external_edit() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let mut config = FileConfigManager::get_default();
+        write(&path, &config);
+
+        let mut watcher = ConfigWatcher::new(path.clone());
+        assert!(watcher.poll().is_none());
+
+        config.volume = 0.25;
+        write(&path, &config);
+        assert_eq!(watcher.poll().unwrap().unwrap().volume, 0.25);
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn test_reformatting_is_not_a_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let config = FileConfigManager::get_default();
+        write(&path, &config);
+
+        let mut watcher = ConfigWatcher::new(path.clone());
+        fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+        assert!(watcher.poll().is_none());
+    }
+
+    #[test]
+    fn test_invalid_edit_keeps_last_good_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let config = FileConfigManager::get_default();
+        write(&path, &config);
+
+        let mut watcher = ConfigWatcher::new(path.clone());
+        fs::write(&path, "{ not json").unwrap();
+        assert!(matches!(watcher.poll(), Some(Err(_))));
+
+        // Restoring the original content is not reported as a change
+        write(&path, &config);
+        assert!(watcher.poll().is_none());
+    }
+}
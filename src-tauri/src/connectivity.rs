@@ -0,0 +1,250 @@
+// Network connectivity monitor and offline work queue
+// A background probe decides whether the machine is online. While offline,
+// streaming API calls fail fast with `MilkError::Offline` instead of waiting
+// on timeouts, and non-urgent work (museum and Bandcamp downloads, scheduled
+// cloud backups) is parked in a queue that runs as soon as the connection is
+// back. Only the skin museum and the enabled streaming services are probed.
+// Every transition is emitted as `connectivity-changed`.
+
+use crate::config::{ConfigManager, FileConfigManager};
+use crate::error::MilkError;
+use crate::logging::{log_info, log_warn};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+/// Always probed, since the skin museum needs no account
+const MUSEUM_PROBE_URL: &str = "https://api.webamp.org/";
+const SPOTIFY_PROBE_URL: &str = "https://api.spotify.com/";
+const YOUTUBE_PROBE_URL: &str = "https://www.googleapis.com/";
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Probe more often while offline so queued work resumes promptly
+const OFFLINE_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Oldest deferred work is dropped beyond this
+const MAX_DEFERRED: usize = 100;
+
+// Assume online until the first probe says otherwise, so startup never waits
+static ONLINE: AtomicBool = AtomicBool::new(true);
+
+// Also guards transitions, so work is never queued just after the queue was drained
+static QUEUE: Mutex<DeferredQueue> = Mutex::new(DeferredQueue::new());
+
+static PROBE_REQUESTED: OnceLock<Notify> = OnceLock::new();
+
+type DeferredTask = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Payload of the `connectivity-changed` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectivityEvent {
+    pub online: bool,
+    /// Deferred tasks waiting for the connection (0 once back online)
+    pub queued: usize,
+}
+
+/// Work waiting for the connection, deduplicated by key
+struct DeferredQueue {
+    tasks: VecDeque<(String, DeferredTask)>,
+}
+
+impl DeferredQueue {
+    const fn new() -> Self {
+        DeferredQueue { tasks: VecDeque::new() }
+    }
+
+    /// Queue `task`, replacing any task with the same key
+    fn push(&mut self, key: String, task: DeferredTask) {
+        if let Some(slot) = self.tasks.iter_mut().find(|(existing, _)| *existing == key) {
+            slot.1 = task;
+            return;
+        }
+        if self.tasks.len() >= MAX_DEFERRED {
+            if let Some((dropped, _)) = self.tasks.pop_front() {
+                log_warn("Connectivity", &format!("Offline queue full, dropping {}", dropped));
+            }
+        }
+        self.tasks.push_back((key, task));
+    }
+
+    fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    fn drain(&mut self) -> Vec<(String, DeferredTask)> {
+        self.tasks.drain(..).collect()
+    }
+}
+
+pub fn is_online() -> bool {
+    ONLINE.load(Ordering::Acquire)
+}
+
+/// Current state, in the shape of the `connectivity-changed` payload
+pub fn status() -> ConnectivityEvent {
+    let queued = QUEUE.lock().unwrap().len();
+    ConnectivityEvent { online: is_online(), queued }
+}
+
+/// Fail fast with `MilkError::Offline` while there is no connection
+pub fn ensure_online() -> Result<(), MilkError> {
+    if is_online() {
+        Ok(())
+    } else {
+        Err(MilkError::Offline)
+    }
+}
+
+/// Re-check connectivity now instead of at the next interval
+///
+/// Called when a request fails to connect, so going offline is noticed quickly.
+pub fn request_probe() {
+    PROBE_REQUESTED.get_or_init(Notify::new).notify_one();
+}
+
+/// Run `task` now if online, otherwise once the connection is back
+///
+/// For non-urgent work only. A later task with the same `key` replaces one
+/// still waiting. Returns true when the task was deferred.
+pub fn run_when_online<F, Fut>(key: impl Into<String>, task: F) -> bool
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut queue = QUEUE.lock().unwrap();
+    if is_online() {
+        drop(queue);
+        tauri::async_runtime::spawn(task());
+        return false;
+    }
+    let key = key.into();
+    log_info("Connectivity", &format!("Offline, deferring {}", key));
+    queue.push(key, Box::new(move || Box::pin(task())));
+    true
+}
+
+fn set_online(app: &AppHandle, online: bool) {
+    let mut queue = QUEUE.lock().unwrap();
+    if ONLINE.swap(online, Ordering::AcqRel) == online {
+        return;
+    }
+    let deferred = if online { queue.drain() } else { Vec::new() };
+    let queued = queue.len();
+    drop(queue);
+
+    if online {
+        log_info("Connectivity", &format!("Back online, running {} deferred task(s)", deferred.len()));
+    } else {
+        log_warn("Connectivity", "Network unreachable, switching to offline mode");
+    }
+    for (_, task) in deferred {
+        tauri::async_runtime::spawn(task());
+    }
+    if let Err(e) = app.emit("connectivity-changed", ConnectivityEvent { online, queued }) {
+        log_warn("Connectivity", &format!("Failed to emit connectivity-changed event: {}", e));
+    }
+}
+
+/// URLs the probe sends HEAD requests to: the museum, plus each streaming service that is enabled
+///
+/// Settings are re-read on every probe, so a service turned off is no longer contacted.
+fn probe_urls() -> Vec<&'static str> {
+    let (spotify, youtube) = FileConfigManager::load()
+        .map(|config| (config.spotify_enabled, config.youtube_enabled))
+        .unwrap_or_default();
+    let mut urls = vec![MUSEUM_PROBE_URL];
+    if spotify {
+        urls.push(SPOTIFY_PROBE_URL);
+    }
+    if youtube {
+        urls.push(YOUTUBE_PROBE_URL);
+    }
+    urls
+}
+
+/// True if any probe URL answers in time
+///
+/// Goes through the shared HTTP client, so a configured proxy is used the same
+/// way real requests use it; any HTTP status counts as reachable.
+async fn probe() -> bool {
+    let client = crate::api_client::http_client();
+    let mut attempts = tokio::task::JoinSet::new();
+    for url in probe_urls() {
+        attempts.spawn(client.head(url).timeout(PROBE_TIMEOUT).send());
+    }
+    while let Some(result) = attempts.join_next().await {
+        if matches!(result, Ok(Ok(_))) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Probe connectivity for the lifetime of the app
+pub fn start(app: AppHandle) {
+    let requested = PROBE_REQUESTED.get_or_init(Notify::new);
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let online = probe().await;
+            set_online(&app, online);
+            let interval = if online { PROBE_INTERVAL } else { OFFLINE_PROBE_INTERVAL };
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = requested.notified() => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    fn counting_task(counter: &Arc<AtomicUsize>, amount: usize) -> DeferredTask {
+        let counter = counter.clone();
+        Box::new(move || {
+            Box::pin(async move {
+                counter.fetch_add(amount, Ordering::SeqCst);
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_deferred_queue_replaces_by_key() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut queue = DeferredQueue::new();
+        queue.push("artwork:1".to_string(), counting_task(&counter, 1));
+        queue.push("artwork:2".to_string(), counting_task(&counter, 10));
+        queue.push("artwork:1".to_string(), counting_task(&counter, 100));
+        assert_eq!(queue.len(), 2);
+
+        let drained = queue.drain();
+        assert_eq!(drained.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>(), ["artwork:1", "artwork:2"]);
+        assert_eq!(queue.len(), 0);
+        for (_, task) in drained {
+            task().await;
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 110);
+    }
+
+    #[test]
+    fn test_deferred_queue_drops_oldest_when_full() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut queue = DeferredQueue::new();
+        for i in 0..=MAX_DEFERRED {
+            queue.push(format!("task:{}", i), counting_task(&counter, 1));
+        }
+        assert_eq!(queue.len(), MAX_DEFERRED);
+        assert_eq!(queue.drain()[0].0, "task:1");
+    }
+}
@@ -0,0 +1,371 @@
+// App data export and import
+// Packs config, playlists, play history, ratings and the skin list into a
+// single zip for moving to another machine.
+// Credentials live in the OS keyring and are never exported.
+
+use crate::config::{self, parse_config, Config, ConfigError, ConfigManager, FileConfigManager};
+use crate::kv_store::{KvError, KvStore};
+use crate::play_history::{self, PlayStats};
+use crate::playlist::Playlist;
+use crate::ratings::{self, TrackRating, MAX_STARS, RATING_KEY_PREFIX};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use zip::write::{FileOptions, ZipWriter};
+use zip::ZipArchive;
+
+/// Bumped when the bundle layout changes incompatibly
+pub const BUNDLE_FORMAT: u32 = 2;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const CONFIG_ENTRY: &str = "config.json";
+/// Play stats by file path; added in format 2
+const PLAY_HISTORY_ENTRY: &str = "play_history.json";
+/// Ratings by file path; added in format 2
+const RATINGS_ENTRY: &str = "ratings.json";
+const PLAYLISTS_PREFIX: &str = "playlists/";
+
+#[derive(Error, Debug)]
+pub enum BundleError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Store error: {0}")]
+    Store(#[from] KvError),
+    #[error("Not a milk data bundle: {0}")]
+    InvalidBundle(String),
+}
+
+/// Describes a bundle's contents; written first so imports can reject unknown formats
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BundleManifest {
+    pub format: u32,
+    pub app_version: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+    pub playlist_count: usize,
+    #[serde(default)]
+    pub play_history_count: usize,
+    #[serde(default)]
+    pub rating_count: usize,
+    /// File names of installed skins; the skin files themselves are not bundled
+    pub skins: Vec<String>,
+}
+
+/// What an import changed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    pub config_imported: bool,
+    pub playlists_imported: usize,
+    /// Files whose play stats were merged in
+    pub play_history_imported: usize,
+    pub ratings_imported: usize,
+    /// Skins listed in the bundle that are not installed on this machine
+    pub missing_skins: Vec<String>,
+}
+
+/// Where app data lives on this machine
+pub struct DataPaths {
+    pub config: PathBuf,
+    pub playlists_dir: PathBuf,
+    pub skins_dir: Option<PathBuf>,
+}
+
+impl DataPaths {
+    pub fn resolve() -> Result<Self, BundleError> {
+        let playlists_dir = dirs::data_local_dir()
+            .map(|d| d.join("milk").join("playlists"))
+            .ok_or(BundleError::Config(ConfigError::InvalidPath))?;
+        Ok(Self {
+            config: FileConfigManager::get_config_path()?,
+            playlists_dir,
+            skins_dir: crate::skin_museum::get_skin_library_dir(),
+        })
+    }
+}
+
+/// Write config, playlists, play history, ratings and the skin list to a zip at `dest`
+pub fn export_bundle(dest: &Path, paths: &DataPaths, store: &KvStore) -> Result<BundleManifest, BundleError> {
+    let config = if paths.config.exists() {
+        parse_config(&fs::read_to_string(&paths.config)?)?
+    } else {
+        FileConfigManager::get_default()
+    };
+    let playlists = read_playlist_files(&paths.playlists_dir)?;
+    let play_history: BTreeMap<String, PlayStats> = play_history::all_play_stats(store).into_iter().collect();
+    let ratings: BTreeMap<String, TrackRating> = store.entries_with_prefix(RATING_KEY_PREFIX).into_iter().collect();
+    let manifest = BundleManifest {
+        format: BUNDLE_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now(),
+        playlist_count: playlists.len(),
+        play_history_count: play_history.len(),
+        rating_count: ratings.len(),
+        skins: list_skins(paths.skins_dir.as_deref()),
+    };
+
+    let mut zip = ZipWriter::new(File::create(dest)?);
+    let options = || FileOptions::<()>::default();
+    zip.start_file(MANIFEST_ENTRY, options())?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    zip.start_file(CONFIG_ENTRY, options())?;
+    zip.write_all(serde_json::to_string_pretty(&config)?.as_bytes())?;
+    zip.start_file(PLAY_HISTORY_ENTRY, options())?;
+    zip.write_all(serde_json::to_string_pretty(&play_history)?.as_bytes())?;
+    zip.start_file(RATINGS_ENTRY, options())?;
+    zip.write_all(serde_json::to_string_pretty(&ratings)?.as_bytes())?;
+    for (name, contents) in playlists {
+        zip.start_file(format!("{}{}", PLAYLISTS_PREFIX, name), options())?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(manifest)
+}
+
+/// Restore a bundle written by `export_bundle`
+///
+/// The whole bundle is validated before anything is written. The imported
+/// config replaces the current one (as a new revision, so open windows see a
+/// normal change); playlists are added or overwritten by id. Play history is
+/// merged so importing twice doesn't double counts, and ratings overwrite
+/// the local ones for the same file.
+pub fn import_bundle(src: &Path, paths: &DataPaths, store: &KvStore) -> Result<ImportSummary, BundleError> {
+    let mut archive = ZipArchive::new(File::open(src)?)?;
+
+    let manifest: BundleManifest = serde_json::from_str(&read_entry(&mut archive, MANIFEST_ENTRY)?)?;
+    if manifest.format > BUNDLE_FORMAT {
+        return Err(BundleError::InvalidBundle(format!(
+            "format {} is newer than this version of milk supports",
+            manifest.format
+        )));
+    }
+
+    let config: Option<Config> = match read_entry(&mut archive, CONFIG_ENTRY) {
+        Ok(contents) => Some(parse_config(&contents)?),
+        Err(BundleError::Zip(zip::result::ZipError::FileNotFound)) => None,
+        Err(e) => return Err(e),
+    };
+    let play_history: BTreeMap<String, PlayStats> = read_optional_entry(&mut archive, PLAY_HISTORY_ENTRY)?;
+    let ratings: BTreeMap<String, TrackRating> = read_optional_entry(&mut archive, RATINGS_ENTRY)?;
+    if let Some((path, rating)) = ratings.iter().find(|(_, rating)| rating.stars > MAX_STARS) {
+        return Err(BundleError::InvalidBundle(format!("invalid rating of {} stars for {}", rating.stars, path)));
+    }
+
+    let mut playlists = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.name().starts_with(PLAYLISTS_PREFIX) || file.is_dir() {
+            continue;
+        }
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let playlist: Playlist = serde_json::from_str(&contents)?;
+        if !is_safe_id(&playlist.id) {
+            return Err(BundleError::InvalidBundle(format!("invalid playlist id '{}'", playlist.id)));
+        }
+        playlists.push((playlist.id, contents));
+    }
+
+    let mut summary = ImportSummary::default();
+    if let Some(mut config) = config {
+        config.revision = fs::read_to_string(&paths.config)
+            .ok()
+            .and_then(|current| parse_config(&current).ok())
+            .map_or(0, |current| current.revision);
+        config::save_with_revision(&paths.config, None, &config)?;
+        summary.config_imported = true;
+    }
+    fs::create_dir_all(&paths.playlists_dir)?;
+    for (id, contents) in playlists {
+        fs::write(paths.playlists_dir.join(format!("{}.json", id)), contents)?;
+        summary.playlists_imported += 1;
+    }
+    for (path, stats) in play_history {
+        play_history::merge_play_stats(store, &path, stats)?;
+        summary.play_history_imported += 1;
+    }
+    for (path, rating) in ratings {
+        store.set(&ratings::rating_key(&path), &rating)?;
+        summary.ratings_imported += 1;
+    }
+    let installed = list_skins(paths.skins_dir.as_deref());
+    summary.missing_skins = manifest
+        .skins
+        .into_iter()
+        .filter(|skin| !installed.contains(skin))
+        .collect();
+    Ok(summary)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<String, BundleError> {
+    let mut contents = String::new();
+    archive.by_name(name)?.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// A JSON entry, or its default when the bundle predates it
+fn read_optional_entry<T: DeserializeOwned + Default>(archive: &mut ZipArchive<File>, name: &str) -> Result<T, BundleError> {
+    match read_entry(archive, name) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(BundleError::Zip(zip::result::ZipError::FileNotFound)) => Ok(T::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Playlist files as (file name, contents), skipping anything that isn't a valid playlist
+fn read_playlist_files(dir: &Path) -> Result<Vec<(String, String)>, BundleError> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)?;
+        if serde_json::from_str::<Playlist>(&contents).is_err() {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            files.push((name.to_string(), contents));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn list_skins(dir: Option<&Path>) -> Vec<String> {
+    let mut skins: Vec<String> = dir
+        .and_then(|d| fs::read_dir(d).ok())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .collect();
+    skins.sort();
+    skins
+}
+
+/// Playlist ids become file names, so they must not contain path components
+pub(crate) fn is_safe_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn paths(root: &Path) -> DataPaths {
+        DataPaths {
+            config: root.join("config.json"),
+            playlists_dir: root.join("playlists"),
+            skins_dir: Some(root.join("skins")),
+        }
+    }
+
+    fn playlist_json(id: &str) -> String {
+        serde_json::json!({
+            "id": id,
+            "name": "Road trip",
+            "tracks": [],
+            "created_at": 0,
+            "modified_at": 0
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = TempDir::new().unwrap();
+        let source_paths = paths(source.path());
+        let mut config = FileConfigManager::get_default();
+        config.volume = 0.3;
+        config::save_with_revision(&source_paths.config, None, &config).unwrap();
+        fs::create_dir_all(&source_paths.playlists_dir).unwrap();
+        fs::write(source_paths.playlists_dir.join("abc.json"), playlist_json("abc")).unwrap();
+        fs::create_dir_all(source.path().join("skins")).unwrap();
+        fs::write(source.path().join("skins").join("base.wsz"), b"skin").unwrap();
+        let source_store = KvStore::in_memory();
+        let played_at = DateTime::from_timestamp_millis(1_700_000_000_000).unwrap();
+        play_history::record_play(&source_store, "/music/a.mp3", played_at).unwrap();
+        play_history::record_play(&source_store, "/music/a.mp3", played_at).unwrap();
+        let rating = TrackRating { stars: 4, loved: true };
+        ratings::set_rating(&source_store, "/music/b.flac", rating).unwrap();
+
+        let bundle = source.path().join("milk-data.zip");
+        let manifest = export_bundle(&bundle, &source_paths, &source_store).unwrap();
+        assert_eq!(manifest.playlist_count, 1);
+        assert_eq!((manifest.play_history_count, manifest.rating_count), (1, 1));
+        assert_eq!(manifest.skins, vec!["base.wsz".to_string()]);
+
+        let target = TempDir::new().unwrap();
+        let target_paths = paths(target.path());
+        let target_store = KvStore::in_memory();
+        let summary = import_bundle(&bundle, &target_paths, &target_store).unwrap();
+        assert!(summary.config_imported);
+        assert_eq!(summary.playlists_imported, 1);
+        assert_eq!((summary.play_history_imported, summary.ratings_imported), (1, 1));
+        assert_eq!(summary.missing_skins, vec!["base.wsz".to_string()]);
+
+        let imported = parse_config(&fs::read_to_string(&target_paths.config).unwrap()).unwrap();
+        assert_eq!(imported.volume, 0.3);
+        assert!(target_paths.playlists_dir.join("abc.json").exists());
+        let stats = play_history::get_play_stats(&target_store, "/music/a.mp3");
+        assert_eq!((stats.count, stats.last_played), (2, played_at.timestamp_millis()));
+        assert_eq!(ratings::get_rating(&target_store, "/music/b.flac"), rating);
+
+        // Importing again doesn't count the same plays twice
+        import_bundle(&bundle, &target_paths, &target_store).unwrap();
+        assert_eq!(play_history::get_play_stats(&target_store, "/music/a.mp3").count, 2);
+    }
+
+    #[test]
+    fn test_rejects_unsafe_playlist_id() {
+        let dir = TempDir::new().unwrap();
+        let bundle = dir.path().join("bad.zip");
+        let mut zip = ZipWriter::new(File::create(&bundle).unwrap());
+        let manifest = BundleManifest {
+            format: BUNDLE_FORMAT,
+            app_version: "0.0.0".to_string(),
+            created_at: Utc::now(),
+            playlist_count: 1,
+            play_history_count: 0,
+            rating_count: 0,
+            skins: Vec::new(),
+        };
+        zip.start_file::<_, ()>(MANIFEST_ENTRY, FileOptions::default()).unwrap();
+        zip.write_all(serde_json::to_string(&manifest).unwrap().as_bytes()).unwrap();
+        zip.start_file::<_, ()>("playlists/evil.json", FileOptions::default()).unwrap();
+        zip.write_all(playlist_json("../../evil").as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let target = paths(dir.path());
+        assert!(matches!(import_bundle(&bundle, &target, &KvStore::in_memory()), Err(BundleError::InvalidBundle(_))));
+        assert!(!target.playlists_dir.exists());
+    }
+
+    #[test]
+    fn test_rejects_non_bundle_zip() {
+        let dir = TempDir::new().unwrap();
+        let bundle = dir.path().join("other.zip");
+        let mut zip = ZipWriter::new(File::create(&bundle).unwrap());
+        zip.start_file::<_, ()>("readme.txt", FileOptions::default()).unwrap();
+        zip.write_all(b"hello").unwrap();
+        zip.finish().unwrap();
+
+        assert!(import_bundle(&bundle, &paths(dir.path()), &KvStore::in_memory()).is_err());
+    }
+}
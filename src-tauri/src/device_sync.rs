@@ -0,0 +1,548 @@
+// Device sync
+// Copies playlists and tracks into a folder: a USB player, an SD card or a
+// phone mounted over MTP. Files are laid out by a tag pattern, transcoding
+// rules shrink lossless or high-bitrate files for the device, and each synced
+// playlist is written next to them as an M3U8 with relative paths. A manifest
+// in the target folder records what every file was made from, so a later
+// sync only writes what changed and removes what is no longer selected;
+// files the manifest doesn't know about are never touched. The manifest lives
+// on the device, so entries that aren't plain relative paths are ignored.
+
+use crate::error_recovery::ErrorRecovery;
+use crate::media_editor::audio_ops::{self, AudioFormat, MAX_BITRATE_KBPS, MIN_BITRATE_KBPS};
+use crate::metadata::MetadataExtractor;
+use crate::tag_pattern::{self, PatternError, TagPattern};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+use thiserror::Error;
+
+/// Manifest of synced files, kept in the target folder
+pub const MANIFEST_NAME: &str = ".milk-sync.json";
+
+pub const DEFAULT_PATTERN: &str = "{artist}/{album}/{track:02} - {title}";
+
+#[derive(Error, Debug)]
+pub enum SyncError {
+    #[error("Sync failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Pattern(#[from] PatternError),
+    #[error("Invalid transcoding rule: {0}")]
+    InvalidRule(String),
+    #[error("Not enough space on {target}: {needed} bytes needed")]
+    DiskFull { target: String, needed: u64 },
+}
+
+/// Convert matching files instead of copying them
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscodeRule {
+    /// Source extensions the rule covers, e.g. ["flac", "wav"]
+    pub extensions: Vec<String>,
+    /// Only files above this bitrate are converted; every matching file when unset
+    #[serde(default)]
+    pub above_kbps: Option<u32>,
+    pub format: AudioFormat,
+    pub bitrate_kbps: u32,
+}
+
+impl TranscodeRule {
+    /// Whether a file with `extension` is converted; its bitrate is only looked up when the rule needs it
+    fn applies(&self, extension: &str, bitrate_kbps: impl FnOnce() -> Option<u32>) -> bool {
+        self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            && self.above_kbps.is_none_or(|limit| bitrate_kbps().is_some_and(|bitrate| bitrate > limit))
+    }
+
+    /// Identifies the output in the manifest, so changing a rule re-converts its files
+    fn output(&self) -> String {
+        format!("{}@{}k", self.format.extension(), self.bitrate_kbps)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncOptions {
+    /// Tag pattern for paths on the device; files missing a tag keep their file name
+    pub pattern: String,
+    /// The first matching rule applies; other files are copied as they are
+    pub transcode: Vec<TranscodeRule>,
+    /// Remove files and playlists an earlier sync wrote that are no longer selected
+    pub remove_unselected: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            pattern: DEFAULT_PATTERN.to_string(),
+            transcode: Vec::new(),
+            remove_unselected: true,
+        }
+    }
+}
+
+impl SyncOptions {
+    pub fn validate(&self) -> Result<(), SyncError> {
+        TagPattern::parse(&self.pattern)?;
+        for rule in &self.transcode {
+            if rule.format.is_lossless() {
+                return Err(SyncError::InvalidRule(format!("{} is not a space-saving format", rule.format.extension())));
+            }
+            if !(MIN_BITRATE_KBPS..=MAX_BITRATE_KBPS).contains(&rule.bitrate_kbps) {
+                return Err(SyncError::InvalidRule(format!(
+                    "bitrate must be between {} and {} kbps, got {}",
+                    MIN_BITRATE_KBPS, MAX_BITRATE_KBPS, rule.bitrate_kbps
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a synced file was made from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct SyncedFile {
+    #[serde(with = "crate::path_codec")]
+    source: PathBuf,
+    size: u64,
+    /// Source modification time, Unix seconds
+    modified: i64,
+    /// "copy", or the format and bitrate it was converted to
+    output: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// By path relative to the target, with `/` separators
+    files: BTreeMap<String, SyncedFile>,
+    playlists: BTreeSet<String>,
+}
+
+impl Manifest {
+    /// The manifest in `target`, without entries that would point outside it
+    fn load(target: &Path) -> Self {
+        let mut manifest: Self = fs::read(target.join(MANIFEST_NAME))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        manifest.files.retain(|file, _| is_plain_relative(file));
+        manifest.playlists.retain(|playlist| is_plain_relative(playlist) && !playlist.contains('/'));
+        manifest
+    }
+
+    fn save(&self, target: &Path) -> Result<(), SyncError> {
+        let data = serde_json::to_vec_pretty(self).map_err(std::io::Error::from)?;
+        fs::write(target.join(MANIFEST_NAME), data)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum SyncAction {
+    Unchanged,
+    Copy,
+    Transcode { format: AudioFormat, bitrate_kbps: u32 },
+}
+
+#[derive(Debug, Clone)]
+struct SyncItem {
+    target: String,
+    action: SyncAction,
+    stamp: SyncedFile,
+    estimated_bytes: u64,
+}
+
+/// Work for one sync, worked out before anything is written
+#[derive(Debug, Default)]
+pub struct SyncPlan {
+    items: Vec<SyncItem>,
+    /// Manifest entries no longer selected
+    removals: Vec<String>,
+    /// Playlist name and its tracks' sources, in order
+    playlists: Vec<(String, Vec<PathBuf>)>,
+}
+
+impl SyncPlan {
+    /// Bytes the sync writes, less what it removes
+    pub fn required_bytes(&self, manifest_sizes: impl Fn(&str) -> u64) -> u64 {
+        let written: u64 = self.items.iter().filter(|item| item.action != SyncAction::Unchanged).map(|item| item.estimated_bytes).sum();
+        let freed: u64 = self.removals.iter().map(|target| manifest_sizes(target)).sum();
+        written.saturating_sub(freed)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub target: String,
+    pub copied: usize,
+    pub transcoded: usize,
+    /// Already on the device from an earlier sync
+    pub unchanged: usize,
+    pub removed: usize,
+    /// Playlist files written
+    pub playlists: usize,
+    pub bytes_written: u64,
+    /// Files that could not be written, with the reason
+    pub failed: Vec<String>,
+}
+
+/// Plan syncing `playlists` and the extra `tracks` into `target`
+///
+/// `bitrate_kbps` looks up a source's bitrate for rules with a threshold.
+/// Files that no longer exist are left out.
+pub fn plan_sync(
+    target: &Path,
+    playlists: Vec<(String, Vec<PathBuf>)>,
+    tracks: &[PathBuf],
+    options: &SyncOptions,
+    extractor: &MetadataExtractor,
+    bitrate_kbps: impl Fn(&Path) -> Option<u32>,
+) -> Result<SyncPlan, SyncError> {
+    options.validate()?;
+    let pattern = TagPattern::parse(&options.pattern)?;
+    let manifest = Manifest::load(target);
+
+    let mut sources: Vec<&PathBuf> = Vec::new();
+    let mut seen = HashSet::new();
+    for source in playlists.iter().flat_map(|(_, entries)| entries).chain(tracks) {
+        if seen.insert(source) && source.is_file() {
+            sources.push(source);
+        }
+    }
+
+    let mut plan = SyncPlan::default();
+    // Lowercased, since device file systems are mostly case-insensitive
+    let mut taken = HashSet::new();
+    for source in sources {
+        let metadata = fs::metadata(source)?;
+        let extension = source.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let rule = options.transcode.iter().find(|rule| rule.applies(&extension, || bitrate_kbps(source)));
+        let tags = extractor.extract(source).ok();
+        let output_extension = rule.map(|rule| rule.format.extension().to_string()).unwrap_or(extension);
+
+        let relative = tags
+            .as_ref()
+            .and_then(|tags| pattern.render(tags, &output_extension).ok())
+            .unwrap_or_else(|| {
+                let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                PathBuf::from(format!("{}.{}", tag_pattern::sanitize(&stem), output_extension))
+            });
+        let target_path = unique_target(&relative, &mut taken, |file| {
+            !manifest.files.contains_key(file) && target.join(file).exists()
+        });
+
+        let stamp = SyncedFile {
+            source: source.clone(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs() as i64)
+                .unwrap_or(0),
+            output: rule.map(TranscodeRule::output).unwrap_or_else(|| "copy".to_string()),
+        };
+        let (action, estimated_bytes) = match rule {
+            _ if manifest.files.get(&target_path) == Some(&stamp) && target.join(&target_path).is_file() => {
+                (SyncAction::Unchanged, 0)
+            }
+            Some(rule) => {
+                let duration = tags.as_ref().and_then(|tags| tags.duration).map(u64::from);
+                // kbps * 125 is bytes per second
+                let estimate = duration.map(|secs| secs * rule.bitrate_kbps as u64 * 125).unwrap_or(stamp.size);
+                (SyncAction::Transcode { format: rule.format, bitrate_kbps: rule.bitrate_kbps }, estimate)
+            }
+            None => (SyncAction::Copy, stamp.size),
+        };
+        plan.items.push(SyncItem { target: target_path, action, stamp, estimated_bytes });
+    }
+
+    if options.remove_unselected {
+        let planned: HashSet<&str> = plan.items.iter().map(|item| item.target.as_str()).collect();
+        plan.removals = manifest.files.keys().filter(|target| !planned.contains(target.as_str())).cloned().collect();
+    }
+    plan.playlists = playlists;
+    Ok(plan)
+}
+
+/// Whether a manifest entry is a `/`-separated path of plain names, staying inside the target
+fn is_plain_relative(file: &str) -> bool {
+    !file.contains(['\\', ':'])
+        && file.split('/').all(|part| !part.is_empty())
+        && Path::new(file).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// `relative` as a `/`-separated string, numbered if another synced file has
+/// the name or an `untracked` file of the user's sits there
+fn unique_target(relative: &Path, taken: &mut HashSet<String>, untracked: impl Fn(&str) -> bool) -> String {
+    let joined = |path: &Path| path.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+    let mut target = joined(relative);
+    let mut n = 2;
+    while !taken.insert(target.to_lowercase()) || untracked(&target) {
+        let stem = relative.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let extension = relative.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        target = joined(&relative.with_file_name(format!("{} ({}){}", stem, n, extension)));
+        n += 1;
+    }
+    target
+}
+
+/// Carry out `plan` in `target`
+///
+/// Fails before writing anything if the target lacks space. Files that fail
+/// are listed in the report and the rest are synced. `progress` gets (files
+/// done, total files). Stops early once `cancel` is set; the manifest is
+/// saved either way, so the next sync picks up where this one stopped.
+pub fn run_sync(
+    target: &Path,
+    plan: &SyncPlan,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<SyncReport, SyncError> {
+    fs::create_dir_all(target)?;
+    let mut manifest = Manifest::load(target);
+    let needed = plan.required_bytes(|file| manifest.files.get(file).map(|synced| synced.size).unwrap_or(0));
+    if !ErrorRecovery::has_free_space(target, needed) {
+        return Err(SyncError::DiskFull { target: target.display().to_string(), needed });
+    }
+
+    let mut report = SyncReport { target: target.to_string_lossy().to_string(), ..Default::default() };
+    for removed in &plan.removals {
+        let path = target.join(removed);
+        match fs::remove_file(&path) {
+            Ok(()) => report.removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                report.failed.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        }
+        manifest.files.remove(removed);
+        remove_empty_parents(target, &path);
+    }
+
+    let total = plan.items.len() as u64;
+    for (done, item) in plan.items.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        progress(done as u64, total);
+        let path = target.join(&item.target);
+        match &item.action {
+            SyncAction::Unchanged => {
+                report.unchanged += 1;
+                continue;
+            }
+            action => match write_file(&item.stamp.source, &path, action, cancel) {
+                Ok(bytes) => {
+                    report.bytes_written += bytes;
+                    match action {
+                        SyncAction::Transcode { .. } => report.transcoded += 1,
+                        _ => report.copied += 1,
+                    }
+                    manifest.files.insert(item.target.clone(), item.stamp.clone());
+                }
+                Err(_) if cancel.load(Ordering::Relaxed) => break,
+                Err(e) => report.failed.push(format!("{}: {}", item.stamp.source.display(), e)),
+            },
+        }
+    }
+
+    if !cancel.load(Ordering::Relaxed) {
+        report.playlists = write_playlists(target, plan, &mut manifest, &mut report.failed);
+        progress(total, total);
+    }
+    manifest.save(target)?;
+    Ok(report)
+}
+
+/// Copy or convert one file, through a hidden temporary next to it; returns the bytes written
+fn write_file(source: &Path, path: &Path, action: &SyncAction, cancel: &AtomicBool) -> Result<u64, String> {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    // Same extension, so FFmpeg picks the right container
+    let partial = parent.join(format!(".{}", path.file_name().unwrap_or_default().to_string_lossy()));
+    let result = match action {
+        SyncAction::Transcode { format, bitrate_kbps } => audio_ops::convert_audio(
+            &source.to_string_lossy(),
+            &partial.to_string_lossy(),
+            *format,
+            Some(*bitrate_kbps),
+            cancel,
+            |_| {},
+        ),
+        _ => fs::copy(source, &partial).map(|_| ()).map_err(|e| e.to_string()),
+    };
+    let written = result
+        .and_then(|()| fs::metadata(&partial).map(|m| m.len()).map_err(|e| e.to_string()))
+        .and_then(|bytes| fs::rename(&partial, path).map(|()| bytes).map_err(|e| e.to_string()));
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    written
+}
+
+/// Write each playlist as an M3U8 of paths relative to the target, and
+/// remove playlist files an earlier sync wrote that aren't part of this one
+fn write_playlists(target: &Path, plan: &SyncPlan, manifest: &mut Manifest, failed: &mut Vec<String>) -> usize {
+    let synced: HashMap<&Path, &str> = plan
+        .items
+        .iter()
+        .filter(|item| manifest.files.contains_key(&item.target))
+        .map(|item| (item.stamp.source.as_path(), item.target.as_str()))
+        .collect();
+    let mut written = BTreeSet::new();
+    for (name, entries) in &plan.playlists {
+        let file_name = format!("{}.m3u8", tag_pattern::sanitize(name));
+        if !manifest.playlists.contains(&file_name) && target.join(&file_name).exists() {
+            failed.push(format!("{}: a file the sync didn't write is in the way", file_name));
+            continue;
+        }
+        let mut content = String::from("#EXTM3U\n");
+        for target in entries.iter().filter_map(|entry| synced.get(entry.as_path())) {
+            content.push_str(target);
+            content.push('\n');
+        }
+        match fs::write(target.join(&file_name), content) {
+            Ok(()) => {
+                written.insert(file_name);
+            }
+            Err(e) => failed.push(format!("{}: {}", file_name, e)),
+        }
+    }
+    for stale in manifest.playlists.difference(&written) {
+        let _ = fs::remove_file(target.join(stale));
+    }
+    let count = written.len();
+    manifest.playlists = written;
+    count
+}
+
+/// Remove the folders above `path` that are now empty, up to `target`
+fn remove_empty_parents(target: &Path, path: &Path) {
+    for folder in path.ancestors().skip(1).take_while(|folder| *folder != target && folder.starts_with(target)) {
+        if fs::remove_dir(folder).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn library(dir: &Path, names: &[&str]) -> Vec<PathBuf> {
+        let music = dir.join("music");
+        fs::create_dir_all(&music).unwrap();
+        names
+            .iter()
+            .map(|name| {
+                let path = music.join(name);
+                fs::write(&path, name.as_bytes()).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn sync(target: &Path, playlists: Vec<(String, Vec<PathBuf>)>, tracks: &[PathBuf]) -> SyncReport {
+        let plan = plan_sync(target, playlists, tracks, &SyncOptions::default(), &MetadataExtractor::new(), |_| None).unwrap();
+        run_sync(target, &plan, &AtomicBool::new(false), |_, _| {}).unwrap()
+    }
+
+    #[test]
+    fn test_delta_sync() {
+        let dir = TempDir::new().unwrap();
+        let files = library(dir.path(), &["a.mp3", "b.mp3", "c.ogg"]);
+        let target = dir.path().join("device");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("own.txt"), b"not ours").unwrap();
+
+        let report = sync(&target, vec![("Road Trip".to_string(), files[..2].to_vec())], &files[2..]);
+        assert_eq!((report.copied, report.unchanged, report.playlists), (3, 0, 1));
+        // Untagged files keep their names
+        assert_eq!(fs::read(target.join("c.ogg")).unwrap(), b"c.ogg");
+        assert_eq!(fs::read_to_string(target.join("Road Trip.m3u8")).unwrap(), "#EXTM3U\na.mp3\nb.mp3\n");
+
+        fs::write(&files[1], b"retagged").unwrap();
+        let report = sync(&target, vec![("Road Trip".to_string(), files[..2].to_vec())], &[]);
+        assert_eq!((report.copied, report.unchanged, report.removed), (1, 1, 1));
+        assert!(!target.join("c.ogg").exists());
+        assert_eq!(fs::read(target.join("b.mp3")).unwrap(), b"retagged");
+
+        let report = sync(&target, vec![], &files[..1]);
+        assert_eq!((report.unchanged, report.removed, report.playlists), (1, 1, 0));
+        assert!(!target.join("Road Trip.m3u8").exists());
+        assert!(target.join("own.txt").exists());
+    }
+
+    #[test]
+    fn test_foreign_files_are_left_alone() {
+        let dir = TempDir::new().unwrap();
+        let files = library(dir.path(), &["a.mp3"]);
+        let target = dir.path().join("device");
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("a.mp3"), b"the user's own").unwrap();
+        let outside = dir.path().join("outside.mp3");
+        fs::write(&outside, b"keep").unwrap();
+        let stamp = r#"{"source":"x","size":1,"modified":0,"output":"copy"}"#;
+        let manifest = format!(r#"{{"files":{{"../outside.mp3":{0},"{1}":{0}}},"playlists":["../x.m3u8"]}}"#, stamp, outside.display());
+        fs::write(target.join(MANIFEST_NAME), manifest.replace('\\', "/")).unwrap();
+
+        let report = sync(&target, vec![], &files);
+        assert_eq!((report.copied, report.removed), (1, 0));
+        assert_eq!(fs::read(target.join("a.mp3")).unwrap(), b"the user's own");
+        assert_eq!(fs::read(target.join("a (2).mp3")).unwrap(), b"a.mp3");
+        assert!(outside.exists());
+        assert!(!is_plain_relative("a/../b.mp3"));
+        assert!(!is_plain_relative("C:/music/a.mp3"));
+        assert!(is_plain_relative("Artist/Album/01 - a.mp3"));
+    }
+
+    #[test]
+    fn test_transcode_rules() {
+        let rule = TranscodeRule { extensions: vec!["flac".into()], above_kbps: Some(320), format: AudioFormat::Mp3, bitrate_kbps: 256 };
+        assert!(rule.applies("flac", || Some(900)));
+        assert!(!rule.applies("flac", || Some(256)));
+        assert!(!rule.applies("mp3", || Some(900)));
+
+        let dir = TempDir::new().unwrap();
+        let files = library(dir.path(), &["song.flac", "other.flac"]);
+        let options = SyncOptions { transcode: vec![rule], ..Default::default() };
+        let lossless = |path: &Path| (path.file_stem().unwrap() == "song").then_some(900);
+        let plan = plan_sync(&dir.path().join("device"), vec![], &files, &options, &MetadataExtractor::new(), lossless).unwrap();
+        let targets: Vec<_> = plan.items.iter().map(|item| (item.target.as_str(), &item.action)).collect();
+        assert_eq!(
+            targets,
+            [("song.mp3", &SyncAction::Transcode { format: AudioFormat::Mp3, bitrate_kbps: 256 }), ("other.flac", &SyncAction::Copy)]
+        );
+
+        let lossless_target = SyncOptions {
+            transcode: vec![TranscodeRule { extensions: vec!["wav".into()], above_kbps: None, format: AudioFormat::Flac, bitrate_kbps: 256 }],
+            ..Default::default()
+        };
+        assert!(matches!(lossless_target.validate(), Err(SyncError::InvalidRule(_))));
+    }
+
+    #[test]
+    fn test_unique_targets_and_space() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_target(Path::new("A/b.mp3"), &mut taken, |_| false), "A/b.mp3");
+        assert_eq!(unique_target(Path::new("a/B.mp3"), &mut taken, |_| false), "a/B (2).mp3");
+        assert_eq!(unique_target(Path::new("c.mp3"), &mut taken, |file| file == "c.mp3"), "c (2).mp3");
+
+        let dir = TempDir::new().unwrap();
+        let files = library(dir.path(), &["a.mp3"]);
+        let plan = plan_sync(&dir.path().join("device"), vec![], &files, &SyncOptions::default(), &MetadataExtractor::new(), |_| None).unwrap();
+        assert_eq!(plan.required_bytes(|_| 0), 5);
+
+        let mut plan = plan;
+        plan.items[0].estimated_bytes = u64::MAX / 2;
+        assert!(matches!(
+            run_sync(&dir.path().join("device"), &plan, &AtomicBool::new(false), |_, _| {}),
+            Err(SyncError::DiskFull { .. })
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+// DSP chain
+// An ordered list of effects applied to the headless player's output before
+// the volume: preamp gain, balance, mono downmix and stereo widening. This is
+// what the skins' preamp and balance sliders drive. The effects hold no state
+// between buffers, so the chain can be swapped while playing. The windowed
+// player builds the same chain from Web Audio nodes (`src/lib/utils/dspGraph.ts`).
+
+use serde::{Deserialize, Serialize};
+
+/// Preamp range in dB, matching the equalizer's preamp slider
+pub const MAX_PREAMP_DB: f32 = 12.0;
+
+/// Widest stereo expander setting; the side signal is at most doubled
+pub const MAX_EXPANDER_WIDTH: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DspEffect {
+    /// Gain in dB, between `-MAX_PREAMP_DB` and `MAX_PREAMP_DB`
+    Preamp { gain_db: f32 },
+    /// -1 is fully left, 1 fully right; the far side is attenuated, never boosted
+    Balance { position: f32 },
+    /// Both channels play the average of the two
+    MonoDownmix,
+    /// Scales the side (left minus right) signal; 0 is mono, 1 leaves the
+    /// image untouched, up to `MAX_EXPANDER_WIDTH`
+    StereoExpander { width: f32 },
+}
+
+impl DspEffect {
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            DspEffect::Preamp { gain_db } if !(-MAX_PREAMP_DB..=MAX_PREAMP_DB).contains(&gain_db) => {
+                Err(format!("preamp gain must be between -{0} and {0} dB", MAX_PREAMP_DB))
+            }
+            DspEffect::Balance { position } if !(-1.0..=1.0).contains(&position) => {
+                Err("balance must be between -1.0 and 1.0".to_string())
+            }
+            DspEffect::StereoExpander { width } if !(0.0..=MAX_EXPANDER_WIDTH).contains(&width) => {
+                Err(format!("stereo expander width must be between 0.0 and {}", MAX_EXPANDER_WIDTH))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, samples: &mut [f32], channels: usize) {
+        match *self {
+            DspEffect::Preamp { gain_db } => {
+                let gain = 10f32.powf(gain_db / 20.0);
+                samples.iter_mut().for_each(|sample| *sample *= gain);
+            }
+            // The stereo effects leave mono and multichannel audio alone
+            _ if channels != 2 => {}
+            DspEffect::Balance { position } => {
+                let (left, right) = ((1.0 - position).min(1.0), (1.0 + position).min(1.0));
+                for frame in samples.chunks_exact_mut(2) {
+                    frame[0] *= left;
+                    frame[1] *= right;
+                }
+            }
+            DspEffect::MonoDownmix => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let mid = (frame[0] + frame[1]) / 2.0;
+                    frame.fill(mid);
+                }
+            }
+            DspEffect::StereoExpander { width } => {
+                for frame in samples.chunks_exact_mut(2) {
+                    let mid = (frame[0] + frame[1]) / 2.0;
+                    let side = (frame[0] - frame[1]) / 2.0 * width;
+                    frame[0] = mid + side;
+                    frame[1] = mid - side;
+                }
+            }
+        }
+    }
+}
+
+/// Effects in the order they are applied; empty passes audio through untouched
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DspChain {
+    pub effects: Vec<DspEffect>,
+}
+
+impl DspChain {
+    /// Range-check every effect
+    pub fn validate(&self) -> Result<(), String> {
+        self.effects.iter().try_for_each(DspEffect::validate)
+    }
+
+    /// Run interleaved samples through every effect in order
+    pub fn process(&self, samples: &mut [f32], channels: usize) {
+        for effect in &self.effects {
+            effect.apply(samples, channels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(effects: Vec<DspEffect>, mut samples: Vec<f32>, channels: usize) -> Vec<f32> {
+        DspChain { effects }.process(&mut samples, channels);
+        samples.iter().map(|s| (s * 1000.0).round() / 1000.0).collect()
+    }
+
+    #[test]
+    fn test_effects() {
+        use DspEffect::*;
+        assert_eq!(process(vec![], vec![0.5, -0.5], 2), [0.5, -0.5]);
+        assert_eq!(process(vec![Preamp { gain_db: -6.0 }], vec![1.0, -1.0, 0.5], 1), [0.501, -0.501, 0.251]);
+        assert_eq!(process(vec![Balance { position: 0.5 }], vec![0.8, 0.8], 2), [0.4, 0.8]);
+        assert_eq!(process(vec![Balance { position: -1.0 }], vec![0.8, 0.8], 2), [0.8, 0.0]);
+        assert_eq!(process(vec![MonoDownmix], vec![1.0, 0.0, 0.2, 0.4], 2), [0.5, 0.5, 0.3, 0.3]);
+        assert_eq!(process(vec![StereoExpander { width: 2.0 }], vec![0.6, 0.2], 2), [0.8, 0.0]);
+        assert_eq!(process(vec![StereoExpander { width: 0.0 }], vec![0.6, 0.2], 2), [0.4, 0.4]);
+        // Stereo effects skip other layouts
+        assert_eq!(process(vec![Balance { position: 1.0 }, MonoDownmix], vec![0.8, 0.2, 0.4], 3), [0.8, 0.2, 0.4]);
+    }
+
+    #[test]
+    fn test_order_matters() {
+        use DspEffect::*;
+        // Downmixing after a hard right balance averages in the silenced left
+        assert_eq!(process(vec![Balance { position: 1.0 }, MonoDownmix], vec![0.8, 0.4], 2), [0.2, 0.2]);
+        assert_eq!(process(vec![MonoDownmix, Balance { position: 1.0 }], vec![0.8, 0.4], 2), [0.0, 0.6]);
+    }
+
+    #[test]
+    fn test_validate() {
+        use DspEffect::*;
+        assert!(DspChain { effects: vec![Preamp { gain_db: 12.0 }, Balance { position: -1.0 }] }.validate().is_ok());
+        assert!(DspChain { effects: vec![Preamp { gain_db: 12.5 }] }.validate().is_err());
+        assert!(DspChain { effects: vec![Balance { position: f32::NAN }] }.validate().is_err());
+        assert!(DspChain { effects: vec![MonoDownmix, StereoExpander { width: 3.0 }] }.validate().is_err());
+    }
+
+    #[test]
+    fn test_serde_shape() {
+        let chain: DspChain =
+            serde_json::from_str(r#"{"effects":[{"type":"preamp","gain_db":3.0},{"type":"mono_downmix"}]}"#).unwrap();
+        assert_eq!(chain.effects, [DspEffect::Preamp { gain_db: 3.0 }, DspEffect::MonoDownmix]);
+    }
+}
@@ -0,0 +1,247 @@
+// Library duplicate detection
+// Finds byte-identical files by content hash, and re-encodes of the same song by
+// matching title, artist and duration.
+
+use crate::library::Track;
+use crate::metadata::MetadataExtractor;
+use md5::{Digest, Md5};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Durations within this many seconds are treated as the same recording
+pub const DURATION_TOLERANCE_SECS: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateKind {
+    /// Files with identical content
+    ExactContent,
+    /// Different files with the same title, artist and duration
+    SameMetadata,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DuplicateFile {
+    #[serde(with = "crate::path_codec")]
+    pub file_path: PathBuf,
+    pub size_bytes: u64,
+    pub extension: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DuplicateGroup {
+    pub kind: DuplicateKind,
+    /// Content hash, or "artist - title" for metadata matches
+    pub key: String,
+    /// Largest file first
+    pub files: Vec<DuplicateFile>,
+    /// Bytes freed by keeping only the largest file
+    pub reclaimable_bytes: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DuplicateReport {
+    pub files_scanned: usize,
+    pub groups: Vec<DuplicateGroup>,
+    pub reclaimable_bytes: u64,
+}
+
+/// Group duplicate tracks
+///
+/// Only files that share a size are hashed. Each set of exact copies takes
+/// part in metadata matching through the file listed first in its group, so
+/// no file is counted as reclaimable twice.
+pub fn find_duplicates(tracks: &[Track], extractor: &MetadataExtractor) -> DuplicateReport {
+    let sizes: HashMap<&Path, u64> = tracks
+        .iter()
+        .filter_map(|t| fs::metadata(&t.file_path).ok().map(|m| (t.file_path.as_path(), m.len())))
+        .collect();
+
+    let mut by_size: HashMap<u64, Vec<&Track>> = HashMap::new();
+    for track in tracks {
+        if let Some(&size) = sizes.get(track.file_path.as_path()) {
+            by_size.entry(size).or_default().push(track);
+        }
+    }
+    let candidates: Vec<&Track> = by_size.into_values().filter(|g| g.len() > 1).flatten().collect();
+    let hashes: HashMap<&Path, String> = candidates
+        .par_iter()
+        .filter_map(|t| hash_file(&t.file_path).ok().map(|h| (t.file_path.as_path(), h)))
+        .collect();
+
+    let file = |track: &Track| DuplicateFile {
+        file_path: track.file_path.clone(),
+        size_bytes: sizes.get(track.file_path.as_path()).copied().unwrap_or(0),
+        extension: track.extension.clone(),
+    };
+
+    let mut groups = Vec::new();
+    let mut by_hash: BTreeMap<&str, Vec<&Track>> = BTreeMap::new();
+    for track in tracks {
+        if let Some(hash) = hashes.get(track.file_path.as_path()) {
+            by_hash.entry(hash).or_default().push(track);
+        }
+    }
+    // Copies already grouped by content, all but the file each group keeps
+    let mut copies: HashSet<PathBuf> = HashSet::new();
+    for (hash, members) in by_hash {
+        if members.len() > 1 {
+            let exact = group(DuplicateKind::ExactContent, hash.to_string(), members.into_iter().map(file).collect());
+            copies.extend(exact.files.iter().skip(1).map(|f| f.file_path.clone()));
+            groups.push(exact);
+        }
+    }
+
+    let mut by_name: BTreeMap<(String, String), Vec<(u32, &Track)>> = BTreeMap::new();
+    for track in tracks.iter().filter(|t| !copies.contains(&t.file_path)) {
+        let Ok(metadata) = extractor.extract(&track.file_path) else {
+            continue;
+        };
+        let (Some(title), Some(artist), Some(duration)) = (metadata.title, metadata.artist, metadata.duration) else {
+            continue;
+        };
+        by_name
+            .entry((normalize(&artist), normalize(&title)))
+            .or_default()
+            .push((duration, track));
+    }
+    for ((artist, title), mut members) in by_name {
+        members.sort_by_key(|(duration, _)| *duration);
+        for cluster in cluster_by_duration(&members) {
+            groups.push(group(
+                DuplicateKind::SameMetadata,
+                format!("{} - {}", artist, title),
+                cluster.into_iter().map(file).collect(),
+            ));
+        }
+    }
+
+    DuplicateReport {
+        files_scanned: tracks.len(),
+        reclaimable_bytes: groups.iter().map(|g| g.reclaimable_bytes).sum(),
+        groups,
+    }
+}
+
+fn group(kind: DuplicateKind, key: String, mut files: Vec<DuplicateFile>) -> DuplicateGroup {
+    files.sort_by(|a, b| b.size_bytes.cmp(&a.size_bytes).then_with(|| a.file_path.cmp(&b.file_path)));
+    let reclaimable_bytes = files.iter().skip(1).map(|f| f.size_bytes).sum();
+    DuplicateGroup { kind, key, files, reclaimable_bytes }
+}
+
+/// Split tracks sorted by duration wherever neighbours are further apart than the tolerance
+fn cluster_by_duration<'a>(members: &[(u32, &'a Track)]) -> Vec<Vec<&'a Track>> {
+    let mut clusters: Vec<Vec<&Track>> = Vec::new();
+    let mut previous: Option<u32> = None;
+    for &(duration, track) in members {
+        match previous {
+            Some(prev) if duration - prev <= DURATION_TOLERANCE_SECS => clusters.last_mut().unwrap().push(track),
+            _ => clusters.push(vec![track]),
+        }
+        previous = Some(duration);
+    }
+    clusters.into_iter().filter(|c| c.len() > 1).collect()
+}
+
+/// Lowercase, drop punctuation and collapse whitespace
+fn normalize(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Md5::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn track(dir: &Path, name: &str, contents: &[u8]) -> Track {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        Track {
+            id: name.to_string(),
+            file_path: path.clone(),
+            file_name: name.to_string(),
+            extension: path.extension().unwrap().to_string_lossy().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_groups_identical_content() {
+        let dir = TempDir::new().unwrap();
+        let tracks = vec![
+            track(dir.path(), "a.wav", b"same bytes"),
+            track(dir.path(), "copy of a.wav", b"same bytes"),
+            track(dir.path(), "b.wav", b"diff bytes"),
+        ];
+
+        let report = find_duplicates(&tracks, &MetadataExtractor::new());
+        let exact: Vec<_> = report.groups.iter().filter(|g| g.kind == DuplicateKind::ExactContent).collect();
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].files.len(), 2);
+        assert_eq!(exact[0].reclaimable_bytes, 10);
+    }
+
+    #[test]
+    fn test_exact_copies_counted_once() {
+        let dir = TempDir::new().unwrap();
+        let tagged = |name: &str, audio: &[u8]| {
+            let track = track(dir.path(), name, audio);
+            use id3::TagLike;
+            let mut tag = id3::Tag::new();
+            tag.set_title("Song");
+            tag.set_artist("Band");
+            tag.set_duration(200_000);
+            tag.write_to_path(&track.file_path, id3::Version::Id3v24).unwrap();
+            track
+        };
+        let tracks = vec![tagged("a.mp3", b"320k"), tagged("copy.mp3", b"320k"), tagged("b.mp3", b"128")];
+        let size = |track: &Track| fs::metadata(&track.file_path).unwrap().len();
+
+        let report = find_duplicates(&tracks, &MetadataExtractor::new());
+        assert_eq!(report.groups.len(), 2);
+        let same_metadata = report.groups.iter().find(|g| g.kind == DuplicateKind::SameMetadata).unwrap();
+        assert_eq!(same_metadata.files.len(), 2);
+        assert!(same_metadata.files.iter().all(|f| f.file_path != tracks[1].file_path));
+        assert_eq!(report.reclaimable_bytes, size(&tracks[1]) + size(&tracks[2]));
+    }
+
+    #[test]
+    fn test_normalize_ignores_case_and_punctuation() {
+        assert_eq!(normalize("  Don't  Stop!"), normalize("dont stop"));
+    }
+
+    #[test]
+    fn test_cluster_by_duration() {
+        let dir = TempDir::new().unwrap();
+        let a = track(dir.path(), "a.wav", b"a");
+        let b = track(dir.path(), "b.wav", b"b");
+        let c = track(dir.path(), "c.wav", b"c");
+        let clusters = cluster_by_duration(&[(200, &a), (201, &b), (260, &c)]);
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].len(), 2);
+    }
+}
@@ -0,0 +1,206 @@
+// Equalizer presets
+// Ten-band presets kept in the KV store by name, and import from Winamp's EQ
+// library (.eqf) and preset (.q1) files, which share one format: a header
+// followed by fixed-size entries of a name, ten band sliders and a preamp
+// slider. Winamp sliders run from 0 at the top (+12 dB) to 63 at the bottom
+// (-12 dB).
+
+use crate::kv_store::{KvError, KvStore};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// KV store key holding every preset
+pub const PRESETS_KEY: &str = "eq_presets";
+
+/// Band centers in Hz, as on Winamp's equalizer
+pub const BAND_FREQUENCIES: [u32; 10] = [60, 170, 310, 600, 1_000, 3_000, 6_000, 12_000, 14_000, 16_000];
+
+/// Slider range in dB either side of flat
+pub const MAX_GAIN_DB: f32 = 12.0;
+
+const EQF_HEADER: &[u8] = b"Winamp EQ library file v1.1\x1a!--";
+
+/// Bytes reserved for a preset name, NUL-padded
+const EQF_NAME_LEN: usize = 257;
+
+/// Name, ten bands and the preamp
+const EQF_ENTRY_LEN: usize = EQF_NAME_LEN + BAND_FREQUENCIES.len() + 1;
+
+/// Lowest slider position, at the bottom of the range
+const EQF_SLIDER_MAX: u8 = 63;
+
+#[derive(Error, Debug)]
+pub enum EqPresetError {
+    #[error("Failed to read preset file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a Winamp EQ preset file: {0}")]
+    InvalidFile(String),
+    #[error(transparent)]
+    Store(#[from] KvError),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EqPreset {
+    pub name: String,
+    pub preamp_db: f32,
+    /// Gains for `BAND_FREQUENCIES`, in order
+    pub bands_db: [f32; 10],
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct EqImportReport {
+    pub added: usize,
+    /// Existing presets of the same name that were overwritten
+    pub replaced: usize,
+    /// Files that could not be read, with the reason
+    pub failed: Vec<String>,
+}
+
+/// Parse an .eqf or .q1 file into its presets
+///
+/// A truncated last entry is ignored.
+pub fn parse_eqf(data: &[u8]) -> Result<Vec<EqPreset>, EqPresetError> {
+    let entries = data
+        .strip_prefix(EQF_HEADER)
+        .ok_or_else(|| EqPresetError::InvalidFile("missing EQ library header".to_string()))?;
+    Ok(entries
+        .chunks_exact(EQF_ENTRY_LEN)
+        .map(|entry| {
+            let (name, sliders) = entry.split_at(EQF_NAME_LEN);
+            let name = name.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect::<String>();
+            let mut bands_db = [0.0; 10];
+            for (band, &slider) in bands_db.iter_mut().zip(sliders) {
+                *band = slider_to_db(slider);
+            }
+            EqPreset { name: name.trim().to_string(), preamp_db: slider_to_db(sliders[10]), bands_db }
+        })
+        .filter(|preset| !preset.name.is_empty())
+        .collect())
+}
+
+/// Winamp slider position to dB, to a tenth; the two middle positions both read as flat
+fn slider_to_db(slider: u8) -> f32 {
+    let center = EQF_SLIDER_MAX as f32 / 2.0;
+    let db = (center - slider.min(EQF_SLIDER_MAX) as f32) / center * MAX_GAIN_DB;
+    if db.abs() < 0.2 {
+        0.0
+    } else {
+        (db * 10.0).round() / 10.0
+    }
+}
+
+/// Saved presets, sorted by name
+pub fn list_presets(store: &KvStore) -> Vec<EqPreset> {
+    let mut presets: Vec<EqPreset> = store.get(PRESETS_KEY).unwrap_or_default();
+    presets.sort_by_key(|preset| preset.name.to_lowercase());
+    presets
+}
+
+/// Add presets, replacing saved ones of the same name; returns (added, replaced)
+pub fn save_presets(store: &KvStore, presets: Vec<EqPreset>) -> Result<(usize, usize), KvError> {
+    let mut saved = list_presets(store);
+    let (mut added, mut replaced) = (0, 0);
+    for preset in presets {
+        match saved.iter_mut().find(|existing| existing.name == preset.name) {
+            Some(existing) => {
+                *existing = preset;
+                replaced += 1;
+            }
+            None => {
+                saved.push(preset);
+                added += 1;
+            }
+        }
+    }
+    store.set(PRESETS_KEY, &saved)?;
+    Ok((added, replaced))
+}
+
+/// Import one preset file, or every .eqf and .q1 file directly in a folder
+///
+/// A single file that cannot be read is an error; in a folder, unreadable
+/// files are listed in the report and the rest are imported.
+pub fn import_presets(store: &KvStore, path: &Path) -> Result<EqImportReport, EqPresetError> {
+    let mut report = EqImportReport::default();
+    let mut presets = Vec::new();
+    if path.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| is_preset_file(file))
+            .collect();
+        files.sort();
+        for file in files {
+            match fs::read(&file).map_err(EqPresetError::from).and_then(|data| parse_eqf(&data)) {
+                Ok(parsed) => presets.extend(parsed),
+                Err(e) => report.failed.push(format!("{}: {}", file.display(), e)),
+            }
+        }
+    } else {
+        presets = parse_eqf(&fs::read(path)?)?;
+    }
+    (report.added, report.replaced) = save_presets(store, presets)?;
+    Ok(report)
+}
+
+pub fn is_preset_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("eqf") || e.eq_ignore_ascii_case("q1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, sliders: [u8; 11]) -> Vec<u8> {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(EQF_NAME_LEN, 0);
+        bytes.extend_from_slice(&sliders);
+        bytes
+    }
+
+    fn eqf(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = EQF_HEADER.to_vec();
+        entries.iter().for_each(|entry| bytes.extend_from_slice(entry));
+        bytes
+    }
+
+    #[test]
+    fn test_parse_eqf() {
+        let mut data = eqf(&[entry("Rock", [0, 63, 31, 32, 16, 47, 0, 0, 0, 0, 31]), entry("Flat", [32; 11])]);
+        // A truncated trailing entry is dropped
+        data.extend_from_slice(b"Partial");
+
+        let presets = parse_eqf(&data).unwrap();
+        assert_eq!(presets.len(), 2);
+        assert_eq!(presets[0].name, "Rock");
+        assert_eq!(presets[0].bands_db[..6], [12.0, -12.0, 0.0, 0.0, 5.9, -5.9]);
+        assert_eq!(presets[0].preamp_db, 0.0);
+        assert_eq!(presets[1].bands_db, [0.0; 10]);
+
+        assert!(parse_eqf(b"not a preset").is_err());
+    }
+
+    #[test]
+    fn test_import_replaces_by_name() {
+        let store = KvStore::in_memory();
+        let dir = tempfile::TempDir::new().unwrap();
+        fs::write(dir.path().join("winamp.q1"), eqf(&[entry("Rock", [0; 11]), entry("Pop", [10; 11])])).unwrap();
+        fs::write(dir.path().join("broken.EQF"), b"garbage").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        let report = import_presets(&store, dir.path()).unwrap();
+        assert_eq!((report.added, report.replaced, report.failed.len()), (2, 0, 1));
+
+        let single = dir.path().join("rock.eqf");
+        fs::write(&single, eqf(&[entry("Rock", [63; 11])])).unwrap();
+        let report = import_presets(&store, &single).unwrap();
+        assert_eq!((report.added, report.replaced), (0, 1));
+
+        let names: Vec<_> = list_presets(&store).into_iter().map(|p| (p.name, p.preamp_db)).collect();
+        assert_eq!(names, [("Pop".to_string(), 8.2), ("Rock".to_string(), -12.0)]);
+        assert!(import_presets(&store, &dir.path().join("broken.EQF")).is_err());
+    }
+}
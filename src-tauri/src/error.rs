@@ -1,4 +1,6 @@
 // Comprehensive error handling for milk application
+use serde::Serialize;
+use std::fmt;
 use thiserror::Error;
 
 /// Main error type for the milk application
@@ -21,6 +23,9 @@ pub enum MilkError {
     #[error("Corrupted file: {0}")]
     CorruptedFile(String),
     
+    #[error("Volume unavailable: {0}")]
+    VolumeUnavailable(String),
+    
     // Network/API Errors
     #[error("Authentication failed: {0}")]
     AuthenticationFailed(String),
@@ -36,6 +41,9 @@ pub enum MilkError {
     
     #[error("Network error: {0}")]
     NetworkError(String),
+
+    #[error("No network connection")]
+    Offline,
     
     // Playback Errors
     #[error("Unsupported audio format: {0}")]
@@ -45,6 +53,7 @@ pub enum MilkError {
     DecodeError(String),
     
     #[error("Audio device unavailable")]
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     AudioDeviceUnavailable,
     
     // Configuration Errors
@@ -57,6 +66,9 @@ pub enum MilkError {
     #[error("Missing required configuration: {0}")]
     MissingConfig(String),
     
+    #[error("Configuration conflict: {0}")]
+    ConfigConflict(String),
+    
     // Skin Errors
     #[error("Skin parse error: {0}")]
     SkinParseError(String),
@@ -78,6 +90,21 @@ pub enum MilkError {
     #[error("Invalid playlist operation: {0}")]
     InvalidPlaylistOperation(String),
     
+    #[error("Playlist changed elsewhere: {0}")]
+    PlaylistConflict(String),
+    
+    // Visualizer Errors
+    #[error("Visualizer preset error: {0}")]
+    VisualizerPreset(String),
+    
+    // Scheduler Errors
+    #[error("Scheduler error: {0}")]
+    Scheduler(String),
+    
+    // Plugin Errors
+    #[error("Plugin error: {0}")]
+    Plugin(String),
+    
     // Storage Errors
     #[error("Secure storage error: {0}")]
     SecureStorageError(String),
@@ -112,12 +139,39 @@ impl MilkError {
             self,
             MilkError::NetworkTimeout(_)
                 | MilkError::RateLimitExceeded
+                | MilkError::Offline
                 | MilkError::CorruptedFile(_)
+                | MilkError::VolumeUnavailable(_)
                 | MilkError::SkinParseError(_)
                 | MilkError::MetadataError(_)
+                | MilkError::ConfigConflict(_)
+                | MilkError::PlaylistConflict(_)
+                | MilkError::VisualizerPreset(_)
+                | MilkError::Scheduler(_)
+                | MilkError::Plugin(_)
         )
     }
 
+    /// Classify an I/O failure while saving or reading `resource`
+    ///
+    /// Full disks and permission problems are critical and get their own
+    /// variants; everything else stays a plain file system error.
+    pub fn from_io(err: std::io::Error, resource: &str) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::StorageFull => MilkError::DiskFull(resource.to_string()),
+            std::io::ErrorKind::PermissionDenied => MilkError::PermissionDenied(resource.to_string()),
+            _ => MilkError::FileSystem(err),
+        }
+    }
+
+    /// Count this error in the session stats and build the frontend payload
+    ///
+    /// Use when handing an error to the frontend, so `get_error_stats` sees it.
+    pub fn report(&self) -> CommandError {
+        crate::error_stats::record(self);
+        CommandError(ErrorPayload::from(self))
+    }
+
     /// Get a user-friendly error message suitable for display via farmer
     pub fn user_message(&self) -> String {
         match self {
@@ -137,6 +191,9 @@ impl MilkError {
             MilkError::CorruptedFile(file) => {
                 format!("This file seems corrupted: {}. I'll use defaults instead.", file)
             }
+            MilkError::VolumeUnavailable(path) => {
+                format!("{} isn't reachable right now. I'll rescan it once the drive or share is back!", path)
+            }
 
             // Network/API Errors
             MilkError::AuthenticationFailed(service) => {
@@ -154,6 +211,9 @@ impl MilkError {
             MilkError::NetworkError(details) => {
                 format!("Network hiccup: {}. Is your internet working?", details)
             }
+            MilkError::Offline => {
+                "You're offline. Local playback still works, and I'll catch up once you're back online!".to_string()
+            }
 
             // Playback Errors
             MilkError::UnsupportedFormat(format) => {
@@ -176,6 +236,9 @@ impl MilkError {
             MilkError::MissingConfig(field) => {
                 format!("Missing configuration: {}. Let's set that up!", field)
             }
+            MilkError::ConfigConflict(_) => {
+                "Settings were changed in another window. I'll load the latest ones so you can try again!".to_string()
+            }
 
             // Skin Errors
             MilkError::SkinParseError(_) => {
@@ -200,6 +263,24 @@ impl MilkError {
             MilkError::InvalidPlaylistOperation(op) => {
                 format!("Oops, can't do that: {}. Try something else!", op)
             }
+            MilkError::PlaylistConflict(_) => {
+                "That playlist was changed in another window. I'll load the latest version so you can try again!".to_string()
+            }
+
+            // Visualizer Errors
+            MilkError::VisualizerPreset(details) => {
+                format!("Couldn't load that visualizer preset: {}. Try another one!", details)
+            }
+
+            // Scheduler Errors
+            MilkError::Scheduler(details) => {
+                format!("Couldn't set that timer: {}", details)
+            }
+
+            // Plugin Errors
+            MilkError::Plugin(details) => {
+                format!("A plugin ran into trouble: {}", details)
+            }
 
             // Storage Errors
             MilkError::SecureStorageError(_) => {
@@ -219,6 +300,45 @@ impl MilkError {
         }
     }
 
+    /// Stable identifier of the variant for the frontend, e.g. `disk_full`
+    pub fn code(&self) -> &'static str {
+        match self {
+            MilkError::FileSystem(_) => "file_system",
+            MilkError::InvalidPath(_) => "invalid_path",
+            MilkError::PermissionDenied(_) => "permission_denied",
+            MilkError::DiskFull(_) => "disk_full",
+            MilkError::CorruptedFile(_) => "corrupted_file",
+            MilkError::VolumeUnavailable(_) => "volume_unavailable",
+            MilkError::AuthenticationFailed(_) => "authentication_failed",
+            MilkError::RateLimitExceeded => "rate_limit_exceeded",
+            MilkError::NetworkTimeout(_) => "network_timeout",
+            MilkError::InvalidResponse(_) => "invalid_response",
+            MilkError::NetworkError(_) => "network_error",
+            MilkError::Offline => "offline",
+            MilkError::UnsupportedFormat(_) => "unsupported_format",
+            MilkError::DecodeError(_) => "decode_error",
+            MilkError::AudioDeviceUnavailable => "audio_device_unavailable",
+            MilkError::InvalidConfig(_) => "invalid_config",
+            MilkError::ConfigParseError(_) => "config_parse_error",
+            MilkError::MissingConfig(_) => "missing_config",
+            MilkError::ConfigConflict(_) => "config_conflict",
+            MilkError::SkinParseError(_) => "skin_parse_error",
+            MilkError::InvalidSkinFormat(_) => "invalid_skin_format",
+            MilkError::MissingSkinAssets(_) => "missing_skin_assets",
+            MilkError::MetadataError(_) => "metadata_error",
+            MilkError::PlaylistNotFound(_) => "playlist_not_found",
+            MilkError::InvalidPlaylistOperation(_) => "invalid_playlist_operation",
+            MilkError::PlaylistConflict(_) => "playlist_conflict",
+            MilkError::VisualizerPreset(_) => "visualizer_preset",
+            MilkError::Scheduler(_) => "scheduler",
+            MilkError::Plugin(_) => "plugin",
+            MilkError::SecureStorageError(_) => "secure_storage_error",
+            MilkError::SystemAudio(_) => "system_audio",
+            MilkError::Internal(_) => "internal",
+            MilkError::Other(_) => "other",
+        }
+    }
+
     /// Get the error category for logging purposes
     pub fn category(&self) -> &'static str {
         match self {
@@ -226,13 +346,15 @@ impl MilkError {
             | MilkError::InvalidPath(_)
             | MilkError::PermissionDenied(_)
             | MilkError::DiskFull(_)
-            | MilkError::CorruptedFile(_) => "FileSystem",
+            | MilkError::CorruptedFile(_)
+            | MilkError::VolumeUnavailable(_) => "FileSystem",
 
             MilkError::AuthenticationFailed(_)
             | MilkError::RateLimitExceeded
             | MilkError::NetworkTimeout(_)
             | MilkError::InvalidResponse(_)
-            | MilkError::NetworkError(_) => "Network",
+            | MilkError::NetworkError(_)
+            | MilkError::Offline => "Network",
 
             MilkError::UnsupportedFormat(_)
             | MilkError::DecodeError(_)
@@ -240,7 +362,8 @@ impl MilkError {
 
             MilkError::InvalidConfig(_)
             | MilkError::ConfigParseError(_)
-            | MilkError::MissingConfig(_) => "Configuration",
+            | MilkError::MissingConfig(_)
+            | MilkError::ConfigConflict(_) => "Configuration",
 
             MilkError::SkinParseError(_)
             | MilkError::InvalidSkinFormat(_)
@@ -248,7 +371,15 @@ impl MilkError {
 
             MilkError::MetadataError(_) => "Metadata",
 
-            MilkError::PlaylistNotFound(_) | MilkError::InvalidPlaylistOperation(_) => "Playlist",
+            MilkError::PlaylistNotFound(_)
+            | MilkError::InvalidPlaylistOperation(_)
+            | MilkError::PlaylistConflict(_) => "Playlist",
+
+            MilkError::VisualizerPreset(_) => "Visualizer",
+
+            MilkError::Scheduler(_) => "Scheduler",
+
+            MilkError::Plugin(_) => "Plugins",
 
             MilkError::SecureStorageError(_) => "Storage",
 
@@ -259,17 +390,81 @@ impl MilkError {
     }
 }
 
+/// What a failed command sends to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ErrorPayload {
+    /// `MilkError::code`, stable across releases
+    pub code: &'static str,
+    /// `MilkError::category`
+    pub category: &'static str,
+    /// Friendly text for farmer, from `user_message`
+    pub message: String,
+    pub recoverable: bool,
+    /// Needs the user's attention, e.g. a full disk
+    pub critical: bool,
+    /// Next step for the user, from `ErrorRecovery::get_recovery_suggestion`
+    pub suggestion: String,
+}
+
+impl From<&MilkError> for ErrorPayload {
+    fn from(err: &MilkError) -> Self {
+        ErrorPayload {
+            code: err.code(),
+            category: err.category(),
+            message: err.user_message(),
+            recoverable: err.is_recoverable(),
+            critical: err.is_critical(),
+            suggestion: crate::error_recovery::ErrorRecovery::get_recovery_suggestion(err),
+        }
+    }
+}
+
+/// Error type of every Tauri command; serializes as an `ErrorPayload`
+///
+/// Built from a `MilkError` with `report` (or `?`), which also counts it in
+/// the session error stats. Plain strings become `MilkError::Other`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub struct CommandError(pub ErrorPayload);
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.message)
+    }
+}
+
+impl From<MilkError> for CommandError {
+    fn from(err: MilkError) -> Self {
+        err.report()
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        MilkError::Other(message).report()
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        MilkError::Other(message.to_string()).report()
+    }
+}
+
 // Conversion implementations for existing error types
 impl From<crate::config::ConfigError> for MilkError {
     fn from(err: crate::config::ConfigError) -> Self {
         match err {
-            crate::config::ConfigError::IoError(e) => MilkError::FileSystem(e),
+            crate::config::ConfigError::IoError(e) => MilkError::from_io(e, "configuration"),
             crate::config::ConfigError::SerializationError(_) => {
                 MilkError::ConfigParseError(err.to_string())
             }
             crate::config::ConfigError::InvalidPath => {
                 MilkError::InvalidPath("configuration directory".to_string())
             }
+            crate::config::ConfigError::Conflict(_) => MilkError::ConfigConflict(err.to_string()),
+            crate::config::ConfigError::Migration { .. } => MilkError::ConfigParseError(err.to_string()),
+            crate::config::ConfigError::InvalidSetting { key, .. } => MilkError::InvalidConfig(key),
         }
     }
 }
@@ -287,6 +482,12 @@ impl From<crate::metadata::MetadataError> for MilkError {
     }
 }
 
+impl From<crate::metadata_cache::MetadataCacheError> for MilkError {
+    fn from(err: crate::metadata_cache::MetadataCacheError) -> Self {
+        MilkError::MetadataError(err.to_string())
+    }
+}
+
 impl From<crate::spotify::ApiError> for MilkError {
     fn from(err: crate::spotify::ApiError) -> Self {
         match err {
@@ -302,6 +503,11 @@ impl From<crate::spotify::ApiError> for MilkError {
             crate::spotify::ApiError::NoActivePlayback => {
                 MilkError::Other("No active playback".to_string())
             }
+            crate::spotify::ApiError::RateLimited(_) => MilkError::RateLimitExceeded,
+            crate::spotify::ApiError::Timeout(e) => MilkError::NetworkTimeout(e),
+            crate::spotify::ApiError::Offline => MilkError::Offline,
+            crate::spotify::ApiError::InvalidRequest(e) => MilkError::Other(format!("Spotify: {}", e)),
+            crate::spotify::ApiError::NotFound(e) => MilkError::Other(format!("Spotify: not found: {}", e)),
         }
     }
 }
@@ -313,10 +519,17 @@ impl From<crate::library::ScanError> for MilkError {
             crate::library::ScanError::InvalidPath => {
                 MilkError::InvalidPath("library directory".to_string())
             }
+            crate::library::ScanError::Cancelled => MilkError::Internal(crate::jobs::CANCELLED.to_string()),
         }
     }
 }
 
+impl From<crate::jobs::JobError> for MilkError {
+    fn from(err: crate::jobs::JobError) -> Self {
+        MilkError::Internal(err.to_string())
+    }
+}
+
 impl From<crate::skin::SkinError> for MilkError {
     fn from(err: crate::skin::SkinError) -> Self {
         match err {
@@ -329,14 +542,318 @@ impl From<crate::skin::SkinError> for MilkError {
     }
 }
 
+impl From<crate::skin_museum::MuseumError> for MilkError {
+    fn from(err: crate::skin_museum::MuseumError) -> Self {
+        match err {
+            crate::skin_museum::MuseumError::Network(e) => MilkError::NetworkError(e),
+            crate::skin_museum::MuseumError::Parse(e) => MilkError::InvalidResponse(e),
+            crate::skin_museum::MuseumError::HashMismatch { .. } => {
+                MilkError::CorruptedFile(err.to_string())
+            }
+            crate::skin_museum::MuseumError::DiskFull(file) => MilkError::DiskFull(file),
+            crate::skin_museum::MuseumError::Io(e) => MilkError::FileSystem(e),
+        }
+    }
+}
+
+impl From<crate::visualizer::VisualizerError> for MilkError {
+    fn from(err: crate::visualizer::VisualizerError) -> Self {
+        match err {
+            crate::visualizer::VisualizerError::Io(e) => MilkError::FileSystem(e),
+            _ => MilkError::VisualizerPreset(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::hotkeys::HotkeyError> for MilkError {
+    fn from(err: crate::hotkeys::HotkeyError) -> Self {
+        MilkError::InvalidConfig(err.to_string())
+    }
+}
+
+impl From<crate::scheduler::SchedulerError> for MilkError {
+    fn from(err: crate::scheduler::SchedulerError) -> Self {
+        MilkError::Scheduler(err.to_string())
+    }
+}
+
+impl From<crate::plugins::PluginError> for MilkError {
+    fn from(err: crate::plugins::PluginError) -> Self {
+        match err {
+            crate::plugins::PluginError::Io(e) => MilkError::FileSystem(e),
+            crate::plugins::PluginError::NotFound(id) => MilkError::InvalidConfig(format!("No plugin named '{}'", id)),
+            other => MilkError::Plugin(other.to_string()),
+        }
+    }
+}
+
+impl From<crate::winamp_vis::WinampVisError> for MilkError {
+    fn from(err: crate::winamp_vis::WinampVisError) -> Self {
+        MilkError::Plugin(err.to_string())
+    }
+}
+
+impl From<crate::scripting::ScriptError> for MilkError {
+    fn from(err: crate::scripting::ScriptError) -> Self {
+        MilkError::Plugin(err.to_string())
+    }
+}
+
+impl From<crate::ratings::RatingError> for MilkError {
+    fn from(err: crate::ratings::RatingError) -> Self {
+        match err {
+            crate::ratings::RatingError::InvalidStars(_) => MilkError::Other(err.to_string()),
+            crate::ratings::RatingError::Store(e) => MilkError::from(e),
+            crate::ratings::RatingError::Tag(e) => MilkError::MetadataError(e),
+        }
+    }
+}
+
+impl From<crate::track_notes::NoteError> for MilkError {
+    fn from(err: crate::track_notes::NoteError) -> Self {
+        match err {
+            crate::track_notes::NoteError::TooLong(_) => MilkError::Other(err.to_string()),
+            crate::track_notes::NoteError::Store(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::waveform::WaveformError> for MilkError {
+    fn from(err: crate::waveform::WaveformError) -> Self {
+        match err {
+            crate::waveform::WaveformError::InvalidResolution(_) => MilkError::Other(err.to_string()),
+            crate::waveform::WaveformError::FileNotFound(path) => MilkError::InvalidPath(path),
+            crate::waveform::WaveformError::Decode(e) => MilkError::DecodeError(e),
+            crate::waveform::WaveformError::Io(e) => MilkError::FileSystem(e),
+        }
+    }
+}
+
+impl From<crate::track_analysis::AnalysisError> for MilkError {
+    fn from(err: crate::track_analysis::AnalysisError) -> Self {
+        match err {
+            crate::track_analysis::AnalysisError::FileNotFound(path) => MilkError::InvalidPath(path),
+            crate::track_analysis::AnalysisError::Decode(e) => MilkError::DecodeError(e),
+            crate::track_analysis::AnalysisError::Database(_) | crate::track_analysis::AnalysisError::Serialization(_) => {
+                MilkError::Internal(err.to_string())
+            }
+        }
+    }
+}
+
+impl From<crate::remote_api::RemoteApiError> for MilkError {
+    fn from(err: crate::remote_api::RemoteApiError) -> Self {
+        match err {
+            crate::remote_api::RemoteApiError::Bind { .. } => MilkError::NetworkError(err.to_string()),
+            crate::remote_api::RemoteApiError::Token(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::file_associations::FileAssociationError> for MilkError {
+    fn from(err: crate::file_associations::FileAssociationError) -> Self {
+        match err {
+            crate::file_associations::FileAssociationError::UnsupportedFormat(ext) => MilkError::UnsupportedFormat(ext),
+            crate::file_associations::FileAssociationError::Io(e) => MilkError::FileSystem(e),
+            _ => MilkError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::window_geometry::WindowGeometryError> for MilkError {
+    fn from(err: crate::window_geometry::WindowGeometryError) -> Self {
+        match err {
+            crate::window_geometry::WindowGeometryError::UnknownWindow(_) => MilkError::Other(err.to_string()),
+            crate::window_geometry::WindowGeometryError::Window(_) => MilkError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::bookmarks::BookmarkError> for MilkError {
+    fn from(err: crate::bookmarks::BookmarkError) -> Self {
+        match err {
+            crate::bookmarks::BookmarkError::InvalidPosition(_) => MilkError::Other(err.to_string()),
+            crate::bookmarks::BookmarkError::Store(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::eq_presets::EqPresetError> for MilkError {
+    fn from(err: crate::eq_presets::EqPresetError) -> Self {
+        match err {
+            crate::eq_presets::EqPresetError::Io(e) => MilkError::FileSystem(e),
+            crate::eq_presets::EqPresetError::InvalidFile(_) => MilkError::Other(err.to_string()),
+            crate::eq_presets::EqPresetError::Store(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::migration::MigrationError> for MilkError {
+    fn from(err: crate::migration::MigrationError) -> Self {
+        match err {
+            crate::migration::MigrationError::NotFound(dir) => MilkError::InvalidPath(dir),
+            crate::migration::MigrationError::Io(e) => MilkError::FileSystem(e),
+            crate::migration::MigrationError::InvalidDatabase(_) => MilkError::CorruptedFile(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::itunes_import::ItunesError> for MilkError {
+    fn from(err: crate::itunes_import::ItunesError) -> Self {
+        match err {
+            crate::itunes_import::ItunesError::Io(e) => MilkError::FileSystem(e),
+            crate::itunes_import::ItunesError::InvalidLibrary(_) => MilkError::CorruptedFile(err.to_string()),
+            crate::itunes_import::ItunesError::Store(e) => MilkError::from(e),
+            crate::itunes_import::ItunesError::Rating(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::player_import::PlayerImportError> for MilkError {
+    fn from(err: crate::player_import::PlayerImportError) -> Self {
+        match err {
+            crate::player_import::PlayerImportError::NotFound(..) => MilkError::InvalidPath(err.to_string()),
+            crate::player_import::PlayerImportError::Io(e) => MilkError::FileSystem(e),
+            crate::player_import::PlayerImportError::Store(e) => MilkError::from(e),
+            crate::player_import::PlayerImportError::Rating(e) => MilkError::from(e),
+            crate::player_import::PlayerImportError::Library(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::device_sync::SyncError> for MilkError {
+    fn from(err: crate::device_sync::SyncError) -> Self {
+        match err {
+            crate::device_sync::SyncError::Io(e) => MilkError::FileSystem(e),
+            crate::device_sync::SyncError::Pattern(e) => MilkError::from(e),
+            crate::device_sync::SyncError::InvalidRule(_) => MilkError::InvalidConfig(err.to_string()),
+            crate::device_sync::SyncError::DiskFull { .. } => MilkError::DiskFull(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::chapters::ChapterError> for MilkError {
+    fn from(err: crate::chapters::ChapterError) -> Self {
+        match err {
+            crate::chapters::ChapterError::FileNotFound(path) => MilkError::InvalidPath(path),
+            crate::chapters::ChapterError::Read(e) => MilkError::MetadataError(e),
+            crate::chapters::ChapterError::OutOfRange { .. } => MilkError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::artwork::ArtworkError> for MilkError {
+    fn from(err: crate::artwork::ArtworkError) -> Self {
+        match err {
+            crate::artwork::ArtworkError::FileNotFound(path) => MilkError::InvalidPath(path),
+            crate::artwork::ArtworkError::Tag(e) => MilkError::MetadataError(e),
+            crate::artwork::ArtworkError::UnsupportedFormat(_)
+            | crate::artwork::ArtworkError::UnsupportedImage(_)
+            | crate::artwork::ArtworkError::InvalidImage(_) => MilkError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::tag_pattern::PatternError> for MilkError {
+    fn from(err: crate::tag_pattern::PatternError) -> Self {
+        match err {
+            crate::tag_pattern::PatternError::Invalid(_) => MilkError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::matching::MatchError> for MilkError {
+    fn from(err: crate::matching::MatchError) -> Self {
+        match err {
+            crate::matching::MatchError::Service(e) => MilkError::from(e),
+            crate::matching::MatchError::LibraryNotIndexed => MilkError::Other(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::headless::HeadlessError> for MilkError {
+    fn from(err: crate::headless::HeadlessError) -> Self {
+        MilkError::Other(err.to_string())
+    }
+}
+
+impl From<crate::kv_store::KvError> for MilkError {
+    fn from(err: crate::kv_store::KvError) -> Self {
+        match err {
+            crate::kv_store::KvError::Io(e) => MilkError::from_io(e, "library data"),
+            crate::kv_store::KvError::Serialization(e) => MilkError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl From<crate::data_bundle::BundleError> for MilkError {
+    fn from(err: crate::data_bundle::BundleError) -> Self {
+        match err {
+            crate::data_bundle::BundleError::Io(e) => MilkError::from_io(e, "data bundle"),
+            crate::data_bundle::BundleError::Config(e) => MilkError::from(e),
+            crate::data_bundle::BundleError::Store(e) => MilkError::from(e),
+            _ => MilkError::CorruptedFile(err.to_string()),
+        }
+    }
+}
+
+impl From<crate::cloud_backup::BackupError> for MilkError {
+    fn from(err: crate::cloud_backup::BackupError) -> Self {
+        match err {
+            crate::cloud_backup::BackupError::NotConfigured => MilkError::MissingConfig(err.to_string()),
+            crate::cloud_backup::BackupError::Unauthorized(_) => MilkError::AuthenticationFailed(err.to_string()),
+            crate::cloud_backup::BackupError::Status(_) => MilkError::NetworkError(err.to_string()),
+            crate::cloud_backup::BackupError::InvalidSnapshot(_) | crate::cloud_backup::BackupError::UnsupportedFormat(_) => {
+                MilkError::InvalidResponse(err.to_string())
+            }
+            crate::cloud_backup::BackupError::Api(e) => MilkError::from(e),
+            crate::cloud_backup::BackupError::Io(e) => MilkError::from_io(e, "backup"),
+            crate::cloud_backup::BackupError::Config(e) => MilkError::from(e),
+            crate::cloud_backup::BackupError::Playlist(e) => MilkError::from(e),
+            crate::cloud_backup::BackupError::Store(e) => MilkError::from(e),
+            crate::cloud_backup::BackupError::Rating(e) => MilkError::from(e),
+        }
+    }
+}
+
+impl From<crate::bandcamp::BandcampError> for MilkError {
+    fn from(err: crate::bandcamp::BandcampError) -> Self {
+        match err {
+            crate::bandcamp::BandcampError::NotSignedIn => MilkError::AuthenticationFailed(err.to_string()),
+            crate::bandcamp::BandcampError::Api(e) => MilkError::from(e),
+            crate::bandcamp::BandcampError::Parse(_) | crate::bandcamp::BandcampError::Zip(_) => {
+                MilkError::InvalidResponse(err.to_string())
+            }
+            crate::bandcamp::BandcampError::FormatUnavailable(_) => MilkError::UnsupportedFormat(err.to_string()),
+            crate::bandcamp::BandcampError::DiskFull(_) => MilkError::DiskFull(err.to_string()),
+            crate::bandcamp::BandcampError::Io(e) => MilkError::from_io(e, "Bandcamp download"),
+        }
+    }
+}
+
 impl From<crate::playlist::PlaylistError> for MilkError {
     fn from(err: crate::playlist::PlaylistError) -> Self {
         match err {
-            crate::playlist::PlaylistError::Io(e) => MilkError::FileSystem(e),
+            crate::playlist::PlaylistError::Io(e) => MilkError::from_io(e, "playlist"),
             crate::playlist::PlaylistError::Serialization(_) => {
                 MilkError::InvalidPlaylistOperation("serialization failed".to_string())
             }
             crate::playlist::PlaylistError::NotFound(id) => MilkError::PlaylistNotFound(id),
+            crate::playlist::PlaylistError::Stale(id) => MilkError::PlaylistConflict(id),
+            crate::playlist::PlaylistError::ReadOnly(_) => MilkError::InvalidPlaylistOperation(err.to_string()),
+            crate::playlist::PlaylistError::InvalidDirectory(path) => MilkError::InvalidPath(path),
+        }
+    }
+}
+
+impl From<crate::setup::SetupError> for MilkError {
+    fn from(err: crate::setup::SetupError) -> Self {
+        match err {
+            crate::setup::SetupError::InvalidPayload { .. } => MilkError::InvalidConfig(err.to_string()),
+            crate::setup::SetupError::LibraryNotFound(path) => MilkError::InvalidPath(path),
+            crate::setup::SetupError::InvalidSkin(reason) => MilkError::InvalidSkinFormat(reason),
+            crate::setup::SetupError::Config(e) => MilkError::from(e),
+            crate::setup::SetupError::Progress(e) => MilkError::from(e),
         }
     }
 }
@@ -4,6 +4,7 @@ use crate::config::{Config, ConfigManager, FileConfigManager};
 use crate::spotify::{SpotifyBridge, StreamingService, Credentials};
 use crate::youtube::YouTubeBridge;
 use crate::logging::{log_info, log_warn, log_error};
+use std::path::Path;
 use std::time::Duration;
 use tokio::time::sleep;
 
@@ -13,6 +14,9 @@ const MAX_RETRIES: u32 = 3;
 /// Base delay for exponential backoff (milliseconds)
 const BASE_DELAY_MS: u64 = 1000;
 
+/// Space left free on top of an operation's estimated size
+const DISK_SPACE_MARGIN: u64 = 16 * 1024 * 1024;
+
 /// Error recovery strategies
 pub struct ErrorRecovery;
 
@@ -29,8 +33,8 @@ impl ErrorRecovery {
                 
                 // Try to save the default config
                 let manager = FileConfigManager;
-                match manager.save(&default_config) {
-                    Ok(()) => {
+                match manager.save_merged(None, &default_config) {
+                    Ok(_) => {
                         log_info("Recovery", "Default configuration saved successfully");
                         Ok(default_config)
                     }
@@ -46,7 +50,7 @@ impl ErrorRecovery {
                 log_info("Recovery", "Creating missing configuration");
                 let default_config = FileConfigManager::get_default();
                 let manager = FileConfigManager;
-                let _ = manager.save(&default_config);
+                let _ = manager.save_merged(None, &default_config);
                 Ok(default_config)
             }
             _ => Err(MilkError::ConfigParseError(error.to_string())),
@@ -165,16 +169,28 @@ impl ErrorRecovery {
         }
     }
 
-    /// Check disk space before saving
-    pub fn check_disk_space(path: &std::path::Path, required_bytes: u64) -> MilkResult<()> {
-        // This is a simplified check - in production, you'd use platform-specific APIs
-        // For now, we'll just try to write and catch the error
-        match std::fs::metadata(path) {
-            Ok(_) => Ok(()),
-            Err(e) if e.kind() == std::io::ErrorKind::Other => {
-                Err(MilkError::DiskFull(path.display().to_string()))
+    /// Check there is room for `required_bytes` (plus a safety margin) at `path`
+    ///
+    /// `path` may be a file or folder that doesn't exist yet; the nearest
+    /// existing parent is measured. If free space can't be queried the check
+    /// passes, so an unusual filesystem never blocks a save.
+    pub fn check_disk_space(path: &Path, required_bytes: u64) -> MilkResult<()> {
+        if Self::has_free_space(path, required_bytes) {
+            Ok(())
+        } else {
+            log_warn("Recovery", &format!("Not enough disk space for {} bytes at {}", required_bytes, path.display()));
+            Err(MilkError::DiskFull(path.display().to_string()))
+        }
+    }
+
+    /// Whether `path`'s volume can hold `required_bytes` plus `DISK_SPACE_MARGIN`
+    pub fn has_free_space(path: &Path, required_bytes: u64) -> bool {
+        match available_space(path) {
+            Ok(available) => available >= required_bytes.saturating_add(DISK_SPACE_MARGIN),
+            Err(e) => {
+                log_warn("Recovery", &format!("Could not query free space at {}: {}", path.display(), e));
+                true
             }
-            Err(e) => Err(MilkError::FileSystem(e)),
         }
     }
 
@@ -213,6 +229,9 @@ impl ErrorRecovery {
             MilkError::AudioDeviceUnavailable => {
                 "No audio device found. Please check your speakers or headphones.".to_string()
             }
+            MilkError::ConfigConflict(_) | MilkError::PlaylistConflict(_) => {
+                "Reload to get the latest changes, then try again.".to_string()
+            }
             MilkError::SkinParseError(_) | MilkError::InvalidSkinFormat(_) => {
                 "That skin file didn't work. I'll use the default look instead.".to_string()
             }
@@ -221,6 +240,44 @@ impl ErrorRecovery {
     }
 }
 
+/// Nearest existing ancestor of `path`, so not-yet-created outputs can be measured
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Bytes available to this user on the volume holding `path`
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = CString::new(existing_ancestor(path).as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `dir` is a valid NUL-terminated string and `stats` is writable
+    if unsafe { libc::statvfs(dir.as_ptr(), &mut stats) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+/// Bytes available to this user on the volume holding `path`
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let dir: Vec<u16> = existing_ancestor(path).as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    // SAFETY: `dir` is NUL-terminated and outlives the call
+    unsafe { GetDiskFreeSpaceExW(PCWSTR(dir.as_ptr()), Some(&mut available as *mut u64), None, None) }
+        .map_err(|e| std::io::Error::other(e.message()))?;
+    Ok(available)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,6 +297,18 @@ mod tests {
         assert!(suggestion.contains("directory"));
     }
 
+    #[test]
+    fn test_check_disk_space() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("exports").join("song.mp3");
+        assert!(available_space(&missing).unwrap() > 0);
+        assert!(ErrorRecovery::check_disk_space(&missing, 0).is_ok());
+        assert!(matches!(
+            ErrorRecovery::check_disk_space(&missing, u64::MAX),
+            Err(MilkError::DiskFull(_))
+        ));
+    }
+
     #[test]
     fn test_config_recovery() {
         let corrupted_error = MilkError::ConfigParseError("test".to_string());
@@ -0,0 +1,155 @@
+// Session error statistics for the diagnostics UI
+// Every error reported to the user is counted by category and severity, and
+// the latest ones are kept in a small ring buffer.
+
+use crate::error::MilkError;
+use chrono::Local;
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+/// Number of errors kept for `ErrorStats::recent_errors`
+const RECENT_CAPACITY: usize = 50;
+
+static COLLECTOR: OnceLock<Mutex<ErrorStatsCollector>> = OnceLock::new();
+
+/// How much an error matters to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorSeverity {
+    /// Needs user attention (`MilkError::is_critical`)
+    Critical,
+    Error,
+    /// Handled gracefully (`MilkError::is_recoverable`)
+    Recoverable,
+}
+
+impl ErrorSeverity {
+    pub fn of(error: &MilkError) -> Self {
+        if error.is_critical() {
+            ErrorSeverity::Critical
+        } else if error.is_recoverable() {
+            ErrorSeverity::Recoverable
+        } else {
+            ErrorSeverity::Error
+        }
+    }
+}
+
+/// One reported error
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorRecord {
+    /// RFC 3339 local time with milliseconds
+    pub timestamp: String,
+    pub category: &'static str,
+    pub severity: ErrorSeverity,
+    pub message: String,
+}
+
+/// Snapshot returned by `get_error_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorStats {
+    pub session_started: String,
+    pub total: u64,
+    pub by_category: BTreeMap<&'static str, u64>,
+    pub by_severity: BTreeMap<ErrorSeverity, u64>,
+    /// Newest first
+    pub recent_errors: Vec<ErrorRecord>,
+}
+
+struct ErrorStatsCollector {
+    session_started: String,
+    by_category: BTreeMap<&'static str, u64>,
+    by_severity: BTreeMap<ErrorSeverity, u64>,
+    recent: VecDeque<ErrorRecord>,
+}
+
+impl ErrorStatsCollector {
+    fn new() -> Self {
+        ErrorStatsCollector {
+            session_started: timestamp(),
+            by_category: BTreeMap::new(),
+            by_severity: BTreeMap::new(),
+            recent: VecDeque::with_capacity(RECENT_CAPACITY),
+        }
+    }
+
+    fn record(&mut self, error: &MilkError) {
+        let category = error.category();
+        let severity = ErrorSeverity::of(error);
+        *self.by_category.entry(category).or_default() += 1;
+        *self.by_severity.entry(severity).or_default() += 1;
+        if self.recent.len() == RECENT_CAPACITY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ErrorRecord {
+            timestamp: timestamp(),
+            category,
+            severity,
+            message: error.to_string(),
+        });
+    }
+
+    fn stats(&self) -> ErrorStats {
+        ErrorStats {
+            session_started: self.session_started.clone(),
+            total: self.by_severity.values().sum(),
+            by_category: self.by_category.clone(),
+            by_severity: self.by_severity.clone(),
+            recent_errors: self.recent.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+fn timestamp() -> String {
+    Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false)
+}
+
+fn collector() -> &'static Mutex<ErrorStatsCollector> {
+    COLLECTOR.get_or_init(|| Mutex::new(ErrorStatsCollector::new()))
+}
+
+/// Count `error` in this session's statistics
+pub fn record(error: &MilkError) {
+    collector().lock().unwrap().record(error);
+}
+
+pub fn stats() -> ErrorStats {
+    collector().lock().unwrap().stats()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_by_category_and_severity() {
+        let mut collector = ErrorStatsCollector::new();
+        collector.record(&MilkError::DiskFull("config".to_string()));
+        collector.record(&MilkError::NetworkTimeout("Spotify".to_string()));
+        collector.record(&MilkError::NetworkError("reset".to_string()));
+
+        let stats = collector.stats();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.by_category["Network"], 2);
+        assert_eq!(stats.by_category["FileSystem"], 1);
+        assert_eq!(stats.by_severity[&ErrorSeverity::Critical], 1);
+        assert_eq!(stats.by_severity[&ErrorSeverity::Recoverable], 1);
+        assert_eq!(stats.by_severity[&ErrorSeverity::Error], 1);
+        assert_eq!(stats.recent_errors[0].message, "Network error: reset");
+    }
+
+    #[test]
+    fn test_recent_errors_are_bounded() {
+        let mut collector = ErrorStatsCollector::new();
+        for i in 0..RECENT_CAPACITY + 5 {
+            collector.record(&MilkError::Other(format!("error {}", i)));
+        }
+
+        let stats = collector.stats();
+        assert_eq!(stats.total, (RECENT_CAPACITY + 5) as u64);
+        assert_eq!(stats.recent_errors.len(), RECENT_CAPACITY);
+        assert_eq!(stats.recent_errors[0].message, format!("error {}", RECENT_CAPACITY + 4));
+        assert_eq!(stats.recent_errors.last().unwrap().message, "error 5");
+    }
+}
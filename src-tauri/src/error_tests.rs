@@ -43,4 +43,30 @@ mod tests {
         let non_recoverable = MilkError::DiskFull("config".to_string());
         assert!(!non_recoverable.is_recoverable());
     }
+
+    #[test]
+    fn test_io_errors_are_classified() {
+        use std::io::{Error, ErrorKind};
+
+        let full = MilkError::from_io(Error::from(ErrorKind::StorageFull), "playlist");
+        assert!(matches!(full, MilkError::DiskFull(ref what) if what == "playlist"));
+        let denied = MilkError::from_io(Error::from(ErrorKind::PermissionDenied), "playlist");
+        assert!(matches!(denied, MilkError::PermissionDenied(_)));
+        assert!(denied.is_critical());
+        let missing = MilkError::from_io(Error::from(ErrorKind::NotFound), "playlist");
+        assert!(matches!(missing, MilkError::FileSystem(_)));
+    }
+
+    #[test]
+    fn test_command_error_payload() {
+        let err = crate::error::CommandError::from(MilkError::DiskFull("config".to_string()));
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["code"], "disk_full");
+        assert_eq!(json["category"], "FileSystem");
+        assert_eq!(json["message"], MilkError::DiskFull("config".to_string()).user_message());
+        assert_eq!(json["recoverable"], false);
+        assert_eq!(json["critical"], true);
+        assert!(!json["suggestion"].as_str().unwrap().is_empty());
+        assert_eq!(err.to_string(), json["message"]);
+    }
 }
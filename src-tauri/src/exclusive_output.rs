@@ -0,0 +1,591 @@
+// Exclusive output
+// Bit-perfect playback for the headless player. The device is taken away from
+// the OS mixer and run at the file's own sample rate, so samples reach the DAC
+// untouched. On Windows the endpoint is opened in WASAPI exclusive mode and fed
+// from a render thread; on macOS the device is taken in hog mode, switched to
+// the file's rate and played through a CoreAudio stream no other app can mix
+// into. Other platforms have no exclusive mode and report `Unsupported`.
+
+use thiserror::Error;
+
+/// Whether this platform can open output devices exclusively
+pub const SUPPORTED: bool = cfg!(any(target_os = "windows", target_os = "macos"));
+
+#[derive(Error, Debug)]
+#[cfg_attr(not(any(target_os = "windows", target_os = "macos")), allow(dead_code))]
+pub enum ExclusiveError {
+    #[error("Exclusive output is not available on this platform")]
+    Unsupported,
+    #[error("Another application is using the output device exclusively")]
+    DeviceInUse,
+    #[error("Output device {0} not found")]
+    DeviceNotFound(String),
+    #[error("The output device cannot play {0} Hz exclusively")]
+    UnsupportedRate(u32),
+    #[error("Exclusive output failed: {0}")]
+    Backend(String),
+}
+
+/// An output device held exclusively; dropping it hands the device back
+pub struct ExclusiveStream {
+    inner: platform::Stream,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl ExclusiveStream {
+    /// Take `device` (the default output when `None`) at `sample_rate`, or at its
+    /// current rate when `None`, paused
+    ///
+    /// `fill` receives interleaved samples to write and the channel count.
+    pub fn open(
+        device: Option<&str>,
+        sample_rate: Option<u32>,
+        fill: impl FnMut(&mut [f32], usize) + Send + 'static,
+    ) -> Result<Self, ExclusiveError> {
+        let (inner, sample_rate, channels) = platform::open(device, sample_rate, fill)?;
+        Ok(Self { inner, sample_rate, channels })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn play(&self) {
+        self.inner.play();
+    }
+
+    pub fn pause(&self) {
+        self.inner.pause();
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::ExclusiveError;
+    use crate::logging::log_error;
+    use cpal::{FromSample, SizedSample};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread::JoinHandle;
+    use std::time::Duration;
+    use windows::core::PCWSTR;
+    use windows::Win32::Devices::FunctionDiscovery::PKEY_Device_FriendlyName;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, S_OK, WAIT_OBJECT_0};
+    use windows::Win32::Media::Audio::{
+        eConsole, eRender, IAudioClient, IAudioRenderClient, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator,
+        AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED, AUDCLNT_E_DEVICE_IN_USE, AUDCLNT_SHAREMODE_EXCLUSIVE,
+        AUDCLNT_STREAMFLAGS_EVENTCALLBACK, DEVICE_STATE_ACTIVE, WAVEFORMATEX, WAVEFORMATEXTENSIBLE,
+        WAVEFORMATEXTENSIBLE_0,
+    };
+    use windows::Win32::Media::KernelStreaming::{KSDATAFORMAT_SUBTYPE_PCM, WAVE_FORMAT_EXTENSIBLE};
+    use windows::Win32::Media::Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ,
+    };
+    use windows::Win32::System::Threading::{CreateEventW, WaitForSingleObject};
+
+    /// How long the render thread waits for the device before checking for shutdown
+    const EVENT_TIMEOUT_MS: u32 = 200;
+
+    /// How often a paused render thread checks for play or shutdown
+    const IDLE_POLL: Duration = Duration::from_millis(20);
+
+    /// Sample layouts tried in order; 24-bit in a 32-bit container suits most DACs
+    #[derive(Clone, Copy)]
+    enum SampleType {
+        I24In32,
+        I32,
+        I16,
+        F32,
+    }
+
+    const SAMPLE_TYPES: [SampleType; 4] = [SampleType::I24In32, SampleType::I32, SampleType::I16, SampleType::F32];
+
+    impl From<windows::core::Error> for ExclusiveError {
+        fn from(err: windows::core::Error) -> Self {
+            if err.code() == AUDCLNT_E_DEVICE_IN_USE {
+                ExclusiveError::DeviceInUse
+            } else {
+                ExclusiveError::Backend(err.message())
+            }
+        }
+    }
+
+    /// COM objects stay on the render thread; this only steers it
+    pub struct Stream {
+        playing: Arc<AtomicBool>,
+        running: Arc<AtomicBool>,
+        thread: Option<JoinHandle<()>>,
+    }
+
+    impl Stream {
+        pub fn play(&self) {
+            self.playing.store(true, Ordering::SeqCst);
+        }
+
+        pub fn pause(&self) {
+            self.playing.store(false, Ordering::SeqCst);
+        }
+    }
+
+    impl Drop for Stream {
+        /// Waits for the render thread so the device is free to reopen right away
+        fn drop(&mut self) {
+            self.running.store(false, Ordering::SeqCst);
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    pub fn open(
+        device: Option<&str>,
+        sample_rate: Option<u32>,
+        fill: impl FnMut(&mut [f32], usize) + Send + 'static,
+    ) -> Result<(Stream, u32, u16), ExclusiveError> {
+        let playing = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        let (opened_tx, opened_rx) = mpsc::channel();
+        let name = device.map(str::to_string);
+        let (thread_playing, thread_running) = (playing.clone(), running.clone());
+        let thread = std::thread::spawn(move || {
+            let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+            match unsafe { Renderer::open(name.as_deref(), sample_rate) } {
+                Ok(renderer) => {
+                    let _ = opened_tx.send(Ok((renderer.sample_rate, renderer.channels)));
+                    renderer.run(fill, &thread_playing, &thread_running);
+                }
+                Err(e) => {
+                    let _ = opened_tx.send(Err(e));
+                }
+            }
+            unsafe { CoUninitialize() };
+        });
+        let opened = opened_rx
+            .recv()
+            .unwrap_or_else(|_| Err(ExclusiveError::Backend("the render thread exited".to_string())));
+        let (sample_rate, channels) = match opened {
+            Ok(format) => format,
+            Err(e) => {
+                let _ = thread.join();
+                return Err(e);
+            }
+        };
+        Ok((Stream { playing, running, thread: Some(thread) }, sample_rate, channels))
+    }
+
+    struct Renderer {
+        client: IAudioClient,
+        render: IAudioRenderClient,
+        event: HANDLE,
+        /// Frames per device period; exclusive mode always writes whole periods
+        frames: u32,
+        sample_type: SampleType,
+        sample_rate: u32,
+        channels: u16,
+    }
+
+    impl Renderer {
+        unsafe fn open(name: Option<&str>, sample_rate: Option<u32>) -> Result<Self, ExclusiveError> {
+            let enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+            let device = match name {
+                Some(name) => find_device(&enumerator, name)?,
+                None => enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?,
+            };
+            let client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+            let mix = client.GetMixFormat()?;
+            let (mix_rate, channels) = ((*mix).nSamplesPerSec, (*mix).nChannels);
+            let channel_mask = if u32::from((*mix).wFormatTag) == WAVE_FORMAT_EXTENSIBLE {
+                (*(mix as *const WAVEFORMATEXTENSIBLE)).dwChannelMask
+            } else {
+                0
+            };
+            CoTaskMemFree(Some(mix as *const _));
+            let sample_rate = sample_rate.unwrap_or(mix_rate);
+            let (sample_type, format) = SAMPLE_TYPES
+                .into_iter()
+                .map(|sample_type| (sample_type, wave_format(sample_type, sample_rate, channels, channel_mask)))
+                .find(|(_, format)| {
+                    client.IsFormatSupported(AUDCLNT_SHAREMODE_EXCLUSIVE, as_wave_format(format), None) == S_OK
+                })
+                .ok_or(ExclusiveError::UnsupportedRate(sample_rate))?;
+            let mut period = 0i64;
+            client.GetDevicePeriod(Some(&mut period), None)?;
+            let initialize = |client: &IAudioClient, period: i64| {
+                client.Initialize(
+                    AUDCLNT_SHAREMODE_EXCLUSIVE,
+                    AUDCLNT_STREAMFLAGS_EVENTCALLBACK,
+                    period,
+                    period,
+                    as_wave_format(&format),
+                    None,
+                )
+            };
+            let client = match initialize(&client, period) {
+                Ok(()) => client,
+                // The period has to be a whole number of the frames the device
+                // transfers; the failed client reports that size
+                Err(e) if e.code() == AUDCLNT_E_BUFFER_SIZE_NOT_ALIGNED => {
+                    let frames = client.GetBufferSize()?;
+                    let period = (10_000_000.0 * f64::from(frames) / f64::from(sample_rate)).round() as i64;
+                    let client: IAudioClient = device.Activate(CLSCTX_ALL, None)?;
+                    initialize(&client, period)?;
+                    client
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let event = CreateEventW(None, false, false, PCWSTR::null())?;
+            client.SetEventHandle(event)?;
+            Ok(Self {
+                render: client.GetService()?,
+                frames: client.GetBufferSize()?,
+                client,
+                event,
+                sample_type,
+                sample_rate,
+                channels,
+            })
+        }
+
+        /// Write a period each time the device asks for one, until the stream is dropped
+        fn run(self, mut fill: impl FnMut(&mut [f32], usize), playing: &AtomicBool, running: &AtomicBool) {
+            let mut scratch = vec![0.0f32; self.frames as usize * self.channels as usize];
+            let mut started = false;
+            while running.load(Ordering::SeqCst) {
+                let play = playing.load(Ordering::SeqCst);
+                if play != started {
+                    // Exclusive mode plays the first period as soon as it starts,
+                    // so it is written before starting
+                    let result = if play {
+                        self.write(&mut fill, &mut scratch).and_then(|_| unsafe { self.client.Start() })
+                    } else {
+                        unsafe { self.client.Stop() }
+                    };
+                    if let Err(e) = result {
+                        log_error("ExclusiveOutput", &format!("Failed to {} output: {}", if play { "start" } else { "pause" }, e));
+                        break;
+                    }
+                    started = play;
+                }
+                if !started {
+                    std::thread::sleep(IDLE_POLL);
+                    continue;
+                }
+                if unsafe { WaitForSingleObject(self.event, EVENT_TIMEOUT_MS) } != WAIT_OBJECT_0 {
+                    continue;
+                }
+                if let Err(e) = self.write(&mut fill, &mut scratch) {
+                    log_error("ExclusiveOutput", &format!("Audio output lost: {}", e));
+                    break;
+                }
+            }
+            unsafe {
+                let _ = self.client.Stop();
+                let _ = CloseHandle(self.event);
+            }
+        }
+
+        fn write(&self, fill: &mut impl FnMut(&mut [f32], usize), scratch: &mut [f32]) -> windows::core::Result<()> {
+            fill(scratch, self.channels as usize);
+            unsafe {
+                let data = self.render.GetBuffer(self.frames)?;
+                match self.sample_type {
+                    SampleType::F32 => convert::<f32>(data, scratch),
+                    SampleType::I16 => convert::<i16>(data, scratch),
+                    SampleType::I24In32 | SampleType::I32 => convert::<i32>(data, scratch),
+                }
+                self.render.ReleaseBuffer(self.frames, 0)
+            }
+        }
+    }
+
+    /// Convert into the device buffer at `data`, which holds `samples.len()` samples of `T`
+    unsafe fn convert<T: SizedSample + FromSample<f32>>(data: *mut u8, samples: &[f32]) {
+        let out = std::slice::from_raw_parts_mut(data as *mut T, samples.len());
+        for (out, &sample) in out.iter_mut().zip(samples) {
+            *out = T::from_sample(sample);
+        }
+    }
+
+    /// The active render endpoint named `name`, as cpal and the settings name it
+    unsafe fn find_device(enumerator: &IMMDeviceEnumerator, name: &str) -> Result<IMMDevice, ExclusiveError> {
+        let devices = enumerator.EnumAudioEndpoints(eRender, DEVICE_STATE_ACTIVE)?;
+        for i in 0..devices.GetCount()? {
+            let device = devices.Item(i)?;
+            let friendly_name = device.OpenPropertyStore(STGM_READ)?.GetValue(&PKEY_Device_FriendlyName)?;
+            if friendly_name.to_string() == name {
+                return Ok(device);
+            }
+        }
+        Err(ExclusiveError::DeviceNotFound(name.to_string()))
+    }
+
+    fn wave_format(sample_type: SampleType, sample_rate: u32, channels: u16, channel_mask: u32) -> WAVEFORMATEXTENSIBLE {
+        let (bits, valid_bits, sub_format) = match sample_type {
+            SampleType::I24In32 => (32, 24, KSDATAFORMAT_SUBTYPE_PCM),
+            SampleType::I32 => (32, 32, KSDATAFORMAT_SUBTYPE_PCM),
+            SampleType::I16 => (16, 16, KSDATAFORMAT_SUBTYPE_PCM),
+            SampleType::F32 => (32, 32, KSDATAFORMAT_SUBTYPE_IEEE_FLOAT),
+        };
+        let block_align = channels * bits / 8;
+        WAVEFORMATEXTENSIBLE {
+            Format: WAVEFORMATEX {
+                wFormatTag: WAVE_FORMAT_EXTENSIBLE as u16,
+                nChannels: channels,
+                nSamplesPerSec: sample_rate,
+                nAvgBytesPerSec: sample_rate * u32::from(block_align),
+                nBlockAlign: block_align,
+                wBitsPerSample: bits,
+                cbSize: (std::mem::size_of::<WAVEFORMATEXTENSIBLE>() - std::mem::size_of::<WAVEFORMATEX>()) as u16,
+            },
+            Samples: WAVEFORMATEXTENSIBLE_0 { wValidBitsPerSample: valid_bits },
+            dwChannelMask: channel_mask,
+            SubFormat: sub_format,
+        }
+    }
+
+    fn as_wave_format(format: &WAVEFORMATEXTENSIBLE) -> *const WAVEFORMATEX {
+        format as *const WAVEFORMATEXTENSIBLE as *const WAVEFORMATEX
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::ExclusiveError;
+    use crate::logging::log_warn;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use std::ffi::{c_char, c_void, CStr};
+    use std::ptr;
+
+    type AudioObjectId = u32;
+
+    #[repr(C)]
+    struct PropertyAddress {
+        selector: u32,
+        scope: u32,
+        element: u32,
+    }
+
+    const fn four_cc(code: &[u8; 4]) -> u32 {
+        u32::from_be_bytes(*code)
+    }
+
+    const SYSTEM_OBJECT: AudioObjectId = 1;
+    const SCOPE_GLOBAL: u32 = four_cc(b"glob");
+    const SCOPE_OUTPUT: u32 = four_cc(b"outp");
+    const ELEMENT_MAIN: u32 = 0;
+    const HARDWARE_DEVICES: u32 = four_cc(b"dev#");
+    const DEFAULT_OUTPUT_DEVICE: u32 = four_cc(b"dOut");
+    /// The name cpal reports, which is what the settings store
+    const DEVICE_NAME: u32 = four_cc(b"lnam");
+    /// The pid holding the device, or -1 when it is free
+    const HOG_MODE: u32 = four_cc(b"oink");
+    const NOMINAL_SAMPLE_RATE: u32 = four_cc(b"nsrt");
+    const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        fn AudioObjectGetPropertyDataSize(
+            object: AudioObjectId,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+        ) -> i32;
+        fn AudioObjectGetPropertyData(
+            object: AudioObjectId,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+        fn AudioObjectSetPropertyData(
+            object: AudioObjectId,
+            address: *const PropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            size: u32,
+            data: *const c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringGetCString(string: *const c_void, buffer: *mut c_char, size: isize, encoding: u32) -> u8;
+        fn CFRelease(object: *const c_void);
+    }
+
+    fn check(status: i32) -> Result<(), ExclusiveError> {
+        if status == 0 {
+            Ok(())
+        } else {
+            Err(ExclusiveError::Backend(format!("CoreAudio error {}", status)))
+        }
+    }
+
+    fn get<T: Copy>(object: AudioObjectId, selector: u32, scope: u32, mut value: T) -> Result<T, ExclusiveError> {
+        let address = PropertyAddress { selector, scope, element: ELEMENT_MAIN };
+        let mut size = std::mem::size_of::<T>() as u32;
+        check(unsafe {
+            AudioObjectGetPropertyData(object, &address, 0, ptr::null(), &mut size, &mut value as *mut T as *mut c_void)
+        })?;
+        Ok(value)
+    }
+
+    fn set<T: Copy>(object: AudioObjectId, selector: u32, value: T) -> Result<(), ExclusiveError> {
+        let address = PropertyAddress { selector, scope: SCOPE_GLOBAL, element: ELEMENT_MAIN };
+        let size = std::mem::size_of::<T>() as u32;
+        check(unsafe { AudioObjectSetPropertyData(object, &address, 0, ptr::null(), size, &value as *const T as *const c_void) })
+    }
+
+    fn device_ids() -> Result<Vec<AudioObjectId>, ExclusiveError> {
+        let address = PropertyAddress { selector: HARDWARE_DEVICES, scope: SCOPE_GLOBAL, element: ELEMENT_MAIN };
+        let mut size = 0u32;
+        check(unsafe { AudioObjectGetPropertyDataSize(SYSTEM_OBJECT, &address, 0, ptr::null(), &mut size) })?;
+        let mut ids = vec![0 as AudioObjectId; size as usize / std::mem::size_of::<AudioObjectId>()];
+        check(unsafe {
+            AudioObjectGetPropertyData(SYSTEM_OBJECT, &address, 0, ptr::null(), &mut size, ids.as_mut_ptr() as *mut c_void)
+        })?;
+        ids.truncate(size as usize / std::mem::size_of::<AudioObjectId>());
+        Ok(ids)
+    }
+
+    fn device_name(device: AudioObjectId) -> Option<String> {
+        let name = get(device, DEVICE_NAME, SCOPE_OUTPUT, ptr::null::<c_void>()).ok().filter(|name| !name.is_null())?;
+        let mut buffer = [0 as c_char; 256];
+        unsafe {
+            let converted = CFStringGetCString(name, buffer.as_mut_ptr(), buffer.len() as isize, CF_STRING_ENCODING_UTF8);
+            CFRelease(name);
+            (converted != 0).then(|| CStr::from_ptr(buffer.as_ptr()).to_string_lossy().into_owned())
+        }
+    }
+
+    /// A device held in hog mode, released with its rate restored on drop
+    struct Hog {
+        device: AudioObjectId,
+        previous_rate: f64,
+    }
+
+    impl Hog {
+        fn take(device: AudioObjectId) -> Result<Self, ExclusiveError> {
+            let pid = std::process::id() as i32;
+            let owner = get(device, HOG_MODE, SCOPE_GLOBAL, -1i32)?;
+            if owner != -1 && owner != pid {
+                return Err(ExclusiveError::DeviceInUse);
+            }
+            if owner != pid {
+                set(device, HOG_MODE, pid)?;
+            }
+            let previous_rate = get(device, NOMINAL_SAMPLE_RATE, SCOPE_GLOBAL, 0.0f64)?;
+            Ok(Self { device, previous_rate })
+        }
+
+        fn set_sample_rate(&self, sample_rate: u32) -> Result<(), ExclusiveError> {
+            set(self.device, NOMINAL_SAMPLE_RATE, f64::from(sample_rate))
+                .map_err(|_| ExclusiveError::UnsupportedRate(sample_rate))
+        }
+    }
+
+    impl Drop for Hog {
+        fn drop(&mut self) {
+            let _ = set(self.device, NOMINAL_SAMPLE_RATE, self.previous_rate);
+            let _ = set(self.device, HOG_MODE, -1i32);
+        }
+    }
+
+    pub struct Stream {
+        // Declared first so the stream closes before hog mode is released
+        stream: cpal::Stream,
+        _hog: Hog,
+    }
+
+    impl Stream {
+        pub fn play(&self) {
+            if let Err(e) = self.stream.play() {
+                log_warn("ExclusiveOutput", &format!("Failed to start audio output: {}", e));
+            }
+        }
+
+        pub fn pause(&self) {
+            if let Err(e) = self.stream.pause() {
+                log_warn("ExclusiveOutput", &format!("Failed to pause audio output: {}", e));
+            }
+        }
+    }
+
+    pub fn open(
+        device: Option<&str>,
+        sample_rate: Option<u32>,
+        mut fill: impl FnMut(&mut [f32], usize) + Send + 'static,
+    ) -> Result<(Stream, u32, u16), ExclusiveError> {
+        let backend = |e: &dyn std::fmt::Display| ExclusiveError::Backend(e.to_string());
+        let id = match device {
+            Some(name) => device_ids()?
+                .into_iter()
+                .find(|id| device_name(*id).as_deref() == Some(name))
+                .ok_or_else(|| ExclusiveError::DeviceNotFound(name.to_string()))?,
+            None => get(SYSTEM_OBJECT, DEFAULT_OUTPUT_DEVICE, SCOPE_GLOBAL, 0 as AudioObjectId)?,
+        };
+        let name = device_name(id).ok_or_else(|| ExclusiveError::Backend("unnamed output device".to_string()))?;
+        let hog = Hog::take(id)?;
+        let sample_rate = match sample_rate {
+            Some(rate) => {
+                hog.set_sample_rate(rate)?;
+                rate
+            }
+            None => hog.previous_rate as u32,
+        };
+        let device = cpal::default_host()
+            .output_devices()
+            .map_err(|e| backend(&e))?
+            .find(|d| d.name().is_ok_and(|n| n == name))
+            .ok_or(ExclusiveError::DeviceNotFound(name))?;
+        let channels = device.default_output_config().map_err(|e| backend(&e))?.channels();
+        let config = cpal::StreamConfig {
+            channels,
+            sample_rate: cpal::SampleRate(sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| fill(data, channels as usize),
+                |e| log_warn("ExclusiveOutput", &format!("Audio output error: {}", e)),
+                None,
+            )
+            .map_err(|e| backend(&e))?;
+        Ok((Stream { stream, _hog: hog }, sample_rate, channels))
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod platform {
+    use super::ExclusiveError;
+    use std::convert::Infallible;
+
+    pub struct Stream(Infallible);
+
+    impl Stream {
+        pub fn play(&self) {
+            match self.0 {}
+        }
+
+        pub fn pause(&self) {
+            match self.0 {}
+        }
+    }
+
+    pub fn open(
+        _device: Option<&str>,
+        _sample_rate: Option<u32>,
+        _fill: impl FnMut(&mut [f32], usize) + Send + 'static,
+    ) -> Result<(Stream, u32, u16), ExclusiveError> {
+        Err(ExclusiveError::Unsupported)
+    }
+}
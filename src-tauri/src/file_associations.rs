@@ -0,0 +1,360 @@
+// OS file associations for audio, playlist and skin formats
+// Registers milk as a handler for the current user: ProgIds under
+// HKCU\Software\Classes on Windows, a .desktop entry plus MIME defaults on
+// Linux. macOS takes associations from the app bundle's Info.plist, so only
+// instructions are returned there.
+
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FileAssociationError {
+    #[error("Unsupported association format: {0}")]
+    UnsupportedFormat(String),
+    #[error("Failed to locate the milk executable: {0}")]
+    Executable(std::io::Error),
+    #[error("Failed to write association files: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("{0}")]
+    Command(String),
+}
+
+pub struct AssociationFormat {
+    pub extension: &'static str,
+    pub mime: &'static str,
+    pub description: &'static str,
+    /// Offer "Enqueue in milk" alongside "Open"
+    pub enqueue: bool,
+}
+
+/// Formats milk can register for
+pub const FORMATS: &[AssociationFormat] = &[
+    AssociationFormat { extension: "mp3", mime: "audio/mpeg", description: "MP3 Audio", enqueue: true },
+    AssociationFormat { extension: "flac", mime: "audio/flac", description: "FLAC Audio", enqueue: true },
+    AssociationFormat { extension: "ogg", mime: "audio/ogg", description: "Ogg Vorbis Audio", enqueue: true },
+    AssociationFormat { extension: "wav", mime: "audio/x-wav", description: "WAV Audio", enqueue: true },
+    AssociationFormat { extension: "m4a", mime: "audio/mp4", description: "MPEG-4 Audio", enqueue: true },
+    AssociationFormat { extension: "m3u", mime: "audio/x-mpegurl", description: "M3U Playlist", enqueue: true },
+    AssociationFormat { extension: "m3u8", mime: "application/vnd.apple.mpegurl", description: "M3U8 Playlist", enqueue: true },
+    AssociationFormat { extension: "pls", mime: "audio/x-scpls", description: "PLS Playlist", enqueue: true },
+    AssociationFormat { extension: "wsz", mime: "application/x-winamp-skin", description: "Winamp Skin (ZIP)", enqueue: false },
+    AssociationFormat { extension: "wal", mime: "application/x-winamp-skin", description: "Winamp Skin (WAL)", enqueue: false },
+];
+
+/// ProgId prefix, shared with the installer's WiX fragment
+const PROG_ID_PREFIX: &str = "milk.";
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+const DESKTOP_FILE: &str = "milk.desktop";
+
+/// Result of `register_file_associations`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct AssociationReport {
+    /// Extensions now associated with milk
+    pub registered: Vec<String>,
+    /// Steps the user has to take themselves, where the OS doesn't allow registration
+    pub manual_steps: Option<String>,
+}
+
+/// Look up requested extensions; an empty request means every format
+pub fn formats_for(requested: &[String]) -> Result<Vec<&'static AssociationFormat>, FileAssociationError> {
+    if requested.is_empty() {
+        return Ok(FORMATS.iter().collect());
+    }
+    requested
+        .iter()
+        .map(|ext| {
+            let ext = ext.trim_start_matches('.').to_lowercase();
+            FORMATS
+                .iter()
+                .find(|f| f.extension == ext)
+                .ok_or(FileAssociationError::UnsupportedFormat(ext))
+        })
+        .collect()
+}
+
+/// `reg add` argument lists registering `formats` for the current user
+///
+/// milk is added to each extension's "Open with" list; Windows reserves
+/// choosing the default app to the user.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn windows_registry_commands(exe: &Path, formats: &[&AssociationFormat]) -> Vec<Vec<String>> {
+    let exe = exe.display();
+    let mut commands = Vec::new();
+    let mut add = |key: String, value: Option<&str>, data: String| {
+        let mut args = vec!["add".to_string(), format!(r"HKCU\Software\Classes\{}", key)];
+        match value {
+            Some(name) => args.extend(["/v".to_string(), name.to_string(), "/t".to_string(), "REG_NONE".to_string()]),
+            None => args.extend(["/ve".to_string(), "/d".to_string(), data]),
+        }
+        args.push("/f".to_string());
+        commands.push(args);
+    };
+    for format in formats {
+        let prog_id = format!("{}{}", PROG_ID_PREFIX, format.extension);
+        add(prog_id.clone(), None, format.description.to_string());
+        add(format!(r"{}\DefaultIcon", prog_id), None, format!("\"{}\",0", exe));
+        add(format!(r"{}\shell\open\command", prog_id), None, format!("\"{}\" \"%1\"", exe));
+        if format.enqueue {
+            add(format!(r"{}\shell\enqueue", prog_id), None, "Enqueue in milk".to_string());
+            add(format!(r"{}\shell\enqueue\command", prog_id), None, format!("\"{}\" --enqueue \"%1\"", exe));
+        }
+        add(format!(r".{}\OpenWithProgids", format.extension), Some(&prog_id), String::new());
+    }
+    commands
+}
+
+/// Contents of the `.desktop` entry advertising `formats`
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn desktop_entry(exe: &Path, formats: &[&AssociationFormat]) -> String {
+    let mut mimes: Vec<&str> = formats.iter().map(|f| f.mime).collect();
+    mimes.sort_unstable();
+    mimes.dedup();
+    format!(
+        "[Desktop Entry]\nType=Application\nName=milk\nComment=Desktop audio player inspired by Winamp\n\
+         Exec=\"{}\" %F\nIcon=milk\nTerminal=false\nCategories=AudioVideo;Audio;Player;\nMimeType={};\n\
+         Actions=Enqueue;\n\n[Desktop Action Enqueue]\nName=Enqueue in milk\nExec=\"{}\" --enqueue %F\n",
+        exe.display(),
+        mimes.join(";"),
+        exe.display()
+    )
+}
+
+/// shared-mime-info definition for skin files, which have no standard MIME type
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn skin_mime_package() -> String {
+    let globs: String = FORMATS
+        .iter()
+        .filter(|f| f.mime == "application/x-winamp-skin")
+        .map(|f| format!("    <glob pattern=\"*.{}\"/>\n", f.extension))
+        .collect();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <mime-info xmlns=\"http://www.freedesktop.org/standards/shared-mime-info\">\n\
+         \x20 <mime-type type=\"application/x-winamp-skin\">\n\
+         \x20   <comment>Winamp skin</comment>\n{}\
+         \x20 </mime-type>\n</mime-info>\n",
+        globs
+    )
+}
+
+/// `mimeapps.list` contents with milk dropped as the default handler
+///
+/// Entries left without a handler are removed so the desktop falls back to its own pick.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn without_default_handler(contents: &str) -> String {
+    let mut in_defaults = false;
+    let mut lines = Vec::new();
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_defaults = trimmed == "[Default Applications]";
+        } else if let Some((mime, handlers)) = trimmed.split_once('=').filter(|_| in_defaults) {
+            if handlers.split(';').any(|h| h == DESKTOP_FILE) {
+                let kept: Vec<&str> = handlers.split(';').filter(|h| !h.is_empty() && *h != DESKTOP_FILE).collect();
+                if !kept.is_empty() {
+                    lines.push(format!("{}={};", mime, kept.join(";")));
+                }
+                continue;
+            }
+        }
+        lines.push(line.to_string());
+    }
+    let mut updated = lines.join("\n");
+    if contents.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated
+}
+
+/// Tell Explorer the associations changed so icons and "Open with" refresh
+#[cfg(target_os = "windows")]
+fn notify_association_change() {
+    use windows::Win32::UI::Shell::{SHChangeNotify, SHCNE_ASSOCCHANGED, SHCNF_IDLIST};
+    unsafe { SHChangeNotify(SHCNE_ASSOCCHANGED, SHCNF_IDLIST, None, None) };
+}
+
+#[cfg_attr(not(any(target_os = "windows", target_os = "linux")), allow(dead_code))]
+fn run(program: &str, args: &[String]) -> Result<(), FileAssociationError> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| FileAssociationError::Command(format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(FileAssociationError::Command(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn xdg_data_dir() -> Result<std::path::PathBuf, FileAssociationError> {
+    dirs::data_dir().ok_or_else(|| FileAssociationError::Command("No user data directory".to_string()))
+}
+
+/// Register milk for `formats` (all formats when empty)
+pub fn register(requested: &[String]) -> Result<AssociationReport, FileAssociationError> {
+    let formats = formats_for(requested)?;
+    let exe = std::env::current_exe().map_err(FileAssociationError::Executable)?;
+    let registered = formats.iter().map(|f| f.extension.to_string()).collect();
+    register_for_platform(&exe, &formats, registered)
+}
+
+#[cfg(target_os = "windows")]
+fn register_for_platform(exe: &Path, formats: &[&AssociationFormat], registered: Vec<String>) -> Result<AssociationReport, FileAssociationError> {
+    for args in windows_registry_commands(exe, formats) {
+        run("reg", &args)?;
+    }
+    notify_association_change();
+    Ok(AssociationReport {
+        registered,
+        manual_steps: Some(
+            "milk was added to \"Open with\". To make it the default, choose Settings > Apps > Default apps.".to_string(),
+        ),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn register_for_platform(exe: &Path, formats: &[&AssociationFormat], registered: Vec<String>) -> Result<AssociationReport, FileAssociationError> {
+    let data_dir = xdg_data_dir()?;
+    let applications = data_dir.join("applications");
+    std::fs::create_dir_all(&applications)?;
+    std::fs::write(applications.join(DESKTOP_FILE), desktop_entry(exe, formats))?;
+
+    if formats.iter().any(|f| f.mime == "application/x-winamp-skin") {
+        let packages = data_dir.join("mime").join("packages");
+        std::fs::create_dir_all(&packages)?;
+        std::fs::write(packages.join("milk.xml"), skin_mime_package())?;
+        // Missing tools only mean the desktop picks the changes up later
+        let _ = run("update-mime-database", &[data_dir.join("mime").display().to_string()]);
+    }
+    let _ = run("update-desktop-database", &[applications.display().to_string()]);
+
+    let mut args = vec!["default".to_string(), DESKTOP_FILE.to_string()];
+    args.extend(formats.iter().map(|f| f.mime.to_string()));
+    run("xdg-mime", &args)?;
+    Ok(AssociationReport { registered, manual_steps: None })
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+fn register_for_platform(_exe: &Path, _formats: &[&AssociationFormat], _registered: Vec<String>) -> Result<AssociationReport, FileAssociationError> {
+    Ok(AssociationReport {
+        registered: Vec::new(),
+        manual_steps: Some(
+            "macOS reads file types from milk's Info.plist. Select a file in Finder, choose Get Info > Open with, \
+             pick milk and click Change All."
+                .to_string(),
+        ),
+    })
+}
+
+/// Remove every association milk registered
+pub fn unregister() -> Result<(), FileAssociationError> {
+    #[cfg(target_os = "windows")]
+    for format in FORMATS {
+        let prog_id = format!("{}{}", PROG_ID_PREFIX, format.extension);
+        // Deleting keys that were never created fails; that's fine
+        let _ = run("reg", &["delete".to_string(), format!(r"HKCU\Software\Classes\{}", prog_id), "/f".to_string()]);
+        let _ = run(
+            "reg",
+            &[
+                "delete".to_string(),
+                format!(r"HKCU\Software\Classes\.{}\OpenWithProgids", format.extension),
+                "/v".to_string(),
+                prog_id,
+                "/f".to_string(),
+            ],
+        );
+    }
+    #[cfg(target_os = "windows")]
+    notify_association_change();
+
+    #[cfg(target_os = "linux")]
+    {
+        let data_dir = xdg_data_dir()?;
+        let applications = data_dir.join("applications");
+        remove_if_exists(&applications.join(DESKTOP_FILE))?;
+        if remove_if_exists(&data_dir.join("mime").join("packages").join("milk.xml"))? {
+            let _ = run("update-mime-database", &[data_dir.join("mime").display().to_string()]);
+        }
+        let _ = run("update-desktop-database", &[applications.display().to_string()]);
+
+        // Undo the defaults `xdg-mime default` wrote
+        if let Some(mimeapps) = dirs::config_dir().map(|dir| dir.join("mimeapps.list")) {
+            match std::fs::read_to_string(&mimeapps) {
+                Ok(contents) => {
+                    let updated = without_default_handler(&contents);
+                    if updated != contents {
+                        std::fs::write(&mimeapps, updated)?;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn remove_if_exists(path: &Path) -> Result<bool, FileAssociationError> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_for() {
+        assert_eq!(formats_for(&[]).unwrap().len(), FORMATS.len());
+        let formats = formats_for(&[".MP3".to_string(), "wsz".to_string()]).unwrap();
+        assert_eq!(formats.iter().map(|f| f.extension).collect::<Vec<_>>(), vec!["mp3", "wsz"]);
+        assert!(matches!(
+            formats_for(&["exe".to_string()]),
+            Err(FileAssociationError::UnsupportedFormat(ext)) if ext == "exe"
+        ));
+    }
+
+    #[test]
+    fn test_windows_registry_commands() {
+        let formats = formats_for(&["mp3".to_string(), "wal".to_string()]).unwrap();
+        let commands = windows_registry_commands(Path::new(r"C:\milk\milk.exe"), &formats);
+        let open = commands
+            .iter()
+            .find(|args| args[1].ends_with(r"milk.mp3\shell\open\command"))
+            .unwrap();
+        assert_eq!(open[4], r#""C:\milk\milk.exe" "%1""#);
+        assert!(commands.iter().any(|args| args[1] == r"HKCU\Software\Classes\.wal\OpenWithProgids" && args[3] == "milk.wal"));
+        // Skins can't be enqueued
+        assert!(!commands.iter().any(|args| args[1].contains(r"milk.wal\shell\enqueue")));
+    }
+
+    #[test]
+    fn test_desktop_entry_lists_mime_types() {
+        let formats = formats_for(&["wsz".to_string(), "flac".to_string(), "wal".to_string()]).unwrap();
+        let entry = desktop_entry(Path::new("/opt/milk/milk"), &formats);
+        assert!(entry.contains("MimeType=application/x-winamp-skin;audio/flac;\n"));
+        assert!(entry.contains("Exec=\"/opt/milk/milk\" --enqueue %F"));
+        assert!(skin_mime_package().contains("<glob pattern=\"*.wal\"/>"));
+    }
+    #[test]
+    fn test_without_default_handler() {
+        let contents = "[Added Associations]\naudio/flac=milk.desktop;\n\n[Default Applications]\n\
+                        audio/flac=milk.desktop;\naudio/mpeg=vlc.desktop;milk.desktop;\nvideo/mp4=mpv.desktop\n";
+        assert_eq!(
+            without_default_handler(contents),
+            "[Added Associations]\naudio/flac=milk.desktop;\n\n[Default Applications]\n\
+             audio/mpeg=vlc.desktop;\nvideo/mp4=mpv.desktop\n"
+        );
+    }
+}
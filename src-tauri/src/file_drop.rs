@@ -0,0 +1,212 @@
+// Drag-and-drop import
+// Classifies paths dropped on a window and imports them: audio files,
+// folders and playlist files become queue entries, skins are copied into the
+// skin library. The result goes to the frontend as one `files-imported` event.
+
+use crate::error_recovery::ErrorRecovery;
+use crate::library::LibraryScanner;
+use crate::metadata::MetadataExtractor;
+use crate::playlist::Track;
+use crate::queue_import::{expand_folder, is_playlist_file, read_playlist_file, track_for_file, EnqueueMode};
+use crate::skin::SkinParser;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What a dropped path is
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DroppedKind {
+    Audio,
+    Folder,
+    Playlist,
+    Skin,
+    Unsupported,
+}
+
+pub fn classify(path: &Path) -> DroppedKind {
+    if path.is_dir() {
+        return DroppedKind::Folder;
+    }
+    if !path.is_file() {
+        return DroppedKind::Unsupported;
+    }
+    let extension = path.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+    if crate::is_skin_file(&path.to_string_lossy()) {
+        DroppedKind::Skin
+    } else if is_playlist_file(path) {
+        DroppedKind::Playlist
+    } else if LibraryScanner::is_supported_extension(&extension) {
+        DroppedKind::Audio
+    } else {
+        DroppedKind::Unsupported
+    }
+}
+
+/// A dropped path that could not be imported
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportFailure {
+    pub path: String,
+    pub kind: DroppedKind,
+    pub message: String,
+}
+
+/// Payload of the `files-imported` event
+#[derive(Debug, Clone, Serialize)]
+pub struct FilesImported {
+    /// Label of the window the files were dropped on
+    pub window: String,
+    /// Tracks to enqueue, in drop order with folders expanded recursively
+    pub tracks: Vec<Track>,
+    pub folders_scanned: usize,
+    pub playlists_read: usize,
+    /// Paths of skins now in the skin library
+    pub skins_installed: Vec<String>,
+    /// Paths that are not audio, folders, playlists or skins
+    pub skipped: Vec<String>,
+    pub failed: Vec<ImportFailure>,
+}
+
+/// Import every dropped path; skins are installed into `skin_dir`
+pub fn import_paths(window: &str, paths: &[PathBuf], skin_dir: Option<&Path>, extractor: &MetadataExtractor) -> FilesImported {
+    let mut result = FilesImported {
+        window: window.to_string(),
+        tracks: Vec::new(),
+        folders_scanned: 0,
+        playlists_read: 0,
+        skins_installed: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for path in paths {
+        let display = path.to_string_lossy().to_string();
+        let kind = classify(path);
+        let outcome = match kind {
+            DroppedKind::Audio => {
+                result.tracks.push(track_for_file(path, extractor));
+                Ok(())
+            }
+            DroppedKind::Folder => expand_folder(path, EnqueueMode::Recursive, extractor)
+                .map(|summary| {
+                    result.folders_scanned += summary.folders_scanned;
+                    result.tracks.extend(summary.tracks);
+                })
+                .map_err(|e| e.to_string()),
+            DroppedKind::Playlist => read_playlist_file(path)
+                .map(|entries| {
+                    result.playlists_read += 1;
+                    for entry in entries {
+                        if classify(&entry) == DroppedKind::Audio {
+                            result.tracks.push(track_for_file(&entry, extractor));
+                        } else {
+                            result.skipped.push(entry.to_string_lossy().to_string());
+                        }
+                    }
+                })
+                .map_err(|e| e.to_string()),
+            DroppedKind::Skin => match skin_dir {
+                Some(dir) => install_skin(path, dir).map(|installed| {
+                    result.skins_installed.push(installed.to_string_lossy().to_string());
+                }),
+                None => Err("No skin library directory available".to_string()),
+            },
+            DroppedKind::Unsupported => {
+                result.skipped.push(display.clone());
+                Ok(())
+            }
+        };
+        if let Err(message) = outcome {
+            result.failed.push(ImportFailure { path: display, kind, message });
+        }
+    }
+    result
+}
+
+/// Validate a skin and copy it into `skin_dir`, returning the installed path
+///
+/// A skin already in the library, or an identical copy of it, is not copied again.
+fn install_skin(path: &Path, skin_dir: &Path) -> Result<PathBuf, String> {
+    SkinParser::parse_wsz(path).map_err(|e| format!("Not a valid skin: {}", e))?;
+    if path.parent() == Some(skin_dir) {
+        return Ok(path.to_path_buf());
+    }
+
+    let contents = fs::read(path).map_err(|e| e.to_string())?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let (stem, extension) = file_name.rsplit_once('.').unwrap_or((&file_name, "wsz"));
+    let mut destination = skin_dir.join(&file_name);
+    let mut copy = 1;
+    while destination.exists() {
+        if fs::read(&destination).is_ok_and(|existing| existing == contents) {
+            return Ok(destination);
+        }
+        copy += 1;
+        destination = skin_dir.join(format!("{} ({}).{}", stem, copy, extension));
+    }
+
+    fs::create_dir_all(skin_dir).map_err(|e| e.to_string())?;
+    ErrorRecovery::check_disk_space(skin_dir, contents.len() as u64).map_err(|e| e.to_string())?;
+    fs::write(&destination, contents).map_err(|e| e.to_string())?;
+    Ok(destination)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn write_skin(path: &Path) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        zip.start_file("main.bmp", zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(b"BM").unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_import_mixed_drop() {
+        let dir = TempDir::new().unwrap();
+        let album = dir.path().join("album");
+        fs::create_dir_all(&album).unwrap();
+        for name in ["2 - two.wav", "1 - one.wav"] {
+            fs::write(album.join(name), b"").unwrap();
+        }
+        fs::write(dir.path().join("single.wav"), b"").unwrap();
+        fs::write(dir.path().join("list.m3u"), "single.wav\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), b"").unwrap();
+        fs::write(dir.path().join("broken.wsz"), b"not a zip").unwrap();
+        write_skin(&dir.path().join("classic.wsz"));
+
+        let paths: Vec<PathBuf> = ["album", "list.m3u", "classic.wsz", "broken.wsz", "notes.txt"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        let skin_dir = dir.path().join("skins");
+        let result = import_paths("main", &paths, Some(&skin_dir), &MetadataExtractor::new());
+
+        let titles: Vec<_> = result.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["one", "two", "single"]);
+        assert_eq!((result.folders_scanned, result.playlists_read), (1, 1));
+        assert_eq!(result.skins_installed, [skin_dir.join("classic.wsz").to_string_lossy()]);
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(result.failed[0].kind, DroppedKind::Skin);
+    }
+
+    #[test]
+    fn test_install_skin_reuses_identical_copy() {
+        let dir = TempDir::new().unwrap();
+        let skin_dir = dir.path().join("skins");
+        let skin = dir.path().join("classic.wsz");
+        write_skin(&skin);
+
+        let first = install_skin(&skin, &skin_dir).unwrap();
+        assert_eq!(install_skin(&skin, &skin_dir).unwrap(), first);
+
+        fs::write(skin_dir.join("other.wsz"), b"different").unwrap();
+        let other = dir.path().join("other.wsz");
+        write_skin(&other);
+        assert_eq!(install_skin(&other, &skin_dir).unwrap(), skin_dir.join("other (2).wsz"));
+    }
+}
@@ -0,0 +1,842 @@
+// Headless daemon mode
+// `milkline --headless [files, folders or playlists]` runs without a window,
+// as a music daemon on a home server. Tracks are decoded by FFmpeg into an
+// output device, or a Snapcast server for multi-room playback, and the remote
+// API is the control surface. Devices marked bit-perfect are held exclusively
+// at each file's own sample rate, with the volume at full and no DSP. Otherwise
+// the DSP chain runs before the volume. The output feeds the
+// visualizer whenever system audio capture is not running. Playback is reported
+// the same way the windowed player reports it, so the session, play history,
+// plugins and the unified now playing stay current.
+
+use crate::config::{OutputMode, OutputSettings};
+use crate::dsp_chain::DspChain;
+use crate::exclusive_output::{self, ExclusiveError, ExclusiveStream};
+use crate::logging::{log_error, log_info, log_warn};
+use crate::media_session::{MediaKeyAction, NowPlaying, PlaybackStatus};
+use crate::playlist::Track;
+use crate::session::RepeatMode;
+use crate::snapcast::{self, SnapcastStream};
+use crate::audio_transport::SampleRing;
+use crate::visualizer_feed::AudioTap;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use thiserror::Error;
+
+/// How often the control loop checks for the end of a track
+const TICK: Duration = Duration::from_millis(250);
+
+/// How often the position is reported while playing
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Decoded audio kept ahead of the output
+const BUFFER_SECS: usize = 2;
+
+/// Ring size for exclusive output until the device reports its format
+const EXCLUSIVE_BUFFER_FORMAT: (u32, u16) = (48_000, 2);
+
+/// Rates reported by `list_output_devices`
+const COMMON_SAMPLE_RATES: [u32; 8] = [44_100, 48_000, 88_200, 96_000, 176_400, 192_000, 352_800, 384_000];
+
+/// "Previous" restarts the track instead once it has played this long
+const RESTART_THRESHOLD_SECS: f64 = 3.0;
+
+#[derive(Error, Debug)]
+pub enum HeadlessError {
+    #[error("No audio output device available")]
+    NoOutputDevice,
+    #[error("Audio output failed: {0}")]
+    Output(String),
+    #[error(transparent)]
+    Exclusive(#[from] ExclusiveError),
+}
+
+/// Sent to the control loop
+pub enum PlayerCommand {
+    Control(MediaKeyAction),
+    /// Switch outputs, carrying on from the same position
+    SetOutput(OutputSettings),
+    /// Replace the effects applied to the output
+    SetDsp(DspChain),
+    Quit,
+}
+
+/// The play queue and the position in it
+#[derive(Debug, Clone, Default)]
+pub struct PlayerQueue {
+    pub tracks: Vec<Track>,
+    pub index: Option<usize>,
+    pub repeat: RepeatMode,
+}
+
+impl PlayerQueue {
+    pub fn current(&self) -> Option<&Track> {
+        self.tracks.get(self.index?)
+    }
+
+    /// Move to the next track; `manual` skips ignore repeating one track
+    ///
+    /// Returns false at the end of the queue unless it repeats.
+    pub fn advance(&mut self, manual: bool) -> bool {
+        if self.tracks.is_empty() {
+            return false;
+        }
+        let next = match (self.index, self.repeat) {
+            (Some(i), RepeatMode::One) if !manual => Some(i),
+            (Some(i), _) if i + 1 < self.tracks.len() => Some(i + 1),
+            (Some(_), RepeatMode::Off) => None,
+            (Some(_), _) | (None, _) => Some(0),
+        };
+        self.index = next.or(self.index);
+        next.is_some()
+    }
+
+    /// Move to the previous track, wrapping around when the queue repeats
+    pub fn back(&mut self) -> bool {
+        let previous = match (self.index, self.repeat) {
+            (Some(0), RepeatMode::All) => self.tracks.len().checked_sub(1),
+            (Some(0), _) | (None, _) => None,
+            (Some(i), _) => Some(i - 1),
+        };
+        self.index = previous.or(self.index);
+        previous.is_some()
+    }
+}
+
+/// State shared with the output callback and the decoder thread
+///
+/// The output callback never blocks: decoded samples arrive through a
+/// lock-free ring, and the visualizer analysis runs on the tap's worker.
+struct OutputBuffer {
+    samples: SampleRing,
+    /// Held by the decoder while it pushes and by `reset`, so a superseded
+    /// decoder cannot push after the ring is cleared
+    producer: Mutex<()>,
+    played_frames: AtomicU64,
+    /// `f32` bits
+    volume: AtomicU32,
+    /// Only ever `try_lock`ed by the callback; swapped in by `set_dsp`
+    dsp: Mutex<DspChain>,
+    tap: Arc<AudioTap>,
+    /// Bumped on every load so a superseded decoder stops
+    generation: AtomicU64,
+    decoded_all: AtomicBool,
+}
+
+impl OutputBuffer {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            samples: SampleRing::new(sample_rate as usize * channels as usize * BUFFER_SECS),
+            producer: Mutex::new(()),
+            played_frames: AtomicU64::new(0),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            dsp: Mutex::new(DspChain::default()),
+            tap: AudioTap::spawn(sample_rate, channels as usize),
+            generation: AtomicU64::new(0),
+            decoded_all: AtomicBool::new(true),
+        }
+    }
+
+    /// Fill an output buffer, padding with silence when decoding falls behind
+    fn fill(&self, data: &mut [f32], channels: usize) {
+        let volume = f32::from_bits(self.volume.load(Ordering::Relaxed));
+        let available = self.samples.len().min(data.len()) / channels * channels;
+        let available = self.samples.pop(&mut data[..available]);
+        // The chain is only locked for a swap; a buffer that lands mid-swap
+        // plays without effects rather than waiting
+        if let Ok(dsp) = self.dsp.try_lock() {
+            dsp.process(&mut data[..available], channels);
+        }
+        self.tap.push(&data[..available]);
+        data[..available].iter_mut().for_each(|sample| *sample *= volume);
+        data[available..].fill(0.0);
+        self.played_frames.fetch_add((available / channels) as u64, Ordering::Relaxed);
+    }
+
+    /// Start over for a new decoder, returning its generation
+    fn reset(&self) -> u64 {
+        let _producer = self.producer.lock().unwrap();
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        self.samples.clear();
+        self.played_frames.store(0, Ordering::Relaxed);
+        generation
+    }
+}
+
+enum OutputTarget {
+    /// Shared through the OS mixer, in the device's own format; not `Send` on
+    /// every platform, so the output stays on the control thread
+    Device(cpal::Stream),
+    /// Bit-perfect, bypassing the OS mixer; `None` while the device is let go
+    Exclusive { device: Option<String>, stream: Option<ExclusiveStream> },
+    Snapcast(SnapcastStream),
+}
+
+/// An output device and the sample rates it can be opened at
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OutputDevice {
+    pub name: String,
+    pub is_default: bool,
+    /// Rates the device can be opened at, ascending
+    pub sample_rates: Vec<u32>,
+    /// Whether the device can play bit-perfect through exclusive output here
+    pub bit_perfect: bool,
+}
+
+/// An output device or Snapcast server, fed from an FFmpeg decoder
+struct AudioOutput {
+    target: OutputTarget,
+    buffer: Arc<OutputBuffer>,
+    sample_rate: u32,
+    channels: u16,
+    /// Where decoding of the loaded track started
+    start_secs: f64,
+    playing: bool,
+}
+
+impl AudioOutput {
+    fn open(settings: &OutputSettings) -> Result<Self, HeadlessError> {
+        match settings.mode {
+            OutputMode::Local => {
+                let bit_perfect = settings.device.as_ref().is_some_and(|d| settings.bit_perfect_devices.contains(d));
+                if bit_perfect {
+                    match Self::open_exclusive(settings.device.as_deref()) {
+                        Ok(output) => return Ok(output),
+                        Err(e) => log_warn("Headless", &format!("Bit-perfect output unavailable, using the shared mixer: {}", e)),
+                    }
+                }
+                Self::open_device(settings.device.as_deref())
+            }
+            OutputMode::Snapcast => Ok(Self::open_snapcast(&settings.snapcast_host, settings.snapcast_port)),
+        }
+    }
+
+    /// Open the named device, or the default one when it is gone or not given
+    fn open_device(name: Option<&str>) -> Result<Self, HeadlessError> {
+        let host = cpal::default_host();
+        let named = name.and_then(|name| {
+            let found = host.output_devices().ok()?.find(|d| d.name().is_ok_and(|n| n == name));
+            if found.is_none() {
+                log_warn("Headless", &format!("Output device {} not found, using the default", name));
+            }
+            found
+        });
+        let device = named.or_else(|| host.default_output_device()).ok_or(HeadlessError::NoOutputDevice)?;
+        let supported = device.default_output_config().map_err(|e| HeadlessError::Output(e.to_string()))?;
+        let format = supported.sample_format();
+        let config = supported.config();
+        let buffer = Arc::new(OutputBuffer::new(config.sample_rate.0, config.channels));
+        let stream = build_stream(&device, &config, format, &buffer)?;
+        Ok(Self {
+            target: OutputTarget::Device(stream),
+            buffer,
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+            start_secs: 0.0,
+            playing: false,
+        })
+    }
+
+    /// Hold the named device exclusively at its current rate
+    fn open_exclusive(name: Option<&str>) -> Result<Self, HeadlessError> {
+        let (sample_rate, channels) = EXCLUSIVE_BUFFER_FORMAT;
+        let buffer = Arc::new(OutputBuffer::new(sample_rate, channels));
+        let stream = open_exclusive_stream(name, None, &buffer)?;
+        let (sample_rate, channels) = (stream.sample_rate(), stream.channels());
+        buffer.tap.set_format(sample_rate, channels as usize);
+        Ok(Self {
+            target: OutputTarget::Exclusive { device: name.map(str::to_string), stream: Some(stream) },
+            buffer,
+            sample_rate,
+            channels,
+            start_secs: 0.0,
+            playing: false,
+        })
+    }
+
+    /// Reopen exclusive output at the file's own rate so nothing is resampled
+    fn match_sample_rate(&mut self, path: &Path) {
+        let OutputTarget::Exclusive { device, stream } = &mut self.target else {
+            return;
+        };
+        let Some(rate) = probe_sample_rate(path).filter(|rate| *rate != self.sample_rate) else {
+            return;
+        };
+        // The device can only be held once, so the old stream is closed first
+        stream.take();
+        let reopened = open_exclusive_stream(device.as_deref(), Some(rate), &self.buffer).or_else(|e| {
+            log_warn("Headless", &format!("Resampling to {} Hz: {}", self.sample_rate, e));
+            open_exclusive_stream(device.as_deref(), Some(self.sample_rate), &self.buffer)
+        });
+        match reopened {
+            Ok(reopened) => {
+                self.sample_rate = reopened.sample_rate();
+                self.channels = reopened.channels();
+                self.buffer.tap.set_format(self.sample_rate, self.channels as usize);
+                *stream = Some(reopened);
+                if self.playing {
+                    self.resume();
+                }
+            }
+            Err(e) => log_error("Headless", &format!("Lost bit-perfect output: {}", e)),
+        }
+    }
+
+    /// Let go of an exclusively held device so it can be opened again
+    fn release(&mut self) {
+        if let OutputTarget::Exclusive { stream, .. } = &mut self.target {
+            stream.take();
+        }
+    }
+
+    /// Take the device back after `release`, at the current rate
+    fn reacquire(&mut self) {
+        if let OutputTarget::Exclusive { device, stream: stream @ None } = &mut self.target {
+            match open_exclusive_stream(device.as_deref(), Some(self.sample_rate), &self.buffer) {
+                Ok(reopened) => *stream = Some(reopened),
+                Err(e) => log_error("Headless", &format!("Lost bit-perfect output: {}", e)),
+            }
+        }
+    }
+
+    fn open_snapcast(host: &str, port: u16) -> Self {
+        let buffer = Arc::new(OutputBuffer::new(snapcast::SAMPLE_RATE, snapcast::CHANNELS));
+        let stream_buffer = buffer.clone();
+        let stream = SnapcastStream::start(host, port, move |data| stream_buffer.fill(data, snapcast::CHANNELS as usize));
+        Self {
+            target: OutputTarget::Snapcast(stream),
+            buffer,
+            sample_rate: snapcast::SAMPLE_RATE,
+            channels: snapcast::CHANNELS,
+            start_secs: 0.0,
+            playing: false,
+        }
+    }
+
+    /// Start decoding `path` from `start_secs`, replacing what was loaded
+    fn load(&mut self, path: PathBuf, start_secs: f64) {
+        let generation = self.buffer.reset();
+        self.match_sample_rate(&path);
+        self.buffer.decoded_all.store(false, Ordering::SeqCst);
+        self.start_secs = start_secs;
+        let buffer = self.buffer.clone();
+        let (sample_rate, channels) = (self.sample_rate, self.channels);
+        std::thread::spawn(move || decode(&buffer, generation, &path, start_secs, sample_rate, channels));
+    }
+
+    /// Drop the loaded track
+    fn unload(&mut self) {
+        self.buffer.reset();
+        self.buffer.decoded_all.store(true, Ordering::SeqCst);
+        self.start_secs = 0.0;
+    }
+
+    fn position_secs(&self) -> f64 {
+        self.start_secs + self.buffer.played_frames.load(Ordering::Relaxed) as f64 / self.sample_rate as f64
+    }
+
+    /// Everything decoded has been played
+    fn finished(&self) -> bool {
+        self.buffer.decoded_all.load(Ordering::SeqCst) && self.buffer.samples.is_empty()
+    }
+
+    fn set_volume(&self, volume: f64) {
+        if matches!(self.target, OutputTarget::Exclusive { .. }) {
+            return log_info("Headless", "Volume stays at full for bit-perfect output");
+        }
+        self.buffer.volume.store((volume.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Relaxed);
+    }
+
+    fn set_dsp(&self, chain: &DspChain) {
+        if matches!(self.target, OutputTarget::Exclusive { .. }) {
+            if !chain.effects.is_empty() {
+                log_info("Headless", "DSP is bypassed for bit-perfect output");
+            }
+            return;
+        }
+        let previous = std::mem::replace(&mut *self.buffer.dsp.lock().unwrap(), chain.clone());
+        drop(previous);
+    }
+
+    fn resume(&mut self) {
+        self.playing = true;
+        match &self.target {
+            OutputTarget::Device(stream) => {
+                if let Err(e) = stream.play() {
+                    log_error("Headless", &format!("Failed to start audio output: {}", e));
+                }
+            }
+            OutputTarget::Exclusive { stream, .. } => stream.iter().for_each(ExclusiveStream::play),
+            OutputTarget::Snapcast(stream) => stream.play(),
+        }
+    }
+
+    fn pause(&mut self) {
+        self.playing = false;
+        match &self.target {
+            OutputTarget::Device(stream) => {
+                if let Err(e) = stream.pause() {
+                    log_warn("Headless", &format!("Failed to pause audio output: {}", e));
+                }
+            }
+            OutputTarget::Exclusive { stream, .. } => stream.iter().for_each(ExclusiveStream::pause),
+            OutputTarget::Snapcast(stream) => stream.pause(),
+        }
+    }
+}
+
+fn build_stream(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    format: SampleFormat,
+    buffer: &Arc<OutputBuffer>,
+) -> Result<cpal::Stream, HeadlessError> {
+    match format {
+        SampleFormat::F32 => build_typed_stream::<f32>(device, config, buffer),
+        SampleFormat::F64 => build_typed_stream::<f64>(device, config, buffer),
+        SampleFormat::I16 => build_typed_stream::<i16>(device, config, buffer),
+        SampleFormat::I32 => build_typed_stream::<i32>(device, config, buffer),
+        SampleFormat::U16 => build_typed_stream::<u16>(device, config, buffer),
+        SampleFormat::U8 => build_typed_stream::<u8>(device, config, buffer),
+        SampleFormat::I8 => build_typed_stream::<i8>(device, config, buffer),
+        other => Err(HeadlessError::Output(format!("unsupported sample format {:?}", other))),
+    }
+}
+
+/// A stream in the device's sample type, converting the buffer's `f32` samples
+fn build_typed_stream<T>(device: &cpal::Device, config: &cpal::StreamConfig, buffer: &Arc<OutputBuffer>) -> Result<cpal::Stream, HeadlessError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let buffer = buffer.clone();
+    let mut scratch: Vec<f32> = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                scratch.resize(data.len(), 0.0);
+                buffer.fill(&mut scratch, channels);
+                for (out, &sample) in data.iter_mut().zip(&scratch) {
+                    *out = T::from_sample(sample);
+                }
+            },
+            |e| log_warn("Headless", &format!("Audio output error: {}", e)),
+            None,
+        )
+        .map_err(|e| HeadlessError::Output(e.to_string()))
+}
+
+/// Exclusive output of `name` at `sample_rate`, filled from `buffer`
+fn open_exclusive_stream(
+    name: Option<&str>,
+    sample_rate: Option<u32>,
+    buffer: &Arc<OutputBuffer>,
+) -> Result<ExclusiveStream, ExclusiveError> {
+    let buffer = buffer.clone();
+    ExclusiveStream::open(name, sample_rate, move |data, channels| buffer.fill(data, channels))
+}
+
+/// Sample rate of the file's first audio stream
+fn probe_sample_rate(path: &Path) -> Option<u32> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=sample_rate", "-of", "csv=p=0"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+}
+
+/// Output devices with the sample rates they support
+pub fn list_output_devices() -> Result<Vec<OutputDevice>, HeadlessError> {
+    let host = cpal::default_host();
+    let default_name = host.default_output_device().and_then(|d| d.name().ok());
+    let devices = host.output_devices().map_err(|e| HeadlessError::Output(e.to_string()))?;
+    Ok(devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let mut sample_rates: Vec<u32> = device
+                .supported_output_configs()
+                .map(|ranges| {
+                    ranges
+                        .flat_map(|range| {
+                            let span = range.min_sample_rate().0..=range.max_sample_rate().0;
+                            COMMON_SAMPLE_RATES.into_iter().filter(move |rate| span.contains(rate))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            sample_rates.sort_unstable();
+            sample_rates.dedup();
+            Some(OutputDevice {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                name,
+                sample_rates,
+                bit_perfect: exclusive_output::SUPPORTED,
+            })
+        })
+        .collect())
+}
+
+/// Stream interleaved `f32` samples from FFmpeg into `buffer` until the file
+/// ends or a newer load takes over
+fn decode(buffer: &OutputBuffer, generation: u64, path: &Path, start_secs: f64, sample_rate: u32, channels: u16) {
+    let current = || buffer.generation.load(Ordering::SeqCst) == generation;
+    let child = Command::new("ffmpeg")
+        .args(["-v", "error", "-ss", &format!("{:.3}", start_secs), "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", &channels.to_string(), "-ar", &sample_rate.to_string(), "-f", "f32le", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            log_error("Headless", &format!("Failed to execute FFmpeg: {}", e));
+            if current() {
+                buffer.decoded_all.store(true, Ordering::SeqCst);
+            }
+            return;
+        }
+    };
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let mut chunk = vec![0u8; 16 * 1024];
+    let mut pending = Vec::new();
+    // Decoded samples the ring had no room for yet
+    let mut decoded: Vec<f32> = Vec::new();
+    while current() {
+        if !decoded.is_empty() {
+            let producer = buffer.producer.lock().unwrap();
+            if !current() {
+                break;
+            }
+            let pushed = buffer.samples.push(&decoded);
+            drop(producer);
+            decoded.drain(..pushed);
+            if !decoded.is_empty() {
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            continue;
+        }
+        let read = match stdout.read(&mut chunk) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        pending.extend_from_slice(&chunk[..read]);
+        let whole = pending.len() / 4 * 4;
+        decoded.extend(pending[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+        pending.drain(..whole);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    if current() {
+        buffer.decoded_all.store(true, Ordering::SeqCst);
+    }
+}
+
+struct HeadlessPlayer {
+    queue: PlayerQueue,
+    output: AudioOutput,
+    /// Kept to reapply when the output changes
+    dsp: DspChain,
+    volume: f64,
+    status: PlaybackStatus,
+    last_report: Instant,
+}
+
+impl HeadlessPlayer {
+    fn handle(&mut self, action: MediaKeyAction) {
+        match action {
+            MediaKeyAction::Play => self.play(),
+            MediaKeyAction::Pause => self.pause(),
+            MediaKeyAction::Toggle if self.status == PlaybackStatus::Playing => self.pause(),
+            MediaKeyAction::Toggle => self.play(),
+            MediaKeyAction::Next => {
+                if self.queue.advance(true) {
+                    self.start_track(0.0);
+                }
+            }
+            MediaKeyAction::Previous => {
+                if self.output.position_secs() > RESTART_THRESHOLD_SECS || !self.queue.back() {
+                    self.seek(0.0);
+                } else {
+                    self.start_track(0.0);
+                }
+            }
+            MediaKeyAction::Stop => self.stop(),
+            MediaKeyAction::SeekBy { offset_secs } => self.seek(self.output.position_secs() + offset_secs),
+            MediaKeyAction::SeekTo { position_secs } => self.seek(position_secs),
+            MediaKeyAction::SetVolume { volume } => {
+                self.volume = volume;
+                self.output.set_volume(volume);
+            }
+        }
+    }
+
+    /// Resume, or start the current (or first) track when stopped
+    fn play(&mut self) {
+        match self.status {
+            PlaybackStatus::Playing => {}
+            PlaybackStatus::Paused => {
+                self.output.resume();
+                self.set_status(PlaybackStatus::Playing);
+            }
+            PlaybackStatus::Stopped => {
+                if self.queue.current().is_some() || self.queue.advance(true) {
+                    self.start_track(0.0);
+                }
+            }
+        }
+    }
+
+    /// Reopen the output and pick up where the old one was
+    fn set_output(&mut self, settings: &OutputSettings) {
+        self.output.pause();
+        // A device held exclusively has to be let go before it can be opened again
+        self.output.release();
+        let mut output = match AudioOutput::open(settings) {
+            Ok(output) => output,
+            Err(e) => {
+                self.output.reacquire();
+                if self.status == PlaybackStatus::Playing {
+                    self.output.resume();
+                }
+                return log_error("Headless", &format!("Keeping the current output: {}", e));
+            }
+        };
+        output.pause();
+        let position_secs = self.output.position_secs();
+        self.output.unload();
+        self.output = output;
+        self.output.set_volume(self.volume);
+        self.output.set_dsp(&self.dsp);
+        log_info("Headless", &format!("Output switched to {:?}", settings.mode));
+        if self.status == PlaybackStatus::Stopped {
+            return;
+        }
+        if let Some(path) = self.queue.current().and_then(local_path) {
+            self.output.load(path, position_secs);
+        }
+        if self.status == PlaybackStatus::Playing {
+            self.output.resume();
+        }
+    }
+
+    fn set_dsp(&mut self, chain: DspChain) {
+        self.output.set_dsp(&chain);
+        self.dsp = chain;
+        log_info("Headless", &format!("DSP chain set to {} effect(s)", self.dsp.effects.len()));
+    }
+
+    fn pause(&mut self) {
+        if self.status == PlaybackStatus::Playing {
+            self.output.pause();
+            self.set_status(PlaybackStatus::Paused);
+        }
+    }
+
+    fn stop(&mut self) {
+        self.output.pause();
+        self.output.unload();
+        self.set_status(PlaybackStatus::Stopped);
+    }
+
+    fn seek(&mut self, position_secs: f64) {
+        if self.status == PlaybackStatus::Stopped {
+            return;
+        }
+        let Some(path) = self.queue.current().and_then(local_path) else {
+            return;
+        };
+        self.output.load(path, position_secs.max(0.0));
+        self.report(false);
+    }
+
+    /// Load and play the current track; streaming entries are skipped
+    fn start_track(&mut self, mut start_secs: f64) {
+        for _ in 0..self.queue.tracks.len() {
+            let Some(track) = self.queue.current() else {
+                break;
+            };
+            let Some(path) = local_path(track) else {
+                log_warn("Headless", &format!("Skipping {}: only local files can be played headless", track.title));
+                if !self.queue.advance(true) {
+                    break;
+                }
+                start_secs = 0.0;
+                continue;
+            };
+            self.output.load(path, start_secs);
+            self.output.resume();
+            self.status = PlaybackStatus::Playing;
+            crate::update_remote_queue(self.queue.tracks.clone(), self.queue.index);
+            return self.report(true);
+        }
+        self.stop();
+    }
+
+    fn set_status(&mut self, status: PlaybackStatus) {
+        self.status = status;
+        self.report(false);
+    }
+
+    fn report(&mut self, track_changed: bool) {
+        let track = self.queue.current().filter(|_| track_changed).map(NowPlaying::from);
+        let position_secs = (self.status != PlaybackStatus::Stopped).then(|| self.output.position_secs());
+        crate::record_playback(None, track, self.status, position_secs);
+        if let Some(position_secs) = position_secs {
+            crate::save_resume_position(position_secs);
+        }
+        self.last_report = Instant::now();
+    }
+
+    /// Move on at the end of a track and keep the position current
+    fn tick(&mut self) {
+        if self.status != PlaybackStatus::Playing {
+            return;
+        }
+        if self.output.finished() {
+            if self.queue.advance(false) {
+                self.start_track(0.0);
+            } else {
+                log_info("Headless", "Reached the end of the queue");
+                self.stop();
+            }
+        } else if self.last_report.elapsed() >= REPORT_INTERVAL {
+            self.report(false);
+        }
+    }
+}
+
+fn local_path(track: &Track) -> Option<PathBuf> {
+    track.file_path.as_deref().map(crate::path_codec::decode)
+}
+
+/// Run the player on the calling thread until `Quit` arrives
+///
+/// Playback starts right away at `start_secs` when the queue has a current track.
+pub fn run(
+    queue: PlayerQueue,
+    start_secs: f64,
+    output: &OutputSettings,
+    dsp: &DspChain,
+    commands: Receiver<PlayerCommand>,
+) -> Result<(), HeadlessError> {
+    let mut output = AudioOutput::open(output)?;
+    output.pause();
+    output.set_dsp(dsp);
+    let mut player = HeadlessPlayer {
+        queue,
+        output,
+        dsp: dsp.clone(),
+        volume: 1.0,
+        status: PlaybackStatus::Stopped,
+        last_report: Instant::now(),
+    };
+    crate::update_remote_queue(player.queue.tracks.clone(), player.queue.index);
+    if player.queue.current().is_some() {
+        player.start_track(start_secs);
+    }
+    loop {
+        match commands.recv_timeout(TICK) {
+            Ok(PlayerCommand::Control(action)) => player.handle(action),
+            Ok(PlayerCommand::SetOutput(settings)) => player.set_output(&settings),
+            Ok(PlayerCommand::SetDsp(chain)) => player.set_dsp(chain),
+            Ok(PlayerCommand::Quit) | Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        player.tick();
+    }
+    player.stop();
+    Ok(())
+}
+
+/// A remote API control sink that drives the headless player
+pub fn control_sink(commands: Sender<PlayerCommand>) -> crate::remote_api::ControlSink {
+    Arc::new(move |action| {
+        let _ = commands.send(PlayerCommand::Control(action));
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::TrackMetadata;
+
+    fn queue(len: usize, repeat: RepeatMode) -> PlayerQueue {
+        let tracks = (0..len)
+            .map(|i| Track {
+                id: i.to_string(),
+                title: i.to_string(),
+                artist: String::new(),
+                album: String::new(),
+                duration: 0.0,
+                file_path: Some(format!("/music/{}.mp3", i)),
+                source: "local".to_string(),
+                metadata: TrackMetadata { year: None, genre: None, track_number: None, disc_number: None, album_art: None },
+            })
+            .collect();
+        PlayerQueue { tracks, index: None, repeat }
+    }
+
+    #[test]
+    fn test_queue_navigation() {
+        let mut off = queue(2, RepeatMode::Off);
+        assert!(!off.back());
+        assert!(off.advance(false) && off.advance(false));
+        assert!(!off.advance(false));
+        assert_eq!(off.index, Some(1));
+        assert!(off.back());
+        assert_eq!(off.index, Some(0));
+
+        let mut one = queue(2, RepeatMode::One);
+        one.index = Some(1);
+        assert!(one.advance(false));
+        assert_eq!(one.index, Some(1));
+        assert!(one.advance(true));
+        assert_eq!(one.index, Some(0));
+
+        let mut all = queue(2, RepeatMode::All);
+        all.index = Some(0);
+        assert!(all.back());
+        assert_eq!(all.index, Some(1));
+        assert!(all.advance(false));
+        assert_eq!(all.index, Some(0));
+        assert!(!queue(0, RepeatMode::All).advance(true));
+    }
+
+    #[test]
+    fn test_output_fill() {
+        let buffer = OutputBuffer::new(48_000, 2);
+        buffer.samples.push(&[0.5, -0.5, 0.25, -0.25, 1.0]);
+        buffer.volume.store(0.5f32.to_bits(), Ordering::Relaxed);
+        let mut data = [1.0f32; 6];
+        buffer.fill(&mut data, 2);
+        // Only whole frames are played; the odd sample waits for its pair
+        assert_eq!(data, [0.25, -0.25, 0.125, -0.125, 0.0, 0.0]);
+        assert_eq!(buffer.played_frames.load(Ordering::Relaxed), 2);
+        assert_eq!(buffer.samples.len(), 1);
+    }
+
+    #[test]
+    fn test_output_fill_applies_dsp_before_volume() {
+        use crate::dsp_chain::DspEffect;
+        let buffer = OutputBuffer::new(48_000, 2);
+        buffer.samples.push(&[0.8, 0.4]);
+        buffer.volume.store(0.5f32.to_bits(), Ordering::Relaxed);
+        *buffer.dsp.lock().unwrap() = DspChain { effects: vec![DspEffect::MonoDownmix] };
+        let mut data = [1.0f32; 4];
+        buffer.fill(&mut data, 2);
+        assert_eq!(data, [0.3, 0.3, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,225 @@
+// System-wide hotkeys for transport control
+// Registers the bindings from Config and forwards presses to the frontend player
+
+use crate::config::{HotkeyAction, HotkeyConfig};
+use crate::logging::{log_debug, log_info, log_warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HotkeyError {
+    #[error("{accelerator} is bound to both {first:?} and {second:?}")]
+    Duplicate {
+        accelerator: String,
+        first: HotkeyAction,
+        second: HotkeyAction,
+    },
+    #[error("Failed to clear registered hotkeys: {0}")]
+    Unregister(String),
+}
+
+/// A binding that could not be registered
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyFailure {
+    pub action: HotkeyAction,
+    pub accelerator: String,
+    pub reason: String,
+}
+
+/// Outcome of applying hotkey bindings
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HotkeyReport {
+    pub registered: Vec<HotkeyAction>,
+    /// Invalid accelerators and keys already taken by other applications
+    pub failed: Vec<HotkeyFailure>,
+}
+
+/// Payload of the `hotkey` event
+#[derive(Debug, Clone, Serialize)]
+pub struct HotkeyEvent {
+    pub action: HotkeyAction,
+}
+
+/// Registered shortcuts and the bindings they were built from
+struct ActiveHotkeys {
+    config: HotkeyConfig,
+    shortcuts: Vec<(Shortcut, HotkeyAction)>,
+}
+
+static ACTIVE_HOTKEYS: Mutex<Option<ActiveHotkeys>> = Mutex::new(None);
+
+/// The global shortcut plugin, wired to dispatch presses to `handle_shortcut`
+pub fn plugin<R: Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, shortcut, event| handle_shortcut(app, shortcut, event))
+        .build()
+}
+
+/// Enabled bindings with duplicates rejected, in action order
+///
+/// Accelerators are compared case-insensitively with modifiers in any order.
+pub fn validate_bindings(config: &HotkeyConfig) -> Result<Vec<(HotkeyAction, String)>, HotkeyError> {
+    if !config.enabled {
+        return Ok(Vec::new());
+    }
+
+    let mut seen: HashMap<String, HotkeyAction> = HashMap::new();
+    let mut bindings = Vec::new();
+    for (&action, accelerator) in &config.bindings {
+        let accelerator = accelerator.trim();
+        if accelerator.is_empty() {
+            continue;
+        }
+        if let Some(&first) = seen.get(&normalize_accelerator(accelerator)) {
+            return Err(HotkeyError::Duplicate {
+                accelerator: accelerator.to_string(),
+                first,
+                second: action,
+            });
+        }
+        seen.insert(normalize_accelerator(accelerator), action);
+        bindings.push((action, accelerator.to_string()));
+    }
+    Ok(bindings)
+}
+
+/// Lowercase, with modifiers sorted ahead of the key
+fn normalize_accelerator(accelerator: &str) -> String {
+    let mut parts: Vec<String> = accelerator
+        .split('+')
+        .map(|part| part.trim().to_lowercase())
+        .collect();
+    let key = parts.pop().unwrap_or_default();
+    parts.sort();
+    parts.dedup();
+    parts.push(key);
+    parts.join("+")
+}
+
+/// Register the configured hotkeys, replacing any previous registration
+///
+/// Unchanged bindings are left alone unless `force` is set. Bindings that
+/// fail to parse or are already claimed by another application are reported
+/// without affecting the rest.
+pub fn apply<R: Runtime>(app: &AppHandle<R>, config: &HotkeyConfig, force: bool) -> Result<HotkeyReport, HotkeyError> {
+    let mut active = ACTIVE_HOTKEYS.lock().unwrap();
+    if !force && active.as_ref().map(|a| &a.config) == Some(config) {
+        return Ok(HotkeyReport {
+            registered: active.as_ref().map(|a| a.shortcuts.iter().map(|(_, action)| *action).collect()).unwrap_or_default(),
+            failed: Vec::new(),
+        });
+    }
+
+    let bindings = validate_bindings(config)?;
+    let global_shortcut = app.global_shortcut();
+    global_shortcut
+        .unregister_all()
+        .map_err(|e| HotkeyError::Unregister(e.to_string()))?;
+
+    let mut report = HotkeyReport::default();
+    let mut shortcuts = Vec::new();
+    for (action, accelerator) in bindings {
+        let result = accelerator
+            .parse::<Shortcut>()
+            .map_err(|e| format!("Invalid shortcut: {}", e))
+            .and_then(|shortcut| {
+                global_shortcut
+                    .register(shortcut)
+                    .map(|_| shortcut)
+                    .map_err(|e| format!("Could not register (in use by another application?): {}", e))
+            });
+        match result {
+            Ok(shortcut) => {
+                shortcuts.push((shortcut, action));
+                report.registered.push(action);
+            }
+            Err(reason) => {
+                log_warn("Hotkeys", &format!("{:?} ({}): {}", action, accelerator, reason));
+                report.failed.push(HotkeyFailure { action, accelerator, reason });
+            }
+        }
+    }
+
+    log_info("Hotkeys", &format!("Registered {} global hotkeys", report.registered.len()));
+    *active = Some(ActiveHotkeys {
+        config: config.clone(),
+        shortcuts,
+    });
+    Ok(report)
+}
+
+/// Window toggling is handled natively; transport actions go to the frontend player
+fn handle_shortcut<R: Runtime>(app: &AppHandle<R>, shortcut: &Shortcut, event: ShortcutEvent) {
+    if event.state() != ShortcutState::Pressed {
+        return;
+    }
+    let action = {
+        let active = ACTIVE_HOTKEYS.lock().unwrap();
+        active
+            .as_ref()
+            .and_then(|a| a.shortcuts.iter().find(|(s, _)| s == shortcut))
+            .map(|(_, action)| *action)
+    };
+    let Some(action) = action else {
+        return;
+    };
+    log_debug("Hotkeys", &format!("Hotkey pressed: {:?}", action));
+
+    if action == HotkeyAction::ToggleWindow {
+        if let Some(window) = app.get_webview_window("main") {
+            let visible = window.is_visible().unwrap_or(true);
+            let _ = if visible { window.hide() } else { window.show().and_then(|_| window.set_focus()) };
+        }
+        return;
+    }
+
+    if let Err(e) = app.emit("hotkey", HotkeyEvent { action }) {
+        log_warn("Hotkeys", &format!("Failed to emit hotkey event: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled(bindings: &[(HotkeyAction, &str)]) -> HotkeyConfig {
+        HotkeyConfig {
+            enabled: true,
+            bindings: bindings.iter().map(|(a, s)| (*a, s.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_default_bindings_are_valid() {
+        let config = HotkeyConfig { enabled: true, ..Default::default() };
+        assert_eq!(validate_bindings(&config).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_disabled_registers_nothing() {
+        assert!(validate_bindings(&HotkeyConfig::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_ignores_case_and_modifier_order() {
+        let config = enabled(&[
+            (HotkeyAction::Next, "Ctrl+Alt+N"),
+            (HotkeyAction::Previous, "alt+ctrl+n"),
+        ]);
+        assert!(matches!(
+            validate_bindings(&config),
+            Err(HotkeyError::Duplicate { first: HotkeyAction::Next, second: HotkeyAction::Previous, .. })
+        ));
+    }
+
+    #[test]
+    fn test_empty_binding_is_skipped() {
+        let config = enabled(&[(HotkeyAction::Stop, "  "), (HotkeyAction::Next, "Ctrl+Alt+N")]);
+        let bindings = validate_bindings(&config).unwrap();
+        assert_eq!(bindings, vec![(HotkeyAction::Next, "Ctrl+Alt+N".to_string())]);
+    }
+}
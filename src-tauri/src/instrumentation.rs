@@ -0,0 +1,135 @@
+// Per-command timing through tracing spans
+// Commands annotated with `#[tracing::instrument(target = "milk::command", ...)]` are timed
+// by `CommandTimingLayer` and aggregated in `performance::PerformanceMetrics`
+
+use std::time::{Duration, Instant};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Span target marking a Tauri command
+pub const COMMAND_TARGET: &str = "milk::command";
+
+/// Timing state attached to an open command span
+struct CommandSpan {
+    started: Instant,
+    failed: bool,
+}
+
+/// Times command spans from creation to close and reports them to a sink
+///
+/// A command counts as failed when an error-level event is recorded inside
+/// it, which is what `#[instrument(err)]` emits for an `Err` return.
+pub struct CommandTimingLayer<F> {
+    sink: F,
+}
+
+impl<F> CommandTimingLayer<F>
+where
+    F: Fn(&str, Duration, bool) + Send + Sync + 'static,
+{
+    pub fn new(sink: F) -> Self {
+        Self { sink }
+    }
+}
+
+impl<S, F> Layer<S> for CommandTimingLayer<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    F: Fn(&str, Duration, bool) + Send + Sync + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().target() != COMMAND_TARGET {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(CommandSpan {
+                started: Instant::now(),
+                failed: false,
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        // Mark the innermost enclosing command
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        for span in scope {
+            if let Some(command) = span.extensions_mut().get_mut::<CommandSpan>() {
+                command.failed = true;
+                return;
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let command = span.extensions_mut().remove::<CommandSpan>();
+        if let Some(command) = command {
+            (self.sink)(span.name(), command.started.elapsed(), command.failed);
+        }
+    }
+}
+
+/// Install the global subscriber that feeds command timings into the performance metrics
+pub fn init() {
+    let subscriber = tracing_subscriber::registry().with(CommandTimingLayer::new(crate::performance::record_command_timing));
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        crate::logging::log_warn("Performance", "Tracing subscriber already installed; command timings disabled");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    type Recorded = Arc<Mutex<Vec<(String, bool)>>>;
+
+    fn with_recorder(f: impl FnOnce()) -> Vec<(String, bool)> {
+        let recorded: Recorded = Arc::default();
+        let sink = recorded.clone();
+        let layer = CommandTimingLayer::new(move |name: &str, _: Duration, failed| {
+            sink.lock().unwrap().push((name.to_string(), failed));
+        });
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+        let recorded = recorded.lock().unwrap().clone();
+        recorded
+    }
+
+    #[tracing::instrument(target = "milk::command", skip_all, err)]
+    fn sample_command(fail: bool) -> Result<(), String> {
+        if fail {
+            Err("boom".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn helper() {}
+
+    #[test]
+    fn test_records_success_and_failure() {
+        let recorded = with_recorder(|| {
+            let _ = sample_command(false);
+            let _ = sample_command(true);
+        });
+        assert_eq!(
+            recorded,
+            vec![("sample_command".to_string(), false), ("sample_command".to_string(), true)]
+        );
+    }
+
+    #[test]
+    fn test_ignores_non_command_spans() {
+        assert!(with_recorder(helper).is_empty());
+    }
+}
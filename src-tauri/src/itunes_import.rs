@@ -0,0 +1,597 @@
+// iTunes library import
+// Reads the "iTunes Library.xml" that iTunes and the Music app export: a
+// property list holding a dictionary of tracks by id and an array of
+// playlists referencing them. Ratings, loved flags, play counts and dates
+// added are carried over for files that still exist, and user playlists are
+// kept. Locations from another machine, typically an old Mac, are remapped by
+// replacing a folder prefix (the library's music folder unless given) with
+// the folder the music lives in now.
+
+use crate::kv_store::{KvError, KvStore};
+use crate::metadata::MetadataExtractor;
+use crate::migration::ImportedPlaylist;
+use crate::play_history::{self, PlayStats};
+use crate::playlist::{PlaylistSummary, Track};
+use crate::queue_import;
+use crate::ratings::{self, RatingError, TrackRating, MAX_STARS};
+use crate::system_playlists;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+/// Name of the playlist holding every imported file
+pub const LIBRARY_PLAYLIST_NAME: &str = "iTunes Library";
+
+/// iTunes ratings run to 100, 20 per star
+const RATING_PER_STAR: i64 = 20;
+
+#[derive(Error, Debug)]
+pub enum ItunesError {
+    #[error("Failed to read iTunes library: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not an iTunes library: {0}")]
+    InvalidLibrary(String),
+    #[error(transparent)]
+    Store(#[from] KvError),
+    #[error(transparent)]
+    Rating(#[from] RatingError),
+}
+
+/// Replace the `from` folder at the start of track locations with `to`
+#[derive(Debug, Clone)]
+pub struct PathRemap {
+    /// A path or file:// URL; the library's "Music Folder" when not given
+    pub from: Option<String>,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItunesTrack {
+    /// Location on this machine, after remapping
+    pub path: PathBuf,
+    pub rating: Option<TrackRating>,
+    pub plays: Option<PlayStats>,
+    pub date_added: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default)]
+pub struct ItunesLibrary {
+    /// Local file tracks by iTunes track id
+    pub tracks: BTreeMap<i64, ItunesTrack>,
+    /// User playlists by name, with their track ids in order
+    pub playlists: Vec<(String, Vec<i64>)>,
+}
+
+/// What was carried over into the KV store, and the tracks to save
+#[derive(Debug, Default)]
+pub struct ItunesData {
+    pub playlists: Vec<ImportedPlaylist>,
+    /// Library files that exist, in track id order
+    pub library: Vec<Track>,
+    /// Ratings set on files milkline had not rated yet
+    pub ratings: usize,
+    pub play_counts: usize,
+    pub dates_added: usize,
+    /// Tracks whose file no longer exists at its (remapped) location
+    pub missing_files: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItunesImportReport {
+    /// Playlists created
+    pub playlists: Vec<PlaylistSummary>,
+    pub library_tracks: usize,
+    /// Library tracks added to the search index that it did not already hold
+    pub indexed_tracks: usize,
+    pub ratings: usize,
+    pub play_counts: usize,
+    pub dates_added: usize,
+    pub missing_files: usize,
+}
+
+/// Parse an exported library, remapping track locations with `remap`
+///
+/// Streams and tracks without a location are left out, as are the built-in
+/// playlists (the library itself, Music, Podcasts and so on) and folders.
+pub fn parse_library(xml: &str, remap: Option<&PathRemap>) -> Result<ItunesLibrary, ItunesError> {
+    let root = PlistParser::parse(xml)?;
+    let root = root.as_dict().ok_or_else(|| ItunesError::InvalidLibrary("top level is not a dictionary".to_string()))?;
+    let tracks = root
+        .get("Tracks")
+        .and_then(Plist::as_dict)
+        .ok_or_else(|| ItunesError::InvalidLibrary("no Tracks dictionary".to_string()))?;
+    let remap = remap.map(|remap| {
+        let from = remap.from.as_deref().or_else(|| root.get("Music Folder").and_then(Plist::as_str));
+        (from.map(location_to_path), &remap.to)
+    });
+
+    let mut library = ItunesLibrary::default();
+    for track in tracks.values().filter_map(Plist::as_dict) {
+        let (Some(id), Some(location)) = (track.get("Track ID").and_then(Plist::as_integer), track.get("Location").and_then(Plist::as_str))
+        else {
+            continue;
+        };
+        if !location.starts_with("file://") {
+            continue;
+        }
+        let location = location_to_path(location);
+        let path = match &remap {
+            Some((Some(from), to)) => remap_path(&location, from, to).unwrap_or_else(|| PathBuf::from(&location)),
+            _ => PathBuf::from(&location),
+        };
+        library.tracks.insert(id, ItunesTrack {
+            path,
+            rating: track_rating(track),
+            plays: track.get("Play Count").and_then(Plist::as_integer).filter(|count| *count > 0).map(|count| PlayStats {
+                count: count.min(u32::MAX as i64) as u32,
+                last_played: track.get("Play Date UTC").and_then(Plist::as_date).map(|date| date.timestamp_millis()).unwrap_or(0),
+            }),
+            date_added: track.get("Date Added").and_then(Plist::as_date),
+        });
+    }
+
+    let playlists = root.get("Playlists").and_then(Plist::as_array).map(Vec::as_slice).unwrap_or_default();
+    for playlist in playlists.iter().filter_map(Plist::as_dict) {
+        let flag = |key: &str| playlist.get(key).is_some_and(|value| *value == Plist::Bool(true));
+        let hidden = playlist.get("Visible") == Some(&Plist::Bool(false));
+        if flag("Master") || flag("Folder") || hidden || playlist.contains_key("Distinguished Kind") {
+            continue;
+        }
+        let Some(name) = playlist.get("Name").and_then(Plist::as_str) else {
+            continue;
+        };
+        let items = playlist.get("Playlist Items").and_then(Plist::as_array).map(Vec::as_slice).unwrap_or_default();
+        let ids = items
+            .iter()
+            .filter_map(|item| item.as_dict()?.get("Track ID")?.as_integer())
+            .filter(|id| library.tracks.contains_key(id))
+            .collect();
+        library.playlists.push((name.to_string(), ids));
+    }
+    Ok(library)
+}
+
+/// Carry ratings, play counts and dates added into `store` and read the tracks
+///
+/// A rating only fills in for a file milkline has no rating for; play counts
+/// keep the higher of the two. `progress` gets (tracks done, total tracks).
+/// Stops early, returning what was done so far, once `cancel` is set.
+pub fn import_library(
+    store: &KvStore,
+    library: &ItunesLibrary,
+    extractor: &MetadataExtractor,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<ItunesData, ItunesError> {
+    let mut data = ItunesData::default();
+    let mut found: HashMap<i64, Track> = HashMap::new();
+    let total = library.tracks.len() as u64;
+    for (done, (id, track)) in library.tracks.iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(data);
+        }
+        if done % 50 == 0 {
+            progress(done as u64, total);
+        }
+        if !track.path.is_file() {
+            data.missing_files += 1;
+            continue;
+        }
+        let file_path = crate::path_codec::encode(&track.path);
+        if let Some(rating) = track.rating {
+            if ratings::get_rating(store, &file_path) == TrackRating::default() {
+                ratings::set_rating(store, &file_path, rating)?;
+                data.ratings += 1;
+            }
+        }
+        if let Some(plays) = track.plays {
+            play_history::merge_play_stats(store, &file_path, plays)?;
+            data.play_counts += 1;
+        }
+        if let Some(added) = track.date_added {
+            system_playlists::set_date_added(store, &file_path, added)?;
+            data.dates_added += 1;
+        }
+        let track = queue_import::track_for_file(&track.path, extractor);
+        data.library.push(track.clone());
+        found.insert(*id, track);
+    }
+    progress(total, total);
+
+    data.playlists = library
+        .playlists
+        .iter()
+        .map(|(name, ids)| ImportedPlaylist {
+            name: name.clone(),
+            tracks: ids.iter().filter_map(|id| found.get(id).cloned()).collect(),
+        })
+        .collect();
+    Ok(data)
+}
+
+/// Stars from the 0-100 rating (album-derived ratings don't count) and the loved flag
+fn track_rating(track: &BTreeMap<String, Plist>) -> Option<TrackRating> {
+    let computed = track.get("Rating Computed") == Some(&Plist::Bool(true));
+    let stars = track
+        .get("Rating")
+        .and_then(Plist::as_integer)
+        .filter(|_| !computed)
+        .map(|rating| (rating / RATING_PER_STAR).clamp(0, MAX_STARS as i64) as u8)
+        .unwrap_or(0);
+    // The Music app calls it "Favorited"
+    let loved = ["Loved", "Favorited"].iter().any(|key| track.get(*key) == Some(&Plist::Bool(true)));
+    let rating = TrackRating { stars, loved };
+    (rating != TrackRating::default()).then_some(rating)
+}
+
+/// Decode a file:// URL to a path with `/` separators; plain paths pass through
+///
+/// Windows locations ("file://localhost/C:/Music/...") lose the leading slash.
+/// Exports often come from another machine, so this decodes the URL as text
+/// rather than with `Url::to_file_path`, which only accepts this platform's paths.
+fn location_to_path(location: &str) -> String {
+    let Some(rest) = location.strip_prefix("file://") else {
+        return location.to_string();
+    };
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let path = percent_encoding::percent_decode_str(rest).decode_utf8_lossy().to_string();
+    match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    }
+}
+
+/// `location` with the `from` folder swapped for `to`, if it lies under `from`
+///
+/// Folders are compared ignoring ASCII case, since macOS and Windows volumes
+/// usually are case-insensitive.
+fn remap_path(location: &str, from: &str, to: &std::path::Path) -> Option<PathBuf> {
+    let from = from.trim_end_matches('/');
+    let head = location.get(..from.len())?;
+    let rest = location[from.len()..].strip_prefix('/')?;
+    head.eq_ignore_ascii_case(from)
+        .then(|| rest.split('/').filter(|part| !part.is_empty()).fold(to.to_path_buf(), |path, part| path.join(part)))
+}
+
+
+/// The subset of property list values an iTunes library uses
+#[derive(Debug, Clone, PartialEq)]
+enum Plist {
+    Dict(BTreeMap<String, Plist>),
+    Array(Vec<Plist>),
+    String(String),
+    Integer(i64),
+    Date(DateTime<Utc>),
+    Bool(bool),
+    /// Reals, data and dates that don't parse
+    Other,
+}
+
+impl Plist {
+    fn as_dict(&self) -> Option<&BTreeMap<String, Plist>> {
+        match self {
+            Plist::Dict(dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&Vec<Plist>> {
+        match self {
+            Plist::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Plist::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn as_integer(&self) -> Option<i64> {
+        match self {
+            Plist::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn as_date(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Plist::Date(date) => Some(*date),
+            _ => None,
+        }
+    }
+}
+
+/// A start or end tag
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    empty: bool,
+}
+
+/// Reads XML property lists; libraries run to tens of megabytes, so this
+/// walks the text once rather than building a DOM
+struct PlistParser<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> PlistParser<'a> {
+    fn parse(xml: &'a str) -> Result<Plist, ItunesError> {
+        let start = xml.find("<plist").ok_or_else(|| invalid("no plist element"))?;
+        let mut parser = Self { xml, pos: start };
+        let plist = parser.tag()?;
+        if plist.empty {
+            return Err(invalid("empty plist"));
+        }
+        parser.value()
+    }
+
+    /// The next tag, skipping text between tags, declarations and comments
+    fn tag(&mut self) -> Result<Tag<'a>, ItunesError> {
+        loop {
+            let open = self.pos + self.xml[self.pos..].find('<').ok_or_else(|| invalid("unexpected end of file"))?;
+            let inner = &self.xml[open + 1..];
+            let end = if inner.starts_with("!--") { "-->" } else { ">" };
+            let close = open + 1 + inner.find(end).ok_or_else(|| invalid("unterminated tag"))?;
+            self.pos = close + end.len();
+            let tag = &self.xml[open + 1..close];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            let empty = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let (closing, tag) = match tag.strip_prefix('/') {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            };
+            let name = tag.split_whitespace().next().unwrap_or_default();
+            return Ok(Tag { name, closing, empty });
+        }
+    }
+
+    /// Text up to the end tag of `name`, unescaped
+    fn text(&mut self, name: &str) -> Result<String, ItunesError> {
+        let end = format!("</{}>", name);
+        let len = self.xml[self.pos..].find(&end).ok_or_else(|| invalid(&format!("unterminated <{}>", name)))?;
+        let text = unescape(&self.xml[self.pos..self.pos + len]);
+        self.pos += len + end.len();
+        Ok(text)
+    }
+
+    fn value(&mut self) -> Result<Plist, ItunesError> {
+        let tag = self.tag()?;
+        if tag.closing {
+            return Err(invalid(&format!("unexpected </{}>", tag.name)));
+        }
+        self.element(tag)
+    }
+
+    fn element(&mut self, tag: Tag<'a>) -> Result<Plist, ItunesError> {
+        let text = |parser: &mut Self| if tag.empty { Ok(String::new()) } else { parser.text(tag.name) };
+        Ok(match tag.name {
+            "dict" => {
+                let mut dict = BTreeMap::new();
+                if !tag.empty {
+                    loop {
+                        let key = self.tag()?;
+                        match (key.name, key.closing) {
+                            ("dict", true) => break,
+                            ("key", false) => {
+                                let key = if key.empty { String::new() } else { self.text("key")? };
+                                dict.insert(key, self.value()?);
+                            }
+                            (name, _) => return Err(invalid(&format!("expected <key>, found <{}>", name))),
+                        }
+                    }
+                }
+                Plist::Dict(dict)
+            }
+            "array" => {
+                let mut items = Vec::new();
+                if !tag.empty {
+                    loop {
+                        let item = self.tag()?;
+                        if item.closing {
+                            break;
+                        }
+                        items.push(self.element(item)?);
+                    }
+                }
+                Plist::Array(items)
+            }
+            "string" => Plist::String(text(self)?),
+            "integer" => text(self)?.trim().parse().map(Plist::Integer).unwrap_or(Plist::Other),
+            "date" => DateTime::parse_from_rfc3339(text(self)?.trim())
+                .map(|date| Plist::Date(date.with_timezone(&Utc)))
+                .unwrap_or(Plist::Other),
+            "true" => Plist::Bool(true),
+            "false" => Plist::Bool(false),
+            name => {
+                if !tag.empty {
+                    self.text(name)?;
+                }
+                Plist::Other
+            }
+        })
+    }
+}
+
+fn invalid(reason: &str) -> ItunesError {
+    ItunesError::InvalidLibrary(reason.to_string())
+}
+
+/// Resolve the predefined entities and numeric character references
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        unescaped.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const LIBRARY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>Major Version</key><integer>1</integer>
+	<key>Music Folder</key><string>file:///Users/old/Music/iTunes/iTunes%20Media/</string>
+	<key>Tracks</key>
+	<dict>
+		<key>101</key>
+		<dict>
+			<key>Track ID</key><integer>101</integer>
+			<key>Name</key><string>Rock &#38; Roll</string>
+			<key>Rating</key><integer>80</integer>
+			<key>Loved</key><true/>
+			<key>Play Count</key><integer>12</integer>
+			<key>Play Date UTC</key><date>2015-06-01T20:00:00Z</date>
+			<key>Date Added</key><date>2009-03-14T10:30:00Z</date>
+			<key>Location</key><string>file:///Users/old/Music/iTunes/iTunes%20Media/Music/Band/Caf%C3%A9.mp3</string>
+		</dict>
+		<key>102</key>
+		<dict>
+			<key>Track ID</key><integer>102</integer>
+			<key>Rating</key><integer>60</integer>
+			<key>Rating Computed</key><true/>
+			<key>Location</key><string>file:///Users/old/Music/iTunes/iTunes%20Media/Music/Band/Gone.mp3</string>
+		</dict>
+		<key>103</key>
+		<dict>
+			<key>Track ID</key><integer>103</integer>
+			<key>Track Type</key><string>URL</string>
+			<key>Location</key><string>http://radio.example/stream</string>
+		</dict>
+	</dict>
+	<key>Playlists</key>
+	<array>
+		<dict>
+			<key>Name</key><string>Library</string>
+			<key>Master</key><true/>
+			<key>Playlist Items</key><array><dict><key>Track ID</key><integer>101</integer></dict></array>
+		</dict>
+		<dict>
+			<key>Name</key><string>Music</string>
+			<key>Distinguished Kind</key><integer>4</integer>
+		</dict>
+		<dict>
+			<key>Name</key><string>Road Trip</string>
+			<key>Playlist Items</key>
+			<array>
+				<dict><key>Track ID</key><integer>102</integer></dict>
+				<dict><key>Track ID</key><integer>101</integer></dict>
+				<dict><key>Track ID</key><integer>103</integer></dict>
+			</array>
+		</dict>
+		<dict><key>Name</key><string>Empty</string></dict>
+	</array>
+</dict>
+</plist>
+"#;
+
+    #[test]
+    fn test_parse_library() {
+        let library = parse_library(LIBRARY, None).unwrap();
+        assert_eq!(library.tracks.len(), 2);
+        let track = &library.tracks[&101];
+        assert_eq!(track.path, PathBuf::from("/Users/old/Music/iTunes/iTunes Media/Music/Band/Café.mp3"));
+        assert_eq!(track.rating, Some(TrackRating { stars: 4, loved: true }));
+        assert_eq!(track.plays.unwrap().count, 12);
+        assert_eq!(track.date_added.unwrap().to_rfc3339(), "2009-03-14T10:30:00+00:00");
+        // Album-derived ratings are not the user's
+        assert_eq!(library.tracks[&102].rating, None);
+
+        assert_eq!(library.playlists, [("Road Trip".to_string(), vec![102, 101]), ("Empty".to_string(), vec![])]);
+        assert!(parse_library("<plist><array></array></plist>", None).is_err());
+        assert!(parse_library("not xml", None).is_err());
+    }
+
+    #[test]
+    fn test_locations() {
+        assert_eq!(location_to_path("file://localhost/C:/My%20Music/a.mp3"), "C:/My Music/a.mp3");
+        assert_eq!(location_to_path("file:///Users/me/100%25.mp3"), "/Users/me/100%.mp3");
+
+        let to = PathBuf::from("/home/me/Music");
+        assert_eq!(remap_path("/Users/Old/Music/Band/a.mp3", "/users/old/music/", &to), Some(to.join("Band").join("a.mp3")));
+        assert_eq!(remap_path("/Volumes/Other/a.mp3", "/Users/old/Music", &to), None);
+        // Only whole folder names match
+        assert_eq!(remap_path("/Users/old/Music2/a.mp3", "/Users/old/Music", &to), None);
+    }
+
+    #[test]
+    fn test_import_library() {
+        let dir = TempDir::new().unwrap();
+        let band = dir.path().join("Music").join("Band");
+        fs::create_dir_all(&band).unwrap();
+        let cafe = band.join("Café.mp3");
+        fs::write(&cafe, b"").unwrap();
+
+        let remap = PathRemap { from: None, to: dir.path().to_path_buf() };
+        let library = parse_library(LIBRARY, Some(&remap)).unwrap();
+        assert_eq!(library.tracks[&101].path, cafe);
+
+        let store = KvStore::in_memory();
+        let file_path = crate::path_codec::encode(&cafe);
+        play_history::record_play(&store, &file_path, Utc::now()).unwrap();
+        let data = import_library(&store, &library, &MetadataExtractor::new(), &AtomicBool::new(false), |_, _| {}).unwrap();
+
+        assert_eq!((data.library.len(), data.missing_files), (1, 1));
+        assert_eq!((data.ratings, data.play_counts, data.dates_added), (1, 1, 1));
+        assert_eq!(data.playlists[0].name, "Road Trip");
+        assert_eq!(data.playlists[0].tracks.len(), 1);
+        assert_eq!(ratings::get_rating(&store, &file_path).stars, 4);
+        let plays = play_history::get_play_stats(&store, &file_path);
+        // The later of the two last plays is kept with the higher count
+        assert_eq!(plays.count, 12);
+        assert!(plays.last_played > DateTime::parse_from_rfc3339("2015-06-01T20:00:00Z").unwrap().timestamp_millis());
+        assert_eq!(system_playlists::date_added(&store, &cafe).unwrap().to_rfc3339(), "2009-03-14T10:30:00+00:00");
+
+        // Ratings set in milkline are left alone on a second import
+        ratings::set_rating(&store, &file_path, TrackRating { stars: 1, loved: false }).unwrap();
+        let again = import_library(&store, &library, &MetadataExtractor::new(), &AtomicBool::new(false), |_, _| {}).unwrap();
+        assert_eq!(again.ratings, 0);
+        assert_eq!(ratings::get_rating(&store, &file_path).stars, 1);
+        assert_eq!(play_history::get_play_stats(&store, &file_path).count, 12);
+    }
+}
@@ -0,0 +1,479 @@
+// Background job manager
+// Long operations (library scans, FFmpeg exports, album artwork and downloads)
+// run as jobs: each has an id, waits for a slot under its kind's concurrency
+// limit, reports typed progress as `job-progress` events and can be cancelled
+// through its `CancelToken`. `list_jobs` and `cancel_job` cover every kind.
+
+use crate::media_editor::ffmpeg::FfmpegProgress;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::Emitter;
+use tokio::sync::{Notify, Semaphore, SemaphorePermit};
+
+/// Error message of a job that stopped because it was cancelled
+pub const CANCELLED: &str = "Job cancelled";
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("Job cancelled")]
+    Cancelled,
+    #[error("Job {0} is already running")]
+    AlreadyRunning(String),
+    #[error("Job {0} panicked: {1}")]
+    Panicked(String, String),
+    #[error("Job queue closed")]
+    QueueClosed,
+}
+
+impl From<JobError> for String {
+    fn from(err: JobError) -> Self {
+        err.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    LibraryScan,
+    /// FFmpeg exports and conversions
+    Media,
+    Artwork,
+    Download,
+    /// Tempo and key analysis
+    Analysis,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 5] = [JobKind::LibraryScan, JobKind::Media, JobKind::Artwork, JobKind::Download, JobKind::Analysis];
+
+    /// Jobs of this kind allowed to run at the same time; the rest wait in the queue
+    pub fn max_concurrent(self) -> usize {
+        match self {
+            // Scans of the same disk only slow each other down
+            JobKind::LibraryScan => 1,
+            JobKind::Media => 2,
+            JobKind::Artwork => 2,
+            JobKind::Download => 3,
+            // Each analysis already decodes a whole track through FFmpeg
+            JobKind::Analysis => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Work done so far, in the unit the job counts in
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "unit", rename_all = "snake_case")]
+pub enum JobDetail {
+    /// Files, tracks or albums processed
+    Items { completed: u64, total: u64 },
+    /// Bytes transferred; `total` is unknown when the server doesn't send a length
+    Bytes { received: u64, total: Option<u64> },
+}
+
+/// Payload of `job-progress`, and an entry of `list_jobs`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct JobProgress {
+    pub job_id: String,
+    pub kind: JobKind,
+    /// Operation name, e.g. "initial_index", "trim_video" or "download_skin"
+    pub operation: &'static str,
+    pub state: JobState,
+    pub percent: Option<f64>,
+    pub eta_sec: Option<f64>,
+    pub detail: Option<JobDetail>,
+    /// Error message for failed jobs
+    pub error: Option<String>,
+}
+
+/// Receives every progress update of a job
+pub type ProgressSink = Arc<dyn Fn(&JobProgress) + Send + Sync>;
+
+/// Sink that emits `job-progress` events to the frontend
+pub fn app_sink(app: tauri::AppHandle) -> ProgressSink {
+    Arc::new(move |progress| {
+        let _ = app.emit("job-progress", progress);
+    })
+}
+
+/// Cancellation flag shared between a job and whoever may cancel it
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<CancelState>);
+
+#[derive(Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::Relaxed);
+        self.0.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// The raw flag, for blocking loops such as `run_ffmpeg`
+    pub fn flag(&self) -> &AtomicBool {
+        &self.0.cancelled
+    }
+
+    /// Resolves once the token is cancelled
+    pub async fn cancelled(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        // Register before checking, so a cancel in between isn't missed
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+struct ActiveJob {
+    cancel: CancelToken,
+    progress: JobProgress,
+}
+
+type Registry = Arc<Mutex<BTreeMap<String, ActiveJob>>>;
+
+/// Handed to a job's work function for cancellation and progress
+pub struct JobContext {
+    job_id: String,
+    kind: JobKind,
+    operation: &'static str,
+    cancel: CancelToken,
+    registry: Registry,
+    sink: ProgressSink,
+}
+
+impl JobContext {
+    pub fn token(&self) -> &CancelToken {
+        &self.cancel
+    }
+
+    /// Flag to pass to `run_ffmpeg`
+    pub fn cancel_flag(&self) -> &AtomicBool {
+        self.cancel.flag()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// `JobError::Cancelled` once the job is cancelled, for checks between steps
+    pub fn checkpoint(&self) -> Result<(), JobError> {
+        if self.is_cancelled() {
+            return Err(JobError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Report running progress as a percentage of the whole job
+    pub fn report(&self, percent: Option<f64>, eta_sec: Option<f64>) {
+        self.emit(JobState::Running, percent, eta_sec, None, None);
+    }
+
+    /// Report `completed` of `total` items processed
+    pub fn report_items(&self, completed: u64, total: u64) {
+        let percent = (total > 0).then(|| completed as f64 * 100.0 / total as f64);
+        self.emit(JobState::Running, percent, None, Some(JobDetail::Items { completed, total }), None);
+    }
+
+    /// Report `received` bytes transferred, of `total` when known
+    pub fn report_bytes(&self, received: u64, total: Option<u64>) {
+        let percent = total.filter(|total| *total > 0).map(|total| received as f64 * 100.0 / total as f64);
+        self.emit(JobState::Running, percent, None, Some(JobDetail::Bytes { received, total }), None);
+    }
+
+    /// Report FFmpeg progress for a job that is a single FFmpeg run
+    pub fn report_ffmpeg(&self, progress: &FfmpegProgress) {
+        self.report(progress.percent, progress.eta_sec);
+    }
+
+    fn emit(&self, state: JobState, percent: Option<f64>, eta_sec: Option<f64>, detail: Option<JobDetail>, error: Option<String>) {
+        let progress = JobProgress {
+            job_id: self.job_id.clone(),
+            kind: self.kind,
+            operation: self.operation,
+            state,
+            percent,
+            eta_sec,
+            detail,
+            error,
+        };
+        if let Some(job) = self.registry.lock().unwrap().get_mut(&self.job_id) {
+            job.progress = progress.clone();
+        }
+        (self.sink)(&progress);
+    }
+}
+
+pub struct JobManager {
+    slots: BTreeMap<JobKind, Semaphore>,
+    /// Queued and running jobs with their latest progress
+    jobs: Registry,
+}
+
+impl JobManager {
+    /// Manager allowing `limit(kind)` concurrent jobs of each kind
+    pub fn new(limit: impl Fn(JobKind) -> usize) -> Self {
+        Self {
+            slots: JobKind::ALL.into_iter().map(|kind| (kind, Semaphore::new(limit(kind).max(1)))).collect(),
+            jobs: Arc::default(),
+        }
+    }
+
+    fn register(&self, job_id: String, kind: JobKind, operation: &'static str, sink: ProgressSink) -> Result<Arc<JobContext>, JobError> {
+        let cancel = CancelToken::default();
+        let context = Arc::new(JobContext { job_id, kind, operation, cancel: cancel.clone(), registry: self.jobs.clone(), sink });
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if jobs.contains_key(&context.job_id) {
+                return Err(JobError::AlreadyRunning(context.job_id.clone()));
+            }
+            let progress = JobProgress {
+                job_id: context.job_id.clone(),
+                kind,
+                operation,
+                state: JobState::Queued,
+                percent: None,
+                eta_sec: None,
+                detail: None,
+                error: None,
+            };
+            jobs.insert(context.job_id.clone(), ActiveJob { cancel, progress });
+        }
+        context.emit(JobState::Queued, None, None, None, None);
+        Ok(context)
+    }
+
+    /// Wait for a worker slot; a job cancelled while queued never starts
+    async fn start(&self, context: &JobContext) -> Result<SemaphorePermit<'_>, JobError> {
+        let permit = self.slots[&context.kind].acquire().await.map_err(|_| JobError::QueueClosed)?;
+        context.checkpoint()?;
+        context.emit(JobState::Running, Some(0.0), None, None, None);
+        Ok(permit)
+    }
+
+    /// Queue blocking `work` under `job_id` and wait for it to finish
+    ///
+    /// The job is reported as queued until a slot for its kind is free, then
+    /// runs on a blocking thread. The work checks the context for cancellation;
+    /// any error it returns after a cancel is reported as the job being cancelled.
+    pub async fn run<T, E, F>(&self, job_id: String, kind: JobKind, operation: &'static str, sink: ProgressSink, work: F) -> Result<T, E>
+    where
+        T: Send + 'static,
+        E: From<JobError> + std::fmt::Display + Send + 'static,
+        F: FnOnce(&JobContext) -> Result<T, E> + Send + 'static,
+    {
+        let context = self.register(job_id, kind, operation, sink)?;
+        let result = match self.start(&context).await {
+            Err(e) => Err(e.into()),
+            Ok(_permit) => {
+                let worker_context = context.clone();
+                tokio::task::spawn_blocking(move || work(&worker_context))
+                    .await
+                    .unwrap_or_else(|e| Err(JobError::Panicked(context.job_id.clone(), e.to_string()).into()))
+            }
+        };
+        self.finish(&context, result)
+    }
+
+    /// Queue async `work` under `job_id` and wait for it to finish
+    ///
+    /// Cancelling drops the work future at its next await point.
+    pub async fn run_async<T, E, F, Fut>(&self, job_id: String, kind: JobKind, operation: &'static str, sink: ProgressSink, work: F) -> Result<T, E>
+    where
+        E: From<JobError> + std::fmt::Display,
+        F: FnOnce(Arc<JobContext>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let context = self.register(job_id, kind, operation, sink)?;
+        let result = match self.start(&context).await {
+            Err(e) => Err(e.into()),
+            Ok(_permit) => {
+                let token = context.token().clone();
+                tokio::select! {
+                    result = work(context.clone()) => result,
+                    _ = token.cancelled() => Err(JobError::Cancelled.into()),
+                }
+            }
+        };
+        self.finish(&context, result)
+    }
+
+    fn finish<T, E: std::fmt::Display>(&self, context: &JobContext, result: Result<T, E>) -> Result<T, E> {
+        match &result {
+            Ok(_) => context.emit(JobState::Completed, Some(100.0), Some(0.0), None, None),
+            Err(_) if context.is_cancelled() => context.emit(JobState::Cancelled, None, None, None, None),
+            Err(e) => context.emit(JobState::Failed, None, None, None, Some(e.to_string())),
+        }
+        self.jobs.lock().unwrap().remove(&context.job_id);
+        result
+    }
+
+    /// Cancel a queued or running job; returns false if no such job exists
+    pub fn cancel(&self, job_id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(job_id) {
+            Some(job) => {
+                job.cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queued and running jobs with their latest progress
+    pub fn active_jobs(&self) -> Vec<JobProgress> {
+        self.jobs.lock().unwrap().values().map(|job| job.progress.clone()).collect()
+    }
+}
+
+static JOB_MANAGER: OnceLock<JobManager> = OnceLock::new();
+
+pub fn job_manager() -> &'static JobManager {
+    JOB_MANAGER.get_or_init(|| JobManager::new(JobKind::max_concurrent))
+}
+
+/// Use the frontend's job id, or make one up when it doesn't need to cancel
+pub fn resolve_job_id(job_id: Option<String>) -> String {
+    job_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// Tauri command listing queued and running jobs of every kind
+#[tauri::command]
+pub fn list_jobs() -> Vec<JobProgress> {
+    job_manager().active_jobs()
+}
+
+/// Tauri command to cancel a job; returns false if no such job is queued or running
+#[tauri::command]
+pub fn cancel_job(job_id: String) -> bool {
+    job_manager().cancel(&job_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Recorded = Arc<Mutex<Vec<(String, JobState)>>>;
+
+    fn recording_sink() -> (ProgressSink, Recorded) {
+        let events: Recorded = Arc::default();
+        let recorded = events.clone();
+        let sink: ProgressSink = Arc::new(move |p: &JobProgress| {
+            recorded.lock().unwrap().push((p.job_id.clone(), p.state));
+        });
+        (sink, events)
+    }
+
+    fn ids(manager: &JobManager) -> Vec<String> {
+        manager.active_jobs().into_iter().map(|job| job.job_id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_job_lifecycle_events() {
+        let manager = JobManager::new(|_| 1);
+        let (sink, events) = recording_sink();
+        let result = manager
+            .run("a".to_string(), JobKind::Media, "test", sink, |ctx| {
+                ctx.report_items(1, 2);
+                Ok::<_, String>(7)
+            })
+            .await;
+        assert_eq!(result, Ok(7));
+        let states: Vec<JobState> = events.lock().unwrap().iter().map(|(_, s)| *s).collect();
+        assert_eq!(
+            states,
+            vec![JobState::Queued, JobState::Running, JobState::Running, JobState::Completed]
+        );
+        assert!(manager.active_jobs().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_queued_job_and_limits_per_kind() {
+        let manager = Arc::new(JobManager::new(|_| 1));
+        let (sink, events) = recording_sink();
+        let release = CancelToken::default();
+
+        let blocker = {
+            let (manager, sink, release) = (manager.clone(), sink.clone(), release.clone());
+            tokio::spawn(async move {
+                manager
+                    .run("running".to_string(), JobKind::LibraryScan, "test", sink, move |ctx| {
+                        ctx.report_items(3, 10);
+                        while !release.is_cancelled() {
+                            std::thread::sleep(std::time::Duration::from_millis(5));
+                        }
+                        Ok::<_, String>(())
+                    })
+                    .await
+            })
+        };
+        while !manager.active_jobs().iter().any(|job| job.detail.is_some()) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(manager.active_jobs()[0].percent, Some(30.0));
+
+        // Another kind has its own slot
+        let other = manager.run("other".to_string(), JobKind::Download, "test", sink.clone(), |_| Ok::<_, String>(())).await;
+        assert_eq!(other, Ok(()));
+
+        let queued = {
+            let (manager, sink) = (manager.clone(), sink.clone());
+            tokio::spawn(async move { manager.run("queued".to_string(), JobKind::LibraryScan, "test", sink, |_| Ok::<_, String>(())).await })
+        };
+        while ids(&manager).len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.cancel("queued"));
+        release.cancel();
+
+        assert_eq!(blocker.await.unwrap(), Ok(()));
+        assert_eq!(queued.await.unwrap(), Err(CANCELLED.to_string()));
+        let events = events.lock().unwrap();
+        assert!(!events.contains(&("queued".to_string(), JobState::Running)));
+        assert!(events.contains(&("queued".to_string(), JobState::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_async_job() {
+        let manager = Arc::new(JobManager::new(|_| 1));
+        let (sink, events) = recording_sink();
+        let job = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                manager
+                    .run_async("download".to_string(), JobKind::Download, "test", sink, |ctx| async move {
+                        ctx.report_bytes(10, None);
+                        std::future::pending::<()>().await;
+                        Ok::<_, String>(())
+                    })
+                    .await
+            })
+        };
+        while !manager.active_jobs().iter().any(|job| job.detail.is_some()) {
+            tokio::task::yield_now().await;
+        }
+        assert!(manager.cancel("download"));
+        assert_eq!(job.await.unwrap(), Err(CANCELLED.to_string()));
+        assert_eq!(events.lock().unwrap().last(), Some(&("download".to_string(), JobState::Cancelled)));
+        assert!(!manager.cancel("download"));
+    }
+}
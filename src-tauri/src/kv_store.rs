@@ -0,0 +1,149 @@
+// Small persistent key-value store for UI and feature state
+// Backed by a single JSON file next to config.json; writes go straight to disk
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum KvError {
+    #[error("Failed to write key-value store: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to serialize value: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+pub struct KvStore {
+    /// None keeps the store in memory only
+    path: Option<PathBuf>,
+    data: Mutex<BTreeMap<String, serde_json::Value>>,
+}
+
+impl KvStore {
+    /// Open the store at `path`, starting empty if the file is missing or corrupted
+    pub fn open(path: PathBuf) -> Self {
+        let data = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            data: Mutex::new(data),
+        }
+    }
+
+    /// A store that is never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            data: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Get the store file path in the AppData directory
+    pub fn get_default_path() -> Option<PathBuf> {
+        let milk_dir = dirs::config_dir()?.join("milk");
+        fs::create_dir_all(&milk_dir).ok()?;
+        Some(milk_dir.join("kv_store.json"))
+    }
+
+    /// Get a value, or None if missing or of a different shape
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let data = self.data.lock().unwrap();
+        data.get(key).and_then(|v| serde_json::from_value(v.clone()).ok())
+    }
+
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), KvError> {
+        let value = serde_json::to_value(value)?;
+        let mut data = self.data.lock().unwrap();
+        data.insert(key.to_string(), value);
+        self.persist(&data)
+    }
+
+    /// Every value whose key starts with `prefix`, keyed by the rest of the key
+    ///
+    /// Values of a different shape are skipped.
+    pub fn entries_with_prefix<T: DeserializeOwned>(&self, prefix: &str) -> Vec<(String, T)> {
+        let data = self.data.lock().unwrap();
+        data.range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, value)| {
+                let value = serde_json::from_value(value.clone()).ok()?;
+                Some((key[prefix.len()..].to_string(), value))
+            })
+            .collect()
+    }
+
+    /// Remove a key, returning whether it existed
+    pub fn remove(&self, key: &str) -> Result<bool, KvError> {
+        let mut data = self.data.lock().unwrap();
+        let existed = data.remove(key).is_some();
+        if existed {
+            self.persist(&data)?;
+        }
+        Ok(existed)
+    }
+
+    /// Write to a temporary file first so a crash never leaves a truncated store
+    fn persist(&self, data: &BTreeMap<String, serde_json::Value>) -> Result<(), KvError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(data)?;
+        let tmp_path = tmp_path_for(path);
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+fn tmp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_get_round_trip_across_reopen() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("kv_store.json");
+
+        let store = KvStore::open(path.clone());
+        store.set("volume", &0.5f32).unwrap();
+        store.set("names", &vec!["a".to_string(), "b".to_string()]).unwrap();
+
+        let reopened = KvStore::open(path);
+        assert_eq!(reopened.get::<f32>("volume"), Some(0.5));
+        assert_eq!(reopened.get::<Vec<String>>("names").unwrap().len(), 2);
+        // Wrong shape reads as missing
+        assert_eq!(reopened.get::<bool>("volume"), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let store = KvStore::in_memory();
+        store.set("key", &1).unwrap();
+        assert!(store.remove("key").unwrap());
+        assert!(!store.remove("key").unwrap());
+        assert_eq!(store.get::<serde_json::Value>("key"), None);
+    }
+
+    #[test]
+    fn test_corrupted_file_starts_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("kv_store.json");
+        fs::write(&path, "{not json").unwrap();
+
+        let store = KvStore::open(path);
+        assert_eq!(store.get::<serde_json::Value>("anything"), None);
+    }
+}
@@ -1,16 +1,86 @@
 mod config;
+mod config_watcher;
+mod data_bundle;
+mod duplicates;
 mod secure_storage;
 mod library;
+mod library_filter;
+mod library_roots;
+mod library_query;
+mod tag_pattern;
+mod tag_rename;
+mod tag_infer;
+mod collation;
+mod path_codec;
+mod jobs;
 mod metadata;
+mod metadata_cache;
+mod chapters;
+mod artwork;
 mod playlist;
+mod system_playlists;
 mod skin;
+mod skin_sprites;
+mod skin_package;
+mod skin_museum;
+mod theme;
+mod queue_import;
+mod cli;
+mod headless;
+mod snapcast;
+mod exclusive_output;
+mod file_drop;
+mod file_associations;
+mod visualizer;
+mod hotkeys;
+mod media_session;
+mod tray;
+mod taskbar;
+mod window_geometry;
+mod session;
+mod setup;
+mod plugins;
+mod winamp_vis;
+mod scripting;
+mod scheduler;
+mod search;
+mod browse;
+mod kv_store;
+mod ratings;
+mod play_history;
+mod bookmarks;
+mod remote_api;
+mod waveform;
+mod track_analysis;
+mod sorting;
+mod connectivity;
+mod api_client;
 mod spotify;
+mod spotify_export;
+mod matching;
+mod now_playing;
+mod dsp_chain;
+mod eq_presets;
+mod migration;
+mod itunes_import;
+mod player_import;
+mod device_sync;
+mod cloud_backup;
+mod bandcamp;
+mod track_notes;
 mod youtube;
 pub mod performance;
 mod error;
+mod error_stats;
 mod error_recovery;
 mod logging;
+mod instrumentation;
 mod system_audio;
+// Only fed by loopback capture, which is Windows-only for now
+mod audio_dsp;
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+mod audio_transport;
+mod visualizer_feed;
 pub mod media_editor;
 
 #[cfg(test)]
@@ -23,37 +93,127 @@ use config::{Config, ConfigManager, FileConfigManager};
 use secure_storage::{PlatformSecureStorage, SecureStorage};
 use library::{LibraryScanner, Track};
 use metadata::{MetadataExtractor, TrackMetadata};
-use playlist::{PlaylistManager, Playlist, Track as PlaylistTrack};
-use skin::{SkinParser, ParsedSkin};
+use playlist::{
+    PlaylistChange, PlaylistDelta, PlaylistManager, PlaylistPage, PlaylistSummary, Playlist, Track as PlaylistTrack,
+};
+use skin::{SkinParser, ParsedSkin, SkinCache};
+use skin_sprites::SkinSpriteExtractor;
+use skin_museum::{SkinMuseumClient, MuseumSkin, MuseumPage};
+use visualizer::{PresetManager, PresetInfo, MilkPreset};
+use media_session::{MediaSession, NowPlaying, PlaybackStatus};
+use scheduler::{Alarm, Scheduler, SleepAction, SleepTimer};
+use kv_store::KvStore;
+use sorting::SortOptions;
 use spotify::{SpotifyBridge, StreamingService, Credentials, Token, TrackMetadata as SpotifyTrackMetadata};
 use youtube::YouTubeBridge;
-use error::{MilkError, MilkResult};
+use error::{CommandError, MilkError, MilkResult};
 use tauri::Emitter;
-use logging::{log_error, log_warn, log_info, log_error_with_context, LoggerConfig};
+use logging::{log_error, log_warn, log_info, log_error_with_context};
 use std::sync::{Arc, Mutex, OnceLock};
-use performance::Timer;
 use media_editor::image_ops::crop_image_command;
 use media_editor::video_ops::{probe_video_metadata_command, trim_and_crop_video_command};
-use system_audio::{SystemAudioCapture, start_system_audio_capture, stop_system_audio_capture, is_system_audio_capture_active};
+use media_editor::audio_ops::{probe_audio_metadata_command, convert_audio_command, trim_audio_command, export_audio_files_command, generate_preview_clip_command};
+use jobs::{cancel_job, list_jobs};
+use media_editor::presets::{list_export_presets, save_custom_preset_command};
+use system_audio::{SystemAudioCapture, list_audio_capture_devices, start_system_audio_capture, stop_system_audio_capture, is_system_audio_capture_active, set_capture_options, get_capture_options, read_capture_buffer};
 
 // Global metadata extractor instance
 static METADATA_EXTRACTOR: OnceLock<MetadataExtractor> = OnceLock::new();
 
 fn get_metadata_extractor() -> &'static MetadataExtractor {
-    METADATA_EXTRACTOR.get_or_init(|| MetadataExtractor::new())
+    METADATA_EXTRACTOR.get_or_init(|| {
+        let extractor = MetadataExtractor::new();
+        let Some(dir) = metadata_cache::default_cache_dir() else {
+            return extractor;
+        };
+        match metadata_cache::MetadataDiskCache::open(&dir) {
+            Ok(disk_cache) => extractor.with_disk_cache(disk_cache),
+            Err(e) => {
+                log_warn("Metadata", &format!("Persistent metadata cache unavailable: {}", e));
+                extractor
+            }
+        }
+    })
 }
 
-// Global playlist manager instance (lazy initialized)
-static PLAYLIST_MANAGER: OnceLock<tokio::sync::Mutex<PlaylistManager>> = OnceLock::new();
+// Search index over the last searched library, rebuilt after a rescan
+static SEARCH_INDEX: Mutex<Option<Arc<search::SearchIndex>>> = Mutex::new(None);
 
-async fn get_playlist_manager() -> &'static tokio::sync::Mutex<PlaylistManager> {
-    // Use get_or_try_init for async initialization
-    if PLAYLIST_MANAGER.get().is_none() {
-        let _ = PLAYLIST_MANAGER.set(tokio::sync::Mutex::new(
-            PlaylistManager::new().await.expect("Failed to initialize playlist manager")
-        ));
+// Shortest file length that gets a resume position, loaded from the config on first use
+static RESUME_MIN_DURATION_SECS: Mutex<Option<u32>> = Mutex::new(None);
+
+// Album artwork thumbnails by album id, kept for the app's lifetime
+static ALBUM_THUMBNAILS: browse::ThumbnailCache = browse::ThumbnailCache::new();
+
+// Playback state mirrored from the frontend for remote clients
+static PLAYER_STATE: remote_api::PlayerState = remote_api::PlayerState::new();
+
+// Running remote-control API server, if enabled
+static REMOTE_SERVER: tokio::sync::Mutex<Option<remote_api::RemoteServer>> = tokio::sync::Mutex::const_new(None);
+
+// Disk cache for seek bar waveforms
+static WAVEFORM_CACHE: OnceLock<Option<waveform::WaveformCache>> = OnceLock::new();
+
+// Stored tempo and key analysis
+static ANALYSIS_STORE: OnceLock<Option<track_analysis::AnalysisStore>> = OnceLock::new();
+
+fn get_analysis_store() -> Option<&'static track_analysis::AnalysisStore> {
+    ANALYSIS_STORE
+        .get_or_init(|| {
+            let dir = track_analysis::default_store_dir()?;
+            track_analysis::AnalysisStore::open(&dir)
+                .map_err(|e| log_warn("Analysis", &format!("Analysis store unavailable: {}", e)))
+                .ok()
+        })
+        .as_ref()
+}
+
+/// Search index over scanned tracks, with stored analysis filling untagged tempo and key
+fn build_search_index(root: &std::path::Path, mut tracks: Vec<Track>) -> search::SearchIndex {
+    // Files brought in by a Winamp or iTunes import stay in the index even
+    // when they live outside the library folder
+    let known: std::collections::HashSet<_> = tracks.iter().map(|track| track.file_path.clone()).collect();
+    let imported: Vec<Track> = migration::imported_files(get_kv_store())
+        .into_iter()
+        .map(|path| path_codec::decode(&path))
+        .filter(|path| !known.contains(path) && path.is_file())
+        .filter_map(|path| LibraryScanner::create_track(&path))
+        .collect();
+    tracks.extend(imported);
+    let mut index = search::SearchIndex::build(root, tracks, get_metadata_extractor());
+    if let Some(store) = get_analysis_store() {
+        index.apply_analysis(|path| store.get(path));
     }
-    PLAYLIST_MANAGER.get().unwrap()
+    index.apply_notes(&track_notes::all_notes(get_kv_store()));
+    index
+}
+
+/// Rebuild the cached library index after files were renamed or retagged
+///
+/// `renamed` maps old paths to new ones; the other files are re-read through
+/// the metadata cache, which notices retagged files by their changed stamps.
+fn reindex_changed_files(renamed: &std::collections::HashMap<&std::path::Path, &std::path::Path>) {
+    let Some(index) = SEARCH_INDEX.lock().unwrap().clone() else {
+        return;
+    };
+    let tracks = index
+        .entries()
+        .filter_map(|(track, _)| match renamed.get(track.file_path.as_path()) {
+            Some(new) => LibraryScanner::create_track(new),
+            None => Some(track.clone()),
+        })
+        .collect();
+    *SEARCH_INDEX.lock().unwrap() = Some(Arc::new(build_search_index(index.root(), tracks)));
+    ALBUM_THUMBNAILS.clear();
+}
+
+// Global playlist manager instance (lazy initialized); it locks per playlist itself
+static PLAYLIST_MANAGER: tokio::sync::OnceCell<PlaylistManager> = tokio::sync::OnceCell::const_new();
+
+async fn get_playlist_manager() -> &'static PlaylistManager {
+    PLAYLIST_MANAGER
+        .get_or_init(|| async { PlaylistManager::new().await.expect("Failed to initialize playlist manager") })
+        .await
 }
 
 // Global Spotify bridge instance (lazy initialized)
@@ -67,6 +227,12 @@ fn get_spotify_bridge() -> &'static SpotifyBridge {
     })
 }
 
+static BANDCAMP: OnceLock<bandcamp::BandcampClient> = OnceLock::new();
+
+fn get_bandcamp() -> &'static bandcamp::BandcampClient {
+    BANDCAMP.get_or_init(bandcamp::BandcampClient::new)
+}
+
 // Global YouTube bridge instance (lazy initialized)
 static YOUTUBE_BRIDGE: OnceLock<YouTubeBridge> = OnceLock::new();
 
@@ -78,6 +244,123 @@ fn get_youtube_bridge() -> &'static YouTubeBridge {
     })
 }
 
+// Global parsed skin cache; a handful of skins is enough to make window switches cheap
+static SKIN_CACHE: OnceLock<SkinCache> = OnceLock::new();
+
+fn get_skin_cache() -> &'static SkinCache {
+    SKIN_CACHE.get_or_init(|| SkinCache::new(4))
+}
+
+/// Check whether a path has a supported skin extension
+fn is_skin_file(skin_path: &str) -> bool {
+    let lower = skin_path.to_lowercase();
+    lower.ends_with(".wsz") || lower.ends_with(".wal")
+}
+
+/// Open files, folders, playlists and skins passed on the command line
+///
+/// Skins go to the frontend as `load-skin-file`; tracks as one `open-files`
+/// event so the whole selection is queued together. `mode` is used unless a
+/// flag picks one.
+fn handle_launch_args(app: tauri::AppHandle, args: Vec<String>, cwd: std::path::PathBuf, mode: cli::LaunchMode) {
+    log_info("FileAssociation", &format!("Received launch arguments: {:?}", args));
+    let parsed = cli::parse_args(&args, &cwd, mode);
+    if parsed.paths.is_empty() {
+        return;
+    }
+    tauri::async_runtime::spawn(async move {
+        let request = match tauri::async_runtime::spawn_blocking(move || cli::resolve(&parsed, get_metadata_extractor())).await {
+            Ok(request) => request,
+            Err(e) => {
+                log_error("FileAssociation", &format!("Failed to read launch arguments: {}", e));
+                return;
+            }
+        };
+        if !request.skipped.is_empty() {
+            log_warn("FileAssociation", &format!("Ignored unsupported arguments: {:?}", request.skipped));
+        }
+        for skin in &request.skins {
+            if let Err(e) = app.emit("load-skin-file", skin) {
+                log_error("FileAssociation", &format!("Failed to emit load-skin-file event: {}", e));
+            }
+        }
+        if !request.tracks.is_empty() {
+            log_info("FileAssociation", &format!("Opening {} tracks ({:?})", request.tracks.len(), request.mode));
+            if let Err(e) = app.emit("open-files", &request) {
+                log_error("FileAssociation", &format!("Failed to emit open-files event: {}", e));
+            }
+        }
+    });
+}
+
+/// Import files dropped on a window and emit the result as `files-imported`
+fn handle_file_drop(window: &tauri::Window, paths: Vec<std::path::PathBuf>) {
+    log_info("FileDrop", &format!("{} path(s) dropped on {}", paths.len(), window.label()));
+    let app = window.app_handle().clone();
+    let label = window.label().to_string();
+    tauri::async_runtime::spawn(async move {
+        let imported = tauri::async_runtime::spawn_blocking(move || {
+            let skin_dir = skin_museum::get_skin_library_dir();
+            file_drop::import_paths(&label, &paths, skin_dir.as_deref(), get_metadata_extractor())
+        })
+        .await;
+        let imported = match imported {
+            Ok(imported) => imported,
+            Err(e) => {
+                log_error("FileDrop", &format!("Failed to import dropped files: {}", e));
+                return;
+            }
+        };
+        for failure in &imported.failed {
+            log_warn("FileDrop", &format!("Could not import {}: {}", failure.path, failure.message));
+        }
+        log_info("FileDrop", &format!(
+            "Imported {} tracks and {} skins ({} skipped)",
+            imported.tracks.len(), imported.skins_installed.len(), imported.skipped.len()
+        ));
+        if let Err(e) = app.emit("files-imported", &imported) {
+            log_error("FileDrop", &format!("Failed to emit files-imported event: {}", e));
+        }
+    });
+}
+
+// Global Milkdrop preset manager
+static PRESET_MANAGER: OnceLock<Mutex<PresetManager>> = OnceLock::new();
+
+fn get_preset_manager() -> &'static Mutex<PresetManager> {
+    PRESET_MANAGER.get_or_init(|| Mutex::new(PresetManager::new()))
+}
+
+// Global key-value store (lazy initialized)
+static KV_STORE: OnceLock<KvStore> = OnceLock::new();
+
+fn get_kv_store() -> &'static KvStore {
+    KV_STORE.get_or_init(|| match KvStore::get_default_path() {
+        Some(path) => KvStore::open(path),
+        None => {
+            log_warn("KvStore", "No config directory available, keeping state in memory");
+            KvStore::in_memory()
+        }
+    })
+}
+
+// OS media controls, set during setup when the platform supports them
+static MEDIA_SESSION: OnceLock<MediaSession> = OnceLock::new();
+
+// Global sleep timer and alarm scheduler (lazy initialized)
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+fn get_scheduler() -> &'static Scheduler {
+    SCHEDULER.get_or_init(Scheduler::new)
+}
+
+// Global Skin Museum client (lazy initialized)
+static SKIN_MUSEUM: OnceLock<SkinMuseumClient> = OnceLock::new();
+
+fn get_skin_museum() -> &'static SkinMuseumClient {
+    SKIN_MUSEUM.get_or_init(SkinMuseumClient::new)
+}
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -85,7 +368,8 @@ fn greet(name: &str) -> String {
 }
 
 #[tauri::command]
-fn load_config() -> Result<Config, String> {
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn load_config() -> Result<Config, CommandError> {
     log_info("Config", "Loading configuration");
     match FileConfigManager::load() {
         Ok(config) => {
@@ -97,19 +381,112 @@ fn load_config() -> Result<Config, String> {
             log_error("Config", &format!("Failed to load config: {}", milk_err));
             
             // Return user-friendly error message
-            Err(milk_err.user_message())
+            Err(milk_err.report())
         }
     }
 }
 
+/// Whether the setup wizard still has to be completed
+#[tauri::command]
+fn is_first_run() -> Result<bool, CommandError> {
+    let config_path = FileConfigManager::get_config_path().map_err(|e| MilkError::from(e).report())?;
+    Ok(setup::needs_setup(get_kv_store(), config_path.exists()))
+}
+
+/// Setup wizard progress, current choices and initial index build progress
+#[tauri::command]
+fn get_setup_state() -> Result<setup::SetupState, CommandError> {
+    let config_path = FileConfigManager::get_config_path().map_err(|e| MilkError::from(e).report())?;
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    Ok(setup::state(get_kv_store(), &config, config_path.exists()))
+}
+
+/// Save one setup wizard step
+///
+/// Choosing a library folder starts its first index build, reported through
+/// `setup-index-progress` events.
 #[tauri::command]
-fn is_first_run() -> Result<bool, String> {
-    let config_path = FileConfigManager::get_config_path().map_err(|e| e.to_string())?;
-    Ok(!config_path.exists())
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn complete_setup_step(
+    app: tauri::AppHandle,
+    step: setup::SetupStep,
+    payload: serde_json::Value,
+) -> Result<setup::SetupProgressView, CommandError> {
+    log_info("Setup", &format!("Completing setup step {:?}", step));
+    let result = FileConfigManager::get_config_path()
+        .map_err(MilkError::from)
+        .and_then(|config_path| {
+            setup::complete_step(get_kv_store(), &config_path, step, payload).map_err(MilkError::from)
+        });
+    let progress = result.map_err(|milk_err| {
+        log_error("Setup", &format!("Failed to complete setup step {:?}: {}", step, milk_err));
+        milk_err.report()
+    })?;
+
+    if step == setup::SetupStep::Library {
+        if let Some(library_path) = FileConfigManager::load().ok().and_then(|config| config.library_path) {
+            start_initial_index(app, std::path::PathBuf::from(library_path));
+        }
+    }
+    Ok(progress)
+}
+
+/// Tags read between cancellation checks while indexing
+const INDEX_TAG_CHUNK: usize = 500;
+
+/// Scan the library, read its tags and build the search index as a background job
+fn start_initial_index(app: tauri::AppHandle, root: std::path::PathBuf) {
+    use setup::{IndexPhase, IndexProgress};
+    let sink = jobs::app_sink(app.clone());
+    let report = move |phase, completed, total, error| {
+        setup::report_index_progress(&app, IndexProgress { phase, completed, total, error });
+    };
+    let work = move |ctx: &jobs::JobContext| {
+        let cancelled = || {
+            report(IndexPhase::Failed, 0, 0, Some("Indexing cancelled".to_string()));
+            Err(jobs::CANCELLED.to_string())
+        };
+        report(IndexPhase::Scanning, 0, 0, None);
+        let tracks = match scan_library_with_timing(&root, ctx.cancel_flag()) {
+            _ if ctx.is_cancelled() => return cancelled(),
+            Ok(tracks) => tracks,
+            Err(e) => {
+                log_error_with_context("Setup", &e, "Initial library scan failed");
+                report(IndexPhase::Failed, 0, 0, Some(e.user_message()));
+                return Err(e.to_string());
+            }
+        };
+        let paths: Vec<std::path::PathBuf> = tracks.iter().map(|t| t.file_path.clone()).collect();
+        report(IndexPhase::ReadingTags, 0, paths.len(), None);
+        ctx.report_items(0, paths.len() as u64);
+        let mut completed = 0;
+        for chunk in paths.chunks(INDEX_TAG_CHUNK) {
+            if ctx.is_cancelled() {
+                return cancelled();
+            }
+            get_metadata_extractor().extract_batch(chunk, |progress| {
+                report(IndexPhase::ReadingTags, completed + progress.completed, paths.len(), None);
+                ctx.report_items((completed + progress.completed) as u64, paths.len() as u64);
+            });
+            completed += chunk.len();
+        }
+
+        // Tags are cached by now, so building the index is quick
+        let index = Arc::new(build_search_index(&root, tracks));
+        log_info("Setup", &format!("Initial index built with {} tracks", index.len()));
+        let total = index.len();
+        *SEARCH_INDEX.lock().unwrap() = Some(index);
+        report(IndexPhase::Done, total, total, None);
+        Ok(())
+    };
+    tauri::async_runtime::spawn(async move {
+        let job_id = jobs::resolve_job_id(None);
+        let _ = jobs::job_manager().run(job_id, jobs::JobKind::LibraryScan, "initial_index", sink, work).await;
+    });
 }
 
 #[tauri::command]
-fn validate_directory_path(path: String) -> Result<bool, String> {
+fn validate_directory_path(path: String) -> Result<bool, CommandError> {
     use std::path::Path;
     let dir_path = Path::new(&path);
     
@@ -130,616 +507,3166 @@ fn validate_directory_path(path: String) -> Result<bool, String> {
     }
 }
 
+/// Save the configuration, merging with changes made by other windows
+///
+/// `base` is the configuration this window last loaded. When the save is
+/// stale and cannot be merged, a `config-conflict` event is emitted with the
+/// current on-disk configuration so the UI can reload and retry.
 #[tauri::command]
-fn save_config(config: Config) -> Result<(), String> {
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn save_config(app: tauri::AppHandle, config: Config, base: Option<Config>) -> Result<Config, CommandError> {
     log_info("Config", "Saving configuration");
     let manager = FileConfigManager;
-    match manager.save(&config) {
-        Ok(()) => {
-            log_info("Config", "Configuration saved successfully");
-            Ok(())
+    let previous_filters = FileConfigManager::load().ok().map(|current| current.library_filters);
+    match manager.save_merged(base.as_ref(), &config) {
+        Ok(saved) => {
+            log_info("Config", &format!("Configuration saved (revision {})", saved.revision));
+            if previous_filters.as_ref() != Some(&saved.library_filters) {
+                *SEARCH_INDEX.lock().unwrap() = None;
+                ALBUM_THUMBNAILS.clear();
+            }
+            logging::reconfigure_logger(saved.logging.to_logger_config());
+            if let Err(e) = hotkeys::apply(&app, &saved.hotkeys, false) {
+                log_warn("Hotkeys", &format!("Global hotkeys not updated: {}", e));
+            }
+            if let Some(unified) = now_playing::set_priority(&saved.playback.source_priority) {
+                publish_now_playing(Some(&app), &unified);
+            }
+            *RESUME_MIN_DURATION_SECS.lock().unwrap() = Some(saved.playback.resume_min_duration_secs);
+            Ok(saved)
+        }
+        Err(config::ConfigError::Conflict(conflict)) => {
+            log_warn("Config", &format!("Rejected stale config save: {}", conflict.fields.join(", ")));
+            if let Err(e) = app.emit("config-conflict", &*conflict) {
+                log_error("Config", &format!("Failed to emit config-conflict event: {}", e));
+            }
+            Err(MilkError::ConfigConflict(conflict.fields.join(", ")).report())
         }
         Err(e) => {
             let milk_err = MilkError::from(e);
             log_error("Config", &format!("Failed to save config: {}", milk_err));
-            Err(milk_err.user_message())
+            Err(milk_err.report())
         }
     }
 }
 
+/// Read a single setting by dotted key, e.g. `volume` or `hotkeys.enabled`
 #[tauri::command]
-fn store_credential(key: String, value: String) -> Result<(), String> {
-    log_info("Storage", &format!("Storing credential: {}", key));
-    let storage = PlatformSecureStorage::new();
-    match storage.store(&key, &value) {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Storage", &format!("Failed to store credential: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn get_setting(key: String) -> Result<serde_json::Value, CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    config::get_setting(&config, &key).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Config", &format!("Failed to read setting {}: {}", key, milk_err));
+        milk_err.report()
+    })
 }
 
+/// Change a single setting without sending the whole configuration
+///
+/// Returns the saved configuration. Hotkeys are re-applied when their
+/// settings change.
 #[tauri::command]
-fn retrieve_credential(key: String) -> Result<Option<String>, String> {
-    let storage = PlatformSecureStorage::new();
-    match storage.retrieve(&key) {
-        Ok(value) => Ok(value),
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_setting(app: tauri::AppHandle, key: String, value: serde_json::Value) -> Result<Config, CommandError> {
+    log_info("Config", &format!("Updating setting {}", key));
+    match FileConfigManager.update_setting(&key, value) {
+        Ok(saved) => {
+            if key.starts_with("hotkeys") {
+                if let Err(e) = hotkeys::apply(&app, &saved.hotkeys, false) {
+                    log_warn("Hotkeys", &format!("Global hotkeys not updated: {}", e));
+                }
+            }
+            if key.starts_with("logging") {
+                logging::reconfigure_logger(saved.logging.to_logger_config());
+            }
+            if key.starts_with("library_filters") {
+                *SEARCH_INDEX.lock().unwrap() = None;
+                ALBUM_THUMBNAILS.clear();
+            }
+            if key.starts_with("playback") {
+                if let Some(unified) = now_playing::set_priority(&saved.playback.source_priority) {
+                    publish_now_playing(Some(&app), &unified);
+                }
+                *RESUME_MIN_DURATION_SECS.lock().unwrap() = Some(saved.playback.resume_min_duration_secs);
+            }
+            Ok(saved)
+        }
         Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Storage", &format!("Failed to retrieve credential: {}", milk_err));
-            Err(milk_err.user_message())
+            log_error("Config", &format!("Failed to update setting {}: {}", key, e));
+            Err(MilkError::from(e).report())
         }
     }
 }
 
+/// Change the log level immediately and remember it across restarts
 #[tauri::command]
-fn delete_credential(key: String) -> Result<(), String> {
-    log_info("Storage", &format!("Deleting credential: {}", key));
-    let storage = PlatformSecureStorage::new();
-    match storage.delete(&key) {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Storage", &format!("Failed to delete credential: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn set_log_level(level: logging::LogLevel) -> Result<(), CommandError> {
+    update_logging_settings(vec![("logging.level".to_string(), serde_json::json!(level))])
 }
 
-/// Helper function using MilkResult to scan library with performance tracking
-fn scan_library_with_timing(path: &std::path::Path) -> MilkResult<Vec<Track>> {
-    let _timer = Timer::new(format!("Library scan: {}", path.display()));
-    LibraryScanner::scan_directory(path).map_err(MilkError::from)
+/// Change the log rotation size (bytes) and number of rotated files kept
+#[tauri::command]
+fn set_log_rotation(max_file_size: u64, max_files: usize) -> Result<(), CommandError> {
+    update_logging_settings(vec![
+        ("logging.max_file_size".to_string(), serde_json::json!(max_file_size)),
+        ("logging.max_files".to_string(), serde_json::json!(max_files)),
+    ])
 }
 
-/// Validate audio file format (constructs DecodeError and UnsupportedFormat variants)
-fn validate_audio_format(file_path: &std::path::Path) -> MilkResult<()> {
-    let extension = file_path.extension()
-        .and_then(|e| e.to_str())
-        .ok_or_else(|| MilkError::UnsupportedFormat("unknown".to_string()))?;
+fn update_logging_settings(changes: Vec<(String, serde_json::Value)>) -> Result<(), CommandError> {
+    let saved = FileConfigManager.update_settings(changes).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Logging", &format!("Failed to update logging settings: {}", milk_err));
+        milk_err.report()
+    })?;
+    logging::reconfigure_logger(saved.logging.to_logger_config());
+    log_info("Logging", &format!("Log level {:?}, rotating at {} bytes, keeping {} files", saved.logging.level, saved.logging.max_file_size, saved.logging.max_files));
+    Ok(())
+}
 
-    // Check if extension is supported
-    if !LibraryScanner::is_supported_extension(extension) {
-        return Err(MilkError::UnsupportedFormat(extension.to_string()));
-    }
+/// Output devices the headless player can use, with the sample rates they open at
+/// and whether they can play bit-perfect
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn list_output_devices() -> Result<Vec<headless::OutputDevice>, CommandError> {
+    headless::list_output_devices().map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Headless", &format!("Failed to list output devices: {}", milk_err));
+        milk_err.report()
+    })
+}
 
-    // In a real implementation, we might verify the file header
-    // For now, we just check if the file exists and is readable
-    if !file_path.exists() {
-        return Err(MilkError::DecodeError("File does not exist".to_string()));
-    }
+/// Send the headless player's audio to the output device or a Snapcast server
+///
+/// Headless only: a running headless player picks the change up from the
+/// config file. The windowed player plays in the webview and always uses the
+/// system output, so multi-room playback needs `--headless`.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_headless_output_mode(mode: config::OutputMode) -> Result<Config, CommandError> {
+    let saved = FileConfigManager.update_setting("output.mode", serde_json::json!(mode)).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Config", &format!("Failed to set headless output mode: {}", milk_err));
+        milk_err.report()
+    })?;
+    log_info("Config", &format!("Headless output mode set to {:?}", saved.output.mode));
+    Ok(saved)
+}
 
-    Ok(())
+/// Replace the effects applied to the player's output
+///
+/// Saved to the config, where a running headless player picks it up and the
+/// windowed player rebuilds its Web Audio nodes on `config-changed`.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_dsp_chain(chain: dsp_chain::DspChain) -> Result<Config, CommandError> {
+    let saved = FileConfigManager.update_setting("dsp", serde_json::json!(chain)).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Config", &format!("Failed to set DSP chain: {}", milk_err));
+        milk_err.report()
+    })?;
+    log_info("Config", &format!("DSP chain set to {} effect(s)", saved.dsp.effects.len()));
+    Ok(saved)
 }
 
-/// Load and validate config (constructs InvalidConfig, MissingConfig variants)
-fn load_and_validate_config() -> MilkResult<Config> {
-    let config = match FileConfigManager::load() {
-        Ok(c) => c,
-        Err(_) => {
-            // If config doesn't exist, that's MissingConfig
-            return Err(MilkError::MissingConfig("configuration file".to_string()));
-        }
-    };
+/// Export config, playlists, play history, ratings and the skin list to a zip for moving to another machine
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn export_app_data(path: String) -> Result<data_bundle::BundleManifest, CommandError> {
+    log_info("DataBundle", &format!("Exporting app data to {}", path));
+    data_bundle::DataPaths::resolve()
+        .and_then(|paths| data_bundle::export_bundle(std::path::Path::new(&path), &paths, get_kv_store()))
+        .map_err(|e| {
+            let milk_err = MilkError::from(e);
+            log_error("DataBundle", &format!("Failed to export app data: {}", milk_err));
+            milk_err.report()
+        })
+}
 
-    // Validate required fields
-    if config.library_path.is_none() {
-        return Err(MilkError::MissingConfig("library_path".to_string()));
-    }
+/// Import a bundle written by `export_app_data`
+///
+/// The imported config is picked up by the config watcher and broadcast as
+/// `config-changed`.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn import_app_data(path: String) -> Result<data_bundle::ImportSummary, CommandError> {
+    log_info("DataBundle", &format!("Importing app data from {}", path));
+    data_bundle::DataPaths::resolve()
+        .and_then(|paths| data_bundle::import_bundle(std::path::Path::new(&path), &paths, get_kv_store()))
+        .map_err(|e| {
+            let milk_err = MilkError::from(e);
+            log_error("DataBundle", &format!("Failed to import app data: {}", milk_err));
+            milk_err.report()
+        })
+}
 
-    // Validate library path exists
-    if let Some(ref path) = config.library_path {
-        if !std::path::Path::new(path).exists() {
-            return Err(MilkError::InvalidConfig(format!("library_path: {} does not exist", path)));
-        }
-    }
+/// Re-register global hotkeys from the saved configuration and report conflicts
+#[tauri::command]
+fn reload_hotkeys(app: tauri::AppHandle) -> Result<hotkeys::HotkeyReport, CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    hotkeys::apply(&app, &config.hotkeys, true).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Hotkeys", &format!("Failed to register hotkeys: {}", milk_err));
+        milk_err.report()
+    })
+}
 
-    Ok(config)
+/// Saved placement of a window, for the frontend to create it where it was left
+#[tauri::command]
+fn get_window_geometry(label: String) -> Option<config::WindowGeometry> {
+    window_geometry::saved_geometry(&label)
 }
 
-/// Generic error handler that can construct Internal error variant
-fn handle_unexpected_error<T>(result: Result<T, Box<dyn std::error::Error>>) -> MilkResult<T> {
-    result.map_err(|e| MilkError::Internal(format!("Unexpected error: {}", e)))
+/// Keep a window above all others, as Winamp's "Always on top"
+#[tauri::command]
+fn set_always_on_top(app: tauri::AppHandle, window: String, enabled: bool) -> Result<(), CommandError> {
+    window_geometry::set_always_on_top(&app, &window, enabled).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Windows", &format!("Failed to set always on top for {}: {}", window, milk_err));
+        milk_err.report()
+    })
 }
 
+/// Collapse a window to its title bar strip, sized from the current skin
 #[tauri::command]
-fn scan_library(path: String) -> Result<Vec<Track>, String> {
-    use std::path::Path;
-    log_info("Library", &format!("Scanning library: {}", path));
-    let library_path = Path::new(&path);
+fn set_window_shade(app: tauri::AppHandle, window: String, shaded: bool) -> Result<(), CommandError> {
+    let skin = FileConfigManager::load()
+        .ok()
+        .and_then(|config| config.last_skin)
+        .and_then(|path| get_skin_cache().get_or_parse(std::path::Path::new(&path)).ok());
+    let shade = match skin {
+        Some(skin) => SkinParser::shade_size(&skin, &window),
+        None => SkinParser::shade_size(&SkinParser::get_default_skin(), &window),
+    };
+    window_geometry::set_shaded(&app, &window, shaded, shade).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Windows", &format!("Failed to change shade mode for {}: {}", window, milk_err));
+        milk_err.report()
+    })
+}
 
-    match scan_library_with_timing(library_path) {
-        Ok(tracks) => {
-            log_info("Library", &format!("Found {} tracks", tracks.len()));
-            Ok(tracks)
-        }
-        Err(e) => {
-            log_error_with_context("Library", &e, "Failed to scan library");
-            Err(e.user_message())
-        }
+/// Create or remove the tray icon to match the saved configuration
+#[tauri::command]
+fn reload_tray(app: tauri::AppHandle) -> Result<(), CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    tray::apply(&app, &config.tray).map_err(|e| {
+        let milk_err = MilkError::Other(e.to_string());
+        log_error("Tray", &format!("Failed to update tray icon: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Start, restart or stop the remote API to match `settings`
+///
+/// Transport commands go to `control`: the frontend player, or the headless one.
+async fn apply_remote_api(control: remote_api::ControlSink, settings: &config::RemoteApiSettings) -> Result<remote_api::RemoteApiStatus, MilkError> {
+    let mut server = REMOTE_SERVER.lock().await;
+    // Stop first so a restart on the same port can bind
+    if let Some(running) = server.take() {
+        running.stop().await;
+    }
+    if settings.enabled {
+        let token = remote_api::get_or_create_token(&PlatformSecureStorage::new())?;
+        let started = remote_api::RemoteServer::start(settings, &token, &PLAYER_STATE, control)?;
+        log_info("RemoteApi", &format!("Remote API listening on {}", started.addr()));
+        *server = Some(started);
     }
+    Ok(remote_api_status(server.as_ref(), settings))
 }
 
-#[tauri::command]
-fn extract_metadata(file_path: String) -> Result<TrackMetadata, String> {
-    use std::path::Path;
-    let path = Path::new(&file_path);
-    let extractor = get_metadata_extractor();
-    match extractor.extract(path) {
-        Ok(metadata) => Ok(metadata),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_warn("Metadata", &format!("Metadata extraction failed for {}: {}", file_path, milk_err));
-            // For metadata errors, we still want to return something (fallback will be applied)
-            // So we log as warning but still return the error
-            Err(milk_err.user_message())
+/// Forward remote transport commands to the frontend player as `media-control` events
+fn media_control_sink(app: tauri::AppHandle) -> remote_api::ControlSink {
+    Arc::new(move |action| {
+        if let Err(e) = app.emit("media-control", &action) {
+            log_warn("RemoteApi", &format!("Failed to emit media-control event: {}", e));
         }
+    })
+}
+
+fn remote_api_status(server: Option<&remote_api::RemoteServer>, settings: &config::RemoteApiSettings) -> remote_api::RemoteApiStatus {
+    remote_api::RemoteApiStatus {
+        running: server.is_some(),
+        port: server.map(|s| s.addr().port()),
+        allow_lan: settings.allow_lan,
     }
 }
 
+/// Apply the saved remote API settings, starting or stopping the server
 #[tauri::command]
-fn extract_artwork(file_path: String) -> Result<Option<Vec<u8>>, String> {
-    use std::path::Path;
-    let path = Path::new(&file_path);
-    let extractor = get_metadata_extractor();
-    extractor.extract_artwork(path).map_err(|e| e.to_string())
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn reload_remote_api(app: tauri::AppHandle) -> Result<remote_api::RemoteApiStatus, CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    apply_remote_api(media_control_sink(app), &config.remote_api).await.map_err(|milk_err| {
+        log_error("RemoteApi", &format!("Failed to start remote API: {}", milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-async fn create_playlist(name: String) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Creating playlist: {}", name));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.create_playlist(name).await {
-        Ok(playlist) => {
-            log_info("Playlist", &format!("Playlist created: {}", playlist.id));
-            Ok(playlist)
-        }
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to create playlist: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+async fn get_remote_api_status() -> Result<remote_api::RemoteApiStatus, CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    Ok(remote_api_status(REMOTE_SERVER.lock().await.as_ref(), &config.remote_api))
 }
 
+/// Token remote clients must send, created on first use
 #[tauri::command]
-async fn list_playlists() -> Result<Vec<Playlist>, String> {
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.list_playlists().await {
-        Ok(playlists) => Ok(playlists),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to list playlists: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn get_remote_api_token() -> Result<String, CommandError> {
+    remote_api::get_or_create_token(&PlatformSecureStorage::new()).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("RemoteApi", &format!("Failed to read API token: {}", milk_err));
+        milk_err.report()
+    })
 }
 
+/// Issue a new token and restart the server so old clients are locked out
 #[tauri::command]
-async fn load_playlist(playlist_id: String) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Loading playlist: {}", playlist_id));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.load_playlist(&playlist_id).await {
-        Ok(playlist) => Ok(playlist),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to load playlist: {}", milk_err));
-            Err(milk_err.user_message())
-        }
+async fn regenerate_remote_api_token(app: tauri::AppHandle) -> Result<String, CommandError> {
+    let to_user = |milk_err: MilkError| {
+        log_error("RemoteApi", &format!("Failed to regenerate API token: {}", milk_err));
+        milk_err.report()
+    };
+    let token = remote_api::regenerate_token(&PlatformSecureStorage::new()).map_err(|e| to_user(e.into()))?;
+    log_info("RemoteApi", "API token regenerated");
+    if REMOTE_SERVER.lock().await.is_some() {
+        let config = FileConfigManager::load().map_err(|e| to_user(e.into()))?;
+        apply_remote_api(media_control_sink(app), &config.remote_api).await.map_err(to_user)?;
     }
+    Ok(token)
 }
 
+/// Publish the play queue for remote clients; also saved with the session
 #[tauri::command]
-async fn delete_playlist(playlist_id: String) -> Result<(), String> {
-    log_info("Playlist", &format!("Deleting playlist: {}", playlist_id));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.delete_playlist(&playlist_id).await {
-        Ok(()) => {
-            log_info("Playlist", "Playlist deleted successfully");
-            Ok(())
-        }
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to delete playlist: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn update_remote_queue(tracks: Vec<PlaylistTrack>, current_index: Option<usize>) {
+    session::update(session::SessionUpdate {
+        queue: Some(tracks.clone()),
+        current_index,
+        ..Default::default()
+    });
+    PLAYER_STATE.set_queue(tracks, current_index);
 }
 
+/// Save changed parts of the player session (queue, position, shuffle/repeat, windows)
+///
+/// Position updates also save the resume position of long local files.
 #[tauri::command]
-async fn add_track_to_playlist(playlist_id: String, track: PlaylistTrack) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Adding track to playlist: {}", playlist_id));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.add_track(&playlist_id, track).await {
-        Ok(playlist) => Ok(playlist),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to add track: {}", milk_err));
-            Err(milk_err.user_message())
-        }
+fn update_session(update: session::SessionUpdate) {
+    let position_secs = update.position_secs;
+    session::update(update);
+    if let Some(position_secs) = position_secs {
+        save_resume_position(position_secs);
     }
 }
 
-#[tauri::command]
-async fn remove_track_from_playlist(playlist_id: String, track_id: String) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Removing track from playlist: {}", playlist_id));
+/// Save the playing file's resume position if it is long enough to resume
+fn save_resume_position(position_secs: f64) {
+    let Some(track) = session::current_track() else {
+        return;
+    };
+    let Some(file_path) = track.file_path else {
+        return;
+    };
+    let duration_secs = if track.duration > 0.0 {
+        track.duration
+    } else {
+        match get_metadata_extractor().extract(&path_codec::decode(&file_path)).ok().and_then(|m| m.duration) {
+            Some(duration) => duration as f64,
+            None => return,
+        }
+    };
+    let min_duration_secs = *RESUME_MIN_DURATION_SECS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| FileConfigManager::load().map(|config| config.playback).unwrap_or_default().resume_min_duration_secs);
+    let recorded = bookmarks::record_position(
+        get_kv_store(),
+        &file_path,
+        position_secs,
+        duration_secs,
+        min_duration_secs as f64,
+        chrono::Utc::now(),
+    );
+    if let Err(e) = recorded {
+        log_warn("Bookmarks", &format!("Failed to save resume position: {}", e));
+    }
+}
+
+/// Bookmark a position in a file
+#[tauri::command]
+fn add_bookmark(file_path: String, position: f64, label: Option<String>) -> Result<bookmarks::Bookmark, CommandError> {
+    bookmarks::add_bookmark(get_kv_store(), &file_path, position, label, chrono::Utc::now()).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Bookmarks", &format!("Failed to bookmark {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Bookmarks of a file, in position order
+#[tauri::command]
+fn list_bookmarks(file_path: String) -> Vec<bookmarks::Bookmark> {
+    bookmarks::list_bookmarks(get_kv_store(), &file_path)
+}
+
+#[tauri::command]
+fn remove_bookmark(file_path: String, bookmark_id: String) -> Result<bool, CommandError> {
+    bookmarks::remove_bookmark(get_kv_store(), &file_path, &bookmark_id).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Bookmarks", &format!("Failed to remove bookmark from {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Import Winamp EQ presets from an .eqf or .q1 file, or a folder of them
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn import_eq_presets(path: String) -> Result<eq_presets::EqImportReport, CommandError> {
+    log_info("Equalizer", &format!("Importing EQ presets from {}", path));
+    let report = eq_presets::import_presets(get_kv_store(), std::path::Path::new(&path)).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Equalizer", &format!("Failed to import EQ presets: {}", milk_err));
+        milk_err.report()
+    })?;
+    for failure in &report.failed {
+        log_warn("Equalizer", &format!("Skipped preset file {}", failure));
+    }
+    log_info("Equalizer", &format!("Imported {} new and {} replaced presets", report.added, report.replaced));
+    Ok(report)
+}
+
+/// Saved EQ presets, sorted by name
+#[tauri::command]
+fn list_eq_presets() -> Vec<eq_presets::EqPreset> {
+    eq_presets::list_presets(get_kv_store())
+}
+
+/// Import a Winamp installation's playlists, media library and EQ presets
+///
+/// The main playlist and each media library playlist become milkline
+/// playlists, and the library's files are saved as a "Winamp Library"
+/// playlist and kept in the search index across rescans. Playlists that
+/// already exist by name gain only the files they lack, so importing again
+/// is safe. Runs as a job reporting files read; files that no longer exist
+/// are counted and left out.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn import_from_winamp(app: tauri::AppHandle, winamp_dir: String) -> Result<migration::MigrationReport, CommandError> {
+    log_info("Migration", &format!("Importing Winamp installation: {}", winamp_dir));
+    let dir = path_codec::decode(&winamp_dir);
+    let work = move |ctx: &jobs::JobContext| {
+        let data = migration::read_installation(
+            &dir,
+            get_kv_store(),
+            get_metadata_extractor(),
+            ctx.cancel_flag(),
+            |completed, total| ctx.report_items(completed, total),
+        )
+        .map_err(MilkError::from)?;
+        ctx.checkpoint()?;
+        Ok::<_, MilkError>(data)
+    };
+
+    let result = async {
+        let data = jobs::job_manager()
+            .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "import_from_winamp", jobs::app_sink(app), work)
+            .await?;
+        let (playlists, indexed_tracks) =
+            save_imported_library(data.playlists, migration::LIBRARY_PLAYLIST_NAME, data.library.clone()).await?;
+        Ok::<_, MilkError>(migration::MigrationReport {
+            playlists,
+            library_tracks: data.library.len(),
+            indexed_tracks,
+            missing_files: data.missing_files,
+            eq_presets: data.eq_presets,
+            warnings: data.warnings,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(report) => {
+            for warning in &report.warnings {
+                log_warn("Migration", &format!("Skipped {}", warning));
+            }
+            log_info("Migration", &format!(
+                "Imported {} playlists, {} library tracks and {} EQ presets ({} missing files)",
+                report.playlists.len(),
+                report.library_tracks,
+                report.eq_presets.added + report.eq_presets.replaced,
+                report.missing_files
+            ));
+            Ok(report)
+        }
+        Err(milk_err) => {
+            log_error("Migration", &format!("Failed to import Winamp installation: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Import an iTunes or Music app library export
+///
+/// Ratings (where milkline has none yet), play counts and dates added are
+/// carried over, user playlists are recreated, and the library's files are
+/// saved as an "iTunes Library" playlist and kept in the search index across
+/// rescans; playlists that already exist by name gain only the files they
+/// lack. `remap_to` replaces `remap_from`, by default the export's music
+/// folder, at the start of every location, for libraries moved from another
+/// machine. Runs as a job reporting tracks read.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn import_itunes_library(
+    app: tauri::AppHandle,
+    xml_path: String,
+    remap_to: Option<String>,
+    remap_from: Option<String>,
+) -> Result<itunes_import::ItunesImportReport, CommandError> {
+    log_info("Migration", &format!("Importing iTunes library: {}", xml_path));
+    let remap = remap_to.map(|to| itunes_import::PathRemap { from: remap_from, to: path_codec::decode(&to) });
+    let work = move |ctx: &jobs::JobContext| {
+        let xml = std::fs::read(&xml_path).map_err(MilkError::from)?;
+        let library = itunes_import::parse_library(&String::from_utf8_lossy(&xml), remap.as_ref()).map_err(MilkError::from)?;
+        let data = itunes_import::import_library(
+            get_kv_store(),
+            &library,
+            get_metadata_extractor(),
+            ctx.cancel_flag(),
+            |completed, total| ctx.report_items(completed, total),
+        )
+        .map_err(MilkError::from)?;
+        ctx.checkpoint()?;
+        Ok::<_, MilkError>(data)
+    };
+
+    let result = async {
+        let data = jobs::job_manager()
+            .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "import_itunes_library", jobs::app_sink(app), work)
+            .await?;
+        system_playlists::clear_recently_added_cache();
+        let (playlists, indexed_tracks) =
+            save_imported_library(data.playlists, itunes_import::LIBRARY_PLAYLIST_NAME, data.library.clone()).await?;
+        Ok::<_, MilkError>(itunes_import::ItunesImportReport {
+            playlists,
+            library_tracks: data.library.len(),
+            indexed_tracks,
+            ratings: data.ratings,
+            play_counts: data.play_counts,
+            dates_added: data.dates_added,
+            missing_files: data.missing_files,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(report) => {
+            log_info("Migration", &format!(
+                "Imported {} playlists and {} library tracks ({} ratings, {} play counts, {} missing files)",
+                report.playlists.len(),
+                report.library_tracks,
+                report.ratings,
+                report.play_counts,
+                report.missing_files
+            ));
+            Ok(report)
+        }
+        Err(milk_err) => {
+            log_error("Migration", &format!("Failed to import iTunes library: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Import playlists and ratings from foobar2000 or MusicBee
+///
+/// `path` is a playlist file, a folder of playlists or the player's profile
+/// folder. Ratings only fill in for files milkline has no rating for. Runs as
+/// a job reporting playlist entries read.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn import_from_player(
+    app: tauri::AppHandle,
+    kind: player_import::PlayerKind,
+    path: String,
+) -> Result<player_import::PlayerImportReport, CommandError> {
+    log_info("Migration", &format!("Importing {:?} playlists from {}", kind, path));
+    let source = path_codec::decode(&path);
+    let work = move |ctx: &jobs::JobContext| {
+        let data = player_import::read_player(
+            kind,
+            &source,
+            get_kv_store(),
+            get_metadata_extractor(),
+            ctx.cancel_flag(),
+            |completed, total| ctx.report_items(completed, total),
+        )
+        .map_err(MilkError::from)?;
+        ctx.checkpoint()?;
+        Ok::<_, MilkError>(data)
+    };
+
+    let result = async {
+        let data = jobs::job_manager()
+            .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "import_from_player", jobs::app_sink(app), work)
+            .await?;
+        let manager = get_playlist_manager().await;
+        let mut playlists = Vec::with_capacity(data.playlists.len());
+        for playlist in data.playlists {
+            playlists.push(manager.import_playlist(playlist.name, playlist.tracks).await?.summary());
+        }
+        Ok::<_, MilkError>(player_import::PlayerImportReport {
+            kind,
+            playlists,
+            ratings: data.ratings,
+            play_counts: data.play_counts,
+            missing_files: data.missing_files,
+            warnings: data.warnings,
+        })
+    }
+    .await;
+
+    match result {
+        Ok(report) => {
+            for warning in &report.warnings {
+                log_warn("Migration", &format!("Skipped {}", warning));
+            }
+            log_info("Migration", &format!(
+                "Imported {} playlists and {} ratings ({} missing files)",
+                report.playlists.len(),
+                report.ratings,
+                report.missing_files
+            ));
+            Ok(report)
+        }
+        Err(milk_err) => {
+            log_error("Migration", &format!("Failed to import {:?} playlists: {}", kind, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Sync playlists and tracks to a device or folder
+///
+/// Files are laid out by `options.pattern` and converted by the first
+/// matching transcoding rule; each playlist is written as an M3U8 at the
+/// target's root. Later syncs to the same target only write what changed.
+/// Runs as a job reporting files synced.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn sync_to_device(
+    app: tauri::AppHandle,
+    target: String,
+    playlist_ids: Vec<String>,
+    tracks: Vec<String>,
+    options: Option<device_sync::SyncOptions>,
+) -> Result<device_sync::SyncReport, CommandError> {
+    log_info("DeviceSync", &format!("Syncing {} playlists and {} tracks to {}", playlist_ids.len(), tracks.len(), target));
+    let destination = path_codec::decode(&target);
+    let options = options.unwrap_or_default();
+
+    let result = async {
+        let mut playlists = Vec::with_capacity(playlist_ids.len());
+        for id in &playlist_ids {
+            let playlist = load_any_playlist(id).await?;
+            let entries = playlist.tracks.iter().filter_map(|t| t.file_path.as_deref()).map(path_codec::decode).collect();
+            playlists.push((playlist.name, entries));
+        }
+        let tracks: Vec<_> = tracks.iter().map(|t| path_codec::decode(t)).collect();
+
+        let work = move |ctx: &jobs::JobContext| {
+            let plan = device_sync::plan_sync(&destination, playlists, &tracks, &options, get_metadata_extractor(), |path| {
+                media_editor::audio_ops::probe_audio_metadata(&path.to_string_lossy())
+                    .ok()
+                    .and_then(|m| m.bit_rate)
+                    .map(|bps| (bps / 1000) as u32)
+            })
+            .map_err(MilkError::from)?;
+            ctx.checkpoint()?;
+            let report = device_sync::run_sync(&destination, &plan, ctx.cancel_flag(), |completed, total| {
+                ctx.report_items(completed, total)
+            })
+            .map_err(MilkError::from)?;
+            ctx.checkpoint()?;
+            Ok::<_, MilkError>(report)
+        };
+        jobs::job_manager()
+            .run(jobs::resolve_job_id(None), jobs::JobKind::Media, "sync_to_device", jobs::app_sink(app), work)
+            .await
+    }
+    .await;
+
+    match result {
+        Ok(report) => {
+            for failure in &report.failed {
+                log_warn("DeviceSync", &format!("Failed to sync {}", failure));
+            }
+            log_info("DeviceSync", &format!(
+                "Synced to {}: {} copied, {} transcoded, {} unchanged, {} removed",
+                report.target, report.copied, report.transcoded, report.unchanged, report.removed
+            ));
+            Ok(report)
+        }
+        Err(milk_err) => {
+            log_error("DeviceSync", &format!("Failed to sync to {}: {}", target, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Save playlists imported from another player, plus one named `library_name`
+/// holding its library, and add the library to the search index
+///
+/// Returns the created playlists and how many tracks the index gained.
+async fn save_imported_library(
+    playlists: Vec<migration::ImportedPlaylist>,
+    library_name: &str,
+    library: Vec<PlaylistTrack>,
+) -> Result<(Vec<PlaylistSummary>, usize), MilkError> {
     let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.remove_track(&playlist_id, &track_id).await {
-        Ok(playlist) => Ok(playlist),
+    let mut created = Vec::new();
+    for playlist in playlists {
+        created.push(manager.import_playlist(playlist.name, playlist.tracks).await?.summary());
+    }
+    if !library.is_empty() {
+        created.push(manager.import_playlist(library_name.to_string(), library.clone()).await?.summary());
+    }
+    let paths: Vec<String> = library.iter().filter_map(|track| track.file_path.clone()).collect();
+    migration::remember_imported_files(get_kv_store(), library_name, &paths)?;
+    let indexed = tauri::async_runtime::spawn_blocking(add_to_search_index)
+        .await
+        .map_err(|e| MilkError::Internal(format!("Indexing imported library failed: {}", e)))?;
+    Ok((created, indexed))
+}
+
+/// Rebuild the cached search index to take in newly remembered imported files; returns how many it gained
+///
+/// When no index has been built yet, the first build picks them up instead.
+fn add_to_search_index() -> usize {
+    let Some(index) = SEARCH_INDEX.lock().unwrap().clone() else {
+        return 0;
+    };
+    let tracks = index.entries().map(|(track, _)| track.clone()).collect();
+    let rebuilt = build_search_index(index.root(), tracks);
+    let count = rebuilt.len().saturating_sub(index.len());
+    if count > 0 {
+        *SEARCH_INDEX.lock().unwrap() = Some(Arc::new(rebuilt));
+        ALBUM_THUMBNAILS.clear();
+    }
+    count
+}
+
+/// Where playback of a long file was left, if it wasn't finished
+#[tauri::command]
+fn get_resume_position(file_path: String) -> Option<bookmarks::ResumePosition> {
+    bookmarks::get_resume_position(get_kv_store(), &file_path)
+}
+
+/// Forget a file's resume position, so it plays from the start next time
+#[tauri::command]
+fn clear_resume_position(file_path: String) -> Result<bool, CommandError> {
+    bookmarks::clear_resume_position(get_kv_store(), &file_path).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Bookmarks", &format!("Failed to clear resume position of {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// The last session, to resume at startup; tracks whose files are gone are left out
+#[tauri::command]
+fn restore_session() -> session::PlayerSession {
+    let restored = session::restore();
+    log_info("Session", &format!(
+        "Restoring session with {} queued tracks", restored.queue.len()
+    ));
+    restored
+}
+
+/// Associate milk with audio, playlist and skin files; all formats when `formats` is omitted
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn register_file_associations(formats: Option<Vec<String>>) -> Result<file_associations::AssociationReport, CommandError> {
+    match file_associations::register(&formats.unwrap_or_default()) {
+        Ok(report) => {
+            log_info("FileAssociation", &format!("Registered file associations: {}", report.registered.join(", ")));
+            Ok(report)
+        }
         Err(e) => {
             let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to remove track: {}", milk_err));
-            Err(milk_err.user_message())
+            log_error("FileAssociation", &format!("Failed to register file associations: {}", milk_err));
+            Err(milk_err.report())
         }
     }
 }
 
 #[tauri::command]
-async fn reorder_playlist_tracks(playlist_id: String, track_ids: Vec<String>) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Reordering tracks in playlist: {}", playlist_id));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.reorder_tracks(&playlist_id, track_ids).await {
-        Ok(playlist) => Ok(playlist),
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn unregister_file_associations() -> Result<(), CommandError> {
+    file_associations::unregister().map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("FileAssociation", &format!("Failed to remove file associations: {}", milk_err));
+        milk_err.report()
+    })?;
+    log_info("FileAssociation", "Removed file associations");
+    Ok(())
+}
+
+#[tauri::command]
+fn store_credential(key: String, value: String) -> Result<(), CommandError> {
+    log_info("Storage", &format!("Storing credential: {}", key));
+    let storage = PlatformSecureStorage::new();
+    match storage.store(&key, &value) {
+        Ok(()) => Ok(()),
         Err(e) => {
             let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to reorder tracks: {}", milk_err));
-            Err(milk_err.user_message())
+            log_error("Storage", &format!("Failed to store credential: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+fn retrieve_credential(key: String) -> Result<Option<String>, CommandError> {
+    let storage = PlatformSecureStorage::new();
+    match storage.retrieve(&key) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Storage", &format!("Failed to retrieve credential: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+fn delete_credential(key: String) -> Result<(), CommandError> {
+    log_info("Storage", &format!("Deleting credential: {}", key));
+    let storage = PlatformSecureStorage::new();
+    match storage.delete(&key) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Storage", &format!("Failed to delete credential: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Resolve the sort for a listing view, remembering an explicit choice
+fn resolve_view_sort(view: &str, sort: Option<SortOptions>) -> Option<SortOptions> {
+    let key = sorting::view_sort_key(view);
+    match sort {
+        Some(sort) => {
+            if let Err(e) = get_kv_store().set(&key, &sort) {
+                log_warn("Sorting", &format!("Failed to persist sort for {}: {}", view, e));
+            }
+            Some(sort)
+        }
+        None => get_kv_store().get(&key),
+    }
+}
+
+/// Helper function using MilkResult to scan library with performance tracking
+///
+/// Scans a library root with the configured filters; setting `cancel` stops the scan.
+fn scan_library_with_timing(path: &std::path::Path, cancel: &std::sync::atomic::AtomicBool) -> MilkResult<Vec<Track>> {
+    // Skip roots on a disconnected drive or share instead of hanging on them
+    library_roots::ensure_available(path)?;
+    let started = std::time::Instant::now();
+    let settings = FileConfigManager::load().map(|config| config.library_filters).unwrap_or_default();
+    let filter = library_filter::LibraryFilter::from_settings(&settings);
+    let result = LibraryScanner::scan_directory_cancellable(path, &filter, cancel).map_err(MilkError::from);
+    performance::record_scan_duration(started.elapsed());
+    result
+}
+
+/// Validate audio file format (constructs DecodeError and UnsupportedFormat variants)
+fn validate_audio_format(file_path: &std::path::Path) -> MilkResult<()> {
+    let extension = file_path.extension()
+        .and_then(|e| e.to_str())
+        .ok_or_else(|| MilkError::UnsupportedFormat("unknown".to_string()))?;
+
+    // Check if extension is supported
+    if !LibraryScanner::is_supported_extension(extension) {
+        return Err(MilkError::UnsupportedFormat(extension.to_string()));
+    }
+
+    // In a real implementation, we might verify the file header
+    // For now, we just check if the file exists and is readable
+    if !file_path.exists() {
+        return Err(MilkError::DecodeError("File does not exist".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Load and validate config (constructs InvalidConfig, MissingConfig variants)
+fn load_and_validate_config() -> MilkResult<Config> {
+    let config = match FileConfigManager::load() {
+        Ok(c) => c,
+        Err(_) => {
+            // If config doesn't exist, that's MissingConfig
+            return Err(MilkError::MissingConfig("configuration file".to_string()));
+        }
+    };
+
+    // Validate required fields
+    if config.library_path.is_none() {
+        return Err(MilkError::MissingConfig("library_path".to_string()));
+    }
+
+    // Validate library path exists
+    if let Some(ref path) = config.library_path {
+        if !std::path::Path::new(path).exists() {
+            return Err(MilkError::InvalidConfig(format!("library_path: {} does not exist", path)));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Generic error handler that can construct Internal error variant
+fn handle_unexpected_error<T>(result: Result<T, Box<dyn std::error::Error>>) -> MilkResult<T> {
+    result.map_err(|e| MilkError::Internal(format!("Unexpected error: {}", e)))
+}
+
+/// Scan `path` as a library job, which `cancel_job` can stop
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn scan_library(app: tauri::AppHandle, path: String, sort: Option<SortOptions>) -> Result<Vec<Track>, CommandError> {
+    log_info("Library", &format!("Scanning library: {}", path));
+    let library_path = path_codec::decode(&path);
+    let root = library_path.clone();
+    let scanned = jobs::job_manager()
+        .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "scan_library", jobs::app_sink(app), move |ctx| {
+            scan_library_with_timing(&root, ctx.cancel_flag())
+        })
+        .await;
+
+    match scanned {
+        Ok(mut tracks) => {
+            log_info("Library", &format!("Found {} tracks", tracks.len()));
+            let mut index = SEARCH_INDEX.lock().unwrap();
+            if index.as_ref().is_some_and(|index| index.root() == library_path) {
+                *index = None;
+                ALBUM_THUMBNAILS.clear();
+            }
+            drop(index);
+            if let Some(sort) = resolve_view_sort("library", sort) {
+                sorting::sort_library_tracks(&mut tracks, &sort, get_metadata_extractor());
+            }
+            Ok(tracks)
+        }
+        Err(e) => {
+            log_error_with_context("Library", &e, "Failed to scan library");
+            Err(e.report())
+        }
+    }
+}
+
+/// Sort library tracks by `key` with the collation rules of `locale`
+///
+/// Uses the system locale when `locale` is omitted.
+#[tauri::command]
+fn sort_tracks(mut tracks: Vec<Track>, key: sorting::SortField, locale: Option<String>) -> Vec<Track> {
+    let options = SortOptions { field: key, descending: false, natural: true, locale };
+    sorting::sort_library_tracks(&mut tracks, &options, get_metadata_extractor());
+    tracks
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn extract_metadata(file_path: String) -> Result<TrackMetadata, CommandError> {
+    let path = path_codec::decode(&file_path);
+    // Off the main thread: metadata plugins run within this call
+    let extracted = tauri::async_runtime::spawn_blocking(move || {
+        let mut metadata = get_metadata_extractor().extract(&path).map_err(MilkError::from)?;
+        plugins::fill_metadata(&path, &mut metadata);
+        if let Some(analysis) = get_analysis_store().and_then(|store| store.get(&path)) {
+            track_analysis::fill_metadata(&mut metadata, &analysis);
+        }
+        Ok(metadata)
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Metadata extraction failed: {}", e)))
+    .and_then(|result| result);
+    match extracted {
+        Ok(metadata) => Ok(metadata),
+        Err(milk_err) => {
+            log_warn("Metadata", &format!("Metadata extraction failed for {}: {}", file_path, milk_err));
+            // For metadata errors, we still want to return something (fallback will be applied)
+            // So we log as warning but still return the error
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Extract metadata for many files in parallel
+///
+/// Emits `metadata-batch-progress` after each chunk so the library view can
+/// fill in as results arrive; the full result list is returned at the end.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn extract_metadata_batch(app: tauri::AppHandle, paths: Vec<String>) -> Result<Vec<metadata::BatchItem>, CommandError> {
+    log_info("Metadata", &format!("Extracting metadata for {} files", paths.len()));
+    let paths: Vec<std::path::PathBuf> = paths.iter().map(|path| path_codec::decode(path)).collect();
+    let results = tauri::async_runtime::spawn_blocking(move || {
+        get_metadata_extractor().extract_batch(&paths, |progress| {
+            if let Err(e) = app.emit("metadata-batch-progress", &progress) {
+                log_warn("Metadata", &format!("Failed to emit metadata-batch-progress event: {}", e));
+            }
+        })
+    })
+    .await
+    .map_err(|e| {
+        let milk_err = MilkError::Internal(format!("Metadata batch failed: {}", e));
+        log_error("Metadata", &milk_err.to_string());
+        milk_err.report()
+    })?;
+    let failed = results.iter().filter(|item| item.error.is_some()).count();
+    if failed > 0 {
+        log_warn("Metadata", &format!("Metadata extraction failed for {} of {} files", failed, results.len()));
+    }
+    Ok(results)
+}
+
+/// Ranked search over title, artist, album, genre and path
+///
+/// Searches `path`, or the configured library when omitted. The index is built
+/// on first use and reused until the library is rescanned.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn search_library(
+    app: tauri::AppHandle,
+    query: String,
+    filters: Option<search::SearchFilters>,
+    path: Option<String>,
+    sort: Option<library_query::QuerySort>,
+) -> Result<Vec<search::SearchHit>, CommandError> {
+    let filters = filters.unwrap_or_default();
+    let index = library_index(&app, path).await?;
+    let Some(sort) = sort else {
+        return Ok(index.search(&query, &filters, |file_path| ratings::get_rating(get_kv_store(), file_path)));
+    };
+    let mut hits = index.matches(&query, &filters, |file_path| ratings::get_rating(get_kv_store(), file_path));
+    library_query::sort_hits(&mut hits, &sort, get_kv_store());
+    hits.truncate(filters.limit.unwrap_or(search::DEFAULT_LIMIT));
+    Ok(hits)
+}
+
+/// Search results grouped by album, artist or folder
+///
+/// Takes the same query, filters and sort as `search_library`; the sort
+/// orders tracks within artist and folder groups, album groups are always in
+/// disc/track order. The filter limit caps the number of tracks.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn group_library(
+    app: tauri::AppHandle,
+    query: String,
+    filters: Option<search::SearchFilters>,
+    sort: Option<library_query::QuerySort>,
+    group_by: library_query::GroupBy,
+    path: Option<String>,
+) -> Result<Vec<library_query::HitGroup>, CommandError> {
+    let filters = filters.unwrap_or_default();
+    let sort = sort.unwrap_or_default();
+    let index = library_index(&app, path).await?;
+    let mut hits = index.matches(&query, &filters, |file_path| ratings::get_rating(get_kv_store(), file_path));
+    library_query::sort_hits(&mut hits, &sort, get_kv_store());
+    hits.truncate(filters.limit.unwrap_or(search::DEFAULT_LIMIT));
+    Ok(library_query::group_hits(index.root(), hits, group_by, sort.locale.as_deref()))
+}
+
+/// Index for `path` or the configured library, built on first use as a library job
+async fn library_index(app: &tauri::AppHandle, path: Option<String>) -> Result<Arc<search::SearchIndex>, CommandError> {
+    let root = match path {
+        Some(path) => path_codec::decode(&path),
+        None => FileConfigManager::load()
+            .ok()
+            .and_then(|config| config.library_path)
+            .map(std::path::PathBuf::from)
+            .ok_or_else(|| MilkError::MissingConfig("library_path".to_string()).report())?,
+    };
+
+    let cached = SEARCH_INDEX.lock().unwrap().clone().filter(|index| index.root() == root);
+    if let Some(index) = cached {
+        return Ok(index);
+    }
+    let work = move |ctx: &jobs::JobContext| {
+        scan_library_with_timing(&root, ctx.cancel_flag()).map(|tracks| Arc::new(build_search_index(&root, tracks)))
+    };
+    let result = jobs::job_manager()
+        .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "library_index", jobs::app_sink(app.clone()), work)
+        .await;
+    let index = result.map_err(|e| {
+        log_error_with_context("Library", &e, "Failed to build library index");
+        e.report()
+    })?;
+    log_info("Library", &format!("Indexed {} tracks", index.len()));
+    *SEARCH_INDEX.lock().unwrap() = Some(index.clone());
+    Ok(index)
+}
+
+/// Artists in the library with album and track counts
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_artists(app: tauri::AppHandle, path: Option<String>) -> Result<Vec<browse::ArtistSummary>, CommandError> {
+    Ok(browse::artists(&*library_index(&app, path).await?))
+}
+
+/// Albums in the library, optionally for one artist, with artwork thumbnails
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_albums(app: tauri::AppHandle, artist: Option<String>, path: Option<String>) -> Result<Vec<browse::AlbumSummary>, CommandError> {
+    let index = library_index(&app, path).await?;
+    // Decoding and scaling artwork runs as a job, capped with the other artwork work
+    jobs::job_manager()
+        .run(jobs::resolve_job_id(None), jobs::JobKind::Artwork, "album_artwork", jobs::app_sink(app), move |_| {
+            Ok::<_, MilkError>(browse::albums(&index, artist.as_deref(), get_metadata_extractor(), &ALBUM_THUMBNAILS))
+        })
+        .await
+        .map_err(|e| MilkError::Internal(format!("Album listing failed: {}", e)).report())
+}
+
+/// Tracks of one album in disc/track order
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_album_tracks(app: tauri::AppHandle, album_id: String, path: Option<String>) -> Result<Vec<browse::AlbumTrack>, CommandError> {
+    let index = library_index(&app, path).await?;
+    browse::album_tracks(&index, &album_id).ok_or_else(|| {
+        let milk_err = MilkError::InvalidPath(format!("album {}", album_id));
+        log_warn("Library", &format!("Album not found: {}", album_id));
+        milk_err.report()
+    })
+}
+
+/// Min/max peaks for drawing a waveform seek bar, `resolution` buckets wide
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn generate_waveform(file_path: String, resolution: usize) -> Result<waveform::Waveform, CommandError> {
+    let path = path_codec::decode(&file_path);
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let cache = WAVEFORM_CACHE.get_or_init(|| waveform::default_cache_dir().map(waveform::WaveformCache::new));
+        waveform::generate_waveform(&path, resolution, cache.as_ref()).map_err(MilkError::from)
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Waveform generation failed: {}", e)))
+    .and_then(|result| result);
+
+    result.map_err(|milk_err| {
+        log_error("Waveform", &format!("Failed to generate waveform for {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Stored analysis of `path`, analysing and storing it first when missing or made before silence detection
+fn stored_analysis(path: &std::path::Path) -> Result<track_analysis::TrackAnalysis, track_analysis::AnalysisError> {
+    let store = get_analysis_store();
+    if let Some(analysis) = store.and_then(|store| store.get(path)).filter(|analysis| analysis.silence.is_some()) {
+        return Ok(analysis);
+    }
+    let analysis = track_analysis::analyze(path)?;
+    if let Some(store) = store {
+        store.put(path, &analysis)?;
+    }
+    Ok(analysis)
+}
+
+/// Chapter markers of an audiobook or chaptered MP3
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_chapters(file_path: String) -> Result<Vec<chapters::Chapter>, CommandError> {
+    let path = path_codec::decode(&file_path);
+    tauri::async_runtime::spawn_blocking(move || chapters::read_chapters(&path).map_err(MilkError::from))
+        .await
+        .map_err(|e| MilkError::Internal(format!("Reading chapters failed: {}", e)))
+        .and_then(|result| result)
+        .map_err(|milk_err| {
+            log_warn("Chapters", &format!("Failed to read chapters of {}: {}", file_path, milk_err));
+            milk_err.report()
+        })
+}
+
+/// Seek the player to the start of a chapter of `file_path`, or of the playing file
+///
+/// The player is moved through a `media-control` seek, like the OS media keys.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn seek_to_chapter(app: tauri::AppHandle, index: usize, file_path: Option<String>) -> Result<chapters::Chapter, CommandError> {
+    let file_path = file_path
+        .or_else(|| PLAYER_STATE.snapshot().now_playing.and_then(|track| track.file_path))
+        .or_else(|| session::current_track().and_then(|track| track.file_path))
+        .ok_or_else(|| MilkError::Other("No local track is playing".to_string()).report())?;
+    let chapters = get_chapters(file_path).await?;
+    let chapter = chapters::chapter_at(&chapters, index).map_err(|e| MilkError::from(e).report())?.clone();
+    let action = media_session::MediaKeyAction::SeekTo { position_secs: chapter.start_secs };
+    if let Err(e) = app.emit("media-control", &action) {
+        log_warn("Chapters", &format!("Failed to emit media-control event: {}", e));
+    }
+    Ok(chapter)
+}
+
+/// Tempo, key and silence of one track, analysed on first request and stored
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn analyze_track(file_path: String) -> Result<track_analysis::TrackAnalysis, CommandError> {
+    let path = path_codec::decode(&file_path);
+    let result = tauri::async_runtime::spawn_blocking(move || stored_analysis(&path).map_err(MilkError::from))
+        .await
+        .map_err(|e| MilkError::Internal(format!("Track analysis failed: {}", e)))
+        .and_then(|result| result);
+
+    result.map_err(|milk_err| {
+        log_error("Analysis", &format!("Failed to analyse {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Leading and trailing silence of one track, for the skip-silence playback option
+///
+/// Shares the stored analysis with `analyze_track`, so a track is decoded once.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn analyze_silence(file_path: String) -> Result<track_analysis::Silence, CommandError> {
+    let path = path_codec::decode(&file_path);
+    let result = tauri::async_runtime::spawn_blocking(move || stored_analysis(&path).map_err(MilkError::from))
+        .await
+        .map_err(|e| MilkError::Internal(format!("Silence analysis failed: {}", e)))
+        .and_then(|result| result.map(|analysis| analysis.silence.unwrap_or_default()));
+
+    result.map_err(|milk_err| {
+        log_error("Analysis", &format!("Failed to analyse silence in {}: {}", file_path, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Analyse tempo, key and silence of every track in the library as a background job
+///
+/// Tracks analysed before are skipped unless `force` is set. Returns the job id
+/// straight away; progress arrives as `job-progress` events, and results are
+/// merged into the search index when the job finishes.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn analyze_library(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    force: Option<bool>,
+    job_id: Option<String>,
+) -> Result<String, CommandError> {
+    let index = library_index(&app, path).await?;
+    let store = get_analysis_store()
+        .ok_or_else(|| MilkError::Internal("Analysis store unavailable".to_string()).report())?;
+    let force = force.unwrap_or(false);
+    let job_id = jobs::resolve_job_id(job_id);
+    let sink = jobs::app_sink(app);
+    let work = move |ctx: &jobs::JobContext| {
+        let paths: Vec<std::path::PathBuf> = index.entries().map(|(track, _)| track.file_path.clone()).collect();
+        let (mut analysed, mut failed) = (0, 0);
+        for (completed, path) in paths.iter().enumerate() {
+            ctx.checkpoint()?;
+            ctx.report_items(completed as u64, paths.len() as u64);
+            if !force && store.get(path).is_some_and(|analysis| analysis.silence.is_some()) {
+                continue;
+            }
+            match track_analysis::analyze(path).and_then(|analysis| store.put(path, &analysis)) {
+                Ok(()) => analysed += 1,
+                Err(e) => {
+                    failed += 1;
+                    log_warn("Analysis", &format!("Failed to analyse {}: {}", path.display(), e));
+                }
+            }
+        }
+        log_info("Analysis", &format!("Analysed {} tracks, {} failed", analysed, failed));
+
+        let mut current = SEARCH_INDEX.lock().unwrap();
+        if let Some(current) = current.as_mut().filter(|current| current.root() == index.root()) {
+            let mut updated = (**current).clone();
+            updated.apply_analysis(|path| store.get(path));
+            *current = Arc::new(updated);
+        }
+        Ok::<_, MilkError>(())
+    };
+    let id = job_id.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = jobs::job_manager().run(id, jobs::JobKind::Analysis, "analyze_library", sink, work).await {
+            log_warn("Analysis", &format!("Library analysis stopped: {}", e));
+        }
+    });
+    Ok(job_id)
+}
+
+#[tauri::command]
+fn get_track_rating(file_path: String) -> ratings::TrackRating {
+    ratings::get_rating(get_kv_store(), &file_path)
+}
+
+/// Rate a track; with `write_tags` the stars are also written into the file's tags
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_track_rating(file_path: String, rating: ratings::TrackRating, write_tags: Option<bool>) -> Result<(), CommandError> {
+    let to_user = |e: ratings::RatingError| {
+        let milk_err = MilkError::from(e);
+        log_error("Ratings", &format!("Failed to rate {}: {}", file_path, milk_err));
+        milk_err.report()
+    };
+    ratings::set_rating(get_kv_store(), &file_path, rating).map_err(to_user)?;
+    if write_tags.unwrap_or(false) {
+        let written = ratings::write_rating_tag(&path_codec::decode(&file_path), rating).map_err(to_user)?;
+        if !written {
+            log_info("Ratings", &format!("Format does not support rating tags: {}", file_path));
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn get_track_note(file_path: String) -> Option<String> {
+    track_notes::get_note(get_kv_store(), &file_path)
+}
+
+/// Annotate a track, e.g. with cue points; an empty note removes it
+///
+/// Returns the note as stored, trimmed.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_track_note(file_path: String, note: Option<String>) -> Result<Option<String>, CommandError> {
+    let note = track_notes::set_note(get_kv_store(), &file_path, note.as_deref()).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Notes", &format!("Failed to save note on {}: {}", file_path, milk_err));
+        milk_err.report()
+    })?;
+    if let Some(index) = SEARCH_INDEX.lock().unwrap().as_mut() {
+        Arc::make_mut(index).set_note(&path_codec::decode(&file_path), note.clone());
+    }
+    Ok(note)
+}
+
+/// Rebuild the search index for a library root whose volume came back or that gained new files
+fn rescan_library_root(app: tauri::AppHandle, root: std::path::PathBuf) {
+    let mut index = SEARCH_INDEX.lock().unwrap();
+    if index.as_ref().is_some_and(|index| index.root() == root) {
+        *index = None;
+        ALBUM_THUMBNAILS.clear();
+    }
+    drop(index);
+    let configured = FileConfigManager::load().ok().and_then(|config| config.library_path);
+    if configured.as_deref().map(std::path::Path::new) != Some(root.as_path()) {
+        return;
+    }
+    let work = move |ctx: &jobs::JobContext| match scan_library_with_timing(&root, ctx.cancel_flag()) {
+        _ if ctx.is_cancelled() => Err(jobs::CANCELLED.to_string()),
+        Ok(tracks) => {
+            let index = Arc::new(build_search_index(&root, tracks));
+            log_info("Library", &format!("Rescanned library: {} tracks", index.len()));
+            *SEARCH_INDEX.lock().unwrap() = Some(index);
+            Ok(())
+        }
+        Err(e) => {
+            log_error_with_context("Library", &e, "Failed to rescan library");
+            Err(e.to_string())
+        }
+    };
+    tauri::async_runtime::spawn(async move {
+        let job_id = jobs::resolve_job_id(None);
+        let _ = jobs::job_manager()
+            .run(job_id, jobs::JobKind::LibraryScan, "rescan_library", jobs::app_sink(app), work)
+            .await;
+    });
+}
+
+/// Library roots on network or removable volumes and whether each is reachable
+#[tauri::command]
+fn get_library_roots() -> Vec<library_roots::RootStatus> {
+    library_roots::statuses()
+}
+
+/// Library exclusion patterns from config
+#[tauri::command]
+fn list_library_exclusions() -> Result<Vec<String>, CommandError> {
+    let config = FileConfigManager::load().map_err(|e| MilkError::from(e).report())?;
+    Ok(config.library_filters.exclusions)
+}
+
+/// Exclude files and folders matching `pattern` from the library
+///
+/// Returns the updated pattern list. The search index is rebuilt on next use.
+#[tauri::command]
+fn add_library_exclusion(pattern: String) -> Result<Vec<String>, CommandError> {
+    let pattern = library_filter::normalize_pattern(&pattern)
+        .ok_or_else(|| MilkError::InvalidConfig("Exclusion pattern cannot be empty".to_string()).report())?;
+    update_library_exclusions(|exclusions| {
+        if !exclusions.contains(&pattern) {
+            exclusions.push(pattern.clone());
+        }
+    })
+}
+
+#[tauri::command]
+fn remove_library_exclusion(pattern: String) -> Result<Vec<String>, CommandError> {
+    let pattern = library_filter::normalize_pattern(&pattern).unwrap_or(pattern);
+    update_library_exclusions(|exclusions| exclusions.retain(|p| *p != pattern))
+}
+
+fn update_library_exclusions(change: impl FnOnce(&mut Vec<String>)) -> Result<Vec<String>, CommandError> {
+    let saved = FileConfigManager
+        .update_with(|current| {
+            let mut exclusions = current.library_filters.exclusions.clone();
+            change(&mut exclusions);
+            config::set_setting(current, "library_filters.exclusions", serde_json::json!(exclusions))
+        })
+        .map_err(|e| {
+            let milk_err = MilkError::from(e);
+            log_error("Library", &format!("Failed to update library exclusions: {}", milk_err));
+            milk_err.report()
+        })?;
+    *SEARCH_INDEX.lock().unwrap() = None;
+    ALBUM_THUMBNAILS.clear();
+    Ok(saved.library_filters.exclusions)
+}
+
+/// Rename files after their tags, e.g. `{artist}/{album}/{track:02} - {title}`
+///
+/// Targets are laid out under the configured library root. Unless `dry_run`
+/// is false only the plan is returned; applied renames carry ratings, resume
+/// positions and bookmarks along and update the library index.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn rename_files_from_tags(
+    paths: Vec<String>,
+    pattern: String,
+    dry_run: Option<bool>,
+) -> Result<tag_rename::RenameReport, CommandError> {
+    let dry_run = dry_run.unwrap_or(true);
+    let pattern = tag_pattern::TagPattern::parse(&pattern).map_err(|e| MilkError::from(e).report())?;
+    let root = FileConfigManager::load()
+        .ok()
+        .and_then(|config| config.library_path)
+        .map(|path| path_codec::decode(&path))
+        .ok_or_else(|| MilkError::MissingConfig("library_path".to_string()).report())?;
+    let paths: Vec<std::path::PathBuf> = paths.iter().map(|path| path_codec::decode(path)).collect();
+
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let plan = tag_rename::plan_renames(&paths, &pattern, &root, get_metadata_extractor());
+        if dry_run {
+            return tag_rename::preview(plan);
+        }
+        let report = tag_rename::apply_renames(plan, &root);
+        let moved: Vec<(&std::path::Path, &std::path::Path)> = report
+            .entries
+            .iter()
+            .filter(|e| e.status == tag_rename::RenameStatus::Renamed)
+            .filter_map(|e| e.target.as_deref().map(|target| (e.source.as_path(), target)))
+            .collect();
+        for (old, new) in &moved {
+            if let Err(e) = tag_rename::move_track_data(get_kv_store(), old, new) {
+                log_warn("Library", &format!("Failed to move track data to {}: {}", new.display(), e));
+            }
+        }
+        if !moved.is_empty() {
+            reindex_changed_files(&moved.into_iter().collect());
+        }
+        report
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Renaming failed: {}", e)).report())?;
+
+    let moves: std::collections::HashMap<String, String> = report
+        .entries
+        .iter()
+        .filter(|e| !dry_run && e.status == tag_rename::RenameStatus::Renamed)
+        .filter_map(|e| e.target.as_deref().map(|target| (path_codec::encode(&e.source), path_codec::encode(target))))
+        .collect();
+    if !moves.is_empty() {
+        match get_playlist_manager().await.rename_file_paths(&moves).await {
+            Ok(changed) if !changed.is_empty() => log_info("Playlist", &format!("Updated renamed files in {} playlists", changed.len())),
+            Ok(_) => {}
+            Err(e) => log_warn("Playlist", &format!("Failed to update playlists for renamed files: {}", e)),
+        }
+    }
+
+    log_info("Library", &format!(
+        "{} files from tags: {} renamed, {} skipped",
+        if dry_run { "Planned renaming" } else { "Renamed" }, report.renamed, report.skipped
+    ));
+    Ok(report)
+}
+
+/// Preview tags read from file names, e.g. with `{artist} - {title}`
+///
+/// Without a pattern the "Artist - Title" and album folder fallback is used.
+/// Each suggestion only holds tags the file is missing.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn infer_tags_from_filenames(
+    paths: Vec<String>,
+    pattern: Option<String>,
+) -> Result<Vec<tag_infer::TagSuggestion>, CommandError> {
+    let pattern = pattern
+        .filter(|pattern| !pattern.trim().is_empty())
+        .map(|pattern| tag_pattern::TagPattern::parse(&pattern))
+        .transpose()
+        .map_err(|e| MilkError::from(e).report())?;
+    let paths: Vec<std::path::PathBuf> = paths.iter().map(|path| path_codec::decode(path)).collect();
+    let suggestions = tauri::async_runtime::spawn_blocking(move || {
+        tag_infer::infer_tags(&paths, pattern.as_ref(), get_metadata_extractor())
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Tag inference failed: {}", e)).report())?;
+    let fillable = suggestions.iter().filter(|s| !s.inferred.is_empty()).count();
+    log_info("Metadata", &format!("Inferred tags for {} of {} files", fillable, suggestions.len()));
+    Ok(suggestions)
+}
+
+/// Write previewed tag suggestions, possibly trimmed by the user
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn apply_inferred_tags(suggestions: Vec<tag_infer::TagSuggestion>) -> Result<tag_infer::TagApplyReport, CommandError> {
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        let report = tag_infer::apply_suggestions(&suggestions, get_metadata_extractor());
+        if report.updated > 0 {
+            reindex_changed_files(&Default::default());
+        }
+        report
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Writing tags failed: {}", e)).report())?;
+    for failure in &report.failed {
+        log_warn("Metadata", &format!("Failed to write tags to {}: {}", failure.path.display(), failure.error));
+    }
+    log_info("Metadata", &format!(
+        "Wrote inferred tags to {} files ({} unchanged, {} failed)",
+        report.updated, report.unchanged, report.failed.len()
+    ));
+    Ok(report)
+}
+
+/// Find duplicate tracks under `path`, or the configured library when omitted
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn find_library_duplicates(app: tauri::AppHandle, path: Option<String>) -> Result<duplicates::DuplicateReport, CommandError> {
+    let path = match path {
+        Some(path) => path,
+        None => FileConfigManager::load()
+            .ok()
+            .and_then(|config| config.library_path)
+            .ok_or_else(|| MilkError::MissingConfig("library_path".to_string()).report())?,
+    };
+    log_info("Library", &format!("Searching for duplicates in {}", path));
+    let work = move |ctx: &jobs::JobContext| {
+        let tracks = scan_library_with_timing(&path_codec::decode(&path), ctx.cancel_flag())?;
+        ctx.checkpoint()?;
+        Ok::<_, MilkError>(duplicates::find_duplicates(&tracks, get_metadata_extractor()))
+    };
+    let result = jobs::job_manager()
+        .run(jobs::resolve_job_id(None), jobs::JobKind::LibraryScan, "find_duplicates", jobs::app_sink(app), work)
+        .await;
+
+    match result {
+        Ok(report) => {
+            log_info("Library", &format!(
+                "Found {} duplicate groups in {} files ({} bytes reclaimable)",
+                report.groups.len(), report.files_scanned, report.reclaimable_bytes
+            ));
+            Ok(report)
+        }
+        Err(e) => {
+            log_error_with_context("Library", &e, "Failed to search for duplicates");
+            Err(e.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn extract_artwork(file_path: String) -> Result<Option<Vec<u8>>, CommandError> {
+    let path = &path_codec::decode(&file_path);
+    let extractor = get_metadata_extractor();
+    extractor.extract_artwork(path).map_err(|e| MilkError::from(e).report())
+}
+
+/// Embed new cover art in a file's tags, replacing the current front cover
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn set_track_artwork(file_path: String, image_bytes: Vec<u8>, mime: String) -> Result<artwork::EmbeddedArtwork, CommandError> {
+    match artwork::set_artwork(&path_codec::decode(&file_path), &image_bytes, &mime) {
+        Ok(embedded) => {
+            if embedded.converted {
+                log_info("Artwork", &format!(
+                    "Converted artwork to {}x{} {} for {}",
+                    embedded.width, embedded.height, embedded.mime, file_path
+                ));
+            }
+            ALBUM_THUMBNAILS.clear();
+            Ok(embedded)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Artwork", &format!("Failed to set artwork for {}: {}", file_path, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Expand a dropped folder into ordered queue entries
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn enqueue_folder(path: String, mode: queue_import::EnqueueMode) -> Result<queue_import::EnqueueSummary, CommandError> {
+    log_info("Queue", &format!("Expanding folder for queue: {} ({:?})", path, mode));
+    match queue_import::expand_folder(&path_codec::decode(&path), mode, get_metadata_extractor()) {
+        Ok(summary) => {
+            log_info("Queue", &format!(
+                "Enqueued {} tracks from {} folders ({} skipped)",
+                summary.tracks.len(), summary.folders_scanned, summary.skipped_files
+            ));
+            Ok(summary)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Queue", &format!("Failed to expand folder: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn create_playlist(name: String) -> Result<Playlist, CommandError> {
+    log_info("Playlist", &format!("Creating playlist: {}", name));
+    let manager = get_playlist_manager().await;
+    match manager.create_playlist(name).await {
+        Ok(playlist) => {
+            log_info("Playlist", &format!("Playlist created: {}", playlist.id));
+            Ok(playlist)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to create playlist: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Create a playlist from the audio files of a folder, named after the folder
+///
+/// Tracks are ordered like a dropped folder: by subfolder, disc and track
+/// number, then natural filename order.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn create_playlist_from_folder(path: String, recursive: bool) -> Result<Playlist, CommandError> {
+    log_info("Playlist", &format!("Creating playlist from folder: {}", path));
+    let folder = path_codec::decode(&path);
+    let mode = if recursive {
+        queue_import::EnqueueMode::Recursive
+    } else {
+        queue_import::EnqueueMode::TopLevel
+    };
+    let name = folder
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.clone());
+
+    let result = async {
+        let summary = tauri::async_runtime::spawn_blocking(move || {
+            queue_import::expand_folder(&folder, mode, get_metadata_extractor()).map_err(MilkError::from)
+        })
+        .await
+        .map_err(|e| MilkError::Internal(format!("Folder scan failed: {}", e)))??;
+        let manager = get_playlist_manager().await;
+        manager
+            .create_playlist_with_tracks(name, summary.tracks)
+            .await
+            .map_err(MilkError::from)
+    }
+    .await;
+
+    match result {
+        Ok(playlist) => {
+            log_info("Playlist", &format!(
+                "Playlist created: {} ({} tracks)", playlist.id, playlist.tracks.len()
+            ));
+            Ok(playlist)
+        }
+        Err(milk_err) => {
+            log_error("Playlist", &format!("Failed to create playlist from {}: {}", path, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// System playlists generated from the library index and play history
+async fn get_system_playlists() -> Vec<Playlist> {
+    let index = SEARCH_INDEX.lock().unwrap().clone();
+    let plays = play_history::all_play_stats(get_kv_store());
+    tauri::async_runtime::spawn_blocking(move || {
+        system_playlists::generate(index.as_ref(), &plays, get_kv_store(), get_metadata_extractor())
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Load a saved playlist, or generate a system one
+async fn load_any_playlist(playlist_id: &str) -> Result<Playlist, playlist::PlaylistError> {
+    if system_playlists::is_system_playlist(playlist_id) {
+        return get_system_playlists()
+            .await
+            .into_iter()
+            .find(|p| p.id == playlist_id)
+            .ok_or_else(|| playlist::PlaylistError::NotFound(playlist_id.to_string()));
+    }
+    get_playlist_manager().await.load_playlist(playlist_id).await
+}
+
+/// List saved playlists, sorted as requested, after the system playlists
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn list_playlists(sort: Option<SortOptions>) -> Result<Vec<Playlist>, CommandError> {
+    let manager = get_playlist_manager().await;
+    match manager.list_playlists().await {
+        Ok(mut playlists) => {
+            if let Some(sort) = resolve_view_sort("playlists", sort) {
+                sorting::sort_playlists(&mut playlists, &sort);
+            }
+            let mut all = get_system_playlists().await;
+            all.append(&mut playlists);
+            Ok(all)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to list playlists: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn load_playlist(playlist_id: String, sort: Option<SortOptions>) -> Result<Playlist, CommandError> {
+    log_info("Playlist", &format!("Loading playlist: {}", playlist_id));
+    match load_any_playlist(&playlist_id).await {
+        Ok(mut playlist) => {
+            // Sorting only changes the returned view; the stored order is kept
+            if let Some(sort) = resolve_view_sort(&format!("playlist.{}", playlist_id), sort) {
+                sorting::sort_playlist_tracks(&mut playlist.tracks, &sort);
+            }
+            scripting::dispatch(scripting::ScriptEvent::PlaylistLoaded(scripting::PlaylistLoaded {
+                id: playlist.id.clone(),
+                name: playlist.name.clone(),
+                track_count: playlist.tracks.len(),
+            }));
+            Ok(playlist)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to load playlist: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// One page of a playlist's tracks, so huge playlists never cross IPC in one piece
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_playlist_page(
+    playlist_id: String,
+    offset: usize,
+    limit: Option<usize>,
+    sort: Option<SortOptions>,
+) -> Result<PlaylistPage, CommandError> {
+    match load_any_playlist(&playlist_id).await {
+        Ok(mut playlist) => {
+            if let Some(sort) = resolve_view_sort(&format!("playlist.{}", playlist_id), sort) {
+                sorting::sort_playlist_tracks(&mut playlist.tracks, &sort);
+            }
+            Ok(playlist.page(offset, limit.unwrap_or(playlist::DEFAULT_PAGE_SIZE)))
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to load playlist page: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Name, track count and total duration of a playlist, without its tracks
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn get_playlist_summary(playlist_id: String) -> Result<PlaylistSummary, CommandError> {
+    match load_any_playlist(&playlist_id).await {
+        Ok(playlist) => Ok(playlist.summary()),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to load playlist summary: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn delete_playlist(playlist_id: String) -> Result<(), CommandError> {
+    log_info("Playlist", &format!("Deleting playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.delete_playlist(&playlist_id).await {
+        Ok(()) => {
+            log_info("Playlist", "Playlist deleted successfully");
+            Ok(())
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to delete playlist: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn add_track_to_playlist(
+    playlist_id: String,
+    track: PlaylistTrack,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Adding track to playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.add_track(&playlist_id, track.clone(), expected_version).await {
+        Ok(playlist) => {
+            let index = playlist.tracks.len() - 1;
+            Ok(playlist.delta(PlaylistChange::TracksAdded { index, tracks: vec![track] }))
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to add track: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Append many tracks in one load/save cycle and one version bump
+///
+/// Emits a single `playlist-changed` event with the delta, so other windows
+/// showing the playlist update once rather than per track.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn add_tracks_to_playlist(
+    app: tauri::AppHandle,
+    playlist_id: String,
+    tracks: Vec<PlaylistTrack>,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Adding {} tracks to playlist: {}", tracks.len(), playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.add_tracks(&playlist_id, tracks.clone(), expected_version).await {
+        Ok(playlist) => {
+            let index = playlist.tracks.len() - tracks.len();
+            let delta = playlist.delta(PlaylistChange::TracksAdded { index, tracks });
+            if let Err(e) = app.emit("playlist-changed", &delta) {
+                log_warn("Playlist", &format!("Failed to emit playlist-changed event: {}", e));
+            }
+            Ok(delta)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to add tracks: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn remove_track_from_playlist(
+    playlist_id: String,
+    track_id: String,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Removing track from playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.remove_track(&playlist_id, &track_id, expected_version).await {
+        Ok(playlist) => Ok(playlist.delta(PlaylistChange::TracksRemoved { track_ids: vec![track_id] })),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to remove track: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn reorder_playlist_tracks(
+    playlist_id: String,
+    track_ids: Vec<String>,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Reordering tracks in playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.reorder_tracks(&playlist_id, track_ids, expected_version).await {
+        Ok(playlist) => {
+            let track_ids = playlist.tracks.iter().map(|t| t.id.clone()).collect();
+            Ok(playlist.delta(PlaylistChange::TracksReordered { track_ids }))
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to reorder tracks: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn update_playlist(
+    playlist_id: String,
+    name: Option<String>,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Updating playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.update_playlist(&playlist_id, name, expected_version).await {
+        Ok(playlist) => Ok(playlist.delta(PlaylistChange::Renamed { name: playlist.name.clone() })),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to update playlist: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Set or clear a playlist's description; an empty description clears it
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn set_playlist_description(
+    playlist_id: String,
+    description: Option<String>,
+    expected_version: Option<i64>,
+) -> Result<PlaylistDelta, CommandError> {
+    log_info("Playlist", &format!("Setting description of playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.set_description(&playlist_id, description, expected_version).await {
+        Ok(playlist) => Ok(playlist.delta(PlaylistChange::DescriptionChanged { description: playlist.description.clone() })),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to set playlist description: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Undo the latest add, remove, reorder, rename or description change of a playlist this session
+///
+/// Returns `None` when there is nothing left to undo.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn undo_playlist_change(playlist_id: String) -> Result<Option<PlaylistDelta>, CommandError> {
+    log_info("Playlist", &format!("Undoing change to playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.undo(&playlist_id).await {
+        Ok(delta) => Ok(delta),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to undo playlist change: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Re-apply the latest undone playlist change; `None` when there is nothing to redo
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn redo_playlist_change(playlist_id: String) -> Result<Option<PlaylistDelta>, CommandError> {
+    log_info("Playlist", &format!("Redoing change to playlist: {}", playlist_id));
+    let manager = get_playlist_manager().await;
+    match manager.redo(&playlist_id).await {
+        Ok(delta) => Ok(delta),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to redo playlist change: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Report which local tracks of a playlist point at files that no longer exist
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn verify_playlist_files(playlist_id: String) -> Result<playlist::PlaylistFileReport, CommandError> {
+    let manager = get_playlist_manager().await;
+    match manager.verify_files(&playlist_id).await {
+        Ok(report) => {
+            if !report.missing.is_empty() {
+                log_warn("Playlist", &format!(
+                    "Playlist {} has {} missing files", playlist_id, report.missing.len()
+                ));
+            }
+            Ok(report)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to verify playlist files: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Re-match a playlist's missing tracks against files under `new_root`
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn relocate_missing_tracks(playlist_id: String, new_root: String) -> Result<playlist::RelocationReport, CommandError> {
+    log_info("Playlist", &format!("Relocating missing tracks of {} under {}", playlist_id, new_root));
+    let manager = get_playlist_manager().await;
+    match manager
+        .relocate_missing_tracks(&playlist_id, &path_codec::decode(&new_root), get_metadata_extractor())
+        .await
+    {
+        Ok(report) => {
+            log_info("Playlist", &format!(
+                "Relocated {} tracks ({} unresolved)", report.relocated.len(), report.unresolved.len()
+            ));
+            Ok(report)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Playlist", &format!("Failed to relocate tracks: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn load_skin(skin_path: String) -> Result<ParsedSkin, CommandError> {
+    use std::path::Path;
+    log_info("Skin", &format!("Loading skin: {}", skin_path));
+    let path = Path::new(&skin_path);
+    
+    // Try to parse as .wsz or .wal
+    let result = if is_skin_file(&skin_path) {
+        get_skin_cache().get_or_parse(path)
+    } else {
+        let err = MilkError::InvalidSkinFormat(skin_path.clone());
+        log_error("Skin", &format!("{}", err));
+        return Err(err.report());
+    };
+
+    match result {
+        Ok(skin) => {
+            // Validate the skin
+            match SkinParser::validate_skin(&skin) {
+                Ok(_) => {
+                    log_info("Skin", "Skin loaded and validated successfully");
+                    Ok(Arc::unwrap_or_clone(skin))
+                }
+                Err(e) => {
+                    let milk_err = MilkError::from(e);
+                    log_warn("Skin", &format!("Skin validation failed, using default: {}", milk_err));
+                    Ok(SkinParser::get_default_skin())
+                }
+            }
+        }
+        Err(e) => {
+            // Return default skin on error (graceful degradation)
+            let milk_err = MilkError::from(e);
+            log_warn("Skin", &format!("Failed to load skin, using default: {}", milk_err));
+            Ok(SkinParser::get_default_skin())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn apply_skin(skin_path: String) -> Result<ParsedSkin, CommandError> {
+    use std::path::Path;
+    log_info("Skin", &format!("Applying skin: {}", skin_path));
+    let path = Path::new(&skin_path);
+    
+    // Load and validate the skin
+    let skin = if is_skin_file(&skin_path) {
+        get_skin_cache().get_or_parse(path)
+    } else {
+        let err = MilkError::InvalidSkinFormat(skin_path.clone());
+        log_error("Skin", &format!("{}", err));
+        return Err(err.report());
+    };
+
+    match skin {
+        Ok(skin) => {
+            // Validate the skin
+            match SkinParser::validate_skin(&skin) {
+                Ok(_) => {
+                    // Save the skin path to config
+                    if let Err(e) = FileConfigManager.update_setting("last_skin", serde_json::json!(skin_path)) {
+                        log_warn("Skin", &format!("Failed to save skin preference: {}", e));
+                    }
+                    log_info("Skin", "Skin applied successfully");
+                    theme::set_skin_colors(&skin);
+                    Ok(Arc::unwrap_or_clone(skin))
+                }
+                Err(e) => {
+                    let milk_err = MilkError::from(e);
+                    log_warn("Skin", &format!("Skin validation failed, using default: {}", milk_err));
+                    let skin = SkinParser::get_default_skin();
+                    theme::set_skin_colors(&skin);
+                    Ok(skin)
+                }
+            }
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_warn("Skin", &format!("Failed to apply skin, using default: {}", milk_err));
+            let skin = SkinParser::get_default_skin();
+            theme::set_skin_colors(&skin);
+            Ok(skin)
+        }
+    }
+}
+
+/// Resolve the active theme colors
+///
+/// Merges the applied skin's colors, the artwork palette of `track_path` (if
+/// given), and the user's overrides from the config.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn get_active_theme_colors(track_path: Option<String>) -> Result<theme::ActiveTheme, CommandError> {
+    let config = FileConfigManager::load().unwrap_or_else(|_| FileConfigManager::get_default());
+
+    let artwork_palette = match track_path {
+        Some(path) => match get_metadata_extractor().extract_artwork(&path_codec::decode(&path)) {
+            Ok(Some(artwork)) => theme::extract_palette(&artwork, theme::ARTWORK_PALETTE_SIZE),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                log_warn("Theme", &format!("Failed to read artwork for palette: {}", e));
+                Vec::new()
+            }
+        },
+        None => Vec::new(),
+    };
+
+    Ok(theme::resolve_theme(&config.theme_overrides, artwork_palette))
+}
+
+#[tauri::command]
+async fn spotify_authenticate(credentials: Credentials, auth_code: String) -> Result<Token, CommandError> {
+    log_info("Spotify", "Authenticating with Spotify");
+    let bridge = get_spotify_bridge();
+    match bridge.authenticate(credentials, auth_code).await {
+        Ok(token) => {
+            log_info("Spotify", "Authentication successful");
+            Ok(token)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Spotify", &format!("Authentication failed: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn spotify_get_now_playing(app: tauri::AppHandle) -> Result<Option<SpotifyTrackMetadata>, CommandError> {
+    let bridge = get_spotify_bridge();
+    match bridge.get_now_playing().await {
+        Ok(metadata) => {
+            report_streaming(&app, now_playing::NowPlayingSource::Spotify, metadata.as_ref());
+            Ok(metadata)
+        }
+        Err(e) => {
+            // Check error type before converting
+            let is_no_playback = matches!(e, spotify::ApiError::NoActivePlayback);
+            if is_no_playback {
+                report_streaming(&app, now_playing::NowPlayingSource::Spotify, None);
+            }
+            let milk_err = MilkError::from(e);
+            
+            // Only log as warning for "no active playback" which is not really an error
+            if is_no_playback {
+                log_info("Spotify", "No active playback");
+            } else {
+                log_warn("Spotify", &format!("Failed to get now playing: {}", milk_err));
+            }
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+async fn spotify_refresh_token(credentials: Credentials) -> Result<Token, CommandError> {
+    log_info("Spotify", "Refreshing Spotify token");
+    let bridge = get_spotify_bridge();
+    match bridge.refresh_token(credentials).await {
+        Ok(token) => {
+            log_info("Spotify", "Token refreshed successfully");
+            Ok(token)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Spotify", &format!("Token refresh failed: {}", milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+fn spotify_check_token_expired() -> Result<bool, CommandError> {
+    let bridge = get_spotify_bridge();
+    bridge.check_token_expired().map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+async fn spotify_ensure_valid_token(credentials: Option<Credentials>) -> Result<String, CommandError> {
+    let bridge = get_spotify_bridge();
+    bridge.ensure_valid_token(credentials).await.map_err(|e| MilkError::from(e).report())
+}
+
+/// Tempo, energy, danceability and other audio features of a Spotify track
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn spotify_get_audio_features(track_id: String) -> Result<spotify::AudioFeatures, CommandError> {
+    get_spotify_bridge().get_audio_features(&track_id).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Spotify", &format!("Failed to get audio features for {}: {}", track_id, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Spotify tracks similar to `seed_tracks`, for smart playlists and auto-DJ
+///
+/// `params` adds artist/genre seeds, a limit and min/max/target values for
+/// tempo, energy, danceability and the other tunable attributes.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn spotify_get_recommendations(
+    seed_tracks: Vec<String>,
+    params: Option<spotify::RecommendationParams>,
+) -> Result<Vec<spotify::RecommendedTrack>, CommandError> {
+    let params = params.unwrap_or_default();
+    get_spotify_bridge().get_recommendations(&seed_tracks, &params).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Spotify", &format!("Failed to get recommendations: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Match a playlist's tracks on Spotify and write them to a Spotify playlist
+///
+/// The first export creates a private playlist; later exports of the same
+/// playlist replace its tracks. Tracks without a confident match are reported.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn export_playlist_to_spotify(playlist_id: String) -> Result<spotify_export::SpotifyExportReport, CommandError> {
+    let playlist = get_playlist_manager().await.load_playlist(&playlist_id).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Spotify", &format!("Failed to load playlist {} for export: {}", playlist_id, milk_err));
+        milk_err.report()
+    })?;
+    match spotify_export::export_playlist(get_spotify_bridge(), get_kv_store(), &playlist).await {
+        Ok(report) => {
+            log_info("Spotify", &format!(
+                "Exported playlist {} to {} ({} matched, {} unmatched)",
+                playlist.name, report.spotify_playlist_id, report.matched.len(), report.unmatched.len()
+            ));
+            Ok(report)
+        }
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            log_error("Spotify", &format!("Failed to export playlist {}: {}", playlist.name, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+/// Find the same recording on another service or in the local library
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn match_track(
+    metadata: matching::MatchQuery,
+    target_service: matching::TargetService,
+) -> Result<Option<matching::TrackMatch>, CommandError> {
+    let library = SEARCH_INDEX.lock().unwrap().clone();
+    let sources = matching::MatchSources {
+        spotify: get_spotify_bridge(),
+        youtube: get_youtube_bridge(),
+        library: library.as_deref(),
+    };
+    matching::match_track(&metadata, target_service, &sources).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Matching", &format!("Failed to match {} - {}: {}", metadata.artist, metadata.title, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Sign in to Bandcamp with the `identity` cookie of a signed-in browser session
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn bandcamp_sign_in(identity: String) -> Result<bandcamp::BandcampFan, CommandError> {
+    let fan = get_bandcamp().sign_in(&identity).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Bandcamp", &format!("Sign-in failed: {}", milk_err));
+        milk_err.report()
+    })?;
+    log_info("Bandcamp", &format!("Signed in as {}", fan.username));
+    Ok(fan)
+}
+
+#[tauri::command]
+fn bandcamp_sign_out() -> Result<(), CommandError> {
+    get_bandcamp().sign_out().map_err(|e| MilkError::from(e).report())
+}
+
+/// A page of the Bandcamp collection, newest purchases first
+///
+/// Pass the returned `next_token` to get the following page.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn bandcamp_list_purchases(token: Option<String>, limit: Option<u32>) -> Result<bandcamp::PurchasePage, CommandError> {
+    get_bandcamp()
+        .list_purchases(token, limit.unwrap_or(bandcamp::MAX_PAGE_SIZE))
+        .await
+        .map_err(|e| {
+            let milk_err = MilkError::from(e);
+            log_error("Bandcamp", &format!("Failed to list purchases: {}", milk_err));
+            milk_err.report()
+        })
+}
+
+/// Tracks of a purchased album or track, with stream URLs for the player
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn bandcamp_stream_tracks(url: String) -> Result<Vec<bandcamp::BandcampTrack>, CommandError> {
+    get_bandcamp().stream_tracks(&url).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Bandcamp", &format!("Failed to load tracks from {}: {}", url, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Download a purchase into the library folder and rescan the library
+///
+/// Runs as a job reporting bytes received. While offline the download is
+/// queued until the connection is back and the offline error is returned.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn bandcamp_download(
+    app: tauri::AppHandle,
+    purchase: bandcamp::Purchase,
+    format: bandcamp::DownloadFormat,
+) -> Result<bandcamp::DownloadedPurchase, CommandError> {
+    if !connectivity::is_online() {
+        let key = format!("bandcamp-download:{}", purchase.id);
+        connectivity::run_when_online(key, move || async move {
+            let _ = download_bandcamp_purchase(app, purchase, format).await;
+        });
+        return Err(MilkError::Offline.report());
+    }
+    download_bandcamp_purchase(app, purchase, format).await
+}
+
+async fn download_bandcamp_purchase(
+    app: tauri::AppHandle,
+    purchase: bandcamp::Purchase,
+    format: bandcamp::DownloadFormat,
+) -> Result<bandcamp::DownloadedPurchase, CommandError> {
+    log_info("Bandcamp", &format!("Downloading {} - {} as {:?}", purchase.artist, purchase.title, format));
+    let result = async {
+        let library = FileConfigManager::load()?
+            .library_path
+            .map(std::path::PathBuf::from)
+            .ok_or_else(|| MilkError::MissingConfig("library_path".to_string()))?;
+        let job_id = format!("bandcamp-download:{}", purchase.id);
+        let downloaded = jobs::job_manager()
+            .run_async(job_id, jobs::JobKind::Download, "bandcamp_download", jobs::app_sink(app.clone()), |ctx| {
+                let (purchase, library) = (&purchase, &library);
+                async move {
+                    get_bandcamp()
+                        .download(purchase, format, library, |received, total| ctx.report_bytes(received, total))
+                        .await
+                        .map_err(MilkError::from)
+                }
+            })
+            .await?;
+        rescan_library_root(app, library);
+        Ok::<_, MilkError>(downloaded)
+    }
+    .await;
+
+    match result {
+        Ok(downloaded) => {
+            log_info("Bandcamp", &format!("Saved {} files to {}", downloaded.files.len(), downloaded.folder.display()));
+            Ok(downloaded)
+        }
+        Err(milk_err) => {
+            log_error("Bandcamp", &format!("Failed to download {}: {}", purchase.title, milk_err));
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+async fn youtube_authenticate(credentials: Credentials, auth_code: String) -> Result<Token, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.authenticate(credentials, auth_code).await.map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn youtube_get_now_playing(app: tauri::AppHandle) -> Result<Option<SpotifyTrackMetadata>, CommandError> {
+    let bridge = get_youtube_bridge();
+    let metadata = bridge.get_now_playing().await.map_err(|e| MilkError::from(e).report())?;
+    report_streaming(&app, now_playing::NowPlayingSource::Youtube, metadata.as_ref());
+    Ok(metadata)
+}
+
+/// Streaming now-playing data carries its own play state; no track means stopped
+fn report_streaming(app: &tauri::AppHandle, source: now_playing::NowPlayingSource, track: Option<&SpotifyTrackMetadata>) {
+    match track {
+        Some(track) => {
+            let status = if track.is_playing { PlaybackStatus::Playing } else { PlaybackStatus::Paused };
+            let position_secs = track.progress_ms.map(|ms| ms as f64 / 1000.0);
+            report_now_playing(Some(app), source, Some(NowPlaying::from(track)), status, position_secs);
+        }
+        None => report_now_playing(Some(app), source, None, PlaybackStatus::Stopped, None),
+    }
+}
+
+#[tauri::command]
+async fn youtube_refresh_token(credentials: Credentials) -> Result<Token, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.refresh_token(credentials).await.map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+fn youtube_check_token_expired() -> Result<bool, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.check_token_expired().map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+async fn youtube_ensure_valid_token(credentials: Option<Credentials>) -> Result<String, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.ensure_valid_token(credentials).await.map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+fn youtube_store_api_key(api_key: String) -> Result<(), CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.store_api_key(&api_key).map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+fn youtube_get_api_key() -> Result<Option<String>, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.get_api_key().map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+async fn youtube_validate_api_key(api_key: String) -> Result<bool, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.validate_api_key(&api_key).await.map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn youtube_get_video_metadata(video_id: String) -> Result<SpotifyTrackMetadata, CommandError> {
+    let bridge = get_youtube_bridge();
+    bridge.get_video_metadata(&video_id).await.map_err(|e| MilkError::from(e).report())
+}
+
+#[tauri::command]
+fn get_performance_metrics() -> Option<performance::PerformanceMetrics> {
+    performance::get_metrics()
+}
+
+#[tauri::command]
+fn get_cache_hit_rate() -> f64 {
+    if let Some(metrics) = performance::get_metrics() {
+        metrics.cache_hit_rate()
+    } else {
+        0.0
+    }
+}
+
+#[tauri::command]
+fn get_memory_usage() -> Option<f64> {
+    performance::get_metrics().and_then(|m| m.memory_usage_mb())
+}
+
+#[tauri::command]
+fn get_peak_memory() -> Option<f64> {
+    performance::get_metrics().and_then(|m| m.peak_memory_mb())
+}
+
+/// Recent log entries for the diagnostics panel, newest first
+///
+/// `level` includes everything at least that severe; `limit` defaults to 100.
+#[tauri::command]
+fn get_recent_logs(level: Option<logging::LogLevel>, category: Option<String>, limit: Option<usize>) -> Vec<logging::LogEntry> {
+    logging::recent_logs(level, category.as_deref(), limit.unwrap_or(100))
+}
+
+/// Per-command call counts, durations and error rates, slowest total first
+#[tauri::command]
+fn get_command_timings() -> Vec<performance::CommandTiming> {
+    performance::get_command_timings()
+}
+
+/// Metrics time series for the current session or the last N days
+#[tauri::command]
+fn get_metrics_history(range: performance::MetricsRange) -> Vec<performance::PerformanceSnapshot> {
+    performance::metrics_history(range)
+}
+
+/// Export a metrics time series as CSV or JSON, returning the number of samples written
+#[tauri::command]
+fn export_metrics_history(range: performance::MetricsRange, format: performance::ExportFormat, path: String) -> Result<usize, CommandError> {
+    performance::export_history(range, format, std::path::Path::new(&path)).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Performance", &format!("Failed to export metrics: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Get persisted performance snapshots from the last `days` days
+#[tauri::command]
+fn get_performance_history(days: u32) -> Vec<performance::PerformanceSnapshot> {
+    performance::get_history(days)
+}
+
+#[tauri::command]
+fn check_metadata_completeness(file_path: String) -> Result<bool, CommandError> {
+    let path = &path_codec::decode(&file_path);
+    let extractor = get_metadata_extractor();
+
+    match extractor.extract(path) {
+        Ok(metadata) => Ok(metadata.is_complete()),
+        Err(e) => {
+            let milk_err = MilkError::from(e);
+            Err(milk_err.report())
+        }
+    }
+}
+
+#[tauri::command]
+fn is_metadata_cached(file_path: String) -> bool {
+    let path = &path_codec::decode(&file_path);
+    let extractor = get_metadata_extractor();
+    extractor.is_cached(path)
+}
+
+#[tauri::command]
+fn clear_metadata_cache() {
+    log_info("Metadata", "Clearing metadata cache");
+    let extractor = get_metadata_extractor();
+    extractor.clear_cache();
+}
+
+/// Entry counts and hit/miss/invalidation counters for the metadata caches
+#[tauri::command]
+fn get_metadata_cache_stats() -> Result<metadata_cache::MetadataCacheStats, CommandError> {
+    get_metadata_extractor().cache_stats().map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Metadata", &format!("Failed to read metadata cache stats: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+#[tauri::command]
+fn check_file_extension_supported(extension: String) -> bool {
+    LibraryScanner::is_supported_extension(&extension)
+}
+
+#[tauri::command]
+fn validate_audio_file(file_path: String) -> Result<(), CommandError> {
+    let path = &path_codec::decode(&file_path);
+    validate_audio_format(path).map_err(|e| e.report())
+}
+
+#[tauri::command]
+fn load_validated_config() -> Result<Config, CommandError> {
+    load_and_validate_config().map_err(|e| e.report())
+}
+
+#[tauri::command]
+fn test_internal_error_handling() -> Result<String, CommandError> {
+    // Example of using handle_unexpected_error
+    let result: Result<String, Box<dyn std::error::Error>> = Ok("test".to_string());
+    handle_unexpected_error(result).map_err(|e| e.report())
+}
+
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn get_skin_assets(skin_path: String) -> Result<std::collections::HashMap<String, Vec<u8>>, CommandError> {
+    use std::path::Path;
+    let path = Path::new(&skin_path);
+
+    let skin = if is_skin_file(&skin_path) {
+        get_skin_cache().get_or_parse(path)
+    } else {
+        return Err(MilkError::InvalidSkinFormat(skin_path).report());
+    };
+
+    let options = FileConfigManager::load().map(|config| config.skin_assets).unwrap_or_default();
+    match skin {
+        Ok(skin) => {
+            match SkinParser::extract_assets(&skin, &options) {
+                Ok(assets) => Ok(assets),
+                Err(e) => Err(MilkError::from(e).report())
+            }
+        }
+        Err(e) => Err(MilkError::from(e).report())
+    }
+}
+
+/// Browse the Winamp Skin Museum catalogue
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn museum_browse_skins(offset: u32, limit: u32) -> Result<MuseumPage, CommandError> {
+    log_info("SkinMuseum", &format!("Browsing skins (offset {}, limit {})", offset, limit));
+    connectivity::ensure_online().map_err(|e| e.report())?;
+    get_skin_museum().browse(offset, limit).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("SkinMuseum", &format!("Failed to browse skins: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Search the Winamp Skin Museum catalogue
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn museum_search_skins(query: String, offset: u32, limit: u32) -> Result<MuseumPage, CommandError> {
+    log_info("SkinMuseum", &format!("Searching skins: {}", query));
+    connectivity::ensure_online().map_err(|e| e.report())?;
+    get_skin_museum().search(&query, offset, limit).await.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("SkinMuseum", &format!("Failed to search skins: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Store (or with `None`, remove) the proxy password and re-apply the proxy
+///
+/// The rest of the proxy settings live in the config and are re-applied
+/// whenever it changes.
+#[tauri::command]
+fn set_proxy_password(password: Option<String>) -> Result<(), CommandError> {
+    let storage = PlatformSecureStorage::new();
+    let stored = match password.as_deref().filter(|p| !p.is_empty()) {
+        Some(password) => storage.store(api_client::PROXY_PASSWORD_KEY, password),
+        None => storage.delete(api_client::PROXY_PASSWORD_KEY),
+    };
+    let config = stored
+        .map_err(MilkError::from)
+        .and_then(|_| FileConfigManager::load().map_err(MilkError::from))
+        .map_err(|milk_err| {
+            log_error("ApiClient", &format!("Failed to update proxy password: {}", milk_err));
+            milk_err.report()
+        })?;
+    api_client::configure_proxy(&config.proxy, &storage).map_err(|milk_err| {
+        log_error("ApiClient", &format!("Failed to apply proxy settings: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Store (or with `None`, remove) the WebDAV password or S3 secret key used for backups
+#[tauri::command]
+fn set_backup_secret(secret: Option<String>) -> Result<(), CommandError> {
+    let storage = PlatformSecureStorage::new();
+    let stored = match secret.as_deref().filter(|s| !s.is_empty()) {
+        Some(secret) => storage.store(cloud_backup::BACKUP_SECRET_KEY, secret),
+        None => storage.delete(cloud_backup::BACKUP_SECRET_KEY),
+    };
+    stored.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("CloudBackup", &format!("Failed to update backup credentials: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Back up to the configured server, merging in changes from other machines
+async fn run_backup() -> Result<cloud_backup::BackupReport, MilkError> {
+    let settings = FileConfigManager::load()?.backup;
+    let secret = PlatformSecureStorage::new().retrieve(cloud_backup::BACKUP_SECRET_KEY)?;
+    let remote = cloud_backup::Remote::from_settings(&settings, secret)?;
+    let config_path = FileConfigManager::get_config_path()?;
+    let report = cloud_backup::backup_now(&remote, settings.provider, get_playlist_manager().await, get_kv_store(), &config_path).await?;
+    log_info("CloudBackup", &format!(
+        "Backed up {} playlists and {} ratings to {:?} ({} playlists and {} ratings pulled, {} conflicts)",
+        report.playlists, report.ratings, report.provider, report.playlists_pulled, report.ratings_pulled, report.conflicts
+    ));
+    Ok(report)
+}
+
+/// Back up playlists, ratings and settings now
+///
+/// Playlists, ratings and settings changed on other machines since the last
+/// backup are applied here first; see `cloud_backup` for how conflicts are
+/// resolved.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn backup_now() -> Result<cloud_backup::BackupReport, CommandError> {
+    connectivity::ensure_online().map_err(|e| e.report())?;
+    run_backup().await.map_err(|milk_err| {
+        log_error("CloudBackup", &format!("Backup failed: {}", milk_err));
+        milk_err.report()
+    })
+}
+
+/// Whether the network is reachable and how much work waits for it
+#[tauri::command]
+fn get_connectivity_status() -> connectivity::ConnectivityEvent {
+    connectivity::status()
+}
+
+/// Download a museum skin into the local skin library
+///
+/// Emits `skin-download-progress` while downloading and verifies the file
+/// against the museum's MD5. When `apply` is set the skin is applied and
+/// remembered as the last skin, exactly like `apply_skin`. While offline the
+/// download is queued until the connection is back and the offline error is
+/// returned.
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+async fn museum_download_skin(app: tauri::AppHandle, skin: MuseumSkin, apply: bool) -> Result<String, CommandError> {
+    if !connectivity::is_online() {
+        let key = format!("skin-download:{}", skin.md5);
+        connectivity::run_when_online(key, move || async move {
+            let _ = download_museum_skin(app, skin, apply).await;
+        });
+        return Err(MilkError::Offline.report());
+    }
+    download_museum_skin(app, skin, apply).await
+}
+
+async fn download_museum_skin(app: tauri::AppHandle, skin: MuseumSkin, apply: bool) -> Result<String, CommandError> {
+    log_info("SkinMuseum", &format!("Downloading skin: {} ({})", skin.filename, skin.md5));
+    let dest_dir = skin_museum::get_skin_library_dir()
+        .ok_or_else(|| MilkError::InvalidPath("skin library directory".to_string()).report())?;
+
+    let sink = jobs::app_sink(app.clone());
+    let job_id = format!("skin-download:{}", skin.md5);
+    let result = jobs::job_manager()
+        .run_async(job_id, jobs::JobKind::Download, "download_skin", sink, |ctx| async move {
+            get_skin_museum()
+                .download(&skin, &dest_dir, |progress| {
+                    ctx.report_bytes(progress.downloaded_bytes, progress.total_bytes);
+                    let _ = app.emit("skin-download-progress", &progress);
+                })
+                .await
+                .map_err(MilkError::from)
+        })
+        .await;
+
+    match result {
+        Ok(path) => {
+            let path = path.to_string_lossy().to_string();
+            log_info("SkinMuseum", &format!("Skin saved to {}", path));
+            if apply {
+                apply_skin(path.clone())?;
+            }
+            Ok(path)
+        }
+        Err(milk_err) => {
+            log_error("SkinMuseum", &format!("Failed to download skin: {}", milk_err));
+            Err(milk_err.report())
         }
     }
 }
 
+/// Drop all cached parsed skins
 #[tauri::command]
-async fn update_playlist(playlist_id: String, name: Option<String>) -> Result<Playlist, String> {
-    log_info("Playlist", &format!("Updating playlist: {}", playlist_id));
-    let manager = get_playlist_manager().await;
-    let manager = manager.lock().await;
-    match manager.update_playlist(&playlist_id, name).await {
-        Ok(playlist) => Ok(playlist),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Playlist", &format!("Failed to update playlist: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn clear_skin_cache() {
+    let cleared = get_skin_cache().clear();
+    log_info("Skin", &format!("Cleared {} cached skins", cleared));
 }
 
+/// Slice a classic skin's sprite sheets into named PNG sprites
 #[tauri::command]
-fn load_skin(skin_path: String) -> Result<ParsedSkin, String> {
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn get_skin_sprites(skin_path: String) -> Result<std::collections::HashMap<String, Vec<u8>>, CommandError> {
     use std::path::Path;
-    log_info("Skin", &format!("Loading skin: {}", skin_path));
+    log_info("Skin", &format!("Extracting sprites: {}", skin_path));
     let path = Path::new(&skin_path);
-    
-    // Try to parse as .wsz or .wal
-    let result = if skin_path.to_lowercase().ends_with(".wsz") {
-        SkinParser::parse_wsz(path)
-    } else if skin_path.to_lowercase().ends_with(".wal") {
-        SkinParser::parse_wal(path)
+
+    let skin = if is_skin_file(&skin_path) {
+        get_skin_cache().get_or_parse(path)
     } else {
         let err = MilkError::InvalidSkinFormat(skin_path.clone());
-        log_error("Skin", &format!("{}", err));
-        return Err(err.user_message());
+        log_error("Skin", &format!("Invalid skin format: {}", err));
+        return Err(err.report());
     };
 
-    match result {
-        Ok(skin) => {
-            // Validate the skin
-            match SkinParser::validate_skin(&skin) {
-                Ok(_) => {
-                    log_info("Skin", "Skin loaded and validated successfully");
-                    Ok(skin)
-                }
-                Err(e) => {
-                    let milk_err = MilkError::from(e);
-                    log_warn("Skin", &format!("Skin validation failed, using default: {}", milk_err));
-                    Ok(SkinParser::get_default_skin())
-                }
-            }
+    match skin.and_then(|skin| SkinSpriteExtractor::extract(&skin)) {
+        Ok(sprites) => {
+            log_info("Skin", &format!("Extracted {} sprites", sprites.len()));
+            Ok(sprites)
         }
         Err(e) => {
-            // Return default skin on error (graceful degradation)
             let milk_err = MilkError::from(e);
-            log_warn("Skin", &format!("Failed to load skin, using default: {}", milk_err));
-            Ok(SkinParser::get_default_skin())
+            log_error("Skin", &format!("Failed to extract sprites: {}", milk_err));
+            Err(milk_err.report())
         }
     }
 }
 
+/// List everything wrong with a skin, including why it would fall back to the default
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn validate_skin_detailed(skin_path: String) -> Result<skin::SkinValidationReport, CommandError> {
+    log_info("Skin", &format!("Validating skin: {}", skin_path));
+    if !is_skin_file(&skin_path) {
+        let err = MilkError::InvalidSkinFormat(skin_path);
+        log_error("Skin", &format!("Invalid skin format: {}", err));
+        return Err(err.report());
+    }
+    let report = SkinParser::validate_detailed(std::path::Path::new(&skin_path));
+    log_info("Skin", &format!("Skin {} has {} issues", report.name, report.issues.len()));
+    Ok(report)
+}
+
+/// Zip a directory of skin assets into a distributable .wsz
+#[tauri::command]
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn package_skin(directory: String, output_wsz: String) -> Result<skin_package::SkinPackageReport, CommandError> {
+    log_info("Skin", &format!("Packaging {} into {}", directory, output_wsz));
+    let is_wsz = std::path::Path::new(&output_wsz)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("wsz"));
+    if !is_wsz {
+        let err = MilkError::InvalidSkinFormat(output_wsz);
+        log_error("Skin", &format!("Invalid skin format: {}", err));
+        return Err(err.report());
+    }
+    skin_package::package_skin(std::path::Path::new(&directory), std::path::Path::new(&output_wsz))
+        .inspect(|report| {
+            log_info("Skin", &format!("Packaged {} files with {} warnings", report.files.len(), report.warnings.len()));
+        })
+        .map_err(|e| {
+            let milk_err = MilkError::from(e);
+            log_error("Skin", &format!("Failed to package skin: {}", milk_err));
+            milk_err.report()
+        })
+}
+
+/// Decode a classic skin's bitmap fonts and cursors
 #[tauri::command]
-fn apply_skin(skin_path: String) -> Result<ParsedSkin, String> {
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn get_skin_fonts(skin_path: String) -> Result<skin::SkinFonts, CommandError> {
     use std::path::Path;
-    log_info("Skin", &format!("Applying skin: {}", skin_path));
+    log_info("Skin", &format!("Extracting fonts and cursors: {}", skin_path));
     let path = Path::new(&skin_path);
-    
-    // Load and validate the skin
-    let skin = if skin_path.to_lowercase().ends_with(".wsz") {
-        SkinParser::parse_wsz(path)
-    } else if skin_path.to_lowercase().ends_with(".wal") {
-        SkinParser::parse_wal(path)
+
+    let skin = if is_skin_file(&skin_path) {
+        get_skin_cache().get_or_parse(path)
     } else {
         let err = MilkError::InvalidSkinFormat(skin_path.clone());
-        log_error("Skin", &format!("{}", err));
-        return Err(err.user_message());
+        log_error("Skin", &format!("Invalid skin format: {}", err));
+        return Err(err.report());
     };
 
-    match skin {
-        Ok(skin) => {
-            // Validate the skin
-            match SkinParser::validate_skin(&skin) {
-                Ok(_) => {
-                    // Save the skin path to config
-                    let mut config = FileConfigManager::load().unwrap_or_else(|_| FileConfigManager::get_default());
-                    config.last_skin = Some(skin_path.clone());
-                    if let Err(e) = FileConfigManager.save(&config) {
-                        log_warn("Skin", &format!("Failed to save skin preference: {}", e));
-                    }
-                    log_info("Skin", "Skin applied successfully");
-                    Ok(skin)
-                }
-                Err(e) => {
-                    let milk_err = MilkError::from(e);
-                    log_warn("Skin", &format!("Skin validation failed, using default: {}", milk_err));
-                    Ok(SkinParser::get_default_skin())
-                }
-            }
+    match skin.and_then(|skin| SkinParser::extract_fonts(&skin)) {
+        Ok(fonts) => {
+            log_info("Skin", &format!("Extracted fonts and {} cursors", fonts.cursors.len()));
+            Ok(fonts)
         }
         Err(e) => {
             let milk_err = MilkError::from(e);
-            log_warn("Skin", &format!("Failed to apply skin, using default: {}", milk_err));
-            Ok(SkinParser::get_default_skin())
+            log_error("Skin", &format!("Failed to extract fonts: {}", milk_err));
+            Err(milk_err.report())
         }
     }
 }
 
+/// Scan a directory of Milkdrop presets (defaults to the app's presets folder)
 #[tauri::command]
-async fn spotify_authenticate(credentials: Credentials, auth_code: String) -> Result<Token, String> {
-    log_info("Spotify", "Authenticating with Spotify");
-    let bridge = get_spotify_bridge();
-    match bridge.authenticate(credentials, auth_code).await {
-        Ok(token) => {
-            log_info("Spotify", "Authentication successful");
-            Ok(token)
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn list_presets(directory: Option<String>) -> Result<Vec<PresetInfo>, CommandError> {
+    let dir = match directory {
+        Some(dir) => std::path::PathBuf::from(dir),
+        None => visualizer::get_default_presets_dir()
+            .ok_or_else(|| MilkError::InvalidPath("presets directory".to_string()).report())?,
+    };
+    log_info("Visualizer", &format!("Scanning presets: {}", dir.display()));
+
+    let mut manager = get_preset_manager().lock().unwrap();
+    match manager.scan_directory(&dir) {
+        Ok(presets) => {
+            log_info("Visualizer", &format!("Found {} presets", presets.len()));
+            Ok(presets.to_vec())
         }
         Err(e) => {
             let milk_err = MilkError::from(e);
-            log_error("Spotify", &format!("Authentication failed: {}", milk_err));
-            Err(milk_err.user_message())
+            log_error("Visualizer", &format!("Failed to scan presets: {}", milk_err));
+            Err(milk_err.report())
         }
     }
 }
 
+/// Load a scanned preset by name or path and make it current
 #[tauri::command]
-async fn spotify_get_now_playing() -> Result<Option<SpotifyTrackMetadata>, String> {
-    let bridge = get_spotify_bridge();
-    match bridge.get_now_playing().await {
-        Ok(metadata) => Ok(metadata),
-        Err(e) => {
-            // Check error type before converting
-            let is_no_playback = matches!(e, spotify::ApiError::NoActivePlayback);
-            let milk_err = MilkError::from(e);
-            
-            // Only log as warning for "no active playback" which is not really an error
-            if is_no_playback {
-                log_info("Spotify", "No active playback");
-            } else {
-                log_warn("Spotify", &format!("Failed to get now playing: {}", milk_err));
-            }
-            Err(milk_err.user_message())
-        }
-    }
+#[tracing::instrument(target = "milk::command", skip_all, err)]
+fn load_preset(name: String) -> Result<MilkPreset, CommandError> {
+    let mut manager = get_preset_manager().lock().unwrap();
+    manager.load(&name).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Visualizer", &format!("Failed to load preset {}: {}", name, milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-async fn spotify_refresh_token(credentials: Credentials) -> Result<Token, String> {
-    log_info("Spotify", "Refreshing Spotify token");
-    let bridge = get_spotify_bridge();
-    match bridge.refresh_token(credentials).await {
-        Ok(token) => {
-            log_info("Spotify", "Token refreshed successfully");
-            Ok(token)
-        }
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            log_error("Spotify", &format!("Token refresh failed: {}", milk_err));
-            Err(milk_err.user_message())
-        }
-    }
+fn next_preset() -> Result<MilkPreset, CommandError> {
+    let mut manager = get_preset_manager().lock().unwrap();
+    manager.next().map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Visualizer", &format!("Failed to advance preset: {}", milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-fn spotify_check_token_expired() -> Result<bool, String> {
-    let bridge = get_spotify_bridge();
-    bridge.check_token_expired().map_err(|e| e.to_string())
+fn prev_preset() -> Result<MilkPreset, CommandError> {
+    let mut manager = get_preset_manager().lock().unwrap();
+    manager.previous().map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_warn("Visualizer", &format!("Failed to go back a preset: {}", milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-async fn spotify_ensure_valid_token(credentials: Option<Credentials>) -> Result<String, String> {
-    let bridge = get_spotify_bridge();
-    bridge.ensure_valid_token(credentials).await.map_err(|e| e.to_string())
+fn set_preset_shuffle(enabled: bool) {
+    get_preset_manager().lock().unwrap().set_shuffle(enabled);
 }
 
+/// Publish local playback state to the OS media overlay
+///
+/// Pass `track` when the track changes; status-only updates can omit it.
+/// Each new local track counts as a play in the play history, and the position
+/// is saved with the player session.
 #[tauri::command]
-async fn youtube_authenticate(credentials: Credentials, auth_code: String) -> Result<Token, String> {
-    let bridge = get_youtube_bridge();
-    bridge.authenticate(credentials, auth_code).await.map_err(|e| e.to_string())
+fn update_media_session(app: tauri::AppHandle, track: Option<NowPlaying>, status: PlaybackStatus, position_secs: Option<f64>) {
+    record_playback(Some(&app), track, status, position_secs);
 }
 
-#[tauri::command]
-async fn youtube_get_now_playing() -> Result<Option<SpotifyTrackMetadata>, String> {
-    let bridge = get_youtube_bridge();
-    bridge.get_now_playing().await.map_err(|e| e.to_string())
+/// Pass a local playback update to everything that follows the player
+///
+/// The headless player has no `AppHandle`, so it goes without the tray
+/// tooltip and `now-playing-changed` events.
+pub(crate) fn record_playback(app: Option<&tauri::AppHandle>, track: Option<NowPlaying>, status: PlaybackStatus, position_secs: Option<f64>) {
+    PLAYER_STATE.set_playback(track.clone(), status, position_secs);
+    if position_secs.is_some() {
+        session::update(session::SessionUpdate { position_secs, ..Default::default() });
+    }
+    if let Some(track) = &track {
+        if let Some(app) = app {
+            tray::set_tooltip(app, Some(track));
+        }
+        if let Some(path) = &track.file_path {
+            if let Err(e) = play_history::record_play(get_kv_store(), path, chrono::Utc::now()) {
+                log_warn("PlayHistory", &format!("Failed to record play: {}", e));
+            }
+        }
+    }
+    if let Some(track) = &track {
+        plugins::playback_event(plugins::PlaybackEvent::TrackChanged { track: track.clone() });
+        scripting::dispatch(scripting::ScriptEvent::TrackChanged(track.clone()));
+    }
+    plugins::playback_event(plugins::PlaybackEvent::State { status, position_secs });
+    report_now_playing(app, now_playing::NowPlayingSource::Local, track, status, position_secs);
 }
 
-#[tauri::command]
-async fn youtube_refresh_token(credentials: Credentials) -> Result<Token, String> {
-    let bridge = get_youtube_bridge();
-    bridge.refresh_token(credentials).await.map_err(|e| e.to_string())
+/// Feed a source's state into the unified now playing
+///
+/// The media overlay follows whichever source is picked, and a
+/// `now-playing-changed` event goes out whenever the pick changes.
+pub(crate) fn report_now_playing(
+    app: Option<&tauri::AppHandle>,
+    source: now_playing::NowPlayingSource,
+    track: Option<NowPlaying>,
+    status: PlaybackStatus,
+    position_secs: Option<f64>,
+) {
+    match now_playing::report(source, track, status, position_secs) {
+        Some(unified) => publish_now_playing(app, &unified),
+        None => {
+            let current = now_playing::current();
+            if let (Some(session), true) = (MEDIA_SESSION.get(), current.source == Some(source)) {
+                session.set_status(current.status, current.position_secs);
+            }
+        }
+    }
 }
 
-#[tauri::command]
-fn youtube_check_token_expired() -> Result<bool, String> {
-    let bridge = get_youtube_bridge();
-    bridge.check_token_expired().map_err(|e| e.to_string())
+fn publish_now_playing(app: Option<&tauri::AppHandle>, unified: &now_playing::UnifiedNowPlaying) {
+    if let Some(session) = MEDIA_SESSION.get() {
+        // System audio and an empty pick have no track, so the previous one must not linger
+        session.set_track(unified.track.clone());
+        session.set_status(unified.status, unified.position_secs);
+    }
+    let Some(app) = app else {
+        return;
+    };
+    if let Err(e) = app.emit("now-playing-changed", unified) {
+        log_warn("NowPlaying", &format!("Failed to emit now-playing-changed event: {}", e));
+    }
 }
 
+/// What is playing across local playback, streaming services and system audio
 #[tauri::command]
-async fn youtube_ensure_valid_token(credentials: Option<Credentials>) -> Result<String, String> {
-    let bridge = get_youtube_bridge();
-    bridge.ensure_valid_token(credentials).await.map_err(|e| e.to_string())
+fn get_unified_now_playing() -> now_playing::UnifiedNowPlaying {
+    now_playing::current()
 }
 
 #[tauri::command]
-fn youtube_store_api_key(api_key: String) -> Result<(), String> {
-    let bridge = get_youtube_bridge();
-    bridge.store_api_key(&api_key).map_err(|e| e.to_string())
+fn set_sleep_timer(app: tauri::AppHandle, minutes: u32, action: SleepAction) -> Result<SleepTimer, CommandError> {
+    get_scheduler().set_sleep_timer(app, minutes, action).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Scheduler", &format!("Failed to set sleep timer: {}", milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-fn youtube_get_api_key() -> Result<Option<String>, String> {
-    let bridge = get_youtube_bridge();
-    bridge.get_api_key().map_err(|e| e.to_string())
+fn cancel_sleep_timer() -> bool {
+    get_scheduler().cancel_sleep_timer()
 }
 
 #[tauri::command]
-async fn youtube_validate_api_key(api_key: String) -> Result<bool, String> {
-    let bridge = get_youtube_bridge();
-    bridge.validate_api_key(&api_key).await.map_err(|e| e.to_string())
+fn get_sleep_timer() -> Option<SleepTimer> {
+    get_scheduler().sleep_timer()
 }
 
+/// Installed plugins with their capabilities and status
 #[tauri::command]
-async fn youtube_get_video_metadata(video_id: String) -> Result<SpotifyTrackMetadata, String> {
-    let bridge = get_youtube_bridge();
-    bridge.get_video_metadata(&video_id).await.map_err(|e| e.to_string())
+fn list_plugins() -> Vec<plugins::PluginInfo> {
+    plugins::list()
 }
 
+/// Enable or disable a plugin; enabling a suspended plugin restarts it
 #[tauri::command]
-fn get_performance_metrics() -> Option<performance::PerformanceMetrics> {
-    performance::get_metrics()
+fn set_plugin_enabled(id: String, enabled: bool) -> Result<plugins::PluginInfo, CommandError> {
+    plugins::set_enabled(get_kv_store(), &id, enabled).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Plugins", &format!("Failed to update plugin {}: {}", id, milk_err));
+        milk_err.report()
+    })
 }
 
+/// User scripts with the handlers they define and their status
 #[tauri::command]
-fn get_cache_hit_rate() -> f64 {
-    if let Some(metrics) = performance::get_metrics() {
-        metrics.cache_hit_rate()
-    } else {
-        0.0
-    }
+fn list_scripts() -> Vec<scripting::ScriptInfo> {
+    scripting::list()
 }
 
+/// Reload every script from the scripts folder
 #[tauri::command]
-fn get_memory_usage() -> Option<f64> {
-    performance::get_metrics().and_then(|m| m.memory_usage_mb())
+async fn reload_scripts() -> Result<Vec<scripting::ScriptInfo>, CommandError> {
+    let result = tauri::async_runtime::spawn_blocking(scripting::reload).await.map_err(|e| {
+        let milk_err = MilkError::Internal(format!("Script reload failed: {}", e));
+        log_error("Scripts", &milk_err.to_string());
+        milk_err.report()
+    })?;
+    result.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Scripts", &format!("Failed to reload scripts: {}", milk_err));
+        milk_err.report()
+    })
 }
 
+/// Classic Winamp vis plugins found in milk's and Winamp's plugin folders
 #[tauri::command]
-fn get_peak_memory() -> Option<f64> {
-    performance::get_metrics().and_then(|m| m.peak_memory_mb())
+fn list_winamp_plugins() -> Vec<winamp_vis::WinampPluginInfo> {
+    winamp_vis::list()
 }
 
+/// Start a Winamp vis plugin fed from system audio capture
+///
+/// Replaces any running plugin. Emits `winamp-vis-stopped` if the plugin
+/// closes itself, e.g. when its window is closed.
 #[tauri::command]
-fn check_metadata_completeness(file_path: String) -> Result<bool, String> {
-    use std::path::Path;
-    let path = Path::new(&file_path);
-    let extractor = get_metadata_extractor();
-
-    match extractor.extract(path) {
-        Ok(metadata) => Ok(metadata.is_complete()),
-        Err(e) => {
-            let milk_err = MilkError::from(e);
-            Err(milk_err.user_message())
+fn start_winamp_vis(app: tauri::AppHandle, plugin: String, module: Option<u32>) -> Result<winamp_vis::WinampVisInfo, CommandError> {
+    let on_exit = move |info: winamp_vis::WinampVisInfo| {
+        if let Err(e) = app.emit("winamp-vis-stopped", &info) {
+            log_warn("WinampVis", &format!("Failed to emit winamp-vis-stopped event: {}", e));
         }
-    }
+    };
+    winamp_vis::start(&plugin, module.unwrap_or(0), on_exit).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("WinampVis", &format!("Failed to start {}: {}", plugin, milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-fn is_metadata_cached(file_path: String) -> bool {
-    use std::path::Path;
-    let path = Path::new(&file_path);
-    let extractor = get_metadata_extractor();
-    extractor.is_cached(path)
+fn stop_winamp_vis() -> bool {
+    winamp_vis::stop()
 }
 
 #[tauri::command]
-fn clear_metadata_cache() {
-    log_info("Metadata", "Clearing metadata cache");
-    let extractor = get_metadata_extractor();
-    extractor.clear_cache();
+fn get_winamp_vis() -> Option<winamp_vis::WinampVisInfo> {
+    winamp_vis::active()
 }
 
+/// Mono samples of what the windowed player is playing, for the visualizer
+/// consumers (remote API, plugins, Winamp vis)
 #[tauri::command]
-fn check_file_extension_supported(extension: String) -> bool {
-    LibraryScanner::is_supported_extension(&extension)
+fn feed_player_audio(samples: Vec<f32>, sample_rate: u32) {
+    let tap = visualizer_feed::webview_tap();
+    tap.set_format(sample_rate, 1);
+    tap.push(&samples);
 }
 
+/// Rescan the plugins folder, e.g. after installing a plugin
 #[tauri::command]
-fn validate_audio_file(file_path: String) -> Result<(), String> {
-    use std::path::Path;
-    let path = Path::new(&file_path);
-    validate_audio_format(path).map_err(|e| e.user_message())
+async fn reload_plugins() -> Result<Vec<plugins::PluginInfo>, CommandError> {
+    tauri::async_runtime::spawn_blocking(|| plugins::start(get_kv_store())).await.map_err(|e| {
+        let milk_err = MilkError::Internal(format!("Plugin reload failed: {}", e));
+        log_error("Plugins", &milk_err.to_string());
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-fn load_validated_config() -> Result<Config, String> {
-    load_and_validate_config().map_err(|e| e.user_message())
+fn set_alarm(app: tauri::AppHandle, time: String, playlist_id: String) -> Result<Alarm, CommandError> {
+    get_scheduler().set_alarm(app, &time, playlist_id).map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Scheduler", &format!("Failed to set alarm: {}", milk_err));
+        milk_err.report()
+    })
 }
 
 #[tauri::command]
-fn test_internal_error_handling() -> Result<String, String> {
-    // Example of using handle_unexpected_error
-    let result: Result<String, Box<dyn std::error::Error>> = Ok("test".to_string());
-    handle_unexpected_error(result).map_err(|e| e.user_message())
+fn cancel_alarm(id: String) -> Result<(), CommandError> {
+    get_scheduler()
+        .cancel_alarm(&id)
+        .map_err(|e| MilkError::from(e).report())
 }
 
 #[tauri::command]
-fn get_skin_assets(skin_path: String) -> Result<std::collections::HashMap<String, Vec<u8>>, String> {
-    use std::path::Path;
-    let path = Path::new(&skin_path);
-
-    let skin = if skin_path.to_lowercase().ends_with(".wsz") {
-        SkinParser::parse_wsz(path)
-    } else if skin_path.to_lowercase().ends_with(".wal") {
-        SkinParser::parse_wal(path)
-    } else {
-        return Err("Invalid skin format".to_string());
-    };
-
-    match skin {
-        Ok(skin) => {
-            match SkinParser::extract_assets(&skin) {
-                Ok(assets) => Ok(assets),
-                Err(e) => Err(e.to_string())
-            }
-        }
-        Err(e) => Err(e.to_string())
-    }
+fn list_alarms() -> Vec<Alarm> {
+    get_scheduler().alarms()
 }
 
 #[tauri::command]
-fn get_error_category(error_msg: String) -> String {
-    // Create a generic error to demonstrate category usage
-    let error = MilkError::Other(error_msg);
-    error.category().to_string()
+fn get_view_sort(view: String) -> Option<SortOptions> {
+    get_kv_store().get(&sorting::view_sort_key(&view))
 }
 
 #[tauri::command]
-fn is_error_critical(error_type: String) -> bool {
-    // Map common error types to check criticality
-    let error = match error_type.as_str() {
-        "disk_full" => MilkError::DiskFull("test".to_string()),
-        "permission_denied" => MilkError::PermissionDenied("test".to_string()),
-        "audio_device" => MilkError::AudioDeviceUnavailable,
-        "auth_failed" => MilkError::AuthenticationFailed("test".to_string()),
-        _ => MilkError::Other(error_type),
+fn set_view_sort(view: String, sort: Option<SortOptions>) -> Result<(), CommandError> {
+    let key = sorting::view_sort_key(&view);
+    let result = match sort {
+        Some(sort) => get_kv_store().set(&key, &sort),
+        None => get_kv_store().remove(&key).map(|_| ()),
     };
-    error.is_critical()
+    result.map_err(|e| {
+        let milk_err = MilkError::from(e);
+        log_error("Sorting", &format!("Failed to save sort for {}: {}", view, milk_err));
+        milk_err.report()
+    })
+}
+
+/// Error counts by category and severity for this session, plus the latest errors
+#[tauri::command]
+fn get_error_stats() -> error_stats::ErrorStats {
+    error_stats::stats()
 }
 
 #[tauri::command]
@@ -756,11 +3683,116 @@ fn is_error_recoverable(error_type: String) -> bool {
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
+/// Run without a window as a daemon controlled through the remote API
+///
+/// Files on the command line replace the saved queue. The remote API is
+/// started even when it is disabled in the settings, since nothing else can
+/// control the player.
+fn run_headless(launch: cli::LaunchArgs) {
+    log_info("Startup", "Starting in headless mode");
+    session::start();
+    let config = FileConfigManager::load().unwrap_or_else(|_| FileConfigManager::get_default());
+    now_playing::set_priority(&config.playback.source_priority);
+    if let Err(e) = api_client::configure_proxy(&config.proxy, &PlatformSecureStorage::new()) {
+        log_warn("ApiClient", &format!("Proxy settings not applied: {}", e));
+    }
+    std::thread::spawn(|| {
+        plugins::start(get_kv_store());
+    });
+
+    let saved = session::restore();
+    let tracks = cli::resolve(&launch, get_metadata_extractor()).tracks;
+    let (queue, start_secs) = if tracks.is_empty() {
+        (headless::PlayerQueue { tracks: saved.queue, index: saved.current_index, repeat: saved.repeat }, saved.position_secs)
+    } else {
+        (headless::PlayerQueue { tracks, index: Some(0), repeat: saved.repeat }, 0.0)
+    };
+
+    let (commands, receiver) = std::sync::mpsc::channel();
+    let control = headless::control_sink(commands.clone());
+    scripting::start(scripting::ScriptApi { store: get_kv_store(), control: control.clone() });
+    // The remote API is the only way to control the daemon, from other machines too
+    let settings = config::RemoteApiSettings { enabled: true, allow_lan: true, ..config.remote_api };
+    if let Err(e) = tauri::async_runtime::block_on(apply_remote_api(control, &settings)) {
+        log_error("RemoteApi", &format!("Remote API not started, nothing could control the player: {}", e));
+        return;
+    }
+    log_info(
+        "RemoteApi",
+        &format!("The API token is kept in secure storage as '{}'; start with --print-token to show it", remote_api::TOKEN_KEY),
+    );
+    if launch.print_token {
+        match remote_api::get_or_create_token(&PlatformSecureStorage::new()) {
+            Ok(token) => println!("{}", token),
+            Err(e) => log_warn("RemoteApi", &format!("Failed to read API token: {}", e)),
+        }
+    }
+
+    // Follow output and DSP changes made with `set_headless_output_mode`, `set_dsp_chain`
+    // or by editing the file
+    if let Ok(path) = FileConfigManager::get_config_path() {
+        let mut watcher = config_watcher::ConfigWatcher::new(path);
+        let mut output = config.output.clone();
+        let mut dsp = config.dsp.clone();
+        let output_commands = commands.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(config_watcher::POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Some(Ok(changed)) = watcher.poll() {
+                    if changed.output != output {
+                        output = changed.output.clone();
+                        let _ = output_commands.send(headless::PlayerCommand::SetOutput(changed.output));
+                    }
+                    if changed.dsp != dsp {
+                        dsp = changed.dsp.clone();
+                        let _ = output_commands.send(headless::PlayerCommand::SetDsp(changed.dsp));
+                    }
+                }
+            }
+        });
+    }
+
+    tauri::async_runtime::spawn(async move {
+        shutdown_signal().await;
+        let _ = commands.send(headless::PlayerCommand::Quit);
+    });
+    if let Err(e) = headless::run(queue, start_secs, &config.output, &config.dsp, receiver) {
+        log_error("Headless", &format!("Player stopped: {}", e));
+    }
+    session::flush();
+    log_info("Headless", "Headless player stopped");
+}
+
+/// Ctrl-C, or SIGTERM from a service manager stopping the daemon
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                log_warn("Headless", &format!("SIGTERM not handled: {}", e));
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
 pub fn run() {
     use std::time::Instant;
     
-    // Initialize logging system
-    let log_config = LoggerConfig::default();
+    // Initialize logging system with the persisted settings
+    let log_config = FileConfigManager::load()
+        .map(|config| config.logging.to_logger_config())
+        .unwrap_or_default();
     if let Err(e) = logging::init_logger(log_config) {
         eprintln!("Failed to initialize logger: {}", e);
     }
@@ -770,71 +3802,282 @@ pub fn run() {
     // Start tracking startup time
     let startup_start = Instant::now();
     performance::init_performance_tracking();
+    instrumentation::init();
+
+    let launch = cli::parse_args(
+        &std::env::args().skip(1).collect::<Vec<_>>(),
+        &std::env::current_dir().unwrap_or_default(),
+        cli::LaunchMode::Play,
+    );
+    if launch.headless {
+        return run_headless(launch);
+    }
     
     tauri::Builder::default()
+        // Must be registered first so a second launch exits before doing any work
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            use tauri::Manager;
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+            // Files opened while running join the queue instead of replacing it
+            let args = argv.into_iter().skip(1).collect();
+            handle_launch_args(app.clone(), args, std::path::PathBuf::from(cwd), cli::LaunchMode::Enqueue);
+        }))
         .plugin(tauri_plugin_opener::init())
+        .plugin(hotkeys::plugin())
         .setup(move |app| {
             // Record startup time once the app is ready
             let startup_duration = startup_start.elapsed();
             performance::record_startup_time(startup_duration);
             log_info("Startup", &format!("Application ready in {:?}", startup_duration));
-            
-            // Handle command-line arguments for file associations
-            if let Some(args) = std::env::args().nth(1) {
-                log_info("FileAssociation", &format!("Received file argument: {}", args));
-                
-                // Check if it's a skin file
-                if args.to_lowercase().ends_with(".wsz") || args.to_lowercase().ends_with(".wal") {
-                    log_info("FileAssociation", "Detected skin file, will load on frontend");
-                    
-                    // Emit event to frontend to load the skin
+            performance::start_snapshot_thread();
+
+            // Attach OS media controls (SMTC needs the main window handle on Windows)
+            #[cfg(target_os = "windows")]
+            let hwnd = {
+                use tauri::Manager;
+                app.get_webview_window("main").and_then(|w| w.hwnd().ok()).map(|h| h.0 as usize)
+            };
+            #[cfg(not(target_os = "windows"))]
+            let hwnd = None;
+            match MediaSession::start(app.handle().clone(), hwnd) {
+                Ok(session) => {
+                    let _ = MEDIA_SESSION.set(session);
+                }
+                Err(e) => log_warn("MediaSession", &e.to_string()),
+            }
+
+            // Taskbar progress and thumbnail transport buttons
+            #[cfg(target_os = "windows")]
+            taskbar::spawn(app.handle().clone(), &PLAYER_STATE, hwnd);
+
+            // Watch connectivity so streaming calls can fail fast while offline
+            connectivity::start(app.handle().clone());
+
+            // Back up playlists, ratings and settings on the configured schedule
+            let backup_events = app.handle().clone();
+            cloud_backup::start(get_kv_store(), move || {
+                let app = backup_events.clone();
+                async move {
+                    match run_backup().await {
+                        Ok(report) => {
+                            if let Err(e) = app.emit("cloud-backup", &report) {
+                                log_warn("CloudBackup", &format!("Failed to emit cloud-backup event: {}", e));
+                            }
+                        }
+                        Err(e) => log_warn("CloudBackup", &format!("Scheduled backup failed: {}", e)),
+                    }
+                }
+            });
+
+            // Track library roots on network shares and removable drives
+            let roots: Vec<std::path::PathBuf> = FileConfigManager::load()
+                .ok()
+                .and_then(|config| config.library_path)
+                .map(std::path::PathBuf::from)
+                .into_iter()
+                .collect();
+            let handle = app.handle().clone();
+            library_roots::start(app.handle().clone(), roots, move |root| rescan_library_root(handle.clone(), root));
+
+            // Restore window placement and start snapping/docking
+            let saved_windows = FileConfigManager::load().map(|config| config.windows).unwrap_or_default();
+            window_geometry::start(app.handle(), saved_windows);
+            session::start();
+
+            // Load enabled plugins off the main thread; compiling modules can take a moment
+            std::thread::spawn(|| {
+                plugins::start(get_kv_store());
+            });
+            let script_events = app.handle().clone();
+            scripting::start(scripting::ScriptApi {
+                store: get_kv_store(),
+                control: Arc::new(move |action| {
+                    if let Err(e) = script_events.emit("media-control", &action) {
+                        log_warn("Scripts", &format!("Failed to emit media-control event: {}", e));
+                    }
+                }),
+            });
+
+            // Broadcast external edits to config.json as `config-changed`
+            if let Err(e) = config_watcher::spawn(app.handle().clone()) {
+                log_warn("Config", &format!("Config file watching disabled: {}", e));
+            }
+
+            // Streaming sources go stale without reporting anything, so check on a timer
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let mut interval = tokio::time::interval(now_playing::STALE_CHECK_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Some(unified) = now_playing::expire_stale() {
+                        publish_now_playing(Some(&app_handle), &unified);
+                    }
+                }
+            });
+
+            // Register global hotkeys from the saved configuration
+            if let Ok(config) = FileConfigManager::load() {
+                if let Err(e) = hotkeys::apply(app.handle(), &config.hotkeys, true) {
+                    log_warn("Hotkeys", &format!("Global hotkeys not registered: {}", e));
+                }
+
+                if let Err(e) = tray::apply(app.handle(), &config.tray) {
+                    log_warn("Tray", &format!("Tray icon not created: {}", e));
+                }
+
+                now_playing::set_priority(&config.playback.source_priority);
+
+                if let Err(e) = api_client::configure_proxy(&config.proxy, &PlatformSecureStorage::new()) {
+                    log_warn("ApiClient", &format!("Proxy settings not applied: {}", e));
+                }
+
+                // Start the remote-control API if it was left enabled
+                if config.remote_api.enabled {
                     let app_handle = app.handle().clone();
                     tauri::async_runtime::spawn(async move {
-                        if let Err(e) = app_handle.emit("load-skin-file", args) {
-                            log_error("FileAssociation", &format!("Failed to emit load-skin-file event: {}", e));
+                        if let Err(e) = apply_remote_api(media_control_sink(app_handle), &config.remote_api).await {
+                            log_warn("RemoteApi", &format!("Remote API not started: {}", e));
                         }
                     });
                 }
             }
             
+            // Handle command-line arguments for file associations
+            let args: Vec<String> = std::env::args().skip(1).collect();
+            if !args.is_empty() {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                handle_launch_args(app.handle().clone(), args, cwd, cli::LaunchMode::Play);
+            }
+            
             Ok(())
         })
+        .on_window_event(|window, event| {
+            window_geometry::handle_window_event(window, event);
+            tray::handle_window_event(window, event);
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                handle_file_drop(window, paths.clone());
+            }
+        })
         .manage(system_audio::SystemAudioCaptureState(Arc::new(Mutex::new(SystemAudioCapture::new()))))
         .invoke_handler(tauri::generate_handler![
             greet,
             load_config,
             save_config,
+            get_setting,
+            set_setting,
+            set_log_level,
+            set_headless_output_mode,
+            set_dsp_chain,
+            list_output_devices,
+            set_log_rotation,
+            export_app_data,
+            import_app_data,
+            reload_hotkeys,
+            reload_tray,
+            get_window_geometry,
+            set_always_on_top,
+            set_window_shade,
             is_first_run,
+            get_setup_state,
+            complete_setup_step,
             validate_directory_path,
             store_credential,
             retrieve_credential,
             delete_credential,
             scan_library,
+            sort_tracks,
             extract_metadata,
+            extract_metadata_batch,
+            get_library_roots,
+            list_library_exclusions,
+            add_library_exclusion,
+            remove_library_exclusion,
+            find_library_duplicates,
+            rename_files_from_tags,
+            infer_tags_from_filenames,
+            apply_inferred_tags,
+            search_library,
+            group_library,
+            generate_waveform,
+            get_chapters,
+            seek_to_chapter,
+            analyze_track,
+            analyze_library,
+            analyze_silence,
+            get_track_rating,
+            set_track_rating,
+            get_track_note,
+            set_track_note,
+            get_artists,
+            get_albums,
+            get_album_tracks,
             extract_artwork,
+            set_track_artwork,
+            enqueue_folder,
             check_metadata_completeness,
             is_metadata_cached,
             clear_metadata_cache,
+            get_metadata_cache_stats,
             check_file_extension_supported,
             validate_audio_file,
             load_validated_config,
             test_internal_error_handling,
             create_playlist,
+            create_playlist_from_folder,
             list_playlists,
             load_playlist,
+            get_playlist_page,
+            get_playlist_summary,
             delete_playlist,
             add_track_to_playlist,
+            add_tracks_to_playlist,
             remove_track_from_playlist,
             reorder_playlist_tracks,
             update_playlist,
+            set_playlist_description,
+            undo_playlist_change,
+            redo_playlist_change,
+            verify_playlist_files,
+            relocate_missing_tracks,
             load_skin,
             apply_skin,
             get_skin_assets,
+            get_skin_sprites,
+            get_skin_fonts,
+            validate_skin_detailed,
+            package_skin,
+            import_eq_presets,
+            list_eq_presets,
+            import_from_winamp,
+            import_itunes_library,
+            import_from_player,
+            sync_to_device,
+            clear_skin_cache,
+            get_active_theme_colors,
+            museum_browse_skins,
+            museum_search_skins,
+            museum_download_skin,
+            get_connectivity_status,
+            set_proxy_password,
+            backup_now,
+            set_backup_secret,
             spotify_authenticate,
             spotify_get_now_playing,
             spotify_refresh_token,
             spotify_check_token_expired,
             spotify_ensure_valid_token,
+            spotify_get_audio_features,
+            spotify_get_recommendations,
+            export_playlist_to_spotify,
+            match_track,
+            bandcamp_sign_in,
+            bandcamp_sign_out,
+            bandcamp_list_purchases,
+            bandcamp_stream_tracks,
+            bandcamp_download,
             youtube_authenticate,
             youtube_get_now_playing,
             youtube_refresh_token,
@@ -844,20 +4087,82 @@ pub fn run() {
             youtube_get_api_key,
             youtube_validate_api_key,
             youtube_get_video_metadata,
+            list_presets,
+            load_preset,
+            next_preset,
+            prev_preset,
+            set_preset_shuffle,
+            update_media_session,
+            get_unified_now_playing,
+            reload_remote_api,
+            get_remote_api_status,
+            get_remote_api_token,
+            regenerate_remote_api_token,
+            update_remote_queue,
+            update_session,
+            add_bookmark,
+            list_bookmarks,
+            remove_bookmark,
+            get_resume_position,
+            clear_resume_position,
+            restore_session,
+            register_file_associations,
+            unregister_file_associations,
+            set_sleep_timer,
+            cancel_sleep_timer,
+            get_sleep_timer,
+            list_plugins,
+            set_plugin_enabled,
+            reload_plugins,
+            list_scripts,
+            reload_scripts,
+            list_winamp_plugins,
+            start_winamp_vis,
+            stop_winamp_vis,
+            get_winamp_vis,
+            feed_player_audio,
+            set_alarm,
+            cancel_alarm,
+            list_alarms,
+            get_view_sort,
+            set_view_sort,
             get_performance_metrics,
             get_cache_hit_rate,
             get_memory_usage,
             get_peak_memory,
-            get_error_category,
-            is_error_critical,
+            get_performance_history,
+            get_metrics_history,
+            export_metrics_history,
+            get_command_timings,
+            get_recent_logs,
+            get_error_stats,
             is_error_recoverable,
             crop_image_command,
             probe_video_metadata_command,
             trim_and_crop_video_command,
+            probe_audio_metadata_command,
+            convert_audio_command,
+            trim_audio_command,
+            export_audio_files_command,
+            generate_preview_clip_command,
+            cancel_job,
+            list_jobs,
+            list_export_presets,
+            save_custom_preset_command,
+            list_audio_capture_devices,
             start_system_audio_capture,
             stop_system_audio_capture,
-            is_system_audio_capture_active
+            is_system_audio_capture_active,
+            set_capture_options,
+            get_capture_options,
+            read_capture_buffer
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                session::flush();
+                winamp_vis::stop();
+            }
+        });
 }
@@ -1,13 +1,19 @@
+use crate::library_filter::LibraryFilter;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Track data model representing an audio file in the library
+///
+/// `file_path` keeps the exact OS path; names that are not valid Unicode are
+/// serialized with `path_codec` so they survive the trip to the frontend.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Track {
     pub id: String,
-    pub file_path: String,
+    #[serde(with = "crate::path_codec")]
+    pub file_path: PathBuf,
     pub file_name: String,
     pub extension: String,
 }
@@ -16,6 +22,7 @@ pub struct Track {
 pub enum ScanError {
     IoError(io::Error),
     InvalidPath,
+    Cancelled,
 }
 
 impl From<io::Error> for ScanError {
@@ -29,6 +36,7 @@ impl std::fmt::Display for ScanError {
         match self {
             ScanError::IoError(e) => write!(f, "IO error: {}", e),
             ScanError::InvalidPath => write!(f, "Invalid path"),
+            ScanError::Cancelled => write!(f, "Scan cancelled"),
         }
     }
 }
@@ -40,10 +48,21 @@ pub struct LibraryScanner;
 
 impl LibraryScanner {
     /// Supported audio file extensions
-    const SUPPORTED_EXTENSIONS: &'static [&'static str] = &["mp3", "flac", "wav"];
+    const SUPPORTED_EXTENSIONS: &'static [&'static str] = &["mp3", "flac", "wav", "m4a", "m4b"];
 
     /// Scan a directory recursively for audio files
     pub fn scan_directory(path: &Path) -> Result<Vec<Track>, ScanError> {
+        Self::scan_directory_filtered(path, &LibraryFilter::default())
+    }
+
+    /// Scan a directory recursively, skipping what `filter` excludes
+    pub fn scan_directory_filtered(path: &Path, filter: &LibraryFilter) -> Result<Vec<Track>, ScanError> {
+        Self::scan_directory_cancellable(path, filter, &AtomicBool::new(false))
+    }
+
+    /// Scan like `scan_directory_filtered`, stopping with `ScanError::Cancelled`
+    /// before the next folder once `cancel` is set
+    pub fn scan_directory_cancellable(path: &Path, filter: &LibraryFilter, cancel: &AtomicBool) -> Result<Vec<Track>, ScanError> {
         if !path.exists() {
             return Err(ScanError::InvalidPath);
         }
@@ -53,12 +72,15 @@ impl LibraryScanner {
         }
 
         let mut tracks = Vec::new();
-        Self::scan_recursive(path, &mut tracks)?;
+        Self::scan_recursive(path, path, filter, cancel, &mut tracks)?;
         Ok(tracks)
     }
 
     /// Recursive helper function for directory traversal
-    fn scan_recursive(path: &Path, tracks: &mut Vec<Track>) -> Result<(), ScanError> {
+    fn scan_recursive(root: &Path, path: &Path, filter: &LibraryFilter, cancel: &AtomicBool, tracks: &mut Vec<Track>) -> Result<(), ScanError> {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(ScanError::Cancelled);
+        }
         let entries = fs::read_dir(path)?;
 
         for entry in entries {
@@ -66,13 +88,15 @@ impl LibraryScanner {
             let entry_path = entry.path();
 
             if entry_path.is_dir() {
-                // Recursively scan subdirectories
-                Self::scan_recursive(&entry_path, tracks)?;
+                // Recursively scan subdirectories that are not excluded
+                if !filter.excludes_path(root, &entry_path) {
+                    Self::scan_recursive(root, &entry_path, filter, cancel, tracks)?;
+                }
             } else if entry_path.is_file() {
                 // Check if file has supported extension
                 if let Some(extension) = entry_path.extension() {
                     let ext_str = extension.to_string_lossy().to_lowercase();
-                    if Self::is_supported_extension(&ext_str) {
+                    if Self::is_supported_extension(&ext_str) && !filter.excludes_file(root, &entry_path) {
                         // Create track from file
                         if let Some(track) = Self::create_track(&entry_path) {
                             tracks.push(track);
@@ -86,24 +110,24 @@ impl LibraryScanner {
     }
 
     /// Create a Track from a file path
-    fn create_track(path: &Path) -> Option<Track> {
-        let file_path = path.to_string_lossy().to_string();
+    pub(crate) fn create_track(path: &Path) -> Option<Track> {
+        // The name is only for display, so a lossy conversion is fine there
         let file_name = path.file_name()?.to_string_lossy().to_string();
         let extension = path.extension()?.to_string_lossy().to_lowercase();
 
         // Generate a simple ID from the file path
-        let id = Self::generate_id(&file_path);
+        let id = Self::generate_id(&crate::path_codec::encode(path));
 
         Some(Track {
             id,
-            file_path,
+            file_path: path.to_path_buf(),
             file_name,
             extension,
         })
     }
 
     /// Generate a unique ID for a track based on its file path
-    fn generate_id(file_path: &str) -> String {
+    pub(crate) fn generate_id(file_path: &str) -> String {
         // Simple hash-like ID generation using the file path
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
@@ -175,6 +199,43 @@ mod tests {
         assert_eq!(tracks.len(), 2);
     }
 
+    #[test]
+    fn test_scan_skips_excluded_folders() {
+        let temp_dir = TempDir::new().unwrap();
+        let podcasts = temp_dir.path().join("Podcasts");
+        fs::create_dir(&podcasts).unwrap();
+        fs::write(podcasts.join("episode.mp3"), b"fake mp3 data").unwrap();
+        fs::write(temp_dir.path().join("song.mp3"), b"fake mp3 data").unwrap();
+        fs::write(temp_dir.path().join("memo.wav"), b"fake wav data").unwrap();
+
+        let filter = LibraryFilter::from_settings(&crate::config::LibraryFilterSettings {
+            exclusions: vec!["podcasts".to_string(), "memo.*".to_string()],
+            hide_explicit: true,
+        });
+        let tracks = LibraryScanner::scan_directory_filtered(temp_dir.path(), &filter).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].file_name, "song.mp3");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_keeps_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let temp_dir = TempDir::new().unwrap();
+        let latin1 = temp_dir.path().join(std::ffi::OsStr::from_bytes(b"Caf\xe9.mp3"));
+        let other = temp_dir.path().join(std::ffi::OsStr::from_bytes(b"Caf\xe8.mp3"));
+        fs::write(&latin1, b"fake mp3 data").unwrap();
+        fs::write(&other, b"fake mp3 data").unwrap();
+
+        let tracks = LibraryScanner::scan_directory(temp_dir.path()).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_ne!(tracks[0].id, tracks[1].id);
+        let track = tracks.iter().find(|t| t.file_path == latin1).unwrap();
+        let json = serde_json::to_string(track).unwrap();
+        let restored: Track = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.file_path, latin1);
+    }
+
     #[test]
     fn test_scan_invalid_path() {
         let result = LibraryScanner::scan_directory(Path::new("/nonexistent/path"));
@@ -187,6 +248,7 @@ mod tests {
         assert!(LibraryScanner::is_supported_extension("MP3"));
         assert!(LibraryScanner::is_supported_extension("flac"));
         assert!(LibraryScanner::is_supported_extension("wav"));
+        assert!(LibraryScanner::is_supported_extension("m4b"));
         assert!(!LibraryScanner::is_supported_extension("jpg"));
         assert!(!LibraryScanner::is_supported_extension("txt"));
     }
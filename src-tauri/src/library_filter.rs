@@ -0,0 +1,105 @@
+// Library exclusion rules
+// Glob patterns and the explicit-content tag keep things like podcast or voice
+// memo folders out of the library. The rules live in config under
+// `library_filters` and are applied while scanning, so the search index and
+// browse views built from a scan never see excluded files.
+
+use crate::config::LibraryFilterSettings;
+use crate::logging::log_warn;
+use crate::metadata;
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Normalize a user-entered pattern; `None` if it is empty
+///
+/// Backslashes become forward slashes and a leading or trailing slash is
+/// dropped, so `Podcasts/`, `/Podcasts` and `Podcasts` mean the same thing.
+pub fn normalize_pattern(pattern: &str) -> Option<String> {
+    let pattern = pattern.trim().replace('\\', "/");
+    let pattern = pattern.trim_matches('/');
+    (!pattern.is_empty()).then(|| pattern.to_string())
+}
+
+/// Compiled exclusion rules
+///
+/// A pattern without a slash is matched against every file and folder name,
+/// like `Podcasts` or `*.m4b`; one with a slash is matched against the path
+/// relative to the library root, like `Audiobooks/**/Drafts`. An excluded
+/// folder is not descended into.
+#[derive(Debug, Default)]
+pub struct LibraryFilter {
+    names: Vec<Pattern>,
+    paths: Vec<Pattern>,
+    hide_explicit: bool,
+}
+
+impl LibraryFilter {
+    pub fn from_settings(settings: &LibraryFilterSettings) -> Self {
+        let mut filter = LibraryFilter { hide_explicit: settings.hide_explicit, ..Default::default() };
+        for pattern in settings.exclusions.iter().filter_map(|p| normalize_pattern(p)) {
+            match Pattern::new(&pattern) {
+                Ok(compiled) if pattern.contains('/') => filter.paths.push(compiled),
+                Ok(compiled) => filter.names.push(compiled),
+                Err(e) => log_warn("Library", &format!("Ignoring invalid exclusion '{}': {}", pattern, e)),
+            }
+        }
+        filter
+    }
+
+    /// Whether a pattern excludes the file or folder at `path` under `root`
+    pub fn excludes_path(&self, root: &Path, path: &Path) -> bool {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if self.names.iter().any(|p| p.matches_with(&name, MATCH_OPTIONS)) {
+            return true;
+        }
+        if self.paths.is_empty() {
+            return false;
+        }
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let relative: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        let relative = relative.join("/");
+        self.paths.iter().any(|p| p.matches_with(&relative, MATCH_OPTIONS))
+    }
+
+    /// Whether an audio file is excluded by a pattern or its explicit-content tag
+    pub fn excludes_file(&self, root: &Path, path: &Path) -> bool {
+        self.excludes_path(root, path) || (self.hide_explicit && metadata::has_explicit_flag(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(exclusions: &[&str]) -> LibraryFilter {
+        LibraryFilter::from_settings(&LibraryFilterSettings {
+            exclusions: exclusions.iter().map(|p| p.to_string()).collect(),
+            hide_explicit: false,
+        })
+    }
+
+    #[test]
+    fn test_name_and_path_patterns() {
+        let root = Path::new("/music");
+        let filter = filter(&["podcasts", "*.M4B", "Audiobooks/**/Drafts/", "  "]);
+        assert!(filter.excludes_path(root, Path::new("/music/Podcasts")));
+        assert!(filter.excludes_path(root, Path::new("/music/Talk/Podcasts")));
+        assert!(filter.excludes_path(root, Path::new("/music/book.m4b")));
+        assert!(filter.excludes_path(root, Path::new("/music/Audiobooks/Fiction/Drafts")));
+        assert!(!filter.excludes_path(root, Path::new("/music/Drafts")));
+        assert!(!filter.excludes_path(root, Path::new("/music/Podcasts Mix.mp3")));
+        assert!(!LibraryFilter::default().excludes_path(root, Path::new("/music/Podcasts")));
+    }
+
+    #[test]
+    fn test_normalize_pattern() {
+        assert_eq!(normalize_pattern(" \\Voice Memos\\ "), Some("Voice Memos".to_string()));
+        assert_eq!(normalize_pattern("/"), None);
+    }
+}
@@ -0,0 +1,268 @@
+// Server-side ordering and grouping of library query results
+// Search hits can be sorted by tag or file fields and grouped by album, artist
+// or folder in the backend, so the frontend never re-sorts the whole library.
+// Album-related orderings keep tracks in disc/track order within each album.
+
+use crate::browse::{album_id, UNKNOWN_ALBUM, UNKNOWN_ARTIST};
+use crate::collation::Collator;
+use crate::kv_store::KvStore;
+use crate::search::SearchHit;
+use crate::sorting::compare_disc_track;
+use crate::system_playlists::date_added;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuerySortKey {
+    /// Search score, as `search_library` ranks by default
+    #[default]
+    Relevance,
+    Title,
+    /// Artist, then album, then disc/track
+    Artist,
+    /// Album, then artist, then disc/track
+    Album,
+    /// Year, then album, then disc/track
+    Year,
+    DateAdded,
+    Duration,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct QuerySort {
+    pub key: QuerySortKey,
+    /// Reverses the main key only; tie-breaks such as disc/track stay ascending
+    pub descending: bool,
+    /// BCP 47 locale for text ordering; the system locale when unset
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    Album,
+    Artist,
+    Folder,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HitGroup {
+    /// Album id (as used by `get_album_tracks`), lowercased artist, or folder path
+    pub key: String,
+    /// Album title, artist name, or folder path relative to the library root
+    pub title: String,
+    /// Album artist, for album groups
+    pub artist: Option<String>,
+    pub hits: Vec<SearchHit>,
+}
+
+fn artist_of(hit: &SearchHit) -> &str {
+    hit.metadata.as_ref().and_then(|m| m.artist.as_deref()).unwrap_or(UNKNOWN_ARTIST)
+}
+
+fn album_of(hit: &SearchHit) -> &str {
+    hit.metadata.as_ref().and_then(|m| m.album.as_deref()).unwrap_or(UNKNOWN_ALBUM)
+}
+
+fn title_of(hit: &SearchHit) -> &str {
+    hit.metadata.as_ref().and_then(|m| m.title.as_deref()).unwrap_or(&hit.track.file_name)
+}
+
+fn year_of(hit: &SearchHit) -> Option<u32> {
+    hit.metadata.as_ref().and_then(|m| m.year)
+}
+
+fn disc_track_of(hit: &SearchHit) -> (Option<u32>, Option<u32>) {
+    let metadata = hit.metadata.as_ref();
+    (metadata.and_then(|m| m.disc_number), metadata.and_then(|m| m.track_number))
+}
+
+/// Compare present values, with missing ones last either way
+fn compare_present<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Album order within a key: album, then disc/track, then title
+fn compare_in_album(a: &SearchHit, b: &SearchHit, collator: &Collator) -> Ordering {
+    collator
+        .compare(album_of(a), album_of(b))
+        .then_with(|| compare_disc_track(disc_track_of(a), disc_track_of(b)))
+        .then_with(|| collator.compare(title_of(a), title_of(b)))
+}
+
+/// Reorder hits by `sort`; relevance keeps the ranked order
+pub fn sort_hits(hits: &mut Vec<SearchHit>, sort: &QuerySort, store: &KvStore) {
+    let collator = Collator::shared(sort.locale.as_deref(), true);
+    let directed = |ordering: Ordering| if sort.descending { ordering.reverse() } else { ordering };
+    match sort.key {
+        QuerySortKey::Relevance => {
+            if sort.descending {
+                hits.reverse();
+            }
+        }
+        QuerySortKey::Title => hits.sort_by(|a, b| directed(collator.compare(title_of(a), title_of(b)))),
+        QuerySortKey::Artist => hits.sort_by(|a, b| {
+            directed(collator.compare(artist_of(a), artist_of(b))).then_with(|| compare_in_album(a, b, &collator))
+        }),
+        QuerySortKey::Album => hits.sort_by(|a, b| {
+            directed(collator.compare(album_of(a), album_of(b)))
+                .then_with(|| collator.compare(artist_of(a), artist_of(b)))
+                .then_with(|| compare_in_album(a, b, &collator))
+        }),
+        QuerySortKey::Year => hits.sort_by(|a, b| {
+            compare_present(year_of(a), year_of(b), sort.descending).then_with(|| compare_in_album(a, b, &collator))
+        }),
+        QuerySortKey::Duration => hits.sort_by(|a, b| {
+            let duration = |hit: &SearchHit| hit.metadata.as_ref().and_then(|m| m.duration);
+            compare_present(duration(a), duration(b), sort.descending)
+                .then_with(|| collator.compare(title_of(a), title_of(b)))
+        }),
+        QuerySortKey::DateAdded => {
+            // One stat per file rather than one per comparison
+            let mut keyed: Vec<(Option<DateTime<Utc>>, SearchHit)> =
+                hits.drain(..).map(|hit| (date_added(store, &hit.track.file_path), hit)).collect();
+            keyed.sort_by(|(a_added, a), (b_added, b)| {
+                compare_present(*a_added, *b_added, sort.descending)
+                    .then_with(|| collator.compare(title_of(a), title_of(b)))
+            });
+            hits.extend(keyed.into_iter().map(|(_, hit)| hit));
+        }
+    }
+}
+
+/// Split hits into groups, keeping their order within each group
+///
+/// Album groups are listed by artist, year and title, with tracks in
+/// disc/track order; artist and folder groups are listed by name.
+pub fn group_hits(root: &Path, hits: Vec<SearchHit>, group_by: GroupBy, locale: Option<&str>) -> Vec<HitGroup> {
+    let collator = Collator::shared(locale, true);
+    let mut groups: Vec<HitGroup> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for hit in hits {
+        let (key, title, artist) = match group_by {
+            GroupBy::Album => {
+                let (artist, album) = (artist_of(&hit), album_of(&hit));
+                (album_id(artist, album), album.to_string(), Some(artist.to_string()))
+            }
+            GroupBy::Artist => (artist_of(&hit).to_lowercase(), artist_of(&hit).to_string(), None),
+            GroupBy::Folder => {
+                let folder = hit.track.file_path.parent().unwrap_or(root);
+                let relative = folder.strip_prefix(root).unwrap_or(folder);
+                (crate::path_codec::encode(folder), relative.to_string_lossy().to_string(), None)
+            }
+        };
+        let position = *positions.entry(key.clone()).or_insert_with(|| {
+            groups.push(HitGroup { key, title, artist, hits: Vec::new() });
+            groups.len() - 1
+        });
+        groups[position].hits.push(hit);
+    }
+
+    match group_by {
+        GroupBy::Album => {
+            let year = |group: &HitGroup| group.hits.iter().filter_map(year_of).min();
+            groups.sort_by(|a, b| {
+                collator
+                    .compare(a.artist.as_deref().unwrap_or_default(), b.artist.as_deref().unwrap_or_default())
+                    .then_with(|| compare_present(year(a), year(b), false))
+                    .then_with(|| collator.compare(&a.title, &b.title))
+            });
+            for group in &mut groups {
+                group.hits.sort_by(|a, b| compare_disc_track(disc_track_of(a), disc_track_of(b)));
+            }
+        }
+        GroupBy::Artist | GroupBy::Folder => groups.sort_by(|a, b| collator.compare(&a.title, &b.title)),
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::Track;
+    use crate::metadata::TrackMetadata;
+    use crate::ratings::TrackRating;
+    use std::path::PathBuf;
+
+    fn hit(path: &str, artist: &str, album: &str, year: Option<u32>, disc_track: (u32, u32)) -> SearchHit {
+        let file_path = PathBuf::from(path);
+        SearchHit {
+            track: Track {
+                id: path.to_string(),
+                file_name: file_path.file_name().unwrap().to_string_lossy().to_string(),
+                file_path,
+                extension: "mp3".to_string(),
+            },
+            metadata: Some(TrackMetadata {
+                title: None,
+                artist: Some(artist.to_string()),
+                album: Some(album.to_string()),
+                year,
+                genre: None,
+                track_number: Some(disc_track.1),
+                disc_number: Some(disc_track.0),
+                duration: None,
+                bpm: None,
+                key: None,
+                comment: None,
+            }),
+            rating: TrackRating::default(),
+            score: 0.0,
+            silence: None,
+            note: None,
+        }
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.track.id.as_str()).collect()
+    }
+
+    fn library() -> Vec<SearchHit> {
+        vec![
+            hit("/m/Zombies/Odessey/02.mp3", "The Zombies", "Odessey", Some(1968), (1, 2)),
+            hit("/m/Abba/Gold/2-01.mp3", "ABBA", "Gold", Some(1992), (2, 1)),
+            hit("/m/Abba/Gold/1-10.mp3", "ABBA", "Gold", Some(1992), (1, 10)),
+            hit("/m/Abba/Arrival/01.mp3", "ABBA", "Arrival", None, (1, 1)),
+            hit("/m/Zombies/Odessey/01.mp3", "The Zombies", "Odessey", Some(1968), (1, 1)),
+        ]
+    }
+
+    #[test]
+    fn test_sort_keeps_disc_track_order() {
+        let mut hits = library();
+        sort_hits(&mut hits, &QuerySort { key: QuerySortKey::Artist, descending: true, locale: Some("en".into()) }, &KvStore::in_memory());
+        assert_eq!(
+            ids(&hits),
+            ["/m/Zombies/Odessey/01.mp3", "/m/Zombies/Odessey/02.mp3", "/m/Abba/Arrival/01.mp3", "/m/Abba/Gold/1-10.mp3", "/m/Abba/Gold/2-01.mp3"]
+        );
+
+        sort_hits(&mut hits, &QuerySort { key: QuerySortKey::Year, descending: false, locale: None }, &KvStore::in_memory());
+        assert_eq!(ids(&hits)[..2], ["/m/Zombies/Odessey/01.mp3", "/m/Zombies/Odessey/02.mp3"]);
+        assert_eq!(ids(&hits)[4], "/m/Abba/Arrival/01.mp3");
+    }
+
+    #[test]
+    fn test_group_by_album_and_folder() {
+        let groups = group_hits(Path::new("/m"), library(), GroupBy::Album, Some("en"));
+        let titles: Vec<&str> = groups.iter().map(|g| g.title.as_str()).collect();
+        assert_eq!(titles, ["Gold", "Arrival", "Odessey"]);
+        assert_eq!(groups[0].key, album_id("ABBA", "Gold"));
+        assert_eq!(ids(&groups[0].hits), ["/m/Abba/Gold/1-10.mp3", "/m/Abba/Gold/2-01.mp3"]);
+
+        let groups = group_hits(Path::new("/m"), library(), GroupBy::Folder, None);
+        let titles: Vec<&str> = groups.iter().map(|g| g.title.as_str()).collect();
+        assert_eq!(titles, ["Abba/Arrival", "Abba/Gold", "Zombies/Odessey"]);
+        assert_eq!(ids(&groups[2].hits), ["/m/Zombies/Odessey/02.mp3", "/m/Zombies/Odessey/01.mp3"]);
+    }
+}
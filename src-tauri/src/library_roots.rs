@@ -0,0 +1,277 @@
+// Library roots on network shares and removable drives
+// A library on a NAS or USB drive can disappear at any time, and touching a
+// dead network mount can block for a minute or more. Roots on such volumes
+// are probed on a separate thread with a timeout before scanning; one that
+// does not answer is marked offline, skipped, and reported as
+// `library-root-offline`. A background monitor keeps probing offline roots
+// and, once the volume is back, emits `library-root-online` and asks for a
+// rescan. Roots on local disks are scanned directly as before.
+
+use crate::error::MilkError;
+use crate::logging::{log_info, log_warn};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// How long a root may take to list before it counts as offline
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+const MONITOR_INTERVAL: Duration = Duration::from_secs(15);
+
+static ROOTS: Mutex<BTreeMap<PathBuf, RootStatus>> = Mutex::new(BTreeMap::new());
+
+// Roots whose last probe is still stuck in the file system; no second probe
+// thread is started for them until it returns
+static PENDING: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+static MONITOR: OnceLock<Monitor> = OnceLock::new();
+
+/// Where a library root lives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeKind {
+    Local,
+    Network,
+    /// USB sticks, SD cards, optical media and other external drives
+    Removable,
+}
+
+/// Payload of `library-root-offline` and `library-root-online`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RootStatus {
+    pub path: String,
+    pub kind: VolumeKind,
+    pub online: bool,
+}
+
+struct Monitor {
+    app: AppHandle,
+    on_remount: Box<dyn Fn(PathBuf) + Send + Sync>,
+}
+
+/// Tracked roots and whether each was reachable at the last probe
+pub fn statuses() -> Vec<RootStatus> {
+    ROOTS.lock().unwrap().values().cloned().collect()
+}
+
+/// Check that `root` can be scanned without hanging
+///
+/// Roots on network or removable volumes are probed and tracked; an
+/// unreachable one fails with `MilkError::VolumeUnavailable` so the scan is
+/// skipped. Local roots always pass and are left to the scanner.
+pub fn ensure_available(root: &Path) -> Result<(), MilkError> {
+    let known = ROOTS.lock().unwrap().get(root).map(|status| status.kind);
+    // Keep the first detected kind, since an unplugged drive may no longer look removable
+    let kind = known.unwrap_or_else(|| volume_kind(root));
+    if kind == VolumeKind::Local {
+        return Ok(());
+    }
+    if update(root, kind, probe(root)) {
+        Ok(())
+    } else {
+        Err(MilkError::VolumeUnavailable(root.display().to_string()))
+    }
+}
+
+/// Record a probe result, emitting an event when the root changes state
+///
+/// Returns whether the root is online.
+fn update(root: &Path, kind: VolumeKind, online: bool) -> bool {
+    let status = RootStatus { path: root.display().to_string(), kind, online };
+    let previous = ROOTS.lock().unwrap().insert(root.to_path_buf(), status.clone());
+    let was_online = previous.map(|previous| previous.online);
+    if was_online == Some(online) || (was_online.is_none() && online) {
+        return online;
+    }
+
+    let event = if online { "library-root-online" } else { "library-root-offline" };
+    if online {
+        log_info("Library", &format!("Library root is back online: {}", status.path));
+    } else {
+        log_warn("Library", &format!("Library root is offline, skipping it: {}", status.path));
+    }
+    if let Some(monitor) = MONITOR.get() {
+        if let Err(e) = monitor.app.emit(event, &status) {
+            log_warn("Library", &format!("Failed to emit {} event: {}", event, e));
+        }
+        if online {
+            (monitor.on_remount)(root.to_path_buf());
+        }
+    }
+    online
+}
+
+/// Whether `root` can be listed within `PROBE_TIMEOUT`
+///
+/// The listing runs on its own thread because a hung network mount can block
+/// for far longer than the timeout; that thread is abandoned if it does not
+/// answer in time.
+fn probe(root: &Path) -> bool {
+    if !PENDING.lock().unwrap().insert(root.to_path_buf()) {
+        return false;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let path = root.to_path_buf();
+    let spawned = std::thread::Builder::new().name("library-root-probe".into()).spawn(move || {
+        let reachable = std::fs::read_dir(&path).is_ok();
+        PENDING.lock().unwrap().remove(&path);
+        let _ = tx.send(reachable);
+    });
+    if let Err(e) = spawned {
+        log_warn("Library", &format!("Failed to probe library root: {}", e));
+        PENDING.lock().unwrap().remove(root);
+        return false;
+    }
+    rx.recv_timeout(PROBE_TIMEOUT).unwrap_or(false)
+}
+
+/// Start watching the tracked roots, seeded with `roots`
+///
+/// `on_remount` runs on the monitor thread whenever an offline root comes
+/// back. Roots on local disks are ignored.
+pub fn start(app: AppHandle, roots: Vec<PathBuf>, on_remount: impl Fn(PathBuf) + Send + Sync + 'static) {
+    if MONITOR.set(Monitor { app, on_remount: Box::new(on_remount) }).is_err() {
+        return;
+    }
+    let spawned = std::thread::Builder::new().name("library-roots".into()).spawn(move || {
+        for root in roots {
+            let _ = ensure_available(&root);
+        }
+        loop {
+            std::thread::sleep(MONITOR_INTERVAL);
+            let tracked: Vec<(PathBuf, VolumeKind)> = ROOTS
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(path, status)| (path.clone(), status.kind))
+                .collect();
+            for (root, kind) in tracked {
+                update(&root, kind, probe(&root));
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        log_warn("Library", &format!("Library root monitor not started: {}", e));
+    }
+}
+
+/// Classify the volume `path` lives on
+#[cfg(target_os = "windows")]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    use std::path::{Component, Prefix};
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDriveTypeW;
+
+    // Drive types from winbase.h
+    const DRIVE_NO_ROOT_DIR: u32 = 1;
+    const DRIVE_REMOVABLE: u32 = 2;
+    const DRIVE_REMOTE: u32 = 4;
+    const DRIVE_CDROM: u32 = 5;
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return VolumeKind::Local;
+    };
+    let letter = match prefix.kind() {
+        Prefix::UNC(..) | Prefix::VerbatimUNC(..) => return VolumeKind::Network,
+        Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => letter,
+        _ => return VolumeKind::Local,
+    };
+    let root: Vec<u16> = format!("{}:\\", letter as char).encode_utf16().chain(Some(0)).collect();
+    match unsafe { GetDriveTypeW(PCWSTR(root.as_ptr())) } {
+        DRIVE_REMOTE => VolumeKind::Network,
+        // A drive letter with nothing behind it is an unplugged drive
+        DRIVE_REMOVABLE | DRIVE_CDROM | DRIVE_NO_ROOT_DIR => VolumeKind::Removable,
+        _ => VolumeKind::Local,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    // Everything but the boot volume, shares included, is mounted under /Volumes
+    if path.starts_with("/Volumes") {
+        VolumeKind::Removable
+    } else {
+        VolumeKind::Local
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn volume_kind(path: &Path) -> VolumeKind {
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    kind_from_mounts(path, &mounts)
+}
+
+/// Network file systems as they appear in /proc/mounts
+#[cfg_attr(any(target_os = "windows", target_os = "macos"), allow(dead_code))]
+const NETWORK_FS: [&str; 9] = ["nfs", "nfs4", "cifs", "smb3", "smbfs", "fuse.sshfs", "9p", "afs", "davfs"];
+
+/// Where desktop environments mount external drives
+#[cfg_attr(any(target_os = "windows", target_os = "macos"), allow(dead_code))]
+const REMOVABLE_MOUNT_DIRS: [&str; 3] = ["/media", "/run/media", "/mnt"];
+
+/// Classify `path` from the contents of /proc/mounts
+///
+/// The longest mount point containing `path` decides. Paths under the usual
+/// external mount directories count as removable even when nothing is
+/// mounted there, so an unplugged drive is still recognized.
+#[cfg_attr(any(target_os = "windows", target_os = "macos"), allow(dead_code))]
+fn kind_from_mounts(path: &Path, mounts: &str) -> VolumeKind {
+    let fs_type = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            // Spaces in mount points are escaped as \040
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            Some((PathBuf::from(mount_point), fs_type))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, fs_type)| fs_type);
+
+    if fs_type.is_some_and(|fs_type| NETWORK_FS.contains(&fs_type)) {
+        VolumeKind::Network
+    } else if REMOVABLE_MOUNT_DIRS.iter().any(|dir| path.starts_with(dir)) {
+        VolumeKind::Removable
+    } else {
+        VolumeKind::Local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "\
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+nas:/export/music /srv/music nfs4 rw,relatime 0 0
+//nas/Shared\\040Music /home/me/Shared\\040Music cifs rw 0 0
+/dev/sdb1 /run/media/me/USB vfat rw 0 0
+";
+
+    #[test]
+    fn test_kind_from_mounts() {
+        assert_eq!(kind_from_mounts(Path::new("/home/me/Music"), MOUNTS), VolumeKind::Local);
+        assert_eq!(kind_from_mounts(Path::new("/srv/music/Albums"), MOUNTS), VolumeKind::Network);
+        assert_eq!(kind_from_mounts(Path::new("/home/me/Shared Music"), MOUNTS), VolumeKind::Network);
+        assert_eq!(kind_from_mounts(Path::new("/run/media/me/USB/Music"), MOUNTS), VolumeKind::Removable);
+        // Unplugged, so no longer in the mount table
+        assert_eq!(kind_from_mounts(Path::new("/media/me/SDCARD"), MOUNTS), VolumeKind::Removable);
+    }
+
+    #[test]
+    fn test_offline_root_is_tracked_and_skipped() {
+        let root = std::env::temp_dir().join("milk-library-roots-missing");
+        assert!(!probe(&root));
+        assert!(!update(&root, VolumeKind::Removable, false));
+        assert!(statuses().iter().any(|status| status.path == root.display().to_string() && !status.online));
+        assert!(matches!(ensure_available(&root), Err(MilkError::VolumeUnavailable(_))));
+        assert!(ensure_available(&std::env::temp_dir()).is_ok());
+    }
+}
@@ -1,16 +1,23 @@
 // Logging system with file rotation and size limits
+use std::collections::{HashMap, VecDeque};
 use std::fs::{self, File, OpenOptions};
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
 use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+/// Number of recent entries kept in memory for `get_recent_logs`
+const RECENT_CAPACITY: usize = 500;
 
 /// Log levels for the application
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
     Error = 0,
     Warn = 1,
     Info = 2,
+    Debug = 3,
 }
 
 impl LogLevel {
@@ -19,15 +26,30 @@ impl LogLevel {
             LogLevel::Error => "ERROR",
             LogLevel::Warn => "WARN",
             LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
         }
     }
 }
 
+/// Log file line format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// `[timestamp] [LEVEL] [Category] message`
+    #[default]
+    Text,
+    /// One JSON-encoded `LogEntry` per line
+    JsonLines,
+}
+
 /// Logger configuration
+#[derive(Debug, Clone)]
 pub struct LoggerConfig {
     pub max_file_size: u64,  // Maximum log file size in bytes (default: 10MB)
     pub max_files: usize,     // Maximum number of rotated log files to keep (default: 5)
     pub min_level: LogLevel,  // Minimum log level to record (default: Info)
+    pub format: LogFormat,    // Log file line format (default: Text)
+    pub category_levels: HashMap<String, LogLevel>, // Per-category overrides of min_level
 }
 
 impl Default for LoggerConfig {
@@ -36,22 +58,58 @@ impl Default for LoggerConfig {
             max_file_size: 10 * 1024 * 1024, // 10MB
             max_files: 5,
             min_level: LogLevel::Info,
+            format: LogFormat::Text,
+            category_levels: HashMap::new(),
+        }
+    }
+}
+
+impl LoggerConfig {
+    /// Minimum level recorded for `category`
+    fn level_for(&self, category: &str) -> LogLevel {
+        self.category_levels.get(category).copied().unwrap_or(self.min_level)
+    }
+}
+
+/// A recorded log line
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LogEntry {
+    /// RFC 3339 local time with milliseconds
+    pub timestamp: String,
+    pub level: LogLevel,
+    pub category: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    fn format(&self, format: LogFormat) -> String {
+        match format {
+            LogFormat::Text => format!("[{}] [{}] [{}] {}\n", self.timestamp, self.level.as_str(), self.category, self.message),
+            LogFormat::JsonLines => {
+                let mut line = serde_json::to_string(self).unwrap_or_default();
+                line.push('\n');
+                line
+            }
         }
     }
 }
 
 /// Global logger instance
 pub struct Logger {
-    config: LoggerConfig,
+    config: RwLock<LoggerConfig>,
     log_file: Mutex<Option<File>>,
     log_path: PathBuf,
+    recent: Mutex<VecDeque<LogEntry>>,
 }
 
 impl Logger {
     /// Create a new logger with the given configuration
     pub fn new(config: LoggerConfig) -> Result<Self, std::io::Error> {
-        let log_path = Self::get_log_path()?;
-        
+        Self::with_path(config, Self::get_log_path()?)
+    }
+
+    /// Create a logger writing to `log_path`
+    fn with_path(config: LoggerConfig, log_path: PathBuf) -> Result<Self, std::io::Error> {
         // Ensure log directory exists
         if let Some(parent) = log_path.parent() {
             fs::create_dir_all(parent)?;
@@ -64,9 +122,10 @@ impl Logger {
             .open(&log_path)?;
 
         Ok(Self {
-            config,
+            config: RwLock::new(config),
             log_file: Mutex::new(Some(log_file)),
             log_path,
+            recent: Mutex::new(VecDeque::with_capacity(RECENT_CAPACITY)),
         })
     }
 
@@ -86,13 +145,29 @@ impl Logger {
     /// Log a message with the specified level
     pub fn log(&self, level: LogLevel, category: &str, message: &str) {
         // Check if we should log this level
-        if level > self.config.min_level {
-            return;
+        let (format, max_file_size) = {
+            let config = self.config.read().unwrap_or_else(|e| e.into_inner());
+            if level > config.level_for(category) {
+                return;
+            }
+            (config.format, config.max_file_size)
+        };
+
+        let entry = LogEntry {
+            timestamp: Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            level,
+            category: category.to_string(),
+            message: message.to_string(),
+        };
+        let log_line = entry.format(format);
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT_CAPACITY {
+                recent.pop_front();
+            }
+            recent.push_back(entry);
         }
 
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
-        let log_line = format!("[{}] [{}] [{}] {}\n", timestamp, level.as_str(), category, message);
-
         // Also print to stderr for development
         eprint!("{}", log_line);
 
@@ -104,7 +179,7 @@ impl Logger {
 
                 // Check if rotation is needed
                 if let Ok(metadata) = file.metadata() {
-                    if metadata.len() >= self.config.max_file_size {
+                    if metadata.len() >= max_file_size {
                         drop(file_guard); // Release lock before rotation
                         let _ = self.rotate_logs();
                     }
@@ -113,20 +188,48 @@ impl Logger {
         }
     }
 
+    /// Replace the configuration, e.g. to turn on debug logging while reproducing an issue
+    ///
+    /// Rotated files beyond a lowered `max_files` are deleted.
+    pub fn reconfigure(&self, config: LoggerConfig) {
+        let mut current = self.config.write().unwrap_or_else(|e| e.into_inner());
+        for i in (config.max_files + 1)..=current.max_files {
+            let _ = fs::remove_file(self.get_rotated_log_path(i));
+        }
+        *current = config;
+    }
+
+    /// Most recent entries first, at `level` or more severe and optionally in one category
+    pub fn recent(&self, level: Option<LogLevel>, category: Option<&str>, limit: usize) -> Vec<LogEntry> {
+        let recent = match self.recent.lock() {
+            Ok(recent) => recent,
+            Err(_) => return Vec::new(),
+        };
+        recent
+            .iter()
+            .rev()
+            .filter(|entry| level.is_none_or(|level| entry.level <= level))
+            .filter(|entry| category.is_none_or(|category| entry.category.eq_ignore_ascii_case(category)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
     /// Rotate log files when size limit is reached
     fn rotate_logs(&self) -> Result<(), std::io::Error> {
+        let max_files = self.config.read().unwrap_or_else(|e| e.into_inner()).max_files;
         let mut file_guard = self.log_file.lock().unwrap();
         
         // Close current log file
         *file_guard = None;
 
         // Rotate existing log files
-        for i in (1..self.config.max_files).rev() {
+        for i in (1..max_files).rev() {
             let old_path = self.get_rotated_log_path(i);
             let new_path = self.get_rotated_log_path(i + 1);
             
             if old_path.exists() {
-                if i + 1 > self.config.max_files {
+                if i + 1 > max_files {
                     // Delete oldest log file
                     let _ = fs::remove_file(&old_path);
                 } else {
@@ -175,6 +278,11 @@ impl Logger {
     pub fn info(&self, category: &str, message: &str) {
         self.log(LogLevel::Info, category, message);
     }
+
+    /// Log a debug message
+    pub fn debug(&self, category: &str, message: &str) {
+        self.log(LogLevel::Debug, category, message);
+    }
 }
 
 // Global logger instance using OnceLock (thread-safe)
@@ -193,6 +301,13 @@ pub fn init_logger(config: LoggerConfig) -> Result<(), std::io::Error> {
     Ok(())
 }
 
+/// Apply a new configuration to the global logger
+pub fn reconfigure_logger(config: LoggerConfig) {
+    if let Some(logger) = get_logger() {
+        logger.reconfigure(config);
+    }
+}
+
 /// Get the global logger instance
 fn get_logger() -> Option<&'static Logger> {
     GLOBAL_LOGGER.get()
@@ -225,6 +340,18 @@ pub fn log_info(category: &str, message: &str) {
     }
 }
 
+/// Log a debug message; only recorded when the level is set to debug
+pub fn log_debug(category: &str, message: &str) {
+    if let Some(logger) = get_logger() {
+        logger.debug(category, message);
+    }
+}
+
+/// Recent log entries from the global logger, newest first
+pub fn recent_logs(level: Option<LogLevel>, category: Option<&str>, limit: usize) -> Vec<LogEntry> {
+    get_logger().map(|logger| logger.recent(level, category, limit)).unwrap_or_default()
+}
+
 /// Log an error with context
 pub fn log_error_with_context(category: &str, error: &dyn std::error::Error, context: &str) {
     let message = format!("{}: {}", context, error);
@@ -271,6 +398,7 @@ mod tests {
     fn test_log_levels() {
         assert!(LogLevel::Error < LogLevel::Warn);
         assert!(LogLevel::Warn < LogLevel::Info);
+        assert!(LogLevel::Info < LogLevel::Debug);
     }
 
     #[test]
@@ -295,6 +423,76 @@ mod tests {
         assert!(log_path.exists());
     }
 
+    fn temp_logger(config: LoggerConfig) -> (TempDir, Logger) {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = Logger::with_path(config, temp_dir.path().join("milk.log")).unwrap();
+        (temp_dir, logger)
+    }
+
+    #[test]
+    fn test_json_lines_format() {
+        let config = LoggerConfig { format: LogFormat::JsonLines, ..Default::default() };
+        let (temp_dir, logger) = temp_logger(config);
+        logger.warn("Config", "quoted \"value\"");
+
+        let contents = fs::read_to_string(temp_dir.path().join("milk.log")).unwrap();
+        let entry: LogEntry = serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+        assert_eq!(entry.level, LogLevel::Warn);
+        assert_eq!(entry.message, "quoted \"value\"");
+    }
+
+    #[test]
+    fn test_category_level_override() {
+        let config = LoggerConfig {
+            min_level: LogLevel::Error,
+            category_levels: HashMap::from([("Audio".to_string(), LogLevel::Info)]),
+            ..Default::default()
+        };
+        let (_temp_dir, logger) = temp_logger(config);
+        logger.info("Audio", "kept");
+        logger.info("Config", "dropped");
+
+        let recent = logger.recent(None, None, 10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].category, "Audio");
+    }
+
+    #[test]
+    fn test_recent_filters_and_limits() {
+        let (_temp_dir, logger) = temp_logger(LoggerConfig::default());
+        logger.error("Config", "first");
+        logger.info("Config", "second");
+        logger.error("Skin", "third");
+
+        let errors = logger.recent(Some(LogLevel::Warn), None, 10);
+        assert_eq!(errors.iter().map(|e| e.message.as_str()).collect::<Vec<_>>(), vec!["third", "first"]);
+        assert_eq!(logger.recent(None, Some("config"), 1)[0].message, "second");
+    }
+
+    #[test]
+    fn test_reconfigure_changes_level_at_runtime() {
+        let (_temp_dir, logger) = temp_logger(LoggerConfig::default());
+        logger.debug("Audio", "dropped");
+        logger.reconfigure(LoggerConfig { min_level: LogLevel::Debug, ..Default::default() });
+        logger.debug("Audio", "kept");
+
+        let recent = logger.recent(None, None, 10);
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].message, "kept");
+    }
+
+    #[test]
+    fn test_reconfigure_prunes_extra_rotated_files() {
+        let (temp_dir, logger) = temp_logger(LoggerConfig { max_files: 3, ..Default::default() });
+        for i in 1..=3 {
+            fs::write(temp_dir.path().join(format!("milk.log.{}", i)), "old").unwrap();
+        }
+        logger.reconfigure(LoggerConfig { max_files: 1, ..Default::default() });
+        assert!(temp_dir.path().join("milk.log.1").exists());
+        assert!(!temp_dir.path().join("milk.log.2").exists());
+        assert!(!temp_dir.path().join("milk.log.3").exists());
+    }
+
     #[test]
     fn test_log_rotation_path() {
         let config = LoggerConfig::default();
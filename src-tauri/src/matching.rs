@@ -0,0 +1,314 @@
+// Cross-service track matching
+// Decides whether a candidate from Spotify, YouTube or the local library is the
+// same recording as a known track, from normalized title, artist and duration.
+// Playlist export and the `match_track` command score candidates here, so
+// every feature agrees on what counts as a match.
+
+use crate::collation::fold;
+use crate::search::{SearchField, SearchFilters, SearchIndex};
+use crate::spotify::{ApiError, RecommendedTrack, SpotifyBridge};
+use crate::youtube::{YouTubeBridge, YouTubeVideo};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Candidates scoring below this are not trusted as the same recording
+pub const MIN_CONFIDENCE: f32 = 0.7;
+
+/// Candidates fetched per search
+const CANDIDATE_LIMIT: u32 = 5;
+
+/// Duration differences up to this many seconds don't lower the score
+const DURATION_SLACK_SECS: f64 = 3.0;
+
+/// Duration differences from here on count as a complete mismatch
+const DURATION_MISMATCH_SECS: f64 = 30.0;
+
+/// Channel name suffixes YouTube adds to artist channels
+const CHANNEL_SUFFIXES: [&str; 3] = [" - Topic", "VEVO", " Official"];
+
+#[derive(Error, Debug)]
+pub enum MatchError {
+    #[error(transparent)]
+    Service(#[from] ApiError),
+    #[error("The library has not been indexed yet")]
+    LibraryNotIndexed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetService {
+    Local,
+    Spotify,
+    Youtube,
+}
+
+/// What is known about the track being matched
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MatchQuery {
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub duration_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MatchCandidate {
+    pub service: TargetService,
+    /// Spotify track URI, YouTube video id or encoded local path
+    pub id: String,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    pub duration_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TrackMatch {
+    pub candidate: MatchCandidate,
+    /// 0 to 1
+    pub confidence: f32,
+    /// At least `MIN_CONFIDENCE`
+    pub confident: bool,
+}
+
+/// Where candidates for each target service come from
+pub struct MatchSources<'a> {
+    pub spotify: &'a SpotifyBridge,
+    pub youtube: &'a YouTubeBridge,
+    /// The library index, once built
+    pub library: Option<&'a SearchIndex>,
+}
+
+impl From<&crate::playlist::Track> for MatchQuery {
+    fn from(track: &crate::playlist::Track) -> Self {
+        MatchQuery {
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            album: Some(track.album.clone()).filter(|album| !album.is_empty()),
+            duration_secs: Some(track.duration).filter(|d| *d > 0.0),
+        }
+    }
+}
+
+impl From<RecommendedTrack> for MatchCandidate {
+    fn from(track: RecommendedTrack) -> Self {
+        MatchCandidate {
+            service: TargetService::Spotify,
+            id: track.uri,
+            title: track.title,
+            artists: track.artists,
+            album: Some(track.album).filter(|album| !album.is_empty()),
+            duration_secs: Some(track.duration_ms as f64 / 1000.0),
+        }
+    }
+}
+
+impl From<YouTubeVideo> for MatchCandidate {
+    /// Music videos are usually titled "Artist - Title"; otherwise the channel
+    /// stands in for the artist
+    fn from(video: YouTubeVideo) -> Self {
+        let channel = CHANNEL_SUFFIXES
+            .iter()
+            .fold(video.channel.trim(), |name, suffix| name.strip_suffix(suffix).unwrap_or(name))
+            .trim()
+            .to_string();
+        let (artists, title) = match video.title.split_once(" - ") {
+            Some((artist, title)) => (vec![artist.trim().to_string(), channel], title.trim().to_string()),
+            None => (vec![channel], video.title.clone()),
+        };
+        MatchCandidate {
+            service: TargetService::Youtube,
+            id: video.id,
+            title,
+            artists,
+            album: None,
+            duration_secs: Some(video.duration_ms as f64 / 1000.0),
+        }
+    }
+}
+
+/// Search `target` for the track and return the best scoring candidate
+///
+/// The best candidate is returned even when it is not `confident`, so callers
+/// can show near misses.
+pub async fn match_track(query: &MatchQuery, target: TargetService, sources: &MatchSources<'_>) -> Result<Option<TrackMatch>, MatchError> {
+    let candidates: Vec<MatchCandidate> = match target {
+        TargetService::Spotify => match spotify_query(query) {
+            Some(q) => sources.spotify.search_tracks(&q, CANDIDATE_LIMIT).await?.into_iter().map(Into::into).collect(),
+            None => Vec::new(),
+        },
+        TargetService::Youtube => match keyword_query(query) {
+            Some(q) => sources.youtube.search_videos(&q, CANDIDATE_LIMIT).await?.into_iter().map(Into::into).collect(),
+            None => Vec::new(),
+        },
+        TargetService::Local => local_candidates(sources.library.ok_or(MatchError::LibraryNotIndexed)?, query),
+    };
+    Ok(best_match(query, candidates))
+}
+
+/// Library tracks whose title matches the query's
+pub fn local_candidates(index: &SearchIndex, query: &MatchQuery) -> Vec<MatchCandidate> {
+    let filters = SearchFilters {
+        fields: vec![SearchField::Title],
+        limit: Some(CANDIDATE_LIMIT as usize),
+        ..Default::default()
+    };
+    index
+        .search(&normalize_title(&query.title), &filters, |_| Default::default())
+        .into_iter()
+        .map(|hit| {
+            let metadata = hit.metadata.unwrap_or_default();
+            MatchCandidate {
+                service: TargetService::Local,
+                id: crate::path_codec::encode(&hit.track.file_path),
+                title: metadata.title.unwrap_or(hit.track.file_name),
+                artists: metadata.artist.into_iter().collect(),
+                album: metadata.album,
+                duration_secs: metadata.duration.map(f64::from),
+            }
+        })
+        .collect()
+}
+
+/// The highest scoring candidate
+pub fn best_match(query: &MatchQuery, candidates: Vec<MatchCandidate>) -> Option<TrackMatch> {
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let confidence = confidence(query, &candidate);
+            TrackMatch { candidate, confidence, confident: confidence >= MIN_CONFIDENCE }
+        })
+        .max_by(|a, b| a.confidence.total_cmp(&b.confidence))
+}
+
+/// How likely `candidate` is the same recording, from 0 to 1
+///
+/// Title similarity weighs most, then the best matching credited artist, then
+/// duration; an unknown duration on either side counts as half a match.
+pub fn confidence(query: &MatchQuery, candidate: &MatchCandidate) -> f32 {
+    let title = similarity(&normalize_title(&query.title), &normalize_title(&candidate.title));
+    let artist = candidate
+        .artists
+        .iter()
+        .map(|artist| similarity(&query.artist, artist))
+        .fold(0.0, f32::max);
+    let duration = match (query.duration_secs, candidate.duration_secs) {
+        (Some(a), Some(b)) => {
+            let over = ((a - b).abs() - DURATION_SLACK_SECS).max(0.0);
+            (1.0 - over / (DURATION_MISMATCH_SECS - DURATION_SLACK_SECS)).max(0.0) as f32
+        }
+        _ => 0.5,
+    };
+    title * 0.5 + artist * 0.3 + duration * 0.2
+}
+
+/// Spotify's field-filtered search syntax, or `None` for a track without a title
+pub fn spotify_query(query: &MatchQuery) -> Option<String> {
+    let title = normalize_title(&query.title);
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+    let quote = |text: &str| text.replace('"', " ");
+    Some(match query.artist.trim() {
+        "" => format!("track:\"{}\"", quote(title)),
+        artist => format!("track:\"{}\" artist:\"{}\"", quote(title), quote(artist)),
+    })
+}
+
+/// Plain "artist title" keywords for services without field filters
+pub fn keyword_query(query: &MatchQuery) -> Option<String> {
+    let title = normalize_title(&query.title);
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some(format!("{} {}", query.artist.trim(), title).trim().to_string())
+}
+
+/// Drop bracketed parts like "(Remastered 2011)" or "[feat. X]" and
+/// " - Live" style suffixes
+pub fn normalize_title(title: &str) -> String {
+    let mut stripped = String::with_capacity(title.len());
+    let mut depth = 0usize;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            c if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+    }
+    match stripped.find(" - ") {
+        Some(end) if end > 0 => stripped[..end].to_string(),
+        _ => stripped,
+    }
+}
+
+/// Dice coefficient of the folded words of `a` and `b`
+fn similarity(a: &str, b: &str) -> f32 {
+    let words = |text: &str| -> HashSet<String> {
+        fold(text)
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    2.0 * a.intersection(&b).count() as f32 / (a.len() + b.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(title: &str, artist: &str, duration_secs: Option<f64>) -> MatchQuery {
+        MatchQuery { title: title.to_string(), artist: artist.to_string(), album: None, duration_secs }
+    }
+
+    fn candidate(title: &str, artists: &[&str], duration_secs: f64) -> MatchCandidate {
+        MatchCandidate {
+            service: TargetService::Spotify,
+            id: "spotify:track:4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            title: title.to_string(),
+            artists: artists.iter().map(|a| a.to_string()).collect(),
+            album: None,
+            duration_secs: Some(duration_secs),
+        }
+    }
+
+    #[test]
+    fn test_confidence() {
+        let track = query("Héroes (Remastered)", "David Bowie", Some(370.0));
+        let same = candidate("Heroes - 2017 Remaster", &["David Bowie"], 371.0);
+        let cover = candidate("Heroes", &["Peter Gabriel"], 405.0);
+        assert!(confidence(&track, &same) > 0.95);
+        assert!(confidence(&track, &cover) < MIN_CONFIDENCE);
+
+        let best = best_match(&track, vec![cover, same.clone()]).unwrap();
+        assert_eq!(best.candidate, same);
+        assert!(best.confident);
+        assert_eq!(spotify_query(&track).unwrap(), "track:\"Héroes\" artist:\"David Bowie\"");
+        assert_eq!(keyword_query(&track).unwrap(), "David Bowie Héroes");
+        assert_eq!(spotify_query(&query("(untitled)", "", None)), None);
+    }
+
+    #[test]
+    fn test_youtube_candidate() {
+        let video = YouTubeVideo {
+            id: "dQw4w9WgXcQ".to_string(),
+            title: "Rick Astley - Never Gonna Give You Up (Official Music Video)".to_string(),
+            channel: "RickAstleyVEVO".to_string(),
+            duration_ms: 213_000,
+        };
+        let candidate = MatchCandidate::from(video);
+        assert_eq!(candidate.artists, ["Rick Astley", "RickAstley"]);
+        let track = query("Never Gonna Give You Up", "Rick Astley", Some(213.5));
+        assert!(confidence(&track, &candidate) > 0.95);
+    }
+}
@@ -0,0 +1,582 @@
+// Audio operations module
+// This module contains audio probing and format conversion functions
+
+use crate::error::{CommandError, MilkError};
+use crate::media_editor::config::DEFAULT_CONFIG;
+use crate::media_editor::types::ExportConfig;
+use crate::media_editor::ffmpeg::{run_ffmpeg, FfmpegProgress};
+use crate::jobs::{app_sink, job_manager, resolve_job_id, JobContext, JobKind};
+use crate::media_editor::jobs::{ensure_disk_space, estimate_output_bytes};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::AtomicBool;
+
+/// Lowest and highest bitrates (kbps) accepted for lossy formats
+pub const MIN_BITRATE_KBPS: u32 = 32;
+pub const MAX_BITRATE_KBPS: u32 = 320;
+
+/// Preview clips are small, low-bitrate files for sharing and hover previews
+pub const PREVIEW_BITRATE_KBPS: u32 = 64;
+pub const PREVIEW_SAMPLE_RATE: u32 = 22050;
+pub const MAX_PREVIEW_SEC: f64 = 60.0;
+
+/// Short fades so previews don't start or stop with a click
+const PREVIEW_FADE_MS: u32 = 250;
+
+/// Target format for audio conversion
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    Mp3,
+    Aac,
+    Ogg,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn codec(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "libmp3lame",
+            AudioFormat::Aac => "aac",
+            AudioFormat::Ogg => "libvorbis",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    /// File extension for the output container
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Aac => "m4a",
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Flac => "flac",
+        }
+    }
+
+    pub fn is_lossless(self) -> bool {
+        matches!(self, AudioFormat::Flac)
+    }
+
+    /// Format for an output file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "mp3" => Some(AudioFormat::Mp3),
+            "m4a" | "aac" => Some(AudioFormat::Aac),
+            "ogg" => Some(AudioFormat::Ogg),
+            "flac" => Some(AudioFormat::Flac),
+            _ => None,
+        }
+    }
+}
+
+/// Audio stream metadata extracted from a media file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioMetadata {
+    pub duration_sec: f64,
+    pub codec: String,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub bit_rate: Option<u64>,
+}
+
+/// Probe audio metadata using FFprobe
+pub fn probe_audio_metadata(path: &str) -> Result<AudioMetadata, String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v", "error",
+            "-select_streams", "a:0",
+            "-show_entries", "stream=codec_name,sample_rate,channels,bit_rate,duration",
+            "-show_entries", "format=duration,bit_rate",
+            "-of", "json",
+            path,
+        ])
+        .output()
+        .map_err(|e| format!("Failed to execute FFprobe: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("FFprobe failed: {}", stderr));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let json: Value = serde_json::from_str(&stdout)
+        .map_err(|e| format!("Failed to parse FFprobe JSON output: {}", e))?;
+    parse_audio_probe(&json)
+}
+
+fn parse_audio_probe(json: &Value) -> Result<AudioMetadata, String> {
+    let stream = json["streams"]
+        .as_array()
+        .and_then(|streams| streams.first())
+        .ok_or_else(|| "No audio streams found".to_string())?;
+
+    // FFprobe reports numbers as strings
+    let number = |value: &Value| value.as_str().and_then(|s| s.parse::<f64>().ok());
+    let duration_sec = number(&stream["duration"])
+        .or_else(|| number(&json["format"]["duration"]))
+        .ok_or_else(|| "Duration not found in audio metadata".to_string())?;
+
+    Ok(AudioMetadata {
+        duration_sec,
+        codec: stream["codec_name"].as_str().unwrap_or("unknown").to_string(),
+        sample_rate: number(&stream["sample_rate"]).map(|v| v as u32),
+        channels: stream["channels"].as_u64().map(|v| v as u32),
+        bit_rate: number(&stream["bit_rate"])
+            .or_else(|| number(&json["format"]["bit_rate"]))
+            .map(|v| v as u64),
+    })
+}
+
+/// Tauri command to probe audio metadata
+#[tauri::command]
+pub async fn probe_audio_metadata_command(path: String) -> Result<AudioMetadata, CommandError> {
+    probe_audio_metadata(&path).map_err(CommandError::from)
+}
+
+/// FFmpeg arguments (after the runner's own flags) for converting `input_path`
+///
+/// Video streams such as embedded cover art are dropped; tags are copied.
+/// `bitrate_kbps` is ignored for lossless formats and defaults to
+/// `DEFAULT_CONFIG.audio_bitrate_kbps` otherwise.
+pub fn build_convert_args(
+    input_path: &str,
+    output_path: &str,
+    format: AudioFormat,
+    bitrate_kbps: Option<u32>,
+) -> Result<Vec<String>, String> {
+    let mut args = vec![
+        "-i".to_string(),
+        input_path.to_string(),
+        "-vn".to_string(),
+        "-map_metadata".to_string(),
+        "0".to_string(),
+        "-c:a".to_string(),
+        format.codec().to_string(),
+    ];
+    if !format.is_lossless() {
+        let bitrate = bitrate_kbps.unwrap_or(DEFAULT_CONFIG.audio_bitrate_kbps);
+        if !(MIN_BITRATE_KBPS..=MAX_BITRATE_KBPS).contains(&bitrate) {
+            return Err(format!(
+                "Bitrate must be between {} and {} kbps, got {}",
+                MIN_BITRATE_KBPS, MAX_BITRATE_KBPS, bitrate
+            ));
+        }
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", bitrate));
+    }
+    args.push(output_path.to_string());
+    Ok(args)
+}
+
+/// Convert an audio file to another format using FFmpeg
+///
+/// Progress is reported through `on_progress`; setting `cancel` stops the
+/// conversion and removes the partial output.
+pub fn convert_audio(
+    input_path: &str,
+    output_path: &str,
+    format: AudioFormat,
+    bitrate_kbps: Option<u32>,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(FfmpegProgress),
+) -> Result<(), String> {
+    let args = build_convert_args(input_path, output_path, format, bitrate_kbps)?;
+    // A failed probe only costs the percentage, not the conversion
+    let duration = probe_audio_metadata(input_path).ok().map(|m| m.duration_sec);
+    let result = run_ffmpeg(&args, duration, cancel, on_progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Output path for `input_path` converted to `format` inside `output_dir`
+pub fn export_path(input_path: &str, output_dir: &Path, format: AudioFormat) -> PathBuf {
+    let stem = Path::new(input_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "track".to_string());
+    output_dir.join(format!("{}.{}", stem, format.extension()))
+}
+
+/// Convert several files, reporting overall progress across them
+fn run_conversion_job(
+    ctx: &JobContext,
+    jobs: &[(String, String)],
+    format: AudioFormat,
+    bitrate_kbps: Option<u32>,
+) -> Result<Vec<String>, String> {
+    let mut outputs = Vec::with_capacity(jobs.len());
+    for (index, (input, output)) in jobs.iter().enumerate() {
+        convert_audio(input, output, format, bitrate_kbps, ctx.cancel_flag(), |progress| {
+            let file_percent = progress.percent.unwrap_or(0.0);
+            let eta = progress.eta_sec.filter(|_| index + 1 == jobs.len());
+            ctx.report(Some((index as f64 * 100.0 + file_percent) / jobs.len() as f64), eta);
+        })?;
+        outputs.push(output.clone());
+    }
+    Ok(outputs)
+}
+
+/// Fade and trim bounds for `trim_audio`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TrimRange {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    #[serde(default)]
+    pub fade_in_ms: u32,
+    #[serde(default)]
+    pub fade_out_ms: u32,
+}
+
+impl TrimRange {
+    pub fn length_sec(&self) -> f64 {
+        self.end_sec - self.start_sec
+    }
+
+    /// Check the range against the source duration
+    pub fn validate(&self, duration_sec: f64) -> Result<(), String> {
+        if !self.start_sec.is_finite() || !self.end_sec.is_finite() || self.start_sec < 0.0 {
+            return Err(format!("Invalid start time: {}", self.start_sec));
+        }
+        if self.end_sec <= self.start_sec {
+            return Err(format!("End time {} must be after start time {}", self.end_sec, self.start_sec));
+        }
+        // FFprobe durations are rounded, so allow a little slack at the end
+        if self.end_sec > duration_sec + 0.05 {
+            return Err(format!("End time {} is past the end of the audio ({:.2}s)", self.end_sec, duration_sec));
+        }
+        let fades_sec = (self.fade_in_ms as f64 + self.fade_out_ms as f64) / 1000.0;
+        if fades_sec > self.length_sec() {
+            return Err(format!(
+                "Fades ({}ms in, {}ms out) are longer than the {:.2}s clip",
+                self.fade_in_ms, self.fade_out_ms, self.length_sec()
+            ));
+        }
+        Ok(())
+    }
+
+    /// `afade` filter chain for the trimmed clip, if any fades are set
+    fn fade_filter(&self) -> Option<String> {
+        let mut filters = Vec::new();
+        if self.fade_in_ms > 0 {
+            filters.push(format!("afade=t=in:st=0:d={}", self.fade_in_ms as f64 / 1000.0));
+        }
+        if self.fade_out_ms > 0 {
+            let duration = self.fade_out_ms as f64 / 1000.0;
+            filters.push(format!("afade=t=out:st={}:d={}", self.length_sec() - duration, duration));
+        }
+        (!filters.is_empty()).then(|| filters.join(","))
+    }
+}
+
+/// FFmpeg arguments for cutting `range` out of `input_path`
+///
+/// The codec comes from the export config's `audio_codec`; lossy codecs use
+/// `DEFAULT_CONFIG.audio_bitrate_kbps`.
+pub fn build_trim_args(input_path: &str, output_path: &str, range: &TrimRange, config: &ExportConfig) -> Vec<String> {
+    let mut args = vec![
+        "-i".to_string(),
+        input_path.to_string(),
+        "-ss".to_string(),
+        range.start_sec.to_string(),
+        "-t".to_string(),
+        range.length_sec().to_string(),
+        "-vn".to_string(),
+        "-map_metadata".to_string(),
+        "0".to_string(),
+    ];
+    if let Some(filter) = range.fade_filter() {
+        args.push("-af".to_string());
+        args.push(filter);
+    }
+    args.push("-c:a".to_string());
+    args.push(config.audio_codec.clone());
+    if !matches!(config.audio_codec.as_str(), "flac" | "alac") && !config.audio_codec.starts_with("pcm_") {
+        args.push("-b:a".to_string());
+        args.push(format!("{}k", DEFAULT_CONFIG.audio_bitrate_kbps));
+    }
+    args.push(output_path.to_string());
+    args
+}
+
+/// Cut a section out of an audio file with optional fade in/out
+///
+/// The range is validated against the probed duration before FFmpeg runs.
+pub fn trim_audio(
+    input_path: &str,
+    output_path: &str,
+    range: &TrimRange,
+    config: &ExportConfig,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(FfmpegProgress),
+) -> Result<(), String> {
+    let metadata = probe_audio_metadata(input_path)?;
+    range.validate(metadata.duration_sec)?;
+    let args = build_trim_args(input_path, output_path, range, config);
+    let result = run_ffmpeg(&args, Some(range.length_sec()), cancel, on_progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Tauri command to trim audio as a media job
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn trim_audio_command(
+    app: tauri::AppHandle,
+    job_id: Option<String>,
+    input_path: String,
+    output_path: String,
+    start_sec: f64,
+    end_sec: f64,
+    fade_in_ms: Option<u32>,
+    fade_out_ms: Option<u32>,
+    config: ExportConfig,
+) -> Result<(), CommandError> {
+    let range = TrimRange {
+        start_sec,
+        end_sec,
+        fade_in_ms: fade_in_ms.unwrap_or(0),
+        fade_out_ms: fade_out_ms.unwrap_or(0),
+    };
+    ensure_disk_space(&output_path, estimate_output_bytes(&[input_path.as_str()]))?;
+    job_manager()
+        .run(resolve_job_id(job_id), JobKind::Media, "trim_audio", app_sink(app), move |ctx| {
+            trim_audio(&input_path, &output_path, &range, &config, ctx.cancel_flag(), |progress| {
+                ctx.report_ffmpeg(&progress)
+            })
+        })
+        .await
+        .map_err(CommandError::from)
+}
+
+/// Clip range for a preview, cut short at the end of the track
+pub fn preview_range(start_sec: f64, duration_sec: f64, track_duration_sec: f64) -> Result<TrimRange, String> {
+    if !duration_sec.is_finite() || duration_sec <= 0.0 || duration_sec > MAX_PREVIEW_SEC {
+        return Err(format!(
+            "Preview duration must be between 0 and {} seconds, got {}",
+            MAX_PREVIEW_SEC, duration_sec
+        ));
+    }
+    let end_sec = (start_sec + duration_sec).min(track_duration_sec);
+    let fade_ms = PREVIEW_FADE_MS.min(((end_sec - start_sec).max(0.0) * 1000.0 / 4.0) as u32);
+    let range = TrimRange { start_sec, end_sec, fade_in_ms: fade_ms, fade_out_ms: fade_ms };
+    range.validate(track_duration_sec)?;
+    Ok(range)
+}
+
+/// FFmpeg arguments for a low-bitrate preview of `range`
+///
+/// Tags and cover art are dropped to keep the clip small.
+pub fn build_preview_args(input_path: &str, output_path: &str, range: &TrimRange, format: AudioFormat) -> Result<Vec<String>, String> {
+    if format.is_lossless() {
+        return Err(format!("Preview clips must use a lossy format, not {}", format.extension()));
+    }
+    let mut args = vec![
+        "-i".to_string(),
+        input_path.to_string(),
+        "-ss".to_string(),
+        range.start_sec.to_string(),
+        "-t".to_string(),
+        range.length_sec().to_string(),
+        "-vn".to_string(),
+        "-map_metadata".to_string(),
+        "-1".to_string(),
+    ];
+    if let Some(filter) = range.fade_filter() {
+        args.push("-af".to_string());
+        args.push(filter);
+    }
+    args.extend([
+        "-ar".to_string(),
+        PREVIEW_SAMPLE_RATE.to_string(),
+        "-c:a".to_string(),
+        format.codec().to_string(),
+        "-b:a".to_string(),
+        format!("{}k", PREVIEW_BITRATE_KBPS),
+        output_path.to_string(),
+    ]);
+    Ok(args)
+}
+
+/// Write a short preview clip of `input_path`; the format follows `output_path`'s extension
+pub fn generate_preview_clip(
+    input_path: &str,
+    output_path: &str,
+    start_sec: f64,
+    duration_sec: f64,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(FfmpegProgress),
+) -> Result<(), String> {
+    let format = Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(AudioFormat::from_extension)
+        .ok_or_else(|| format!("Unsupported preview format: {}", output_path))?;
+    let metadata = probe_audio_metadata(input_path)?;
+    let range = preview_range(start_sec, duration_sec, metadata.duration_sec)?;
+    let args = build_preview_args(input_path, output_path, &range, format)?;
+    let result = run_ffmpeg(&args, Some(range.length_sec()), cancel, on_progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Tauri command to generate a preview clip as a media job
+///
+/// Probe and validation errors are reported like FFmpeg failures: as a
+/// failed `job-progress` event and as the command's error.
+#[tauri::command]
+pub async fn generate_preview_clip_command(
+    app: tauri::AppHandle,
+    job_id: Option<String>,
+    file_path: String,
+    start_sec: f64,
+    duration_sec: f64,
+    output: String,
+) -> Result<String, CommandError> {
+    let preview_bytes = PREVIEW_BITRATE_KBPS as f64 * 1000.0 / 8.0 * duration_sec.clamp(0.0, MAX_PREVIEW_SEC);
+    ensure_disk_space(&output, preview_bytes as u64)?;
+    job_manager()
+        .run(resolve_job_id(job_id), JobKind::Media, "preview_clip", app_sink(app), move |ctx| {
+            generate_preview_clip(&file_path, &output, start_sec, duration_sec, ctx.cancel_flag(), |progress| {
+                ctx.report_ffmpeg(&progress)
+            })?;
+            Ok::<_, String>(output)
+        })
+        .await
+        .map_err(CommandError::from)
+}
+
+/// Tauri command to convert one audio file
+#[tauri::command]
+pub async fn convert_audio_command(
+    app: tauri::AppHandle,
+    job_id: Option<String>,
+    input_path: String,
+    output_path: String,
+    format: AudioFormat,
+    bitrate_kbps: Option<u32>,
+) -> Result<String, CommandError> {
+    ensure_disk_space(&output_path, estimate_output_bytes(&[input_path.as_str()]))?;
+    job_manager()
+        .run(resolve_job_id(job_id), JobKind::Media, "convert_audio", app_sink(app), move |ctx| {
+            convert_audio(&input_path, &output_path, format, bitrate_kbps, ctx.cancel_flag(), |progress| {
+                ctx.report_ffmpeg(&progress)
+            })?;
+            Ok::<_, String>(output_path)
+        })
+        .await
+        .map_err(CommandError::from)
+}
+
+/// Tauri command to export several tracks (e.g. a playlist selection) into a folder
+///
+/// Output files keep the input file names with the new format's extension.
+#[tauri::command]
+pub async fn export_audio_files_command(
+    app: tauri::AppHandle,
+    job_id: Option<String>,
+    input_paths: Vec<String>,
+    output_dir: String,
+    format: AudioFormat,
+    bitrate_kbps: Option<u32>,
+) -> Result<Vec<String>, CommandError> {
+    let output_dir = PathBuf::from(output_dir);
+    std::fs::create_dir_all(&output_dir).map_err(|e| MilkError::from_io(e, "export folder"))?;
+    let inputs: Vec<&str> = input_paths.iter().map(String::as_str).collect();
+    ensure_disk_space(&output_dir, estimate_output_bytes(&inputs))?;
+    let jobs: Vec<(String, String)> = input_paths
+        .into_iter()
+        .map(|input| {
+            let output = export_path(&input, &output_dir, format).to_string_lossy().to_string();
+            (input, output)
+        })
+        .collect();
+    job_manager()
+        .run(resolve_job_id(job_id), JobKind::Media, "export_audio", app_sink(app), move |ctx| {
+            run_conversion_job(ctx, &jobs, format, bitrate_kbps)
+        })
+        .await
+        .map_err(CommandError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_convert_args() {
+        let args = build_convert_args("in.flac", "out.mp3", AudioFormat::Mp3, Some(256)).unwrap();
+        assert_eq!(
+            args,
+            vec!["-i", "in.flac", "-vn", "-map_metadata", "0", "-c:a", "libmp3lame", "-b:a", "256k", "out.mp3"]
+        );
+
+        let args = build_convert_args("in.mp3", "out.flac", AudioFormat::Flac, Some(999)).unwrap();
+        assert!(!args.contains(&"-b:a".to_string()));
+
+        assert!(build_convert_args("in.flac", "out.ogg", AudioFormat::Ogg, Some(8)).is_err());
+    }
+
+    #[test]
+    fn test_trim_range_validation() {
+        let range = TrimRange { start_sec: 10.0, end_sec: 40.0, fade_in_ms: 2000, fade_out_ms: 3000 };
+        assert!(range.validate(40.02).is_ok());
+        assert!(range.validate(30.0).is_err());
+        assert!(TrimRange { end_sec: 5.0, ..range }.validate(60.0).is_err());
+        assert!(TrimRange { fade_out_ms: 29_000, ..range }.validate(60.0).is_err());
+    }
+
+    #[test]
+    fn test_build_trim_args_with_fades() {
+        let range = TrimRange { start_sec: 10.0, end_sec: 40.0, fade_in_ms: 500, fade_out_ms: 2000 };
+        let config = ExportConfig {
+            video_codec: "libx264".to_string(),
+            audio_codec: "aac".to_string(),
+            quality: "23".to_string(),
+        };
+        let args = build_trim_args("in.mp3", "out.m4a", &range, &config);
+        let filter = &args[args.iter().position(|a| a == "-af").unwrap() + 1];
+        assert_eq!(filter, "afade=t=in:st=0:d=0.5,afade=t=out:st=28:d=2");
+        assert!(args.windows(2).any(|w| w[0] == "-t" && w[1] == "30"));
+        assert!(args.contains(&"-b:a".to_string()));
+    }
+
+    #[test]
+    fn test_preview_clip_args() {
+        let range = preview_range(170.0, 30.0, 180.0).unwrap();
+        assert_eq!((range.end_sec, range.fade_in_ms), (180.0, 250));
+        assert!(preview_range(0.0, 0.0, 180.0).is_err());
+        assert!(preview_range(0.0, 120.0, 180.0).is_err());
+        assert!(preview_range(200.0, 10.0, 180.0).is_err());
+
+        let args = build_preview_args("in.flac", "out.mp3", &range, AudioFormat::Mp3).unwrap();
+        assert!(args.windows(2).any(|w| w == ["-b:a", "64k"]));
+        assert!(args.windows(2).any(|w| w == ["-map_metadata", "-1"]));
+        assert!(build_preview_args("in.mp3", "out.flac", &range, AudioFormat::Flac).is_err());
+    }
+
+    #[test]
+    fn test_export_path() {
+        let path = export_path("/music/Artist - Song.flac", Path::new("/phone"), AudioFormat::Aac);
+        assert_eq!(path, Path::new("/phone/Artist - Song.m4a"));
+    }
+
+    #[test]
+    fn test_parse_audio_probe() {
+        let json = serde_json::json!({
+            "streams": [{"codec_name": "flac", "sample_rate": "44100", "channels": 2}],
+            "format": {"duration": "183.5", "bit_rate": "900000"}
+        });
+        let metadata = parse_audio_probe(&json).unwrap();
+        assert_eq!(metadata.duration_sec, 183.5);
+        assert_eq!(metadata.sample_rate, Some(44100));
+        assert_eq!(metadata.bit_rate, Some(900000));
+    }
+}
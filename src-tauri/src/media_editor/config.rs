@@ -4,6 +4,7 @@ pub struct ExportDefaults {
     pub audio_codec: &'static str,
     pub image_format: &'static str,
     pub video_quality: &'static str,
+    pub audio_bitrate_kbps: u32,
 }
 
 /// Default configuration constants
@@ -12,6 +13,7 @@ pub const DEFAULT_CONFIG: ExportDefaults = ExportDefaults {
     audio_codec: "aac",
     image_format: "png",
     video_quality: "23", // CRF value
+    audio_bitrate_kbps: 192,
 };
 
 /// Named export preset with specific encoding parameters
@@ -21,6 +23,13 @@ pub struct ExportPreset {
     pub crf: u8,
     pub preset: &'static str,
     pub audio_codec: &'static str,
+    /// Container (file extension) the output should use
+    pub container: &'static str,
+    /// Downscale to at most this many lines, keeping the aspect ratio
+    pub max_height: Option<u32>,
+    pub frame_rate: Option<u32>,
+    /// Use a hardware encoder for the codec when one is available
+    pub hardware: bool,
 }
 
 /// Available export presets
@@ -31,6 +40,10 @@ pub const PRESETS: &[ExportPreset] = &[
         crf: 18,
         preset: "slow",
         audio_codec: "aac",
+        container: "mp4",
+        max_height: None,
+        frame_rate: None,
+        hardware: false,
     },
     ExportPreset {
         name: "mobile_video",
@@ -38,5 +51,20 @@ pub const PRESETS: &[ExportPreset] = &[
         crf: 28,
         preset: "fast",
         audio_codec: "aac",
+        container: "mp4",
+        max_height: Some(720),
+        frame_rate: Some(30),
+        hardware: true,
+    },
+    ExportPreset {
+        name: "fast_share",
+        video_codec: "libx264",
+        crf: 23,
+        preset: "veryfast",
+        audio_codec: "aac",
+        container: "mp4",
+        max_height: Some(1080),
+        frame_rate: None,
+        hardware: true,
     },
 ];
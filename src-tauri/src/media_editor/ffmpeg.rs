@@ -0,0 +1,155 @@
+// FFmpeg process runner
+// Runs FFmpeg with machine-readable progress output so long operations can
+// report progress and be cancelled.
+
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Error returned when a run is cancelled
+pub use crate::jobs::CANCELLED;
+
+/// Progress of a running FFmpeg process
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct FfmpegProgress {
+    /// Seconds of output written so far
+    pub out_time_sec: f64,
+    /// 0-100, or `None` when the total duration is unknown
+    pub percent: Option<f64>,
+    /// Encoding speed relative to realtime
+    pub speed: Option<f64>,
+    /// Estimated seconds remaining
+    pub eta_sec: Option<f64>,
+}
+
+/// Accumulates `-progress` key=value lines into snapshots
+#[derive(Debug, Default)]
+pub struct ProgressParser {
+    duration_sec: Option<f64>,
+    current: FfmpegProgress,
+}
+
+impl ProgressParser {
+    pub fn new(duration_sec: Option<f64>) -> Self {
+        Self {
+            duration_sec: duration_sec.filter(|d| *d > 0.0),
+            current: FfmpegProgress::default(),
+        }
+    }
+
+    /// Feed one line; returns a snapshot at the end of each progress block
+    pub fn feed(&mut self, line: &str) -> Option<FfmpegProgress> {
+        let (key, value) = line.trim().split_once('=')?;
+        match key {
+            // Both keys are in microseconds despite the name of the second
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<f64>() {
+                    self.current.out_time_sec = (us / 1_000_000.0).max(0.0);
+                }
+            }
+            "speed" => {
+                self.current.speed = value.trim_end_matches('x').trim().parse().ok();
+            }
+            "progress" => {
+                let finished = value == "end";
+                if let Some(duration) = self.duration_sec {
+                    let done = if finished { duration } else { self.current.out_time_sec.min(duration) };
+                    self.current.percent = Some(done / duration * 100.0);
+                    self.current.eta_sec = match self.current.speed {
+                        _ if finished => Some(0.0),
+                        Some(speed) if speed > 0.0 => Some((duration - done) / speed),
+                        _ => None,
+                    };
+                }
+                return Some(self.current.clone());
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Run FFmpeg with `args`, reporting progress until it exits
+///
+/// `duration_sec` is the expected output duration used for percentages.
+/// Setting `cancel` kills the process at the next progress update and
+/// returns `CANCELLED`; the caller is responsible for removing partial output.
+pub fn run_ffmpeg(
+    args: &[String],
+    duration_sec: Option<f64>,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(FfmpegProgress),
+) -> Result<(), String> {
+    let mut child = Command::new("ffmpeg")
+        .args(["-y", "-nostats", "-progress", "pipe:1"])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
+
+    // Drain stderr on its own thread so a chatty FFmpeg can't block on a full pipe
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = std::thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+
+    let mut parser = ProgressParser::new(duration_sec);
+    let stdout = child.stdout.take().expect("stdout is piped");
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if cancel.load(Ordering::Relaxed) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(CANCELLED.to_string());
+        }
+        if let Some(progress) = parser.feed(&line) {
+            on_progress(progress);
+        }
+    }
+
+    let status = child.wait().map_err(|e| format!("Failed to wait for FFmpeg: {}", e))?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    if cancel.load(Ordering::Relaxed) {
+        return Err(CANCELLED.to_string());
+    }
+    if !status.success() {
+        return Err(format!("FFmpeg failed: {}", stderr));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_parser() {
+        let mut parser = ProgressParser::new(Some(10.0));
+        assert!(parser.feed("out_time_us=2500000").is_none());
+        assert!(parser.feed("speed=2.5x").is_none());
+        let progress = parser.feed("progress=continue").unwrap();
+        assert_eq!(progress.out_time_sec, 2.5);
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.eta_sec, Some(3.0));
+
+        let progress = parser.feed("progress=end").unwrap();
+        assert_eq!(progress.percent, Some(100.0));
+        assert_eq!(progress.eta_sec, Some(0.0));
+    }
+
+    #[test]
+    fn test_progress_without_duration() {
+        let mut parser = ProgressParser::new(None);
+        parser.feed("out_time_ms=1000000");
+        let progress = parser.feed("progress=continue").unwrap();
+        assert_eq!(progress.out_time_sec, 1.0);
+        assert_eq!(progress.percent, None);
+    }
+}
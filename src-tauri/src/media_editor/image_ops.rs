@@ -1,6 +1,8 @@
 // Image operations module
 // This module contains image cropping and manipulation functions
 
+use crate::error::CommandError;
+use crate::media_editor::jobs::{ensure_disk_space, estimate_output_bytes};
 use crate::media_editor::types::CropRect;
 use image::{DynamicImage, GenericImageView};
 use std::path::Path;
@@ -77,8 +79,9 @@ pub async fn crop_image_command(
     input_path: String,
     output_path: String,
     crop_rect: CropRect,
-) -> Result<(), String> {
-    crop_image(input_path, output_path, &crop_rect)
+) -> Result<(), CommandError> {
+    ensure_disk_space(&output_path, estimate_output_bytes(&[input_path.as_str()]))?;
+    crop_image(input_path, output_path, &crop_rect).map_err(CommandError::from)
 }
 
 #[cfg(test)]
@@ -0,0 +1,27 @@
+// Export checks for media jobs
+// Media jobs run under the shared `crate::jobs` manager; these checks run
+// before one is queued so exports fail fast instead of part-way through.
+
+use crate::error::MilkResult;
+use crate::error_recovery::ErrorRecovery;
+use std::path::Path;
+
+/// Refuse to start an export onto a volume that can't hold `required_bytes`
+///
+/// Checked before queueing, so a full disk fails fast with `MilkError::DiskFull`
+/// instead of leaving a half-written file behind.
+pub fn ensure_disk_space(output_path: impl AsRef<Path>, required_bytes: u64) -> MilkResult<()> {
+    ErrorRecovery::check_disk_space(output_path.as_ref(), required_bytes)
+}
+
+/// Expected output size of exporting `input_paths`: their combined size
+///
+/// Trims, crops and lossy conversions come out smaller, so this errs on the
+/// safe side. Unreadable inputs count as empty and fail later in FFmpeg.
+pub fn estimate_output_bytes(input_paths: &[&str]) -> u64 {
+    input_paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
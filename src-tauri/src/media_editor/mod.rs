@@ -1,8 +1,12 @@
-// Media editor module for image and video editing operations
+// Media editor module for image, video and audio editing operations
 pub mod types;
 pub mod config;
 pub mod image_ops;
 pub mod video_ops;
+pub mod audio_ops;
+pub mod ffmpeg;
+pub mod jobs;
+pub mod presets;
 
 // Re-export commonly used types
 pub use types::{CropRect, VideoMetadata, ExportConfig};
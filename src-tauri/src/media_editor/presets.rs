@@ -0,0 +1,383 @@
+// Video export presets
+// Built-in and user-defined presets, hardware encoder detection and the
+// FFmpeg arguments a preset translates to.
+
+use crate::error::{CommandError, MilkError};
+use crate::media_editor::config::{ExportPreset, PRESETS};
+use crate::media_editor::types::ExportConfig;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+/// Software codecs presets may use; H.264 and HEVC have hardware counterparts
+pub const VIDEO_CODECS: &[&str] = &["libx264", "libx265", "libvpx-vp9"];
+pub const CONTAINERS: &[&str] = &["mp4", "mkv", "mov", "webm"];
+pub const MAX_CRF: u8 = 51;
+
+/// Hardware encoder families FFmpeg can use
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HardwareEncoder {
+    VideoToolbox,
+    Nvenc,
+    Qsv,
+}
+
+impl HardwareEncoder {
+    /// In order of preference
+    pub const ALL: [HardwareEncoder; 3] = [Self::VideoToolbox, Self::Nvenc, Self::Qsv];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Self::VideoToolbox => "videotoolbox",
+            Self::Nvenc => "nvenc",
+            Self::Qsv => "qsv",
+        }
+    }
+
+    /// FFmpeg encoder name for a software codec, if this family supports it
+    pub fn encoder_for(self, video_codec: &str) -> Option<String> {
+        let family = match video_codec {
+            "libx264" => "h264",
+            "libx265" => "hevc",
+            _ => return None,
+        };
+        Some(format!("{}_{}", family, self.suffix()))
+    }
+
+    /// Quality arguments equivalent to a CRF value
+    fn quality_args(self, crf: u8) -> Vec<String> {
+        match self {
+            Self::Nvenc => vec!["-rc".into(), "vbr".into(), "-cq".into(), crf.to_string()],
+            Self::Qsv => vec!["-global_quality".into(), crf.to_string()],
+            // VideoToolbox quality runs 1-100 with higher meaning better
+            Self::VideoToolbox => {
+                let quality = 100 - (crf.min(MAX_CRF) as u32 * 100 / MAX_CRF as u32);
+                vec!["-q:v".into(), quality.clamp(1, 100).to_string()]
+            }
+        }
+    }
+}
+
+/// Export preset as seen by the frontend and stored for custom presets
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VideoPreset {
+    pub name: String,
+    pub video_codec: String,
+    pub crf: u8,
+    /// x264/x265 speed preset; ignored by hardware encoders
+    pub preset: String,
+    pub audio_codec: String,
+    pub container: String,
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub frame_rate: Option<u32>,
+    #[serde(default)]
+    pub hardware: bool,
+}
+
+impl From<&ExportPreset> for VideoPreset {
+    fn from(preset: &ExportPreset) -> Self {
+        Self {
+            name: preset.name.to_string(),
+            video_codec: preset.video_codec.to_string(),
+            crf: preset.crf,
+            preset: preset.preset.to_string(),
+            audio_codec: preset.audio_codec.to_string(),
+            container: preset.container.to_string(),
+            max_height: preset.max_height,
+            frame_rate: preset.frame_rate,
+            hardware: preset.hardware,
+        }
+    }
+}
+
+impl VideoPreset {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("Preset name cannot be empty".to_string());
+        }
+        if !VIDEO_CODECS.contains(&self.video_codec.as_str()) {
+            return Err(format!("Unsupported video codec: {}", self.video_codec));
+        }
+        if !CONTAINERS.contains(&self.container.as_str()) {
+            return Err(format!("Unsupported container: {}", self.container));
+        }
+        if self.crf > MAX_CRF {
+            return Err(format!("CRF must be between 0 and {}, got {}", MAX_CRF, self.crf));
+        }
+        if self.max_height == Some(0) || self.frame_rate == Some(0) {
+            return Err("Resolution and frame rate must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Hardware encoder the preset would use given the available ones
+    pub fn hardware_encoder(&self, available: &[HardwareEncoder]) -> Option<HardwareEncoder> {
+        if !self.hardware {
+            return None;
+        }
+        HardwareEncoder::ALL
+            .into_iter()
+            .find(|hw| available.contains(hw) && hw.encoder_for(&self.video_codec).is_some())
+    }
+}
+
+/// Video filters and codec arguments for an export
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodingArgs {
+    /// Filters to append to the `-vf` chain
+    pub filters: Vec<String>,
+    pub codec_args: Vec<String>,
+}
+
+impl EncodingArgs {
+    pub fn from_config(config: &ExportConfig) -> Self {
+        Self {
+            filters: Vec::new(),
+            codec_args: vec![
+                "-c:v".into(),
+                config.video_codec.clone(),
+                "-c:a".into(),
+                config.audio_codec.clone(),
+                "-crf".into(),
+                config.quality.clone(),
+            ],
+        }
+    }
+
+    pub fn from_preset(preset: &VideoPreset, available: &[HardwareEncoder]) -> Self {
+        let mut filters = Vec::new();
+        if let Some(height) = preset.max_height {
+            // Never upscale; -2 keeps the width even for yuv420p
+            filters.push(format!("scale=-2:'min({},ih)'", height));
+        }
+        if let Some(fps) = preset.frame_rate {
+            filters.push(format!("fps={}", fps));
+        }
+
+        let mut codec_args = vec!["-c:v".to_string()];
+        match preset.hardware_encoder(available) {
+            Some(hw) => {
+                codec_args.push(hw.encoder_for(&preset.video_codec).unwrap_or_default());
+                codec_args.extend(hw.quality_args(preset.crf));
+            }
+            None => {
+                codec_args.push(preset.video_codec.clone());
+                codec_args.extend(["-crf".to_string(), preset.crf.to_string()]);
+                codec_args.extend(["-preset".to_string(), preset.preset.clone()]);
+            }
+        }
+        codec_args.extend(["-c:a".to_string(), preset.audio_codec.clone()]);
+        Self { filters, codec_args }
+    }
+}
+
+/// Hardware encoder names listed by `ffmpeg -encoders`
+pub fn parse_encoder_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let flags = columns.next()?;
+            let name = columns.next()?;
+            // Video encoder rows look like " V....D h264_nvenc  NVIDIA NVENC H.264 encoder"
+            let is_video = flags.len() == 6 && flags.starts_with('V');
+            let is_hardware = HardwareEncoder::ALL
+                .iter()
+                .any(|hw| name.ends_with(&format!("_{}", hw.suffix())));
+            (is_video && is_hardware).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Whether FFmpeg can actually open an encoder on this machine
+///
+/// Builds often list NVENC or QSV without the hardware or driver being
+/// present, so each candidate encodes a single test frame.
+fn encoder_works(encoder: &str) -> bool {
+    Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i", "color=size=256x256:duration=0.1"])
+        .args(["-frames:v", "1", "-c:v", encoder, "-f", "null", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+fn detect_hardware_encoders() -> Vec<HardwareEncoder> {
+    let listed = Command::new("ffmpeg")
+        .args(["-hide_banner", "-encoders"])
+        .output()
+        .map(|output| parse_encoder_list(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+
+    HardwareEncoder::ALL
+        .into_iter()
+        .filter(|hw| {
+            let h264 = hw.encoder_for("libx264").unwrap_or_default();
+            listed.contains(&h264) && encoder_works(&h264)
+        })
+        .collect()
+}
+
+static HARDWARE_ENCODERS: OnceLock<Vec<HardwareEncoder>> = OnceLock::new();
+
+/// Hardware encoder families usable on this machine, detected once per run
+pub fn hardware_encoders() -> &'static [HardwareEncoder] {
+    HARDWARE_ENCODERS.get_or_init(detect_hardware_encoders)
+}
+
+/// File holding user-defined presets
+pub fn custom_presets_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("milk").join("export_presets.json"))
+}
+
+pub fn load_custom_presets(path: &Path) -> Vec<VideoPreset> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Add or replace a custom preset by name
+pub fn save_custom_preset(path: &Path, preset: VideoPreset) -> Result<(), String> {
+    preset.validate()?;
+    if PRESETS.iter().any(|p| p.name == preset.name) {
+        return Err(format!("Cannot overwrite built-in preset: {}", preset.name));
+    }
+    let mut presets = load_custom_presets(path);
+    presets.retain(|p| p.name != preset.name);
+    presets.push(preset);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create preset directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&presets).map_err(|e| format!("Failed to serialize presets: {}", e))?;
+    std::fs::write(path, content).map_err(|e| format!("Failed to save presets: {}", e))
+}
+
+/// Built-in presets followed by custom ones
+pub fn all_presets(custom_path: Option<&Path>) -> Vec<(VideoPreset, bool)> {
+    let builtin = PRESETS.iter().map(|p| (VideoPreset::from(p), true));
+    let custom = custom_path
+        .map(load_custom_presets)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| (p, false));
+    builtin.chain(custom).collect()
+}
+
+/// Look up a preset by name
+pub fn find_preset(name: &str) -> Result<VideoPreset, String> {
+    all_presets(custom_presets_path().as_deref())
+        .into_iter()
+        .map(|(preset, _)| preset)
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| format!("Unknown export preset: {}", name))
+}
+
+/// Preset listing entry with the encoder it resolves to on this machine
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetInfo {
+    #[serde(flatten)]
+    pub preset: VideoPreset,
+    pub builtin: bool,
+    /// FFmpeg video encoder the preset will use, e.g. "h264_nvenc"
+    pub encoder: String,
+}
+
+/// Tauri command listing export presets and the encoders they will use
+#[tauri::command]
+pub async fn list_export_presets() -> Result<Vec<PresetInfo>, CommandError> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let available = hardware_encoders();
+        all_presets(custom_presets_path().as_deref())
+            .into_iter()
+            .map(|(preset, builtin)| {
+                let encoder = preset
+                    .hardware_encoder(available)
+                    .and_then(|hw| hw.encoder_for(&preset.video_codec))
+                    .unwrap_or_else(|| preset.video_codec.clone());
+                PresetInfo { preset, builtin, encoder }
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| MilkError::Internal(format!("Preset listing failed: {}", e)).report())
+}
+
+/// Tauri command to add or replace a custom export preset
+#[tauri::command]
+pub fn save_custom_preset_command(preset: VideoPreset) -> Result<(), CommandError> {
+    let path = custom_presets_path().ok_or(MilkError::InvalidPath("config directory".to_string()))?;
+    save_custom_preset(&path, preset).map_err(CommandError::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn custom(name: &str) -> VideoPreset {
+        VideoPreset {
+            name: name.to_string(),
+            video_codec: "libx264".to_string(),
+            crf: 20,
+            preset: "medium".to_string(),
+            audio_codec: "aac".to_string(),
+            container: "mkv".to_string(),
+            max_height: Some(480),
+            frame_rate: None,
+            hardware: true,
+        }
+    }
+
+    #[test]
+    fn test_parse_encoder_list() {
+        let output = "Encoders:\n V..... = Video\n ------\n V....D libx264              libx264 H.264\n \
+                      V....D h264_nvenc           NVIDIA NVENC H.264 encoder\n \
+                      V....D hevc_videotoolbox    VideoToolbox H.265 Encoder\n \
+                      A....D aac                  AAC (Advanced Audio Coding)\n";
+        assert_eq!(parse_encoder_list(output), vec!["h264_nvenc", "hevc_videotoolbox"]);
+    }
+
+    #[test]
+    fn test_preset_args_use_hardware_when_available() {
+        let preset = custom("small");
+        let software = EncodingArgs::from_preset(&preset, &[]);
+        assert_eq!(software.filters, vec!["scale=-2:'min(480,ih)'"]);
+        assert!(software.codec_args.windows(2).any(|w| w == ["-c:v", "libx264"]));
+        assert!(software.codec_args.windows(2).any(|w| w == ["-preset", "medium"]));
+
+        let hardware = EncodingArgs::from_preset(&preset, &[HardwareEncoder::Qsv, HardwareEncoder::Nvenc]);
+        assert!(hardware.codec_args.windows(2).any(|w| w == ["-c:v", "h264_nvenc"]));
+        assert!(!hardware.codec_args.contains(&"-crf".to_string()));
+
+        let vp9 = VideoPreset { video_codec: "libvpx-vp9".to_string(), ..custom("web") };
+        assert_eq!(vp9.hardware_encoder(&[HardwareEncoder::Nvenc]), None);
+    }
+
+    #[test]
+    fn test_save_custom_preset() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("presets.json");
+
+        save_custom_preset(&path, custom("small")).unwrap();
+        save_custom_preset(&path, VideoPreset { crf: 30, ..custom("small") }).unwrap();
+        let saved = load_custom_presets(&path);
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].crf, 30);
+
+        assert!(save_custom_preset(&path, custom("mobile_video")).is_err());
+        assert!(save_custom_preset(&path, VideoPreset { container: "avi".to_string(), ..custom("x") }).is_err());
+
+        let all = all_presets(Some(&path));
+        assert_eq!(all.len(), PRESETS.len() + 1);
+        assert!(all.iter().any(|(p, builtin)| p.name == "small" && !builtin));
+    }
+}
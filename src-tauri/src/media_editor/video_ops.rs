@@ -1,8 +1,14 @@
 // Video operations module
 // This module contains video trimming, cropping, and metadata extraction functions
 
+use crate::error::CommandError;
+use crate::media_editor::ffmpeg::{run_ffmpeg, FfmpegProgress};
+use crate::jobs::{app_sink, job_manager, resolve_job_id, JobKind};
+use crate::media_editor::jobs::{ensure_disk_space, estimate_output_bytes};
+use crate::media_editor::presets::{find_preset, hardware_encoders, EncodingArgs};
 use crate::media_editor::types::{CropRect, VideoMetadata, ExportConfig};
 use std::process::Command;
+use std::sync::atomic::AtomicBool;
 use serde_json::Value;
 
 /// Probe video metadata using FFprobe
@@ -70,8 +76,8 @@ pub fn probe_video_metadata(path: &str) -> Result<VideoMetadata, String> {
 
 /// Tauri command to probe video metadata
 #[tauri::command]
-pub async fn probe_video_metadata_command(path: String) -> Result<VideoMetadata, String> {
-    probe_video_metadata(&path)
+pub async fn probe_video_metadata_command(path: String) -> Result<VideoMetadata, CommandError> {
+    probe_video_metadata(&path).map_err(CommandError::from)
 }
 
 /// Trim and optionally crop a video using FFmpeg
@@ -85,6 +91,26 @@ pub fn trim_and_crop_video(
     end_sec: f64,
     crop_rect: Option<CropRect>,
     config: &ExportConfig,
+) -> Result<(), String> {
+    let never_cancelled = AtomicBool::new(false);
+    let encoding = EncodingArgs::from_config(config);
+    trim_and_crop_video_with_progress(input_path, output_path, start_sec, end_sec, crop_rect, &encoding, &never_cancelled, |_| {})
+}
+
+/// `trim_and_crop_video` with progress reporting and cancellation
+///
+/// `encoding` supplies the codec arguments and any filters (scaling, frame
+/// rate) to run after the crop.
+#[allow(clippy::too_many_arguments)]
+pub fn trim_and_crop_video_with_progress(
+    input_path: &str,
+    output_path: &str,
+    start_sec: f64,
+    end_sec: f64,
+    crop_rect: Option<CropRect>,
+    encoding: &EncodingArgs,
+    cancel: &AtomicBool,
+    on_progress: impl FnMut(FfmpegProgress),
 ) -> Result<(), String> {
     // For accurate trimming:
     // 1. Use -ss after -i for frame-accurate seeking (slower but precise)
@@ -92,8 +118,8 @@ pub fn trim_and_crop_video(
     // 3. Add -avoid_negative_ts make_zero for timestamp handling
     let duration = end_sec - start_sec;
     
+    // The runner adds -y to overwrite the output file
     let mut args = vec![
-        "-y".to_string(), // Overwrite output file
         "-i".to_string(),
         input_path.to_string(),
         "-ss".to_string(),
@@ -104,51 +130,71 @@ pub fn trim_and_crop_video(
         "make_zero".to_string(),
     ];
 
-    // Add crop filter if provided
+    // Crop first so scaling applies to the cropped frame
+    let mut filters = Vec::new();
     if let Some(crop) = crop_rect {
-        let crop_filter = format!(
+        filters.push(format!(
             "crop={}:{}:{}:{}",
             crop.width, crop.height, crop.x, crop.y
-        );
+        ));
+    }
+    filters.extend(encoding.filters.iter().cloned());
+    if !filters.is_empty() {
         args.push("-vf".to_string());
-        args.push(crop_filter);
+        args.push(filters.join(","));
     }
 
     // Add codec and quality settings
-    args.push("-c:v".to_string());
-    args.push(config.video_codec.clone());
-    args.push("-c:a".to_string());
-    args.push(config.audio_codec.clone());
-    args.push("-crf".to_string());
-    args.push(config.quality.clone());
+    args.extend(encoding.codec_args.iter().cloned());
 
     args.push(output_path.to_string());
 
     // Execute FFmpeg
-    let output = Command::new("ffmpeg")
-        .args(&args)
-        .output()
-        .map_err(|e| format!("Failed to execute FFmpeg: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("FFmpeg failed: {}", stderr));
+    let result = run_ffmpeg(&args, Some(duration), cancel, on_progress);
+    if result.is_err() {
+        let _ = std::fs::remove_file(output_path);
     }
-
-    Ok(())
+    result
 }
 
 /// Tauri command to trim and crop video
+///
+/// Runs as a media job: progress arrives as `job-progress` events and
+/// passing a `job_id` allows `cancel_job`. A named `preset` (see
+/// `list_export_presets`) takes precedence over `config`.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn trim_and_crop_video_command(
+    app: tauri::AppHandle,
+    job_id: Option<String>,
     input_path: String,
     output_path: String,
     start_sec: f64,
     end_sec: f64,
     crop_rect: Option<CropRect>,
     config: ExportConfig,
-) -> Result<(), String> {
-    trim_and_crop_video(&input_path, &output_path, start_sec, end_sec, crop_rect, &config)
+    preset: Option<String>,
+) -> Result<(), CommandError> {
+    ensure_disk_space(&output_path, estimate_output_bytes(&[input_path.as_str()]))?;
+    job_manager()
+        .run(resolve_job_id(job_id), JobKind::Media, "trim_video", app_sink(app), move |ctx| {
+            let encoding = match preset {
+                Some(name) => EncodingArgs::from_preset(&find_preset(&name)?, hardware_encoders()),
+                None => EncodingArgs::from_config(&config),
+            };
+            trim_and_crop_video_with_progress(
+                &input_path,
+                &output_path,
+                start_sec,
+                end_sec,
+                crop_rect,
+                &encoding,
+                ctx.cancel_flag(),
+                |progress| ctx.report_ffmpeg(&progress),
+            )
+        })
+        .await
+        .map_err(CommandError::from)
 }
 
 #[cfg(test)]
@@ -0,0 +1,303 @@
+// OS media session integration
+// Publishes now-playing info to SMTC (Windows), MPNowPlayingInfoCenter (macOS) and
+// MPRIS (Linux), and forwards media key presses to the frontend player
+
+use crate::library::LibraryScanner;
+use crate::logging::{log_debug, log_info, log_warn};
+use crate::metadata::MetadataExtractor;
+use crate::playlist::Track;
+use crate::spotify::TrackMetadata as StreamingTrackMetadata;
+use serde::{Deserialize, Serialize};
+use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig, SeekDirection};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// Seconds skipped by media keys that seek without a target position
+const SEEK_STEP_SECS: f64 = 5.0;
+
+#[derive(Debug)]
+pub struct MediaSessionError(pub String);
+
+impl std::fmt::Display for MediaSessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Media session unavailable: {}", self.0)
+    }
+}
+
+impl std::error::Error for MediaSessionError {}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// Track shown in the OS media overlay
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_secs: Option<f64>,
+    /// Local file; its embedded artwork is used when no artwork URL is given
+    pub file_path: Option<String>,
+    /// http(s) or file:// URL of the cover image
+    pub artwork_url: Option<String>,
+}
+
+impl From<&Track> for NowPlaying {
+    fn from(track: &Track) -> Self {
+        let text = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+        NowPlaying {
+            title: track.title.clone(),
+            artist: text(&track.artist),
+            album: text(&track.album),
+            duration_secs: Some(track.duration).filter(|d| *d > 0.0),
+            file_path: track.file_path.clone(),
+            artwork_url: None,
+        }
+    }
+}
+
+impl From<&StreamingTrackMetadata> for NowPlaying {
+    fn from(track: &StreamingTrackMetadata) -> Self {
+        Self {
+            title: track.title.clone(),
+            artist: Some(track.artist.clone()),
+            album: Some(track.album.clone()),
+            duration_secs: Some(track.duration_ms as f64 / 1000.0),
+            file_path: None,
+            artwork_url: None,
+        }
+    }
+}
+
+/// Payload of the `media-control` event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum MediaKeyAction {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Stop,
+    /// Relative seek in seconds (negative = backwards)
+    SeekBy { offset_secs: f64 },
+    SeekTo { position_secs: f64 },
+    /// 0.0-1.0
+    SetVolume { volume: f64 },
+}
+
+enum SessionCommand {
+    /// `None` clears the track shown
+    Metadata(Option<NowPlaying>),
+    Playback(PlaybackStatus, Option<f64>),
+}
+
+/// Handle to the media controls, which live on their own thread because
+/// some platform implementations are not `Send`
+pub struct MediaSession {
+    sender: mpsc::Sender<SessionCommand>,
+}
+
+impl MediaSession {
+    /// Create the platform media controls and start forwarding media keys
+    ///
+    /// `hwnd` is the main window handle, required on Windows.
+    pub fn start<R: Runtime>(app: AppHandle<R>, hwnd: Option<usize>) -> Result<Self, MediaSessionError> {
+        let (sender, receiver) = mpsc::channel();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        std::thread::Builder::new()
+            .name("media-session".to_string())
+            .spawn(move || {
+                let config = PlatformConfig {
+                    display_name: "milk",
+                    dbus_name: "milk",
+                    hwnd: hwnd.map(|h| h as *mut std::ffi::c_void),
+                };
+                let mut controls = match MediaControls::new(config) {
+                    Ok(controls) => controls,
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(MediaSessionError(format!("{:?}", e))));
+                        return;
+                    }
+                };
+                let attached = controls.attach(move |event| {
+                    if let Some(action) = map_event(event) {
+                        log_debug("MediaSession", &format!("Media key: {:?}", action));
+                        if let Err(e) = app.emit("media-control", &action) {
+                            log_warn("MediaSession", &format!("Failed to emit media-control event: {}", e));
+                        }
+                    }
+                });
+                if let Err(e) = attached {
+                    let _ = ready_tx.send(Err(MediaSessionError(format!("{:?}", e))));
+                    return;
+                }
+                let _ = ready_tx.send(Ok(()));
+
+                let artwork_dir = dirs::cache_dir().map(|d| d.join("milk").join("now_playing"));
+                let extractor = MetadataExtractor::new();
+                while let Ok(command) = receiver.recv() {
+                    let result = match command {
+                        SessionCommand::Metadata(None) => controls.set_metadata(MediaMetadata::default()),
+                        SessionCommand::Metadata(Some(track)) => {
+                            let cover_url = track.artwork_url.clone().or_else(|| {
+                                cache_local_artwork(artwork_dir.as_ref()?, track.file_path.as_deref()?, &extractor)
+                            });
+                            controls.set_metadata(MediaMetadata {
+                                title: Some(&track.title),
+                                artist: track.artist.as_deref(),
+                                album: track.album.as_deref(),
+                                cover_url: cover_url.as_deref(),
+                                duration: track
+                                    .duration_secs
+                                    .filter(|d| d.is_finite() && *d >= 0.0)
+                                    .map(Duration::from_secs_f64),
+                            })
+                        }
+                        SessionCommand::Playback(status, position) => controls.set_playback(to_playback(status, position)),
+                    };
+                    if let Err(e) = result {
+                        log_warn("MediaSession", &format!("Failed to update media session: {:?}", e));
+                    }
+                }
+            })
+            .map_err(|e| MediaSessionError(e.to_string()))?;
+
+        ready_rx
+            .recv()
+            .map_err(|_| MediaSessionError("media session thread exited".to_string()))??;
+        log_info("MediaSession", "OS media controls attached");
+        Ok(Self { sender })
+    }
+
+    /// Show `track`, or no track at all for `None`
+    pub fn set_track(&self, track: Option<NowPlaying>) {
+        let _ = self.sender.send(SessionCommand::Metadata(track));
+    }
+
+    pub fn set_status(&self, status: PlaybackStatus, position_secs: Option<f64>) {
+        let _ = self.sender.send(SessionCommand::Playback(status, position_secs));
+    }
+}
+
+fn to_playback(status: PlaybackStatus, position_secs: Option<f64>) -> MediaPlayback {
+    let progress = position_secs
+        .filter(|p| p.is_finite() && *p >= 0.0)
+        .map(|p| MediaPosition(Duration::from_secs_f64(p)));
+    match status {
+        PlaybackStatus::Playing => MediaPlayback::Playing { progress },
+        PlaybackStatus::Paused => MediaPlayback::Paused { progress },
+        PlaybackStatus::Stopped => MediaPlayback::Stopped,
+    }
+}
+
+/// Translate an OS media event into a frontend action; window events are ignored
+fn map_event(event: MediaControlEvent) -> Option<MediaKeyAction> {
+    let seek_offset = |direction: SeekDirection, secs: f64| match direction {
+        SeekDirection::Forward => secs,
+        SeekDirection::Backward => -secs,
+    };
+    Some(match event {
+        MediaControlEvent::Play => MediaKeyAction::Play,
+        MediaControlEvent::Pause => MediaKeyAction::Pause,
+        MediaControlEvent::Toggle => MediaKeyAction::Toggle,
+        MediaControlEvent::Next => MediaKeyAction::Next,
+        MediaControlEvent::Previous => MediaKeyAction::Previous,
+        MediaControlEvent::Stop => MediaKeyAction::Stop,
+        MediaControlEvent::Seek(direction) => MediaKeyAction::SeekBy {
+            offset_secs: seek_offset(direction, SEEK_STEP_SECS),
+        },
+        MediaControlEvent::SeekBy(direction, amount) => MediaKeyAction::SeekBy {
+            offset_secs: seek_offset(direction, amount.as_secs_f64()),
+        },
+        MediaControlEvent::SetPosition(MediaPosition(position)) => MediaKeyAction::SeekTo {
+            position_secs: position.as_secs_f64(),
+        },
+        MediaControlEvent::SetVolume(volume) => MediaKeyAction::SetVolume {
+            volume: volume.clamp(0.0, 1.0),
+        },
+        MediaControlEvent::OpenUri(_) | MediaControlEvent::Raise | MediaControlEvent::Quit => return None,
+    })
+}
+
+/// Write a local file's embedded artwork to the cache and return its file:// URL
+///
+/// The file name is derived from the track so the OS does not show a stale
+/// cached cover; the previous cover is removed.
+fn cache_local_artwork(dir: &Path, file_path: &str, extractor: &MetadataExtractor) -> Option<String> {
+    let bytes = extractor.extract_artwork(&crate::path_codec::decode(file_path)).ok()??;
+    fs::create_dir_all(dir).ok()?;
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+    let extension = image::guess_format(&bytes)
+        .ok()
+        .and_then(|f| f.extensions_str().first().copied())
+        .unwrap_or("jpg");
+    let path: PathBuf = dir.join(format!("{}.{}", LibraryScanner::generate_id(file_path), extension));
+    fs::write(&path, &bytes).ok()?;
+    url::Url::from_file_path(&path).ok().map(|u| u.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_transport_events() {
+        assert_eq!(map_event(MediaControlEvent::Toggle), Some(MediaKeyAction::Toggle));
+        assert_eq!(map_event(MediaControlEvent::Next), Some(MediaKeyAction::Next));
+        assert_eq!(map_event(MediaControlEvent::Raise), None);
+    }
+
+    #[test]
+    fn test_map_seek_events() {
+        assert_eq!(
+            map_event(MediaControlEvent::Seek(SeekDirection::Backward)),
+            Some(MediaKeyAction::SeekBy { offset_secs: -SEEK_STEP_SECS })
+        );
+        assert_eq!(
+            map_event(MediaControlEvent::SetPosition(MediaPosition(Duration::from_secs(42)))),
+            Some(MediaKeyAction::SeekTo { position_secs: 42.0 })
+        );
+        assert_eq!(
+            map_event(MediaControlEvent::SetVolume(1.5)),
+            Some(MediaKeyAction::SetVolume { volume: 1.0 })
+        );
+    }
+
+    #[test]
+    fn test_playback_ignores_invalid_position() {
+        assert_eq!(
+            to_playback(PlaybackStatus::Paused, Some(f64::NAN)),
+            MediaPlayback::Paused { progress: None }
+        );
+        assert_eq!(to_playback(PlaybackStatus::Stopped, Some(3.0)), MediaPlayback::Stopped);
+    }
+
+    #[test]
+    fn test_streaming_metadata_conversion() {
+        let track = StreamingTrackMetadata {
+            title: "Song".to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration_ms: 90_500,
+            is_playing: true,
+            progress_ms: Some(1000),
+        };
+        let now_playing = NowPlaying::from(&track);
+        assert_eq!(now_playing.duration_secs, Some(90.5));
+        assert_eq!(now_playing.artist.as_deref(), Some("Artist"));
+    }
+}
@@ -1,12 +1,14 @@
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use lru::LruCache;
 use std::num::NonZeroUsize;
 use id3::TagLike;
+use crate::metadata_cache::{FileStamp, MetadataCacheStats, MetadataDiskCache};
 
 /// Track metadata extracted from audio files
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct TrackMetadata {
     pub title: Option<String>,
     pub artist: Option<String>,
@@ -14,7 +16,18 @@ pub struct TrackMetadata {
     pub year: Option<u32>,
     pub genre: Option<String>,
     pub track_number: Option<u32>,
+    #[serde(default)]
+    pub disc_number: Option<u32>,
     pub duration: Option<u32>,
+    /// Tempo from the BPM tag, or from `track_analysis` when untagged
+    #[serde(default)]
+    pub bpm: Option<f32>,
+    /// Musical key as tagged (e.g. "Am", "8A") or analysed (e.g. "A minor")
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Free-text comment tag (ID3 COMM, Vorbis COMMENT), e.g. cue notes from DJ software
+    #[serde(default)]
+    pub comment: Option<String>,
 }
 
 impl TrackMetadata {
@@ -36,7 +49,11 @@ impl TrackMetadata {
             && self.year.is_none()
             && self.genre.is_none()
             && self.track_number.is_none()
+            && self.disc_number.is_none()
             && self.duration.is_none()
+            && self.bpm.is_none()
+            && self.key.is_none()
+            && self.comment.is_none()
     }
 }
 
@@ -73,9 +90,76 @@ impl std::fmt::Display for MetadataError {
 
 impl std::error::Error for MetadataError {}
 
+/// Whether a file's tags mark it as explicit
+///
+/// Reads the iTunes advisory flag (`ITUNESADVISORY` = 1, as an ID3 TXXX frame
+/// or Vorbis comment) and the Vorbis `EXPLICIT` comment. Unreadable files count
+/// as not explicit.
+pub fn has_explicit_flag(file_path: &Path) -> bool {
+    let is_set = |value: &str| matches!(value.trim().to_lowercase().as_str(), "1" | "true" | "yes");
+    let extension = file_path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => id3::Tag::read_from_path(file_path).is_ok_and(|tag| {
+            tag.extended_texts()
+                .any(|text| text.description.eq_ignore_ascii_case("ITUNESADVISORY") && is_set(&text.value))
+        }),
+        "flac" => metaflac::Tag::read_from_path(file_path).is_ok_and(|tag| {
+            let Some(vorbis) = tag.vorbis_comments() else {
+                return false;
+            };
+            ["ITUNESADVISORY", "EXPLICIT"]
+                .iter()
+                .filter_map(|key| vorbis.get(key))
+                .flatten()
+                .any(|value| is_set(value))
+        }),
+        _ => false,
+    }
+}
+
+/// Tempo from a BPM tag; taggers write both "128" and "127.98"
+fn parse_bpm(text: &str) -> Option<f32> {
+    text.trim().parse::<f32>().ok().filter(|bpm| *bpm > 0.0)
+}
+
+/// The first ID3 comment meant for people; iTunes and others keep machine data
+/// in comments with descriptions like "iTunNORM"
+fn id3_comment(tag: &id3::Tag) -> Option<String> {
+    tag.comments()
+        .filter(|comment| !comment.description.starts_with("iTun"))
+        .find_map(|comment| non_empty(&comment.text))
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Number of files extracted between progress reports in `extract_batch`
+pub const BATCH_CHUNK_SIZE: usize = 64;
+
+/// Outcome of extracting one file in a batch
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct BatchItem {
+    pub path: String,
+    pub metadata: Option<TrackMetadata>,
+    pub error: Option<String>,
+}
+
+/// Partial results reported while a batch runs
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub total: usize,
+    pub items: Vec<BatchItem>,
+}
+
 /// MetadataExtractor handles extracting metadata from audio files
 pub struct MetadataExtractor {
-    cache: Mutex<LruCache<String, TrackMetadata>>,
+    /// In-memory tier; entries carry the file stamp they were read at
+    cache: Mutex<LruCache<String, (Option<FileStamp>, TrackMetadata)>>,
+    /// Optional persistent tier shared across restarts
+    disk_cache: Option<MetadataDiskCache>,
 }
 
 impl MetadataExtractor {
@@ -85,6 +169,7 @@ impl MetadataExtractor {
     pub fn new() -> Self {
         Self {
             cache: Mutex::new(LruCache::new(NonZeroUsize::new(500).unwrap())),
+            disk_cache: None,
         }
     }
     
@@ -92,21 +177,41 @@ impl MetadataExtractor {
     pub fn with_cache_size(size: usize) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(NonZeroUsize::new(size).unwrap())),
+            disk_cache: None,
         }
     }
 
+    /// Back the in-memory cache with a persistent one
+    pub fn with_disk_cache(mut self, disk_cache: MetadataDiskCache) -> Self {
+        self.disk_cache = Some(disk_cache);
+        self
+    }
+
     /// Extract metadata from an audio file
     pub fn extract(&self, file_path: &Path) -> Result<TrackMetadata, MetadataError> {
-        let path_str = file_path.to_string_lossy().to_string();
+        let path_str = crate::path_codec::encode(file_path);
+        let stamp = FileStamp::of(file_path);
 
-        // Check cache first
+        // Check cache first; an entry for an older version of the file is stale
         {
             let mut cache = self.cache.lock().unwrap();
-            if let Some(cached) = cache.get(&path_str) {
-                // Cache hit - record for performance tracking
+            if let Some((cached_stamp, cached)) = cache.get(&path_str) {
+                if *cached_stamp == stamp {
+                    // Cache hit - record for performance tracking
+                    #[cfg(not(test))]
+                    crate::performance::record_cache_hit();
+                    return Ok(cached.clone());
+                }
+            }
+        }
+
+        // Then the disk cache, which survives restarts
+        if let (Some(disk_cache), Some(stamp)) = (&self.disk_cache, stamp) {
+            if let Some(cached) = disk_cache.get(&path_str, stamp) {
                 #[cfg(not(test))]
                 crate::performance::record_cache_hit();
-                return Ok(cached.clone());
+                self.cache.lock().unwrap().put(path_str, (Some(stamp), cached.clone()));
+                return Ok(cached);
             }
         }
         
@@ -114,27 +219,7 @@ impl MetadataExtractor {
         #[cfg(not(test))]
         crate::performance::record_cache_miss();
 
-        // Extract metadata based on file extension
-        let extension = file_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase())
-            .ok_or(MetadataError::UnsupportedFormat)?;
-
-        let mut metadata = match extension.as_str() {
-            "mp3" => self.extract_id3(file_path)?,
-            "flac" => self.extract_flac(file_path)?,
-            "wav" => TrackMetadata {
-                title: None,
-                artist: None,
-                album: None,
-                year: None,
-                genre: None,
-                track_number: None,
-                duration: None,
-            },
-            _ => return Err(MetadataError::UnsupportedFormat),
-        };
+        let mut metadata = self.read_tags(file_path)?;
 
         // Apply fallback parsing if metadata is incomplete
         if metadata.is_empty() || metadata.title.is_none() {
@@ -151,14 +236,36 @@ impl MetadataExtractor {
         }
 
         // Cache the result
+        if let (Some(disk_cache), Some(stamp)) = (&self.disk_cache, stamp) {
+            if let Err(e) = disk_cache.put(&path_str, stamp, &metadata) {
+                crate::logging::log_warn("Metadata", &format!("Failed to persist metadata for {}: {}", file_path.display(), e));
+            }
+        }
         {
             let mut cache = self.cache.lock().unwrap();
-            cache.put(path_str, metadata.clone());
+            cache.put(path_str, (stamp, metadata.clone()));
         }
 
         Ok(metadata)
     }
 
+    /// Read only the file's own tags, bypassing the caches and file name fallback
+    pub fn read_tags(&self, file_path: &Path) -> Result<TrackMetadata, MetadataError> {
+        let extension = file_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|s| s.to_lowercase())
+            .ok_or(MetadataError::UnsupportedFormat)?;
+
+        match extension.as_str() {
+            "mp3" => self.extract_id3(file_path),
+            "flac" => self.extract_flac(file_path),
+            // MP4 tags aren't read; the file name fills in the title in `extract`
+            "wav" | "m4a" | "m4b" => Ok(TrackMetadata::default()),
+            _ => Err(MetadataError::UnsupportedFormat),
+        }
+    }
+
     /// Extract ID3v2 tags from mp3 files
     fn extract_id3(&self, file_path: &Path) -> Result<TrackMetadata, MetadataError> {
         // Try to read ID3 tags, but return empty metadata if no tags exist
@@ -170,7 +277,11 @@ impl MetadataExtractor {
                 year: tag.year().map(|y| y as u32),
                 genre: tag.genre().map(|s| s.to_string()),
                 track_number: tag.track().map(|t| t as u32),
+                disc_number: tag.disc(),
                 duration: tag.duration().map(|d| d as u32),
+                bpm: tag.get("TBPM").and_then(|frame| frame.content().text()).and_then(parse_bpm),
+                key: tag.get("TKEY").and_then(|frame| frame.content().text()).and_then(non_empty),
+                comment: id3_comment(&tag),
             }),
             Err(id3::Error {
                 kind: id3::ErrorKind::NoTag,
@@ -184,7 +295,11 @@ impl MetadataExtractor {
                     year: None,
                     genre: None,
                     track_number: None,
+                    disc_number: None,
                     duration: None,
+                    bpm: None,
+                    key: None,
+                    comment: None,
                 })
             }
             Err(e) => Err(MetadataError::from(e)),
@@ -221,21 +336,41 @@ impl MetadataExtractor {
                 .map(|s| s.to_string()),
             track_number: vorbis
                 .and_then(|v| v.track()),
+            disc_number: vorbis
+                .and_then(|v| v.get("DISCNUMBER"))
+                .and_then(|d| d.first())
+                .and_then(|s| s.split('/').next())
+                .and_then(|s| s.trim().parse::<u32>().ok()),
             duration: None, // FLAC duration requires more complex parsing
+            bpm: vorbis
+                .and_then(|v| v.get("BPM"))
+                .and_then(|b| b.first())
+                .and_then(|s| parse_bpm(s)),
+            key: vorbis
+                .and_then(|v| v.get("INITIALKEY").or_else(|| v.get("KEY")))
+                .and_then(|k| k.first())
+                .and_then(|s| non_empty(s)),
+            comment: vorbis
+                .and_then(|v| v.get("COMMENT").or_else(|| v.get("DESCRIPTION")))
+                .and_then(|c| c.first())
+                .and_then(|s| non_empty(s)),
         })
     }
 
     /// Parse metadata from filename and directory structure as fallback
-    fn parse_fallback(&self, file_path: &Path) -> TrackMetadata {
+    pub(crate) fn parse_fallback(&self, file_path: &Path) -> TrackMetadata {
+        // Names that are not valid Unicode still give a readable, if lossy, title
         let file_name = file_path
             .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
+            .map(|s| s.to_string_lossy())
+            .unwrap_or_default();
+        let file_name = file_name.as_ref();
 
         let parent_dir = file_path
             .parent()
             .and_then(|p| p.file_name())
-            .and_then(|s| s.to_str());
+            .map(|s| s.to_string_lossy());
+        let parent_dir = parent_dir.as_deref();
 
         // Try to parse "Artist - Title" format
         let (artist, title) = if file_name.contains(" - ") {
@@ -259,7 +394,11 @@ impl MetadataExtractor {
             year: None,
             genre: None,
             track_number: None,
+            disc_number: None,
             duration: None,
+            bpm: None,
+            key: None,
+            comment: None,
         }
     }
 
@@ -275,6 +414,7 @@ impl MetadataExtractor {
             "mp3" => self.extract_artwork_id3(file_path),
             "flac" => self.extract_artwork_flac(file_path),
             "wav" => Ok(None), // WAV files typically don't have embedded artwork
+            "m4a" | "m4b" => Ok(None),
             _ => Err(MetadataError::UnsupportedFormat),
         }
     }
@@ -283,13 +423,12 @@ impl MetadataExtractor {
     fn extract_artwork_id3(&self, file_path: &Path) -> Result<Option<Vec<u8>>, MetadataError> {
         let tag = id3::Tag::read_from_path(file_path)?;
 
-        // Look for picture frames
-        for picture in tag.pictures() {
-            // Return the first picture found (usually the cover art)
-            return Ok(Some(picture.data.clone()));
-        }
-
-        Ok(None)
+        // Prefer the front cover, then fall back to the first picture found
+        let picture = tag
+            .pictures()
+            .find(|p| p.picture_type == id3::frame::PictureType::CoverFront)
+            .or_else(|| tag.pictures().next());
+        Ok(picture.map(|p| p.data.clone()))
     }
 
     /// Extract artwork from FLAC tags
@@ -297,26 +436,75 @@ impl MetadataExtractor {
         let tag = metaflac::Tag::read_from_path(file_path)
             .map_err(|e| MetadataError::FlacError(e.to_string()))?;
 
-        // Look for picture blocks
-        for picture in tag.pictures() {
-            // Return the first picture found (usually the cover art)
-            return Ok(Some(picture.data.clone()));
-        }
+        // Prefer the front cover, then fall back to the first picture found
+        let picture = tag
+            .pictures()
+            .find(|p| p.picture_type == metaflac::block::PictureType::CoverFront)
+            .or_else(|| tag.pictures().next());
+        Ok(picture.map(|p| p.data.clone()))
+    }
 
-        Ok(None)
+    /// Extract metadata for many files in parallel
+    ///
+    /// Files are processed in chunks of `BATCH_CHUNK_SIZE` across the rayon
+    /// pool; `on_progress` receives each finished chunk in input order. The
+    /// LRU cache is shared with `extract`, and one bad file never fails the batch.
+    pub fn extract_batch<F>(&self, paths: &[PathBuf], mut on_progress: F) -> Vec<BatchItem>
+    where
+        F: FnMut(BatchProgress),
+    {
+        let mut results = Vec::with_capacity(paths.len());
+        for chunk in paths.chunks(BATCH_CHUNK_SIZE) {
+            let items: Vec<BatchItem> = chunk
+                .par_iter()
+                .map(|path| {
+                    let (metadata, error) = match self.extract(path) {
+                        Ok(metadata) => (Some(metadata), None),
+                        Err(e) => (None, Some(e.to_string())),
+                    };
+                    BatchItem {
+                        path: crate::path_codec::encode(path),
+                        metadata,
+                        error,
+                    }
+                })
+                .collect();
+            results.extend(items.iter().cloned());
+            on_progress(BatchProgress {
+                completed: results.len(),
+                total: paths.len(),
+                items,
+            });
+        }
+        results
     }
 
     /// Check if a file path is in the cache
     pub fn is_cached(&self, file_path: &Path) -> bool {
-        let path_str = file_path.to_string_lossy().to_string();
+        let path_str = crate::path_codec::encode(file_path);
         let cache = self.cache.lock().unwrap();
         cache.contains(&path_str)
     }
 
-    /// Clear the metadata cache
+    /// Clear the metadata cache, including the disk tier
     pub fn clear_cache(&self) {
         let mut cache = self.cache.lock().unwrap();
         cache.clear();
+        if let Some(disk_cache) = &self.disk_cache {
+            if let Err(e) = disk_cache.clear() {
+                crate::logging::log_warn("Metadata", &format!("Failed to clear disk metadata cache: {}", e));
+            }
+        }
+    }
+
+    /// Entry counts and hit rates for both cache tiers
+    pub fn cache_stats(&self) -> Result<MetadataCacheStats, crate::metadata_cache::MetadataCacheError> {
+        let mut stats = match &self.disk_cache {
+            Some(disk_cache) => disk_cache.stats()?,
+            None => MetadataCacheStats::default(),
+        };
+        stats.memory_entries = self.cache.lock().unwrap().len();
+        Ok(stats)
     }
 }
 
@@ -326,6 +514,56 @@ impl Default for MetadataExtractor {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_batch_reports_chunks_in_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut paths = Vec::new();
+        for i in 0..BATCH_CHUNK_SIZE + 5 {
+            let path = temp_dir.path().join(format!("Artist - Song {:03}.wav", i));
+            std::fs::write(&path, b"").unwrap();
+            paths.push(path);
+        }
+        paths.push(temp_dir.path().join("notes.txt"));
+
+        let extractor = MetadataExtractor::new();
+        let mut reports = Vec::new();
+        let results = extractor.extract_batch(&paths, |progress| {
+            reports.push((progress.completed, progress.total, progress.items.len()));
+        });
+
+        let total = paths.len();
+        assert_eq!(reports, vec![(BATCH_CHUNK_SIZE, total, BATCH_CHUNK_SIZE), (total, total, 6)]);
+        assert_eq!(results.len(), total);
+        assert_eq!(results[0].metadata.as_ref().unwrap().title.as_deref(), Some("Song 000"));
+        assert!(results.last().unwrap().error.is_some());
+        assert!(extractor.is_cached(&paths[10]));
+    }
+
+    #[test]
+    fn test_modified_file_is_not_served_from_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("Artist - Song.wav");
+        std::fs::write(&path, b"").unwrap();
+        let disk_cache = MetadataDiskCache::open(&temp_dir.path().join("cache")).unwrap();
+        let extractor = MetadataExtractor::new().with_disk_cache(disk_cache);
+
+        extractor.extract(&path).unwrap();
+        extractor.extract(&path).unwrap();
+        assert_eq!(extractor.cache_stats().unwrap().disk_entries, 1);
+
+        std::fs::rename(&path, temp_dir.path().join("old.wav")).unwrap();
+        std::fs::write(&path, b"RIFF").unwrap();
+        extractor.extract(&path).unwrap();
+        let stats = extractor.cache_stats().unwrap();
+        assert_eq!(stats.invalidations, 1);
+        assert_eq!(stats.disk_entries, 1);
+    }
+}
 
 #[cfg(test)]
 mod property_tests {
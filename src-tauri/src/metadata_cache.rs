@@ -0,0 +1,175 @@
+// Disk-backed metadata cache
+// Keeps parsed tags across restarts so unchanged files are not re-read on every scan.
+// Entries are keyed by path and stamped with the file's mtime and size; a stamp
+// mismatch means the file changed and the entry is dropped.
+
+use crate::metadata::TrackMetadata;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MetadataCacheError {
+    #[error("Cache database error: {0}")]
+    Database(#[from] sled::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Identifies one version of a file on disk
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileStamp {
+    pub mtime_ms: u128,
+    pub size: u64,
+}
+
+impl FileStamp {
+    /// Stamp for the file at `path`, or `None` if it can't be read
+    pub fn of(path: &Path) -> Option<Self> {
+        let meta = fs::metadata(path).ok()?;
+        let mtime_ms = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_millis();
+        Some(Self { mtime_ms, size: meta.len() })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    stamp: FileStamp,
+    metadata: TrackMetadata,
+}
+
+/// Counters reported by `get_metadata_cache_stats`
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct MetadataCacheStats {
+    pub disk_entries: usize,
+    pub disk_size_bytes: u64,
+    pub memory_entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped because the file changed since it was cached
+    pub invalidations: u64,
+}
+
+pub struct MetadataDiskCache {
+    db: sled::Db,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl MetadataDiskCache {
+    /// Open (or create) the cache database in `dir`
+    pub fn open(dir: &Path) -> Result<Self, MetadataCacheError> {
+        Ok(Self {
+            db: sled::open(dir)?,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        })
+    }
+
+    /// Cached metadata for `path` if it was stored for the same `stamp`
+    pub fn get(&self, path: &str, stamp: FileStamp) -> Option<TrackMetadata> {
+        let entry = self
+            .db
+            .get(path.as_bytes())
+            .ok()
+            .flatten()
+            .and_then(|bytes| serde_json::from_slice::<CacheEntry>(&bytes).ok());
+        match entry {
+            Some(entry) if entry.stamp == stamp => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.metadata)
+            }
+            Some(_) => {
+                let _ = self.db.remove(path.as_bytes());
+                self.invalidations.fetch_add(1, Ordering::Relaxed);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, path: &str, stamp: FileStamp, metadata: &TrackMetadata) -> Result<(), MetadataCacheError> {
+        let entry = CacheEntry { stamp, metadata: metadata.clone() };
+        self.db.insert(path.as_bytes(), serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<(), MetadataCacheError> {
+        self.db.clear()?;
+        Ok(())
+    }
+
+    /// Stats for the disk tier; `memory_entries` is filled in by the extractor
+    pub fn stats(&self) -> Result<MetadataCacheStats, MetadataCacheError> {
+        Ok(MetadataCacheStats {
+            disk_entries: self.db.len(),
+            disk_size_bytes: self.db.size_on_disk()?,
+            memory_entries: 0,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Default location of the cache database
+pub fn default_cache_dir() -> Option<std::path::PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("milk").join("metadata_cache"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn metadata(title: &str) -> TrackMetadata {
+        TrackMetadata {
+            title: Some(title.to_string()),
+            artist: None,
+            album: None,
+            year: None,
+            genre: None,
+            track_number: None,
+            disc_number: None,
+            duration: None,
+            bpm: None,
+            key: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let stamp = FileStamp { mtime_ms: 1, size: 10 };
+        {
+            let cache = MetadataDiskCache::open(dir.path()).unwrap();
+            cache.put("/music/a.mp3", stamp, &metadata("A")).unwrap();
+            cache.db.flush().unwrap();
+        }
+        let cache = MetadataDiskCache::open(dir.path()).unwrap();
+        assert_eq!(cache.get("/music/a.mp3", stamp), Some(metadata("A")));
+        assert_eq!(cache.stats().unwrap().hits, 1);
+    }
+
+    #[test]
+    fn test_changed_file_invalidates_entry() {
+        let dir = TempDir::new().unwrap();
+        let cache = MetadataDiskCache::open(dir.path()).unwrap();
+        cache.put("/music/a.mp3", FileStamp { mtime_ms: 1, size: 10 }, &metadata("A")).unwrap();
+
+        assert_eq!(cache.get("/music/a.mp3", FileStamp { mtime_ms: 2, size: 10 }), None);
+        let stats = cache.stats().unwrap();
+        assert_eq!(stats.invalidations, 1);
+        assert_eq!(stats.disk_entries, 0);
+    }
+}
@@ -0,0 +1,503 @@
+// Winamp migration
+// Reads an existing Winamp installation: the main playlist (winamp.m3u8 or
+// winamp.m3u), the media library's local files and saved playlists under
+// Plugins/ml, and the EQ presets in winamp.q1 and any .eqf files beside it.
+// The media library is an NDE database: main.idx lists the offset of each
+// record in main.dat, and each record is a chain of typed fields whose
+// columns are named by the table's first record. Only the filename column is
+// read; tags come from the files themselves. Saving the playlists is left to
+// the caller. Imported library files are remembered in the KV store, so the
+// search index keeps them across rescans even when they live outside the
+// library folder.
+
+use crate::eq_presets::{self, EqImportReport};
+use crate::kv_store::{KvError, KvStore};
+use crate::metadata::MetadataExtractor;
+use crate::playlist::{PlaylistSummary, Track};
+use crate::queue_import;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+/// Name of the playlist holding Winamp's main playlist
+pub const MAIN_PLAYLIST_NAME: &str = "Winamp";
+
+/// Name of the playlist holding the media library's files
+pub const LIBRARY_PLAYLIST_NAME: &str = "Winamp Library";
+
+/// Key prefix for the files each import added to the library, by import name
+pub const IMPORTED_FILES_KEY_PREFIX: &str = "imported_files.";
+
+const NDE_TABLE_SIGNATURE: &[u8] = b"NDETABLE";
+const NDE_INDEX_SIGNATURE: &[u8] = b"NDEINDEX";
+
+/// Column id, field type, data size, next and previous field offsets
+const NDE_FIELD_HEADER_LEN: usize = 14;
+
+const NDE_FIELD_COLUMN: u8 = 0;
+const NDE_FIELD_REDIRECTOR: u8 = 2;
+const NDE_FIELD_STRING: u8 = 3;
+const NDE_FIELD_FILENAME: u8 = 12;
+
+/// Records 0 and 1 hold the column and index definitions
+const NDE_FIRST_DATA_RECORD: usize = 2;
+
+/// Guards against field chains that loop back on themselves
+const NDE_MAX_FIELDS: usize = 256;
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("No Winamp playlist, media library or presets found in {0}")]
+    NotFound(String),
+    #[error("Failed to read Winamp data: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid media library database: {0}")]
+    InvalidDatabase(String),
+}
+
+/// A playlist read from another player, ready to be saved
+#[derive(Debug, Clone)]
+pub struct ImportedPlaylist {
+    pub name: String,
+    pub tracks: Vec<Track>,
+}
+
+/// Everything read from an installation
+#[derive(Debug, Default)]
+pub struct WinampData {
+    /// The main playlist first, then the media library's playlists
+    pub playlists: Vec<ImportedPlaylist>,
+    /// Media library files that still exist
+    pub library: Vec<Track>,
+    pub eq_presets: EqImportReport,
+    /// Entries pointing at files that no longer exist; they are left out
+    pub missing_files: usize,
+    /// Parts of the installation that could not be read
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    /// Playlists created, or existing ones of the same name that were added to
+    pub playlists: Vec<PlaylistSummary>,
+    pub library_tracks: usize,
+    /// Library tracks added to the search index that it did not already hold
+    pub indexed_tracks: usize,
+    pub missing_files: usize,
+    pub eq_presets: EqImportReport,
+    pub warnings: Vec<String>,
+}
+
+/// Remember the files an import added to the library, replacing that import's previous list
+///
+/// `source` names the import, such as its library playlist; paths are
+/// encoded as playlists store them.
+pub fn remember_imported_files(store: &KvStore, source: &str, paths: &[String]) -> Result<(), KvError> {
+    store.set(&format!("{}{}", IMPORTED_FILES_KEY_PREFIX, source), &paths)
+}
+
+/// Every file any import added to the library, without repeats
+pub fn imported_files(store: &KvStore) -> Vec<String> {
+    let mut paths: Vec<String> = store
+        .entries_with_prefix::<Vec<String>>(IMPORTED_FILES_KEY_PREFIX)
+        .into_iter()
+        .flat_map(|(_, paths)| paths)
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Where each part of an installation lives, when present
+struct WinampFiles {
+    main_playlist: Option<PathBuf>,
+    library: Option<(PathBuf, PathBuf)>,
+    playlists_xml: Option<PathBuf>,
+}
+
+impl WinampFiles {
+    fn locate(dir: &Path) -> Self {
+        let existing = |path: PathBuf| path.is_file().then_some(path);
+        let ml = dir.join("Plugins").join("ml");
+        Self {
+            main_playlist: existing(dir.join("winamp.m3u8")).or_else(|| existing(dir.join("winamp.m3u"))),
+            library: existing(ml.join("main.dat")).zip(existing(ml.join("main.idx"))),
+            playlists_xml: existing(ml.join("playlists.xml")),
+        }
+    }
+}
+
+/// Read the installation at `dir` and import its EQ presets into `store`
+///
+/// `progress` gets (files read, total files) while tracks are read. A part of
+/// the installation that cannot be read is noted in the warnings and the rest
+/// is still imported. Stops early, returning what was read so far, once
+/// `cancel` is set.
+pub fn read_installation(
+    dir: &Path,
+    store: &KvStore,
+    extractor: &MetadataExtractor,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<WinampData, MigrationError> {
+    let files = WinampFiles::locate(dir);
+    let has_presets = fs::read_dir(dir)
+        .map(|entries| entries.filter_map(Result::ok).any(|entry| eq_presets::is_preset_file(&entry.path())))
+        .unwrap_or(false);
+    if files.main_playlist.is_none() && files.library.is_none() && files.playlists_xml.is_none() && !has_presets {
+        return Err(MigrationError::NotFound(dir.display().to_string()));
+    }
+
+    let mut data = WinampData::default();
+    let mut playlists: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    if let Some(path) = &files.main_playlist {
+        match queue_import::read_playlist_file(path) {
+            Ok(entries) => playlists.push((MAIN_PLAYLIST_NAME.to_string(), entries)),
+            Err(e) => data.warnings.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    if let Some(path) = &files.playlists_xml {
+        match fs::read(path) {
+            Ok(xml) => {
+                let folder = path.with_file_name("playlists");
+                for (title, filename) in parse_playlists_xml(&decode_text(&xml)) {
+                    let file = folder.join(&filename);
+                    match queue_import::read_playlist_file(&file) {
+                        Ok(entries) => playlists.push((title, entries)),
+                        Err(e) => data.warnings.push(format!("{}: {}", file.display(), e)),
+                    }
+                }
+            }
+            Err(e) => data.warnings.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    let library: Vec<PathBuf> = match &files.library {
+        Some((dat, idx)) => {
+            let read = fs::read(dat)
+                .and_then(|table| Ok((table, fs::read(idx)?)))
+                .map_err(MigrationError::from)
+                .and_then(|(table, index)| read_nde_filenames(&table, &index));
+            match read {
+                // Streams saved in the library are not local files
+                Ok(names) => names.into_iter().filter(|name| !name.contains("://")).map(PathBuf::from).collect(),
+                Err(e) => {
+                    data.warnings.push(format!("{}: {}", dat.display(), e));
+                    Vec::new()
+                }
+            }
+        }
+        None => Vec::new(),
+    };
+
+    if has_presets {
+        match eq_presets::import_presets(store, dir) {
+            Ok(report) => data.eq_presets = report,
+            Err(e) => data.warnings.push(format!("EQ presets: {}", e)),
+        }
+    }
+
+    // Files shared by several playlists and the library are read once
+    let mut tracks: HashMap<PathBuf, Option<Track>> = HashMap::new();
+    let total = playlists.iter().map(|(_, entries)| entries.len()).sum::<usize>() + library.len();
+    let mut completed = 0;
+    let mut resolve = |path: &PathBuf| -> Option<Track> {
+        completed += 1;
+        if completed % 50 == 0 || completed == total {
+            progress(completed as u64, total as u64);
+        }
+        tracks
+            .entry(path.clone())
+            .or_insert_with(|| path.is_file().then(|| queue_import::track_for_file(path, extractor)))
+            .clone()
+    };
+
+    for (name, entries) in playlists {
+        let mut playlist = ImportedPlaylist { name, tracks: Vec::with_capacity(entries.len()) };
+        for entry in &entries {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(data);
+            }
+            match resolve(entry) {
+                Some(track) => playlist.tracks.push(track),
+                None => data.missing_files += 1,
+            }
+        }
+        data.playlists.push(playlist);
+    }
+    for entry in &library {
+        if cancel.load(Ordering::Relaxed) {
+            return Ok(data);
+        }
+        match resolve(entry) {
+            Some(track) => data.library.push(track),
+            None => data.missing_files += 1,
+        }
+    }
+    Ok(data)
+}
+
+/// Values of the `filename` column of every record in an NDE table, in index order
+pub fn read_nde_filenames(dat: &[u8], idx: &[u8]) -> Result<Vec<String>, MigrationError> {
+    if !dat.starts_with(NDE_TABLE_SIGNATURE) {
+        return Err(MigrationError::InvalidDatabase("missing table signature".to_string()));
+    }
+    let offsets = read_nde_index(idx)?;
+    let columns = offsets.first().map(|&offset| read_nde_record(dat, offset)).unwrap_or_default();
+    let filename_column = columns
+        .iter()
+        .find(|field| field.kind == NDE_FIELD_COLUMN && column_name(field.data).eq_ignore_ascii_case("filename"))
+        .map(|field| field.column)
+        .ok_or_else(|| MigrationError::InvalidDatabase("no filename column".to_string()))?;
+
+    Ok(offsets
+        .iter()
+        .skip(NDE_FIRST_DATA_RECORD)
+        .filter_map(|&offset| {
+            read_nde_record(dat, offset)
+                .into_iter()
+                .find(|field| field.column == filename_column)
+                .filter(|field| matches!(field.kind, NDE_FIELD_STRING | NDE_FIELD_FILENAME))
+                .map(|field| nde_string(field.data))
+                .filter(|name| !name.is_empty())
+        })
+        .collect())
+}
+
+/// Record offsets from the primary (first) index of an NDE index file
+fn read_nde_index(idx: &[u8]) -> Result<Vec<usize>, MigrationError> {
+    let entries = idx
+        .strip_prefix(NDE_INDEX_SIGNATURE)
+        .ok_or_else(|| MigrationError::InvalidDatabase("missing index signature".to_string()))?;
+    let count = read_u32(entries, 0).ok_or_else(|| MigrationError::InvalidDatabase("truncated index".to_string()))? as usize;
+    // The record count is followed by the index id, then an (offset, insertion id) pair per record
+    let pairs = entries.get(8..).unwrap_or_default();
+    if pairs.len() / 8 < count {
+        return Err(MigrationError::InvalidDatabase("truncated index".to_string()));
+    }
+    Ok(pairs.chunks_exact(8).take(count).filter_map(|pair| read_u32(pair, 0)).map(|offset| offset as usize).collect())
+}
+
+struct NdeField<'a> {
+    column: u8,
+    kind: u8,
+    data: &'a [u8],
+}
+
+/// Fields of the record starting at `offset`, following redirectors
+fn read_nde_record(dat: &[u8], mut offset: usize) -> Vec<NdeField<'_>> {
+    let mut fields = Vec::new();
+    while offset != 0 && fields.len() < NDE_MAX_FIELDS {
+        let Some(mut field) = read_nde_field(dat, offset) else {
+            break;
+        };
+        if field.1.kind == NDE_FIELD_REDIRECTOR {
+            let Some(target) = read_u32(field.1.data, 0).and_then(|target| read_nde_field(dat, target as usize)) else {
+                break;
+            };
+            field = (field.0, target.1);
+        }
+        fields.push(field.1);
+        offset = field.0;
+    }
+    fields
+}
+
+/// The field at `offset` and the offset of the next one in its record (0 at the end)
+fn read_nde_field(dat: &[u8], offset: usize) -> Option<(usize, NdeField<'_>)> {
+    let header = dat.get(offset..offset.checked_add(NDE_FIELD_HEADER_LEN)?)?;
+    let size = read_u32(header, 2)? as usize;
+    let next = read_u32(header, 6)? as usize;
+    let start = offset + NDE_FIELD_HEADER_LEN;
+    let data = dat.get(start..start.checked_add(size)?)?;
+    Some((next, NdeField { column: header[0], kind: header[1], data }))
+}
+
+/// Column definition: field type, unique flag, name length, then the name
+fn column_name(data: &[u8]) -> String {
+    let len = data.get(2).copied().unwrap_or(0) as usize;
+    data.get(3..3 + len).map(|name| String::from_utf8_lossy(name).to_string()).unwrap_or_default()
+}
+
+/// String field: a byte length, then UTF-16LE text after a BOM or single-byte text
+fn nde_string(data: &[u8]) -> String {
+    let len = data.get(..2).map(|len| u16::from_le_bytes([len[0], len[1]]) as usize).unwrap_or(0);
+    let bytes = data.get(2..2 + len).unwrap_or_default();
+    decode_text(bytes).trim_end_matches('\0').to_string()
+}
+
+/// UTF-16LE text with a byte order mark, or UTF-8 (lossily) without one
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes.strip_prefix(&[0xFF, 0xFE]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => String::from_utf8_lossy(bytes).trim_start_matches('\u{feff}').to_string(),
+    }
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// (title, playlist file name) of every `<playlist>` in the media library's playlists.xml
+fn parse_playlists_xml(xml: &str) -> Vec<(String, String)> {
+    xml.split("<playlist ")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            let filename = xml_attribute(tag, "filename")?;
+            let title = xml_attribute(tag, "title").unwrap_or_else(|| filename.clone());
+            Some((title, filename))
+        })
+        .collect()
+}
+
+fn xml_attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!(" {}=\"", name)).or_else(|| tag.starts_with(&format!("{}=\"", name)).then_some(0))?;
+    let value = &tag[start..];
+    let value = &value[value.find('"')? + 1..];
+    let value = &value[..value.find('"')?];
+    Some(
+        value
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Table and index bytes with a filename column (id 0) and an artist column (id 1)
+    fn nde(records: &[Vec<(u8, u8, Vec<u8>)>]) -> (Vec<u8>, Vec<u8>) {
+        let mut dat = NDE_TABLE_SIGNATURE.to_vec();
+        let mut offsets = Vec::new();
+        for record in records {
+            offsets.push(dat.len() as u32);
+            for (i, (column, kind, data)) in record.iter().enumerate() {
+                let start = dat.len();
+                let next = if i + 1 == record.len() { 0 } else { (start + NDE_FIELD_HEADER_LEN + data.len()) as u32 };
+                dat.extend_from_slice(&[*column, *kind]);
+                dat.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                dat.extend_from_slice(&next.to_le_bytes());
+                dat.extend_from_slice(&0u32.to_le_bytes());
+                dat.extend_from_slice(data);
+            }
+        }
+        let mut idx = NDE_INDEX_SIGNATURE.to_vec();
+        idx.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+        idx.extend_from_slice(&255u32.to_le_bytes());
+        for (i, offset) in offsets.iter().enumerate() {
+            idx.extend_from_slice(&offset.to_le_bytes());
+            idx.extend_from_slice(&(i as u32).to_le_bytes());
+        }
+        (dat, idx)
+    }
+
+    fn column(name: &str) -> Vec<u8> {
+        let mut data = vec![NDE_FIELD_FILENAME, 0, name.len() as u8];
+        data.extend_from_slice(name.as_bytes());
+        data
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut text = vec![0xFF, 0xFE];
+        value.encode_utf16().for_each(|unit| text.extend_from_slice(&unit.to_le_bytes()));
+        let mut data = (text.len() as u16).to_le_bytes().to_vec();
+        data.extend(text);
+        data
+    }
+
+    fn library(files: &[&str]) -> (Vec<u8>, Vec<u8>) {
+        let mut records = vec![
+            vec![(0, NDE_FIELD_COLUMN, column("filename")), (1, NDE_FIELD_COLUMN, column("artist"))],
+            vec![(0, 1, Vec::new())],
+        ];
+        records.extend(files.iter().map(|file| vec![(1, NDE_FIELD_STRING, string("Artist")), (0, NDE_FIELD_FILENAME, string(file))]));
+        nde(&records)
+    }
+
+    #[test]
+    fn test_read_nde_filenames() {
+        let (dat, idx) = library(&["C:\\Music\\Ünïcode.mp3", "D:\\b.flac"]);
+        assert_eq!(read_nde_filenames(&dat, &idx).unwrap(), ["C:\\Music\\Ünïcode.mp3", "D:\\b.flac"]);
+
+        assert!(read_nde_filenames(b"garbage", &idx).is_err());
+        assert!(read_nde_filenames(&dat, &idx[..idx.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn test_parse_playlists_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-16"?><playlists playlists="2">
+            <playlist filename="plf1A.m3u8" title="Rock &amp; Roll" id="{1}" songs="3"/>
+            <playlist title="Untitled" filename="plf2B.m3u8"/>
+            <playlist id="{3}"/></playlists>"#;
+        assert_eq!(
+            parse_playlists_xml(xml),
+            [
+                ("Rock & Roll".to_string(), "plf1A.m3u8".to_string()),
+                ("Untitled".to_string(), "plf2B.m3u8".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imported_files_are_remembered_per_source() {
+        let store = KvStore::in_memory();
+        remember_imported_files(&store, LIBRARY_PLAYLIST_NAME, &["/m/b.mp3".to_string(), "/m/a.mp3".to_string()]).unwrap();
+        remember_imported_files(&store, "iTunes Library", &["/m/a.mp3".to_string()]).unwrap();
+        assert_eq!(imported_files(&store), ["/m/a.mp3", "/m/b.mp3"]);
+
+        remember_imported_files(&store, LIBRARY_PLAYLIST_NAME, &[]).unwrap();
+        assert_eq!(imported_files(&store), ["/m/a.mp3"]);
+    }
+
+    #[test]
+    fn test_read_installation() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(&music).unwrap();
+        for name in ["a.mp3", "b.mp3"] {
+            fs::write(music.join(name), b"").unwrap();
+        }
+        let track = |name: &str| music.join(name).to_string_lossy().to_string();
+        fs::write(dir.path().join("winamp.m3u8"), format!("#EXTM3U\n{}\n{}\n", track("a.mp3"), track("gone.mp3"))).unwrap();
+
+        let ml = dir.path().join("Plugins").join("ml");
+        fs::create_dir_all(ml.join("playlists")).unwrap();
+        let (dat, idx) = library(&[&track("b.mp3"), &track("a.mp3"), "http://radio.example/stream"]);
+        fs::write(ml.join("main.dat"), dat).unwrap();
+        fs::write(ml.join("main.idx"), idx).unwrap();
+        fs::write(ml.join("playlists.xml"), r#"<playlist filename="plf1.m3u8" title="Mix"/><playlist filename="lost.m3u8" title="Lost"/>"#).unwrap();
+        fs::write(ml.join("playlists").join("plf1.m3u8"), format!("{}\n", track("b.mp3"))).unwrap();
+
+        let store = KvStore::in_memory();
+        let mut reported = Vec::new();
+        let data = read_installation(dir.path(), &store, &MetadataExtractor::new(), &AtomicBool::new(false), |done, total| {
+            reported.push((done, total))
+        })
+        .unwrap();
+
+        let names: Vec<_> = data.playlists.iter().map(|p| (p.name.as_str(), p.tracks.len())).collect();
+        assert_eq!(names, [(MAIN_PLAYLIST_NAME, 1), ("Mix", 1)]);
+        assert_eq!(data.library.len(), 2);
+        assert_eq!(data.missing_files, 1);
+        assert_eq!(data.warnings.len(), 1);
+        assert!(data.warnings[0].contains("lost.m3u8"));
+        assert_eq!(reported.last(), Some(&(5, 5)));
+
+        let empty = TempDir::new().unwrap();
+        assert!(matches!(
+            read_installation(empty.path(), &store, &MetadataExtractor::new(), &AtomicBool::new(false), |_, _| {}),
+            Err(MigrationError::NotFound(_))
+        ));
+    }
+}
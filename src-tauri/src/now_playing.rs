@@ -0,0 +1,306 @@
+// Unified now playing
+// Local playback, the streaming services and system audio capture each report
+// what they are playing, and one of them is picked as the now playing track
+// for the UI, the OS media overlay and scrobbling. Sources that know their
+// track beat system audio capture, which hears every source including local
+// playback and so only stands in when nothing else is playing or paused.
+// Then a playing source beats a paused one, whichever started playing or
+// changed track last wins, and the configured priority breaks ties.
+// Streaming state comes from polling and is dropped once stale; a timer
+// checks for that so the pick moves on even when nothing else reports.
+
+use crate::media_session::{NowPlaying, PlaybackStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Polled sources not heard from for this long no longer count
+const STALE_AFTER: Duration = Duration::from_secs(90);
+
+/// How often `expire_stale` should run
+pub const STALE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+const EVENT_BUFFER: usize = 16;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NowPlayingSource {
+    Local,
+    Spotify,
+    Youtube,
+    SystemAudio,
+}
+
+impl NowPlayingSource {
+    /// Sources that only update while polled, as opposed to reporting every change
+    fn is_polled(self) -> bool {
+        matches!(self, NowPlayingSource::Spotify | NowPlayingSource::Youtube)
+    }
+}
+
+/// Highest priority first
+pub const DEFAULT_SOURCE_PRIORITY: [NowPlayingSource; 4] = [
+    NowPlayingSource::Local,
+    NowPlayingSource::Spotify,
+    NowPlayingSource::Youtube,
+    NowPlayingSource::SystemAudio,
+];
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UnifiedNowPlaying {
+    /// `None` when nothing is playing or paused
+    pub source: Option<NowPlayingSource>,
+    /// System audio has no track information
+    pub track: Option<NowPlaying>,
+    pub status: PlaybackStatus,
+    pub position_secs: Option<f64>,
+}
+
+impl Default for UnifiedNowPlaying {
+    fn default() -> Self {
+        Self { source: None, track: None, status: PlaybackStatus::Stopped, position_secs: None }
+    }
+}
+
+impl UnifiedNowPlaying {
+    /// Same source, track and status; position changes alone are not announced
+    fn same_as(&self, other: &UnifiedNowPlaying) -> bool {
+        self.source == other.source && self.track == other.track && self.status == other.status
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SourceReport {
+    track: Option<NowPlaying>,
+    status: PlaybackStatus,
+    position_secs: Option<f64>,
+    updated: Instant,
+    /// Last time the source started playing or changed track
+    active_since: Instant,
+}
+
+/// Latest report from every source and the pick among them
+#[derive(Debug)]
+pub struct NowPlayingArbiter {
+    reports: HashMap<NowPlayingSource, SourceReport>,
+    /// Sources left out are ignored
+    priority: Vec<NowPlayingSource>,
+    current: UnifiedNowPlaying,
+}
+
+impl NowPlayingArbiter {
+    pub fn new(priority: &[NowPlayingSource]) -> Self {
+        Self { reports: HashMap::new(), priority: priority.to_vec(), current: UnifiedNowPlaying::default() }
+    }
+
+    /// Record a source's state; `None` for the track keeps the one reported before
+    ///
+    /// Returns the new pick when its source, track or status changed.
+    pub fn report(
+        &mut self,
+        source: NowPlayingSource,
+        track: Option<NowPlaying>,
+        status: PlaybackStatus,
+        position_secs: Option<f64>,
+        now: Instant,
+    ) -> Option<UnifiedNowPlaying> {
+        let previous = self.reports.remove(&source);
+        let report = match previous {
+            Some(previous) if status != PlaybackStatus::Stopped => {
+                let started = status == PlaybackStatus::Playing && previous.status != PlaybackStatus::Playing;
+                let new_track = track.as_ref().is_some_and(|t| previous.track.as_ref() != Some(t));
+                SourceReport {
+                    track: track.or(previous.track),
+                    status,
+                    position_secs,
+                    updated: now,
+                    active_since: if started || new_track { now } else { previous.active_since },
+                }
+            }
+            _ => SourceReport { track, status, position_secs, updated: now, active_since: now },
+        };
+        self.reports.insert(source, report);
+        self.refresh(now)
+    }
+
+    /// Change the source order; returns the new pick when it changed
+    pub fn set_priority(&mut self, priority: &[NowPlayingSource], now: Instant) -> Option<UnifiedNowPlaying> {
+        self.priority = priority.to_vec();
+        self.refresh(now)
+    }
+
+    /// The current pick, with stale sources dropped
+    pub fn current(&mut self, now: Instant) -> UnifiedNowPlaying {
+        self.refresh(now);
+        self.current.clone()
+    }
+
+    /// Drop stale sources; returns the new pick when that changed it
+    pub fn expire(&mut self, now: Instant) -> Option<UnifiedNowPlaying> {
+        self.refresh(now)
+    }
+
+    fn refresh(&mut self, now: Instant) -> Option<UnifiedNowPlaying> {
+        let unified = self.arbitrate(now);
+        let changed = !unified.same_as(&self.current);
+        self.current = unified;
+        changed.then(|| self.current.clone())
+    }
+
+    fn arbitrate(&self, now: Instant) -> UnifiedNowPlaying {
+        let rank = |status: PlaybackStatus| match status {
+            PlaybackStatus::Playing => 0,
+            PlaybackStatus::Paused => 1,
+            PlaybackStatus::Stopped => 2,
+        };
+        self.priority
+            .iter()
+            .enumerate()
+            .filter_map(|(order, source)| {
+                let report = self.reports.get(source)?;
+                let stale = source.is_polled() && now.duration_since(report.updated) > STALE_AFTER;
+                (!stale && report.status != PlaybackStatus::Stopped).then_some((order, *source, report))
+            })
+            .min_by(|(a_order, _, a), (b_order, _, b)| {
+                a.track
+                    .is_none()
+                    .cmp(&b.track.is_none())
+                    .then(rank(a.status).cmp(&rank(b.status)))
+                    .then(b.active_since.cmp(&a.active_since))
+                    .then(a_order.cmp(b_order))
+            })
+            .map(|(_, source, report)| UnifiedNowPlaying {
+                source: Some(source),
+                track: report.track.clone(),
+                status: report.status,
+                position_secs: report.position_secs,
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn arbiter() -> &'static Mutex<NowPlayingArbiter> {
+    static ARBITER: OnceLock<Mutex<NowPlayingArbiter>> = OnceLock::new();
+    ARBITER.get_or_init(|| Mutex::new(NowPlayingArbiter::new(&DEFAULT_SOURCE_PRIORITY)))
+}
+
+fn event_bus() -> &'static broadcast::Sender<UnifiedNowPlaying> {
+    static EVENTS: OnceLock<broadcast::Sender<UnifiedNowPlaying>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+fn publish(change: Option<UnifiedNowPlaying>) -> Option<UnifiedNowPlaying> {
+    if let Some(unified) = &change {
+        // Fails only when nobody is subscribed
+        let _ = event_bus().send(unified.clone());
+    }
+    change
+}
+
+/// Record a source's state in the global arbiter; returns the pick when it changed
+pub fn report(
+    source: NowPlayingSource,
+    track: Option<NowPlaying>,
+    status: PlaybackStatus,
+    position_secs: Option<f64>,
+) -> Option<UnifiedNowPlaying> {
+    let change = arbiter().lock().unwrap().report(source, track, status, position_secs, Instant::now());
+    publish(change)
+}
+
+/// Apply the configured source order
+pub fn set_priority(priority: &[NowPlayingSource]) -> Option<UnifiedNowPlaying> {
+    let change = arbiter().lock().unwrap().set_priority(priority, Instant::now());
+    publish(change)
+}
+
+pub fn current() -> UnifiedNowPlaying {
+    arbiter().lock().unwrap().current(Instant::now())
+}
+
+/// Drop sources that stopped reporting; returns the pick when that changed it
+pub fn expire_stale() -> Option<UnifiedNowPlaying> {
+    let change = arbiter().lock().unwrap().expire(Instant::now());
+    publish(change)
+}
+
+/// Every change of the pick, for in-process consumers such as scrobbling
+// Nothing in-process listens yet; the UI gets `now-playing-changed` events
+#[allow(dead_code)]
+pub fn subscribe() -> broadcast::Receiver<UnifiedNowPlaying> {
+    event_bus().subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str) -> Option<NowPlaying> {
+        Some(NowPlaying {
+            title: title.to_string(),
+            artist: None,
+            album: None,
+            duration_secs: None,
+            file_path: None,
+            artwork_url: None,
+        })
+    }
+
+    #[test]
+    fn test_arbitration() {
+        use NowPlayingSource::*;
+        use PlaybackStatus::*;
+        let start = Instant::now();
+        let mut arbiter = NowPlayingArbiter::new(&DEFAULT_SOURCE_PRIORITY);
+
+        let picked = arbiter.report(Local, track("Teardrop"), Playing, Some(1.0), start).unwrap();
+        assert_eq!((picked.source, picked.status), (Some(Local), Playing));
+        assert!(arbiter.report(Local, None, Playing, Some(2.0), start).is_none());
+
+        // A playing stream beats paused local playback
+        assert!(arbiter.report(Spotify, track("Windowlicker"), Playing, None, start).is_none());
+        let picked = arbiter.report(Local, None, Paused, Some(2.0), start).unwrap();
+        assert_eq!(picked.source, Some(Spotify));
+
+        // Local resumes after the stream started, so it wins, keeping its track
+        let resumed = start + Duration::from_secs(5);
+        let picked = arbiter.report(Local, None, Playing, Some(2.0), resumed).unwrap();
+        assert_eq!(picked.track, track("Teardrop"));
+
+        // A new stream track is newer activity, even from a lower priority source
+        let skipped = resumed + Duration::from_secs(5);
+        let picked = arbiter.report(Spotify, track("Flim"), Playing, None, skipped).unwrap();
+        assert_eq!(picked.source, Some(Spotify));
+        assert!(arbiter.report(Spotify, None, Playing, None, skipped + Duration::from_secs(1)).is_none());
+
+        // Without local, the stream goes stale unless polled again
+        arbiter.report(Local, None, Stopped, None, skipped);
+        let later = skipped + STALE_AFTER + Duration::from_secs(2);
+        assert_eq!(arbiter.expire(later), Some(UnifiedNowPlaying::default()));
+        assert!(arbiter.expire(later).is_none());
+
+        let picked = arbiter.report(SystemAudio, None, Playing, None, later).unwrap();
+        assert_eq!((picked.source, picked.track), (Some(SystemAudio), None));
+        assert_eq!(arbiter.set_priority(&[Local, Spotify], later), Some(UnifiedNowPlaying::default()));
+    }
+
+    #[test]
+    fn test_system_audio_only_stands_in() {
+        use NowPlayingSource::*;
+        use PlaybackStatus::*;
+        let start = Instant::now();
+        let mut arbiter = NowPlayingArbiter::new(&DEFAULT_SOURCE_PRIORITY);
+        arbiter.report(Local, track("Teardrop"), Playing, None, start);
+
+        // Capture hears local playback too, so it neither takes over nor survives a pause
+        let later = start + Duration::from_secs(1);
+        assert!(arbiter.report(SystemAudio, None, Playing, None, later).is_none());
+        let picked = arbiter.report(Local, None, Paused, None, later).unwrap();
+        assert_eq!((picked.source, picked.status), (Some(Local), Paused));
+
+        let picked = arbiter.report(Local, None, Stopped, None, later).unwrap();
+        assert_eq!((picked.source, picked.track), (Some(SystemAudio), None));
+    }
+}
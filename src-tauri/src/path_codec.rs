@@ -0,0 +1,162 @@
+// Lossless path <-> string conversion
+// Track paths cross into the frontend, the KV store and the metadata caches
+// as strings, but file names are not always valid Unicode: Linux allows any
+// bytes, and Windows allows unpaired UTF-16 surrogates. `to_string_lossy`
+// turns those into U+FFFD, so the file could never be opened again and
+// distinct files collided under one key.
+//
+// Valid paths are passed through unchanged. Any other path is written with a
+// leading NUL, which no real path can contain, followed by the path with `%`
+// as `%25`, each stray byte as `%XX` (Unix) and each unpaired surrogate as
+// `%uXXXX` (Windows). Long Windows paths need nothing special here: the
+// standard library adds the `\\?\` prefix itself when a path exceeds MAX_PATH.
+
+use serde::{Deserialize, Deserializer, Serializer};
+use std::path::{Path, PathBuf};
+
+/// Marks an escaped path
+const ESCAPED: char = '\0';
+
+/// `path` as a string that `decode` turns back into the same path
+pub fn encode(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) => path.to_string(),
+        None => {
+            let mut encoded = String::from(ESCAPED);
+            escape_into(path, &mut encoded);
+            encoded
+        }
+    }
+}
+
+#[cfg(unix)]
+fn escape_into(path: &Path, out: &mut String) {
+    use std::os::unix::ffi::OsStrExt;
+    for chunk in path.as_os_str().as_bytes().utf8_chunks() {
+        push_escaped(chunk.valid(), out);
+        for byte in chunk.invalid() {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+}
+
+#[cfg(windows)]
+fn escape_into(path: &Path, out: &mut String) {
+    use std::os::windows::ffi::OsStrExt;
+    for unit in char::decode_utf16(path.as_os_str().encode_wide()) {
+        match unit {
+            Ok('%') => out.push_str("%25"),
+            Ok(c) => out.push(c),
+            Err(e) => out.push_str(&format!("%u{:04X}", e.unpaired_surrogate())),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn push_escaped(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '%' => out.push_str("%25"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Path for a string from `encode`, or any plain path string
+pub fn decode(encoded: &str) -> PathBuf {
+    match encoded.strip_prefix(ESCAPED) {
+        Some(escaped) => unescape(escaped),
+        None => PathBuf::from(encoded),
+    }
+}
+
+/// Hex number in `text[start..start + len]`
+fn hex_at(text: &str, start: usize, len: usize) -> Option<u32> {
+    let digits = text.get(start..start + len)?;
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+#[cfg(unix)]
+fn unescape(escaped: &str) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(index) = rest.find('%') {
+        bytes.extend_from_slice(&rest.as_bytes()[..index]);
+        match hex_at(rest, index + 1, 2) {
+            Some(byte) => {
+                bytes.push(byte as u8);
+                rest = &rest[index + 3..];
+            }
+            None => {
+                bytes.push(b'%');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+    bytes.extend_from_slice(rest.as_bytes());
+    PathBuf::from(OsString::from_vec(bytes))
+}
+
+#[cfg(windows)]
+fn unescape(escaped: &str) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    let mut units = Vec::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(index) = rest.find('%') {
+        units.extend(rest[..index].encode_utf16());
+        let after = &rest[index + 1..];
+        if let Some(unit) = after.strip_prefix('u').and_then(|_| hex_at(after, 1, 4)) {
+            units.push(unit as u16);
+            rest = &after[5..];
+        } else if let Some(after) = after.strip_prefix("25") {
+            units.push(u16::from(b'%'));
+            rest = after;
+        } else {
+            units.push(u16::from(b'%'));
+            rest = after;
+        }
+    }
+    units.extend(rest.encode_utf16());
+    PathBuf::from(OsString::from_wide(&units))
+}
+
+/// Serde adapter for `PathBuf` fields, via `#[serde(with = "crate::path_codec")]`
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(path))
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    String::deserialize(deserializer).map(|encoded| decode(&encoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_paths_pass_through() {
+        for path in ["/music/Café/100% Pure.mp3", "", "relative/%41.flac"] {
+            assert_eq!(encode(Path::new(path)), path);
+            assert_eq!(decode(path), PathBuf::from(path));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let latin1 = Path::new(OsStr::from_bytes(b"/music/Caf\xe9 50%.mp3"));
+        let other = Path::new(OsStr::from_bytes(b"/music/Caf\xe8 50%.mp3"));
+        let encoded = encode(latin1);
+        assert_eq!(encoded, "\0/music/Caf%E9 50%25.mp3");
+        assert_ne!(encoded, encode(other));
+        assert_eq!(decode(&encoded), latin1);
+    }
+}
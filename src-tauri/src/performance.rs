@@ -1,8 +1,24 @@
 // Performance monitoring utilities
+use std::collections::{BTreeMap, VecDeque};
 use std::time::{Duration, Instant};
 use std::sync::Mutex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Maximum number of snapshots kept in the history file
+pub const MAX_HISTORY_ENTRIES: usize = 2000;
+
+/// How often a snapshot is written while the app is running
+pub const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often the in-memory session series is sampled
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Session samples kept in memory (24 hours at `SAMPLE_INTERVAL`)
+pub const MAX_SESSION_SAMPLES: usize = 2880;
+
 /// Performance metrics for the application
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
@@ -12,6 +28,53 @@ pub struct PerformanceMetrics {
     pub playlist_operations: u64,
     pub memory_usage_bytes: Option<u64>,
     pub peak_memory_bytes: Option<u64>,
+    #[serde(default)]
+    pub last_scan_duration_ms: Option<u64>,
+    /// Per-command timings keyed by command name, fed by `instrumentation`
+    #[serde(default)]
+    pub command_timings: BTreeMap<String, CommandTiming>,
+}
+
+/// Aggregated durations for one Tauri command
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CommandTiming {
+    pub command: String,
+    pub count: u64,
+    pub error_count: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    pub last_ms: f64,
+    pub avg_ms: f64,
+    /// Fraction of calls that returned an error, 0.0-1.0
+    pub error_rate: f64,
+}
+
+impl CommandTiming {
+    fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            count: 0,
+            error_count: 0,
+            total_ms: 0.0,
+            max_ms: 0.0,
+            last_ms: 0.0,
+            avg_ms: 0.0,
+            error_rate: 0.0,
+        }
+    }
+
+    fn record(&mut self, duration: Duration, failed: bool) {
+        let ms = duration.as_secs_f64() * 1000.0;
+        self.count += 1;
+        if failed {
+            self.error_count += 1;
+        }
+        self.total_ms += ms;
+        self.max_ms = self.max_ms.max(ms);
+        self.last_ms = ms;
+        self.avg_ms = self.total_ms / self.count as f64;
+        self.error_rate = self.error_count as f64 / self.count as f64;
+    }
 }
 
 impl PerformanceMetrics {
@@ -23,9 +86,18 @@ impl PerformanceMetrics {
             playlist_operations: 0,
             memory_usage_bytes: None,
             peak_memory_bytes: None,
+            last_scan_duration_ms: None,
+            command_timings: BTreeMap::new(),
         }
     }
 
+    pub fn record_command(&mut self, command: &str, duration: Duration, failed: bool) {
+        self.command_timings
+            .entry(command.to_string())
+            .or_insert_with(|| CommandTiming::new(command))
+            .record(duration, failed);
+    }
+
     pub fn cache_hit_rate(&self) -> f64 {
         let total = self.metadata_cache_hits + self.metadata_cache_misses;
         if total == 0 {
@@ -92,6 +164,33 @@ pub fn record_playlist_operation() {
     }
 }
 
+/// Record how long the last library scan took
+pub fn record_scan_duration(duration: Duration) {
+    let mut metrics = METRICS.lock().unwrap();
+    if let Some(ref mut m) = *metrics {
+        m.last_scan_duration_ms = Some(duration.as_millis() as u64);
+    }
+}
+
+/// Record one command invocation
+pub fn record_command_timing(command: &str, duration: Duration, failed: bool) {
+    let mut metrics = METRICS.lock().unwrap();
+    if let Some(ref mut m) = *metrics {
+        m.record_command(command, duration, failed);
+    }
+}
+
+/// Command timings, slowest total time first
+pub fn get_command_timings() -> Vec<CommandTiming> {
+    let metrics = METRICS.lock().unwrap();
+    let mut timings: Vec<CommandTiming> = metrics
+        .as_ref()
+        .map(|m| m.command_timings.values().cloned().collect())
+        .unwrap_or_default();
+    timings.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    timings
+}
+
 /// Update memory usage metrics
 pub fn update_memory_usage() {
     #[cfg(target_os = "macos")]
@@ -148,6 +247,183 @@ pub fn get_metrics() -> Option<PerformanceMetrics> {
     metrics.clone()
 }
 
+/// A point-in-time summary of the session metrics, persisted across runs
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PerformanceSnapshot {
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub app_version: String,
+    pub startup_time_ms: Option<u64>,
+    pub cache_hit_rate: f64,
+    pub peak_memory_bytes: Option<u64>,
+    pub last_scan_duration_ms: Option<u64>,
+    pub playlist_operations: u64,
+    #[serde(default)]
+    pub memory_usage_bytes: Option<u64>,
+}
+
+impl PerformanceSnapshot {
+    pub fn from_metrics(metrics: &PerformanceMetrics) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            startup_time_ms: metrics.startup_time_ms,
+            cache_hit_rate: metrics.cache_hit_rate(),
+            peak_memory_bytes: metrics.peak_memory_bytes,
+            last_scan_duration_ms: metrics.last_scan_duration_ms,
+            playlist_operations: metrics.playlist_operations,
+            memory_usage_bytes: metrics.memory_usage_bytes,
+        }
+    }
+}
+
+/// Time window for `metrics_history`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum MetricsRange {
+    /// Samples taken every `SAMPLE_INTERVAL` since this launch
+    Session,
+    /// Persisted snapshots from the last N days, across sessions
+    Days(u32),
+}
+
+/// Output format for `export_history`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Rolling samples for the current session
+static SESSION_SAMPLES: Mutex<VecDeque<PerformanceSnapshot>> = Mutex::new(VecDeque::new());
+
+/// Take a sample of the current metrics into the session series
+pub fn record_sample() {
+    let Some(metrics) = get_metrics() else {
+        return;
+    };
+    let mut samples = SESSION_SAMPLES.lock().unwrap();
+    if samples.len() == MAX_SESSION_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(PerformanceSnapshot::from_metrics(&metrics));
+}
+
+/// Samples for `range`, oldest first
+pub fn metrics_history(range: MetricsRange) -> Vec<PerformanceSnapshot> {
+    match range {
+        MetricsRange::Session => SESSION_SAMPLES.lock().unwrap().iter().cloned().collect(),
+        MetricsRange::Days(days) => get_history(days),
+    }
+}
+
+const CSV_HEADER: &str = "timestamp,app_version,startup_time_ms,cache_hit_rate,memory_usage_bytes,peak_memory_bytes,last_scan_duration_ms,playlist_operations";
+
+/// Render snapshots as CSV with an RFC 3339 timestamp column; missing values are empty
+pub fn history_to_csv(history: &[PerformanceSnapshot]) -> String {
+    let opt = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for s in history {
+        csv.push_str(&format!(
+            "{},{},{},{:.4},{},{},{},{}\n",
+            s.timestamp.to_rfc3339(),
+            s.app_version,
+            opt(s.startup_time_ms),
+            s.cache_hit_rate,
+            opt(s.memory_usage_bytes),
+            opt(s.peak_memory_bytes),
+            opt(s.last_scan_duration_ms),
+            s.playlist_operations,
+        ));
+    }
+    csv
+}
+
+/// Write the samples for `range` to `path`, returning how many were written
+pub fn export_history(range: MetricsRange, format: ExportFormat, path: &Path) -> std::io::Result<usize> {
+    let history = metrics_history(range);
+    let contents = match format {
+        ExportFormat::Csv => history_to_csv(&history),
+        ExportFormat::Json => serde_json::to_string_pretty(&history)?,
+    };
+    fs::write(path, contents)?;
+    Ok(history.len())
+}
+
+/// Get the history file path in the AppData directory
+fn get_history_path() -> Option<PathBuf> {
+    let milk_dir = dirs::config_dir()?.join("milk");
+    fs::create_dir_all(&milk_dir).ok()?;
+    Some(milk_dir.join("perf_history.json"))
+}
+
+/// Read all snapshots from a history file; a missing or corrupted file is empty
+fn read_history(path: &Path) -> Vec<PerformanceSnapshot> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Append a snapshot to the history file, dropping the oldest past the limit
+pub fn append_snapshot(path: &Path, snapshot: PerformanceSnapshot) -> std::io::Result<()> {
+    let mut history = read_history(path);
+    history.push(snapshot);
+    if history.len() > MAX_HISTORY_ENTRIES {
+        let excess = history.len() - MAX_HISTORY_ENTRIES;
+        history.drain(..excess);
+    }
+    let json = serde_json::to_string(&history)?;
+    fs::write(path, json)
+}
+
+/// Load snapshots from the last `days` days, oldest first
+pub fn load_history(path: &Path, days: u32) -> Vec<PerformanceSnapshot> {
+    let cutoff = Utc::now() - chrono::Duration::days(days as i64);
+    read_history(path)
+        .into_iter()
+        .filter(|s| s.timestamp >= cutoff)
+        .collect()
+}
+
+/// Persist a snapshot of the current session metrics
+pub fn record_snapshot() -> std::io::Result<()> {
+    let Some(metrics) = get_metrics() else {
+        return Ok(());
+    };
+    let path = get_history_path()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No config directory"))?;
+    append_snapshot(&path, PerformanceSnapshot::from_metrics(&metrics))
+}
+
+/// Get persisted snapshots from the last `days` days
+pub fn get_history(days: u32) -> Vec<PerformanceSnapshot> {
+    get_history_path()
+        .map(|path| load_history(&path, days))
+        .unwrap_or_default()
+}
+
+/// Spawn a background thread that samples metrics into the session series
+/// and persists a snapshot every `SNAPSHOT_INTERVAL`
+pub fn start_snapshot_thread() {
+    let samples_per_snapshot = (SNAPSHOT_INTERVAL.as_secs() / SAMPLE_INTERVAL.as_secs()).max(1);
+    std::thread::spawn(move || {
+        let mut tick: u64 = 0;
+        loop {
+            record_sample();
+            if tick.is_multiple_of(samples_per_snapshot) {
+                if let Err(e) = record_snapshot() {
+                    crate::logging::log_warn("Performance", &format!("Failed to save metrics snapshot: {}", e));
+                }
+            }
+            tick += 1;
+            std::thread::sleep(SAMPLE_INTERVAL);
+        }
+    });
+}
+
 /// Timer for measuring operation duration
 pub struct Timer {
     start: Instant,
@@ -195,6 +471,21 @@ mod tests {
         assert_eq!(metrics.cache_hit_rate(), 0.8);
     }
 
+    #[test]
+    fn test_command_timing_aggregation() {
+        let mut metrics = PerformanceMetrics::new();
+        metrics.record_command("scan_library", Duration::from_millis(30), false);
+        metrics.record_command("scan_library", Duration::from_millis(10), true);
+
+        let timing = &metrics.command_timings["scan_library"];
+        assert_eq!(timing.count, 2);
+        assert_eq!(timing.error_count, 1);
+        assert_eq!(timing.error_rate, 0.5);
+        assert!((timing.avg_ms - 20.0).abs() < 1e-6);
+        assert!((timing.max_ms - 30.0).abs() < 1e-6);
+        assert!((timing.last_ms - 10.0).abs() < 1e-6);
+    }
+
     #[test]
     fn test_timer() {
         let timer = Timer::new("test_operation");
@@ -202,4 +493,70 @@ mod tests {
         let elapsed = timer.elapsed();
         assert!(elapsed >= Duration::from_millis(10));
     }
+
+    fn snapshot_at(timestamp: DateTime<Utc>) -> PerformanceSnapshot {
+        let mut snapshot = PerformanceSnapshot::from_metrics(&PerformanceMetrics::new());
+        snapshot.timestamp = timestamp;
+        snapshot
+    }
+
+    #[test]
+    fn test_history_round_trip_and_day_filter() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("perf_history.json");
+
+        append_snapshot(&path, snapshot_at(Utc::now() - chrono::Duration::days(10))).unwrap();
+        append_snapshot(&path, snapshot_at(Utc::now() - chrono::Duration::hours(1))).unwrap();
+
+        assert_eq!(load_history(&path, 30).len(), 2);
+        assert_eq!(load_history(&path, 1).len(), 1);
+    }
+
+    #[test]
+    fn test_history_is_capped() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("perf_history.json");
+
+        let mut history: Vec<_> = (0..MAX_HISTORY_ENTRIES).map(|_| snapshot_at(Utc::now())).collect();
+        history[0].playlist_operations = 42;
+        fs::write(&path, serde_json::to_string(&history).unwrap()).unwrap();
+
+        append_snapshot(&path, snapshot_at(Utc::now())).unwrap();
+        let history = read_history(&path);
+        assert_eq!(history.len(), MAX_HISTORY_ENTRIES);
+        // The oldest entry was dropped
+        assert_eq!(history[0].playlist_operations, 0);
+    }
+
+    #[test]
+    fn test_history_to_csv() {
+        let mut snapshot = snapshot_at(Utc::now());
+        snapshot.startup_time_ms = Some(120);
+        snapshot.cache_hit_rate = 0.5;
+        let csv = history_to_csv(&[snapshot]);
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADER);
+        let fields: Vec<&str> = lines[1].split(',').collect();
+        assert_eq!(fields.len(), CSV_HEADER.split(',').count());
+        assert_eq!(fields[2], "120");
+        assert_eq!(fields[3], "0.5000");
+        assert_eq!(fields[4], "");
+    }
+
+    #[test]
+    fn test_old_snapshots_without_memory_usage_load() {
+        let json = r#"[{"timestamp":0,"app_version":"0.1.0","startup_time_ms":null,"cache_hit_rate":0.0,"peak_memory_bytes":null,"last_scan_duration_ms":null,"playlist_operations":0}]"#;
+        let history: Vec<PerformanceSnapshot> = serde_json::from_str(json).unwrap();
+        assert_eq!(history[0].memory_usage_bytes, None);
+    }
+
+    #[test]
+    fn test_corrupted_history_is_empty() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("perf_history.json");
+        fs::write(&path, "not json").unwrap();
+        assert!(load_history(&path, 7).is_empty());
+    }
 }
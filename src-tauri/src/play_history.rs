@@ -0,0 +1,76 @@
+// Play history
+// Play counts and last-played times per local file, kept in the KV store.
+// Feeds the "Recently Played" and "Top 25 Most Played" system playlists.
+
+use crate::kv_store::{KvError, KvStore};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Key prefix for per-file play stats in the KV store
+pub const PLAY_KEY_PREFIX: &str = "plays.";
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayStats {
+    pub count: u32,
+    /// Unix milliseconds of the latest play
+    pub last_played: i64,
+}
+
+/// KV store key holding the play stats of a file
+pub fn play_key(file_path: &str) -> String {
+    format!("{}{}", PLAY_KEY_PREFIX, file_path)
+}
+
+/// Stored stats for a file; never-played files have a zero count
+pub fn get_play_stats(store: &KvStore, file_path: &str) -> PlayStats {
+    store.get(&play_key(file_path)).unwrap_or_default()
+}
+
+/// Count one play of `file_path` at `played_at`
+pub fn record_play(store: &KvStore, file_path: &str, played_at: DateTime<Utc>) -> Result<PlayStats, KvError> {
+    let mut stats = get_play_stats(store, file_path);
+    stats.count = stats.count.saturating_add(1);
+    stats.last_played = played_at.timestamp_millis();
+    store.set(&play_key(file_path), &stats)?;
+    Ok(stats)
+}
+
+/// Carry over stats from another player, keeping the higher count and the later play
+///
+/// Importing the same history twice doesn't count its plays twice.
+pub fn merge_play_stats(store: &KvStore, file_path: &str, imported: PlayStats) -> Result<PlayStats, KvError> {
+    let current = get_play_stats(store, file_path);
+    let stats = PlayStats {
+        count: current.count.max(imported.count),
+        last_played: current.last_played.max(imported.last_played),
+    };
+    store.set(&play_key(file_path), &stats)?;
+    Ok(stats)
+}
+
+/// Every played file with its stats
+pub fn all_play_stats(store: &KvStore) -> Vec<(String, PlayStats)> {
+    store.entries_with_prefix(PLAY_KEY_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_play() {
+        let store = KvStore::in_memory();
+        store.set("rating./music/a.mp3", &3).unwrap();
+        let first = Utc::now();
+        record_play(&store, "/music/a.mp3", first).unwrap();
+        let stats = record_play(&store, "/music/a.mp3", first + chrono::Duration::seconds(200)).unwrap();
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.last_played, first.timestamp_millis() + 200_000);
+        record_play(&store, "/music/b.mp3", first).unwrap();
+
+        let all = all_play_stats(&store);
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0], ("/music/a.mp3".to_string(), stats));
+        assert_eq!(get_play_stats(&store, "/music/c.mp3"), PlayStats::default());
+    }
+}
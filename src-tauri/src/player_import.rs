@@ -0,0 +1,384 @@
+// Playlist and rating import from other players
+// foobar2000 keeps playlists as binary .fpl files, read by picking out the
+// file:// locations they store, or since 1.4 as plain .fplite lists, and
+// exports M3U8. The playlist files are numbered; their names are listed in
+// an index.txt beside them. MusicBee keeps static playlists as binary .mbp
+// files, read by picking out the length-prefixed paths they store, and can
+// save an iTunes-compatible library XML with ratings and play counts, which
+// goes through the iTunes importer. Otherwise both players keep ratings in
+// their own databases, so files without a rating in milkline take the rating
+// either player wrote to their tags. foobar2000's foo_playcount database is
+// not read, so its ratings and play counts only come across once written to
+// the files with its "Write statistics to file tags" option.
+
+use crate::itunes_import::{self, ItunesError};
+use crate::kv_store::{KvError, KvStore};
+use crate::metadata::MetadataExtractor;
+use crate::migration::ImportedPlaylist;
+use crate::playlist::{PlaylistSummary, Track};
+use crate::queue_import;
+use crate::ratings::{self, RatingError, TrackRating};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+/// Library export MusicBee writes when "iTunes compatible XML" is enabled
+const MUSICBEE_LIBRARY_XML: &str = "iTunes Music Library.xml";
+
+/// Names of the numbered playlist files in a foobar2000 playlist folder
+const FOOBAR_PLAYLIST_INDEX: &str = "index.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerKind {
+    Foobar2000,
+    MusicBee,
+}
+
+impl PlayerKind {
+    /// Playlist folders inside the player's profile folder
+    fn playlist_folders(self) -> &'static [&'static str] {
+        match self {
+            PlayerKind::Foobar2000 => &["playlists-v2.0", "playlists-v1.4", "playlists"],
+            PlayerKind::MusicBee => &["Playlists"],
+        }
+    }
+
+    fn reads(self, extension: &str) -> bool {
+        match self {
+            PlayerKind::Foobar2000 => matches!(extension, "fpl" | "fplite" | "m3u" | "m3u8"),
+            PlayerKind::MusicBee => matches!(extension, "mbp" | "m3u" | "m3u8"),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum PlayerImportError {
+    #[error("No {0:?} playlists found in {1}")]
+    NotFound(PlayerKind, String),
+    #[error("Failed to read playlists: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Store(#[from] KvError),
+    #[error(transparent)]
+    Rating(#[from] RatingError),
+    /// MusicBee's library XML
+    #[error(transparent)]
+    Library(#[from] ItunesError),
+}
+
+#[derive(Debug, Default)]
+pub struct PlayerData {
+    pub playlists: Vec<ImportedPlaylist>,
+    /// Ratings set on files milkline had not rated yet
+    pub ratings: usize,
+    pub play_counts: usize,
+    /// Playlist entries pointing at files that no longer exist; they are left out
+    pub missing_files: usize,
+    /// Files that could not be read
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerImportReport {
+    pub kind: PlayerKind,
+    /// Playlists created, or existing ones of the same name that were added to
+    pub playlists: Vec<PlaylistSummary>,
+    pub ratings: usize,
+    pub play_counts: usize,
+    pub missing_files: usize,
+    pub warnings: Vec<String>,
+}
+
+/// Read playlists and ratings from a playlist file, a playlist folder or the
+/// player's profile folder
+///
+/// For MusicBee, a library XML in the folder (or given as `path`) supplies the
+/// playlists, ratings and play counts, and the .mbp files are only read when
+/// there is none. foobar2000 playlists take their names from the folder's
+/// index.txt, and ratings only from file tags. `progress` gets (entries read,
+/// total entries). Stops early, returning what was read so far, once `cancel`
+/// is set.
+pub fn read_player(
+    kind: PlayerKind,
+    path: &Path,
+    store: &KvStore,
+    extractor: &MetadataExtractor,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<PlayerData, PlayerImportError> {
+    let mut data = PlayerData::default();
+    if kind == PlayerKind::MusicBee {
+        let xml = if path.is_dir() { path.join(MUSICBEE_LIBRARY_XML) } else { path.to_path_buf() };
+        if xml.extension().is_some_and(|e| e.eq_ignore_ascii_case("xml")) && xml.is_file() {
+            match read_library_xml(&xml, store, extractor, cancel, &mut progress) {
+                Ok(library) => return Ok(library),
+                Err(e) => data.warnings.push(format!("{}: {}", xml.display(), e)),
+            }
+        }
+    }
+
+    let files = playlist_files(kind, path)?;
+    if files.is_empty() {
+        return Err(PlayerImportError::NotFound(kind, path.display().to_string()));
+    }
+    let names = if kind == PlayerKind::Foobar2000 { foobar_playlist_names(&files) } else { HashMap::new() };
+    let mut playlists = Vec::with_capacity(files.len());
+    for file in &files {
+        match read_playlist(file) {
+            Ok(entries) => {
+                let name = names
+                    .get(file)
+                    .cloned()
+                    .unwrap_or_else(|| file.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default());
+                playlists.push((name, entries));
+            }
+            Err(e) => data.warnings.push(format!("{}: {}", file.display(), e)),
+        }
+    }
+
+    // Files in several playlists are read, and their rating looked up, once
+    let mut tracks: HashMap<PathBuf, Option<Track>> = HashMap::new();
+    let total = playlists.iter().map(|(_, entries)| entries.len()).sum::<usize>() as u64;
+    let mut done = 0;
+    for (name, entries) in playlists {
+        let mut playlist = ImportedPlaylist { name, tracks: Vec::with_capacity(entries.len()) };
+        for entry in entries {
+            if cancel.load(Ordering::Relaxed) {
+                return Ok(data);
+            }
+            done += 1;
+            if done % 50 == 0 || done == total {
+                progress(done, total);
+            }
+            if !tracks.contains_key(&entry) {
+                let track = entry.is_file().then(|| queue_import::track_for_file(&entry, extractor));
+                if track.is_some() && rating_from_tags(store, &entry)? {
+                    data.ratings += 1;
+                }
+                tracks.insert(entry.clone(), track);
+            }
+            match &tracks[&entry] {
+                Some(track) => playlist.tracks.push(track.clone()),
+                None => data.missing_files += 1,
+            }
+        }
+        data.playlists.push(playlist);
+    }
+    Ok(data)
+}
+
+fn read_library_xml(
+    xml: &Path,
+    store: &KvStore,
+    extractor: &MetadataExtractor,
+    cancel: &AtomicBool,
+    progress: impl FnMut(u64, u64),
+) -> Result<PlayerData, PlayerImportError> {
+    let library = itunes_import::parse_library(&String::from_utf8_lossy(&fs::read(xml)?), None)?;
+    let imported = itunes_import::import_library(store, &library, extractor, cancel, progress)?;
+    Ok(PlayerData {
+        playlists: imported.playlists,
+        ratings: imported.ratings,
+        play_counts: imported.play_counts,
+        missing_files: imported.missing_files,
+        warnings: Vec::new(),
+    })
+}
+
+/// Store the rating in a file's tags, unless milkline already has one; true if stored
+fn rating_from_tags(store: &KvStore, file: &Path) -> Result<bool, PlayerImportError> {
+    let file_path = crate::path_codec::encode(file);
+    if ratings::get_rating(store, &file_path) != TrackRating::default() {
+        return Ok(false);
+    }
+    let Some(stars) = ratings::read_rating_tag(file) else {
+        return Ok(false);
+    };
+    ratings::set_rating(store, &file_path, TrackRating { stars, loved: false })?;
+    Ok(true)
+}
+
+/// `path` itself, or the playlists directly in it and in the player's playlist folders, sorted
+fn playlist_files(kind: PlayerKind, path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let folders = std::iter::once(path.to_path_buf()).chain(kind.playlist_folders().iter().map(|folder| path.join(folder)));
+    let mut files = Vec::new();
+    for folder in folders.filter(|folder| folder.is_dir()) {
+        files.extend(
+            fs::read_dir(folder)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| file.extension().is_some_and(|e| kind.reads(&e.to_string_lossy().to_lowercase()))),
+        );
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Playlist names from the index.txt beside each file, by playlist file
+///
+/// Each line pairs a playlist file, with or without its extension, with the
+/// name shown in foobar2000, as `00000001.fplite:Name`.
+fn foobar_playlist_names(files: &[PathBuf]) -> HashMap<PathBuf, String> {
+    let mut indexes: HashMap<&Path, HashMap<String, String>> = HashMap::new();
+    let mut names = HashMap::new();
+    for file in files {
+        let Some(folder) = file.parent() else {
+            continue;
+        };
+        let index = indexes.entry(folder).or_insert_with(|| {
+            let Ok(content) = fs::read_to_string(folder.join(FOOBAR_PLAYLIST_INDEX)) else {
+                return HashMap::new();
+            };
+            content
+                .lines()
+                .filter_map(|line| line.trim_start_matches('\u{feff}').split_once(':'))
+                .map(|(file, name)| {
+                    let stem = Path::new(file.trim()).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                    (stem, name.trim().to_string())
+                })
+                .filter(|(stem, name)| !stem.is_empty() && !name.is_empty())
+                .collect()
+        });
+        let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        if let Some(name) = index.get(&stem) {
+            names.insert(file.clone(), name.clone());
+        }
+    }
+    names
+}
+
+/// Local file entries of one playlist file, in order
+fn read_playlist(file: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let extension = file.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "fpl" => Ok(fpl_paths(&fs::read(file)?)),
+        "mbp" => Ok(mbp_paths(&fs::read(file)?)),
+        "fplite" => {
+            let content = fs::read_to_string(file)?;
+            Ok(content.lines().filter_map(|line| line.trim().strip_prefix("file://")).map(PathBuf::from).collect())
+        }
+        _ => queue_import::read_playlist_file(file),
+    }
+}
+
+/// Locations in a foobar2000 .fpl: NUL-terminated strings, each track's prefixed with file://
+fn fpl_paths(data: &[u8]) -> Vec<PathBuf> {
+    data.split(|&b| b == 0)
+        .filter_map(|text| std::str::from_utf8(text).ok()?.strip_prefix("file://"))
+        .filter(|path| is_absolute_location(path))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Locations in a MusicBee .mbp: UTF-8 strings each preceded by its byte
+/// length as a 7-bit varint, among other binary fields
+fn mbp_paths(data: &[u8]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let (len, prefix) = match (data[i], data.get(i + 1)) {
+            (low, Some(&high)) if low & 0x80 != 0 && high & 0x80 == 0 => ((low & 0x7F) as usize | (high as usize) << 7, 2),
+            (len, _) if len & 0x80 == 0 => (len as usize, 1),
+            _ => (0, 1),
+        };
+        let text = data.get(i + prefix..i + prefix + len).and_then(|text| std::str::from_utf8(text).ok());
+        match text {
+            Some(text) if len > 3 && is_absolute_location(text) && !text.chars().any(char::is_control) => {
+                paths.push(PathBuf::from(text));
+                i += prefix + len;
+            }
+            _ => i += 1,
+        }
+    }
+    paths
+}
+
+/// A drive path (C:\...), a UNC path (\\server\...) or a Unix path
+fn is_absolute_location(path: &str) -> bool {
+    match path.as_bytes() {
+        [drive, b':', b'\\' | b'/', ..] => drive.is_ascii_alphabetic(),
+        [b'\\', b'\\', ..] | [b'/', ..] => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn dotnet_string(text: &str) -> Vec<u8> {
+        let len = text.len();
+        let mut bytes = if len < 0x80 { vec![len as u8] } else { vec![(len as u8) | 0x80, (len >> 7) as u8] };
+        bytes.extend_from_slice(text.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_binary_playlists() {
+        let mut fpl = b"\xE1\xA0\x9C\x91\xF8\x3C\x77\x42\x85\x2C\x3B\xCC\x14\x01\xD3\xF2\x40\x00\x00\x00".to_vec();
+        fpl.extend_from_slice(b"file://C:\\Music\\a.mp3\0Artist\0file:///home/me/b.flac\0\x01\0");
+        assert_eq!(fpl_paths(&fpl), [PathBuf::from("C:\\Music\\a.mp3"), PathBuf::from("/home/me/b.flac")]);
+
+        let long = format!("D:\\Music\\{}.mp3", "x".repeat(150));
+        let mut mbp = b"mbp\x01\x02".to_vec();
+        for text in ["C:\\Music\\A.mp3", "Some title", &long, "\\\\nas\\share\\c.ogg"] {
+            mbp.extend(dotnet_string(text));
+            mbp.extend_from_slice(&[0x00, 0x05, 0x00, 0x00]);
+        }
+        assert_eq!(
+            mbp_paths(&mbp),
+            [PathBuf::from("C:\\Music\\A.mp3"), PathBuf::from(&long), PathBuf::from("\\\\nas\\share\\c.ogg")]
+        );
+    }
+
+    #[test]
+    fn test_read_foobar_profile() {
+        let dir = TempDir::new().unwrap();
+        let music = dir.path().join("music");
+        fs::create_dir_all(&music).unwrap();
+        fs::write(music.join("a.mp3"), b"").unwrap();
+        let a = music.join("a.mp3").to_string_lossy().to_string();
+        let playlists = dir.path().join("playlists-v1.4");
+        fs::create_dir_all(&playlists).unwrap();
+        fs::write(playlists.join("00000001.fplite"), format!("file://{}\nfile://{}/gone.mp3\n", a, music.display())).unwrap();
+        fs::write(playlists.join("00000002.fplite"), format!("file://{}\n", a)).unwrap();
+        fs::write(playlists.join("index.txt"), "00000001.fplite:Road Trip\n").unwrap();
+        fs::write(dir.path().join("Favourites.m3u8"), format!("#EXTM3U\n{}\n", a)).unwrap();
+        fs::write(dir.path().join("notes.txt"), b"ignored").unwrap();
+
+        let store = KvStore::in_memory();
+        let data = read_player(PlayerKind::Foobar2000, dir.path(), &store, &MetadataExtractor::new(), &AtomicBool::new(false), |_, _| {})
+            .unwrap();
+        let names: Vec<_> = data.playlists.iter().map(|p| (p.name.as_str(), p.tracks.len())).collect();
+        // Playlists missing from the index keep their file name
+        assert_eq!(names, [("Favourites", 1), ("Road Trip", 1), ("00000002", 1)]);
+        assert_eq!((data.missing_files, data.ratings), (1, 0));
+
+        let empty = TempDir::new().unwrap();
+        assert!(matches!(
+            read_player(PlayerKind::MusicBee, empty.path(), &store, &MetadataExtractor::new(), &AtomicBool::new(false), |_, _| {}),
+            Err(PlayerImportError::NotFound(PlayerKind::MusicBee, _))
+        ));
+    }
+
+    #[test]
+    fn test_ratings_from_tags() {
+        let dir = TempDir::new().unwrap();
+        let song = dir.path().join("song.mp3");
+        fs::write(&song, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+        ratings::write_rating_tag(&song, TrackRating { stars: 4, loved: false }).unwrap();
+        let store = KvStore::in_memory();
+
+        assert!(rating_from_tags(&store, &song).unwrap());
+        assert_eq!(ratings::get_rating(&store, &crate::path_codec::encode(&song)).stars, 4);
+        // A rating already in milkline wins
+        assert!(!rating_from_tags(&store, &song).unwrap());
+    }
+}
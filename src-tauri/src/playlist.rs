@@ -1,7 +1,13 @@
+use crate::library::LibraryScanner;
+use crate::metadata::MetadataExtractor;
+use crate::system_playlists::is_system_playlist;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 use tokio::fs;
+use tokio::sync::OwnedMutexGuard;
 
 #[derive(Debug, Error)]
 pub enum PlaylistError {
@@ -11,6 +17,56 @@ pub enum PlaylistError {
     Serialization(#[from] serde_json::Error),
     #[error("Playlist not found: {0}")]
     NotFound(String),
+    #[error("Playlist {0} was modified since it was loaded")]
+    Stale(String),
+    #[error("Playlist {0} is generated automatically and can't be edited")]
+    ReadOnly(String),
+    #[error("Invalid directory: {0}")]
+    InvalidDirectory(String),
+}
+
+/// Tracks per `get_playlist_page` response when the caller doesn't say, and at most
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Edits remembered per playlist for undo; older ones are forgotten
+pub const MAX_UNDO_STEPS: usize = 100;
+
+/// Durations (seconds) within this tolerance count as the same recording when relocating
+const RELOCATE_DURATION_TOLERANCE: f64 = 2.0;
+
+/// A playlist track whose file is no longer on disk
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct MissingTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub file_path: String,
+}
+
+/// Result of `verify_files`
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistFileReport {
+    pub playlist_id: String,
+    /// Tracks backed by a local file
+    pub local_tracks: usize,
+    pub missing: Vec<MissingTrack>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RelocatedTrack {
+    pub track_id: String,
+    pub old_path: String,
+    pub new_path: String,
+}
+
+/// Result of `relocate_missing_tracks`
+#[derive(Debug, Clone, Serialize)]
+pub struct RelocationReport {
+    pub playlist: Playlist,
+    pub relocated: Vec<RelocatedTrack>,
+    /// Missing tracks with no match under the new root
+    pub unresolved: Vec<MissingTrack>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +74,8 @@ pub struct TrackMetadata {
     pub year: Option<u32>,
     pub genre: Option<String>,
     pub track_number: Option<u32>,
+    #[serde(default)]
+    pub disc_number: Option<u32>,
     pub album_art: Option<String>,
 }
 
@@ -42,10 +100,257 @@ pub struct Playlist {
     pub created_at: chrono::DateTime<chrono::Utc>,
     #[serde(with = "chrono::serde::ts_milliseconds")]
     pub modified_at: chrono::DateTime<chrono::Utc>,
+    /// Generated by `system_playlists` and read-only
+    #[serde(default)]
+    pub system: bool,
+    /// Free-text notes on the playlist, e.g. the set it was prepared for
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A playlist without its tracks, for lists and headers
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PlaylistSummary {
+    pub id: String,
+    pub name: String,
+    pub track_count: usize,
+    /// Seconds
+    pub total_duration: f64,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+    pub system: bool,
+    pub description: Option<String>,
+}
+
+/// A slice of a playlist's tracks, see `Playlist::page`
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistPage {
+    pub playlist_id: String,
+    pub version: i64,
+    /// Tracks in the whole playlist
+    pub total: usize,
+    pub offset: usize,
+    pub tracks: Vec<Track>,
+}
+
+/// What a mutation changed
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaylistChange {
+    /// `tracks` now start at `index`
+    TracksAdded { index: usize, tracks: Vec<Track> },
+    TracksRemoved { track_ids: Vec<String> },
+    /// An add undone: the `count` tracks from `index` are gone
+    TracksRemovedAt { index: usize, count: usize },
+    /// The full new track order
+    TracksReordered { track_ids: Vec<String> },
+    /// Tracks put back by an undo: `tracks[i]` is now at `indices[i]`, ascending
+    TracksRestored { indices: Vec<usize>, tracks: Vec<Track> },
+    /// The whole track list after undoing a reorder
+    TracksReplaced { tracks: Vec<Track> },
+    /// Tracks whose file path changed: `tracks[i]` is the one at `indices[i]`
+    TracksUpdated { indices: Vec<usize>, tracks: Vec<Track> },
+    Renamed { name: String },
+    DescriptionChanged { description: Option<String> },
+}
+
+/// A recorded playlist edit, with enough detail to redo or undo it
+#[derive(Debug, Clone)]
+enum PlaylistEdit {
+    Added { index: usize, tracks: Vec<Track> },
+    /// Removed tracks with the positions they had, ascending
+    Removed { tracks: Vec<(usize, Track)> },
+    /// `previous` is kept whole because a reorder drops tracks missing from `order`
+    Reordered { previous: Vec<Track>, order: Vec<String> },
+    /// File paths moved by a relocation or rename, as (position, old path, new path)
+    Relocated { paths: Vec<(usize, String, String)> },
+    Renamed { from: String, to: String },
+    Described { from: Option<String>, to: Option<String> },
+}
+
+impl PlaylistEdit {
+    fn apply(&self, playlist: &mut Playlist) -> PlaylistChange {
+        match self {
+            PlaylistEdit::Added { index, tracks } => {
+                let index = (*index).min(playlist.tracks.len());
+                playlist.tracks.splice(index..index, tracks.iter().cloned());
+                PlaylistChange::TracksAdded { index, tracks: tracks.clone() }
+            }
+            PlaylistEdit::Removed { tracks } => {
+                remove_tracks(playlist, tracks.iter().map(|(_, t)| t.id.clone()).collect())
+            }
+            PlaylistEdit::Reordered { order, .. } => {
+                // Create a map of track_id to track for quick lookup
+                let mut track_map: HashMap<String, Track> = std::mem::take(&mut playlist.tracks)
+                    .into_iter()
+                    .map(|t| (t.id.clone(), t))
+                    .collect();
+
+                // Reorder tracks based on the provided order
+                playlist.tracks = order.iter().filter_map(|id| track_map.remove(id)).collect();
+                PlaylistChange::TracksReordered { track_ids: playlist.tracks.iter().map(|t| t.id.clone()).collect() }
+            }
+            PlaylistEdit::Relocated { paths } => {
+                set_paths(playlist, paths.iter().map(|(index, _, new)| (*index, new)))
+            }
+            PlaylistEdit::Renamed { to, .. } => {
+                playlist.name = to.clone();
+                PlaylistChange::Renamed { name: to.clone() }
+            }
+            PlaylistEdit::Described { to, .. } => {
+                playlist.description = to.clone();
+                PlaylistChange::DescriptionChanged { description: to.clone() }
+            }
+        }
+    }
+
+    fn revert(&self, playlist: &mut Playlist) -> PlaylistChange {
+        match self {
+            // By position, since copies of the same track may have been there before
+            PlaylistEdit::Added { index, tracks } => {
+                let index = (*index).min(playlist.tracks.len());
+                let end = (index + tracks.len()).min(playlist.tracks.len());
+                playlist.tracks.drain(index..end);
+                PlaylistChange::TracksRemovedAt { index, count: end - index }
+            }
+            PlaylistEdit::Removed { tracks } => {
+                let mut indices = Vec::with_capacity(tracks.len());
+                for (index, track) in tracks {
+                    let index = (*index).min(playlist.tracks.len());
+                    playlist.tracks.insert(index, track.clone());
+                    indices.push(index);
+                }
+                PlaylistChange::TracksRestored { indices, tracks: tracks.iter().map(|(_, t)| t.clone()).collect() }
+            }
+            PlaylistEdit::Reordered { previous, .. } => {
+                playlist.tracks = previous.clone();
+                PlaylistChange::TracksReplaced { tracks: previous.clone() }
+            }
+            PlaylistEdit::Relocated { paths } => {
+                set_paths(playlist, paths.iter().map(|(index, old, _)| (*index, old)))
+            }
+            PlaylistEdit::Renamed { from, .. } => {
+                playlist.name = from.clone();
+                PlaylistChange::Renamed { name: from.clone() }
+            }
+            PlaylistEdit::Described { from, .. } => {
+                playlist.description = from.clone();
+                PlaylistChange::DescriptionChanged { description: from.clone() }
+            }
+        }
+    }
+}
+
+fn remove_tracks(playlist: &mut Playlist, track_ids: Vec<String>) -> PlaylistChange {
+    let ids: HashSet<&str> = track_ids.iter().map(String::as_str).collect();
+    playlist.tracks.retain(|t| !ids.contains(t.id.as_str()));
+    PlaylistChange::TracksRemoved { track_ids }
+}
+
+fn set_paths<'a>(playlist: &mut Playlist, paths: impl Iterator<Item = (usize, &'a String)>) -> PlaylistChange {
+    let mut indices = Vec::new();
+    let mut tracks = Vec::new();
+    for (index, path) in paths {
+        if let Some(track) = playlist.tracks.get_mut(index) {
+            track.file_path = Some(path.clone());
+            indices.push(index);
+            tracks.push(track.clone());
+        }
+    }
+    PlaylistChange::TracksUpdated { indices, tracks }
+}
+
+/// Undo and redo stacks of one playlist, kept for the session
+#[derive(Debug, Default)]
+struct EditHistory {
+    undo: VecDeque<PlaylistEdit>,
+    redo: Vec<PlaylistEdit>,
+}
+
+impl EditHistory {
+    /// Remember a new edit; anything undone before it can no longer be redone
+    fn record(&mut self, edit: PlaylistEdit) {
+        if self.undo.len() == MAX_UNDO_STEPS {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+        self.redo.clear();
+    }
+}
+
+/// Mutation response: the change plus the new version, instead of the whole playlist
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistDelta {
+    pub playlist_id: String,
+    /// Pass back as `expected_version` for the next edit
+    pub version: i64,
+    pub track_count: usize,
+    pub change: PlaylistChange,
+}
+
+impl Playlist {
+    pub fn summary(&self) -> PlaylistSummary {
+        PlaylistSummary {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            track_count: self.tracks.len(),
+            total_duration: self.tracks.iter().map(|t| t.duration).sum(),
+            created_at: self.created_at,
+            modified_at: self.modified_at,
+            system: self.system,
+            description: self.description.clone(),
+        }
+    }
+
+    /// Up to `limit` tracks from `offset`, capped at `MAX_PAGE_SIZE`
+    pub fn page(&self, offset: usize, limit: usize) -> PlaylistPage {
+        let start = offset.min(self.tracks.len());
+        let end = start.saturating_add(limit.min(MAX_PAGE_SIZE)).min(self.tracks.len());
+        PlaylistPage {
+            playlist_id: self.id.clone(),
+            version: self.version(),
+            total: self.tracks.len(),
+            offset: start,
+            tracks: self.tracks[start..end].to_vec(),
+        }
+    }
+
+    /// Describe `change`, which was just applied to this playlist
+    pub fn delta(&self, change: PlaylistChange) -> PlaylistDelta {
+        PlaylistDelta {
+            playlist_id: self.id.clone(),
+            version: self.version(),
+            track_count: self.tracks.len(),
+            change,
+        }
+    }
+
+    /// Version for optimistic concurrency: `modified_at` in milliseconds, as serialized
+    pub fn version(&self) -> i64 {
+        self.modified_at.timestamp_millis()
+    }
+
+    /// Bump `modified_at`, always to a new version even within the same millisecond
+    fn touch(&mut self) {
+        let next = self.modified_at + chrono::Duration::milliseconds(1);
+        self.modified_at = chrono::Utc::now().max(next);
+    }
 }
 
+/// Reads and writes playlists as JSON files
+///
+/// Every edit is a load-modify-save under that playlist's own lock, so two
+/// windows editing the same playlist can't lose each other's changes while
+/// edits to different playlists run in parallel. Mutations optionally take
+/// the `version` the caller last saw and fail with `PlaylistError::Stale` if
+/// the playlist has changed since. Add, remove, reorder, rename, description
+/// and file path changes are logged per playlist for `undo` and `redo`.
 pub struct PlaylistManager {
     playlists_dir: PathBuf,
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    history: Mutex<HashMap<String, EditHistory>>,
 }
 
 impl PlaylistManager {
@@ -57,7 +362,15 @@ impl PlaylistManager {
             fs::create_dir_all(&playlists_dir).await?;
         }
         
-        Ok(Self { playlists_dir })
+        Ok(Self::with_directory(playlists_dir))
+    }
+
+    fn with_directory(playlists_dir: PathBuf) -> Self {
+        Self {
+            playlists_dir,
+            locks: Mutex::new(HashMap::new()),
+            history: Mutex::new(HashMap::new()),
+        }
     }
 
     fn get_playlists_directory() -> Result<PathBuf, PlaylistError> {
@@ -74,7 +387,95 @@ impl PlaylistManager {
         self.playlists_dir.join(format!("{}.json", playlist_id))
     }
 
+    /// Wait for exclusive write access to one playlist
+    ///
+    /// Fails for system playlists, which are never written.
+    async fn lock_playlist(&self, playlist_id: &str) -> Result<OwnedMutexGuard<()>, PlaylistError> {
+        if is_system_playlist(playlist_id) {
+            return Err(PlaylistError::ReadOnly(playlist_id.to_string()));
+        }
+        let lock = self.locks.lock().unwrap().entry(playlist_id.to_string()).or_default().clone();
+        Ok(lock.lock_owned().await)
+    }
+
+    /// Apply and record the edit `plan` makes for the loaded playlist, holding its lock
+    ///
+    /// Rejects the edit if `expected_version` is given and no longer current.
+    /// When `plan` returns `None` nothing changes and nothing is saved.
+    async fn modify(
+        &self,
+        playlist_id: &str,
+        expected_version: Option<i64>,
+        plan: impl FnOnce(&Playlist) -> Option<PlaylistEdit>,
+    ) -> Result<Playlist, PlaylistError> {
+        let _guard = self.lock_playlist(playlist_id).await?;
+        let mut playlist = self.load_playlist(playlist_id).await?;
+        if expected_version.is_some_and(|version| version != playlist.version()) {
+            return Err(PlaylistError::Stale(playlist_id.to_string()));
+        }
+        let Some(edit) = plan(&playlist) else {
+            return Ok(playlist);
+        };
+        self.commit(&mut playlist, edit).await?;
+        Ok(playlist)
+    }
+
+    /// Apply, save and record `edit`; the caller holds the playlist's lock
+    async fn commit(&self, playlist: &mut Playlist, edit: PlaylistEdit) -> Result<(), PlaylistError> {
+        edit.apply(playlist);
+        playlist.touch();
+        self.save_playlist(playlist).await?;
+        self.history.lock().unwrap().entry(playlist.id.clone()).or_default().record(edit);
+        Ok(())
+    }
+
+    /// Reverse the playlist's latest edit; `None` if there is nothing to undo
+    pub async fn undo(&self, playlist_id: &str) -> Result<Option<PlaylistDelta>, PlaylistError> {
+        self.step_history(playlist_id, true).await
+    }
+
+    /// Re-apply the latest undone edit; `None` if there is nothing to redo
+    pub async fn redo(&self, playlist_id: &str) -> Result<Option<PlaylistDelta>, PlaylistError> {
+        self.step_history(playlist_id, false).await
+    }
+
+    async fn step_history(&self, playlist_id: &str, undo: bool) -> Result<Option<PlaylistDelta>, PlaylistError> {
+        let _guard = self.lock_playlist(playlist_id).await?;
+        let mut playlist = self.load_playlist(playlist_id).await?;
+        let edit = {
+            let mut history = self.history.lock().unwrap();
+            let Some(history) = history.get_mut(playlist_id) else {
+                return Ok(None);
+            };
+            match if undo { history.undo.pop_back() } else { history.redo.pop() } {
+                Some(edit) => edit,
+                None => return Ok(None),
+            }
+        };
+        let change = if undo { edit.revert(&mut playlist) } else { edit.apply(&mut playlist) };
+        playlist.touch();
+        let saved = self.save_playlist(&playlist).await;
+
+        // Move the edit to the other stack, or put it back if saving failed
+        {
+            let mut history = self.history.lock().unwrap();
+            let history = history.entry(playlist_id.to_string()).or_default();
+            if saved.is_ok() == undo {
+                history.redo.push(edit);
+            } else {
+                history.undo.push_back(edit);
+            }
+        }
+        saved?;
+        Ok(Some(playlist.delta(change)))
+    }
+
     pub async fn create_playlist(&self, name: String) -> Result<Playlist, PlaylistError> {
+        self.create_playlist_with_tracks(name, Vec::new()).await
+    }
+
+    /// Create and save a playlist already holding `tracks`, in the given order
+    pub async fn create_playlist_with_tracks(&self, name: String, tracks: Vec<Track>) -> Result<Playlist, PlaylistError> {
         #[cfg(not(test))]
         crate::performance::record_playlist_operation();
         
@@ -82,19 +483,48 @@ impl PlaylistManager {
         let playlist = Playlist {
             id: uuid::Uuid::new_v4().to_string(),
             name,
-            tracks: Vec::new(),
+            tracks,
             created_at: now,
             modified_at: now,
+            system: false,
+            description: None,
         };
         
         self.save_playlist(&playlist).await?;
         Ok(playlist)
     }
 
+    /// Save imported `tracks` under `name`, merging into a playlist of that name if there is one
+    ///
+    /// Tracks whose file the existing playlist already holds are skipped, so
+    /// running an import again adds only what is new, as one undoable edit.
+    pub async fn import_playlist(&self, name: String, tracks: Vec<Track>) -> Result<Playlist, PlaylistError> {
+        let existing = self
+            .list_playlists()
+            .await?
+            .into_iter()
+            .find(|playlist| !is_system_playlist(&playlist.id) && playlist.name == name);
+        let Some(existing) = existing else {
+            return self.create_playlist_with_tracks(name, tracks).await;
+        };
+        self.modify(&existing.id, None, |playlist| {
+            let known: HashSet<&str> = playlist.tracks.iter().filter_map(|t| t.file_path.as_deref()).collect();
+            let new: Vec<Track> = tracks
+                .into_iter()
+                .filter(|t| t.file_path.as_deref().is_none_or(|path| !known.contains(path)))
+                .collect();
+            (!new.is_empty()).then_some(PlaylistEdit::Added { index: playlist.tracks.len(), tracks: new })
+        })
+        .await
+    }
+
+    /// Write a playlist, replacing the file atomically so readers never see half of it
     pub async fn save_playlist(&self, playlist: &Playlist) -> Result<(), PlaylistError> {
         let path = self.get_playlist_path(&playlist.id);
+        let temp_path = path.with_extension("json.tmp");
         let json = serde_json::to_string_pretty(playlist)?;
-        fs::write(path, json).await?;
+        fs::write(&temp_path, json).await?;
+        fs::rename(&temp_path, &path).await?;
         Ok(())
     }
 
@@ -135,6 +565,7 @@ impl PlaylistManager {
     }
 
     pub async fn delete_playlist(&self, playlist_id: &str) -> Result<(), PlaylistError> {
+        let _guard = self.lock_playlist(playlist_id).await?;
         let path = self.get_playlist_path(playlist_id);
         
         if !path.exists() {
@@ -142,61 +573,278 @@ impl PlaylistManager {
         }
         
         fs::remove_file(path).await?;
+        // Waiters hold clones of the lock; dropping the entry under them would
+        // let a later caller lock a fresh one alongside them. The map and our
+        // guard account for two references.
+        let mut locks = self.locks.lock().unwrap();
+        if locks.get(playlist_id).is_some_and(|lock| Arc::strong_count(lock) <= 2) {
+            locks.remove(playlist_id);
+        }
+        drop(locks);
+        self.history.lock().unwrap().remove(playlist_id);
         Ok(())
     }
 
-    pub async fn add_track(&self, playlist_id: &str, track: Track) -> Result<Playlist, PlaylistError> {
-        let mut playlist = self.load_playlist(playlist_id).await?;
-        playlist.tracks.push(track);
-        playlist.modified_at = chrono::Utc::now();
-        self.save_playlist(&playlist).await?;
-        Ok(playlist)
+    pub async fn add_track(
+        &self,
+        playlist_id: &str,
+        track: Track,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        self.add_tracks(playlist_id, vec![track], expected_version).await
     }
 
-    pub async fn remove_track(&self, playlist_id: &str, track_id: &str) -> Result<Playlist, PlaylistError> {
-        let mut playlist = self.load_playlist(playlist_id).await?;
-        playlist.tracks.retain(|t| t.id != track_id);
-        playlist.modified_at = chrono::Utc::now();
-        self.save_playlist(&playlist).await?;
-        Ok(playlist)
+    /// Append `tracks` in one save, undone as a single step
+    pub async fn add_tracks(
+        &self,
+        playlist_id: &str,
+        tracks: Vec<Track>,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        self.modify(playlist_id, expected_version, |playlist| {
+            (!tracks.is_empty()).then_some(PlaylistEdit::Added { index: playlist.tracks.len(), tracks })
+        })
+        .await
+    }
+
+    pub async fn remove_track(
+        &self,
+        playlist_id: &str,
+        track_id: &str,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        self.modify(playlist_id, expected_version, |playlist| {
+            let tracks: Vec<(usize, Track)> = playlist
+                .tracks
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| t.id == track_id)
+                .map(|(i, t)| (i, t.clone()))
+                .collect();
+            (!tracks.is_empty()).then_some(PlaylistEdit::Removed { tracks })
+        })
+        .await
     }
 
-    pub async fn reorder_tracks(&self, playlist_id: &str, track_ids: Vec<String>) -> Result<Playlist, PlaylistError> {
+    pub async fn reorder_tracks(
+        &self,
+        playlist_id: &str,
+        track_ids: Vec<String>,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        self.modify(playlist_id, expected_version, |playlist| {
+            Some(PlaylistEdit::Reordered { previous: playlist.tracks.clone(), order: track_ids })
+        })
+        .await
+    }
+
+    /// List the playlist's local tracks whose files no longer exist
+    pub async fn verify_files(&self, playlist_id: &str) -> Result<PlaylistFileReport, PlaylistError> {
+        let playlist = self.load_playlist(playlist_id).await?;
+        let mut report = PlaylistFileReport {
+            playlist_id: playlist.id.clone(),
+            local_tracks: 0,
+            missing: Vec::new(),
+        };
+        for track in &playlist.tracks {
+            let Some(path) = &track.file_path else {
+                continue;
+            };
+            report.local_tracks += 1;
+            if !fs::try_exists(path).await.unwrap_or(false) {
+                report.missing.push(MissingTrack::of(track, path));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Re-point missing tracks at files found under `new_root`
+    ///
+    /// A missing file is matched by file name first, preferring the candidate
+    /// that shares the most trailing folders with the old path; failing that,
+    /// by title, artist and duration read through `extractor`. The playlist is
+    /// saved only if something was relocated.
+    pub async fn relocate_missing_tracks(
+        &self,
+        playlist_id: &str,
+        new_root: &Path,
+        extractor: &'static MetadataExtractor,
+    ) -> Result<RelocationReport, PlaylistError> {
+        let _guard = self.lock_playlist(playlist_id).await?;
         let mut playlist = self.load_playlist(playlist_id).await?;
-        
-        // Create a map of track_id to track for quick lookup
-        let track_map: std::collections::HashMap<String, Track> = playlist.tracks
-            .into_iter()
-            .map(|t| (t.id.clone(), t))
+        let missing: Vec<(usize, String)> = playlist
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, t)| t.file_path.clone().map(|p| (i, p)))
+            .filter(|(_, path)| !crate::path_codec::decode(path).exists())
             .collect();
-        
-        // Reorder tracks based on the provided order
-        let mut new_tracks = Vec::new();
-        for track_id in track_ids {
-            if let Some(track) = track_map.get(&track_id) {
-                new_tracks.push(track.clone());
+        if missing.is_empty() {
+            return Ok(RelocationReport { playlist, relocated: Vec::new(), unresolved: Vec::new() });
+        }
+
+        let root = new_root.to_path_buf();
+        let candidates = tokio::task::spawn_blocking(move || LibraryScanner::scan_directory(&root))
+            .await
+            .map_err(|e| PlaylistError::Io(std::io::Error::other(e)))?
+            .map_err(|e| match e {
+                crate::library::ScanError::IoError(e) => PlaylistError::Io(e),
+                crate::library::ScanError::InvalidPath => {
+                    PlaylistError::InvalidDirectory(new_root.to_string_lossy().to_string())
+                }
+                e @ crate::library::ScanError::Cancelled => PlaylistError::Io(std::io::Error::other(e)),
+            })?;
+        let candidates: Vec<PathBuf> = candidates.into_iter().map(|t| t.file_path).collect();
+
+        let tracks: Vec<Track> = missing.iter().map(|(i, _)| playlist.tracks[*i].clone()).collect();
+        let matches = tokio::task::spawn_blocking(move || {
+            tracks.iter().map(|t| find_relocation(t, &candidates, extractor)).collect::<Vec<_>>()
+        })
+        .await
+        .map_err(|e| PlaylistError::Io(std::io::Error::other(e)))?;
+
+        let mut relocated = Vec::new();
+        let mut unresolved = Vec::new();
+        let mut paths = Vec::new();
+        for ((i, old_path), found) in missing.into_iter().zip(matches) {
+            let track = &playlist.tracks[i];
+            match found {
+                Some(new_path) => {
+                    let new_path = crate::path_codec::encode(&new_path);
+                    paths.push((i, old_path.clone(), new_path.clone()));
+                    relocated.push(RelocatedTrack { track_id: track.id.clone(), old_path, new_path });
+                }
+                None => unresolved.push(MissingTrack::of(track, &old_path)),
             }
         }
-        
-        playlist.tracks = new_tracks;
-        playlist.modified_at = chrono::Utc::now();
-        self.save_playlist(&playlist).await?;
-        Ok(playlist)
+        if !paths.is_empty() {
+            self.commit(&mut playlist, PlaylistEdit::Relocated { paths }).await?;
+        }
+        Ok(RelocationReport { playlist, relocated, unresolved })
     }
 
-    pub async fn update_playlist(&self, playlist_id: &str, name: Option<String>) -> Result<Playlist, PlaylistError> {
-        let mut playlist = self.load_playlist(playlist_id).await?;
-        
-        if let Some(new_name) = name {
-            playlist.name = new_name;
+    /// Point every playlist entry for a moved file at its new path
+    ///
+    /// `moves` maps old paths to new ones, encoded as playlists store them.
+    /// Returns the ids of the playlists that changed.
+    pub async fn rename_file_paths(&self, moves: &HashMap<String, String>) -> Result<Vec<String>, PlaylistError> {
+        let refers_to_moved = |playlist: &Playlist| {
+            playlist.tracks.iter().any(|t| t.file_path.as_ref().is_some_and(|path| moves.contains_key(path)))
+        };
+        let mut changed = Vec::new();
+        for listed in self.list_playlists().await? {
+            if is_system_playlist(&listed.id) || !refers_to_moved(&listed) {
+                continue;
+            }
+            let mut renamed = false;
+            let playlist = self
+                .modify(&listed.id, None, |playlist| {
+                    let paths: Vec<(usize, String, String)> = playlist
+                        .tracks
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, t)| {
+                            let old = t.file_path.as_ref()?;
+                            moves.get(old).map(|new| (i, old.clone(), new.clone()))
+                        })
+                        .collect();
+                    renamed = !paths.is_empty();
+                    renamed.then_some(PlaylistEdit::Relocated { paths })
+                })
+                .await?;
+            if renamed {
+                changed.push(playlist.id);
+            }
         }
-        
-        playlist.modified_at = chrono::Utc::now();
-        self.save_playlist(&playlist).await?;
-        Ok(playlist)
+        Ok(changed)
+    }
+
+    pub async fn update_playlist(
+        &self,
+        playlist_id: &str,
+        name: Option<String>,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        self.modify(playlist_id, expected_version, |playlist| {
+            name.map(|to| PlaylistEdit::Renamed { from: playlist.name.clone(), to })
+        })
+        .await
+    }
+
+    /// Set the description, trimmed; a blank description clears it
+    pub async fn set_description(
+        &self,
+        playlist_id: &str,
+        description: Option<String>,
+        expected_version: Option<i64>,
+    ) -> Result<Playlist, PlaylistError> {
+        let to = description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+        self.modify(playlist_id, expected_version, |playlist| {
+            (playlist.description != to).then(|| PlaylistEdit::Described { from: playlist.description.clone(), to })
+        })
+        .await
     }
 }
 
+impl MissingTrack {
+    fn of(track: &Track, file_path: &str) -> Self {
+        Self {
+            track_id: track.id.clone(),
+            title: track.title.clone(),
+            artist: track.artist.clone(),
+            file_path: file_path.to_string(),
+        }
+    }
+}
+
+/// Best candidate file for a moved track, see `relocate_missing_tracks`
+fn find_relocation(track: &Track, candidates: &[PathBuf], extractor: &MetadataExtractor) -> Option<PathBuf> {
+    let old_path = crate::path_codec::decode(track.file_path.as_deref()?);
+    let file_name = old_path.file_name()?.to_string_lossy().to_lowercase();
+    let by_name = candidates
+        .iter()
+        .filter(|c| c.file_name().is_some_and(|n| n.to_string_lossy().to_lowercase() == file_name))
+        .max_by_key(|c| shared_suffix(&old_path, c));
+    if let Some(found) = by_name {
+        return Some(found.clone());
+    }
+
+    let title = track.title.trim().to_lowercase();
+    let artist = track.artist.trim().to_lowercase();
+    if title.is_empty() {
+        return None;
+    }
+    let mut metadata_cache: HashMap<&Path, Option<crate::metadata::TrackMetadata>> = HashMap::new();
+    candidates
+        .iter()
+        .find(|candidate| {
+            let metadata = metadata_cache
+                .entry(candidate.as_path())
+                .or_insert_with(|| extractor.extract(candidate).ok());
+            let Some(m) = metadata else {
+                return false;
+            };
+            let same_text = |tag: &Option<String>, wanted: &str| {
+                tag.as_deref().is_some_and(|v| v.trim().to_lowercase() == wanted)
+            };
+            let same_duration = match m.duration {
+                Some(d) if track.duration > 0.0 => (d as f64 - track.duration).abs() <= RELOCATE_DURATION_TOLERANCE,
+                _ => true,
+            };
+            same_text(&m.title, &title) && (artist.is_empty() || same_text(&m.artist, &artist)) && same_duration
+        })
+        .cloned()
+}
+
+/// Number of trailing path components two paths share
+fn shared_suffix(a: &Path, b: &Path) -> usize {
+    a.components()
+        .rev()
+        .zip(b.components().rev())
+        .take_while(|(x, y)| x.as_os_str().eq_ignore_ascii_case(y.as_os_str()))
+        .count()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,9 +853,7 @@ mod tests {
 
     fn create_test_manager() -> (PlaylistManager, TempDir) {
         let temp_dir = TempDir::new().unwrap();
-        let manager = PlaylistManager {
-            playlists_dir: temp_dir.path().to_path_buf(),
-        };
+        let manager = PlaylistManager::with_directory(temp_dir.path().to_path_buf());
         (manager, temp_dir)
     }
 
@@ -233,6 +879,7 @@ mod tests {
                     year: None,
                     genre: None,
                     track_number: None,
+                    disc_number: None,
                     album_art: None,
                 },
             }
@@ -249,6 +896,255 @@ mod tests {
         assert!(manager.is_ok());
     }
 
+    fn local_track(id: &str, title: &str, artist: &str, file_path: &Path) -> Track {
+        Track {
+            id: id.to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: String::new(),
+            duration: 0.0,
+            file_path: Some(file_path.to_string_lossy().to_string()),
+            source: "local".to_string(),
+            metadata: TrackMetadata {
+                year: None,
+                genre: None,
+                track_number: None,
+                disc_number: None,
+                album_art: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_relocate_moved_folder() {
+        let (manager, temp_dir) = create_test_manager();
+        let old_root = temp_dir.path().join("old");
+        let new_root = temp_dir.path().join("new");
+        std::fs::create_dir_all(new_root.join("Album")).unwrap();
+        std::fs::create_dir_all(new_root.join("Other")).unwrap();
+        std::fs::write(new_root.join("Album").join("01 Intro.wav"), b"").unwrap();
+        std::fs::write(new_root.join("Other").join("01 Intro.wav"), b"").unwrap();
+        std::fs::write(new_root.join("Other").join("Boards - Roygbiv.wav"), b"").unwrap();
+
+        let playlist = manager.create_playlist("Moved".to_string()).await.unwrap();
+        let tracks = [
+            local_track("a", "Intro", "", &old_root.join("Album").join("01 Intro.wav")),
+            local_track("b", "Roygbiv", "Boards", &old_root.join("renamed.wav")),
+            local_track("c", "Gone", "", &old_root.join("gone.wav")),
+        ];
+        for track in tracks {
+            manager.add_track(&playlist.id, track, None).await.unwrap();
+        }
+
+        let report = manager.verify_files(&playlist.id).await.unwrap();
+        assert_eq!(report.local_tracks, 3);
+        assert_eq!(report.missing.len(), 3);
+
+        let extractor: &'static MetadataExtractor = Box::leak(Box::new(MetadataExtractor::new()));
+        let result = manager.relocate_missing_tracks(&playlist.id, &new_root, extractor).await.unwrap();
+        assert_eq!(result.relocated.len(), 2);
+        assert_eq!(result.relocated[0].new_path, new_root.join("Album").join("01 Intro.wav").to_string_lossy());
+        assert_eq!(result.relocated[1].new_path, new_root.join("Other").join("Boards - Roygbiv.wav").to_string_lossy());
+        assert_eq!(result.unresolved.len(), 1);
+        assert_eq!(manager.verify_files(&playlist.id).await.unwrap().missing.len(), 1);
+
+        let delta = manager.undo(&playlist.id).await.unwrap().unwrap();
+        assert!(matches!(delta.change, PlaylistChange::TracksUpdated { ref indices, .. } if indices == &[0, 1]));
+        assert_eq!(manager.verify_files(&playlist.id).await.unwrap().missing.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_from_folder_tracks() {
+        let (manager, temp_dir) = create_test_manager();
+        let folder = temp_dir.path().join("Album");
+        std::fs::create_dir(&folder).unwrap();
+        for name in ["10.wav", "2.wav", "1.wav", "cover.jpg"] {
+            std::fs::write(folder.join(name), b"").unwrap();
+        }
+        let summary = crate::queue_import::expand_folder(
+            &folder,
+            crate::queue_import::EnqueueMode::TopLevel,
+            &crate::metadata::MetadataExtractor::new(),
+        )
+        .unwrap();
+
+        let playlist = manager.create_playlist_with_tracks("Album".to_string(), summary.tracks).await.unwrap();
+        let loaded = manager.load_playlist(&playlist.id).await.unwrap();
+        let titles: Vec<&str> = loaded.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, ["1", "2", "10"]);
+    }
+
+    #[test]
+    fn test_playlist_pages() {
+        let now = chrono::Utc::now();
+        let tracks: Vec<Track> = (0..5)
+            .map(|i| local_track(&format!("t{}", i), "Song", "", Path::new("song.mp3")))
+            .collect();
+        let playlist = Playlist { id: "p".to_string(), name: "Big".to_string(), tracks, created_at: now, modified_at: now, system: false, description: None };
+
+        let page = playlist.page(3, 10);
+        assert_eq!((page.total, page.offset), (5, 3));
+        assert_eq!(page.tracks.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["t3", "t4"]);
+        assert!(playlist.page(9, 10).tracks.is_empty());
+        assert_eq!(playlist.page(0, usize::MAX).tracks.len(), 5);
+        assert_eq!(playlist.summary().track_count, 5);
+    }
+
+    #[tokio::test]
+    async fn test_rename_file_paths() {
+        let (manager, temp_dir) = create_test_manager();
+        let (old, new) = (temp_dir.path().join("a.mp3"), temp_dir.path().join("Artist/01 A.mp3"));
+        let mix = manager.create_playlist("Mix".to_string()).await.unwrap();
+        manager.add_track(&mix.id, local_track("a", "A", "", &old), None).await.unwrap();
+        manager.add_track(&mix.id, local_track("b", "B", "", &temp_dir.path().join("b.mp3")), None).await.unwrap();
+        let other = manager.create_playlist("Other".to_string()).await.unwrap();
+
+        let moves = HashMap::from([(old.to_string_lossy().to_string(), new.to_string_lossy().to_string())]);
+        assert_eq!(manager.rename_file_paths(&moves).await.unwrap(), vec![mix.id.clone()]);
+        let paths: Vec<_> = manager.load_playlist(&mix.id).await.unwrap().tracks.into_iter().map(|t| t.file_path.unwrap()).collect();
+        assert_eq!(paths, [new.to_string_lossy().to_string(), temp_dir.path().join("b.mp3").to_string_lossy().to_string()]);
+        assert_eq!(manager.load_playlist(&other.id).await.unwrap().version(), other.version());
+    }
+
+    #[tokio::test]
+    async fn test_import_playlist_merges_by_name() {
+        let (manager, temp_dir) = create_test_manager();
+        let track = |id: &str, name: &str| local_track(id, name, "", &temp_dir.path().join(name));
+        let first = manager.import_playlist("Winamp".to_string(), vec![track("a", "a.mp3")]).await.unwrap();
+        let again = manager
+            .import_playlist("Winamp".to_string(), vec![track("a2", "a.mp3"), track("b", "b.mp3")])
+            .await
+            .unwrap();
+
+        assert_eq!(again.id, first.id);
+        assert_eq!(again.tracks.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(manager.list_playlists().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_add_tracks_in_one_edit() {
+        let (manager, temp_dir) = create_test_manager();
+        let playlist = manager.create_playlist("Bulk".to_string()).await.unwrap();
+        let tracks: Vec<Track> = (0..3)
+            .map(|i| local_track(&format!("t{}", i), "Song", "", &temp_dir.path().join("song.mp3")))
+            .collect();
+
+        let updated = manager.add_tracks(&playlist.id, tracks, Some(playlist.version())).await.unwrap();
+        assert_eq!(updated.tracks.len(), 3);
+        let unchanged = manager.add_tracks(&playlist.id, Vec::new(), Some(updated.version())).await.unwrap();
+        assert_eq!(unchanged.version(), updated.version());
+
+        manager.undo(&playlist.id).await.unwrap();
+        assert!(manager.load_playlist(&playlist.id).await.unwrap().tracks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undo_add_keeps_earlier_copies() {
+        let (manager, temp_dir) = create_test_manager();
+        let playlist = manager.create_playlist("Loop".to_string()).await.unwrap();
+        let track = local_track("a", "Song", "", &temp_dir.path().join("song.mp3"));
+        manager.add_track(&playlist.id, track.clone(), None).await.unwrap();
+        manager.add_tracks(&playlist.id, vec![track.clone(), track], None).await.unwrap();
+
+        let delta = manager.undo(&playlist.id).await.unwrap().unwrap();
+        assert!(matches!(delta.change, PlaylistChange::TracksRemovedAt { index: 1, count: 2 }));
+        assert_eq!(manager.load_playlist(&playlist.id).await.unwrap().tracks.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_undo_and_redo() {
+        let (manager, temp_dir) = create_test_manager();
+        let playlist = manager.create_playlist("Mix".to_string()).await.unwrap();
+        for id in ["a", "b", "c"] {
+            let track = local_track(id, "Song", "", &temp_dir.path().join("song.mp3"));
+            manager.add_track(&playlist.id, track, None).await.unwrap();
+        }
+        let ids = |p: &Playlist| p.tracks.iter().map(|t| t.id.clone()).collect::<Vec<_>>();
+
+        manager.remove_track(&playlist.id, "b", None).await.unwrap();
+        manager.reorder_tracks(&playlist.id, vec!["c".to_string()], None).await.unwrap();
+        manager.update_playlist(&playlist.id, Some("Renamed".to_string()), None).await.unwrap();
+        let described = manager.set_description(&playlist.id, Some(" Warm-up set \n".to_string()), None).await.unwrap();
+        assert_eq!(described.description.as_deref(), Some("Warm-up set"));
+
+        let delta = manager.undo(&playlist.id).await.unwrap().unwrap();
+        assert!(matches!(delta.change, PlaylistChange::DescriptionChanged { description: None }));
+        let delta = manager.undo(&playlist.id).await.unwrap().unwrap();
+        assert!(matches!(delta.change, PlaylistChange::Renamed { ref name } if name == "Mix"));
+        manager.undo(&playlist.id).await.unwrap();
+        assert_eq!(ids(&manager.load_playlist(&playlist.id).await.unwrap()), ["a", "c"]);
+        let delta = manager.undo(&playlist.id).await.unwrap().unwrap();
+        assert!(matches!(delta.change, PlaylistChange::TracksRestored { ref indices, .. } if indices == &[1]));
+        assert_eq!(ids(&manager.load_playlist(&playlist.id).await.unwrap()), ["a", "b", "c"]);
+
+        manager.redo(&playlist.id).await.unwrap();
+        assert_eq!(ids(&manager.load_playlist(&playlist.id).await.unwrap()), ["a", "c"]);
+
+        // A new edit drops what was left to redo
+        manager.update_playlist(&playlist.id, Some("Other".to_string()), None).await.unwrap();
+        assert!(manager.redo(&playlist.id).await.unwrap().is_none());
+        for _ in 0..5 {
+            manager.undo(&playlist.id).await.unwrap();
+        }
+        let restored = manager.load_playlist(&playlist.id).await.unwrap();
+        assert!(restored.tracks.is_empty());
+        assert_eq!(restored.name, "Mix");
+        assert!(manager.undo(&playlist.id).await.unwrap().is_none());
+        assert!(matches!(manager.undo("system:most-played").await, Err(PlaylistError::ReadOnly(_))));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_edits_are_not_lost() {
+        let (manager, temp_dir) = create_test_manager();
+        let manager = Arc::new(manager);
+        let playlist = manager.create_playlist("Shared".to_string()).await.unwrap();
+
+        let mut edits = tokio::task::JoinSet::new();
+        for i in 0..20 {
+            let manager = manager.clone();
+            let id = playlist.id.clone();
+            let track = local_track(&format!("t{}", i), "Song", "", &temp_dir.path().join("song.mp3"));
+            edits.spawn(async move { manager.add_track(&id, track, None).await });
+        }
+        while let Some(result) = edits.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        assert_eq!(manager.load_playlist(&playlist.id).await.unwrap().tracks.len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_delete_keeps_lock_while_others_wait() {
+        let (manager, _temp_dir) = create_test_manager();
+        let playlist = manager.create_playlist("Gone".to_string()).await.unwrap();
+        // Stands in for a caller queued behind the delete
+        let waiter = manager.locks.lock().unwrap().entry(playlist.id.clone()).or_default().clone();
+
+        manager.delete_playlist(&playlist.id).await.unwrap();
+        assert!(Arc::ptr_eq(&manager.locks.lock().unwrap()[&playlist.id], &waiter));
+
+        drop(waiter);
+        manager.save_playlist(&playlist).await.unwrap();
+        manager.delete_playlist(&playlist.id).await.unwrap();
+        assert!(!manager.locks.lock().unwrap().contains_key(&playlist.id));
+    }
+
+    #[tokio::test]
+    async fn test_stale_version_is_rejected() {
+        let (manager, temp_dir) = create_test_manager();
+        let playlist = manager.create_playlist("Versioned".to_string()).await.unwrap();
+        let track = local_track("a", "Song", "", &temp_dir.path().join("song.mp3"));
+
+        let updated = manager.add_track(&playlist.id, track, Some(playlist.version())).await.unwrap();
+        assert!(updated.version() > playlist.version());
+
+        let stale = manager.update_playlist(&playlist.id, Some("Renamed".to_string()), Some(playlist.version())).await;
+        assert!(matches!(stale, Err(PlaylistError::Stale(_))));
+        let renamed = manager.update_playlist(&playlist.id, Some("Renamed".to_string()), Some(updated.version())).await;
+        assert_eq!(renamed.unwrap().name, "Renamed");
+        assert_eq!(manager.load_playlist(&playlist.id).await.unwrap().tracks.len(), 1);
+    }
+
     // **Feature: milk-player, Property 18: Playlist persistence**
     // **Validates: Requirements 9.1, 9.2, 9.5**
     // For any playlist modification (create, add track, remove track, reorder), 
@@ -286,7 +1182,7 @@ mod tests {
                 
                 // Create playlist and add track
                 let playlist = manager.create_playlist(name).await.unwrap();
-                let _updated = manager.add_track(&playlist.id, track.clone()).await.unwrap();
+                let _updated = manager.add_track(&playlist.id, track.clone(), None).await.unwrap();
                 
                 // Load it back
                 let loaded = manager.load_playlist(&playlist.id).await.unwrap();
@@ -312,12 +1208,12 @@ mod tests {
                 let playlist = manager.create_playlist(name).await.unwrap();
                 let mut current_playlist = playlist;
                 for track in &tracks {
-                    current_playlist = manager.add_track(&current_playlist.id, track.clone()).await.unwrap();
+                    current_playlist = manager.add_track(&current_playlist.id, track.clone(), None).await.unwrap();
                 }
                 
                 // Remove first track
                 let track_to_remove = tracks[0].id.clone();
-                manager.remove_track(&current_playlist.id, &track_to_remove).await.unwrap();
+                manager.remove_track(&current_playlist.id, &track_to_remove, None).await.unwrap();
                 
                 // Load it back
                 let loaded = manager.load_playlist(&current_playlist.id).await.unwrap();
@@ -342,14 +1238,14 @@ mod tests {
                 let playlist = manager.create_playlist(name).await.unwrap();
                 let mut current_playlist = playlist;
                 for track in &tracks {
-                    current_playlist = manager.add_track(&current_playlist.id, track.clone()).await.unwrap();
+                    current_playlist = manager.add_track(&current_playlist.id, track.clone(), None).await.unwrap();
                 }
                 
                 // Reverse the order
                 let mut reversed_ids: Vec<String> = tracks.iter().map(|t| t.id.clone()).collect();
                 reversed_ids.reverse();
                 
-                manager.reorder_tracks(&current_playlist.id, reversed_ids.clone()).await.unwrap();
+                manager.reorder_tracks(&current_playlist.id, reversed_ids.clone(), None).await.unwrap();
                 
                 // Load it back
                 let loaded = manager.load_playlist(&current_playlist.id).await.unwrap();
@@ -392,14 +1288,14 @@ mod tests {
                     if track.file_path.is_some() && i < file_paths.len() {
                         track.file_path = Some(file_paths[i].to_string_lossy().to_string());
                     }
-                    current_playlist = manager.add_track(&current_playlist.id, track).await.unwrap();
+                    current_playlist = manager.add_track(&current_playlist.id, track, None).await.unwrap();
                 }
                 
                 // Remove first track
                 let track_to_remove = &current_playlist.tracks[0];
                 let removed_file_path = track_to_remove.file_path.clone();
                 
-                manager.remove_track(&current_playlist.id, &track_to_remove.id).await.unwrap();
+                manager.remove_track(&current_playlist.id, &track_to_remove.id, None).await.unwrap();
                 
                 // Verify the original file still exists if it had a file path
                 if let Some(path) = removed_file_path {
@@ -0,0 +1,738 @@
+// Third-party plugins
+// Plugins are WebAssembly modules in `<data dir>/milk/plugins/<id>/`, next to a
+// `plugin.json` manifest. They run in an interpreter with their own memory, a
+// memory cap and an instruction budget per call, so a plugin that traps, loops
+// or runs out of memory fails that call instead of taking the app down. A
+// plugin that keeps failing is suspended for the rest of the session.
+//
+// Plugin ABI: the module exports `memory` and `milk_alloc(len) -> ptr`, plus
+// any of the hooks below. The host writes a UTF-8 JSON argument into memory
+// from `milk_alloc` and calls the hook with `(ptr, len)`; `milk_free(ptr, len)`
+// is called afterwards if exported. The `milk.log(ptr, len)` import writes to
+// the app log, up to `MAX_LOG_BYTES` per call.
+//
+// Hooks never run on the caller's thread while it waits on another plugin:
+// frames and playback events are queued for a plugin thread, and metadata
+// lookups skip the plugins while the host is busy.
+// - `milk_visualizer_frame(ptr, len)`: an `audio_dsp::VisualizerFrame`
+// - `milk_metadata(ptr, len) -> i64`: `{ "path", "metadata" }`; returns
+//   `ptr << 32 | len` of a JSON object with tag fields to fill in, or 0
+// - `milk_playback_event(ptr, len)`: a `PlaybackEvent`
+
+use crate::audio_dsp::VisualizerFrame;
+use crate::kv_store::KvStore;
+use crate::logging::{log_info, log_warn};
+use crate::media_session::{NowPlaying, PlaybackStatus};
+use crate::metadata::TrackMetadata;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use thiserror::Error;
+use wasmi::{Caller, Config, Engine, Extern, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+const MANIFEST_FILE: &str = "plugin.json";
+
+/// KV store key listing enabled plugin ids; plugins start disabled
+const ENABLED_KEY: &str = "plugins.enabled";
+
+/// Instructions a plugin may run per call
+const FUEL_PER_CALL: u64 = 10_000_000;
+
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Longest message a plugin can log in one call
+const MAX_LOG_BYTES: usize = 4096;
+
+/// Failed calls before a plugin is suspended for the session
+const MAX_FAULTS: u32 = 3;
+
+/// Frames waiting for the plugin thread; newer frames are dropped while it is busy
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+const FRAME_QUEUE: usize = 2;
+
+/// Playback events waiting for the plugin thread
+const EVENT_QUEUE: usize = 64;
+
+static HOST: Mutex<Option<PluginHost>> = Mutex::new(None);
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+static FRAMES: OnceLock<SyncSender<VisualizerFrame>> = OnceLock::new();
+
+static EVENTS: OnceLock<SyncSender<PlaybackEvent>> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum PluginError {
+    #[error("Plugin file error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid plugin manifest: {0}")]
+    Manifest(String),
+    #[error("Failed to load plugin module: {0}")]
+    Load(String),
+    #[error("Plugin does not export {0}")]
+    MissingExport(&'static str),
+    #[error("Plugin call failed: {0}")]
+    Trap(String),
+    #[error("Plugin returned invalid data: {0}")]
+    InvalidOutput(String),
+    #[error("No plugin named '{0}'")]
+    NotFound(String),
+}
+
+impl From<wasmi::Error> for PluginError {
+    fn from(err: wasmi::Error) -> Self {
+        PluginError::Trap(err.to_string())
+    }
+}
+
+/// Hooks a plugin can implement, detected from its exports
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Visualizer,
+    Metadata,
+    Playback,
+}
+
+impl Capability {
+    fn export(self) -> &'static str {
+        match self {
+            Capability::Visualizer => "milk_visualizer_frame",
+            Capability::Metadata => "milk_metadata",
+            Capability::Playback => "milk_playback_event",
+        }
+    }
+}
+
+/// Consumes spectrum and beat data from the visualizer
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub trait VisualizerConsumer {
+    fn visualizer_frame(&mut self, frame: &VisualizerFrame) -> Result<(), PluginError>;
+}
+
+/// Supplies tags a file is missing
+pub trait MetadataProvider {
+    fn provide_metadata(&mut self, path: &Path, known: &TrackMetadata) -> Result<Option<TrackMetadata>, PluginError>;
+}
+
+/// Observes playback changes
+pub trait PlaybackHook {
+    fn playback_event(&mut self, event: &PlaybackEvent) -> Result<(), PluginError>;
+}
+
+/// Sent to playback hooks
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    TrackChanged { track: NowPlaying },
+    State { status: PlaybackStatus, position_secs: Option<f64> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Manifest {
+    id: String,
+    name: String,
+    version: String,
+    #[serde(default)]
+    description: String,
+    /// Module file relative to the plugin folder
+    #[serde(default = "default_module")]
+    module: String,
+}
+
+fn default_module() -> String {
+    "plugin.wasm".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginStatus {
+    Loaded,
+    Disabled,
+    /// Failed too often and was suspended until restart or re-enable
+    Suspended,
+    /// Could not be loaded
+    Failed,
+}
+
+/// Returned by `list_plugins`
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub capabilities: Vec<Capability>,
+    pub enabled: bool,
+    pub status: PluginStatus,
+    /// Latest load or call error
+    pub error: Option<String>,
+}
+
+struct HostState {
+    plugin_id: String,
+    limits: StoreLimits,
+}
+
+/// An instantiated plugin module
+pub struct WasmPlugin {
+    store: Store<HostState>,
+    memory: wasmi::Memory,
+    alloc: TypedFunc<i32, i32>,
+    free: Option<TypedFunc<(i32, i32), ()>>,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    visualizer: Option<TypedFunc<(i32, i32), ()>>,
+    metadata: Option<TypedFunc<(i32, i32), i64>>,
+    playback: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+impl WasmPlugin {
+    fn instantiate(engine: &Engine, module: &Module, plugin_id: &str) -> Result<Self, PluginError> {
+        let limits = StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build();
+        let mut store = Store::new(engine, HostState { plugin_id: plugin_id.to_string(), limits });
+        store.limiter(|state| &mut state.limits);
+        store.set_fuel(FUEL_PER_CALL).map_err(|e| PluginError::Load(e.to_string()))?;
+
+        let mut linker = Linker::<HostState>::new(engine);
+        linker
+            .func_wrap("milk", "log", |caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+                let Some(memory) = caller.get_export("memory").and_then(Extern::into_memory) else {
+                    return;
+                };
+                let (Ok(ptr), Ok(len)) = (usize::try_from(ptr), usize::try_from(len)) else {
+                    return;
+                };
+                // Read in place, the plugin controls the length
+                let Some(message) = memory.data(&caller).get(ptr..ptr.saturating_add(len.min(MAX_LOG_BYTES))) else {
+                    return;
+                };
+                let plugin = format!("Plugin:{}", caller.data().plugin_id);
+                log_info(&plugin, &String::from_utf8_lossy(message));
+            })
+            .map_err(|e| PluginError::Load(e.to_string()))?;
+        let instance = linker
+            .instantiate(&mut store, module)
+            .and_then(|pre| pre.start(&mut store))
+            .map_err(|e| PluginError::Load(e.to_string()))?;
+
+        let memory = instance.get_memory(&store, "memory").ok_or(PluginError::MissingExport("memory"))?;
+        let alloc = instance
+            .get_typed_func::<i32, i32>(&store, "milk_alloc")
+            .map_err(|_| PluginError::MissingExport("milk_alloc"))?;
+        Ok(WasmPlugin {
+            free: instance.get_typed_func(&store, "milk_free").ok(),
+            visualizer: instance.get_typed_func(&store, Capability::Visualizer.export()).ok(),
+            metadata: instance.get_typed_func(&store, Capability::Metadata.export()).ok(),
+            playback: instance.get_typed_func(&store, Capability::Playback.export()).ok(),
+            store,
+            memory,
+            alloc,
+        })
+    }
+
+    /// Copy `value` as JSON into plugin memory and pass it to `hook`
+    fn call<R>(&mut self, hook: TypedFunc<(i32, i32), R>, value: &impl Serialize) -> Result<R, PluginError>
+    where
+        R: wasmi::WasmResults,
+    {
+        let json = serde_json::to_vec(value).map_err(|e| PluginError::InvalidOutput(e.to_string()))?;
+        let len = i32::try_from(json.len()).map_err(|_| PluginError::InvalidOutput("argument too large".to_string()))?;
+        self.store.set_fuel(FUEL_PER_CALL).map_err(|e| PluginError::Trap(e.to_string()))?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as usize, &json)
+            .map_err(|e| PluginError::Trap(e.to_string()))?;
+        let result = hook.call(&mut self.store, (ptr, len))?;
+        if let Some(free) = self.free {
+            free.call(&mut self.store, (ptr, len))?;
+        }
+        Ok(result)
+    }
+
+    fn read_output(&self, packed: i64) -> Result<Vec<u8>, PluginError> {
+        let (ptr, len) = ((packed >> 32) as u32 as usize, packed as u32 as usize);
+        if len > MEMORY_LIMIT {
+            return Err(PluginError::InvalidOutput(format!("{} byte result", len)));
+        }
+        let mut output = vec![0; len];
+        self.memory
+            .read(&self.store, ptr, &mut output)
+            .map_err(|e| PluginError::InvalidOutput(e.to_string()))?;
+        Ok(output)
+    }
+}
+
+impl VisualizerConsumer for WasmPlugin {
+    fn visualizer_frame(&mut self, frame: &VisualizerFrame) -> Result<(), PluginError> {
+        let hook = self.visualizer.ok_or(PluginError::MissingExport("milk_visualizer_frame"))?;
+        self.call(hook, frame)
+    }
+}
+
+impl MetadataProvider for WasmPlugin {
+    fn provide_metadata(&mut self, path: &Path, known: &TrackMetadata) -> Result<Option<TrackMetadata>, PluginError> {
+        let hook = self.metadata.ok_or(PluginError::MissingExport("milk_metadata"))?;
+        let packed = self.call(hook, &serde_json::json!({ "path": path, "metadata": known }))?;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let output = self.read_output(packed)?;
+        let provided: PartialMetadata = serde_json::from_slice(&output).map_err(|e| PluginError::InvalidOutput(e.to_string()))?;
+        Ok(Some(provided.into()))
+    }
+}
+
+impl PlaybackHook for WasmPlugin {
+    fn playback_event(&mut self, event: &PlaybackEvent) -> Result<(), PluginError> {
+        let hook = self.playback.ok_or(PluginError::MissingExport("milk_playback_event"))?;
+        self.call(hook, event)
+    }
+}
+
+/// Plugin metadata output, where every field is optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PartialMetadata {
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    year: Option<u32>,
+    genre: Option<String>,
+    track_number: Option<u32>,
+    disc_number: Option<u32>,
+    duration: Option<u32>,
+    bpm: Option<f32>,
+    key: Option<String>,
+}
+
+impl From<PartialMetadata> for TrackMetadata {
+    fn from(m: PartialMetadata) -> Self {
+        TrackMetadata {
+            title: m.title,
+            artist: m.artist,
+            album: m.album,
+            year: m.year,
+            genre: m.genre,
+            track_number: m.track_number,
+            disc_number: m.disc_number,
+            duration: m.duration,
+            bpm: m.bpm,
+            key: m.key,
+            comment: None,
+        }
+    }
+}
+
+/// Fill fields of `metadata` that are missing from `provided`
+fn fill_missing(metadata: &mut TrackMetadata, provided: TrackMetadata) {
+    metadata.title = metadata.title.take().or(provided.title);
+    metadata.artist = metadata.artist.take().or(provided.artist);
+    metadata.album = metadata.album.take().or(provided.album);
+    metadata.year = metadata.year.or(provided.year);
+    metadata.genre = metadata.genre.take().or(provided.genre);
+    metadata.track_number = metadata.track_number.or(provided.track_number);
+    metadata.disc_number = metadata.disc_number.or(provided.disc_number);
+    metadata.duration = metadata.duration.or(provided.duration);
+    metadata.bpm = metadata.bpm.or(provided.bpm);
+    metadata.key = metadata.key.take().or(provided.key);
+    metadata.comment = metadata.comment.take().or(provided.comment);
+}
+
+struct PluginEntry {
+    manifest: Manifest,
+    module: Option<Module>,
+    capabilities: Vec<Capability>,
+    enabled: bool,
+    instance: Option<WasmPlugin>,
+    faults: u32,
+    error: Option<String>,
+}
+
+impl PluginEntry {
+    fn status(&self) -> PluginStatus {
+        match (self.enabled, &self.instance) {
+            (false, _) => PluginStatus::Disabled,
+            (true, Some(_)) => PluginStatus::Loaded,
+            (true, None) if self.faults >= MAX_FAULTS => PluginStatus::Suspended,
+            (true, None) => PluginStatus::Failed,
+        }
+    }
+
+    fn info(&self) -> PluginInfo {
+        PluginInfo {
+            id: self.manifest.id.clone(),
+            name: self.manifest.name.clone(),
+            version: self.manifest.version.clone(),
+            description: self.manifest.description.clone(),
+            capabilities: self.capabilities.clone(),
+            enabled: self.enabled,
+            status: self.status(),
+            error: self.error.clone(),
+        }
+    }
+
+    fn load(&mut self, engine: &Engine) {
+        self.instance = None;
+        self.faults = 0;
+        let Some(module) = &self.module else {
+            return;
+        };
+        match WasmPlugin::instantiate(engine, module, &self.manifest.id) {
+            Ok(instance) => {
+                self.instance = Some(instance);
+                self.error = None;
+            }
+            Err(e) => {
+                log_warn("Plugins", &format!("Failed to start plugin {}: {}", self.manifest.id, e));
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    /// Run `f` on the loaded plugin, suspending it after repeated failures
+    fn dispatch<T>(&mut self, capability: Capability, f: impl FnOnce(&mut WasmPlugin) -> Result<T, PluginError>) -> Option<T> {
+        if !self.capabilities.contains(&capability) {
+            return None;
+        }
+        let instance = self.instance.as_mut()?;
+        match f(instance) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.faults += 1;
+                self.error = Some(e.to_string());
+                log_warn("Plugins", &format!("Plugin {} failed: {}", self.manifest.id, e));
+                if self.faults >= MAX_FAULTS {
+                    log_warn("Plugins", &format!("Suspending plugin {} after {} failures", self.manifest.id, self.faults));
+                    // Dropping the instance discards whatever state the failures left behind
+                    self.instance = None;
+                }
+                None
+            }
+        }
+    }
+}
+
+pub struct PluginHost {
+    engine: Engine,
+    entries: Vec<PluginEntry>,
+}
+
+impl PluginHost {
+    /// Read every plugin folder in `dir` and start the enabled plugins
+    pub fn discover(dir: &Path, enabled: &[String]) -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+
+        let mut folders: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect())
+            .unwrap_or_default();
+        folders.sort();
+
+        let mut entries: Vec<PluginEntry> = Vec::new();
+        for folder in folders {
+            let manifest = match read_manifest(&folder) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    log_warn("Plugins", &format!("Skipping {}: {}", folder.display(), e));
+                    continue;
+                }
+            };
+            if entries.iter().any(|entry| entry.manifest.id == manifest.id) {
+                log_warn("Plugins", &format!("Skipping {}: duplicate plugin id {}", folder.display(), manifest.id));
+                continue;
+            }
+            let (module, error) = match fs::read(folder.join(&manifest.module)) {
+                Ok(bytes) => match Module::new(&engine, &bytes) {
+                    Ok(module) => (Some(module), None),
+                    Err(e) => (None, Some(PluginError::Load(e.to_string()).to_string())),
+                },
+                Err(e) => (None, Some(PluginError::Io(e).to_string())),
+            };
+            let capabilities = module.as_ref().map(capabilities_of).unwrap_or_default();
+            let mut entry = PluginEntry {
+                enabled: enabled.contains(&manifest.id),
+                manifest,
+                module,
+                capabilities,
+                instance: None,
+                faults: 0,
+                error,
+            };
+            if entry.enabled {
+                entry.load(&engine);
+            }
+            entries.push(entry);
+        }
+        PluginHost { engine, entries }
+    }
+
+    pub fn list(&self) -> Vec<PluginInfo> {
+        self.entries.iter().map(PluginEntry::info).collect()
+    }
+
+    pub fn enabled_ids(&self) -> Vec<String> {
+        self.entries.iter().filter(|e| e.enabled).map(|e| e.manifest.id.clone()).collect()
+    }
+
+    /// Enabling a suspended plugin starts it again with a clean slate
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> Result<PluginInfo, PluginError> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.manifest.id == id)
+            .ok_or_else(|| PluginError::NotFound(id.to_string()))?;
+        entry.enabled = enabled;
+        if enabled {
+            entry.load(&self.engine);
+        } else {
+            entry.instance = None;
+        }
+        Ok(entry.info())
+    }
+
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    fn has_capability(&self, capability: Capability) -> bool {
+        self.entries.iter().any(|e| e.instance.is_some() && e.capabilities.contains(&capability))
+    }
+
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    pub fn visualizer_frame(&mut self, frame: &VisualizerFrame) {
+        for entry in &mut self.entries {
+            entry.dispatch(Capability::Visualizer, |plugin| plugin.visualizer_frame(frame));
+        }
+    }
+
+    /// Let metadata plugins fill in missing tags, in plugin order
+    pub fn fill_metadata(&mut self, path: &Path, metadata: &mut TrackMetadata) {
+        for entry in &mut self.entries {
+            if metadata.is_complete() {
+                return;
+            }
+            if let Some(Some(provided)) = entry.dispatch(Capability::Metadata, |plugin| plugin.provide_metadata(path, metadata)) {
+                fill_missing(metadata, provided);
+            }
+        }
+    }
+
+    pub fn playback_event(&mut self, event: &PlaybackEvent) {
+        for entry in &mut self.entries {
+            entry.dispatch(Capability::Playback, |plugin| plugin.playback_event(event));
+        }
+    }
+}
+
+fn read_manifest(folder: &Path) -> Result<Manifest, PluginError> {
+    let contents = fs::read_to_string(folder.join(MANIFEST_FILE))?;
+    let manifest: Manifest = serde_json::from_str(&contents).map_err(|e| PluginError::Manifest(e.to_string()))?;
+    if manifest.id.is_empty() || manifest.id.contains(['/', '\\']) {
+        return Err(PluginError::Manifest(format!("invalid id '{}'", manifest.id)));
+    }
+    Ok(manifest)
+}
+
+fn capabilities_of(module: &Module) -> Vec<Capability> {
+    [Capability::Visualizer, Capability::Metadata, Capability::Playback]
+        .into_iter()
+        .filter(|capability| module.exports().any(|export| export.name() == capability.export()))
+        .collect()
+}
+
+pub fn get_plugins_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("milk").join("plugins"))
+}
+
+/// Discover plugins and start the enabled ones; also used to rescan the folder
+pub fn start(store: &KvStore) -> Vec<PluginInfo> {
+    let Some(dir) = get_plugins_dir() else {
+        return Vec::new();
+    };
+    let enabled: Vec<String> = store.get(ENABLED_KEY).unwrap_or_default();
+    let host = PluginHost::discover(&dir, &enabled);
+    let plugins = host.list();
+    if !plugins.is_empty() {
+        log_info("Plugins", &format!("Found {} plugin(s) in {}", plugins.len(), dir.display()));
+    }
+    *HOST.lock().unwrap() = Some(host);
+    plugins
+}
+
+pub fn list() -> Vec<PluginInfo> {
+    HOST.lock().unwrap().as_ref().map(PluginHost::list).unwrap_or_default()
+}
+
+/// Enable or disable a plugin and remember the choice
+pub fn set_enabled(store: &KvStore, id: &str, enabled: bool) -> Result<PluginInfo, PluginError> {
+    let mut host = HOST.lock().unwrap();
+    let host = host.as_mut().ok_or_else(|| PluginError::NotFound(id.to_string()))?;
+    let info = host.set_enabled(id, enabled)?;
+    if let Err(e) = store.set(ENABLED_KEY, &host.enabled_ids()) {
+        log_warn("Plugins", &format!("Failed to save enabled plugins: {}", e));
+    }
+    Ok(info)
+}
+
+/// Hand a frame to visualizer plugins without blocking the capture thread
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn visualizer_frame(frame: &VisualizerFrame) {
+    let has_consumers = HOST
+        .try_lock()
+        .ok()
+        .and_then(|host| host.as_ref().map(|h| h.has_capability(Capability::Visualizer)))
+        .unwrap_or(false);
+    if !has_consumers {
+        return;
+    }
+    let frames = FRAMES.get_or_init(|| {
+        let (sender, receiver) = sync_channel::<VisualizerFrame>(FRAME_QUEUE);
+        std::thread::spawn(move || {
+            for frame in receiver {
+                if let Some(host) = HOST.lock().unwrap().as_mut() {
+                    host.visualizer_frame(&frame);
+                }
+            }
+        });
+        sender
+    });
+    let _ = frames.try_send(frame.clone());
+}
+
+/// Let metadata plugins fill in missing tags; skipped while the host is busy
+///
+/// Call off the main thread, a plugin may use its whole instruction budget.
+pub fn fill_metadata(path: &Path, metadata: &mut TrackMetadata) {
+    if let Ok(mut host) = HOST.try_lock() {
+        if let Some(host) = host.as_mut() {
+            host.fill_metadata(path, metadata);
+        }
+    }
+}
+
+/// Queue a playback event for playback plugins without blocking the caller
+pub fn playback_event(event: PlaybackEvent) {
+    let has_consumers = HOST
+        .try_lock()
+        .ok()
+        .and_then(|host| host.as_ref().map(|h| h.has_capability(Capability::Playback)))
+        .unwrap_or(true);
+    if !has_consumers {
+        return;
+    }
+    let events = EVENTS.get_or_init(|| {
+        let (sender, receiver) = sync_channel::<PlaybackEvent>(EVENT_QUEUE);
+        std::thread::spawn(move || {
+            for event in receiver {
+                if let Some(host) = HOST.lock().unwrap().as_mut() {
+                    host.playback_event(&event);
+                }
+            }
+        });
+        sender
+    });
+    if events.try_send(event).is_err() {
+        log_warn("Plugins", "Playback plugins are falling behind, dropped an event");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // Fills in the genre after logging it and an oversized message, and spins
+    // forever on playback events
+    const TEST_PLUGIN: &str = r#"
+        (module
+          (import "milk" "log" (func $log (param i32 i32)))
+          (memory (export "memory") 1)
+          (global $next (mut i32) (i32.const 1024))
+          (data (i32.const 16) "{\"genre\":\"Chiptune\"}")
+          (func (export "milk_alloc") (param $len i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $next))
+            (global.set $next (i32.add (global.get $next) (local.get $len)))
+            (local.get $ptr))
+          (func (export "milk_metadata") (param i32 i32) (result i64)
+            (call $log (i32.const 16) (i32.const 20))
+            (call $log (i32.const 16) (i32.const 2147483647))
+            (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const 20)))
+          (func (export "milk_playback_event") (param i32 i32)
+            (loop $spin (br $spin))))
+    "#;
+
+    fn install(dir: &Path, id: &str, wat: &str) {
+        let folder = dir.join(id);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join(MANIFEST_FILE), format!(r#"{{"id":"{}","name":"Test","version":"1.0"}}"#, id)).unwrap();
+        fs::write(folder.join("plugin.wasm"), wat::parse_str(wat).unwrap()).unwrap();
+    }
+
+    fn untagged() -> TrackMetadata {
+        TrackMetadata {
+            title: Some("Song".to_string()),
+            artist: None,
+            album: None,
+            year: None,
+            genre: None,
+            track_number: None,
+            disc_number: None,
+            duration: None,
+            bpm: None,
+            key: None,
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_discover_and_fill_metadata() {
+        let dir = TempDir::new().unwrap();
+        install(dir.path(), "chip", TEST_PLUGIN);
+        install(dir.path(), "off", TEST_PLUGIN);
+        fs::create_dir_all(dir.path().join("broken")).unwrap();
+
+        let mut host = PluginHost::discover(dir.path(), &["chip".to_string()]);
+        let plugins = host.list();
+        assert_eq!(plugins.len(), 2);
+        assert_eq!(plugins[0].status, PluginStatus::Loaded);
+        assert_eq!(plugins[0].capabilities, [Capability::Metadata, Capability::Playback]);
+        assert_eq!(plugins[1].status, PluginStatus::Disabled);
+
+        let mut metadata = untagged();
+        host.fill_metadata(Path::new("song.mp3"), &mut metadata);
+        assert_eq!(metadata.genre.as_deref(), Some("Chiptune"));
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn test_failing_plugin_is_suspended() {
+        let dir = TempDir::new().unwrap();
+        install(dir.path(), "chip", TEST_PLUGIN);
+        let mut host = PluginHost::discover(dir.path(), &["chip".to_string()]);
+
+        let event = PlaybackEvent::State { status: PlaybackStatus::Playing, position_secs: None };
+        for _ in 0..MAX_FAULTS {
+            host.playback_event(&event);
+        }
+        let info = &host.list()[0];
+        assert_eq!(info.status, PluginStatus::Suspended);
+        assert!(info.error.is_some());
+
+        // Suspended plugins are skipped until re-enabled
+        let mut metadata = untagged();
+        host.fill_metadata(Path::new("song.mp3"), &mut metadata);
+        assert_eq!(metadata.genre, None);
+        assert_eq!(host.set_enabled("chip", true).unwrap().status, PluginStatus::Loaded);
+        assert!(matches!(host.set_enabled("missing", true), Err(PluginError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_plugin_without_allocator_fails_to_load() {
+        let dir = TempDir::new().unwrap();
+        install(dir.path(), "bare", r#"(module (memory (export "memory") 1))"#);
+        let host = PluginHost::discover(dir.path(), &["bare".to_string()]);
+        let info = &host.list()[0];
+        assert_eq!(info.status, PluginStatus::Failed);
+        assert!(info.error.as_deref().unwrap().contains("milk_alloc"));
+    }
+}
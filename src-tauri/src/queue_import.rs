@@ -0,0 +1,328 @@
+// Folder drop expansion into ordered queue entries
+// Orders tracks by folder, disc and track number, then natural filename order.
+// Also reads M3U and PLS playlist files handed to the app from outside.
+
+use crate::library::LibraryScanner;
+use crate::metadata::{MetadataExtractor, TrackMetadata};
+use crate::playlist::{Track, TrackMetadata as PlaylistTrackMetadata};
+use crate::sorting::{compare_disc_track, natural_cmp};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How far into a dropped folder to look for tracks
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EnqueueMode {
+    /// Only files directly inside the folder
+    TopLevel,
+    /// The folder and all of its subfolders
+    Recursive,
+}
+
+/// Result of expanding a folder into queue entries
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnqueueSummary {
+    /// Tracks in playback order
+    pub tracks: Vec<Track>,
+    pub folders_scanned: usize,
+    /// Files skipped because their format is unsupported
+    pub skipped_files: usize,
+    /// Tracks added with filename-only info because tags could not be read
+    pub metadata_failures: usize,
+}
+
+/// A discovered file with the fields used for ordering
+struct Candidate {
+    path: PathBuf,
+    folder: String,
+    file_name: String,
+    metadata: Option<TrackMetadata>,
+}
+
+/// Expand `folder` into ordered queue entries
+pub fn expand_folder(folder: &Path, mode: EnqueueMode, extractor: &MetadataExtractor) -> Result<EnqueueSummary, std::io::Error> {
+    if !folder.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Not a folder: {}", folder.display()),
+        ));
+    }
+
+    let mut files = Vec::new();
+    let mut folders_scanned = 0;
+    let mut skipped_files = 0;
+    collect_files(folder, folder, mode, &mut files, &mut folders_scanned, &mut skipped_files)?;
+
+    let mut metadata_failures = 0;
+    let mut candidates: Vec<Candidate> = files
+        .into_iter()
+        .map(|(path, relative_folder)| {
+            let metadata = extractor.extract(&path).ok();
+            if metadata.is_none() {
+                metadata_failures += 1;
+            }
+            Candidate {
+                file_name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                folder: relative_folder,
+                path,
+                metadata,
+            }
+        })
+        .collect();
+
+    candidates.sort_by(compare_candidates);
+
+    Ok(EnqueueSummary {
+        tracks: candidates.into_iter().map(to_track).collect(),
+        folders_scanned,
+        skipped_files,
+        metadata_failures,
+    })
+}
+
+/// Gather supported files along with their folder relative to `root`
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    mode: EnqueueMode,
+    files: &mut Vec<(PathBuf, String)>,
+    folders_scanned: &mut usize,
+    skipped_files: &mut usize,
+) -> Result<(), std::io::Error> {
+    *folders_scanned += 1;
+    let relative = dir
+        .strip_prefix(root)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if mode == EnqueueMode::Recursive {
+                collect_files(root, &path, mode, files, folders_scanned, skipped_files)?;
+            }
+        } else if path.is_file() {
+            let supported = path
+                .extension()
+                .map(|e| LibraryScanner::is_supported_extension(&e.to_string_lossy()))
+                .unwrap_or(false);
+            if supported {
+                files.push((path, relative.clone()));
+            } else {
+                *skipped_files += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// True for playlist files `read_playlist_file` understands
+pub fn is_playlist_file(path: &Path) -> bool {
+    path.extension()
+        .map(|e| matches!(e.to_string_lossy().to_lowercase().as_str(), "m3u" | "m3u8" | "pls"))
+        .unwrap_or(false)
+}
+
+/// Local file entries of an M3U/M3U8 or PLS playlist, in order
+///
+/// Relative entries are resolved against the playlist's folder. Stream URLs
+/// are left out since the queue only plays local files.
+pub fn read_playlist_file(path: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let content = fs::read_to_string(path)?;
+    let content = content.trim_start_matches('\u{feff}');
+    let base = path.parent().unwrap_or(Path::new(""));
+    let is_pls = path
+        .extension()
+        .map(|e| e.to_string_lossy().eq_ignore_ascii_case("pls"))
+        .unwrap_or(false);
+
+    let entries = content.lines().map(str::trim).filter_map(|line| {
+        if is_pls {
+            let (key, value) = line.split_once('=')?;
+            key.to_lowercase().starts_with("file").then_some(value.trim())
+        } else {
+            (!line.is_empty() && !line.starts_with('#')).then_some(line)
+        }
+    });
+    Ok(entries
+        .filter_map(|entry| {
+            if entry.starts_with("file://") {
+                return url::Url::parse(entry).ok()?.to_file_path().ok();
+            }
+            if entry.contains("://") {
+                return None;
+            }
+            Some(base.join(entry))
+        })
+        .collect())
+}
+
+/// Queue entry for a single file, using filename-only info if tags can't be read
+pub fn track_for_file(path: &Path, extractor: &MetadataExtractor) -> Track {
+    to_track(Candidate {
+        path: path.to_path_buf(),
+        folder: String::new(),
+        file_name: path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        metadata: extractor.extract(path).ok(),
+    })
+}
+
+/// Folder, then disc and track number as the listing views order them, then natural filename
+fn compare_candidates(a: &Candidate, b: &Candidate) -> Ordering {
+    let disc_track = |c: &Candidate| c.metadata.as_ref().map_or((None, None), |m| (m.disc_number, m.track_number));
+
+    natural_cmp(&a.folder, &b.folder)
+        .then_with(|| compare_disc_track(disc_track(a), disc_track(b)))
+        .then_with(|| natural_cmp(&a.file_name, &b.file_name))
+}
+
+fn to_track(candidate: Candidate) -> Track {
+    let file_path = crate::path_codec::encode(&candidate.path);
+    let metadata = candidate.metadata.unwrap_or(TrackMetadata {
+        title: None,
+        artist: None,
+        album: None,
+        year: None,
+        genre: None,
+        track_number: None,
+        disc_number: None,
+        duration: None,
+        bpm: None,
+        key: None,
+        comment: None,
+    });
+    let stem = candidate
+        .path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Track {
+        id: LibraryScanner::generate_id(&file_path),
+        title: metadata.title.unwrap_or(stem),
+        artist: metadata.artist.unwrap_or_else(|| "Unknown Artist".to_string()),
+        album: metadata.album.unwrap_or_else(|| "Unknown Album".to_string()),
+        duration: metadata.duration.map(|d| d as f64).unwrap_or(0.0),
+        file_path: Some(file_path),
+        source: "local".to_string(),
+        metadata: PlaylistTrackMetadata {
+            year: metadata.year,
+            genre: metadata.genre,
+            track_number: metadata.track_number,
+            disc_number: metadata.disc_number,
+            album_art: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn test_expand_top_level_sorts_naturally() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["10 - ten.wav", "2 - two.wav", "1 - one.wav", "cover.jpg"] {
+            touch(&temp_dir.path().join(name));
+        }
+        touch(&temp_dir.path().join("sub").join("3 - three.wav"));
+
+        let extractor = MetadataExtractor::new();
+        let summary = expand_folder(temp_dir.path(), EnqueueMode::TopLevel, &extractor).unwrap();
+
+        let titles: Vec<_> = summary.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["one", "two", "ten"]);
+        assert_eq!(summary.skipped_files, 1);
+        assert_eq!(summary.folders_scanned, 1);
+    }
+
+    #[test]
+    fn test_expand_recursive_orders_by_folder() {
+        let temp_dir = TempDir::new().unwrap();
+        touch(&temp_dir.path().join("CD10").join("a.wav"));
+        touch(&temp_dir.path().join("CD2").join("b.wav"));
+        touch(&temp_dir.path().join("root.wav"));
+
+        let extractor = MetadataExtractor::new();
+        let summary = expand_folder(temp_dir.path(), EnqueueMode::Recursive, &extractor).unwrap();
+
+        let titles: Vec<_> = summary.tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["root", "b", "a"]);
+        assert_eq!(summary.folders_scanned, 3);
+    }
+
+    #[test]
+    fn test_tagged_tracks_sort_by_disc_then_track() {
+        let candidate = |name: &str, disc, track| Candidate {
+            path: PathBuf::from(name),
+            folder: String::new(),
+            file_name: name.to_string(),
+            metadata: Some(TrackMetadata {
+                title: None,
+                artist: None,
+                album: None,
+                year: None,
+                genre: None,
+                track_number: track,
+                disc_number: disc,
+                duration: None,
+                bpm: None,
+                key: None,
+                comment: None,
+            }),
+        };
+        let mut candidates = [
+            candidate("z.mp3", Some(1), Some(1)),
+            candidate("untagged.mp3", None, None),
+            candidate("a.mp3", Some(2), Some(1)),
+            candidate("m.mp3", Some(1), Some(2)),
+        ];
+        candidates.sort_by(compare_candidates);
+
+        let names: Vec<_> = candidates.iter().map(|c| c.file_name.as_str()).collect();
+        // An untagged file counts as disc 1, after its numbered tracks
+        assert_eq!(names, vec!["z.mp3", "m.mp3", "untagged.mp3", "a.mp3"]);
+    }
+
+    #[test]
+    fn test_read_playlist_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let m3u = temp_dir.path().join("mix.m3u8");
+        let url = url::Url::from_file_path(temp_dir.path().join("My Music").join("d.mp3")).unwrap();
+        fs::write(&m3u, format!("\u{feff}#EXTM3U\n#EXTINF:120,Song\nsub/a.mp3\n\nhttp://radio/stream\n/abs/b.flac\n{}\n", url)).unwrap();
+        assert_eq!(
+            read_playlist_file(&m3u).unwrap(),
+            vec![
+                temp_dir.path().join("sub/a.mp3"),
+                PathBuf::from("/abs/b.flac"),
+                temp_dir.path().join("My Music").join("d.mp3"),
+            ]
+        );
+
+        let pls = temp_dir.path().join("mix.pls");
+        fs::write(&pls, "[playlist]\nFile1=c.ogg\nTitle1=C\nNumberOfEntries=1\n").unwrap();
+        assert_eq!(read_playlist_file(&pls).unwrap(), vec![temp_dir.path().join("c.ogg")]);
+        assert!(is_playlist_file(&pls) && !is_playlist_file(Path::new("c.ogg")));
+    }
+
+    #[test]
+    fn test_expand_missing_folder() {
+        let extractor = MetadataExtractor::new();
+        assert!(expand_folder(Path::new("/nonexistent/folder"), EnqueueMode::Recursive, &extractor).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+// Track ratings and favorites
+// Star ratings (0-5) and a loved flag per file, stored in the KV store and
+// optionally written back to the file's tags (ID3 POPM for MP3, FMPS_RATING for FLAC).
+
+use crate::kv_store::{KvError, KvStore};
+use id3::TagLike;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use thiserror::Error;
+
+/// Key prefix for per-file ratings in the KV store
+pub const RATING_KEY_PREFIX: &str = "rating.";
+
+pub const MAX_STARS: u8 = 5;
+
+/// POPM user identifier for ratings written by milk
+const POPM_USER: &str = "milk";
+
+#[derive(Error, Debug)]
+pub enum RatingError {
+    #[error("Rating must be between 0 and {MAX_STARS} stars, got {0}")]
+    InvalidStars(u8),
+    #[error("Failed to store rating: {0}")]
+    Store(#[from] KvError),
+    #[error("Failed to write rating tag: {0}")]
+    Tag(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackRating {
+    pub stars: u8,
+    #[serde(default)]
+    pub loved: bool,
+}
+
+impl TrackRating {
+    /// True when the rating has at least `min_stars` and, if `loved` is set, the same loved flag
+    pub fn satisfies(&self, min_stars: Option<u8>, loved: Option<bool>) -> bool {
+        min_stars.is_none_or(|min| self.stars >= min) && loved.is_none_or(|loved| self.loved == loved)
+    }
+}
+
+/// KV store key holding the rating of a file
+pub fn rating_key(file_path: &str) -> String {
+    format!("{}{}", RATING_KEY_PREFIX, file_path)
+}
+
+/// Stored rating for a file; unrated files have zero stars and are not loved
+pub fn get_rating(store: &KvStore, file_path: &str) -> TrackRating {
+    store.get(&rating_key(file_path)).unwrap_or_default()
+}
+
+/// Store a rating, dropping the entry entirely when it returns to the default
+pub fn set_rating(store: &KvStore, file_path: &str, rating: TrackRating) -> Result<(), RatingError> {
+    if rating.stars > MAX_STARS {
+        return Err(RatingError::InvalidStars(rating.stars));
+    }
+    let key = rating_key(file_path);
+    if rating == TrackRating::default() {
+        store.remove(&key)?;
+    } else {
+        store.set(&key, &rating)?;
+    }
+    Ok(())
+}
+
+/// Write the star rating into the file's tags
+///
+/// MP3 files get a POPM frame (WMP's 1/64/128/196/255 scale) plus an
+/// FMPS_Rating text frame; FLAC files get an FMPS_RATING comment (0.0-1.0).
+/// Other formats are left untouched.
+pub fn write_rating_tag(file_path: &Path, rating: TrackRating) -> Result<bool, RatingError> {
+    let extension = file_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let fmps = format!("{:.1}", rating.stars as f32 / MAX_STARS as f32);
+    match extension.as_deref() {
+        Some("mp3") => {
+            let mut tag = match id3::Tag::read_from_path(file_path) {
+                Ok(tag) => tag,
+                Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+                Err(e) => return Err(RatingError::Tag(e.to_string())),
+            };
+            tag.add_frame(id3::frame::Popularimeter {
+                user: POPM_USER.to_string(),
+                rating: popm_rating(rating.stars),
+                counter: 0,
+            });
+            tag.add_frame(id3::frame::ExtendedText {
+                description: "FMPS_Rating".to_string(),
+                value: fmps,
+            });
+            tag.write_to_path(file_path, id3::Version::Id3v24)
+                .map_err(|e| RatingError::Tag(e.to_string()))?;
+            Ok(true)
+        }
+        Some("flac") => {
+            let mut tag = metaflac::Tag::read_from_path(file_path).map_err(|e| RatingError::Tag(e.to_string()))?;
+            tag.set_vorbis("FMPS_RATING", vec![fmps]);
+            tag.save().map_err(|e| RatingError::Tag(e.to_string()))?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Star rating stored in the file's tags by milk or another player, if any
+///
+/// MP3 files are read from any POPM frame, then an FMPS_Rating text frame;
+/// FLAC files from FMPS_RATING (0.0-1.0), then RATING, which players write
+/// either as 1-5 stars or as a 0-100 score.
+pub fn read_rating_tag(file_path: &Path) -> Option<u8> {
+    let extension = file_path.extension()?.to_str()?.to_lowercase();
+    let fmps_stars = |value: &str| value.trim().parse::<f32>().ok().map(|v| (v.clamp(0.0, 1.0) * MAX_STARS as f32).round() as u8);
+    let stars = match extension.as_str() {
+        "mp3" => {
+            let tag = id3::Tag::read_from_path(file_path).ok()?;
+            let popm = tag.frames().find_map(|f| f.content().popularimeter()).map(|popm| popm_stars(popm.rating));
+            popm.or_else(|| {
+                tag.extended_texts().find(|t| t.description.eq_ignore_ascii_case("FMPS_Rating")).and_then(|t| fmps_stars(&t.value))
+            })
+        }
+        "flac" => {
+            let tag = metaflac::Tag::read_from_path(file_path).ok()?;
+            let comment = |key: &str| tag.get_vorbis(key).and_then(|mut values| values.next().map(str::to_string));
+            comment("FMPS_RATING").and_then(|value| fmps_stars(&value)).or_else(|| {
+                let rating = comment("RATING")?.trim().parse::<u32>().ok()?;
+                let stars = if rating <= MAX_STARS as u32 { rating } else { (rating.min(100) + 10) / 20 };
+                Some(stars as u8)
+            })
+        }
+        _ => None,
+    };
+    stars.filter(|stars| *stars > 0)
+}
+
+/// Inverse of `popm_rating`, also accepting in-between values other players write
+fn popm_stars(rating: u8) -> u8 {
+    match rating {
+        0 => 0,
+        1..=31 => 1,
+        32..=95 => 2,
+        96..=159 => 3,
+        160..=223 => 4,
+        _ => 5,
+    }
+}
+
+fn popm_rating(stars: u8) -> u8 {
+    match stars {
+        0 => 0,
+        1 => 1,
+        2 => 64,
+        3 => 128,
+        4 => 196,
+        _ => 255,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_set_and_clear_rating() {
+        let store = KvStore::in_memory();
+        let rating = TrackRating { stars: 4, loved: true };
+        set_rating(&store, "/music/a.mp3", rating).unwrap();
+        assert_eq!(get_rating(&store, "/music/a.mp3"), rating);
+
+        set_rating(&store, "/music/a.mp3", TrackRating::default()).unwrap();
+        assert!(store.get::<TrackRating>(&rating_key("/music/a.mp3")).is_none());
+        assert!(matches!(
+            set_rating(&store, "/music/a.mp3", TrackRating { stars: 6, loved: false }),
+            Err(RatingError::InvalidStars(6))
+        ));
+    }
+
+    #[test]
+    fn test_write_popm_tag() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("a.mp3");
+        std::fs::write(&path, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+
+        assert!(write_rating_tag(&path, TrackRating { stars: 3, loved: false }).unwrap());
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        let popm = tag.frames().find_map(|f| f.content().popularimeter()).unwrap();
+        assert_eq!((popm.user.as_str(), popm.rating), (POPM_USER, 128));
+        assert_eq!(read_rating_tag(&path), Some(3));
+        assert_eq!(popm_stars(popm_rating(4)), 4);
+        assert_eq!(popm_stars(242), 5);
+    }
+
+    #[test]
+    fn test_satisfies() {
+        let rating = TrackRating { stars: 3, loved: true };
+        assert!(rating.satisfies(Some(3), Some(true)));
+        assert!(!rating.satisfies(Some(4), None));
+        assert!(!rating.satisfies(None, Some(false)));
+    }
+}
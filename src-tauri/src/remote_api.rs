@@ -0,0 +1,508 @@
+// Local HTTP remote control
+// An optional REST API so phones and scripts on the LAN can drive playback,
+// in the spirit of Winamp's httpQ plugin. Every request needs the bearer token
+// kept in secure storage; transport commands reach the frontend player as
+// `media-control` events, the same path media keys take. `/api/events` is a
+// WebSocket stream of player changes for overlays and stream widgets.
+
+use crate::audio_dsp::VisualizerFrame;
+use crate::config::RemoteApiSettings;
+use crate::media_session::{MediaKeyAction, NowPlaying, PlaybackStatus};
+use crate::playlist::Track;
+use crate::secure_storage::{SecureStorage, StorageError};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use thiserror::Error;
+use tokio::sync::{broadcast, watch};
+
+/// Secure storage key of the API token
+pub const TOKEN_KEY: &str = "remote_api_token";
+
+/// Events buffered per WebSocket client before the slowest ones skip ahead
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum RemoteApiError {
+    #[error("Failed to listen on port {port}: {source}")]
+    Bind { port: u16, source: std::io::Error },
+    #[error("Failed to access remote API token: {0}")]
+    Token(#[from] StorageError),
+}
+
+/// Playback state reported by the frontend, served to remote clients
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSnapshot {
+    pub now_playing: Option<NowPlaying>,
+    pub status: PlaybackStatus,
+    pub position_secs: Option<f64>,
+    pub queue: Vec<Track>,
+    /// Index of the current track in `queue`
+    pub queue_index: Option<usize>,
+    /// When `position_secs` was reported
+    #[serde(skip)]
+    pub position_updated: Option<Instant>,
+}
+
+impl PlayerSnapshot {
+    /// Position at `now`, advanced from the last report while playing
+    pub fn position_at(&self, now: Instant) -> Option<f64> {
+        let reported = self.position_secs?;
+        let elapsed = match (self.status, self.position_updated) {
+            (PlaybackStatus::Playing, Some(updated)) => now.saturating_duration_since(updated).as_secs_f64(),
+            _ => 0.0,
+        };
+        let position = reported + elapsed;
+        Some(match self.now_playing.as_ref().and_then(|t| t.duration_secs) {
+            Some(duration) => position.min(duration),
+            None => position,
+        })
+    }
+}
+
+/// Message sent to `/api/events` subscribers
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemoteEvent {
+    NowPlaying(NowPlaying),
+    PlayerState {
+        status: PlaybackStatus,
+        position_secs: Option<f64>,
+    },
+    /// Only sent to clients that connect with `?visualizer=true`
+    // Frames come from loopback capture, which is Windows-only for now
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    VisualizerFrame(VisualizerFrame),
+}
+
+fn event_bus() -> &'static broadcast::Sender<RemoteEvent> {
+    static EVENTS: OnceLock<broadcast::Sender<RemoteEvent>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+fn publish(event: RemoteEvent) {
+    // Fails only when nobody is subscribed
+    let _ = event_bus().send(event);
+}
+
+/// Forward a visualizer frame to WebSocket clients; cheap when none are connected
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn publish_visualizer_frame(frame: &VisualizerFrame) {
+    if event_bus().receiver_count() > 0 {
+        publish(RemoteEvent::VisualizerFrame(frame.clone()));
+    }
+}
+
+pub struct PlayerState(Mutex<PlayerSnapshot>);
+
+impl PlayerState {
+    pub const fn new() -> Self {
+        Self(Mutex::new(PlayerSnapshot {
+            now_playing: None,
+            status: PlaybackStatus::Stopped,
+            position_secs: None,
+            queue: Vec::new(),
+            queue_index: None,
+            position_updated: None,
+        }))
+    }
+
+    /// Record a playback update; `track` is only given when it changes
+    ///
+    /// Track changes and status transitions are published to event
+    /// subscribers; position-only updates are not.
+    pub fn set_playback(&self, track: Option<NowPlaying>, status: PlaybackStatus, position_secs: Option<f64>) {
+        let mut state = self.0.lock().unwrap();
+        if let Some(track) = track.filter(|t| state.now_playing.as_ref() != Some(t)) {
+            state.now_playing = Some(track.clone());
+            publish(RemoteEvent::NowPlaying(track));
+        }
+        if state.status != status {
+            publish(RemoteEvent::PlayerState { status, position_secs });
+        }
+        state.status = status;
+        state.position_secs = position_secs;
+        state.position_updated = position_secs.map(|_| Instant::now());
+    }
+
+    pub fn set_queue(&self, queue: Vec<Track>, queue_index: Option<usize>) {
+        let mut state = self.0.lock().unwrap();
+        state.queue_index = queue_index.filter(|i| *i < queue.len());
+        state.queue = queue;
+    }
+
+    pub fn snapshot(&self) -> PlayerSnapshot {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives transport commands from remote clients
+pub type ControlSink = Arc<dyn Fn(MediaKeyAction) + Send + Sync>;
+
+#[derive(Clone)]
+struct ApiState {
+    token: Arc<str>,
+    player: &'static PlayerState,
+    control: ControlSink,
+    /// Flips to true when the server stops, closing open WebSockets
+    shutdown: watch::Receiver<bool>,
+}
+
+#[derive(Serialize)]
+struct NowPlayingResponse {
+    now_playing: Option<NowPlaying>,
+    status: PlaybackStatus,
+    position_secs: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct QueueResponse {
+    tracks: Vec<Track>,
+    current_index: Option<usize>,
+}
+
+/// Routes of the remote API, all behind token auth
+fn router(token: &str, player: &'static PlayerState, control: ControlSink, shutdown: watch::Receiver<bool>) -> Router {
+    let state = ApiState { token: Arc::from(token), player, control, shutdown };
+    Router::new()
+        .route("/api/now-playing", get(now_playing))
+        .route("/api/queue", get(queue))
+        .route("/api/events", get(events))
+        .route("/api/play", post(|s: State<ApiState>| transport(s, MediaKeyAction::Play)))
+        .route("/api/pause", post(|s: State<ApiState>| transport(s, MediaKeyAction::Pause)))
+        .route("/api/toggle", post(|s: State<ApiState>| transport(s, MediaKeyAction::Toggle)))
+        .route("/api/next", post(|s: State<ApiState>| transport(s, MediaKeyAction::Next)))
+        .route("/api/previous", post(|s: State<ApiState>| transport(s, MediaKeyAction::Previous)))
+        .route("/api/stop", post(|s: State<ApiState>| transport(s, MediaKeyAction::Stop)))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+async fn now_playing(State(state): State<ApiState>) -> Json<NowPlayingResponse> {
+    let snapshot = state.player.snapshot();
+    Json(NowPlayingResponse {
+        now_playing: snapshot.now_playing,
+        status: snapshot.status,
+        position_secs: snapshot.position_secs,
+    })
+}
+
+async fn queue(State(state): State<ApiState>) -> Json<QueueResponse> {
+    let snapshot = state.player.snapshot();
+    Json(QueueResponse { tracks: snapshot.queue, current_index: snapshot.queue_index })
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    #[serde(default)]
+    visualizer: bool,
+}
+
+async fn events(State(state): State<ApiState>, Query(query): Query<EventsQuery>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| stream_events(socket, state, query.visualizer))
+}
+
+/// Send the current state, then every event until the client or server goes away
+async fn stream_events(mut socket: WebSocket, mut state: ApiState, visualizer: bool) {
+    let mut events = event_bus().subscribe();
+    let snapshot = state.player.snapshot();
+    let initial = snapshot
+        .now_playing
+        .map(RemoteEvent::NowPlaying)
+        .into_iter()
+        .chain([RemoteEvent::PlayerState { status: snapshot.status, position_secs: snapshot.position_secs }]);
+    for event in initial {
+        if send_event(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(RemoteEvent::VisualizerFrame(_)) if !visualizer => {}
+                Ok(event) => {
+                    if send_event(&mut socket, &event).await.is_err() {
+                        return;
+                    }
+                }
+                // A slow client misses events rather than holding up the rest
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            _ = state.shutdown.changed() => {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+        }
+    }
+}
+
+async fn send_event(socket: &mut WebSocket, event: &RemoteEvent) -> Result<(), axum::Error> {
+    let json = serde_json::to_string(event).map_err(axum::Error::new)?;
+    socket.send(Message::Text(json.into())).await
+}
+
+async fn transport(State(state): State<ApiState>, action: MediaKeyAction) -> StatusCode {
+    (state.control)(action);
+    StatusCode::NO_CONTENT
+}
+
+/// Accept `Authorization: Bearer <token>` or, for clients that can't set
+/// headers, a `token` query parameter
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let query_token = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+
+    match header_token.or(query_token) {
+        Some(token) if tokens_match(token, &state.token) => next.run(request).await,
+        _ => (StatusCode::UNAUTHORIZED, "Invalid or missing API token").into_response(),
+    }
+}
+
+/// Compare without short-circuiting so response timing doesn't leak the token
+fn tokens_match(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Stored API token, generating and storing one on first use
+pub fn get_or_create_token(storage: &impl SecureStorage) -> Result<String, RemoteApiError> {
+    if let Some(token) = storage.retrieve(TOKEN_KEY)? {
+        return Ok(token);
+    }
+    regenerate_token(storage)
+}
+
+/// Replace the API token, locking out every existing client
+pub fn regenerate_token(storage: &impl SecureStorage) -> Result<String, RemoteApiError> {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    storage.store(TOKEN_KEY, &token)?;
+    Ok(token)
+}
+
+/// A running API server; dropping it shuts the server down
+pub struct RemoteServer {
+    addr: SocketAddr,
+    shutdown: watch::Sender<bool>,
+    task: Option<tauri::async_runtime::JoinHandle<()>>,
+}
+
+impl RemoteServer {
+    /// Bind the configured port and serve the API in the background
+    ///
+    /// Binding happens before returning so a port in use is reported to the caller.
+    pub fn start(
+        settings: &RemoteApiSettings,
+        token: &str,
+        player: &'static PlayerState,
+        control: ControlSink,
+    ) -> Result<Self, RemoteApiError> {
+        let host = if settings.allow_lan { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+        let bind_error = |source| RemoteApiError::Bind { port: settings.port, source };
+        let listener = std::net::TcpListener::bind((host, settings.port)).map_err(bind_error)?;
+        listener.set_nonblocking(true).map_err(bind_error)?;
+        let addr = listener.local_addr().map_err(bind_error)?;
+
+        let (shutdown, mut stopped) = watch::channel(false);
+        let app = router(token, player, control, stopped.clone());
+        let task = tauri::async_runtime::spawn(async move {
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    crate::logging::log_error("RemoteApi", &format!("Failed to start listener: {}", e));
+                    return;
+                }
+            };
+            let result = axum::serve(listener, app)
+                .with_graceful_shutdown(async move {
+                    let _ = stopped.changed().await;
+                })
+                .await;
+            if let Err(e) = result {
+                crate::logging::log_error("RemoteApi", &format!("Server stopped: {}", e));
+            }
+        });
+        Ok(Self { addr, shutdown, task: Some(task) })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Shut down and wait until the port is released
+    pub async fn stop(mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+/// Reported by `get_remote_api_status`
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RemoteApiStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub allow_lan: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PLAYER: PlayerState = PlayerState::new();
+
+    fn start_test_server() -> (RemoteServer, Arc<Mutex<Vec<MediaKeyAction>>>) {
+        let actions: Arc<Mutex<Vec<MediaKeyAction>>> = Arc::default();
+        let recorded = actions.clone();
+        let control: ControlSink = Arc::new(move |action| recorded.lock().unwrap().push(action));
+        let settings = RemoteApiSettings { enabled: true, port: 0, allow_lan: false };
+        let server = RemoteServer::start(&settings, "secret", &PLAYER, control).unwrap();
+        (server, actions)
+    }
+
+    #[tokio::test]
+    async fn test_requests_need_token() {
+        let (server, actions) = start_test_server();
+        let url = format!("http://{}/api/next", server.addr());
+        let client = reqwest::Client::new();
+
+        let response = client.post(&url).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let response = client.post(&url).bearer_auth("wrong").send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert!(actions.lock().unwrap().is_empty());
+
+        let response = client.post(&url).bearer_auth("secret").send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+        let response = client.post(format!("{}?token=secret", url)).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+        assert_eq!(*actions.lock().unwrap(), vec![MediaKeyAction::Next, MediaKeyAction::Next]);
+    }
+
+    #[tokio::test]
+    async fn test_now_playing() {
+        let (server, _) = start_test_server();
+        let track = NowPlaying {
+            title: "Song".to_string(),
+            artist: Some("Artist".to_string()),
+            album: None,
+            duration_secs: Some(180.0),
+            file_path: None,
+            artwork_url: None,
+        };
+        PLAYER.set_playback(Some(track), PlaybackStatus::Playing, Some(12.5));
+        PLAYER.set_playback(None, PlaybackStatus::Paused, Some(13.0));
+
+        let body: serde_json::Value = reqwest::Client::new()
+            .get(format!("http://{}/api/now-playing", server.addr()))
+            .bearer_auth("secret")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(body["now_playing"]["title"], "Song");
+        assert_eq!(body["status"], "paused");
+        assert_eq!(body["position_secs"], 13.0);
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+        let (server, _) = start_test_server();
+        let url = format!("ws://{}/api/events?token=secret", server.addr());
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        // The current state arrives first, once the server is subscribed
+        assert!(matches!(socket.next().await, Some(Ok(WsMessage::Text(_)))));
+
+        // No visualizer opt-in, so this frame must not arrive
+        publish_visualizer_frame(&VisualizerFrame {
+            bins: vec![],
+            bands: vec![],
+            volume: 0.0,
+            beat: false,
+            onset: false,
+            beat_strength: 1.0,
+            sample_rate: 48000,
+        });
+        let track = NowPlaying {
+            title: "Streamed".to_string(),
+            artist: None,
+            album: None,
+            duration_secs: None,
+            file_path: None,
+            artwork_url: None,
+        };
+        PLAYER.set_playback(Some(track), PlaybackStatus::Playing, Some(0.0));
+
+        // Other tests share the player, so skip anything that isn't ours
+        let mut streamed = false;
+        while let Some(Ok(WsMessage::Text(text))) = socket.next().await {
+            let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+            assert_ne!(event["type"], "visualizer_frame");
+            if event["type"] == "now_playing" && event["title"] == "Streamed" {
+                streamed = true;
+                break;
+            }
+        }
+        assert!(streamed);
+        server.stop().await;
+    }
+
+    #[test]
+    fn test_tokens_match() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abd", "abc"));
+        assert!(!tokens_match("ab", "abc"));
+    }
+
+    #[test]
+    fn test_token_persists_until_regenerated() {
+        let storage = crate::secure_storage::InMemorySecureStorage::new();
+        let token = get_or_create_token(&storage).unwrap();
+        assert_eq!(get_or_create_token(&storage).unwrap(), token);
+
+        let regenerated = regenerate_token(&storage).unwrap();
+        assert_ne!(regenerated, token);
+        assert_eq!(get_or_create_token(&storage).unwrap(), regenerated);
+    }
+}
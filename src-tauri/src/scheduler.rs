@@ -0,0 +1,268 @@
+// Sleep timer and alarm scheduling
+// Timers run as tokio tasks and notify the frontend through events when they fire
+
+use crate::logging::{log_info, log_warn};
+use chrono::{DateTime, Local, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::JoinHandle;
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+
+/// Longest sleep timer accepted (24 hours)
+pub const MAX_SLEEP_MINUTES: u32 = 24 * 60;
+
+/// How long the frontend should take to fade out before stopping
+pub const FADE_OUT_SECS: u32 = 30;
+
+/// Grace period after the `sleep-timer` event before a quit, so the frontend can save state
+const QUIT_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Error, Debug)]
+pub enum SchedulerError {
+    #[error("Sleep timer must be between 1 and {MAX_SLEEP_MINUTES} minutes")]
+    InvalidDuration,
+    #[error("Invalid alarm time '{0}', expected HH:MM or an RFC 3339 date-time")]
+    InvalidTime(String),
+    #[error("Alarm time {0} is in the past")]
+    PastTime(String),
+    #[error("Alarm not found: {0}")]
+    AlarmNotFound(String),
+}
+
+/// What happens when the sleep timer runs out
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepAction {
+    Pause,
+    Stop,
+    /// Stop playback and exit the application
+    Quit,
+    /// Fade the volume out over `FADE_OUT_SECS`, then stop
+    FadeOut,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SleepTimer {
+    pub action: SleepAction,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub fires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Alarm {
+    pub id: String,
+    pub playlist_id: String,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub fires_at: DateTime<Utc>,
+}
+
+/// Payload of the `sleep-timer` event
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepTimerFired {
+    pub action: SleepAction,
+    /// Set for `fade_out`
+    pub fade_secs: Option<u32>,
+}
+
+/// Payload of the `alarm` event; the frontend starts the playlist
+#[derive(Debug, Clone, Serialize)]
+pub struct AlarmFired {
+    pub id: String,
+    pub playlist_id: String,
+}
+
+/// Owns the pending timer tasks; replacing or cancelling a timer aborts its task
+pub struct Scheduler {
+    sleep: Mutex<Option<(SleepTimer, JoinHandle<()>)>>,
+    alarms: Mutex<HashMap<String, (Alarm, JoinHandle<()>)>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            sleep: Mutex::new(None),
+            alarms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Start the sleep timer, replacing any running one
+    pub fn set_sleep_timer(&'static self, app: AppHandle, minutes: u32, action: SleepAction) -> Result<SleepTimer, SchedulerError> {
+        if minutes == 0 || minutes > MAX_SLEEP_MINUTES {
+            return Err(SchedulerError::InvalidDuration);
+        }
+        let delay = Duration::from_secs(minutes as u64 * 60);
+        let timer = SleepTimer {
+            action,
+            fires_at: Utc::now() + chrono::Duration::minutes(minutes as i64),
+        };
+
+        let fires_at = timer.fires_at;
+        let task = tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(delay).await;
+            {
+                let mut sleep = self.sleep.lock().unwrap();
+                if sleep.as_ref().map(|(timer, _)| timer.fires_at) == Some(fires_at) {
+                    sleep.take();
+                }
+            }
+            log_info("Scheduler", &format!("Sleep timer fired: {:?}", action));
+
+            let payload = SleepTimerFired {
+                action,
+                fade_secs: (action == SleepAction::FadeOut).then_some(FADE_OUT_SECS),
+            };
+            if let Err(e) = app.emit("sleep-timer", payload) {
+                log_warn("Scheduler", &format!("Failed to emit sleep-timer event: {}", e));
+            }
+            if action == SleepAction::Quit {
+                tokio::time::sleep(QUIT_DELAY).await;
+                app.exit(0);
+            }
+        });
+
+        if let Some((_, previous)) = self.sleep.lock().unwrap().replace((timer.clone(), task)) {
+            previous.abort();
+        }
+        log_info("Scheduler", &format!("Sleep timer set: {:?} in {} minutes", action, minutes));
+        Ok(timer)
+    }
+
+    /// Cancel the sleep timer, returning whether one was running
+    pub fn cancel_sleep_timer(&self) -> bool {
+        match self.sleep.lock().unwrap().take() {
+            Some((_, task)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.sleep.lock().unwrap().as_ref().map(|(timer, _)| timer.clone())
+    }
+
+    /// Schedule `playlist_id` to start at `time` (HH:MM local, or RFC 3339)
+    pub fn set_alarm(&'static self, app: AppHandle, time: &str, playlist_id: String) -> Result<Alarm, SchedulerError> {
+        let fires_at = parse_alarm_time(time, Local::now())?;
+        let delay = (fires_at - Utc::now()).to_std().unwrap_or_default();
+        let alarm = Alarm {
+            id: uuid::Uuid::new_v4().to_string(),
+            playlist_id,
+            fires_at,
+        };
+
+        let fired = AlarmFired {
+            id: alarm.id.clone(),
+            playlist_id: alarm.playlist_id.clone(),
+        };
+        // Registered under the map's lock, so an alarm that fires at once can't
+        // look itself up before it is there
+        let mut alarms = self.alarms.lock().unwrap();
+        let task = tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(delay).await;
+            self.alarms.lock().unwrap().remove(&fired.id);
+            log_info("Scheduler", &format!("Alarm fired: playlist {}", fired.playlist_id));
+            if let Err(e) = app.emit("alarm", fired) {
+                log_warn("Scheduler", &format!("Failed to emit alarm event: {}", e));
+            }
+        });
+
+        alarms.insert(alarm.id.clone(), (alarm.clone(), task));
+        drop(alarms);
+        log_info("Scheduler", &format!("Alarm set for {}", alarm.fires_at));
+        Ok(alarm)
+    }
+
+    pub fn cancel_alarm(&self, id: &str) -> Result<(), SchedulerError> {
+        let (_, task) = self
+            .alarms
+            .lock()
+            .unwrap()
+            .remove(id)
+            .ok_or_else(|| SchedulerError::AlarmNotFound(id.to_string()))?;
+        task.abort();
+        Ok(())
+    }
+
+    /// Pending alarms, soonest first
+    pub fn alarms(&self) -> Vec<Alarm> {
+        let mut alarms: Vec<Alarm> = self
+            .alarms
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(alarm, _)| alarm.clone())
+            .collect();
+        alarms.sort_by_key(|alarm| alarm.fires_at);
+        alarms
+    }
+}
+
+/// Resolve an alarm time relative to `now`
+///
+/// `HH:MM` means the next occurrence of that local time (tomorrow if it has
+/// already passed today); RFC 3339 date-times must be in the future.
+fn parse_alarm_time(time: &str, now: DateTime<Local>) -> Result<DateTime<Utc>, SchedulerError> {
+    let time = time.trim();
+    if let Ok(at) = DateTime::parse_from_rfc3339(time) {
+        let at = at.with_timezone(&Utc);
+        if at <= now.with_timezone(&Utc) {
+            return Err(SchedulerError::PastTime(time.to_string()));
+        }
+        return Ok(at);
+    }
+
+    let clock = NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| SchedulerError::InvalidTime(time.to_string()))?;
+    let mut date = now.date_naive();
+    if clock <= now.time() {
+        date = date.succ_opt().ok_or_else(|| SchedulerError::InvalidTime(time.to_string()))?;
+    }
+    // Across a DST gap the local time may not exist; take the earliest valid mapping
+    Local
+        .from_local_datetime(&date.and_time(clock))
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| SchedulerError::InvalidTime(time.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 15, h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_clock_time_later_today() {
+        let at = parse_alarm_time("07:30", local(6, 0)).unwrap();
+        assert_eq!(at, local(7, 30).with_timezone(&Utc));
+    }
+
+    #[test]
+    fn test_clock_time_already_passed_is_tomorrow() {
+        let at = parse_alarm_time("07:30", local(8, 0)).unwrap();
+        let expected = Local.with_ymd_and_hms(2024, 6, 16, 7, 30, 0).unwrap();
+        assert_eq!(at, expected.with_timezone(&Utc));
+    }
+
+    #[test]
+    fn test_rfc3339_time() {
+        let now = Local::now();
+        let future = (now + chrono::Duration::hours(1)).to_rfc3339();
+        assert!(parse_alarm_time(&future, now).is_ok());
+
+        let past = (now - chrono::Duration::hours(1)).to_rfc3339();
+        assert!(matches!(parse_alarm_time(&past, now), Err(SchedulerError::PastTime(_))));
+    }
+
+    #[test]
+    fn test_invalid_time() {
+        assert!(matches!(parse_alarm_time("25:00", local(6, 0)), Err(SchedulerError::InvalidTime(_))));
+        assert!(matches!(parse_alarm_time("soon", local(6, 0)), Err(SchedulerError::InvalidTime(_))));
+    }
+}
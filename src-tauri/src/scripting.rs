@@ -0,0 +1,448 @@
+// User scripts
+// Lua scripts in `<data dir>/milk/scripts/*.lua` automate things by defining
+// global handlers for player events:
+// - `on_track_change(track)`: the `NowPlaying` track
+// - `on_playlist_load(playlist)`: `{ id, name, track_count }`
+//
+// Scripts run on one background thread with only the table, string, math and
+// utf8 libraries, a memory cap and an instruction budget per handler call, so
+// a script can't touch files or stall playback. A script that keeps failing is
+// suspended until the scripts are reloaded. The `milk` table is all they can
+// reach of the app:
+// - `milk.log(message)`, also used for `print`
+// - `milk.http_post(url, body[, content_type])`: sent in the background
+// - `milk.control(action)`: "play", "pause", "toggle", "next", "previous" or "stop"
+// - `milk.get_rating(path)` / `milk.set_rating(path, stars)`
+
+use crate::kv_store::KvStore;
+use crate::logging::{log_info, log_warn};
+use crate::media_session::{MediaKeyAction, NowPlaying};
+use crate::ratings::{self, TrackRating};
+use crate::remote_api::ControlSink;
+use mlua::{HookTriggers, Lua, LuaOptions, LuaSerdeExt, SerializeOptions, StdLib};
+use serde::Serialize;
+use std::cell::Cell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{sync_channel, Receiver, Sender, SyncSender, TrySendError};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use thiserror::Error;
+
+const MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+/// Instructions between budget checks
+const HOOK_INTERVAL: u32 = 1000;
+
+/// Budget checks a handler may pass, i.e. about 10M instructions
+const MAX_HOOK_TICKS: u32 = 10_000;
+
+/// Failed handler calls before a script is suspended
+const MAX_FAULTS: u32 = 3;
+
+/// Events waiting for the script thread; newer events are dropped while it is full
+const EVENT_QUEUE: usize = 64;
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+const HANDLERS: [&str; 2] = ["on_track_change", "on_playlist_load"];
+
+static SENDER: OnceLock<SyncSender<Message>> = OnceLock::new();
+
+/// Last known state of the scripts, for `list_scripts`
+static SCRIPTS: Mutex<Vec<ScriptInfo>> = Mutex::new(Vec::new());
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("Scripts are not running")]
+    NotRunning,
+    #[error("Script reload timed out")]
+    Timeout,
+}
+
+/// An event scripts can handle
+#[derive(Debug, Clone)]
+pub enum ScriptEvent {
+    TrackChanged(NowPlaying),
+    PlaylistLoaded(PlaylistLoaded),
+}
+
+impl ScriptEvent {
+    fn handler(&self) -> &'static str {
+        match self {
+            ScriptEvent::TrackChanged(_) => "on_track_change",
+            ScriptEvent::PlaylistLoaded(_) => "on_playlist_load",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaylistLoaded {
+    pub id: String,
+    pub name: String,
+    pub track_count: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptStatus {
+    Running,
+    /// Failed too often and was suspended until the next reload
+    Suspended,
+    /// Could not be loaded
+    Failed,
+}
+
+/// Returned by `list_scripts`
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub path: String,
+    /// Event handlers the script defines
+    pub handlers: Vec<String>,
+    pub status: ScriptStatus,
+    /// Latest load or handler error
+    pub error: Option<String>,
+}
+
+/// What scripts can do to the app
+#[derive(Clone)]
+pub struct ScriptApi {
+    pub store: &'static KvStore,
+    pub control: ControlSink,
+}
+
+enum Message {
+    Event(ScriptEvent),
+    Reload(Sender<Vec<ScriptInfo>>),
+}
+
+struct Script {
+    name: String,
+    path: PathBuf,
+    lua: Option<Lua>,
+    /// Budget checks used by the current call
+    ticks: Rc<Cell<u32>>,
+    faults: u32,
+    error: Option<String>,
+}
+
+impl Script {
+    fn load(path: &Path, api: &ScriptApi) -> Self {
+        let name = path.file_stem().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let ticks = Rc::new(Cell::new(0));
+        let (lua, error) = match fs::read_to_string(path).map_err(mlua::Error::external).and_then(|source| {
+            let lua = sandbox(api, &name, Rc::clone(&ticks))?;
+            lua.load(&source).set_name(path.to_string_lossy()).exec()?;
+            Ok(lua)
+        }) {
+            Ok(lua) => (Some(lua), None),
+            Err(e) => {
+                log_warn("Scripts", &format!("Failed to load script {}: {}", name, e));
+                (None, Some(e.to_string()))
+            }
+        };
+        Script { name, path: path.to_path_buf(), lua, ticks, faults: 0, error }
+    }
+
+    fn handlers(&self) -> Vec<String> {
+        let Some(lua) = &self.lua else {
+            return Vec::new();
+        };
+        HANDLERS
+            .into_iter()
+            .filter(|handler| matches!(lua.globals().get::<_, mlua::Value>(*handler), Ok(mlua::Value::Function(_))))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn info(&self) -> ScriptInfo {
+        let status = match (&self.lua, self.faults) {
+            (Some(_), _) => ScriptStatus::Running,
+            (None, faults) if faults >= MAX_FAULTS => ScriptStatus::Suspended,
+            (None, _) => ScriptStatus::Failed,
+        };
+        ScriptInfo {
+            name: self.name.clone(),
+            path: self.path.to_string_lossy().to_string(),
+            handlers: self.handlers(),
+            status,
+            error: self.error.clone(),
+        }
+    }
+
+    fn handle(&mut self, event: &ScriptEvent) {
+        let Some(lua) = &self.lua else {
+            return;
+        };
+        let handler = event.handler();
+        let Ok(mlua::Value::Function(function)) = lua.globals().get::<_, mlua::Value>(handler) else {
+            return;
+        };
+        self.ticks.set(0);
+        let options = SerializeOptions::new().serialize_none_to_null(false);
+        let result = match event {
+            ScriptEvent::TrackChanged(track) => lua.to_value_with(track, options),
+            ScriptEvent::PlaylistLoaded(playlist) => lua.to_value_with(playlist, options),
+        }
+        .and_then(|argument| function.call::<_, ()>(argument))
+        .map_err(|e| e.to_string());
+        drop(function);
+        if let Err(e) = result {
+            self.faults += 1;
+            log_warn("Scripts", &format!("Script {} failed in {}: {}", self.name, handler, e));
+            self.error = Some(e);
+            if self.faults >= MAX_FAULTS {
+                log_warn("Scripts", &format!("Suspending script {} after {} failures", self.name, self.faults));
+                self.lua = None;
+            }
+        }
+    }
+}
+
+/// A Lua state with the safe libraries, resource limits and the `milk` API
+fn sandbox(api: &ScriptApi, name: &str, ticks: Rc<Cell<u32>>) -> mlua::Result<Lua> {
+    let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8, LuaOptions::default())?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    lua.set_hook(HookTriggers::new().every_nth_instruction(HOOK_INTERVAL), move |_, _| {
+        ticks.set(ticks.get() + 1);
+        match ticks.get() > MAX_HOOK_TICKS {
+            true => Err(mlua::Error::RuntimeError("instruction limit exceeded".to_string())),
+            false => Ok(()),
+        }
+    });
+
+    let milk = lua.create_table()?;
+    let category = format!("Script:{}", name);
+    let log = lua.create_function(move |_, message: mlua::Value| {
+        log_info(&category, &message.to_string()?);
+        Ok(())
+    })?;
+    lua.globals().set("print", log.clone())?;
+    milk.set("log", log)?;
+
+    milk.set(
+        "http_post",
+        lua.create_function(|_, (url, body, content_type): (String, String, Option<String>)| {
+            http_post(&url, body, content_type).map_err(mlua::Error::RuntimeError)
+        })?,
+    )?;
+
+    let control = api.control.clone();
+    milk.set(
+        "control",
+        lua.create_function(move |_, action: String| {
+            let action = match action.as_str() {
+                "play" => MediaKeyAction::Play,
+                "pause" => MediaKeyAction::Pause,
+                "toggle" => MediaKeyAction::Toggle,
+                "next" => MediaKeyAction::Next,
+                "previous" => MediaKeyAction::Previous,
+                "stop" => MediaKeyAction::Stop,
+                other => return Err(mlua::Error::RuntimeError(format!("unknown action '{}'", other))),
+            };
+            control(action);
+            Ok(())
+        })?,
+    )?;
+
+    let store = api.store;
+    milk.set("get_rating", lua.create_function(move |_, path: String| Ok(ratings::get_rating(store, &path).stars))?)?;
+    milk.set(
+        "set_rating",
+        lua.create_function(move |_, (path, stars): (String, u8)| {
+            let rating = TrackRating { stars, ..ratings::get_rating(store, &path) };
+            ratings::set_rating(store, &path, rating).map_err(|e| mlua::Error::RuntimeError(e.to_string()))
+        })?,
+    )?;
+
+    lua.globals().set("milk", milk)?;
+    Ok(lua)
+}
+
+/// Send a POST in the background; only the URL is checked up front
+fn http_post(url: &str, body: String, content_type: Option<String>) -> Result<(), String> {
+    let parsed = url::Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(format!("unsupported URL scheme '{}'", parsed.scheme()));
+    }
+    let client = HTTP_CLIENT.get_or_init(|| reqwest::Client::builder().timeout(HTTP_TIMEOUT).build().unwrap_or_default());
+    let request = client
+        .post(parsed)
+        .header(reqwest::header::CONTENT_TYPE, content_type.unwrap_or_else(|| "text/plain; charset=utf-8".to_string()))
+        .body(body);
+    let url = url.to_string();
+    tauri::async_runtime::spawn(async move {
+        match request.send().await.and_then(|response| response.error_for_status()) {
+            Ok(_) => {}
+            Err(e) => log_warn("Scripts", &format!("POST to {} failed: {}", url, e)),
+        }
+    });
+    Ok(())
+}
+
+struct ScriptHost {
+    scripts: Vec<Script>,
+}
+
+impl ScriptHost {
+    fn load(dir: &Path, api: &ScriptApi) -> Self {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        paths.retain(|path| path.is_file() && path.extension().is_some_and(|e| e.eq_ignore_ascii_case("lua")));
+        paths.sort();
+        ScriptHost { scripts: paths.iter().map(|path| Script::load(path, api)).collect() }
+    }
+
+    fn list(&self) -> Vec<ScriptInfo> {
+        self.scripts.iter().map(Script::info).collect()
+    }
+
+    fn handle(&mut self, event: &ScriptEvent) {
+        for script in &mut self.scripts {
+            script.handle(event);
+        }
+    }
+}
+
+pub fn get_scripts_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("milk").join("scripts"))
+}
+
+/// Load the scripts and start handling events on a background thread
+pub fn start(api: ScriptApi) {
+    let (sender, receiver) = sync_channel(EVENT_QUEUE);
+    if SENDER.set(sender).is_err() {
+        return;
+    }
+    std::thread::spawn(move || run(api, receiver));
+}
+
+fn run(api: ScriptApi, receiver: Receiver<Message>) {
+    let Some(dir) = get_scripts_dir() else {
+        log_warn("Scripts", "No data directory available, scripts disabled");
+        return;
+    };
+    let load = || {
+        let host = ScriptHost::load(&dir, &api);
+        *SCRIPTS.lock().unwrap() = host.list();
+        host
+    };
+    let mut host = load();
+    if !host.scripts.is_empty() {
+        log_info("Scripts", &format!("Loaded {} script(s) from {}", host.scripts.len(), dir.display()));
+    }
+    for message in receiver {
+        match message {
+            Message::Event(event) => {
+                host.handle(&event);
+                *SCRIPTS.lock().unwrap() = host.list();
+            }
+            Message::Reload(reply) => {
+                host = load();
+                let _ = reply.send(host.list());
+            }
+        }
+    }
+}
+
+/// Queue an event for the scripts without waiting for them
+pub fn dispatch(event: ScriptEvent) {
+    let Some(sender) = SENDER.get() else {
+        return;
+    };
+    if let Err(TrySendError::Full(_)) = sender.try_send(Message::Event(event)) {
+        log_warn("Scripts", "Script event queue full, dropping event");
+    }
+}
+
+pub fn list() -> Vec<ScriptInfo> {
+    SCRIPTS.lock().unwrap().clone()
+}
+
+/// Reload every script, e.g. after editing one; waits for queued events first
+pub fn reload() -> Result<Vec<ScriptInfo>, ScriptError> {
+    let sender = SENDER.get().ok_or(ScriptError::NotRunning)?;
+    let (reply, response) = std::sync::mpsc::channel();
+    sender.send(Message::Reload(reply)).map_err(|_| ScriptError::NotRunning)?;
+    response.recv_timeout(Duration::from_secs(30)).map_err(|_| ScriptError::Timeout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn api(actions: Arc<Mutex<Vec<MediaKeyAction>>>) -> ScriptApi {
+        ScriptApi {
+            store: Box::leak(Box::new(KvStore::in_memory())),
+            control: Arc::new(move |action| actions.lock().unwrap().push(action)),
+        }
+    }
+
+    fn track(title: &str) -> ScriptEvent {
+        ScriptEvent::TrackChanged(NowPlaying {
+            title: title.to_string(),
+            artist: Some("Artist".to_string()),
+            album: None,
+            duration_secs: None,
+            file_path: Some("/music/song.mp3".to_string()),
+            artwork_url: None,
+        })
+    }
+
+    #[test]
+    fn test_handlers_call_api() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("skip.lua"),
+            r#"
+            function on_track_change(track)
+              if track.album == nil and track.title == "Skip me" then
+                milk.control("next")
+                milk.set_rating(track.file_path, 1)
+              end
+            end
+            "#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("sandboxed.lua"), "assert(io == nil and os == nil and require == nil)").unwrap();
+        fs::write(dir.path().join("broken.lua"), "function (").unwrap();
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        let api = api(Arc::clone(&actions));
+
+        let mut host = ScriptHost::load(dir.path(), &api);
+        let scripts = host.list();
+        let statuses: Vec<_> = scripts.iter().map(|s| (s.name.as_str(), s.status)).collect();
+        assert_eq!(
+            statuses,
+            [("broken", ScriptStatus::Failed), ("sandboxed", ScriptStatus::Running), ("skip", ScriptStatus::Running)]
+        );
+        assert_eq!(scripts[2].handlers, ["on_track_change"]);
+
+        host.handle(&track("Keep me"));
+        host.handle(&track("Skip me"));
+        assert_eq!(*actions.lock().unwrap(), [MediaKeyAction::Next]);
+        assert_eq!(ratings::get_rating(api.store, "/music/song.mp3").stars, 1);
+    }
+
+    #[test]
+    fn test_runaway_script_is_suspended() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("spin.lua"), "function on_playlist_load(playlist) while true do end end").unwrap();
+        let mut host = ScriptHost::load(dir.path(), &api(Arc::default()));
+
+        let event = ScriptEvent::PlaylistLoaded(PlaylistLoaded { id: "1".to_string(), name: "Mix".to_string(), track_count: 3 });
+        for _ in 0..MAX_FAULTS {
+            host.handle(&event);
+        }
+        let info = &host.list()[0];
+        assert_eq!(info.status, ScriptStatus::Suspended);
+        assert!(info.error.as_deref().unwrap().contains("instruction limit"));
+    }
+}
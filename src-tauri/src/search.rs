@@ -0,0 +1,484 @@
+// Library search
+// An inverted index over title/artist/album/genre/comment/path with prefix and
+// typo-tolerant matching, so large libraries are searched in the backend instead of the frontend.
+
+use crate::library::Track;
+use crate::metadata::{MetadataExtractor, TrackMetadata};
+use crate::ratings::TrackRating;
+use crate::track_analysis::{self, MusicalKey, Silence, TrackAnalysis};
+use crate::collation::{self, Collator};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Results returned when the caller doesn't set a limit
+pub const DEFAULT_LIMIT: usize = 200;
+
+/// Query terms shorter than this are matched by prefix only, never fuzzily
+const MIN_FUZZY_LEN: usize = 4;
+
+/// Indexed fields, in descending order of ranking weight
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    Genre,
+    /// The file's comment tag and the user's note
+    Comment,
+    Path,
+}
+
+impl SearchField {
+    fn weight(self) -> f32 {
+        match self {
+            SearchField::Title => 3.0,
+            SearchField::Artist => 2.5,
+            SearchField::Album => 2.0,
+            SearchField::Genre => 1.0,
+            SearchField::Comment => 0.75,
+            SearchField::Path => 0.5,
+        }
+    }
+}
+
+/// Narrow results after matching; text filters are case-insensitive exact matches
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchFilters {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub extension: Option<String>,
+    pub year_from: Option<u32>,
+    pub year_to: Option<u32>,
+    /// Tempo range, inclusive
+    pub bpm_min: Option<f32>,
+    pub bpm_max: Option<f32>,
+    /// Key in any notation `MusicalKey::parse` reads, so "Am" matches "8A"
+    pub key: Option<String>,
+    /// Minimum star rating
+    pub min_rating: Option<u8>,
+    /// Only loved (or only not-loved) tracks
+    pub loved: Option<bool>,
+    /// Restrict matching to these fields; all fields when empty
+    pub fields: Vec<SearchField>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub track: Track,
+    pub metadata: Option<TrackMetadata>,
+    pub rating: TrackRating,
+    pub score: f32,
+    /// Leading and trailing silence, once the track has been analysed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub silence: Option<Silence>,
+    /// The user's note on the track
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Clone)]
+struct Document {
+    track: Track,
+    metadata: Option<TrackMetadata>,
+    silence: Option<Silence>,
+    note: Option<String>,
+}
+
+/// How well a query term matched an indexed term
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum MatchQuality {
+    Fuzzy = 1,
+    Prefix = 2,
+    Exact = 3,
+}
+
+#[derive(Clone)]
+pub struct SearchIndex {
+    root: PathBuf,
+    documents: Vec<Document>,
+    /// Term -> (document, field) postings
+    terms: BTreeMap<String, Vec<(usize, SearchField)>>,
+}
+
+impl SearchIndex {
+    /// Index scanned tracks, reading tags through the extractor cache
+    pub fn build(root: &Path, tracks: Vec<Track>, extractor: &MetadataExtractor) -> Self {
+        let mut index = Self {
+            root: root.to_path_buf(),
+            documents: Vec::with_capacity(tracks.len()),
+            terms: BTreeMap::new(),
+        };
+        for track in tracks {
+            let metadata = extractor.extract(&track.file_path).ok();
+            let id = index.documents.len();
+            if let Some(m) = &metadata {
+                for (field, value) in [
+                    (SearchField::Title, &m.title),
+                    (SearchField::Artist, &m.artist),
+                    (SearchField::Album, &m.album),
+                    (SearchField::Genre, &m.genre),
+                    (SearchField::Comment, &m.comment),
+                ] {
+                    if let Some(value) = value {
+                        index.add_terms(id, field, value);
+                    }
+                }
+            }
+            if metadata.as_ref().is_none_or(|m| m.title.is_none()) {
+                index.add_terms(id, SearchField::Title, &track.file_name);
+            }
+            let relative = track.file_path.strip_prefix(root).unwrap_or(&track.file_path).to_string_lossy().to_string();
+            index.add_terms(id, SearchField::Path, &relative);
+            index.documents.push(Document { track, metadata, silence: None, note: None });
+        }
+        index
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Every indexed track with its tags, in scan order
+    pub fn entries(&self) -> impl Iterator<Item = (&Track, Option<&TrackMetadata>)> {
+        self.documents.iter().map(|d| (&d.track, d.metadata.as_ref()))
+    }
+
+    /// Fill untagged tempo and key, and silence, from stored analysis looked up by path
+    pub fn apply_analysis(&mut self, lookup: impl Fn(&Path) -> Option<TrackAnalysis>) {
+        for document in &mut self.documents {
+            let Some(analysis) = lookup(&document.track.file_path) else {
+                continue;
+            };
+            if let Some(metadata) = &mut document.metadata {
+                track_analysis::fill_metadata(metadata, &analysis);
+            }
+            document.silence = analysis.silence;
+        }
+    }
+
+    /// Attach notes, keyed by encoded file path, and index their words
+    pub fn apply_notes(&mut self, notes: &HashMap<String, String>) {
+        for id in 0..self.documents.len() {
+            let key = crate::path_codec::encode(&self.documents[id].track.file_path);
+            if let Some(note) = notes.get(&key) {
+                self.add_terms(id, SearchField::Comment, note);
+                self.documents[id].note = Some(note.clone());
+            }
+        }
+    }
+
+    /// Replace the note on one track, re-indexing its comment terms
+    ///
+    /// Returns false when the track isn't in the index.
+    pub fn set_note(&mut self, file_path: &Path, note: Option<String>) -> bool {
+        let Some(id) = self.documents.iter().position(|d| d.track.file_path == file_path) else {
+            return false;
+        };
+        self.terms.retain(|_, postings| {
+            postings.retain(|&posting| posting != (id, SearchField::Comment));
+            !postings.is_empty()
+        });
+        let comment = self.documents[id].metadata.as_ref().and_then(|m| m.comment.clone());
+        for value in comment.iter().chain(note.iter()) {
+            self.add_terms(id, SearchField::Comment, value);
+        }
+        self.documents[id].note = note;
+        true
+    }
+
+    fn add_terms(&mut self, id: usize, field: SearchField, value: &str) {
+        for term in tokenize(value) {
+            let postings = self.terms.entry(term).or_default();
+            if !postings.contains(&(id, field)) {
+                postings.push((id, field));
+            }
+        }
+    }
+
+    /// Ranked matches for `query`, cut to the filter limit
+    ///
+    /// Every query term must match some field of a track, exactly, as a prefix
+    /// or within one edit. Each term scores its best field weight times match
+    /// quality; ties are broken by title. An empty query lists the filtered library.
+    ///
+    /// Ratings are looked up by file path through `rating_of` for the rating
+    /// filters and results.
+    pub fn search(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        rating_of: impl Fn(&str) -> TrackRating,
+    ) -> Vec<SearchHit> {
+        let mut hits = self.matches(query, filters, rating_of);
+        hits.truncate(filters.limit.unwrap_or(DEFAULT_LIMIT));
+        hits
+    }
+
+    /// Every match for `query` in ranked order, ignoring the filter limit
+    ///
+    /// For callers that reorder the results before applying the limit.
+    pub fn matches(
+        &self,
+        query: &str,
+        filters: &SearchFilters,
+        rating_of: impl Fn(&str) -> TrackRating,
+    ) -> Vec<SearchHit> {
+        let query_terms = tokenize(query);
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+
+        if query_terms.is_empty() {
+            scores = (0..self.documents.len()).map(|id| (id, 0.0)).collect();
+        }
+        for (i, term) in query_terms.iter().enumerate() {
+            let mut best: HashMap<usize, f32> = HashMap::new();
+            for (quality, postings) in self.matching_terms(term) {
+                for &(id, field) in postings {
+                    if !filters.fields.is_empty() && !filters.fields.contains(&field) {
+                        continue;
+                    }
+                    let score = field.weight() * quality as u8 as f32;
+                    let entry = best.entry(id).or_insert(0.0);
+                    *entry = entry.max(score);
+                }
+            }
+            if i == 0 {
+                scores = best;
+            } else {
+                scores.retain(|id, score| match best.get(id) {
+                    Some(term_score) => {
+                        *score += term_score;
+                        true
+                    }
+                    None => false,
+                });
+            }
+        }
+
+        let mut hits: Vec<(usize, f32, TrackRating)> = scores
+            .into_iter()
+            .filter(|(id, _)| matches_filters(&self.documents[*id], filters))
+            .map(|(id, score)| (id, score, rating_of(&crate::path_codec::encode(&self.documents[id].track.file_path))))
+            .filter(|(_, _, rating)| rating.satisfies(filters.min_rating, filters.loved))
+            .collect();
+        let collator = Collator::shared(None, true);
+        hits.sort_by(|(a_id, a, _), (b_id, b, _)| {
+            b.total_cmp(a)
+                .then_with(|| collator.compare(self.title(*a_id), self.title(*b_id)))
+        });
+        hits.into_iter()
+            .map(|(id, score, rating)| SearchHit {
+                track: self.documents[id].track.clone(),
+                metadata: self.documents[id].metadata.clone(),
+                rating,
+                score,
+                silence: self.documents[id].silence,
+                note: self.documents[id].note.clone(),
+            })
+            .collect()
+    }
+
+    fn title(&self, id: usize) -> &str {
+        let document = &self.documents[id];
+        document
+            .metadata
+            .as_ref()
+            .and_then(|m| m.title.as_deref())
+            .unwrap_or(&document.track.file_name)
+    }
+
+    fn matching_terms<'a>(&'a self, term: &'a str) -> Vec<(MatchQuality, &'a Vec<(usize, SearchField)>)> {
+        let mut matches: Vec<_> = self
+            .terms
+            .range(term.to_string()..)
+            .take_while(|(indexed, _)| indexed.starts_with(term))
+            .map(|(indexed, postings)| {
+                let quality = if indexed == term { MatchQuality::Exact } else { MatchQuality::Prefix };
+                (quality, postings)
+            })
+            .collect();
+        if term.chars().count() >= MIN_FUZZY_LEN {
+            matches.extend(
+                self.terms
+                    .iter()
+                    .filter(|(indexed, _)| !indexed.starts_with(term) && within_one_edit(term, indexed))
+                    .map(|(_, postings)| (MatchQuality::Fuzzy, postings)),
+            );
+        }
+        matches
+    }
+}
+
+fn matches_filters(document: &Document, filters: &SearchFilters) -> bool {
+    let metadata = document.metadata.as_ref();
+    let text_matches = |wanted: &Option<String>, actual: Option<&String>| match wanted {
+        Some(wanted) => actual.is_some_and(|actual| collation::fold(actual) == collation::fold(wanted)),
+        None => true,
+    };
+    let year = metadata.and_then(|m| m.year);
+    let bpm = metadata.and_then(|m| m.bpm);
+    text_matches(&filters.artist, metadata.and_then(|m| m.artist.as_ref()))
+        && text_matches(&filters.album, metadata.and_then(|m| m.album.as_ref()))
+        && text_matches(&filters.genre, metadata.and_then(|m| m.genre.as_ref()))
+        && text_matches(&filters.extension, Some(&document.track.extension))
+        && filters.year_from.is_none_or(|from| year.is_some_and(|y| y >= from))
+        && filters.year_to.is_none_or(|to| year.is_some_and(|y| y <= to))
+        && filters.bpm_min.is_none_or(|min| bpm.is_some_and(|b| b >= min))
+        && filters.bpm_max.is_none_or(|max| bpm.is_some_and(|b| b <= max))
+        && filters.key.as_deref().is_none_or(|wanted| {
+            metadata.and_then(|m| m.key.as_deref()).is_some_and(|key| keys_match(wanted, key))
+        })
+}
+
+/// Same key in any notation, or the same text when either side isn't a key
+fn keys_match(wanted: &str, actual: &str) -> bool {
+    match (MusicalKey::parse(wanted), MusicalKey::parse(actual)) {
+        (Some(wanted), Some(actual)) => wanted == actual,
+        _ => collation::fold(wanted) == collation::fold(actual),
+    }
+}
+
+/// Alphanumeric words, accent- and case-folded with `collation::fold`
+fn tokenize(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(collation::fold)
+        .collect()
+}
+
+/// True when `a` becomes `b` with at most one insertion, deletion or substitution
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > 1 {
+        return false;
+    }
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    a.len().max(b.len()) - prefix - suffix <= 1
+}
+
+/// An index over empty files named `names`, whose tags come from the "Artist - Title" file names
+///
+/// Shared by the tests of modules built on the index.
+#[cfg(test)]
+pub(crate) fn test_index(names: &[&str]) -> (tempfile::TempDir, SearchIndex) {
+    let dir = tempfile::TempDir::new().unwrap();
+    let tracks = names
+        .iter()
+        .map(|name| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"").unwrap();
+            Track {
+                id: name.to_string(),
+                file_path: path,
+                file_name: name.to_string(),
+                extension: "wav".to_string(),
+            }
+        })
+        .collect();
+    let index = SearchIndex::build(dir.path(), tracks, &MetadataExtractor::new());
+    (dir, index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_index as index;
+
+    fn unrated(_: &str) -> TrackRating {
+        TrackRating::default()
+    }
+
+    fn ids(hits: &[SearchHit]) -> Vec<&str> {
+        hits.iter().map(|h| h.track.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_prefix_and_ranking() {
+        let (_dir, index) = index(&["Sunrise - Morning.wav", "Someone - Sunrise.wav", "Other - Song.wav"]);
+        let hits = index.search("sunrise", &SearchFilters::default(), unrated);
+        // Title match outranks artist match
+        assert_eq!(ids(&hits), vec!["Someone - Sunrise.wav", "Sunrise - Morning.wav"]);
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        let (_dir, index) = index(&["Daft Punk - Around The World.wav", "Daft Punk - Da Funk.wav"]);
+        let hits = index.search("daft world", &SearchFilters::default(), unrated);
+        assert_eq!(ids(&hits), vec!["Daft Punk - Around The World.wav"]);
+    }
+
+    #[test]
+    fn test_fuzzy_match_and_filters() {
+        let (_dir, index) = index(&["Aphex Twin - Windowlicker.wav"]);
+        assert_eq!(index.search("aphx", &SearchFilters::default(), unrated).len(), 1);
+        let filters = SearchFilters { artist: Some("someone else".to_string()), ..Default::default() };
+        assert!(index.search("aphex", &filters, unrated).is_empty());
+    }
+
+    #[test]
+    fn test_rating_filters() {
+        let (_dir, index) = index(&["A - One.wav", "B - Two.wav"]);
+        let rating_of = |path: &str| TrackRating { stars: if path.ends_with("One.wav") { 5 } else { 1 }, loved: false };
+        let filters = SearchFilters { min_rating: Some(4), ..Default::default() };
+        let hits = index.search("", &filters, rating_of);
+        assert_eq!(ids(&hits), vec!["A - One.wav"]);
+        assert_eq!(hits[0].rating.stars, 5);
+    }
+
+    #[test]
+    fn test_analysis_filters() {
+        let (_dir, mut index) = index(&["A - Slow.wav", "B - Fast.wav", "C - Untagged.wav"]);
+        index.apply_analysis(|path| {
+            let name = path.file_name()?.to_string_lossy().to_string();
+            let (bpm, key) = match name.as_str() {
+                "A - Slow.wav" => (92.0, "A minor"),
+                "B - Fast.wav" => (126.0, "C major"),
+                _ => return None,
+            };
+            Some(TrackAnalysis { bpm: Some(bpm), key: Some(key.to_string()), ..Default::default() })
+        });
+        let filters = SearchFilters { bpm_min: Some(120.0), bpm_max: Some(130.0), ..Default::default() };
+        assert_eq!(ids(&index.search("", &filters, unrated)), vec!["B - Fast.wav"]);
+        let filters = SearchFilters { key: Some("8A".to_string()), ..Default::default() };
+        assert_eq!(ids(&index.search("", &filters, unrated)), vec!["A - Slow.wav"]);
+    }
+
+    #[test]
+    fn test_notes_are_searchable() {
+        let (dir, mut index) = index(&["A - One.wav", "B - Two.wav"]);
+        let one = dir.path().join("A - One.wav");
+        let notes = HashMap::from([(crate::path_codec::encode(&one), "cue at the breakdown".to_string())]);
+        index.apply_notes(&notes);
+        let hits = index.search("breakdown", &SearchFilters::default(), unrated);
+        assert_eq!(ids(&hits), vec!["A - One.wav"]);
+        assert_eq!(hits[0].note.as_deref(), Some("cue at the breakdown"));
+
+        assert!(index.set_note(&one, Some("long intro".to_string())));
+        assert!(index.search("breakdown", &SearchFilters::default(), unrated).is_empty());
+        assert_eq!(ids(&index.search("intro", &SearchFilters::default(), unrated)), vec!["A - One.wav"]);
+        assert!(!index.set_note(&dir.path().join("missing.wav"), None));
+    }
+
+    #[test]
+    fn test_within_one_edit() {
+        assert!(within_one_edit("aphex", "aphx"));
+        assert!(!within_one_edit("wrold", "world"));
+        assert!(within_one_edit("word", "world"));
+    }
+}
@@ -3,20 +3,33 @@ use aes_gcm::{
     Aes256Gcm, Nonce,
 };
 use base64::{engine::general_purpose, Engine as _};
+use crate::logging::log_warn;
 use keyring::Entry;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
 const SERVICE_NAME: &str = "milk-player";
 const ENCRYPTION_KEY_NAME: &str = "milk-encryption-key";
 
+/// Passphrase for the encrypted file store; the machine ID is used when unset
+pub const PASSPHRASE_ENV: &str = "MILK_STORAGE_PASSPHRASE";
+
+/// PBKDF2-HMAC-SHA256 iterations for new credential files
+const PBKDF2_ROUNDS: u32 = 600_000;
+
 #[derive(Debug)]
 pub enum StorageError {
     KeyringError(keyring::Error),
     EncryptionError(String),
     DecryptionError(String),
     Base64Error(base64::DecodeError),
+    FileStoreError(String),
 }
 
 impl From<keyring::Error> for StorageError {
@@ -38,10 +51,17 @@ impl fmt::Display for StorageError {
             StorageError::EncryptionError(e) => write!(f, "Encryption error: {}", e),
             StorageError::DecryptionError(e) => write!(f, "Decryption error: {}", e),
             StorageError::Base64Error(e) => write!(f, "Base64 error: {}", e),
+            StorageError::FileStoreError(e) => write!(f, "Credential file error: {}", e),
         }
     }
 }
 
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::FileStoreError(err.to_string())
+    }
+}
+
 impl std::error::Error for StorageError {}
 
 /// Trait for secure credential storage
@@ -108,26 +128,7 @@ impl PlatformSecureStorage {
     /// Encrypt data using AES-256-GCM
     fn encrypt(&self, plaintext: &str) -> Result<String, StorageError> {
         let key_bytes = self.get_or_create_encryption_key()?;
-        
-        let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
-        
-        // Generate random nonce
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        
-        // Encrypt
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext.as_bytes())
-            .map_err(|e| StorageError::EncryptionError(e.to_string()))?;
-        
-        // Encode to base64
-        let encrypted_data = EncryptedData {
-            nonce: general_purpose::STANDARD.encode(nonce_bytes),
-            ciphertext: general_purpose::STANDARD.encode(ciphertext),
-        };
-        
+        let encrypted_data = encrypt_with_key(&key_bytes, plaintext)?;
         serde_json::to_string(&encrypted_data)
             .map_err(|e| StorageError::EncryptionError(e.to_string()))
     }
@@ -136,31 +137,86 @@ impl PlatformSecureStorage {
     fn decrypt(&self, encrypted: &str) -> Result<String, StorageError> {
         let key_bytes = self.get_or_create_encryption_key()?;
         
-        let key = aes_gcm::Key::<Aes256Gcm>::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
-        
         // Parse encrypted data
         let encrypted_data: EncryptedData = serde_json::from_str(encrypted)
             .map_err(|e| StorageError::DecryptionError(e.to_string()))?;
-        
-        // Decode from base64
-        let nonce_bytes = general_purpose::STANDARD.decode(&encrypted_data.nonce)?;
-        let ciphertext = general_purpose::STANDARD.decode(&encrypted_data.ciphertext)?;
-        
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        
-        // Decrypt
-        let plaintext = cipher
-            .decrypt(nonce, ciphertext.as_ref())
-            .map_err(|e| StorageError::DecryptionError(e.to_string()))?;
-        
-        String::from_utf8(plaintext)
-            .map_err(|e| StorageError::DecryptionError(e.to_string()))
+        decrypt_with_key(&key_bytes, &encrypted_data)
+    }
+}
+
+fn encrypt_with_key(key_bytes: &[u8], plaintext: &str) -> Result<EncryptedData, StorageError> {
+    let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key_bytes);
+    let cipher = Aes256Gcm::new(key);
+    
+    // Generate random nonce
+    let mut nonce_bytes = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    
+    // Encrypt
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext.as_bytes())
+        .map_err(|e| StorageError::EncryptionError(e.to_string()))?;
+    
+    // Encode to base64
+    Ok(EncryptedData {
+        nonce: general_purpose::STANDARD.encode(nonce_bytes),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    })
+}
+
+fn decrypt_with_key(key_bytes: &[u8], encrypted_data: &EncryptedData) -> Result<String, StorageError> {
+    let key = aes_gcm::Key::<Aes256Gcm>::from_slice(key_bytes);
+    let cipher = Aes256Gcm::new(key);
+    
+    // Decode from base64
+    let nonce_bytes = general_purpose::STANDARD.decode(&encrypted_data.nonce)?;
+    let ciphertext = general_purpose::STANDARD.decode(&encrypted_data.ciphertext)?;
+    if nonce_bytes.len() != 12 {
+        return Err(StorageError::DecryptionError("invalid nonce length".to_string()));
     }
+    
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    
+    // Decrypt
+    let plaintext = cipher
+        .decrypt(nonce, ciphertext.as_ref())
+        .map_err(|e| StorageError::DecryptionError(e.to_string()))?;
+    
+    String::from_utf8(plaintext)
+        .map_err(|e| StorageError::DecryptionError(e.to_string()))
+}
+
+/// File store used when the system keyring cannot be reached, decided once per run
+///
+/// Linux desktops without a Secret Service daemon have no keyring at all, so
+/// credentials go to an encrypted file in the config directory instead.
+fn fallback_store() -> Option<&'static FileSecureStorage> {
+    static FALLBACK: OnceLock<Option<FileSecureStorage>> = OnceLock::new();
+    FALLBACK
+        .get_or_init(|| {
+            let probe = Entry::new(SERVICE_NAME, ENCRYPTION_KEY_NAME).and_then(|entry| entry.get_password());
+            match probe {
+                Ok(_) | Err(keyring::Error::NoEntry) | Err(keyring::Error::Ambiguous(_)) => None,
+                Err(e) => {
+                    let path = FileSecureStorage::default_path()?;
+                    log_warn(
+                        "SecureStorage",
+                        &format!("System keyring unavailable ({}); using encrypted file {}", e, path.display()),
+                    );
+                    Some(FileSecureStorage::new(path, KeySource::from_env()))
+                }
+            }
+        })
+        .as_ref()
 }
 
 impl SecureStorage for PlatformSecureStorage {
     fn store(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        if let Some(fallback) = fallback_store() {
+            return fallback.store(key, value);
+        }
+
         // Encrypt the value
         let encrypted = self.encrypt(value)?;
         
@@ -172,6 +228,10 @@ impl SecureStorage for PlatformSecureStorage {
     }
     
     fn retrieve(&self, key: &str) -> Result<Option<String>, StorageError> {
+        if let Some(fallback) = fallback_store() {
+            return fallback.retrieve(key);
+        }
+
         let entry = Entry::new(SERVICE_NAME, key).map_err(StorageError::KeyringError)?;
         
         match entry.get_password() {
@@ -187,19 +247,264 @@ impl SecureStorage for PlatformSecureStorage {
     }
     
     fn delete(&self, key: &str) -> Result<(), StorageError> {
+        if let Some(fallback) = fallback_store() {
+            return fallback.delete(key);
+        }
+
         let entry = Entry::new(SERVICE_NAME, key).map_err(StorageError::KeyringError)?;
         entry.delete_credential().map_err(StorageError::KeyringError)?;
         Ok(())
     }
 }
 
+/// Where the file store's encryption key is derived from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    Passphrase(String),
+    /// The OS installation ID; keeps casual readers out without a prompt,
+    /// but not other users who can read the same ID
+    MachineId,
+}
+
+impl KeySource {
+    /// `MILK_STORAGE_PASSPHRASE` when set, otherwise the machine ID
+    pub fn from_env() -> Self {
+        match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) if !passphrase.is_empty() => KeySource::Passphrase(passphrase),
+            _ => KeySource::MachineId,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            KeySource::Passphrase(_) => "passphrase",
+            KeySource::MachineId => "machine_id",
+        }
+    }
+
+    fn secret(&self) -> Result<String, StorageError> {
+        match self {
+            KeySource::Passphrase(passphrase) => Ok(passphrase.clone()),
+            KeySource::MachineId => machine_id(),
+        }
+    }
+}
+
+/// Stable per-installation identifier
+fn machine_id() -> Result<String, StorageError> {
+    #[cfg(target_os = "linux")]
+    let id = ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string());
+
+    #[cfg(target_os = "windows")]
+    let id = std::process::Command::new("reg")
+        .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+        .output()
+        .ok()
+        .and_then(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            stdout
+                .lines()
+                .find(|line| line.contains("MachineGuid"))
+                .and_then(|line| line.split_whitespace().last())
+                .map(str::to_string)
+        });
+
+    #[cfg(target_os = "macos")]
+    let id = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()
+        .and_then(|output| {
+            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            stdout
+                .lines()
+                .find(|line| line.contains("IOPlatformUUID"))
+                .and_then(|line| line.split('"').nth(3))
+                .map(str::to_string)
+        });
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    let id: Option<String> = None;
+
+    id.filter(|id| !id.is_empty())
+        .ok_or_else(|| StorageError::FileStoreError(format!("No machine ID available; set {} instead", PASSPHRASE_ENV)))
+}
+
+/// On-disk layout of the credential file
+#[derive(Serialize, Deserialize)]
+struct CredentialFile {
+    /// `KeySource::name` of the key the entries were encrypted with
+    key_source: String,
+    salt: String,
+    rounds: u32,
+    entries: BTreeMap<String, EncryptedData>,
+}
+
+/// AES-256-GCM encrypted credentials in a single JSON file
+///
+/// The key is derived with PBKDF2 from a passphrase or the machine ID and a
+/// random salt stored alongside the entries.
+pub struct FileSecureStorage {
+    path: PathBuf,
+    source: KeySource,
+    rounds: u32,
+    /// Derived key for the salt it was derived with; PBKDF2 is deliberately slow
+    key_cache: Mutex<Option<(String, [u8; 32])>>,
+    /// Held from load to rename by writers, which share one temporary file
+    write_lock: Mutex<()>,
+}
+
+impl FileSecureStorage {
+    pub fn new(path: impl Into<PathBuf>, source: KeySource) -> Self {
+        FileSecureStorage {
+            path: path.into(),
+            source,
+            rounds: PBKDF2_ROUNDS,
+            key_cache: Mutex::new(None),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("milk").join("credentials.json"))
+    }
+
+    fn load(&self) -> Result<CredentialFile, StorageError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| StorageError::FileStoreError(e.to_string())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                Ok(CredentialFile {
+                    key_source: self.source.name().to_string(),
+                    salt: general_purpose::STANDARD.encode(salt),
+                    rounds: self.rounds,
+                    entries: BTreeMap::new(),
+                })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write through a temporary file, readable only by the current user
+    fn save(&self, file: &CredentialFile) -> Result<(), StorageError> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(file).map_err(|e| StorageError::FileStoreError(e.to_string()))?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        write_private(&tmp_path, json.as_bytes())?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn key_for(&self, file: &CredentialFile) -> Result<[u8; 32], StorageError> {
+        if file.key_source != self.source.name() {
+            return Err(StorageError::DecryptionError(format!(
+                "credentials were encrypted with the {} key; set or unset {} to match",
+                file.key_source.replace('_', " "),
+                PASSPHRASE_ENV
+            )));
+        }
+        let mut cache = self.key_cache.lock().unwrap();
+        if let Some((salt, key)) = cache.as_ref() {
+            if *salt == file.salt {
+                return Ok(*key);
+            }
+        }
+        let salt = general_purpose::STANDARD.decode(&file.salt)?;
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(self.source.secret()?.as_bytes(), &salt, file.rounds, &mut key);
+        *cache = Some((file.salt.clone(), key));
+        Ok(key)
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    // The per-user config directory is already private on Windows
+    fs::write(path, contents)
+}
+
+impl SecureStorage for FileSecureStorage {
+    fn store(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        let _write = self.write_lock.lock().unwrap();
+        let mut file = self.load()?;
+        let encrypted = encrypt_with_key(&self.key_for(&file)?, value)?;
+        file.entries.insert(key.to_string(), encrypted);
+        self.save(&file)
+    }
+
+    fn retrieve(&self, key: &str) -> Result<Option<String>, StorageError> {
+        let file = self.load()?;
+        match file.entries.get(key) {
+            Some(encrypted) => decrypt_with_key(&self.key_for(&file)?, encrypted).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let _write = self.write_lock.lock().unwrap();
+        let mut file = self.load()?;
+        if file.entries.remove(key).is_some() {
+            self.save(&file)?;
+        }
+        Ok(())
+    }
+}
+
+/// Process-local storage that never touches the keyring or disk
+///
+/// Injected into the API bridges in tests so they run hermetically.
+#[cfg(test)]
+#[derive(Default)]
+pub struct InMemorySecureStorage {
+    entries: Mutex<std::collections::HashMap<String, String>>,
+}
+
+#[cfg(test)]
+impl InMemorySecureStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+impl SecureStorage for InMemorySecureStorage {
+    fn store(&self, key: &str, value: &str) -> Result<(), StorageError> {
+        self.entries.lock().unwrap().insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn retrieve(&self, key: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), StorageError> {
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_store_and_retrieve() {
-        let storage = PlatformSecureStorage::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("test".to_string()));
         let test_key = "test_credential";
         let test_value = "secret_token_12345";
         
@@ -216,14 +521,16 @@ mod tests {
 
     #[test]
     fn test_retrieve_nonexistent() {
-        let storage = PlatformSecureStorage::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("test".to_string()));
         let result = storage.retrieve("nonexistent_key").unwrap();
         assert_eq!(result, None);
     }
 
     #[test]
     fn test_delete() {
-        let storage = PlatformSecureStorage::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("test".to_string()));
         let test_key = "test_delete";
         let test_value = "delete_me";
         
@@ -240,19 +547,86 @@ mod tests {
 
     #[test]
     fn test_encryption_decryption() {
-        let storage = PlatformSecureStorage::new();
+        let key = [7u8; 32];
         let plaintext = "sensitive_data_123";
         
-        let encrypted = storage.encrypt(plaintext).unwrap();
-        assert_ne!(encrypted, plaintext);
+        let encrypted = encrypt_with_key(&key, plaintext).unwrap();
+        assert_ne!(encrypted.ciphertext, plaintext);
         
-        let decrypted = storage.decrypt(&encrypted).unwrap();
+        let decrypted = decrypt_with_key(&key, &encrypted).unwrap();
         assert_eq!(decrypted, plaintext);
+        assert!(decrypt_with_key(&[8u8; 32], &encrypted).is_err());
+    }
+
+    #[test]
+    fn test_in_memory_store() {
+        let storage = InMemorySecureStorage::new();
+        assert_eq!(storage.retrieve("token").unwrap(), None);
+
+        storage.store("token", "first").unwrap();
+        storage.store("token", "second").unwrap();
+        assert_eq!(storage.retrieve("token").unwrap(), Some("second".to_string()));
+
+        storage.delete("token").unwrap();
+        storage.delete("token").unwrap();
+        assert_eq!(storage.retrieve("token").unwrap(), None);
+    }
+
+    fn file_store(dir: &tempfile::TempDir, source: KeySource) -> FileSecureStorage {
+        FileSecureStorage {
+            rounds: 1_000,
+            ..FileSecureStorage::new(dir.path().join("credentials.json"), source)
+        }
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("hunter2".to_string()));
+
+        storage.store("token", "secret_token_12345").unwrap();
+        storage.store("other", "value").unwrap();
+        assert_eq!(storage.retrieve("token").unwrap(), Some("secret_token_12345".to_string()));
+        assert_eq!(storage.retrieve("missing").unwrap(), None);
+
+        let on_disk = fs::read_to_string(dir.path().join("credentials.json")).unwrap();
+        assert!(!on_disk.contains("secret_token_12345"));
+
+        storage.delete("token").unwrap();
+        assert_eq!(storage.retrieve("token").unwrap(), None);
+        assert_eq!(storage.retrieve("other").unwrap(), Some("value".to_string()));
+    }
+
+    #[test]
+    fn test_file_store_concurrent_writers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("hunter2".to_string()));
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let storage = &storage;
+                scope.spawn(move || storage.store(&format!("key{}", i), "value").unwrap());
+            }
+        });
+        for i in 0..8 {
+            assert_eq!(storage.retrieve(&format!("key{}", i)).unwrap().as_deref(), Some("value"));
+        }
+    }
+
+    #[test]
+    fn test_file_store_rejects_wrong_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        file_store(&dir, KeySource::Passphrase("right".to_string())).store("token", "value").unwrap();
+
+        let wrong = file_store(&dir, KeySource::Passphrase("wrong".to_string()));
+        assert!(matches!(wrong.retrieve("token"), Err(StorageError::DecryptionError(_))));
+        let machine = file_store(&dir, KeySource::MachineId);
+        assert!(matches!(machine.retrieve("token"), Err(StorageError::DecryptionError(_))));
     }
 
     #[test]
     fn test_overwrite_credential() {
-        let storage = PlatformSecureStorage::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let storage = file_store(&dir, KeySource::Passphrase("test".to_string()));
         let test_key = "test_overwrite";
         
         // Store first value
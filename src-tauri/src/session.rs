@@ -0,0 +1,275 @@
+// Player session persistence
+// The queue, current track, seek position, shuffle/repeat and open windows are
+// kept here as the frontend reports them and written to session.json a moment
+// after they change, and once more on exit. `restore_session` hands the last
+// session back at startup so playback resumes where it left off.
+
+use crate::logging::log_warn;
+use crate::playlist::Track;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often a changed session is written to disk
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+static SESSION: Mutex<SessionState> = Mutex::new(SessionState::new());
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    All,
+    One,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSession {
+    pub queue: Vec<Track>,
+    /// Index into `queue` of the playing track
+    pub current_index: Option<usize>,
+    pub position_secs: f64,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+    /// Labels of the windows that were open
+    pub open_windows: Vec<String>,
+}
+
+impl PlayerSession {
+    /// Drop local tracks whose file is gone, keeping `current_index` on the same track
+    ///
+    /// Starts from the beginning of the queue if the playing track itself is gone.
+    fn without_missing_files(mut self) -> Self {
+        let current = self.current_index.and_then(|i| self.queue.get(i)).map(|t| t.id.clone());
+        self.queue.retain(|track| track.file_path.as_deref().is_none_or(|path| crate::path_codec::decode(path).exists()));
+        self.current_index = match current {
+            Some(id) => match self.queue.iter().position(|t| t.id == id) {
+                Some(index) => Some(index),
+                None => {
+                    self.position_secs = 0.0;
+                    (!self.queue.is_empty()).then_some(0)
+                }
+            },
+            None => None,
+        };
+        self
+    }
+}
+
+/// Fields of the session that changed; missing fields are kept
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SessionUpdate {
+    pub queue: Option<Vec<Track>>,
+    /// Index into the (new) queue; cleared if out of range
+    pub current_index: Option<usize>,
+    pub position_secs: Option<f64>,
+    pub shuffle: Option<bool>,
+    pub repeat: Option<RepeatMode>,
+    pub open_windows: Option<Vec<String>>,
+}
+
+struct SessionState {
+    session: PlayerSession,
+    dirty: bool,
+}
+
+impl SessionState {
+    const fn new() -> Self {
+        SessionState {
+            session: PlayerSession {
+                queue: Vec::new(),
+                current_index: None,
+                position_secs: 0.0,
+                shuffle: false,
+                repeat: RepeatMode::Off,
+                open_windows: Vec::new(),
+            },
+            dirty: false,
+        }
+    }
+
+    fn apply(&mut self, update: SessionUpdate) {
+        let session = &mut self.session;
+        if let Some(queue) = update.queue {
+            session.queue = queue;
+            session.current_index = None;
+        }
+        if let Some(index) = update.current_index {
+            session.current_index = Some(index).filter(|i| *i < session.queue.len());
+        }
+        if let Some(position) = update.position_secs {
+            session.position_secs = position.max(0.0);
+        }
+        if let Some(shuffle) = update.shuffle {
+            session.shuffle = shuffle;
+        }
+        if let Some(repeat) = update.repeat {
+            session.repeat = repeat;
+        }
+        if let Some(windows) = update.open_windows {
+            session.open_windows = windows;
+        }
+        self.dirty = true;
+    }
+
+    fn take_dirty(&mut self) -> Option<PlayerSession> {
+        std::mem::take(&mut self.dirty).then(|| self.session.clone())
+    }
+}
+
+/// The track the session is currently playing
+pub fn current_track() -> Option<Track> {
+    let state = SESSION.lock().unwrap();
+    state.session.current_index.and_then(|i| state.session.queue.get(i)).cloned()
+}
+
+/// Get the session file path in the AppData directory
+pub fn get_default_path() -> Option<PathBuf> {
+    let milk_dir = dirs::config_dir()?.join("milk");
+    fs::create_dir_all(&milk_dir).ok()?;
+    Some(milk_dir.join("session.json"))
+}
+
+/// Read a saved session; None if there is none or it can't be read
+pub fn load(path: &Path) -> Option<PlayerSession> {
+    let contents = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(session) => Some(session),
+        Err(e) => {
+            log_warn("Session", &format!("Ignoring unreadable session file: {}", e));
+            None
+        }
+    }
+}
+
+/// Write through a temporary file so a crash never leaves a truncated session
+pub fn save(path: &Path, session: &PlayerSession) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(session)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, json)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Merge changed fields into the session; written to disk within `SAVE_INTERVAL`
+pub fn update(update: SessionUpdate) {
+    SESSION.lock().unwrap().apply(update);
+}
+
+/// Load the last session and start saving changes
+pub fn start() {
+    let Some(path) = get_default_path() else {
+        log_warn("Session", "No config directory available, session will not be saved");
+        return;
+    };
+    if let Some(saved) = load(&path) {
+        let mut state = SESSION.lock().unwrap();
+        // Changes reported before startup finished win over the saved session
+        if !state.dirty {
+            state.session = saved;
+        }
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            flush_to(&path);
+        }
+    });
+}
+
+/// Write the session now if it changed since the last save
+pub fn flush() {
+    if let Some(path) = get_default_path() {
+        flush_to(&path);
+    }
+}
+
+fn flush_to(path: &Path) {
+    let Some(session) = SESSION.lock().unwrap().take_dirty() else {
+        return;
+    };
+    if let Err(e) = save(path, &session) {
+        log_warn("Session", &format!("Failed to save session: {}", e));
+    }
+}
+
+/// The session to resume, without tracks whose files have disappeared
+pub fn restore() -> PlayerSession {
+    SESSION.lock().unwrap().session.clone().without_missing_files()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::TrackMetadata;
+    use tempfile::TempDir;
+
+    fn track(id: &str, file_path: Option<&Path>) -> Track {
+        Track {
+            id: id.to_string(),
+            title: id.to_string(),
+            artist: String::new(),
+            album: String::new(),
+            duration: 0.0,
+            file_path: file_path.map(|p| p.to_string_lossy().to_string()),
+            source: if file_path.is_some() { "local" } else { "spotify" }.to_string(),
+            metadata: TrackMetadata {
+                year: None,
+                genre: None,
+                track_number: None,
+                disc_number: None,
+                album_art: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_updates_round_trip_through_disk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("session.json");
+        let mut state = SessionState::new();
+        assert!(state.take_dirty().is_none());
+
+        state.apply(SessionUpdate {
+            queue: Some(vec![track("a", None), track("b", None)]),
+            current_index: Some(1),
+            ..Default::default()
+        });
+        state.apply(SessionUpdate { position_secs: Some(42.5), repeat: Some(RepeatMode::One), ..Default::default() });
+        state.apply(SessionUpdate { current_index: Some(7), ..Default::default() });
+        let session = state.take_dirty().unwrap();
+        assert!(state.take_dirty().is_none());
+        assert_eq!(session.current_index, None);
+
+        save(&path, &session).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!((loaded.queue.len(), loaded.position_secs, loaded.repeat), (2, 42.5, RepeatMode::One));
+    }
+
+    #[test]
+    fn test_restore_skips_missing_files() {
+        let dir = TempDir::new().unwrap();
+        let present = dir.path().join("present.wav");
+        fs::write(&present, b"").unwrap();
+        let gone = dir.path().join("gone.wav");
+
+        let session = PlayerSession {
+            queue: vec![track("gone", Some(&gone)), track("stream", None), track("present", Some(&present))],
+            current_index: Some(2),
+            position_secs: 30.0,
+            ..Default::default()
+        };
+        let restored = session.clone().without_missing_files();
+        assert_eq!(restored.queue.len(), 2);
+        assert_eq!((restored.current_index, restored.position_secs), (Some(1), 30.0));
+
+        let restored = PlayerSession { current_index: Some(0), ..session }.without_missing_files();
+        assert_eq!((restored.current_index, restored.position_secs), (Some(0), 0.0));
+    }
+}
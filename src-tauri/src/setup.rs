@@ -0,0 +1,265 @@
+// First-run setup
+// The setup wizard walks through choosing the library folder, a skin and
+// optional streaming sign-in. Each step's settings are written as soon as it
+// completes and progress is kept in the KV store, so quitting half-way resumes
+// at the next step. The library's first index build starts as soon as its
+// folder is chosen and reports progress as `setup-index-progress`.
+
+use crate::config::{update_settings_at, Config, ConfigError};
+use crate::kv_store::{KvError, KvStore};
+use crate::logging::log_warn;
+use crate::skin::SkinParser;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+use thiserror::Error;
+
+const SETUP_KEY: &str = "setup";
+
+// Latest progress of the initial index build, for `get_setup_state`
+static INDEXING: Mutex<Option<IndexProgress>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    Library,
+    Skin,
+    Streaming,
+}
+
+impl SetupStep {
+    /// In the order the wizard shows them
+    pub const ALL: [SetupStep; 3] = [SetupStep::Library, SetupStep::Skin, SetupStep::Streaming];
+}
+
+#[derive(Error, Debug)]
+pub enum SetupError {
+    #[error("Invalid {step:?} step payload: {reason}")]
+    InvalidPayload { step: SetupStep, reason: String },
+    #[error("Library folder not found: {0}")]
+    LibraryNotFound(String),
+    #[error("Not a usable skin: {0}")]
+    InvalidSkin(String),
+    #[error("Failed to save setup settings: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Failed to save setup progress: {0}")]
+    Progress(#[from] KvError),
+}
+
+/// `library` step; no path skips choosing a library
+#[derive(Debug, Deserialize)]
+struct LibraryPayload {
+    path: Option<String>,
+}
+
+/// `skin` step; no path keeps the built-in skin
+#[derive(Debug, Deserialize)]
+struct SkinPayload {
+    skin_path: Option<String>,
+}
+
+/// `streaming` step; sign-in itself goes through the existing auth commands
+#[derive(Debug, Deserialize)]
+struct StreamingPayload {
+    #[serde(default)]
+    spotify_enabled: bool,
+    #[serde(default)]
+    youtube_enabled: bool,
+}
+
+/// Saved in the KV store
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SetupProgress {
+    completed: Vec<SetupStep>,
+    finished: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexPhase {
+    Scanning,
+    ReadingTags,
+    Done,
+    Failed,
+}
+
+/// Payload of the `setup-index-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexProgress {
+    pub phase: IndexPhase,
+    /// Files whose tags were read so far
+    pub completed: usize,
+    /// Audio files found; 0 while scanning
+    pub total: usize,
+    pub error: Option<String>,
+}
+
+/// Returned by `get_setup_state`
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupState {
+    pub needs_setup: bool,
+    pub completed_steps: Vec<SetupStep>,
+    /// First step not yet completed
+    pub next_step: Option<SetupStep>,
+    pub library_path: Option<String>,
+    pub skin: Option<String>,
+    pub spotify_enabled: bool,
+    pub youtube_enabled: bool,
+    /// Progress of the initial index build, once started
+    pub indexing: Option<IndexProgress>,
+}
+
+/// Returned by `complete_setup_step`
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupProgressView {
+    pub completed_steps: Vec<SetupStep>,
+    pub next_step: Option<SetupStep>,
+    pub finished: bool,
+}
+
+fn progress(store: &KvStore) -> Option<SetupProgress> {
+    store.get(SETUP_KEY)
+}
+
+fn next_step(completed: &[SetupStep]) -> Option<SetupStep> {
+    SetupStep::ALL.into_iter().find(|step| !completed.contains(step))
+}
+
+/// Whether the wizard should be shown
+///
+/// Installs from before setup progress was tracked count as set up once they
+/// have a config file.
+pub fn needs_setup(store: &KvStore, config_exists: bool) -> bool {
+    match progress(store) {
+        Some(progress) => !progress.finished,
+        None => !config_exists,
+    }
+}
+
+pub fn state(store: &KvStore, config: &Config, config_exists: bool) -> SetupState {
+    let completed = progress(store).map(|p| p.completed).unwrap_or_default();
+    SetupState {
+        needs_setup: needs_setup(store, config_exists),
+        next_step: next_step(&completed),
+        completed_steps: completed,
+        library_path: config.library_path.clone(),
+        skin: config.last_skin.clone(),
+        spotify_enabled: config.spotify_enabled,
+        youtube_enabled: config.youtube_enabled,
+        indexing: INDEXING.lock().unwrap().clone(),
+    }
+}
+
+/// Apply a step's choices to the config at `config_path` and record it as done
+///
+/// Steps can be completed again to change the choice. Setup is finished once
+/// every step has been completed.
+pub fn complete_step(store: &KvStore, config_path: &Path, step: SetupStep, payload: Value) -> Result<SetupProgressView, SetupError> {
+    let invalid = |e: serde_json::Error| SetupError::InvalidPayload { step, reason: e.to_string() };
+    let changes = match step {
+        SetupStep::Library => {
+            let LibraryPayload { path } = serde_json::from_value(payload).map_err(invalid)?;
+            let path = path.map(|p| p.trim().to_string()).filter(|p| !p.is_empty());
+            if let Some(path) = &path {
+                if !Path::new(path).is_dir() {
+                    return Err(SetupError::LibraryNotFound(path.clone()));
+                }
+            }
+            vec![("library_path".to_string(), json!(path))]
+        }
+        SetupStep::Skin => {
+            let SkinPayload { skin_path } = serde_json::from_value(payload).map_err(invalid)?;
+            if let Some(path) = &skin_path {
+                SkinParser::parse_wsz(Path::new(path)).map_err(|e| SetupError::InvalidSkin(format!("{}: {}", path, e)))?;
+            }
+            vec![("last_skin".to_string(), json!(skin_path))]
+        }
+        SetupStep::Streaming => {
+            let payload: StreamingPayload = serde_json::from_value(payload).map_err(invalid)?;
+            vec![
+                ("spotify_enabled".to_string(), json!(payload.spotify_enabled)),
+                ("youtube_enabled".to_string(), json!(payload.youtube_enabled)),
+            ]
+        }
+    };
+    update_settings_at(config_path, changes)?;
+
+    let mut progress = progress(store).unwrap_or_default();
+    if !progress.completed.contains(&step) {
+        progress.completed.push(step);
+    }
+    progress.finished = next_step(&progress.completed).is_none();
+    store.set(SETUP_KEY, &progress)?;
+    Ok(SetupProgressView {
+        next_step: next_step(&progress.completed),
+        completed_steps: progress.completed,
+        finished: progress.finished,
+    })
+}
+
+/// Record and emit initial index build progress
+pub fn report_index_progress(app: &AppHandle, progress: IndexProgress) {
+    *INDEXING.lock().unwrap() = Some(progress.clone());
+    if let Err(e) = app.emit("setup-index-progress", &progress) {
+        log_warn("Setup", &format!("Failed to emit setup-index-progress event: {}", e));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_steps_save_settings_and_progress() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("config.json");
+        let store = KvStore::in_memory();
+        assert!(needs_setup(&store, false));
+        assert!(!needs_setup(&store, true));
+
+        let library = dir.path().to_string_lossy().to_string();
+        let view = complete_step(&store, &config_path, SetupStep::Library, json!({ "path": library })).unwrap();
+        assert_eq!(view.next_step, Some(SetupStep::Skin));
+        // The config file now exists, but setup is still unfinished
+        assert!(needs_setup(&store, true));
+
+        complete_step(&store, &config_path, SetupStep::Skin, json!({ "skin_path": null })).unwrap();
+        let view = complete_step(&store, &config_path, SetupStep::Streaming, json!({ "spotify_enabled": true })).unwrap();
+        assert!(view.finished);
+        assert!(!needs_setup(&store, true));
+
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(&config_path).unwrap()).unwrap();
+        let state = state(&store, &config, true);
+        assert_eq!(state.library_path, Some(library));
+        assert!(state.spotify_enabled && !state.youtube_enabled);
+        assert_eq!(state.next_step, None);
+    }
+
+    #[test]
+    fn test_invalid_steps_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let config_path = dir.path().join("config.json");
+        let store = KvStore::in_memory();
+        let missing = dir.path().join("missing").to_string_lossy().to_string();
+
+        assert!(matches!(
+            complete_step(&store, &config_path, SetupStep::Library, json!({ "path": missing })),
+            Err(SetupError::LibraryNotFound(_))
+        ));
+        assert!(matches!(
+            complete_step(&store, &config_path, SetupStep::Streaming, json!("yes")),
+            Err(SetupError::InvalidPayload { .. })
+        ));
+        let not_a_skin = dir.path().join("skin.wsz");
+        std::fs::write(&not_a_skin, b"").unwrap();
+        assert!(matches!(
+            complete_step(&store, &config_path, SetupStep::Skin, json!({ "skin_path": not_a_skin })),
+            Err(SetupError::InvalidSkin(_))
+        ));
+        assert!(progress(&store).is_none());
+        assert!(!config_path.exists());
+    }
+}
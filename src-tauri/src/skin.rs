@@ -1,8 +1,13 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use lru::LruCache;
+use image::{GenericImageView, ImageFormat};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
+use std::io::{BufReader, Cursor, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use thiserror::Error;
 use zip::ZipArchive;
 
@@ -25,11 +30,82 @@ pub struct ParsedSkin {
     pub name: String,
     pub assets: HashMap<String, Vec<u8>>,
     pub regions: Option<RegionConfig>,
+    /// Playlist editor colors and font from pledit.txt
+    pub playlist_style: Option<PlaylistStyle>,
+    /// Visualizer palette from viscolor.txt (24 entries for classic skins)
+    pub vis_colors: Option<Vec<RgbColor>>,
 }
 
+/// An RGB color parsed from a skin configuration file
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl RgbColor {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Parse a `#RRGGBB` (or bare `RRGGBB`) hex color
+    pub fn from_hex(value: &str) -> Option<Self> {
+        let hex = value.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        Some(Self {
+            r: u8::from_str_radix(&hex[0..2], 16).ok()?,
+            g: u8::from_str_radix(&hex[2..4], 16).ok()?,
+            b: u8::from_str_radix(&hex[4..6], 16).ok()?,
+        })
+    }
+}
+
+/// Playlist editor styling from pledit.txt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlaylistStyle {
+    pub normal: RgbColor,
+    pub current: RgbColor,
+    pub normal_bg: RgbColor,
+    pub selected_bg: RgbColor,
+    pub font: String,
+    pub minibrowser_bg: Option<RgbColor>,
+    pub minibrowser_fg: Option<RgbColor>,
+}
+
+impl Default for PlaylistStyle {
+    /// Colors used by the base Winamp 2.x skin
+    fn default() -> Self {
+        Self {
+            normal: RgbColor::new(0x00, 0xff, 0x00),
+            current: RgbColor::new(0xff, 0xff, 0xff),
+            normal_bg: RgbColor::new(0x00, 0x00, 0x00),
+            selected_bg: RgbColor::new(0x00, 0x00, 0xc6),
+            font: "Arial".to_string(),
+            minibrowser_bg: None,
+            minibrowser_fg: None,
+        }
+    }
+}
+
+/// Number of palette entries in a classic viscolor.txt
+pub const VIS_COLOR_COUNT: usize = 24;
+
+/// Height of the title-bar strip a classic window collapses to in shade mode
+pub const SHADE_HEIGHT: u32 = 14;
+
+/// Window outlines from region.txt, as bounding boxes
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegionConfig {
     pub main: Region,
+    /// `[WindowShade]`: the main window in shade mode
+    #[serde(default)]
+    pub shade: Option<Region>,
+    /// `[EqualizerWS]`: the equalizer in shade mode
+    #[serde(default)]
+    pub equalizer_shade: Option<Region>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +116,150 @@ pub struct Region {
     pub height: u32,
 }
 
+/// Magenta marks transparent pixels in classic skin bitmaps
+const TRANSPARENT_KEY: [u8; 3] = [0xFF, 0x00, 0xFF];
+
+/// How `extract_assets` prepares bitmaps for the webview
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SkinAssetOptions {
+    /// Re-encode BMP assets as PNG so the frontend needs no BMP decoder
+    pub transcode_bmp: bool,
+    /// While transcoding, make #FF00FF pixels fully transparent
+    pub magenta_transparency: bool,
+}
+
+impl Default for SkinAssetOptions {
+    fn default() -> Self {
+        Self { transcode_bmp: true, magenta_transparency: true }
+    }
+}
+
+/// Glyph size in text.bmp
+const TEXT_GLYPH_SIZE: (u32, u32) = (5, 6);
+
+/// Digit size in nums_ex.bmp and numbers.bmp
+const NUMBER_GLYPH_SIZE: (u32, u32) = (9, 13);
+
+/// Rows of text.bmp, one glyph per character; `\0` marks unused cells
+const TEXT_ROWS: [&str; 3] = [
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZ\"@\0\0 ",
+    "0123456789\u{2026}.:()-'!_+\\/[]^&%,=$#",
+    "\u{c5}\u{d6}\u{c4}?*",
+];
+
+/// Characters without a glyph of their own, drawn with another one
+const TEXT_ALIASES: [(char, char); 4] = [('<', '['), ('>', ']'), ('{', '['), ('}', ']')];
+
+/// nums_ex.bmp extends numbers.bmp with a blank and a minus sign after the digits
+const NUMBER_ROW: &str = "0123456789 -";
+
+/// Bitmap fonts and cursors a classic skin draws its text and pointer with
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SkinFonts {
+    /// text.bmp, used for the song title and info displays
+    pub text: Option<BitmapFont>,
+    /// nums_ex.bmp, or numbers.bmp when the skin has no extended sheet
+    pub numbers: Option<BitmapFont>,
+    /// Cursors by lowercase file stem (`normal`, `close`, `posbar`, ...)
+    pub cursors: BTreeMap<String, SkinCursor>,
+}
+
+/// A font sheet and where each character's glyph sits in it
+#[derive(Debug, Clone, Serialize)]
+pub struct BitmapFont {
+    /// The sheet as PNG
+    pub image: Vec<u8>,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    /// Top-left corner of each glyph; letters are listed in both cases
+    pub glyphs: BTreeMap<char, GlyphPosition>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct GlyphPosition {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// A .cur cursor decoded to PNG
+#[derive(Debug, Clone, Serialize)]
+pub struct SkinCursor {
+    pub image: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub hotspot_x: u16,
+    pub hotspot_y: u16,
+}
+
+/// Sheets and config files a classic skin is expected to ship; Winamp draws
+/// the base skin's version of anything missing
+const STANDARD_ASSETS: &[&str] = &[
+    "main.bmp",
+    "cbuttons.bmp",
+    "titlebar.bmp",
+    "posbar.bmp",
+    "playpaus.bmp",
+    "monoster.bmp",
+    "shufrep.bmp",
+    "volume.bmp",
+    "balance.bmp",
+    "text.bmp",
+    "eqmain.bmp",
+    "pledit.bmp",
+    "pledit.txt",
+    "viscolor.txt",
+];
+
+/// Assets larger than this are flagged; classic sheets are well under 100 KB
+const MAX_ASSET_SIZE: usize = 1024 * 1024;
+
+/// Modern (Winamp 3/5) skin files that are ignored here, with what they provide
+const MODERN_SKIN_FILES: &[(&str, &str)] = &[
+    ("skin.xml", "modern skin XML layout"),
+    (".maki", "compiled MAKI script"),
+    (".m", "MAKI script source"),
+    (".ani", "animated cursor"),
+];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// The skin is replaced by the default one
+    Error,
+    /// The skin loads but will not look as intended
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SkinIssueKind {
+    Unparseable,
+    MissingAsset,
+    UnreadableImage,
+    OversizedFile,
+    UnsupportedFeature,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SkinIssue {
+    pub kind: SkinIssueKind,
+    pub severity: IssueSeverity,
+    /// Asset the issue is about, as named in the archive
+    pub asset: Option<String>,
+    pub message: String,
+}
+
+/// Everything wrong with a skin, for skin authors
+#[derive(Debug, Clone, Serialize)]
+pub struct SkinValidationReport {
+    pub name: String,
+    /// Whether loading it falls back to the default skin
+    pub falls_back: bool,
+    pub asset_count: usize,
+    pub issues: Vec<SkinIssue>,
+}
+
 pub struct SkinParser;
 
 impl SkinParser {
@@ -84,10 +304,18 @@ impl SkinParser {
         // Parse region.txt if it exists
         let regions = Self::parse_regions(&assets);
 
+        // Parse optional color configuration files
+        let playlist_style = Self::find_asset(&assets, "pledit.txt")
+            .map(|data| Self::parse_pledit(&String::from_utf8_lossy(data)));
+        let vis_colors = Self::find_asset(&assets, "viscolor.txt")
+            .and_then(|data| Self::parse_viscolor(&String::from_utf8_lossy(data)));
+
         Ok(ParsedSkin {
             name: skin_name,
             assets,
             regions,
+            playlist_style,
+            vis_colors,
         })
     }
 
@@ -98,38 +326,265 @@ impl SkinParser {
     }
 
     /// Extract assets from a parsed skin
-    pub fn extract_assets(skin: &ParsedSkin) -> Result<HashMap<String, Vec<u8>>, SkinError> {
-        Ok(skin.assets.clone())
+    ///
+    /// Transcoded bitmaps keep their `.bmp` names so lookups by file name still
+    /// work; the content is PNG. Bitmaps that fail to decode are passed through
+    /// untouched.
+    pub fn extract_assets(skin: &ParsedSkin, options: &SkinAssetOptions) -> Result<HashMap<String, Vec<u8>>, SkinError> {
+        if !options.transcode_bmp {
+            return Ok(skin.assets.clone());
+        }
+        Ok(skin
+            .assets
+            .iter()
+            .map(|(name, data)| {
+                let png = name
+                    .to_lowercase()
+                    .ends_with(".bmp")
+                    .then(|| Self::bmp_to_png(data, options.magenta_transparency))
+                    .flatten();
+                (name.clone(), png.unwrap_or_else(|| data.clone()))
+            })
+            .collect())
+    }
+
+    /// Re-encode a BMP as PNG, optionally keying out magenta
+    fn bmp_to_png(data: &[u8], magenta_transparency: bool) -> Option<Vec<u8>> {
+        let mut image = image::load_from_memory_with_format(data, ImageFormat::Bmp).ok()?.to_rgba8();
+        if magenta_transparency {
+            for pixel in image.pixels_mut().filter(|pixel| pixel.0[..3] == TRANSPARENT_KEY) {
+                // Cleared rather than just hidden so scaled edges don't bleed pink
+                pixel.0 = [0, 0, 0, 0];
+            }
+        }
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).ok()?;
+        Some(png)
     }
 
     /// Parse region.txt for window shaping
+    ///
+    /// Each `[Section]` lists polygon points as `PointList=x,y, x,y, ...`;
+    /// only their bounding box is kept. A file without a usable `[Normal]`
+    /// section still yields the classic 275x116 main window.
     fn parse_regions(assets: &HashMap<String, Vec<u8>>) -> Option<RegionConfig> {
-        // Look for region.txt (case-insensitive)
-        let region_data = assets
-            .iter()
-            .find(|(name, _)| name.to_lowercase().ends_with("region.txt"))
-            .map(|(_, data)| data)?;
-
+        let region_data = Self::find_asset(assets, "region.txt")?;
         let region_text = String::from_utf8_lossy(region_data);
-
-        // Simple parsing - just extract main window dimensions
-        // Real Winamp region.txt files are more complex, but this is a minimal implementation
-        let lines: Vec<&str> = region_text.lines().collect();
-        if lines.is_empty() {
+        if region_text.trim().is_empty() {
             return None;
         }
 
-        // Default region if parsing fails
+        let mut sections: HashMap<String, Region> = HashMap::new();
+        let mut section = String::new();
+        for line in region_text.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = name.trim().to_lowercase();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if !key.trim().eq_ignore_ascii_case("pointlist") {
+                continue;
+            }
+            if let Some(region) = Self::bounding_box(value) {
+                sections.insert(section.clone(), region);
+            }
+        }
+
         Some(RegionConfig {
-            main: Region {
-                x: 0,
-                y: 0,
-                width: 275,
-                height: 116,
-            },
+            main: sections.remove("normal").unwrap_or(Region { x: 0, y: 0, width: 275, height: 116 }),
+            shade: sections.remove("windowshade"),
+            equalizer_shade: sections.remove("equalizerws"),
         })
     }
 
+    /// Bounding box of a point list such as `0,0, 275,0, 275,14, 0,14`
+    fn bounding_box(points: &str) -> Option<Region> {
+        let numbers: Vec<i32> = points
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse().ok())
+            .collect::<Option<_>>()?;
+        let xs = numbers.iter().step_by(2);
+        let ys = numbers.iter().skip(1).step_by(2);
+        let (min_x, max_x) = (*xs.clone().min()?, *xs.max()?);
+        let (min_y, max_y) = (*ys.clone().min()?, *ys.max()?);
+        Some(Region {
+            x: min_x,
+            y: min_y,
+            width: (max_x - min_x) as u32,
+            height: (max_y - min_y) as u32,
+        })
+    }
+
+    /// Size in skin pixels of `window` in shade mode
+    ///
+    /// Width is `None` for windows that keep their own width when shaded,
+    /// like the resizable playlist editor.
+    pub fn shade_size(skin: &ParsedSkin, window: &str) -> (Option<u32>, u32) {
+        let regions = skin.regions.as_ref();
+        let region = match window {
+            "main" => regions.and_then(|r| r.shade.as_ref()),
+            "equalizer" => regions.and_then(|r| r.equalizer_shade.as_ref()),
+            _ => return (None, SHADE_HEIGHT),
+        };
+        match region {
+            Some(region) => (Some(region.width), region.height),
+            None => (Some(275), SHADE_HEIGHT),
+        }
+    }
+
+    /// Find an asset by file name, ignoring case and any directory prefix
+    fn find_asset<'a>(assets: &'a HashMap<String, Vec<u8>>, file_name: &str) -> Option<&'a Vec<u8>> {
+        assets
+            .iter()
+            .find(|(name, _)| {
+                let lower = name.to_lowercase();
+                lower == file_name || lower.ends_with(&format!("/{}", file_name))
+            })
+            .map(|(_, data)| data)
+    }
+
+    /// Parse pledit.txt into playlist editor colors
+    ///
+    /// Keys are read case-insensitively from any section; missing or malformed
+    /// values keep the classic defaults.
+    pub fn parse_pledit(text: &str) -> PlaylistStyle {
+        let mut style = PlaylistStyle::default();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('[') || line.starts_with(';') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            match key.as_str() {
+                "font" if !value.is_empty() => style.font = value.to_string(),
+                "normal" => style.normal = RgbColor::from_hex(value).unwrap_or(style.normal),
+                "current" => style.current = RgbColor::from_hex(value).unwrap_or(style.current),
+                "normalbg" => style.normal_bg = RgbColor::from_hex(value).unwrap_or(style.normal_bg),
+                "selectedbg" => style.selected_bg = RgbColor::from_hex(value).unwrap_or(style.selected_bg),
+                "mbbg" => style.minibrowser_bg = RgbColor::from_hex(value),
+                "mbfg" => style.minibrowser_fg = RgbColor::from_hex(value),
+                _ => {}
+            }
+        }
+
+        style
+    }
+
+    /// Parse viscolor.txt into the 24-entry visualizer palette
+    ///
+    /// Each line holds `r,g,b` optionally followed by a `//` comment. Returns
+    /// None if fewer than 24 colors could be read.
+    pub fn parse_viscolor(text: &str) -> Option<Vec<RgbColor>> {
+        let mut colors = Vec::with_capacity(VIS_COLOR_COUNT);
+
+        for line in text.lines() {
+            let content = line.split("//").next().unwrap_or("");
+            let components: Vec<u8> = content
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|part| !part.is_empty())
+                .take(3)
+                .filter_map(|part| part.parse::<u16>().ok())
+                .map(|v| v.min(255) as u8)
+                .collect();
+
+            if components.len() == 3 {
+                colors.push(RgbColor::new(components[0], components[1], components[2]));
+                if colors.len() == VIS_COLOR_COUNT {
+                    return Some(colors);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Decode the skin's bitmap fonts and cursors
+    ///
+    /// Glyphs that fall outside an undersized sheet are left out. A broken font
+    /// sheet is an error; a cursor that cannot be decoded is skipped, leaving
+    /// the system pointer in its place.
+    pub fn extract_fonts(skin: &ParsedSkin) -> Result<SkinFonts, SkinError> {
+        let text_rows: Vec<Vec<char>> = TEXT_ROWS.iter().map(|row| row.chars().collect()).collect();
+        let text = Self::find_asset(&skin.assets, "text.bmp")
+            .map(|data| Self::bitmap_font(data, TEXT_GLYPH_SIZE, &text_rows))
+            .transpose()?
+            .map(|mut font| {
+                for (alias, glyph) in TEXT_ALIASES {
+                    if let Some(position) = font.glyphs.get(&glyph).copied() {
+                        font.glyphs.insert(alias, position);
+                    }
+                }
+                font
+            });
+
+        // numbers.bmp stops after the digits, so the extra cells simply don't fit
+        let number_rows = [NUMBER_ROW.chars().collect()];
+        let numbers = Self::find_asset(&skin.assets, "nums_ex.bmp")
+            .or_else(|| Self::find_asset(&skin.assets, "numbers.bmp"))
+            .map(|data| Self::bitmap_font(data, NUMBER_GLYPH_SIZE, &number_rows))
+            .transpose()?;
+
+        let cursors = skin
+            .assets
+            .iter()
+            .filter_map(|(name, data)| {
+                let file_name = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+                let stem = file_name.strip_suffix(".cur")?;
+                Some((stem.to_string(), Self::decode_cursor(data)?))
+            })
+            .collect();
+
+        Ok(SkinFonts { text, numbers, cursors })
+    }
+
+    /// Map a grid of characters onto a font sheet
+    fn bitmap_font(data: &[u8], (width, height): (u32, u32), rows: &[Vec<char>]) -> Result<BitmapFont, SkinError> {
+        let sheet = image::load_from_memory(data)?;
+        let (sheet_width, sheet_height) = sheet.dimensions();
+        let mut glyphs = BTreeMap::new();
+        for (row, chars) in rows.iter().enumerate() {
+            for (column, &c) in chars.iter().enumerate() {
+                let position = GlyphPosition { x: column as u32 * width, y: row as u32 * height };
+                if c == '\0' || position.x + width > sheet_width || position.y + height > sheet_height {
+                    continue;
+                }
+                let other_case = c.to_lowercase().chain(c.to_uppercase()).filter(|other| *other != c);
+                for key in std::iter::once(c).chain(other_case) {
+                    glyphs.entry(key).or_insert(position);
+                }
+            }
+        }
+
+        let mut image = Vec::new();
+        sheet.write_to(&mut Cursor::new(&mut image), ImageFormat::Png)?;
+        Ok(BitmapFont { image, glyph_width: width, glyph_height: height, glyphs })
+    }
+
+    /// Decode a .cur file; the hotspot comes from its first entry
+    fn decode_cursor(data: &[u8]) -> Option<SkinCursor> {
+        // An icon directory of type 2, whose entries hold the hotspot in place
+        // of the color planes and bit count
+        let field = |offset: usize| data.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+        if field(2)? != 2 || field(4)? == 0 {
+            return None;
+        }
+        let (hotspot_x, hotspot_y) = (field(10)?, field(12)?);
+        let cursor = image::load_from_memory_with_format(data, ImageFormat::Ico).ok()?;
+        let mut image = Vec::new();
+        cursor.write_to(&mut Cursor::new(&mut image), ImageFormat::Png).ok()?;
+        Some(SkinCursor { image, width: cursor.width(), height: cursor.height(), hotspot_x, hotspot_y })
+    }
+
     /// Validate that a skin has the minimum required assets
     pub fn validate_skin(skin: &ParsedSkin) -> Result<(), SkinError> {
         // Check for at least one BMP or PNG file
@@ -156,6 +611,106 @@ impl SkinParser {
         Ok(())
     }
 
+    /// Check a skin file and list every problem found, not just the first
+    ///
+    /// Errors are exactly the problems that make `validate_skin` reject the
+    /// skin; everything else is a warning.
+    pub fn validate_detailed(skin_path: &Path) -> SkinValidationReport {
+        let name = skin_path.file_stem().and_then(|s| s.to_str()).unwrap_or("unknown").to_string();
+        let skin = match Self::parse_wsz(skin_path) {
+            Ok(skin) => skin,
+            Err(e) => {
+                let issue = SkinIssue {
+                    kind: SkinIssueKind::Unparseable,
+                    severity: IssueSeverity::Error,
+                    asset: None,
+                    message: e.to_string(),
+                };
+                return SkinValidationReport { name, falls_back: true, asset_count: 0, issues: vec![issue] };
+            }
+        };
+
+        let issue = |kind, severity, asset: &str, message: String| SkinIssue {
+            kind,
+            severity,
+            asset: Some(asset.to_string()),
+            message,
+        };
+        let mut issues = Vec::new();
+        // Both of its checks are about assets that are not there
+        if let Err(e) = Self::validate_skin(&skin) {
+            issues.push(SkinIssue {
+                kind: SkinIssueKind::MissingAsset,
+                severity: IssueSeverity::Error,
+                asset: None,
+                message: e.to_string(),
+            });
+        }
+
+        for standard in STANDARD_ASSETS {
+            let found = skin.assets.keys().find(|name| {
+                let lower = name.to_lowercase();
+                lower == *standard || lower.ends_with(&format!("/{}", standard))
+            });
+            match found {
+                // validate_skin only accepts main.bmp at the top of the archive
+                Some(found) if *standard == "main.bmp" && found.contains('/') => issues.push(issue(
+                    SkinIssueKind::MissingAsset,
+                    IssueSeverity::Warning,
+                    found,
+                    format!("{} is in a folder; move it to the top level of the archive", found),
+                )),
+                Some(_) => {}
+                // Already reported by validate_skin
+                None if *standard == "main.bmp" => {}
+                None => issues.push(issue(
+                    SkinIssueKind::MissingAsset,
+                    IssueSeverity::Warning,
+                    standard,
+                    format!("{} is missing; the default will be used", standard),
+                )),
+            }
+        }
+
+        let mut assets: Vec<_> = skin.assets.iter().collect();
+        assets.sort_by(|a, b| a.0.cmp(b.0));
+        for (asset, data) in assets {
+            let lower = asset.to_lowercase();
+            let is_image = lower.ends_with(".bmp") || lower.ends_with(".png");
+            if let Some(error) = is_image.then(|| image::load_from_memory(data).err()).flatten() {
+                issues.push(issue(
+                    SkinIssueKind::UnreadableImage,
+                    IssueSeverity::Warning,
+                    asset,
+                    format!("{} cannot be decoded: {}", asset, error),
+                ));
+            }
+            if data.len() > MAX_ASSET_SIZE {
+                issues.push(issue(
+                    SkinIssueKind::OversizedFile,
+                    IssueSeverity::Warning,
+                    asset,
+                    format!("{} is {} KB; classic skin assets are far smaller", asset, data.len() / 1024),
+                ));
+            }
+            let file_name = lower.rsplit('/').next().unwrap_or(&lower);
+            let modern = MODERN_SKIN_FILES
+                .iter()
+                .find(|(pattern, _)| file_name == *pattern || (pattern.starts_with('.') && file_name.ends_with(pattern)));
+            if let Some((_, feature)) = modern {
+                issues.push(issue(
+                    SkinIssueKind::UnsupportedFeature,
+                    IssueSeverity::Warning,
+                    asset,
+                    format!("{} is a {}, which is not supported and will be ignored", asset, feature),
+                ));
+            }
+        }
+
+        let falls_back = issues.iter().any(|issue| issue.severity == IssueSeverity::Error);
+        SkinValidationReport { name, falls_back, asset_count: skin.assets.len(), issues }
+    }
+
     /// Get a default fallback skin
     pub fn get_default_skin() -> ParsedSkin {
         ParsedSkin {
@@ -168,11 +723,70 @@ impl SkinParser {
                     width: 275,
                     height: 116,
                 },
+                shade: None,
+                equalizer_shade: None,
             }),
+            playlist_style: Some(PlaylistStyle::default()),
+            vis_colors: None,
         }
     }
 }
 
+/// In-memory cache of parsed skins keyed by path and modification time
+///
+/// A skin is re-parsed when its file's mtime changes, so edited skins are
+/// picked up without clearing the cache.
+pub struct SkinCache {
+    cache: Mutex<LruCache<PathBuf, (SystemTime, Arc<ParsedSkin>)>>,
+}
+
+impl SkinCache {
+    /// Create a cache holding up to `size` parsed skins
+    pub fn new(size: usize) -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(NonZeroUsize::new(size.max(1)).unwrap())),
+        }
+    }
+
+    /// Return the cached skin for `skin_path`, parsing it on a miss
+    pub fn get_or_parse(&self, skin_path: &Path) -> Result<Arc<ParsedSkin>, SkinError> {
+        let modified = std::fs::metadata(skin_path)?.modified()?;
+        let key = skin_path.to_path_buf();
+
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some((cached_mtime, skin)) = cache.get(&key) {
+                if *cached_mtime == modified {
+                    return Ok(Arc::clone(skin));
+                }
+            }
+        }
+
+        // Parse outside the lock so a large archive doesn't block other lookups
+        let is_wal = skin_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("wal"));
+        let skin = Arc::new(if is_wal {
+            SkinParser::parse_wal(skin_path)?
+        } else {
+            SkinParser::parse_wsz(skin_path)?
+        });
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.put(key, (modified, Arc::clone(&skin)));
+        Ok(skin)
+    }
+
+    /// Drop all cached skins, returning how many were removed
+    pub fn clear(&self) -> usize {
+        let mut cache = self.cache.lock().unwrap();
+        let count = cache.len();
+        cache.clear();
+        count
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -223,6 +837,51 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_validate_detailed() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut zip = ZipWriter::new(temp_file.reopen().unwrap());
+        let assets: [(&str, Vec<u8>); 4] = [
+            ("Classic/Main.bmp", encode_image(275, 116, ImageFormat::Bmp)),
+            ("text.bmp", vec![0x42, 0x4D]),
+            ("scripts/player.maki", vec![0; 8]),
+            ("huge.png", vec![0; MAX_ASSET_SIZE + 1]),
+        ];
+        for (name, data) in assets {
+            zip.start_file::<_, ()>(name, FileOptions::default()).unwrap();
+            zip.write_all(&data).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let report = SkinParser::validate_detailed(temp_file.path());
+        assert!(report.falls_back);
+        assert_eq!(report.asset_count, 4);
+        let issues = |kind| report.issues.iter().filter(|issue| issue.kind == kind).collect::<Vec<_>>();
+
+        // The nested main.bmp is rejected, with a hint about why
+        let missing = issues(SkinIssueKind::MissingAsset);
+        assert_eq!(missing[0].severity, IssueSeverity::Error);
+        assert!(missing.iter().any(|issue| issue.asset.as_deref() == Some("Classic/Main.bmp")));
+        assert!(missing.iter().any(|issue| issue.asset.as_deref() == Some("viscolor.txt")));
+        assert!(!missing.iter().any(|issue| issue.asset.as_deref() == Some("text.bmp")));
+
+        let unreadable = issues(SkinIssueKind::UnreadableImage);
+        let unreadable_assets: Vec<_> = unreadable.iter().filter_map(|issue| issue.asset.as_deref()).collect();
+        assert_eq!(unreadable_assets, ["huge.png", "text.bmp"]);
+        assert_eq!(issues(SkinIssueKind::OversizedFile)[0].asset.as_deref(), Some("huge.png"));
+        assert_eq!(issues(SkinIssueKind::UnsupportedFeature)[0].asset.as_deref(), Some("scripts/player.maki"));
+    }
+
+    #[test]
+    fn test_validate_detailed_unparseable() {
+        let temp_file = NamedTempFile::new().unwrap();
+        std::fs::write(temp_file.path(), b"not a zip").unwrap();
+        let report = SkinParser::validate_detailed(temp_file.path());
+        assert!(report.falls_back);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, SkinIssueKind::Unparseable);
+    }
+
     #[test]
     fn test_validate_skin_success() {
         let temp_wsz = create_test_wsz();
@@ -237,17 +896,179 @@ mod tests {
             name: "test".to_string(),
             assets: HashMap::new(),
             regions: None,
+            playlist_style: None,
+            vis_colors: None,
         };
         let result = SkinParser::validate_skin(&skin);
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_pledit() {
+        let text = "[Text]\r\nNormal=#00FF00\r\nCurrent=#FFFFFF\r\nNormalBG=#0A0B0C\r\nSelectedBG=#0000C6\r\nFont=Tahoma\r\nmbFG=#112233\r\n";
+        let style = SkinParser::parse_pledit(text);
+        assert_eq!(style.normal, RgbColor::new(0, 255, 0));
+        assert_eq!(style.normal_bg, RgbColor::new(10, 11, 12));
+        assert_eq!(style.font, "Tahoma");
+        assert_eq!(style.minibrowser_fg, Some(RgbColor::new(0x11, 0x22, 0x33)));
+        assert_eq!(style.minibrowser_bg, None);
+    }
+
+    #[test]
+    fn test_parse_pledit_keeps_defaults_for_bad_values() {
+        let style = SkinParser::parse_pledit("[Text]\nNormal=green\nFont=\n");
+        assert_eq!(style, PlaylistStyle::default());
+    }
+
+    #[test]
+    fn test_parse_viscolor() {
+        let mut text = String::new();
+        for i in 0..24 {
+            text.push_str(&format!("{},{},{}, // color {}\n", i, i * 2, i * 3, i));
+        }
+        let colors = SkinParser::parse_viscolor(&text).unwrap();
+        assert_eq!(colors.len(), VIS_COLOR_COUNT);
+        assert_eq!(colors[0], RgbColor::new(0, 0, 0));
+        assert_eq!(colors[23], RgbColor::new(23, 46, 69));
+    }
+
+    #[test]
+    fn test_parse_viscolor_incomplete() {
+        assert!(SkinParser::parse_viscolor("0,0,0\n24,33,41\n").is_none());
+    }
+
+    #[test]
+    fn test_skin_cache_reuses_parsed_skin() {
+        let temp_wsz = create_test_wsz();
+        let wsz_path = temp_wsz.path();
+
+        let cache = SkinCache::new(2);
+        let first = cache.get_or_parse(wsz_path).unwrap();
+        let second = cache.get_or_parse(wsz_path).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        assert_eq!(cache.clear(), 1);
+        assert_eq!(cache.clear(), 0);
+        let third = cache.get_or_parse(wsz_path).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+    }
+
+    #[test]
+    fn test_skin_cache_missing_file() {
+        let cache = SkinCache::new(2);
+        assert!(cache.get_or_parse(Path::new("/nonexistent/skin.wsz")).is_err());
+    }
+
+    #[test]
+    fn test_rgb_from_hex() {
+        assert_eq!(RgbColor::from_hex("#1A2b3C"), Some(RgbColor::new(0x1a, 0x2b, 0x3c)));
+        assert_eq!(RgbColor::from_hex("1a2b3c"), Some(RgbColor::new(0x1a, 0x2b, 0x3c)));
+        assert!(RgbColor::from_hex("#12345").is_none());
+    }
+
+    #[test]
+    fn test_parse_region_sections() {
+        let text = "[Normal]\nNumPoints=4\nPointList=0,0, 275,0, 275,116, 0,116\n\n[WindowShade]\nNumPoints=4\npointlist=0,0,260,0,260,12,0,12\n";
+        let assets = HashMap::from([("Skin/REGION.TXT".to_string(), text.as_bytes().to_vec())]);
+        let regions = SkinParser::parse_regions(&assets).unwrap();
+        assert_eq!((regions.main.width, regions.main.height), (275, 116));
+        let shade = regions.shade.unwrap();
+        assert_eq!((shade.width, shade.height), (260, 12));
+        assert!(regions.equalizer_shade.is_none());
+
+        let skin = ParsedSkin { regions: Some(SkinParser::parse_regions(&assets).unwrap()), ..SkinParser::get_default_skin() };
+        assert_eq!(SkinParser::shade_size(&skin, "main"), (Some(260), 12));
+        assert_eq!(SkinParser::shade_size(&skin, "equalizer"), (Some(275), SHADE_HEIGHT));
+        assert_eq!(SkinParser::shade_size(&skin, "playlist"), (None, SHADE_HEIGHT));
+    }
+
     #[test]
     fn test_get_default_skin() {
         let skin = SkinParser::get_default_skin();
         assert_eq!(skin.name, "default");
         assert!(skin.regions.is_some());
     }
+
+    fn encode_image(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let img = image::RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8, y as u8, 0, 255]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(img).write_to(&mut Cursor::new(&mut bytes), format).unwrap();
+        bytes
+    }
+
+    /// A single-entry .cur wrapping a PNG
+    fn cursor_bytes(hotspot: (u16, u16)) -> Vec<u8> {
+        let png = encode_image(4, 4, ImageFormat::Png);
+        let mut bytes = vec![0, 0, 2, 0, 1, 0, 4, 4, 0, 0];
+        bytes.extend_from_slice(&hotspot.0.to_le_bytes());
+        bytes.extend_from_slice(&hotspot.1.to_le_bytes());
+        bytes.extend_from_slice(&(png.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&22u32.to_le_bytes());
+        bytes.extend_from_slice(&png);
+        bytes
+    }
+
+    #[test]
+    fn test_extract_fonts() {
+        let mut skin = SkinParser::get_default_skin();
+        skin.assets.insert("Skin/TEXT.BMP".to_string(), encode_image(155, 18, ImageFormat::Bmp));
+        skin.assets.insert("numbers.bmp".to_string(), encode_image(99, 13, ImageFormat::Bmp));
+        skin.assets.insert("Close.cur".to_string(), cursor_bytes((1, 2)));
+        skin.assets.insert("broken.cur".to_string(), vec![0, 0, 2, 0]);
+
+        let fonts = SkinParser::extract_fonts(&skin).unwrap();
+        let text = fonts.text.unwrap();
+        assert_eq!((text.glyph_width, text.glyph_height), (5, 6));
+        assert_eq!(text.glyphs[&'a'], GlyphPosition { x: 0, y: 0 });
+        assert_eq!(text.glyphs[&'A'], text.glyphs[&'a']);
+        assert_eq!(text.glyphs[&' '], GlyphPosition { x: 150, y: 0 });
+        assert_eq!(text.glyphs[&'#'], GlyphPosition { x: 150, y: 6 });
+        assert_eq!(text.glyphs[&'<'], text.glyphs[&'[']);
+        assert_eq!(text.glyphs[&'\u{e4}'], GlyphPosition { x: 10, y: 12 });
+        assert!(!text.glyphs.contains_key(&'\0'));
+
+        // numbers.bmp has no room for the extended glyphs
+        let numbers = fonts.numbers.unwrap();
+        assert_eq!(numbers.glyphs[&'9'], GlyphPosition { x: 81, y: 0 });
+        assert!(!numbers.glyphs.contains_key(&'-'));
+
+        assert_eq!(fonts.cursors.len(), 1);
+        let close = &fonts.cursors["close"];
+        assert_eq!((close.width, close.height, close.hotspot_x, close.hotspot_y), (4, 4, 1, 2));
+        assert!(image::load_from_memory_with_format(&close.image, ImageFormat::Png).is_ok());
+    }
+
+    #[test]
+    fn test_extract_assets_transcodes_bmp() {
+        let sheet = image::RgbImage::from_fn(2, 1, |x, _| if x == 0 { image::Rgb(TRANSPARENT_KEY) } else { image::Rgb([1, 2, 3]) });
+        let mut bmp = Vec::new();
+        image::DynamicImage::ImageRgb8(sheet).write_to(&mut Cursor::new(&mut bmp), ImageFormat::Bmp).unwrap();
+        let mut skin = SkinParser::get_default_skin();
+        skin.assets.insert("Main.BMP".to_string(), bmp.clone());
+        skin.assets.insert("broken.bmp".to_string(), vec![0x42, 0x4D]);
+        skin.assets.insert("region.txt".to_string(), b"275 116".to_vec());
+
+        let assets = SkinParser::extract_assets(&skin, &SkinAssetOptions::default()).unwrap();
+        let main = image::load_from_memory_with_format(&assets["Main.BMP"], ImageFormat::Png).unwrap().to_rgba8();
+        assert_eq!(main.get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(main.get_pixel(1, 0).0, [1, 2, 3, 255]);
+        assert_eq!(assets["broken.bmp"], [0x42, 0x4D]);
+        assert_eq!(assets["region.txt"], b"275 116");
+
+        let opaque = SkinAssetOptions { magenta_transparency: false, ..SkinAssetOptions::default() };
+        let assets = SkinParser::extract_assets(&skin, &opaque).unwrap();
+        let main = image::load_from_memory_with_format(&assets["Main.BMP"], ImageFormat::Png).unwrap().to_rgba8();
+        assert_eq!(main.get_pixel(0, 0).0, [0xFF, 0x00, 0xFF, 255]);
+
+        let untouched = SkinAssetOptions { transcode_bmp: false, ..SkinAssetOptions::default() };
+        assert_eq!(SkinParser::extract_assets(&skin, &untouched).unwrap()["Main.BMP"], bmp);
+    }
+
+    #[test]
+    fn test_extract_fonts_without_assets() {
+        let fonts = SkinParser::extract_fonts(&SkinParser::get_default_skin()).unwrap();
+        assert!(fonts.text.is_none() && fonts.numbers.is_none() && fonts.cursors.is_empty());
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,279 @@
+// Winamp Skin Museum (skins.webamp.org) integration
+// Browses and searches the museum catalogue and downloads skins into the local skin library
+
+use crate::api_client;
+use crate::error_recovery::ErrorRecovery;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+
+const MUSEUM_GRAPHQL_URL: &str = "https://api.webamp.org/graphql";
+
+/// Skin archives can take a while on slow links; API calls keep the shared client's timeout
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Maximum number of skins returned by a single browse or search request
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+#[derive(Error, Debug)]
+pub enum MuseumError {
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("Unexpected museum response: {0}")]
+    Parse(String),
+    #[error("Downloaded skin hash mismatch (expected {expected}, got {actual})")]
+    HashMismatch { expected: String, actual: String },
+    #[error("Not enough disk space to save {0}")]
+    DiskFull(String),
+    #[error("Failed to save skin: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// A skin listed in the museum catalogue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MuseumSkin {
+    /// MD5 of the skin file; the museum's primary key
+    pub md5: String,
+    pub filename: String,
+    pub download_url: String,
+    pub screenshot_url: Option<String>,
+    #[serde(default)]
+    pub nsfw: bool,
+}
+
+/// One page of museum results
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuseumPage {
+    pub total: Option<u64>,
+    pub skins: Vec<MuseumSkin>,
+}
+
+/// Payload of the `skin-download-progress` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkinDownloadProgress {
+    pub md5: String,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+const SKIN_FIELDS: &str = "md5 filename download_url screenshot_url nsfw";
+
+/// Museum API client; requests go through the shared, proxy-aware HTTP client
+pub struct SkinMuseumClient;
+
+impl SkinMuseumClient {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Browse the museum in its default ordering
+    pub async fn browse(&self, offset: u32, limit: u32) -> Result<MuseumPage, MuseumError> {
+        let query = format!(
+            "query($first: Int, $offset: Int) {{ skins(first: $first, offset: $offset) {{ count nodes {{ {} }} }} }}",
+            SKIN_FIELDS
+        );
+        let variables = serde_json::json!({ "first": limit.min(MAX_PAGE_SIZE), "offset": offset });
+        let data = self.graphql(&query, variables).await?;
+
+        let connection = data
+            .get("skins")
+            .ok_or_else(|| MuseumError::Parse("Missing 'skins' field".to_string()))?;
+        Ok(MuseumPage {
+            total: connection.get("count").and_then(|v| v.as_u64()),
+            skins: Self::parse_skins(connection.get("nodes"))?,
+        })
+    }
+
+    /// Full-text search of the museum catalogue
+    pub async fn search(&self, query_text: &str, offset: u32, limit: u32) -> Result<MuseumPage, MuseumError> {
+        let query = format!(
+            "query($query: String!, $first: Int, $offset: Int) {{ search_skins(query: $query, first: $first, offset: $offset) {{ {} }} }}",
+            SKIN_FIELDS
+        );
+        let variables = serde_json::json!({
+            "query": query_text,
+            "first": limit.min(MAX_PAGE_SIZE),
+            "offset": offset,
+        });
+        let data = self.graphql(&query, variables).await?;
+
+        Ok(MuseumPage {
+            total: None,
+            skins: Self::parse_skins(data.get("search_skins"))?,
+        })
+    }
+
+    /// Download a skin into `dest_dir`, verifying its MD5 against the museum hash
+    ///
+    /// `on_progress` is called after every received chunk. Returns the path of
+    /// the saved skin file. Fails with `MuseumError::DiskFull` before the body
+    /// is downloaded when the reported size won't fit.
+    pub async fn download<F>(&self, skin: &MuseumSkin, dest_dir: &Path, mut on_progress: F) -> Result<PathBuf, MuseumError>
+    where
+        F: FnMut(SkinDownloadProgress),
+    {
+        let mut response = api_client::http_client()
+            .get(&skin.download_url)
+            .timeout(DOWNLOAD_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| MuseumError::Network(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(MuseumError::Network(format!("Status {}", response.status())));
+        }
+
+        let total_bytes = response.content_length();
+        if let Some(total) = total_bytes {
+            if !ErrorRecovery::has_free_space(dest_dir, total) {
+                return Err(MuseumError::DiskFull(skin.filename.clone()));
+            }
+        }
+        let mut hasher = Md5::new();
+        let mut bytes = Vec::with_capacity(total_bytes.unwrap_or(0) as usize);
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| MuseumError::Network(e.to_string()))?
+        {
+            hasher.update(&chunk);
+            bytes.extend_from_slice(&chunk);
+            on_progress(SkinDownloadProgress {
+                md5: skin.md5.clone(),
+                downloaded_bytes: bytes.len() as u64,
+                total_bytes,
+            });
+        }
+
+        verify_md5(&skin.md5, &format!("{:x}", hasher.finalize()))?;
+
+        tokio::fs::create_dir_all(dest_dir).await?;
+        let path = dest_dir.join(sanitize_filename(&skin.filename, &skin.md5));
+        tokio::fs::write(&path, &bytes).await?;
+        Ok(path)
+    }
+
+    /// Run a GraphQL query and return its `data` object
+    async fn graphql(&self, query: &str, variables: serde_json::Value) -> Result<serde_json::Value, MuseumError> {
+        let response = api_client::http_client()
+            .post(MUSEUM_GRAPHQL_URL)
+            .json(&serde_json::json!({ "query": query, "variables": variables }))
+            .send()
+            .await
+            .map_err(|e| MuseumError::Network(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(MuseumError::Network(format!("Status {}: {}", status, error_text)));
+        }
+
+        let mut json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| MuseumError::Parse(e.to_string()))?;
+
+        if let Some(errors) = json.get("errors").and_then(|v| v.as_array()) {
+            if let Some(message) = errors.first().and_then(|e| e.get("message")).and_then(|m| m.as_str()) {
+                return Err(MuseumError::Parse(message.to_string()));
+            }
+        }
+
+        json.get_mut("data")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| MuseumError::Parse("Missing 'data' field".to_string()))
+    }
+
+    fn parse_skins(nodes: Option<&serde_json::Value>) -> Result<Vec<MuseumSkin>, MuseumError> {
+        let nodes = nodes.ok_or_else(|| MuseumError::Parse("Missing skin list".to_string()))?;
+        serde_json::from_value(nodes.clone()).map_err(|e| MuseumError::Parse(e.to_string()))
+    }
+}
+
+impl Default for SkinMuseumClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Directory downloaded skins are saved into
+pub fn get_skin_library_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("milk").join("skins"))
+}
+
+/// Compare a computed MD5 against the museum's hash, ignoring case
+fn verify_md5(expected: &str, actual: &str) -> Result<(), MuseumError> {
+    if expected.eq_ignore_ascii_case(actual) {
+        Ok(())
+    } else {
+        Err(MuseumError::HashMismatch {
+            expected: expected.to_lowercase(),
+            actual: actual.to_lowercase(),
+        })
+    }
+}
+
+/// Reduce a museum filename to a safe local file name
+///
+/// Path separators and other reserved characters are replaced, and a `.wsz`
+/// extension is ensured. Falls back to the hash when nothing usable remains.
+fn sanitize_filename(filename: &str, md5: &str) -> String {
+    let cleaned: String = filename
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_start_matches('.');
+
+    let stem = if cleaned.is_empty() { md5 } else { cleaned };
+    let lower = stem.to_lowercase();
+    if lower.ends_with(".wsz") || lower.ends_with(".wal") {
+        stem.to_string()
+    } else {
+        format!("{}.wsz", stem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_md5() {
+        let hash = format!("{:x}", Md5::digest(b"skin"));
+        assert!(verify_md5(&hash.to_uppercase(), &hash).is_ok());
+        assert!(matches!(
+            verify_md5("00000000000000000000000000000000", &hash),
+            Err(MuseumError::HashMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("Base 2.91.wsz", "abc"), "Base 2.91.wsz");
+        assert_eq!(sanitize_filename("../evil/skin.WSZ", "abc"), "_evil_skin.WSZ");
+        assert_eq!(sanitize_filename("Modern", "abc"), "Modern.wsz");
+        assert_eq!(sanitize_filename("  ", "abc"), "abc.wsz");
+    }
+
+    #[test]
+    fn test_parse_skins() {
+        let nodes = serde_json::json!([{
+            "md5": "5e4f10275dcb1fb211d4a8b4f1bda236",
+            "filename": "base-2.91.wsz",
+            "download_url": "https://example.com/base.wsz",
+            "screenshot_url": null,
+            "nsfw": false
+        }]);
+        let skins = SkinMuseumClient::parse_skins(Some(&nodes)).unwrap();
+        assert_eq!(skins.len(), 1);
+        assert_eq!(skins[0].filename, "base-2.91.wsz");
+        assert!(SkinMuseumClient::parse_skins(None).is_err());
+    }
+}
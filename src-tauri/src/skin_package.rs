@@ -0,0 +1,187 @@
+// Skin packaging
+// Zips a directory of skin assets into a distributable .wsz: names are
+// lowercased with forward slashes, a single wrapping folder is dropped, and
+// OS clutter is left out. The result is validated the same way a skin is
+// checked on load, and anything worth fixing comes back as warnings.
+
+use crate::skin::{SkinError, SkinIssue, SkinParser};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use zip::write::{FileOptions, ZipWriter};
+
+/// Files operating systems and editors leave behind
+const CLUTTER: &[&str] = &["thumbs.db", "desktop.ini"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkinPackageReport {
+    pub output: String,
+    /// Names written to the archive, sorted
+    pub files: Vec<String>,
+    /// Hidden files, OS clutter and symlinks that were left out
+    pub skipped: Vec<String>,
+    /// Problems found in the packaged skin; it is written regardless
+    pub warnings: Vec<SkinIssue>,
+}
+
+/// Package `directory` into the .wsz at `output`, replacing it
+///
+/// Nothing is written unless main.bmp is present and no two files share a
+/// name once lowercased.
+pub fn package_skin(directory: &Path, output: &Path) -> Result<SkinPackageReport, SkinError> {
+    if !directory.is_dir() {
+        return Err(SkinError::InvalidFormat(format!("{} is not a directory", directory.display())));
+    }
+    let mut found = Vec::new();
+    let mut skipped = Vec::new();
+    collect_files(directory, directory, output, &mut found, &mut skipped)?;
+
+    let mut entries: BTreeMap<String, PathBuf> = BTreeMap::new();
+    let wrapper = single_wrapper(&found);
+    for (relative, path) in found {
+        let name = relative.strip_prefix(wrapper.as_deref().unwrap_or("")).unwrap_or(&relative).to_lowercase();
+        if let Some(existing) = entries.insert(name.clone(), path.clone()) {
+            return Err(SkinError::InvalidFormat(format!(
+                "{} and {} would both be packaged as {}",
+                existing.display(),
+                path.display(),
+                name
+            )));
+        }
+    }
+    if !entries.contains_key("main.bmp") {
+        return Err(SkinError::MissingAsset("main.bmp".to_string()));
+    }
+
+    // Written next to the destination first so a failure leaves no half-written skin
+    let partial = output.with_extension("wsz.part");
+    let written = write_archive(&partial, &entries).and_then(|_| Ok(fs::rename(&partial, output)?));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    let warnings = SkinParser::validate_detailed(output).issues;
+    skipped.sort();
+    Ok(SkinPackageReport {
+        output: output.to_string_lossy().to_string(),
+        files: entries.into_keys().collect(),
+        skipped,
+        warnings,
+    })
+}
+
+/// Every file under `dir` as (path relative to `root` with `/` separators, full path)
+///
+/// Symlinks are skipped rather than followed, so a link back up the tree can't loop.
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    output: &Path,
+    found: &mut Vec<(String, PathBuf)>,
+    skipped: &mut Vec<String>,
+) -> Result<(), SkinError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let file_type = fs::symlink_metadata(&path)?.file_type();
+        if file_name.starts_with('.') || CLUTTER.contains(&file_name.as_str()) || file_type.is_symlink() {
+            skipped.push(relative);
+        } else if file_type.is_dir() {
+            collect_files(root, &path, output, found, skipped)?;
+        } else if path != output {
+            found.push((relative, path));
+        }
+    }
+    Ok(())
+}
+
+/// The folder holding every file, when the directory wraps its contents in one
+fn single_wrapper(files: &[(String, PathBuf)]) -> Option<String> {
+    let (first, _) = files.first()?;
+    let (folder, _) = first.split_once('/')?;
+    let prefix = format!("{}/", folder);
+    files.iter().all(|(relative, _)| relative.starts_with(&prefix)).then_some(prefix)
+}
+
+fn write_archive(path: &Path, entries: &BTreeMap<String, PathBuf>) -> Result<(), SkinError> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    for (name, source) in entries {
+        zip.start_file::<_, ()>(name.as_str(), FileOptions::default())?;
+        zip.write_all(&fs::read(source)?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skin::SkinIssueKind;
+    use tempfile::TempDir;
+
+    fn write(dir: &Path, name: &str, contents: &[u8]) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_package_normalizes_names() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "MySkin/Main.BMP", b"not really a bitmap");
+        write(dir.path(), "MySkin/PLEDIT.TXT", b"[Text]\nNormal=#00FF00\n");
+        write(dir.path(), "MySkin/.DS_Store", b"");
+        write(dir.path(), "MySkin/Thumbs.db", b"");
+        let output = dir.path().join("out.wsz");
+
+        let report = package_skin(dir.path(), &output).unwrap();
+        assert_eq!(report.files, ["main.bmp", "pledit.txt"]);
+        assert_eq!(report.skipped, ["MySkin/.DS_Store", "MySkin/Thumbs.db"]);
+        assert!(!dir.path().join("out.wsz.part").exists());
+
+        let skin = SkinParser::parse_wsz(&output).unwrap();
+        assert_eq!(skin.assets["main.bmp"], b"not really a bitmap");
+        assert!(SkinParser::validate_skin(&skin).is_ok());
+        assert!(report.warnings.iter().any(|w| w.kind == SkinIssueKind::UnreadableImage));
+        assert!(report.warnings.iter().any(|w| w.asset.as_deref() == Some("viscolor.txt")));
+    }
+
+    #[test]
+    fn test_package_rejects_incomplete_skins() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "text.bmp", b"");
+        let output = dir.path().join("out.wsz");
+        assert!(matches!(package_skin(dir.path(), &output), Err(SkinError::MissingAsset(_))));
+
+        write(dir.path(), "main.bmp", b"");
+        write(dir.path(), "MAIN.bmp", b"");
+        // Case-insensitive file systems cannot hold both names
+        if fs::read_dir(dir.path()).unwrap().count() == 3 {
+            assert!(matches!(package_skin(dir.path(), &output), Err(SkinError::InvalidFormat(_))));
+        }
+        assert!(!output.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_package_skips_symlinks() {
+        let dir = TempDir::new().unwrap();
+        write(dir.path(), "skin/main.bmp", b"");
+        std::os::unix::fs::symlink(dir.path().join("skin"), dir.path().join("skin/loop")).unwrap();
+        let output = dir.path().join("out.wsz");
+
+        let report = package_skin(&dir.path().join("skin"), &output).unwrap();
+        assert_eq!(report.files, ["main.bmp"]);
+        assert_eq!(report.skipped, ["loop"]);
+    }
+}
@@ -0,0 +1,276 @@
+// Sprite-sheet slicing for classic Winamp skins
+// Coordinates follow the canonical Winamp 2.x skin layout
+
+use crate::skin::{ParsedSkin, SkinError};
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::collections::HashMap;
+use std::io::Cursor;
+
+/// A named rectangle within a sprite sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteRect {
+    pub name: &'static str,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+const fn sprite(name: &'static str, x: u32, y: u32, width: u32, height: u32) -> SpriteRect {
+    SpriteRect { name, x, y, width, height }
+}
+
+const MAIN_SPRITES: &[SpriteRect] = &[
+    sprite("main_window_background", 0, 0, 275, 116),
+];
+
+const CBUTTONS_SPRITES: &[SpriteRect] = &[
+    sprite("previous_button", 0, 0, 23, 18),
+    sprite("previous_button_pressed", 0, 18, 23, 18),
+    sprite("play_button", 23, 0, 23, 18),
+    sprite("play_button_pressed", 23, 18, 23, 18),
+    sprite("pause_button", 46, 0, 23, 18),
+    sprite("pause_button_pressed", 46, 18, 23, 18),
+    sprite("stop_button", 69, 0, 23, 18),
+    sprite("stop_button_pressed", 69, 18, 23, 18),
+    sprite("next_button", 92, 0, 22, 18),
+    sprite("next_button_pressed", 92, 18, 22, 18),
+    sprite("eject_button", 114, 0, 22, 16),
+    sprite("eject_button_pressed", 114, 16, 22, 16),
+];
+
+const TITLEBAR_SPRITES: &[SpriteRect] = &[
+    sprite("title_bar", 27, 15, 275, 14),
+    sprite("title_bar_selected", 27, 0, 275, 14),
+    sprite("shade_background", 27, 42, 275, 14),
+    sprite("shade_background_selected", 27, 29, 275, 14),
+    sprite("options_button", 0, 0, 9, 9),
+    sprite("options_button_pressed", 0, 9, 9, 9),
+    sprite("minimize_button", 9, 0, 9, 9),
+    sprite("minimize_button_pressed", 9, 9, 9, 9),
+    sprite("close_button", 18, 0, 9, 9),
+    sprite("close_button_pressed", 18, 9, 9, 9),
+    sprite("shade_button", 0, 18, 9, 9),
+    sprite("shade_button_pressed", 9, 18, 9, 9),
+    sprite("clutter_bar", 304, 0, 8, 43),
+];
+
+const NUMBERS_SPRITES: &[SpriteRect] = &[
+    sprite("digit_0", 0, 0, 9, 13),
+    sprite("digit_1", 9, 0, 9, 13),
+    sprite("digit_2", 18, 0, 9, 13),
+    sprite("digit_3", 27, 0, 9, 13),
+    sprite("digit_4", 36, 0, 9, 13),
+    sprite("digit_5", 45, 0, 9, 13),
+    sprite("digit_6", 54, 0, 9, 13),
+    sprite("digit_7", 63, 0, 9, 13),
+    sprite("digit_8", 72, 0, 9, 13),
+    sprite("digit_9", 81, 0, 9, 13),
+    sprite("no_minus_sign", 9, 6, 5, 1),
+    sprite("minus_sign", 20, 6, 5, 1),
+];
+
+const POSBAR_SPRITES: &[SpriteRect] = &[
+    sprite("position_background", 0, 0, 248, 10),
+    sprite("position_thumb", 248, 0, 29, 10),
+    sprite("position_thumb_selected", 278, 0, 29, 10),
+];
+
+const PLAYPAUS_SPRITES: &[SpriteRect] = &[
+    sprite("playing_indicator", 0, 0, 9, 9),
+    sprite("paused_indicator", 9, 0, 9, 9),
+    sprite("stopped_indicator", 18, 0, 9, 9),
+    sprite("not_working_indicator", 36, 0, 3, 9),
+    sprite("working_indicator", 39, 0, 3, 9),
+];
+
+const MONOSTER_SPRITES: &[SpriteRect] = &[
+    sprite("stereo", 0, 12, 29, 12),
+    sprite("stereo_selected", 0, 0, 29, 12),
+    sprite("mono", 29, 12, 27, 12),
+    sprite("mono_selected", 29, 0, 27, 12),
+];
+
+const SHUFREP_SPRITES: &[SpriteRect] = &[
+    sprite("repeat_button", 0, 0, 28, 15),
+    sprite("repeat_button_pressed", 0, 15, 28, 15),
+    sprite("repeat_button_selected", 0, 30, 28, 15),
+    sprite("repeat_button_selected_pressed", 0, 45, 28, 15),
+    sprite("shuffle_button", 28, 0, 47, 15),
+    sprite("shuffle_button_pressed", 28, 15, 47, 15),
+    sprite("shuffle_button_selected", 28, 30, 47, 15),
+    sprite("shuffle_button_selected_pressed", 28, 45, 47, 15),
+    sprite("eq_button", 0, 61, 23, 12),
+    sprite("eq_button_selected", 0, 73, 23, 12),
+    sprite("eq_button_pressed", 46, 61, 23, 12),
+    sprite("eq_button_selected_pressed", 46, 73, 23, 12),
+    sprite("playlist_button", 23, 61, 23, 12),
+    sprite("playlist_button_selected", 23, 73, 23, 12),
+    sprite("playlist_button_pressed", 69, 61, 23, 12),
+    sprite("playlist_button_selected_pressed", 69, 73, 23, 12),
+];
+
+const VOLUME_SPRITES: &[SpriteRect] = &[
+    sprite("volume_thumb", 15, 422, 14, 11),
+    sprite("volume_thumb_selected", 0, 422, 14, 11),
+];
+
+const BALANCE_SPRITES: &[SpriteRect] = &[
+    sprite("balance_thumb", 15, 422, 14, 11),
+    sprite("balance_thumb_selected", 0, 422, 14, 11),
+];
+
+/// Sprite tables keyed by the sheet they are cut from
+const SHEETS: &[(&str, &[SpriteRect])] = &[
+    ("main.bmp", MAIN_SPRITES),
+    ("cbuttons.bmp", CBUTTONS_SPRITES),
+    ("titlebar.bmp", TITLEBAR_SPRITES),
+    ("numbers.bmp", NUMBERS_SPRITES),
+    ("posbar.bmp", POSBAR_SPRITES),
+    ("playpaus.bmp", PLAYPAUS_SPRITES),
+    ("monoster.bmp", MONOSTER_SPRITES),
+    ("shufrep.bmp", SHUFREP_SPRITES),
+    ("volume.bmp", VOLUME_SPRITES),
+    ("balance.bmp", BALANCE_SPRITES),
+];
+
+/// Number of stacked slider backgrounds in volume.bmp and balance.bmp
+const SLIDER_FRAMES: u32 = 28;
+
+pub struct SkinSpriteExtractor;
+
+impl SkinSpriteExtractor {
+    /// Slice every known sheet in the skin into named PNG sprites
+    ///
+    /// Sheets missing from the skin are skipped, as are sprites that fall
+    /// outside a sheet (some skins ship undersized bitmaps).
+    pub fn extract(skin: &ParsedSkin) -> Result<HashMap<String, Vec<u8>>, SkinError> {
+        let mut sprites = HashMap::new();
+
+        for (sheet_name, rects) in SHEETS {
+            let Some(sheet) = Self::load_sheet(skin, sheet_name)? else {
+                continue;
+            };
+
+            for rect in rects.iter() {
+                if let Some(png) = Self::slice(&sheet, rect.x, rect.y, rect.width, rect.height)? {
+                    sprites.insert(rect.name.to_string(), png);
+                }
+            }
+
+            // Slider backgrounds are stacked vertically, 15px apart
+            let slider = match *sheet_name {
+                "volume.bmp" => Some(("volume_background", 0, 68)),
+                "balance.bmp" => Some(("balance_background", 9, 38)),
+                _ => None,
+            };
+            if let Some((prefix, x, width)) = slider {
+                for frame in 0..SLIDER_FRAMES {
+                    if let Some(png) = Self::slice(&sheet, x, frame * 15, width, 13)? {
+                        sprites.insert(format!("{}_{}", prefix, frame), png);
+                    }
+                }
+            }
+        }
+
+        Ok(sprites)
+    }
+
+    /// Decode a sheet from the skin assets, matching the file name case-insensitively
+    fn load_sheet(skin: &ParsedSkin, sheet_name: &str) -> Result<Option<DynamicImage>, SkinError> {
+        let data = skin.assets.iter().find(|(name, _)| {
+            let lower = name.to_lowercase();
+            lower == sheet_name || lower.ends_with(&format!("/{}", sheet_name))
+        });
+
+        match data {
+            Some((_, bytes)) => Ok(Some(image::load_from_memory(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Crop a rectangle out of a sheet and encode it as PNG
+    fn slice(sheet: &DynamicImage, x: u32, y: u32, width: u32, height: u32) -> Result<Option<Vec<u8>>, SkinError> {
+        let (sheet_width, sheet_height) = sheet.dimensions();
+        if x + width > sheet_width || y + height > sheet_height {
+            return Ok(None);
+        }
+
+        let mut png = Vec::new();
+        sheet
+            .crop_imm(x, y, width, height)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        Ok(Some(png))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    fn bmp_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 0])
+        });
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Bmp)
+            .unwrap();
+        bytes
+    }
+
+    fn skin_with(assets: &[(&str, Vec<u8>)]) -> ParsedSkin {
+        let mut skin = crate::skin::SkinParser::get_default_skin();
+        for (name, data) in assets {
+            skin.assets.insert(name.to_string(), data.clone());
+        }
+        skin
+    }
+
+    #[test]
+    fn test_extract_cbuttons() {
+        let skin = skin_with(&[("CButtons.bmp", bmp_bytes(136, 36))]);
+        let sprites = SkinSpriteExtractor::extract(&skin).unwrap();
+
+        assert_eq!(sprites.len(), CBUTTONS_SPRITES.len());
+        let play = image::load_from_memory(&sprites["play_button"]).unwrap();
+        assert_eq!(play.dimensions(), (23, 18));
+        // The sprite starts at x=23 in the sheet
+        assert_eq!(play.to_rgb8().get_pixel(0, 0)[0], 23);
+    }
+
+    #[test]
+    fn test_extract_slider_backgrounds() {
+        let skin = skin_with(&[("volume.bmp", bmp_bytes(68, 433))]);
+        let sprites = SkinSpriteExtractor::extract(&skin).unwrap();
+
+        for frame in 0..SLIDER_FRAMES {
+            assert!(sprites.contains_key(&format!("volume_background_{}", frame)));
+        }
+        assert!(sprites.contains_key("volume_thumb"));
+    }
+
+    #[test]
+    fn test_undersized_sheet_skips_out_of_bounds_sprites() {
+        // Only the first two buttons fit
+        let skin = skin_with(&[("cbuttons.bmp", bmp_bytes(46, 36))]);
+        let sprites = SkinSpriteExtractor::extract(&skin).unwrap();
+
+        assert!(sprites.contains_key("previous_button"));
+        assert!(sprites.contains_key("play_button_pressed"));
+        assert!(!sprites.contains_key("pause_button"));
+    }
+
+    #[test]
+    fn test_invalid_sheet_is_an_error() {
+        let skin = skin_with(&[("main.bmp", vec![0, 1, 2, 3])]);
+        assert!(SkinSpriteExtractor::extract(&skin).is_err());
+    }
+
+    #[test]
+    fn test_empty_skin_has_no_sprites() {
+        let skin = crate::skin::SkinParser::get_default_skin();
+        assert!(SkinSpriteExtractor::extract(&skin).unwrap().is_empty());
+    }
+}
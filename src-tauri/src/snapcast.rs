@@ -0,0 +1,156 @@
+// Snapcast output
+// Streams raw PCM to a Snapcast server's TCP source so every Snapcast client
+// plays in sync, room by room. The server needs a source such as
+// `source = tcp://0.0.0.0:4953?name=milk&mode=server` with the default
+// 48000:16:2 sample format. Audio is sent at playback pace; the server does
+// the buffering and clock sync.
+
+use crate::logging::{log_info, log_warn};
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Sample format the Snapcast source expects: 16-bit little-endian stereo
+pub const SAMPLE_RATE: u32 = 48_000;
+pub const CHANNELS: u16 = 2;
+
+/// Audio is sent in chunks this long
+const CHUNK: Duration = Duration::from_millis(20);
+
+/// Wait between connection attempts while the server is unreachable
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// A running stream to a Snapcast server; dropping it disconnects
+pub struct SnapcastStream {
+    playing: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+}
+
+impl SnapcastStream {
+    /// Connect to `host:port` in the background, pulling interleaved samples from
+    /// `fill` while playing
+    ///
+    /// The connection is retried for as long as the stream exists, so the server
+    /// can come and go.
+    pub fn start(host: &str, port: u16, fill: impl FnMut(&mut [f32]) + Send + 'static) -> Self {
+        let playing = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        let addr = format!("{}:{}", host, port);
+        let (thread_playing, thread_running) = (playing.clone(), running.clone());
+        std::thread::spawn(move || stream(&addr, fill, &thread_playing, &thread_running));
+        Self { playing, running }
+    }
+
+    pub fn play(&self) {
+        self.playing.store(true, Ordering::SeqCst);
+    }
+
+    /// Stop sending; the server treats the source as idle
+    pub fn pause(&self) {
+        self.playing.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for SnapcastStream {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+fn stream(addr: &str, mut fill: impl FnMut(&mut [f32]), playing: &AtomicBool, running: &AtomicBool) {
+    let mut reported_failure = false;
+    while running.load(Ordering::SeqCst) {
+        let mut connection = match TcpStream::connect(addr) {
+            Ok(connection) => connection,
+            Err(e) => {
+                if !std::mem::replace(&mut reported_failure, true) {
+                    log_warn("Snapcast", &format!("Cannot reach Snapcast server at {}: {}", addr, e));
+                }
+                std::thread::sleep(RECONNECT_DELAY);
+                continue;
+            }
+        };
+        log_info("Snapcast", &format!("Streaming to {}", addr));
+        reported_failure = false;
+        let _ = connection.set_nodelay(true);
+        if let Err(e) = send_paced(&mut connection, &mut fill, playing, running) {
+            log_warn("Snapcast", &format!("Connection to {} lost: {}", addr, e));
+        }
+    }
+}
+
+/// Write audio at playback pace until the stream is dropped or the connection fails
+fn send_paced(
+    connection: &mut impl Write,
+    fill: &mut impl FnMut(&mut [f32]),
+    playing: &AtomicBool,
+    running: &AtomicBool,
+) -> std::io::Result<()> {
+    let mut samples = Vec::new();
+    let mut bytes = Vec::new();
+    // Playback clock, restarted on every resume
+    let mut clock: Option<(Instant, u64)> = None;
+    while running.load(Ordering::SeqCst) {
+        if !playing.load(Ordering::SeqCst) {
+            clock = None;
+            std::thread::sleep(CHUNK);
+            continue;
+        }
+        let (started, sent_frames) = clock.get_or_insert_with(|| (Instant::now(), 0));
+        let due_frames = (started.elapsed().as_secs_f64() * SAMPLE_RATE as f64) as u64;
+        // Start a little ahead so the first chunk doesn't arrive late
+        let due_frames = due_frames + SAMPLE_RATE as u64 * CHUNK.as_millis() as u64 / 1000;
+        let frames = due_frames.saturating_sub(*sent_frames) as usize;
+        if frames > 0 {
+            samples.clear();
+            samples.resize(frames * CHANNELS as usize, 0.0);
+            fill(&mut samples);
+            encode(&samples, &mut bytes);
+            connection.write_all(&bytes)?;
+            *sent_frames += frames as u64;
+        }
+        std::thread::sleep(CHUNK);
+    }
+    Ok(())
+}
+
+/// Interleaved `f32` to 16-bit little-endian PCM
+fn encode(samples: &[f32], bytes: &mut Vec<u8>) {
+    bytes.clear();
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_encode() {
+        let mut bytes = Vec::new();
+        encode(&[0.0, 1.0, -1.0, 2.0], &mut bytes);
+        let values: Vec<i16> = bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect();
+        assert_eq!(values, [0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+
+    #[test]
+    fn test_streams_while_playing() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let stream = SnapcastStream::start("127.0.0.1", port, |samples| samples.fill(0.5));
+        let (mut server, _) = listener.accept().unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        stream.play();
+        let mut frame = [0u8; 4];
+        server.read_exact(&mut frame).unwrap();
+        assert_eq!(i16::from_le_bytes([frame[0], frame[1]]), (0.5 * i16::MAX as f32).round() as i16);
+        drop(stream);
+    }
+}
@@ -0,0 +1,271 @@
+// Sorting options shared by library and playlist listings
+// Locale-aware, numeric-aware text ordering (see `collation`) and
+// disc/track-number ordering
+
+use crate::collation::Collator;
+use crate::library::Track as LibraryTrack;
+use crate::metadata::MetadataExtractor;
+use crate::playlist::{Playlist, Track as PlaylistTrack};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Field a listing is sorted by
+///
+/// `Created` and `Modified` apply to playlists; track listings fall back to name.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    /// Track title, library file name, or playlist name
+    Name,
+    Artist,
+    Album,
+    Path,
+    /// Disc number, then track number, then name
+    DiscTrack,
+    Created,
+    Modified,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SortOptions {
+    pub field: SortField,
+    #[serde(default)]
+    pub descending: bool,
+    /// Compare digit runs numerically ("Track 2" before "Track 10")
+    #[serde(default = "default_natural")]
+    pub natural: bool,
+    /// BCP 47 locale whose collation rules apply, like "sv-SE"; the system locale when unset
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+fn default_natural() -> bool {
+    true
+}
+
+/// Key prefix for per-view sort options in the KV store
+pub const VIEW_SORT_KEY_PREFIX: &str = "view_sort.";
+
+/// KV store key holding the sort options of a view
+pub fn view_sort_key(view: &str) -> String {
+    format!("{}{}", VIEW_SORT_KEY_PREFIX, view)
+}
+
+/// A comparable value extracted from an item for one sort field
+enum SortValue {
+    Text(String),
+    DiscTrack(Option<u32>, Option<u32>, String),
+    Time(i64),
+}
+
+fn compare_values(a: &SortValue, b: &SortValue, collator: &Collator) -> Ordering {
+    match (a, b) {
+        (SortValue::Text(x), SortValue::Text(y)) => collator.compare(x, y),
+        (SortValue::DiscTrack(ad, at, an), SortValue::DiscTrack(bd, bt, bn)) => {
+            compare_disc_track((*ad, *at), (*bd, *bt)).then_with(|| collator.compare(an, bn))
+        }
+        (SortValue::Time(x), SortValue::Time(y)) => x.cmp(y),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Stable sort using a per-item value for the selected field
+fn sort_with<T>(items: &mut Vec<T>, options: &SortOptions, value: impl Fn(&T) -> SortValue) {
+    let collator = Collator::shared(options.locale.as_deref(), options.natural);
+    let mut keyed: Vec<(SortValue, T)> = items.drain(..).map(|item| (value(&item), item)).collect();
+    keyed.sort_by(|(a, _), (b, _)| {
+        let ordering = compare_values(a, b, &collator);
+        if options.descending { ordering.reverse() } else { ordering }
+    });
+    items.extend(keyed.into_iter().map(|(_, item)| item));
+}
+
+/// Order by disc then track number
+///
+/// A missing disc number counts as disc 1; a missing track number sorts
+/// after the numbered tracks of its disc.
+pub fn compare_disc_track(a: (Option<u32>, Option<u32>), b: (Option<u32>, Option<u32>)) -> Ordering {
+    let disc = |d: Option<u32>| d.unwrap_or(1);
+    let track = |t: Option<u32>| t.unwrap_or(u32::MAX);
+    disc(a.0).cmp(&disc(b.0)).then_with(|| track(a.1).cmp(&track(b.1)))
+}
+
+/// Compare strings treating runs of digits as numbers ("track2" < "track10")
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                        chars.next();
+                    }
+                    digits
+                };
+                let x_digits = take_number(&mut a_chars);
+                let y_digits = take_number(&mut b_chars);
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// Sort scanned library files; tag-based fields read metadata through the extractor cache
+pub fn sort_library_tracks(tracks: &mut Vec<LibraryTrack>, options: &SortOptions, extractor: &MetadataExtractor) {
+    sort_with(tracks, options, |track| {
+        let metadata = || extractor.extract(&track.file_path).ok();
+        match options.field {
+            SortField::Artist => SortValue::Text(metadata().and_then(|m| m.artist).unwrap_or_default()),
+            SortField::Album => SortValue::Text(metadata().and_then(|m| m.album).unwrap_or_default()),
+            SortField::Path => SortValue::Text(track.file_path.to_string_lossy().to_string()),
+            SortField::DiscTrack => {
+                let m = metadata();
+                SortValue::DiscTrack(
+                    m.as_ref().and_then(|m| m.disc_number),
+                    m.as_ref().and_then(|m| m.track_number),
+                    track.file_name.clone(),
+                )
+            }
+            SortField::Name | SortField::Created | SortField::Modified => SortValue::Text(track.file_name.clone()),
+        }
+    });
+}
+
+/// Sort the tracks of a playlist
+pub fn sort_playlist_tracks(tracks: &mut Vec<PlaylistTrack>, options: &SortOptions) {
+    sort_with(tracks, options, |track| match options.field {
+        SortField::Artist => SortValue::Text(track.artist.clone()),
+        SortField::Album => SortValue::Text(track.album.clone()),
+        SortField::Path => SortValue::Text(track.file_path.clone().unwrap_or_default()),
+        SortField::DiscTrack => SortValue::DiscTrack(
+            track.metadata.disc_number,
+            track.metadata.track_number,
+            track.title.clone(),
+        ),
+        SortField::Name | SortField::Created | SortField::Modified => SortValue::Text(track.title.clone()),
+    });
+}
+
+/// Sort a list of playlists
+pub fn sort_playlists(playlists: &mut Vec<Playlist>, options: &SortOptions) {
+    sort_with(playlists, options, |playlist| match options.field {
+        SortField::Created => SortValue::Time(playlist.created_at.timestamp_millis()),
+        SortField::Modified => SortValue::Time(playlist.modified_at.timestamp_millis()),
+        _ => SortValue::Text(playlist.name.clone()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::TrackMetadata;
+
+    fn playlist_track(title: &str, disc: Option<u32>, track: Option<u32>) -> PlaylistTrack {
+        PlaylistTrack {
+            id: title.to_string(),
+            title: title.to_string(),
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            duration: 0.0,
+            file_path: None,
+            source: "local".to_string(),
+            metadata: TrackMetadata {
+                year: None,
+                genre: None,
+                track_number: track,
+                disc_number: disc,
+                album_art: None,
+            },
+        }
+    }
+
+    fn titles(tracks: &[PlaylistTrack]) -> Vec<&str> {
+        tracks.iter().map(|t| t.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("track2", "track10"), Ordering::Less);
+        assert_eq!(natural_cmp("Track 02", "track 2"), Ordering::Equal);
+        assert_eq!(natural_cmp("b", "A"), Ordering::Greater);
+        assert_eq!(natural_cmp("cd1", "cd1 bonus"), Ordering::Less);
+    }
+
+    #[test]
+    fn test_natural_versus_lexical_name_sort() {
+        let mut tracks = vec![
+            playlist_track("Track 10", None, None),
+            playlist_track("Track 2", None, None),
+            playlist_track("Track 1", None, None),
+        ];
+        let mut options = SortOptions { field: SortField::Name, descending: false, natural: true, locale: None };
+        sort_playlist_tracks(&mut tracks, &options);
+        assert_eq!(titles(&tracks), vec!["Track 1", "Track 2", "Track 10"]);
+
+        options.natural = false;
+        sort_playlist_tracks(&mut tracks, &options);
+        assert_eq!(titles(&tracks), vec!["Track 1", "Track 10", "Track 2"]);
+
+        options.descending = true;
+        options.natural = true;
+        sort_playlist_tracks(&mut tracks, &options);
+        assert_eq!(titles(&tracks), vec!["Track 10", "Track 2", "Track 1"]);
+    }
+
+    #[test]
+    fn test_disc_track_sort() {
+        let mut tracks = vec![
+            playlist_track("untagged", None, None),
+            playlist_track("d2t1", Some(2), Some(1)),
+            playlist_track("d1t2", Some(1), Some(2)),
+            playlist_track("d1t1", Some(1), Some(1)),
+        ];
+        let options = SortOptions { field: SortField::DiscTrack, descending: false, natural: true, locale: None };
+        sort_playlist_tracks(&mut tracks, &options);
+        assert_eq!(titles(&tracks), vec!["d1t1", "d1t2", "untagged", "d2t1"]);
+    }
+
+    #[test]
+    fn test_sort_is_stable() {
+        let mut tracks = vec![
+            playlist_track("b", None, None),
+            playlist_track("a", None, None),
+        ];
+        tracks[0].artist = "Same".to_string();
+        tracks[1].artist = "Same".to_string();
+        let options = SortOptions { field: SortField::Artist, descending: false, natural: true, locale: None };
+        sort_playlist_tracks(&mut tracks, &options);
+        assert_eq!(titles(&tracks), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_sort_options_defaults() {
+        let options: SortOptions = serde_json::from_str(r#"{"field":"disc_track"}"#).unwrap();
+        assert_eq!(options.field, SortField::DiscTrack);
+        assert!(options.natural);
+        assert!(!options.descending);
+    }
+}
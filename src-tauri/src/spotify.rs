@@ -1,15 +1,30 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
-use reqwest::Client;
+use crate::api_client::{ApiClient, RateBudget};
 use crate::secure_storage::{SecureStorage, PlatformSecureStorage};
 
 const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/api/token";
 const SPOTIFY_NOW_PLAYING_URL: &str = "https://api.spotify.com/v1/me/player/currently-playing";
+const SPOTIFY_AUDIO_FEATURES_URL: &str = "https://api.spotify.com/v1/audio-features";
+const SPOTIFY_RECOMMENDATIONS_URL: &str = "https://api.spotify.com/v1/recommendations";
+const SPOTIFY_SEARCH_URL: &str = "https://api.spotify.com/v1/search";
+const SPOTIFY_ME_URL: &str = "https://api.spotify.com/v1/me";
+const SPOTIFY_USERS_URL: &str = "https://api.spotify.com/v1/users";
+const SPOTIFY_PLAYLISTS_URL: &str = "https://api.spotify.com/v1/playlists";
 const TOKEN_KEY: &str = "spotify_access_token";
 const REFRESH_TOKEN_KEY: &str = "spotify_refresh_token";
 const TOKEN_EXPIRY_KEY: &str = "spotify_token_expiry";
 
+/// Spotify enforces a rolling 30 second window; stay well inside it while polling
+const RATE_BUDGET: RateBudget = RateBudget::new(10, 3.0);
+
+/// Seeds (tracks, artists and genres together) one recommendations request may use
+pub const MAX_RECOMMENDATION_SEEDS: usize = 5;
+const MAX_RECOMMENDATIONS: u32 = 100;
+/// Track URIs one add or replace request may carry
+const MAX_PLAYLIST_ITEMS_PER_REQUEST: usize = 100;
+
 #[derive(Debug)]
 pub enum ApiError {
     NetworkError(String),
@@ -18,6 +33,15 @@ pub enum ApiError {
     StorageError(String),
     TokenExpired,
     NoActivePlayback,
+    /// Still rate limited after retrying; seconds the service asked us to wait
+    RateLimited(u64),
+    Timeout(String),
+    /// Not sent because the connectivity monitor reports no network
+    Offline,
+    /// Rejected before sending, e.g. a malformed track id or too many seeds
+    InvalidRequest(String),
+    /// The requested resource, e.g. a deleted playlist, does not exist
+    NotFound(String),
 }
 
 impl fmt::Display for ApiError {
@@ -29,6 +53,11 @@ impl fmt::Display for ApiError {
             ApiError::StorageError(e) => write!(f, "Storage error: {}", e),
             ApiError::TokenExpired => write!(f, "Token expired"),
             ApiError::NoActivePlayback => write!(f, "No active playback"),
+            ApiError::RateLimited(secs) => write!(f, "Rate limited, retry after {}s", secs),
+            ApiError::Timeout(e) => write!(f, "Request timed out: {}", e),
+            ApiError::Offline => write!(f, "No network connection"),
+            ApiError::InvalidRequest(e) => write!(f, "Invalid request: {}", e),
+            ApiError::NotFound(e) => write!(f, "Not found: {}", e),
         }
     }
 }
@@ -64,6 +93,176 @@ pub struct TrackMetadata {
     pub progress_ms: Option<u64>,
 }
 
+/// Audio analysis of a Spotify track
+///
+/// Values in 0.0-1.0 except `tempo` (BPM), `loudness` (dB), `key` (pitch
+/// class, -1 when unknown) and `mode` (1 major, 0 minor).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AudioFeatures {
+    pub id: String,
+    pub tempo: f64,
+    pub energy: f64,
+    pub danceability: f64,
+    pub valence: f64,
+    pub acousticness: f64,
+    pub instrumentalness: f64,
+    pub liveness: f64,
+    pub speechiness: f64,
+    pub loudness: f64,
+    pub key: i32,
+    pub mode: i32,
+    pub time_signature: u32,
+    pub duration_ms: u64,
+}
+
+/// Bounds and target for one tunable attribute of a recommendations request
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Tunable {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub target: Option<f64>,
+}
+
+/// Everything but the seed tracks of a recommendations request
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RecommendationParams {
+    /// Tracks to return, 1-100; Spotify's default of 20 when unset
+    pub limit: Option<u32>,
+    pub seed_artists: Vec<String>,
+    pub seed_genres: Vec<String>,
+    /// ISO 3166-1 market to return playable tracks for
+    pub market: Option<String>,
+    pub tempo: Option<Tunable>,
+    pub energy: Option<Tunable>,
+    pub danceability: Option<Tunable>,
+    pub valence: Option<Tunable>,
+    pub acousticness: Option<Tunable>,
+    pub instrumentalness: Option<Tunable>,
+}
+
+impl RecommendationParams {
+    /// Query string pairs for `seed_tracks` plus these parameters
+    fn query(&self, seed_tracks: &[String]) -> Result<Vec<(String, String)>, ApiError> {
+        let seeds = seed_tracks.len() + self.seed_artists.len() + self.seed_genres.len();
+        if seeds == 0 || seeds > MAX_RECOMMENDATION_SEEDS {
+            return Err(ApiError::InvalidRequest(format!(
+                "{} seeds given, 1 to {} allowed",
+                seeds, MAX_RECOMMENDATION_SEEDS
+            )));
+        }
+        let ids = |ids: &[String], kind| ids.iter().map(|id| parse_id(id, kind)).collect::<Result<Vec<_>, _>>();
+        let mut query = Vec::new();
+        for (name, values) in [
+            ("seed_tracks", ids(seed_tracks, "track")?),
+            ("seed_artists", ids(&self.seed_artists, "artist")?),
+            ("seed_genres", self.seed_genres.clone()),
+        ] {
+            if !values.is_empty() {
+                query.push((name.to_string(), values.join(",")));
+            }
+        }
+        if let Some(limit) = self.limit {
+            query.push(("limit".to_string(), limit.clamp(1, MAX_RECOMMENDATIONS).to_string()));
+        }
+        if let Some(market) = &self.market {
+            query.push(("market".to_string(), market.clone()));
+        }
+        for (name, tunable) in [
+            ("tempo", self.tempo),
+            ("energy", self.energy),
+            ("danceability", self.danceability),
+            ("valence", self.valence),
+            ("acousticness", self.acousticness),
+            ("instrumentalness", self.instrumentalness),
+        ] {
+            let Some(tunable) = tunable else { continue };
+            for (prefix, value) in [("min", tunable.min), ("max", tunable.max), ("target", tunable.target)] {
+                if let Some(value) = value {
+                    query.push((format!("{}_{}", prefix, name), value.to_string()));
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
+/// A track suggested by `get_recommendations`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RecommendedTrack {
+    pub id: String,
+    pub uri: String,
+    pub title: String,
+    /// All credited artists, first one leading
+    pub artists: Vec<String>,
+    pub album: String,
+    pub duration_ms: u64,
+    /// 30 second MP3 preview, when Spotify offers one
+    pub preview_url: Option<String>,
+}
+
+/// Bare Spotify id from an id, a `spotify:<kind>:` URI or an open.spotify.com link
+fn parse_id(input: &str, kind: &str) -> Result<String, ApiError> {
+    let input = input.trim();
+    let uri_prefix = format!("spotify:{}:", kind);
+    let link_marker = format!("open.spotify.com/{}/", kind);
+    let id = if let Some(id) = input.strip_prefix(&uri_prefix) {
+        id
+    } else if let Some(start) = input.find(&link_marker) {
+        let rest = &input[start + link_marker.len()..];
+        rest.split(['?', '#', '/']).next().unwrap_or_default()
+    } else {
+        input
+    };
+    // Ids are base62; anything else would end up in the request URL
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ApiError::InvalidRequest(format!("Not a Spotify {} id: {}", kind, input)));
+    }
+    Ok(id.to_string())
+}
+
+fn parse_recommendations(json: &serde_json::Value) -> Result<Vec<RecommendedTrack>, ApiError> {
+    let tracks = json.get("tracks")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| ApiError::ParseError("Missing 'tracks' field".to_string()))?;
+    parse_tracks(tracks)
+}
+
+fn parse_search_results(json: &serde_json::Value) -> Result<Vec<RecommendedTrack>, ApiError> {
+    let items = json.get("tracks")
+        .and_then(|v| v.get("items"))
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| ApiError::ParseError("Missing 'tracks.items' field".to_string()))?;
+    // Unavailable tracks come back as null items
+    let items: Vec<serde_json::Value> = items.iter().filter(|item| !item.is_null()).cloned().collect();
+    parse_tracks(&items)
+}
+
+fn parse_tracks(tracks: &[serde_json::Value]) -> Result<Vec<RecommendedTrack>, ApiError> {
+    let text = |value: &serde_json::Value, key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    tracks
+        .iter()
+        .map(|track| {
+            let missing = |field: &str| ApiError::ParseError(format!("Missing track {}", field));
+            Ok(RecommendedTrack {
+                id: text(track, "id").ok_or_else(|| missing("id"))?,
+                uri: text(track, "uri").ok_or_else(|| missing("uri"))?,
+                title: text(track, "name").ok_or_else(|| missing("name"))?,
+                artists: track.get("artists")
+                    .and_then(|v| v.as_array())
+                    .map(|artists| artists.iter().filter_map(|a| text(a, "name")).collect())
+                    .unwrap_or_default(),
+                album: track.get("album").and_then(|a| text(a, "name")).unwrap_or_default(),
+                duration_ms: track.get("duration_ms")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| missing("duration"))?,
+                preview_url: text(track, "preview_url"),
+            })
+        })
+        .collect()
+}
+
 /// Trait for streaming service integration
 pub trait StreamingService {
     /// Authenticate with the service using OAuth 2.0
@@ -78,15 +277,20 @@ pub trait StreamingService {
 
 /// Spotify API bridge implementation
 pub struct SpotifyBridge {
-    client: Client,
-    storage: PlatformSecureStorage,
+    client: ApiClient,
+    storage: Box<dyn SecureStorage + Send + Sync>,
 }
 
 impl SpotifyBridge {
     pub fn new() -> Self {
+        Self::with_storage(PlatformSecureStorage::new())
+    }
+
+    /// Create a bridge that keeps its tokens in `storage` instead of the OS keyring
+    pub fn with_storage(storage: impl SecureStorage + Send + Sync + 'static) -> Self {
         SpotifyBridge {
-            client: Client::new(),
-            storage: PlatformSecureStorage::new(),
+            client: ApiClient::new("spotify", RATE_BUDGET),
+            storage: Box::new(storage),
         }
     }
 
@@ -182,6 +386,105 @@ impl SpotifyBridge {
     pub async fn ensure_valid_token(&self, credentials: Option<Credentials>) -> Result<String, ApiError> {
         self.get_valid_token(credentials).await
     }
+
+    /// GET a Web API endpoint with the stored access token and parse the JSON body
+    async fn get_json(&self, url: &str, query: &[(String, String)]) -> Result<serde_json::Value, ApiError> {
+        self.send_json(self.client.get(url).query(query)).await
+    }
+
+    /// Send a Web API request with the stored access token and parse the JSON body
+    async fn send_json(&self, request: reqwest::RequestBuilder) -> Result<serde_json::Value, ApiError> {
+        let access_token = self.get_access_token()?
+            .ok_or(ApiError::AuthenticationError("No access token found".to_string()))?;
+
+        let response = self.client.send(request.bearer_auth(&access_token)).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+
+            match status.as_u16() {
+                401 => return Err(ApiError::TokenExpired),
+                // Tokens granted before playlist export lack the playlist scopes
+                403 => return Err(ApiError::AuthenticationError(format!("Permission denied: {}", error_text))),
+                404 => return Err(ApiError::NotFound(error_text)),
+                _ => {}
+            }
+
+            return Err(ApiError::NetworkError(format!("Status {}: {}", status, error_text)));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::ParseError(e.to_string()))
+    }
+
+    /// Tempo, energy, danceability and the other audio features of a track
+    ///
+    /// `track_id` may be a bare id, a `spotify:track:` URI or a track link.
+    pub async fn get_audio_features(&self, track_id: &str) -> Result<AudioFeatures, ApiError> {
+        let id = parse_id(track_id, "track")?;
+        let json = self.get_json(&format!("{}/{}", SPOTIFY_AUDIO_FEATURES_URL, id), &[]).await?;
+        serde_json::from_value(json).map_err(|e| ApiError::ParseError(e.to_string()))
+    }
+
+    /// Tracks similar to the seeds, steered by the tunable attributes in `params`
+    ///
+    /// Seed tracks, artists and genres together must number 1 to
+    /// `MAX_RECOMMENDATION_SEEDS`.
+    pub async fn get_recommendations(&self, seed_tracks: &[String], params: &RecommendationParams) -> Result<Vec<RecommendedTrack>, ApiError> {
+        let query = params.query(seed_tracks)?;
+        let json = self.get_json(SPOTIFY_RECOMMENDATIONS_URL, &query).await?;
+        parse_recommendations(&json)
+    }
+
+    /// Catalog tracks matching a search query such as `track:Title artist:Name`
+    pub async fn search_tracks(&self, query: &str, limit: u32) -> Result<Vec<RecommendedTrack>, ApiError> {
+        let query = [
+            ("q".to_string(), query.to_string()),
+            ("type".to_string(), "track".to_string()),
+            ("limit".to_string(), limit.clamp(1, 50).to_string()),
+        ];
+        let json = self.get_json(SPOTIFY_SEARCH_URL, &query).await?;
+        parse_search_results(&json)
+    }
+
+    /// Create a private playlist for the signed-in user and return its id
+    pub async fn create_playlist(&self, name: &str, description: &str) -> Result<String, ApiError> {
+        let me = self.get_json(SPOTIFY_ME_URL, &[]).await?;
+        let user_id = me.get("id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| ApiError::ParseError("Missing user id".to_string()))?;
+        let body = serde_json::json!({ "name": name, "description": description, "public": false });
+        // Older user ids aren't base62, so the id is escaped rather than validated
+        let mut url = reqwest::Url::parse(SPOTIFY_USERS_URL).map_err(|e| ApiError::InvalidRequest(e.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|_| ApiError::InvalidRequest("Bad users URL".to_string()))?
+            .push(user_id)
+            .push("playlists");
+        let request = self.client.post(url).json(&body);
+        let json = self.send_json(request).await?;
+        json.get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| ApiError::ParseError("Missing playlist id".to_string()))
+    }
+
+    /// Replace the items of a playlist with `uris`, in order
+    ///
+    /// The first batch replaces the contents and later batches are appended,
+    /// since one request carries at most `MAX_PLAYLIST_ITEMS_PER_REQUEST` items.
+    pub async fn replace_playlist_tracks(&self, playlist_id: &str, uris: &[String]) -> Result<(), ApiError> {
+        let url = format!("{}/{}/tracks", SPOTIFY_PLAYLISTS_URL, parse_id(playlist_id, "playlist")?);
+        let mut batches = uris.chunks(MAX_PLAYLIST_ITEMS_PER_REQUEST);
+        let first = batches.next().unwrap_or_default();
+        self.send_json(self.client.put(&url).json(&serde_json::json!({ "uris": first }))).await?;
+        for batch in batches {
+            self.send_json(self.client.post(&url).json(&serde_json::json!({ "uris": batch }))).await?;
+        }
+        Ok(())
+    }
 }
 
 impl StreamingService for SpotifyBridge {
@@ -194,12 +497,10 @@ impl StreamingService for SpotifyBridge {
             ("client_secret", &credentials.client_secret),
         ];
 
-        let response = self.client
+        let request = self.client
             .post(SPOTIFY_AUTH_URL)
-            .form(&params)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+            .form(&params);
+        let response = self.client.send(request).await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
@@ -221,12 +522,10 @@ impl StreamingService for SpotifyBridge {
         let access_token = self.get_access_token()?
             .ok_or(ApiError::AuthenticationError("No access token found".to_string()))?;
 
-        let response = self.client
+        let request = self.client
             .get(SPOTIFY_NOW_PLAYING_URL)
-            .bearer_auth(&access_token)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+            .bearer_auth(&access_token);
+        let response = self.client.send(request).await?;
 
         // 204 No Content means no active playback
         if response.status() == 204 {
@@ -307,12 +606,10 @@ impl StreamingService for SpotifyBridge {
             ("client_secret", &credentials.client_secret),
         ];
 
-        let response = self.client
+        let request = self.client
             .post(SPOTIFY_AUTH_URL)
-            .form(&params)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+            .form(&params);
+        let response = self.client.send(request).await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
@@ -339,6 +636,7 @@ impl StreamingService for SpotifyBridge {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::secure_storage::InMemorySecureStorage;
 
     #[test]
     fn test_spotify_bridge_creation() {
@@ -346,6 +644,31 @@ mod tests {
         assert!(bridge.client.get("https://example.com").build().is_ok());
     }
 
+    fn token(expires_in: u64) -> Token {
+        Token {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in,
+            refresh_token: Some("refresh".to_string()),
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn test_token_storage_with_injected_storage() {
+        let bridge = SpotifyBridge::with_storage(InMemorySecureStorage::new());
+        assert!(bridge.check_token_expired().unwrap());
+
+        bridge.store_token(&token(3600)).unwrap();
+        assert!(!bridge.check_token_expired().unwrap());
+        assert_eq!(bridge.get_access_token().unwrap(), Some("access".to_string()));
+        assert_eq!(bridge.get_refresh_token().unwrap(), Some("refresh".to_string()));
+
+        // Within the 60 second safety margin counts as expired
+        bridge.store_token(&token(30)).unwrap();
+        assert!(bridge.check_token_expired().unwrap());
+    }
+
     #[test]
     fn test_track_metadata_equality() {
         let metadata1 = TrackMetadata {
@@ -368,6 +691,62 @@ mod tests {
 
         assert_eq!(metadata1, metadata2);
     }
+
+    #[test]
+    fn test_recommendation_query() {
+        let params = RecommendationParams {
+            limit: Some(500),
+            seed_genres: vec!["house".to_string()],
+            energy: Some(Tunable { min: Some(0.6), max: None, target: Some(0.8) }),
+            ..Default::default()
+        };
+        let seeds = vec![
+            "spotify:track:4uLU6hMCjMI75M1A2tKUQC".to_string(),
+            "https://open.spotify.com/track/7GhIk7Il098yCjg4BQjzvb?si=abc".to_string(),
+        ];
+        let query = params.query(&seeds).unwrap();
+        let pairs: Vec<(&str, &str)> = query.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        assert_eq!(
+            pairs,
+            [
+                ("seed_tracks", "4uLU6hMCjMI75M1A2tKUQC,7GhIk7Il098yCjg4BQjzvb"),
+                ("seed_genres", "house"),
+                ("limit", "100"),
+                ("min_energy", "0.6"),
+                ("target_energy", "0.8"),
+            ]
+        );
+
+        assert!(params.query(&[]).is_ok());
+        assert!(matches!(RecommendationParams::default().query(&[]), Err(ApiError::InvalidRequest(_))));
+        let too_many = vec!["4uLU6hMCjMI75M1A2tKUQC".to_string(); MAX_RECOMMENDATION_SEEDS];
+        assert!(matches!(params.query(&too_many), Err(ApiError::InvalidRequest(_))));
+        assert!(matches!(parse_id("../me/player", "track"), Err(ApiError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn test_parse_recommendations() {
+        let json = serde_json::json!({
+            "seeds": [],
+            "tracks": [{
+                "id": "4uLU6hMCjMI75M1A2tKUQC",
+                "uri": "spotify:track:4uLU6hMCjMI75M1A2tKUQC",
+                "name": "Never Gonna Give You Up",
+                "artists": [{ "name": "Rick Astley" }],
+                "album": { "name": "Whenever You Need Somebody" },
+                "duration_ms": 213573,
+                "preview_url": null
+            }]
+        });
+        let tracks = parse_recommendations(&json).unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].artists, ["Rick Astley"]);
+        assert_eq!(tracks[0].preview_url, None);
+        assert!(parse_recommendations(&serde_json::json!({})).is_err());
+
+        let search = serde_json::json!({ "tracks": { "items": [json["tracks"][0].clone(), null] } });
+        assert_eq!(parse_search_results(&search).unwrap(), tracks);
+    }
 }
 
 #[cfg(test)]
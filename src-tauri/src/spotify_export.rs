@@ -0,0 +1,140 @@
+// Playlist export to Spotify
+// Local playlist tracks are matched to catalog tracks through search and
+// scored by `matching`. Matches go into a Spotify playlist that is remembered
+// per local playlist, so exporting again updates it instead of creating
+// another one.
+
+use crate::kv_store::KvStore;
+use crate::matching::{self, MatchQuery};
+use crate::playlist::{Playlist, Track};
+use crate::spotify::{ApiError, SpotifyBridge};
+use serde::Serialize;
+
+/// Key prefix mapping local playlist ids to exported Spotify playlist ids
+pub const EXPORT_KEY_PREFIX: &str = "spotify_export.";
+
+const SEARCH_LIMIT: u32 = 5;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ExportedTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    pub spotify_uri: String,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UnmatchedTrack {
+    pub track_id: String,
+    pub title: String,
+    pub artist: String,
+    /// Score of the closest candidate, when the search found any
+    pub best_confidence: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SpotifyExportReport {
+    pub spotify_playlist_id: String,
+    pub url: String,
+    /// False when an earlier export was updated
+    pub created: bool,
+    pub matched: Vec<ExportedTrack>,
+    pub unmatched: Vec<UnmatchedTrack>,
+}
+
+fn export_key(playlist_id: &str) -> String {
+    format!("{}{}", EXPORT_KEY_PREFIX, playlist_id)
+}
+
+/// Match the playlist's tracks and write them to its Spotify playlist
+///
+/// Tracks that already came from Spotify keep their id. A remembered Spotify
+/// playlist that was deleted in the meantime is created again.
+pub async fn export_playlist(
+    bridge: &SpotifyBridge,
+    store: &KvStore,
+    playlist: &Playlist,
+) -> Result<SpotifyExportReport, ApiError> {
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+    for track in &playlist.tracks {
+        if track.source == "spotify" && track.file_path.is_none() {
+            if let Some(uri) = spotify_uri(&track.id) {
+                matched.push(exported(track, uri, 1.0));
+                continue;
+            }
+        }
+        let query = MatchQuery::from(track);
+        let candidates = match matching::spotify_query(&query) {
+            Some(q) => bridge.search_tracks(&q, SEARCH_LIMIT).await?.into_iter().map(Into::into).collect(),
+            None => Vec::new(),
+        };
+        match matching::best_match(&query, candidates) {
+            Some(best) if best.confident => matched.push(exported(track, best.candidate.id, best.confidence)),
+            best => unmatched.push(UnmatchedTrack {
+                track_id: track.id.clone(),
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                best_confidence: best.map(|best| best.confidence),
+            }),
+        }
+    }
+
+    let uris: Vec<String> = matched.iter().map(|t| t.spotify_uri.clone()).collect();
+    let existing: Option<String> = store.get(&export_key(&playlist.id));
+    let (spotify_playlist_id, created) = match existing {
+        Some(id) => match bridge.replace_playlist_tracks(&id, &uris).await {
+            Ok(()) => (id, false),
+            Err(ApiError::NotFound(_)) => (create(bridge, playlist, &uris).await?, true),
+            Err(e) => return Err(e),
+        },
+        None => (create(bridge, playlist, &uris).await?, true),
+    };
+    store
+        .set(&export_key(&playlist.id), &spotify_playlist_id)
+        .map_err(|e| ApiError::StorageError(e.to_string()))?;
+
+    Ok(SpotifyExportReport {
+        url: format!("https://open.spotify.com/playlist/{}", spotify_playlist_id),
+        spotify_playlist_id,
+        created,
+        matched,
+        unmatched,
+    })
+}
+
+async fn create(bridge: &SpotifyBridge, playlist: &Playlist, uris: &[String]) -> Result<String, ApiError> {
+    let id = bridge.create_playlist(&playlist.name, "Exported from milk").await?;
+    bridge.replace_playlist_tracks(&id, uris).await?;
+    Ok(id)
+}
+
+fn exported(track: &Track, spotify_uri: String, confidence: f32) -> ExportedTrack {
+    ExportedTrack {
+        track_id: track.id.clone(),
+        title: track.title.clone(),
+        artist: track.artist.clone(),
+        spotify_uri,
+        confidence,
+    }
+}
+
+/// `spotify:track:` URI for a bare track id or URI
+fn spotify_uri(id: &str) -> Option<String> {
+    let id = id.trim();
+    let id = id.strip_prefix("spotify:track:").unwrap_or(id);
+    (id.len() == 22 && id.chars().all(|c| c.is_ascii_alphanumeric())).then(|| format!("spotify:track:{}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spotify_uri() {
+        assert_eq!(spotify_uri("spotify:track:4uLU6hMCjMI75M1A2tKUQC").unwrap(), "spotify:track:4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(spotify_uri("4uLU6hMCjMI75M1A2tKUQC").unwrap(), "spotify:track:4uLU6hMCjMI75M1A2tKUQC");
+        assert_eq!(spotify_uri("track_1a2b"), None);
+    }
+}
@@ -1,4 +1,13 @@
-use crate::error::MilkError;
+#[cfg(target_os = "windows")]
+use crate::audio_dsp::FFT_SIZE;
+#[cfg(target_os = "windows")]
+use crate::audio_transport::downsample;
+#[cfg(target_os = "windows")]
+use crate::visualizer_feed::{self, VisualizerFeed};
+use crate::audio_transport::{CaptureBufferRead, CaptureOptions, CaptureRingBuffer, CaptureTransport};
+use crate::error::{CommandError, MilkError};
+use crate::media_session::PlaybackStatus;
+use crate::now_playing::NowPlayingSource;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::sync::{Arc, Mutex};
 use tauri::{AppHandle, Emitter};
@@ -7,7 +16,49 @@ use tauri::{AppHandle, Emitter};
 pub struct SystemAudioCapture {
     #[cfg(target_os = "windows")]
     stream: Option<cpal::Stream>,
+    /// Device the active stream was opened on (None = default output)
+    #[cfg(target_os = "windows")]
+    device_id: Option<String>,
     is_active: Arc<Mutex<bool>>,
+    /// Read by the capture callback on every block, so changes apply immediately
+    options: Arc<Mutex<CaptureOptions>>,
+    ring: Arc<Mutex<CaptureRingBuffer>>,
+}
+
+/// An output device that can feed the visualizer
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AudioCaptureDevice {
+    /// Identifier to pass to `start_system_audio_capture` (the device name; cpal has no stable ids)
+    pub id: String,
+    pub name: String,
+    pub is_default: bool,
+}
+
+/// List output devices available for loopback capture
+pub fn list_capture_devices() -> std::result::Result<Vec<AudioCaptureDevice>, MilkError> {
+    #[cfg(target_os = "windows")]
+    {
+        let host = cpal::default_host();
+        let default_name = host.default_output_device().and_then(|d| d.name().ok());
+        let devices = host
+            .output_devices()
+            .map_err(|e| MilkError::SystemAudio(format!("Failed to list output devices: {}", e)))?;
+
+        Ok(devices
+            .filter_map(|device| device.name().ok())
+            .map(|name| AudioCaptureDevice {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                id: name.clone(),
+                name,
+            })
+            .collect())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        // Loopback capture is Windows-only, so there is nothing to choose from
+        Ok(Vec::new())
+    }
 }
 
 impl SystemAudioCapture {
@@ -15,37 +66,70 @@ impl SystemAudioCapture {
         Self {
             #[cfg(target_os = "windows")]
             stream: None,
+            #[cfg(target_os = "windows")]
+            device_id: None,
             is_active: Arc::new(Mutex::new(false)),
+            options: Arc::new(Mutex::new(CaptureOptions::default())),
+            ring: Arc::new(Mutex::new(CaptureRingBuffer::new(CaptureOptions::default().ring_capacity))),
+        }
+    }
+
+    pub fn options(&self) -> CaptureOptions {
+        self.options.lock().unwrap().clone()
+    }
+
+    /// Update delivery options; takes effect on the next captured block
+    pub fn set_options(&self, options: CaptureOptions) -> std::result::Result<(), MilkError> {
+        options.validate().map_err(MilkError::SystemAudio)?;
+        self.ring.lock().unwrap().resize(options.ring_capacity);
+        *self.options.lock().unwrap() = options;
+        Ok(())
+    }
+
+    /// Samples captured after `cursor` (ring buffer transport only)
+    pub fn read_buffer(&self, cursor: u64) -> std::result::Result<CaptureBufferRead, MilkError> {
+        if self.options.lock().unwrap().transport != CaptureTransport::RingBuffer {
+            return Err(MilkError::SystemAudio("Ring buffer transport is not enabled".to_string()));
         }
+        Ok(self.ring.lock().unwrap().read_since(cursor))
     }
 
     /// Start capturing system audio (loopback recording on Windows)
-    pub fn start(&mut self, app_handle: AppHandle) -> std::result::Result<(), MilkError> {
+    ///
+    /// `device_id` selects an output device from `list_capture_devices`; None uses
+    /// the default output. Requesting a different device while active switches to it.
+    pub fn start(&mut self, app_handle: AppHandle, device_id: Option<&str>) -> std::result::Result<(), MilkError> {
         #[cfg(target_os = "windows")]
         {
             // Check if already active
-            {
-                let is_active = self.is_active.lock().unwrap();
-                if *is_active {
+            if self.is_active() {
+                if self.device_id.as_deref() == device_id {
                     return Ok(());
                 }
+                self.stop()?;
             }
 
             // Get the default host
             let host = cpal::default_host();
 
-            // Try to get loopback device (Windows WASAPI)
-            let device = {
-                // On Windows, we need to use the loopback device
-                // This captures all system audio output
-                host.default_output_device()
-                    .ok_or_else(|| MilkError::SystemAudio("No output device found".to_string()))?
+            // On Windows, opening an input stream on an output device captures
+            // everything played through it (WASAPI loopback)
+            let device = match device_id {
+                Some(id) => host
+                    .output_devices()
+                    .map_err(|e| MilkError::SystemAudio(format!("Failed to list output devices: {}", e)))?
+                    .find(|d| d.name().map(|name| name == id).unwrap_or(false))
+                    .ok_or_else(|| MilkError::SystemAudio(format!("Audio device not found: {}", id)))?,
+                None => host
+                    .default_output_device()
+                    .ok_or(MilkError::AudioDeviceUnavailable)?,
             };
 
-            // Get the default config
+            // Loopback streams use the render device's mix format; asking an
+            // output device for an input config fails on WASAPI
             let config = device
-                .default_input_config()
-                .map_err(|e| MilkError::SystemAudio(format!("Failed to get default config: {}", e)))?;
+                .default_output_config()
+                .map_err(|e| MilkError::SystemAudio(format!("Failed to get output device format: {}", e)))?;
 
             let is_active = Arc::clone(&self.is_active);
 
@@ -67,7 +151,9 @@ impl SystemAudioCapture {
                 .map_err(|e| MilkError::SystemAudio(format!("Failed to start stream: {}", e)))?;
 
             self.stream = Some(stream);
+            self.device_id = device_id.map(str::to_string);
             *self.is_active.lock().unwrap() = true;
+            visualizer_feed::set_capture_active(true);
 
             Ok(())
         }
@@ -75,6 +161,7 @@ impl SystemAudioCapture {
         #[cfg(not(target_os = "windows"))]
         {
             // System audio capture is only supported on Windows
+            let _ = (app_handle, device_id);
             Err(MilkError::SystemAudio(
                 "System audio capture is only supported on Windows".to_string(),
             ))
@@ -88,8 +175,10 @@ impl SystemAudioCapture {
             if let Some(stream) = self.stream.take() {
                 drop(stream);
             }
+            self.device_id = None;
         }
         *self.is_active.lock().unwrap() = false;
+        crate::visualizer_feed::set_capture_active(false);
         Ok(())
     }
 
@@ -115,8 +204,13 @@ impl SystemAudioCapture {
         let sample_rate = config.sample_rate.0;
 
         // Buffer to accumulate samples for FFT
-        let buffer_size = 2048; // Match FFT size in visualizer
+        let buffer_size = FFT_SIZE;
         let buffer = Arc::new(Mutex::new(Vec::with_capacity(buffer_size)));
+        let mut feed = VisualizerFeed::new(sample_rate);
+        let error_handle = app_handle.clone();
+        let error_active = Arc::clone(&is_active);
+        let options = Arc::clone(&self.options);
+        let ring = Arc::clone(&self.ring);
 
         let stream = device
             .build_input_stream(
@@ -143,21 +237,47 @@ impl SystemAudioCapture {
                         // When buffer is full, send to frontend
                         if buffer.len() >= buffer_size {
                             let audio_data: Vec<f32> = buffer.drain(..).collect();
-                            
+                            let options = options.lock().unwrap().clone();
+
+                            // Spectrum, bands and beats are computed here so the frontend can skip its own DSP
+                            let frame = feed.analyze(&audio_data, options.max_fps);
+
+                            // Analysis runs at full rate; only delivered samples are downsampled
+                            let samples = downsample(&audio_data, options.downsample_factor);
+                            let delivered_rate = sample_rate / options.downsample_factor;
+                            if options.transport == CaptureTransport::RingBuffer {
+                                ring.lock().unwrap().push(&samples, delivered_rate);
+                            }
+
+                            let Some(frame) = frame else {
+                                continue;
+                            };
+                            visualizer_feed::publish(&frame, &audio_data);
+                            let _ = app_handle.emit("visualizer-frame", frame);
+
                             // Emit event to frontend with audio data
-                            let _ = app_handle.emit("system-audio-data", SystemAudioData {
-                                samples: audio_data,
-                                sample_rate,
-                            });
+                            if options.transport == CaptureTransport::Events {
+                                let _ = app_handle.emit("system-audio-data", SystemAudioData {
+                                    samples,
+                                    sample_rate: delivered_rate,
+                                });
+                            }
                         }
                     }
                 },
                 move |err| {
-                    eprintln!("System audio capture error: {}", err);
+                    let message = stream_error_message(&err);
+                    crate::logging::log_error("SystemAudio", &format!("Capture error: {}", message));
+                    // A lost device ends the capture; let the frontend fall back
+                    if matches!(err, cpal::StreamError::DeviceNotAvailable) {
+                        *error_active.lock().unwrap() = false;
+                        visualizer_feed::set_capture_active(false);
+                    }
+                    let _ = error_handle.emit("system-audio-error", SystemAudioError { message });
                 },
                 None,
             )
-            .map_err(|e| MilkError::SystemAudio(format!("Failed to build stream: {}", e)))?;
+            .map_err(|e| MilkError::SystemAudio(build_error_message(&e)))?;
 
         Ok(stream)
     }
@@ -170,32 +290,113 @@ pub struct SystemAudioData {
     pub sample_rate: u32,
 }
 
-/// Tauri command to start system audio capture
+/// Capture error payload sent to frontend
+#[cfg(target_os = "windows")]
+#[derive(Clone, serde::Serialize)]
+pub struct SystemAudioError {
+    pub message: String,
+}
+
+/// WASAPI AUDCLNT_E_DEVICE_IN_USE: another app holds the device in exclusive mode
+#[cfg(target_os = "windows")]
+const AUDCLNT_E_DEVICE_IN_USE: &str = "0x8889000A";
+
+/// Explain why a loopback stream could not be opened
+#[cfg(target_os = "windows")]
+fn build_error_message(err: &cpal::BuildStreamError) -> String {
+    match err {
+        cpal::BuildStreamError::DeviceNotAvailable => {
+            "The audio device is no longer available. It may have been unplugged or disabled.".to_string()
+        }
+        cpal::BuildStreamError::StreamConfigNotSupported => {
+            "The audio device's output format cannot be captured.".to_string()
+        }
+        cpal::BuildStreamError::BackendSpecific { err } if err.description.contains(AUDCLNT_E_DEVICE_IN_USE) => {
+            "Another application is using the audio device in exclusive mode. Disable exclusive mode for that app or pick a different device.".to_string()
+        }
+        other => format!("Failed to open loopback capture: {}", other),
+    }
+}
+
+/// Explain an error reported while a loopback stream is running
+#[cfg(target_os = "windows")]
+fn stream_error_message(err: &cpal::StreamError) -> String {
+    match err {
+        cpal::StreamError::DeviceNotAvailable => {
+            "The audio device was disconnected. System audio capture has stopped.".to_string()
+        }
+        cpal::StreamError::BackendSpecific { err } if err.description.contains(AUDCLNT_E_DEVICE_IN_USE) => {
+            "Another application took exclusive control of the audio device.".to_string()
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Tauri command to list devices that can be captured
+#[tauri::command]
+pub async fn list_audio_capture_devices() -> std::result::Result<Vec<AudioCaptureDevice>, CommandError> {
+    list_capture_devices().map_err(|e| e.report())
+}
+
+/// Tauri command to start system audio capture, optionally on a specific device
 #[tauri::command]
 pub async fn start_system_audio_capture(
     app_handle: AppHandle,
     state: tauri::State<'_, SystemAudioCaptureState>,
-) -> std::result::Result<(), String> {
+    device_id: Option<String>,
+) -> std::result::Result<(), CommandError> {
     let mut capture = state.0.lock().unwrap();
-    capture.start(app_handle).map_err(|e| e.to_string())?;
+    capture.start(app_handle.clone(), device_id.as_deref()).map_err(|e| e.report())?;
+    crate::report_now_playing(Some(&app_handle), NowPlayingSource::SystemAudio, None, PlaybackStatus::Playing, None);
     Ok(())
 }
 
 /// Tauri command to stop system audio capture
 #[tauri::command]
 pub async fn stop_system_audio_capture(
+    app_handle: AppHandle,
     state: tauri::State<'_, SystemAudioCaptureState>,
-) -> std::result::Result<(), String> {
+) -> std::result::Result<(), CommandError> {
     let mut capture = state.0.lock().unwrap();
-    capture.stop().map_err(|e| e.to_string())?;
+    capture.stop().map_err(|e| e.report())?;
+    crate::report_now_playing(Some(&app_handle), NowPlayingSource::SystemAudio, None, PlaybackStatus::Stopped, None);
     Ok(())
 }
 
+/// Tauri command to tune capture frame rate, downsampling and transport
+#[tauri::command]
+pub async fn set_capture_options(
+    state: tauri::State<'_, SystemAudioCaptureState>,
+    options: CaptureOptions,
+) -> std::result::Result<(), CommandError> {
+    let capture = state.0.lock().unwrap();
+    capture.set_options(options).map_err(|e| e.report())
+}
+
+/// Tauri command to get the current capture options
+#[tauri::command]
+pub async fn get_capture_options(
+    state: tauri::State<'_, SystemAudioCaptureState>,
+) -> std::result::Result<CaptureOptions, CommandError> {
+    let capture = state.0.lock().unwrap();
+    Ok(capture.options())
+}
+
+/// Tauri command to read samples from the capture ring buffer
+#[tauri::command]
+pub async fn read_capture_buffer(
+    state: tauri::State<'_, SystemAudioCaptureState>,
+    cursor: u64,
+) -> std::result::Result<CaptureBufferRead, CommandError> {
+    let capture = state.0.lock().unwrap();
+    capture.read_buffer(cursor).map_err(|e| e.report())
+}
+
 /// Tauri command to check if system audio capture is active
 #[tauri::command]
 pub async fn is_system_audio_capture_active(
     state: tauri::State<'_, SystemAudioCaptureState>,
-) -> std::result::Result<bool, String> {
+) -> std::result::Result<bool, CommandError> {
     let capture = state.0.lock().unwrap();
     Ok(capture.is_active())
 }
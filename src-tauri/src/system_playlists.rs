@@ -0,0 +1,168 @@
+// System-managed playlists
+// "Recently Added", "Recently Played" and "Top 25 Most Played" are generated
+// from the library index and play history rather than saved, and are listed
+// alongside user playlists with `system: true`. They can't be edited.
+
+use crate::kv_store::{KvError, KvStore};
+use crate::metadata::MetadataExtractor;
+use crate::play_history::PlayStats;
+use crate::playlist::{Playlist, Track};
+use crate::queue_import::track_for_file;
+use crate::search::SearchIndex;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+/// Ids of system playlists start with this
+pub const SYSTEM_ID_PREFIX: &str = "system:";
+
+const RECENTLY_ADDED: (&str, &str) = ("system:recently-added", "Recently Added");
+const RECENTLY_PLAYED: (&str, &str) = ("system:recently-played", "Recently Played");
+const MOST_PLAYED: (&str, &str) = ("system:most-played", "Top 25 Most Played");
+
+/// Key prefix for dates added carried over from another player, in Unix milliseconds
+pub const DATE_ADDED_KEY_PREFIX: &str = "added.";
+
+const RECENT_LIMIT: usize = 50;
+const MOST_PLAYED_LIMIT: usize = 25;
+
+// Newest library files of the last index, so files are only stat'ed again after a rescan
+static RECENTLY_ADDED_CACHE: Mutex<Option<(Arc<SearchIndex>, Vec<String>)>> = Mutex::new(None);
+
+pub fn is_system_playlist(playlist_id: &str) -> bool {
+    playlist_id.starts_with(SYSTEM_ID_PREFIX)
+}
+
+/// Generate every system playlist from the current library index and play history
+///
+/// "Recently Added" is empty until the library has been indexed.
+pub fn generate(
+    index: Option<&Arc<SearchIndex>>,
+    plays: &[(String, PlayStats)],
+    store: &KvStore,
+    extractor: &MetadataExtractor,
+) -> Vec<Playlist> {
+    let added = index.map(|index| recently_added_cached(index, store)).unwrap_or_default();
+    vec![
+        system_playlist(RECENTLY_ADDED, &added, extractor),
+        system_playlist(RECENTLY_PLAYED, &recently_played(plays, RECENT_LIMIT), extractor),
+        system_playlist(MOST_PLAYED, &most_played(plays, MOST_PLAYED_LIMIT), extractor),
+    ]
+}
+
+fn recently_added_cached(index: &Arc<SearchIndex>, store: &KvStore) -> Vec<String> {
+    let mut cache = RECENTLY_ADDED_CACHE.lock().unwrap();
+    if let Some((cached_index, paths)) = cache.as_ref() {
+        if Arc::ptr_eq(cached_index, index) {
+            return paths.clone();
+        }
+    }
+    let paths = recently_added(store, index.entries().map(|(track, _)| crate::path_codec::encode(&track.file_path)), RECENT_LIMIT);
+    *cache = Some((index.clone(), paths.clone()));
+    paths
+}
+
+/// Forget the cached "Recently Added" files, after dates added have changed
+pub fn clear_recently_added_cache() {
+    *RECENTLY_ADDED_CACHE.lock().unwrap() = None;
+}
+
+/// KV store key holding the imported date added of a file
+pub fn date_added_key(file_path: &str) -> String {
+    format!("{}{}", DATE_ADDED_KEY_PREFIX, file_path)
+}
+
+/// Record when a file was added to another player's library
+pub fn set_date_added(store: &KvStore, file_path: &str, added: DateTime<Utc>) -> Result<(), KvError> {
+    store.set(&date_added_key(file_path), &added.timestamp_millis())
+}
+
+/// When a file was added: the imported date if there is one, else its
+/// creation time where the filesystem records it, else its mtime
+pub fn date_added(store: &KvStore, path: &std::path::Path) -> Option<DateTime<Utc>> {
+    let imported = store.get::<i64>(&date_added_key(&crate::path_codec::encode(path)));
+    if let Some(added) = imported.and_then(DateTime::from_timestamp_millis) {
+        return Some(added);
+    }
+    let metadata = std::fs::metadata(path).ok()?;
+    metadata.created().or_else(|_| metadata.modified()).ok().map(DateTime::from)
+}
+
+/// The `limit` newest files, by `date_added`
+pub fn recently_added(store: &KvStore, paths: impl IntoIterator<Item = String>, limit: usize) -> Vec<String> {
+    let mut dated: Vec<(DateTime<Utc>, String)> = paths
+        .into_iter()
+        .filter_map(|path| Some((date_added(store, &crate::path_codec::decode(&path))?, path)))
+        .collect();
+    dated.sort_by_key(|(added, _)| std::cmp::Reverse(*added));
+    dated.into_iter().take(limit).map(|(_, path)| path).collect()
+}
+
+/// The `limit` most recently played files, latest first
+pub fn recently_played(plays: &[(String, PlayStats)], limit: usize) -> Vec<String> {
+    let mut plays: Vec<&(String, PlayStats)> = plays.iter().collect();
+    plays.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.last_played));
+    plays.into_iter().take(limit).map(|(path, _)| path.clone()).collect()
+}
+
+/// The `limit` most played files; ties go to the more recently played
+pub fn most_played(plays: &[(String, PlayStats)], limit: usize) -> Vec<String> {
+    let mut plays: Vec<&(String, PlayStats)> = plays.iter().collect();
+    plays.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(b.1.last_played.cmp(&a.1.last_played)));
+    plays.into_iter().take(limit).map(|(path, _)| path.clone()).collect()
+}
+
+/// Files that no longer exist are left out
+fn system_playlist((id, name): (&str, &str), paths: &[String], extractor: &MetadataExtractor) -> Playlist {
+    let tracks: Vec<Track> = paths
+        .iter()
+        .map(|path| crate::path_codec::decode(path))
+        .filter(|path| path.exists())
+        .map(|path| track_for_file(&path, extractor))
+        .collect();
+    let now = Utc::now();
+    Playlist {
+        id: id.to_string(),
+        name: name.to_string(),
+        tracks,
+        created_at: now,
+        modified_at: now,
+        system: true,
+        description: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plays() -> Vec<(String, PlayStats)> {
+        vec![
+            ("a.mp3".to_string(), PlayStats { count: 3, last_played: 100 }),
+            ("b.mp3".to_string(), PlayStats { count: 9, last_played: 50 }),
+            ("c.mp3".to_string(), PlayStats { count: 3, last_played: 300 }),
+        ]
+    }
+
+    #[test]
+    fn test_played_orderings() {
+        assert_eq!(recently_played(&plays(), 2), ["c.mp3", "a.mp3"]);
+        assert_eq!(most_played(&plays(), 3), ["b.mp3", "c.mp3", "a.mp3"]);
+        assert!(is_system_playlist(MOST_PLAYED.0));
+        assert!(!is_system_playlist("3f2a9c"));
+    }
+
+    #[test]
+    fn test_missing_files_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let present = dir.path().join("present.wav");
+        std::fs::write(&present, b"").unwrap();
+        let paths = vec![present.to_string_lossy().to_string(), dir.path().join("gone.wav").to_string_lossy().to_string()];
+
+        let store = KvStore::in_memory();
+        assert_eq!(recently_added(&store, paths.clone(), 10), paths[..1]);
+        let playlist = system_playlist(RECENTLY_PLAYED, &paths, &MetadataExtractor::new());
+        assert!(playlist.system);
+        assert_eq!(playlist.tracks.len(), 1);
+        assert_eq!(playlist.tracks[0].title, "present");
+    }
+}
@@ -0,0 +1,223 @@
+// Tags from file names
+// Reads tags out of file and folder names with a `tag_pattern` pattern, or the
+// extractor's "Artist - Title" fallback when there is none, and writes them
+// into files that lack them. Tags a file already has are never overwritten.
+
+use crate::metadata::{MetadataExtractor, TrackMetadata};
+use crate::tag_pattern::TagPattern;
+use id3::TagLike;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TagWriteError {
+    #[error("Writing tags is not supported for .{0} files")]
+    UnsupportedFormat(String),
+    #[error("Failed to read tags: {0}")]
+    Read(String),
+    #[error("Failed to write tags: {0}")]
+    Write(String),
+}
+
+/// Tags a file is missing that its name provides
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TagSuggestion {
+    #[serde(with = "crate::path_codec")]
+    pub path: PathBuf,
+    /// Tags in the file now
+    #[serde(default)]
+    pub current: TrackMetadata,
+    /// Only these are written; fields can be cleared to leave them out
+    pub inferred: TrackMetadata,
+    /// Why nothing was inferred
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TagWriteFailure {
+    #[serde(with = "crate::path_codec")]
+    pub path: PathBuf,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TagApplyReport {
+    pub updated: usize,
+    /// Files with nothing left to fill
+    pub unchanged: usize,
+    pub failed: Vec<TagWriteFailure>,
+}
+
+/// Suggest missing tags for each file from its name
+pub fn infer_tags(paths: &[PathBuf], pattern: Option<&TagPattern>, extractor: &MetadataExtractor) -> Vec<TagSuggestion> {
+    paths
+        .iter()
+        .map(|path| {
+            let suggestion = |current, inferred, note: Option<&str>| TagSuggestion {
+                path: path.clone(),
+                current,
+                inferred,
+                note: note.map(str::to_string),
+            };
+            let current = match extractor.read_tags(path) {
+                Ok(current) => current,
+                Err(e) => return suggestion(TrackMetadata::default(), TrackMetadata::default(), Some(&e.to_string())),
+            };
+            let captured = match pattern {
+                Some(pattern) => pattern.capture(path),
+                None => Some(extractor.parse_fallback(path)),
+            };
+            let Some(captured) = captured else {
+                return suggestion(current, TrackMetadata::default(), Some("file name does not match the pattern"));
+            };
+            let inferred = missing_from(&current, captured);
+            let note = inferred.is_empty().then_some("no missing tags to fill");
+            suggestion(current, inferred, note)
+        })
+        .collect()
+}
+
+/// Write the suggested tags, re-checking each file so present tags are kept
+pub fn apply_suggestions(suggestions: &[TagSuggestion], extractor: &MetadataExtractor) -> TagApplyReport {
+    let mut report = TagApplyReport::default();
+    for suggestion in suggestions {
+        let result = extractor
+            .read_tags(&suggestion.path)
+            .map_err(|e| TagWriteError::Read(e.to_string()))
+            .and_then(|current| {
+                let tags = missing_from(&current, suggestion.inferred.clone());
+                if tags.is_empty() {
+                    return Ok(false);
+                }
+                write_tags(&suggestion.path, &tags).map(|()| true)
+            });
+        match result {
+            Ok(true) => report.updated += 1,
+            Ok(false) => report.unchanged += 1,
+            Err(e) => report.failed.push(TagWriteFailure { path: suggestion.path.clone(), error: e.to_string() }),
+        }
+    }
+    report
+}
+
+/// The fields of `candidate` that `current` lacks
+fn missing_from(current: &TrackMetadata, candidate: TrackMetadata) -> TrackMetadata {
+    let text = |current: &Option<String>, candidate: Option<String>| {
+        candidate
+            .filter(|_| current.as_deref().is_none_or(|c| c.trim().is_empty()))
+            .map(|c| c.trim().to_string())
+            .filter(|c| !c.is_empty())
+    };
+    TrackMetadata {
+        title: text(&current.title, candidate.title),
+        artist: text(&current.artist, candidate.artist),
+        album: text(&current.album, candidate.album),
+        year: candidate.year.filter(|_| current.year.is_none()),
+        genre: text(&current.genre, candidate.genre),
+        track_number: candidate.track_number.filter(|_| current.track_number.is_none()),
+        disc_number: candidate.disc_number.filter(|_| current.disc_number.is_none()),
+        ..Default::default()
+    }
+}
+
+/// Set the given fields in the file's tags, leaving the others alone
+fn write_tags(path: &Path, tags: &TrackMetadata) -> Result<(), TagWriteError> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp3" => {
+            let mut tag = match id3::Tag::read_from_path(path) {
+                Ok(tag) => tag,
+                Err(e) if matches!(e.kind, id3::ErrorKind::NoTag) => id3::Tag::new(),
+                Err(e) => return Err(TagWriteError::Read(e.to_string())),
+            };
+            if let Some(title) = &tags.title {
+                tag.set_title(title);
+            }
+            if let Some(artist) = &tags.artist {
+                tag.set_artist(artist);
+            }
+            if let Some(album) = &tags.album {
+                tag.set_album(album);
+            }
+            if let Some(year) = tags.year {
+                tag.set_year(year as i32);
+            }
+            if let Some(genre) = &tags.genre {
+                tag.set_genre(genre);
+            }
+            if let Some(track) = tags.track_number {
+                tag.set_track(track);
+            }
+            if let Some(disc) = tags.disc_number {
+                tag.set_disc(disc);
+            }
+            tag.write_to_path(path, id3::Version::Id3v24)
+                .map_err(|e| TagWriteError::Write(e.to_string()))
+        }
+        "flac" => {
+            let mut tag = metaflac::Tag::read_from_path(path).map_err(|e| TagWriteError::Read(e.to_string()))?;
+            let fields = [
+                ("TITLE", tags.title.clone()),
+                ("ARTIST", tags.artist.clone()),
+                ("ALBUM", tags.album.clone()),
+                ("DATE", tags.year.map(|y| y.to_string())),
+                ("GENRE", tags.genre.clone()),
+                ("TRACKNUMBER", tags.track_number.map(|t| t.to_string())),
+                ("DISCNUMBER", tags.disc_number.map(|d| d.to_string())),
+            ];
+            for (key, value) in fields {
+                if let Some(value) = value {
+                    tag.set_vorbis(key, vec![value]);
+                }
+            }
+            tag.save().map_err(|e| TagWriteError::Write(e.to_string()))
+        }
+        _ => Err(TagWriteError::UnsupportedFormat(extension)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_and_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let album = dir.path().join("Moon Safari");
+        std::fs::create_dir_all(&album).unwrap();
+        let untagged = album.join("02 - Air - Sexy Boy.mp3");
+        let tagged = album.join("03 - Air - All I Need.mp3");
+        std::fs::write(&untagged, b"").unwrap();
+        std::fs::write(&tagged, b"").unwrap();
+        let mut tag = id3::Tag::new();
+        tag.set_title("All I Need (Album Version)");
+        tag.write_to_path(&tagged, id3::Version::Id3v24).unwrap();
+
+        let extractor = MetadataExtractor::new();
+        let pattern = TagPattern::parse("{album}/{track} - {artist} - {title}").unwrap();
+        let paths = vec![untagged.clone(), tagged.clone(), dir.path().join("notes.mp3")];
+        let suggestions = infer_tags(&paths, Some(&pattern), &extractor);
+        assert_eq!(suggestions[0].inferred.title.as_deref(), Some("Sexy Boy"));
+        assert_eq!(suggestions[0].inferred.track_number, Some(2));
+        assert_eq!(suggestions[1].inferred.title, None);
+        assert_eq!(suggestions[1].inferred.artist.as_deref(), Some("Air"));
+        assert!(suggestions[2].note.is_some());
+
+        // Leave the album out of the first file
+        let mut selected = suggestions[..2].to_vec();
+        selected[0].inferred.album = None;
+        let report = apply_suggestions(&selected, &extractor);
+        assert_eq!((report.updated, report.unchanged, report.failed.len()), (2, 0, 0));
+
+        let written = extractor.read_tags(&untagged).unwrap();
+        assert_eq!(written.artist.as_deref(), Some("Air"));
+        assert_eq!(written.album, None);
+        assert_eq!(extractor.read_tags(&tagged).unwrap().title.as_deref(), Some("All I Need (Album Version)"));
+    }
+}
@@ -0,0 +1,281 @@
+// Tag patterns for file names
+// A pattern such as `{artist}/{album}/{track:02} - {title}` is rendered from
+// tags when renaming files, and matched against paths to read tags back out
+// of file names. `/` separates folders; the file extension is never part of it.
+
+use crate::metadata::TrackMetadata;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PatternError {
+    #[error("Invalid tag pattern: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Artist,
+    Album,
+    Title,
+    Genre,
+    Year,
+    Track,
+    Disc,
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "artist" => Some(Field::Artist),
+            "album" => Some(Field::Album),
+            "title" => Some(Field::Title),
+            "genre" => Some(Field::Genre),
+            "year" => Some(Field::Year),
+            "track" => Some(Field::Track),
+            "disc" => Some(Field::Disc),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Artist => "artist",
+            Field::Album => "album",
+            Field::Title => "title",
+            Field::Genre => "genre",
+            Field::Year => "year",
+            Field::Track => "track",
+            Field::Disc => "disc",
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Field::Year | Field::Track | Field::Disc)
+    }
+
+    fn value(self, metadata: &TrackMetadata, width: usize) -> Option<String> {
+        let text = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+        let number = |value: Option<u32>| value.map(|n| format!("{:0width$}", n, width = width));
+        match self {
+            Field::Artist => text(&metadata.artist),
+            Field::Album => text(&metadata.album),
+            Field::Title => text(&metadata.title),
+            Field::Genre => text(&metadata.genre),
+            Field::Year => number(metadata.year),
+            Field::Track => number(metadata.track_number),
+            Field::Disc => number(metadata.disc_number),
+        }
+    }
+
+    /// Store a captured value; numbers must parse
+    fn assign(self, metadata: &mut TrackMetadata, value: &str) -> bool {
+        let text = Some(value.to_string());
+        match self {
+            Field::Artist => metadata.artist = text,
+            Field::Album => metadata.album = text,
+            Field::Title => metadata.title = text,
+            Field::Genre => metadata.genre = text,
+            Field::Year | Field::Track | Field::Disc => {
+                let Ok(number) = value.parse::<u32>() else {
+                    return false;
+                };
+                match self {
+                    Field::Year => metadata.year = Some(number),
+                    Field::Track => metadata.track_number = Some(number),
+                    _ => metadata.disc_number = Some(number),
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Field { field: Field, width: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPattern {
+    components: Vec<Vec<Segment>>,
+}
+
+impl TagPattern {
+    /// Parse a pattern of literal text and `{field}` or `{field:0N}` placeholders
+    ///
+    /// Fields are artist, album, title, genre, year, track and disc; the
+    /// zero-padded width is only allowed on the numeric ones.
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let invalid = |reason: &str| PatternError::Invalid(format!("{} in \"{}\"", reason, pattern));
+        let mut components = Vec::new();
+        for component in pattern.trim().split(['/', '\\']) {
+            let mut segments = Vec::new();
+            let mut rest = component;
+            while !rest.is_empty() {
+                let Some(open) = rest.find(['{', '}']) else {
+                    segments.push(Segment::Text(rest.to_string()));
+                    break;
+                };
+                if rest[open..].starts_with('}') {
+                    return Err(invalid("unmatched '}'"));
+                }
+                if open > 0 {
+                    segments.push(Segment::Text(rest[..open].to_string()));
+                }
+                let close = rest[open..].find('}').ok_or_else(|| invalid("unclosed '{'"))? + open;
+                let placeholder = &rest[open + 1..close];
+                let (name, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+                let field = Field::parse(name.trim()).ok_or_else(|| invalid(&format!("unknown field '{}'", name)))?;
+                let width = match spec {
+                    "" => 0,
+                    spec if field.is_numeric() && spec.starts_with('0') => {
+                        spec.parse().map_err(|_| invalid(&format!("bad width '{}'", spec)))?
+                    }
+                    spec => return Err(invalid(&format!("unsupported format '{}' for {}", spec, name))),
+                };
+                segments.push(Segment::Field { field, width });
+                rest = &rest[close + 1..];
+            }
+            if segments.iter().all(|s| matches!(s, Segment::Text(t) if t.trim().trim_matches('.').is_empty())) {
+                return Err(invalid("empty or relative folder name"));
+            }
+            components.push(segments);
+        }
+        Ok(Self { components })
+    }
+
+    /// Relative path for a file with these tags, or the first missing field
+    pub fn render(&self, metadata: &TrackMetadata, extension: &str) -> Result<PathBuf, &'static str> {
+        let mut path = PathBuf::new();
+        for (i, segments) in self.components.iter().enumerate() {
+            let mut name = String::new();
+            for segment in segments {
+                match segment {
+                    Segment::Text(text) => name.push_str(text),
+                    Segment::Field { field, width } => name.push_str(&field.value(metadata, *width).ok_or(field.name())?),
+                }
+            }
+            let mut name = sanitize(&name);
+            if i + 1 == self.components.len() && !extension.is_empty() {
+                name = format!("{}.{}", name, extension);
+            }
+            path.push(name);
+        }
+        Ok(path)
+    }
+
+    /// Tags read from the trailing folders and stem of `path`, if it fits the pattern
+    ///
+    /// Text fields match as little as possible, so in `{artist} - {title}` the
+    /// artist ends at the first " - ". Numbers must be all digits.
+    pub fn capture(&self, path: &Path) -> Option<TrackMetadata> {
+        let stem = path.file_stem()?.to_string_lossy().to_string();
+        let mut names: Vec<String> = path
+            .parent()
+            .map(|parent| parent.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect())
+            .unwrap_or_default();
+        names.push(stem);
+        if names.len() < self.components.len() {
+            return None;
+        }
+        let names = &names[names.len() - self.components.len()..];
+        let mut metadata = TrackMetadata::default();
+        for (segments, name) in self.components.iter().zip(names) {
+            if !capture_segments(segments, name, &mut metadata) {
+                return None;
+            }
+        }
+        Some(metadata)
+    }
+}
+
+/// Backtracking match of one path component
+fn capture_segments(segments: &[Segment], text: &str, metadata: &mut TrackMetadata) -> bool {
+    let Some((first, rest)) = segments.split_first() else {
+        return text.is_empty();
+    };
+    match first {
+        Segment::Text(literal) => text
+            .strip_prefix(literal.as_str())
+            .is_some_and(|remaining| capture_segments(rest, remaining, metadata)),
+        Segment::Field { field, .. } => {
+            let ends = text.char_indices().map(|(i, c)| i + c.len_utf8());
+            for end in ends {
+                let value = text[..end].trim();
+                if field.is_numeric() && !text[..end].chars().all(|c| c.is_ascii_digit()) {
+                    break;
+                }
+                if value.is_empty() {
+                    continue;
+                }
+                let mut attempt = metadata.clone();
+                if field.assign(&mut attempt, value) && capture_segments(rest, &text[end..], &mut attempt) {
+                    *metadata = attempt;
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// Replace unsafe characters and trailing dots so the name is valid everywhere
+pub fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').trim_end();
+    if cleaned.is_empty() {
+        "_".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(artist: Option<&str>, title: &str, track: u32) -> TrackMetadata {
+        TrackMetadata {
+            title: Some(title.to_string()),
+            artist: artist.map(str::to_string),
+            album: Some("Discovery".to_string()),
+            track_number: Some(track),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_and_render() {
+        let pattern = TagPattern::parse("{artist}/{album}/{track:02} - {title}").unwrap();
+        assert_eq!(
+            pattern.render(&metadata(Some("AC/DC"), "What? Now.", 3), "mp3").unwrap(),
+            PathBuf::from("AC_DC").join("Discovery").join("03 - What_ Now.mp3")
+        );
+        assert_eq!(pattern.render(&metadata(None, "One", 1), "mp3"), Err("artist"));
+
+        for bad in ["{artist", "{artst}/{title}", "{title:02}", "../{title}", "{artist}//{title}", "title}"] {
+            assert!(TagPattern::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_capture() {
+        let pattern = TagPattern::parse("{artist}/{album}/{track} - {title}").unwrap();
+        let captured = pattern.capture(Path::new("/music/Daft Punk/Discovery/03 - Digital Love - Live.mp3")).unwrap();
+        assert_eq!(captured, metadata(Some("Daft Punk"), "Digital Love - Live", 3));
+
+        let flat = TagPattern::parse("{artist} - {title}").unwrap();
+        assert_eq!(flat.capture(Path::new("Air - La femme d'argent.flac")).unwrap().artist.as_deref(), Some("Air"));
+        assert_eq!(flat.capture(Path::new("untitled.mp3")), None);
+        assert_eq!(pattern.capture(Path::new("/music/x/Intro - One.mp3")), None);
+    }
+}
@@ -0,0 +1,246 @@
+// Batch renaming of files after their tags
+// A pattern such as `{artist}/{album}/{track:02} - {title}` lays files out
+// under a library root. Renames are planned first so the frontend can preview
+// them; files whose target is taken, or would be taken twice, are left alone.
+
+use crate::bookmarks::{BOOKMARK_KEY_PREFIX, RESUME_KEY_PREFIX};
+use crate::kv_store::{KvError, KvStore};
+use crate::metadata::MetadataExtractor;
+use crate::play_history::PLAY_KEY_PREFIX;
+use crate::ratings::RATING_KEY_PREFIX;
+use crate::system_playlists::DATE_ADDED_KEY_PREFIX;
+use crate::tag_pattern::TagPattern;
+use crate::track_notes::NOTE_KEY_PREFIX;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RenameStatus {
+    /// Will be renamed when the plan is applied
+    Ready,
+    Renamed,
+    /// Already at its target path
+    Unchanged,
+    MissingTag,
+    /// Target exists, or several files would get the same name
+    Collision,
+    OutsideRoot,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RenameEntry {
+    #[serde(with = "crate::path_codec")]
+    pub source: PathBuf,
+    #[serde(serialize_with = "serialize_target")]
+    pub target: Option<PathBuf>,
+    pub status: RenameStatus,
+    /// Missing field, colliding path or error message
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RenameReport {
+    pub dry_run: bool,
+    pub entries: Vec<RenameEntry>,
+    pub renamed: usize,
+    pub skipped: usize,
+}
+
+fn serialize_target<S: serde::Serializer>(target: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+    match target {
+        Some(path) => crate::path_codec::serialize(path, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Work out the new name of every file without touching the disk
+///
+/// Targets are compared case-insensitively, so a plan is also safe on
+/// case-insensitive file systems.
+pub fn plan_renames(paths: &[PathBuf], pattern: &TagPattern, root: &Path, extractor: &MetadataExtractor) -> Vec<RenameEntry> {
+    let mut entries: Vec<RenameEntry> = paths
+        .iter()
+        .map(|source| {
+            let entry = |target: Option<PathBuf>, status, detail: Option<String>| RenameEntry {
+                source: source.clone(),
+                target,
+                status,
+                detail,
+            };
+            if !source.starts_with(root) {
+                return entry(None, RenameStatus::OutsideRoot, Some(root.to_string_lossy().to_string()));
+            }
+            let metadata = match extractor.extract(source) {
+                Ok(metadata) => metadata,
+                Err(e) => return entry(None, RenameStatus::Failed, Some(e.to_string())),
+            };
+            let extension = source.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+            match pattern.render(&metadata, &extension) {
+                Ok(relative) => {
+                    let target = root.join(relative);
+                    let status = if target == *source { RenameStatus::Unchanged } else { RenameStatus::Ready };
+                    entry(Some(target), status, None)
+                }
+                Err(field) => entry(None, RenameStatus::MissingTag, Some(field.to_string())),
+            }
+        })
+        .collect();
+
+    let mut claimed: HashMap<String, usize> = HashMap::new();
+    for entry in &entries {
+        if let Some(target) = &entry.target {
+            *claimed.entry(target.to_string_lossy().to_lowercase()).or_default() += 1;
+        }
+    }
+    for entry in entries.iter_mut().filter(|e| e.status == RenameStatus::Ready) {
+        let target = entry.target.as_ref().unwrap();
+        if claimed[&target.to_string_lossy().to_lowercase()] > 1 {
+            entry.status = RenameStatus::Collision;
+            entry.detail = Some("another file would get the same name".to_string());
+        } else if target.exists() && !same_file(&entry.source, target) {
+            entry.status = RenameStatus::Collision;
+            entry.detail = Some("a file with this name already exists".to_string());
+        }
+    }
+    entries
+}
+
+/// Report for a plan that is only previewed
+pub fn preview(entries: Vec<RenameEntry>) -> RenameReport {
+    let skipped = entries
+        .iter()
+        .filter(|e| !matches!(e.status, RenameStatus::Ready | RenameStatus::Unchanged))
+        .count();
+    RenameReport { dry_run: true, entries, renamed: 0, skipped }
+}
+
+/// Move every `Ready` file to its target and prune folders left empty
+pub fn apply_renames(entries: Vec<RenameEntry>, root: &Path) -> RenameReport {
+    let mut report = RenameReport::default();
+    for mut entry in entries {
+        if entry.status == RenameStatus::Ready {
+            let target = entry.target.clone().unwrap();
+            match rename_file(&entry.source, &target) {
+                Ok(()) => {
+                    entry.status = RenameStatus::Renamed;
+                    if let Some(parent) = entry.source.parent() {
+                        prune_empty_dirs(parent, root);
+                    }
+                }
+                Err(e) => {
+                    entry.status = RenameStatus::Failed;
+                    entry.detail = Some(e.to_string());
+                }
+            }
+        }
+        if entry.status == RenameStatus::Renamed {
+            report.renamed += 1;
+        } else if entry.status != RenameStatus::Unchanged {
+            report.skipped += 1;
+        }
+        report.entries.push(entry);
+    }
+    report
+}
+
+fn rename_file(source: &Path, target: &Path) -> std::io::Result<()> {
+    // The plan may be stale by the time it is applied
+    if target.exists() && !same_file(source, target) {
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "target already exists"));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(source, target)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn prune_empty_dirs(dir: &Path, root: &Path) {
+    let mut dir = dir.to_path_buf();
+    while dir != root && dir.starts_with(root) && fs::remove_dir(&dir).is_ok() {
+        if !dir.pop() {
+            break;
+        }
+    }
+}
+
+/// KV store prefixes of data kept by file path
+const PATH_KEY_PREFIXES: [&str; 6] =
+    [RATING_KEY_PREFIX, RESUME_KEY_PREFIX, BOOKMARK_KEY_PREFIX, PLAY_KEY_PREFIX, DATE_ADDED_KEY_PREFIX, NOTE_KEY_PREFIX];
+
+/// Carry ratings, resume positions, play history, dates added, notes and
+/// bookmarks over to a renamed file
+///
+/// Playlist entries are repointed separately, see
+/// `PlaylistManager::rename_file_paths`.
+pub fn move_track_data(store: &KvStore, old: &Path, new: &Path) -> Result<(), KvError> {
+    let (old_path, new_path) = (crate::path_codec::encode(old), crate::path_codec::encode(new));
+    for prefix in PATH_KEY_PREFIXES {
+        let from = format!("{}{}", prefix, old_path);
+        if let Some(value) = store.get::<serde_json::Value>(&from) {
+            store.set(&format!("{}{}", prefix, new_path), &value)?;
+            store.remove(&from)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use id3::TagLike;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_plan_and_apply() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        let messy = root.join("messy");
+        fs::create_dir_all(&messy).unwrap();
+        let mut paths = Vec::new();
+        for (name, title, track) in [("a.mp3", "One", 1), ("b.mp3", "Two", 2), ("c.mp3", "Two", 2)] {
+            let path = messy.join(name);
+            fs::write(&path, b"").unwrap();
+            let mut tag = id3::Tag::new();
+            tag.set_artist("Daft Punk");
+            tag.set_album("Discovery");
+            tag.set_title(title);
+            tag.set_track(track);
+            tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+            paths.push(path);
+        }
+        paths.push(PathBuf::from("/elsewhere/d.mp3"));
+
+        let pattern = TagPattern::parse("{artist}/{album}/{track:02} {title}").unwrap();
+        let plan = plan_renames(&paths, &pattern, root, &MetadataExtractor::new());
+        let statuses: Vec<RenameStatus> = plan.iter().map(|e| e.status).collect();
+        assert_eq!(
+            statuses,
+            [RenameStatus::Ready, RenameStatus::Collision, RenameStatus::Collision, RenameStatus::OutsideRoot]
+        );
+
+        let report = apply_renames(plan, root);
+        assert_eq!((report.renamed, report.skipped), (1, 3));
+        assert!(root.join("Daft Punk/Discovery/01 One.mp3").exists());
+        assert!(messy.join("b.mp3").exists());
+
+        let store = KvStore::in_memory();
+        let moved = root.join("Daft Punk/Discovery/01 One.mp3");
+        let (old_path, new_path) = (crate::path_codec::encode(&paths[0]), crate::path_codec::encode(&moved));
+        for prefix in PATH_KEY_PREFIXES {
+            store.set(&format!("{}{}", prefix, old_path), &4).unwrap();
+        }
+        move_track_data(&store, &paths[0], &moved).unwrap();
+        for prefix in PATH_KEY_PREFIXES {
+            assert_eq!(store.get::<u8>(&format!("{}{}", prefix, new_path)), Some(4), "{}", prefix);
+            assert_eq!(store.get::<u8>(&format!("{}{}", prefix, old_path)), None, "{}", prefix);
+        }
+    }
+}
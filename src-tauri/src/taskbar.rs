@@ -0,0 +1,366 @@
+// Windows taskbar integration
+// Mirrors playback on the taskbar button: a progress bar following the track
+// position and previous/play-pause/next buttons under the thumbnail preview.
+// Both are driven from the player state the frontend already reports through
+// `update_media_session`; button clicks come back as `media-control` events.
+
+// Only started on Windows; the pure parts are built everywhere for the tests
+#![cfg_attr(not(target_os = "windows"), allow(dead_code))]
+
+use crate::media_session::PlaybackStatus;
+use crate::remote_api::{PlayerSnapshot, PlayerState};
+use std::time::{Duration, Instant};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Manager, Runtime};
+
+/// How often the progress bar is refreshed while playing
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What the taskbar button shows for the current playback state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskbarProgress {
+    /// Stopped, or the track length is unknown
+    Hidden,
+    /// Percent of the track played
+    Playing(u64),
+    Paused(u64),
+}
+
+impl TaskbarProgress {
+    pub fn from_snapshot(snapshot: &PlayerSnapshot, now: Instant) -> Self {
+        let duration = snapshot.now_playing.as_ref().and_then(|t| t.duration_secs).filter(|d| *d > 0.0);
+        let (Some(duration), Some(position)) = (duration, snapshot.position_at(now)) else {
+            return Self::Hidden;
+        };
+        let percent = ((position / duration) * 100.0).round().clamp(0.0, 100.0) as u64;
+        match snapshot.status {
+            PlaybackStatus::Playing => Self::Playing(percent),
+            PlaybackStatus::Paused => Self::Paused(percent),
+            PlaybackStatus::Stopped => Self::Hidden,
+        }
+    }
+
+    fn is_playing(self) -> bool {
+        matches!(self, Self::Playing(_))
+    }
+
+    fn to_state(self) -> ProgressBarState {
+        let (status, progress) = match self {
+            Self::Hidden => (ProgressBarStatus::None, None),
+            Self::Playing(percent) => (ProgressBarStatus::Normal, Some(percent)),
+            Self::Paused(percent) => (ProgressBarStatus::Paused, Some(percent)),
+        };
+        ProgressBarState { status: Some(status), progress }
+    }
+}
+
+/// Transport buttons shown under the taskbar thumbnail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glyph {
+    Previous,
+    Play,
+    Pause,
+    Next,
+}
+
+/// Rasterize a button icon as a `size`×`size` coverage mask, row by row
+///
+/// Drawn in code so the buttons need no bundled icon resources.
+pub fn glyph_mask(glyph: Glyph, size: usize) -> Vec<bool> {
+    let mut mask = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            // Sample at pixel centers in 0..1 space
+            let u = (x as f32 + 0.5) / size as f32;
+            let v = (y as f32 + 0.5) / size as f32;
+            mask.push(glyph_covers(glyph, u, v));
+        }
+    }
+    mask
+}
+
+fn glyph_covers(glyph: Glyph, u: f32, v: f32) -> bool {
+    let bar = |left: f32, right: f32| (left..=right).contains(&u) && (0.2..=0.8).contains(&v);
+    match glyph {
+        Glyph::Play => triangle_right(u, v, 0.25, 0.8, 0.15, 0.85),
+        Glyph::Pause => bar(0.25, 0.42) || bar(0.58, 0.75),
+        Glyph::Next => triangle_right(u, v, 0.2, 0.65, 0.2, 0.8) || bar(0.67, 0.8),
+        Glyph::Previous => glyph_covers(Glyph::Next, 1.0 - u, v),
+    }
+}
+
+/// Triangle with a vertical left edge and its point on the right
+fn triangle_right(u: f32, v: f32, left: f32, right: f32, top: f32, bottom: f32) -> bool {
+    if !(left..=right).contains(&u) {
+        return false;
+    }
+    let half_height = (bottom - top) / 2.0 * (right - u) / (right - left);
+    (v - (top + bottom) / 2.0).abs() <= half_height
+}
+
+/// Keep the taskbar in step with `player` until the app exits
+///
+/// `hwnd` is the main window handle, used for the thumbnail buttons.
+pub fn spawn<R: Runtime>(app: AppHandle<R>, player: &'static PlayerState, hwnd: Option<usize>) {
+    #[cfg(target_os = "windows")]
+    if let Some(hwnd) = hwnd {
+        thumbbar::install(&app, hwnd);
+    }
+    #[cfg(not(target_os = "windows"))]
+    let _ = hwnd;
+
+    std::thread::spawn(move || {
+        let mut shown = None;
+        loop {
+            let progress = TaskbarProgress::from_snapshot(&player.snapshot(), Instant::now());
+            if shown != Some(progress) {
+                let Some(window) = app.get_webview_window("main") else {
+                    return;
+                };
+                let _ = window.set_progress_bar(progress.to_state());
+                #[cfg(target_os = "windows")]
+                if shown.map(TaskbarProgress::is_playing) != Some(progress.is_playing()) {
+                    thumbbar::set_playing(&app, progress.is_playing());
+                }
+                shown = Some(progress);
+            }
+            std::thread::sleep(REFRESH_INTERVAL);
+        }
+    });
+}
+
+#[cfg(target_os = "windows")]
+mod thumbbar {
+    use super::{glyph_mask, Glyph};
+    use crate::logging::log_warn;
+    use crate::media_session::MediaKeyAction;
+    use std::cell::RefCell;
+    use std::sync::OnceLock;
+    use tauri::{AppHandle, Emitter, Runtime};
+    use windows::core::w;
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER};
+    use windows::Win32::UI::Shell::{
+        DefSubclassProc, ITaskbarList3, SetWindowSubclass, TaskbarList, THBF_ENABLED, THBN_CLICKED, THB_FLAGS, THB_ICON,
+        THB_TOOLTIP, THUMBBUTTON,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateIcon, GetSystemMetrics, RegisterWindowMessageW, HICON, SM_CXSMICON, WM_COMMAND,
+    };
+
+    const SUBCLASS_ID: usize = 0x6d696c6b;
+
+    /// Button ids reported in `WM_COMMAND`
+    const PREVIOUS_ID: u32 = 0;
+    const PLAY_PAUSE_ID: u32 = 1;
+    const NEXT_ID: u32 = 2;
+
+    /// Forwards button clicks from the window procedure, which has no app handle
+    static CLICK_SINK: OnceLock<Box<dyn Fn(MediaKeyAction) + Send + Sync>> = OnceLock::new();
+
+    /// Registered message id of `TaskbarButtonCreated`, resent when Explorer restarts
+    static TASKBAR_CREATED: OnceLock<u32> = OnceLock::new();
+
+    // COM objects and icons live on the UI thread that owns the window
+    thread_local! {
+        static TASKBAR: RefCell<Option<(HWND, ITaskbarList3)>> = const { RefCell::new(None) };
+        static PLAYING: RefCell<bool> = const { RefCell::new(false) };
+        static ICONS: RefCell<Vec<(Glyph, HICON)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Subclass the main window and add the buttons; must run on the UI thread
+    pub fn install<R: Runtime>(app: &AppHandle<R>, hwnd: usize) {
+        let emitter = app.clone();
+        let _ = CLICK_SINK.set(Box::new(move |action| {
+            if let Err(e) = emitter.emit("media-control", action) {
+                log_warn("Taskbar", &format!("Failed to emit media-control event: {}", e));
+            }
+        }));
+        let hwnd = HWND(hwnd as *mut _);
+        unsafe {
+            TASKBAR_CREATED.get_or_init(|| RegisterWindowMessageW(w!("TaskbarButtonCreated")));
+            if !SetWindowSubclass(hwnd, Some(subclass_proc), SUBCLASS_ID, 0).as_bool() {
+                log_warn("Taskbar", "Failed to subclass the main window; thumbnail buttons disabled");
+                return;
+            }
+        }
+        // The taskbar button usually exists already; otherwise TaskbarButtonCreated adds them
+        add_buttons(hwnd);
+    }
+
+    /// Swap the middle button between play and pause
+    pub fn set_playing<R: Runtime>(app: &AppHandle<R>, playing: bool) {
+        let result = app.run_on_main_thread(move || {
+            PLAYING.with(|p| *p.borrow_mut() = playing);
+            TASKBAR.with(|taskbar| {
+                if let Some((hwnd, taskbar)) = taskbar.borrow().as_ref() {
+                    let button = play_pause_button(playing);
+                    if let Err(e) = unsafe { taskbar.ThumbBarUpdateButtons(*hwnd, &[button]) } {
+                        log_warn("Taskbar", &format!("Failed to update thumbnail button: {}", e));
+                    }
+                }
+            });
+        });
+        if let Err(e) = result {
+            log_warn("Taskbar", &format!("Failed to schedule thumbnail update: {}", e));
+        }
+    }
+
+    fn add_buttons(hwnd: HWND) {
+        let result = unsafe {
+            CoCreateInstance::<_, ITaskbarList3>(&TaskbarList, None, CLSCTX_INPROC_SERVER).and_then(|taskbar| {
+                taskbar.HrInit()?;
+                let buttons = [
+                    button(PREVIOUS_ID, Glyph::Previous, "Previous"),
+                    play_pause_button(PLAYING.with(|p| *p.borrow())),
+                    button(NEXT_ID, Glyph::Next, "Next"),
+                ];
+                taskbar.ThumbBarAddButtons(hwnd, &buttons)?;
+                Ok(taskbar)
+            })
+        };
+        match result {
+            Ok(taskbar) => TASKBAR.with(|t| *t.borrow_mut() = Some((hwnd, taskbar))),
+            Err(e) => log_warn("Taskbar", &format!("Thumbnail buttons not added: {}", e)),
+        }
+    }
+
+    fn play_pause_button(playing: bool) -> THUMBBUTTON {
+        match playing {
+            true => button(PLAY_PAUSE_ID, Glyph::Pause, "Pause"),
+            false => button(PLAY_PAUSE_ID, Glyph::Play, "Play"),
+        }
+    }
+
+    fn button(id: u32, glyph: Glyph, tip: &str) -> THUMBBUTTON {
+        let mut button = THUMBBUTTON {
+            dwMask: THB_ICON | THB_TOOLTIP | THB_FLAGS,
+            iId: id,
+            hIcon: icon(glyph),
+            dwFlags: THBF_ENABLED,
+            ..Default::default()
+        };
+        for (dst, src) in button.szTip.iter_mut().zip(tip.encode_utf16()) {
+            *dst = src;
+        }
+        button
+    }
+
+    /// White glyph on a transparent background, created once per glyph
+    fn icon(glyph: Glyph) -> HICON {
+        ICONS.with(|icons| {
+            let mut icons = icons.borrow_mut();
+            if let Some((_, icon)) = icons.iter().find(|(g, _)| *g == glyph) {
+                return *icon;
+            }
+            let icon = create_icon(glyph);
+            icons.push((glyph, icon));
+            icon
+        })
+    }
+
+    fn create_icon(glyph: Glyph) -> HICON {
+        let size = unsafe { GetSystemMetrics(SM_CXSMICON) }.max(16) as usize;
+        let color: Vec<u8> = glyph_mask(glyph, size)
+            .into_iter()
+            .flat_map(|on| if on { [0xff; 4] } else { [0; 4] })
+            .collect();
+        // Alpha comes from the color bits, so the AND mask stays clear
+        let and_mask = vec![0u8; size.div_ceil(16) * 2 * size];
+        unsafe {
+            CreateIcon(None, size as i32, size as i32, 1, 32, and_mask.as_ptr(), color.as_ptr()).unwrap_or_default()
+        }
+    }
+
+    unsafe extern "system" fn subclass_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+        _id: usize,
+        _data: usize,
+    ) -> LRESULT {
+        if Some(&msg) == TASKBAR_CREATED.get() {
+            add_buttons(hwnd);
+        } else if msg == WM_COMMAND && (wparam.0 >> 16) as u32 & 0xffff == THBN_CLICKED {
+            let action = match (wparam.0 & 0xffff) as u32 {
+                PREVIOUS_ID => Some(MediaKeyAction::Previous),
+                PLAY_PAUSE_ID => Some(MediaKeyAction::Toggle),
+                NEXT_ID => Some(MediaKeyAction::Next),
+                _ => None,
+            };
+            if let (Some(action), Some(sink)) = (action, CLICK_SINK.get()) {
+                sink(action);
+                return LRESULT(0);
+            }
+        }
+        DefSubclassProc(hwnd, msg, wparam, lparam)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media_session::NowPlaying;
+
+    fn snapshot(status: PlaybackStatus, position_secs: Option<f64>, duration_secs: Option<f64>) -> PlayerSnapshot {
+        PlayerSnapshot {
+            now_playing: Some(NowPlaying {
+                title: "Song".to_string(),
+                artist: None,
+                album: None,
+                duration_secs,
+                file_path: None,
+                artwork_url: None,
+            }),
+            status,
+            position_secs,
+            queue: Vec::new(),
+            queue_index: None,
+            position_updated: None,
+        }
+    }
+
+    #[test]
+    fn test_progress_from_snapshot() {
+        let now = Instant::now();
+        let progress = |s| TaskbarProgress::from_snapshot(&s, now);
+        assert_eq!(progress(snapshot(PlaybackStatus::Playing, Some(30.0), Some(120.0))), TaskbarProgress::Playing(25));
+        assert_eq!(progress(snapshot(PlaybackStatus::Paused, Some(60.0), Some(120.0))), TaskbarProgress::Paused(50));
+        assert_eq!(progress(snapshot(PlaybackStatus::Stopped, Some(60.0), Some(120.0))), TaskbarProgress::Hidden);
+        // Streams without a known length show nothing rather than a stuck bar
+        assert_eq!(progress(snapshot(PlaybackStatus::Playing, Some(60.0), None)), TaskbarProgress::Hidden);
+    }
+
+    #[test]
+    fn test_progress_advances_while_playing() {
+        let reported = Instant::now();
+        let mut playing = snapshot(PlaybackStatus::Playing, Some(10.0), Some(100.0));
+        playing.position_updated = Some(reported);
+        let later = reported + Duration::from_secs(40);
+        assert_eq!(TaskbarProgress::from_snapshot(&playing, later), TaskbarProgress::Playing(50));
+        assert_eq!(TaskbarProgress::from_snapshot(&playing, later + Duration::from_secs(500)), TaskbarProgress::Playing(100));
+
+        playing.status = PlaybackStatus::Paused;
+        assert_eq!(TaskbarProgress::from_snapshot(&playing, later), TaskbarProgress::Paused(10));
+    }
+
+    #[test]
+    fn test_glyph_masks() {
+        let size = 16;
+        for glyph in [Glyph::Previous, Glyph::Play, Glyph::Pause, Glyph::Next] {
+            let mask = glyph_mask(glyph, size);
+            assert_eq!(mask.len(), size * size);
+            assert!(mask.iter().any(|on| *on), "{:?} is empty", glyph);
+        }
+
+        // Previous is Next mirrored horizontally
+        let next = glyph_mask(Glyph::Next, size);
+        let previous = glyph_mask(Glyph::Previous, size);
+        for y in 0..size {
+            for x in 0..size {
+                assert_eq!(previous[y * size + x], next[y * size + size - 1 - x]);
+            }
+        }
+    }
+}
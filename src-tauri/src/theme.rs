@@ -0,0 +1,224 @@
+// Theme color resolution
+// Merges skin colors, album artwork palette, and user overrides into a single theme
+
+use crate::config::ThemeOverrides;
+use crate::skin::{ParsedSkin, PlaylistStyle, RgbColor, VIS_COLOR_COUNT};
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Visualizer palette of the base Winamp 2.x skin, used when a skin has no viscolor.txt
+pub const DEFAULT_VIS_COLORS: [RgbColor; VIS_COLOR_COUNT] = [
+    RgbColor::new(0, 0, 0),
+    RgbColor::new(24, 33, 41),
+    RgbColor::new(239, 49, 16),
+    RgbColor::new(206, 41, 16),
+    RgbColor::new(214, 90, 0),
+    RgbColor::new(214, 102, 0),
+    RgbColor::new(214, 115, 0),
+    RgbColor::new(198, 123, 8),
+    RgbColor::new(222, 165, 24),
+    RgbColor::new(214, 181, 33),
+    RgbColor::new(189, 222, 41),
+    RgbColor::new(148, 222, 33),
+    RgbColor::new(41, 206, 16),
+    RgbColor::new(50, 190, 16),
+    RgbColor::new(57, 181, 16),
+    RgbColor::new(49, 156, 8),
+    RgbColor::new(41, 148, 0),
+    RgbColor::new(24, 132, 8),
+    RgbColor::new(255, 255, 255),
+    RgbColor::new(214, 214, 222),
+    RgbColor::new(181, 189, 189),
+    RgbColor::new(160, 170, 175),
+    RgbColor::new(148, 156, 165),
+    RgbColor::new(150, 150, 150),
+];
+
+/// Number of colors extracted from album artwork
+pub const ARTWORK_PALETTE_SIZE: usize = 5;
+
+/// Colors taken from the most recently applied skin
+#[derive(Debug, Clone, Default)]
+struct SkinColors {
+    playlist_style: Option<PlaylistStyle>,
+    vis_colors: Option<Vec<RgbColor>>,
+}
+
+static ACTIVE_SKIN_COLORS: Mutex<Option<SkinColors>> = Mutex::new(None);
+
+/// The resolved theme sent to the frontend
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActiveTheme {
+    pub vis_colors: Vec<RgbColor>,
+    pub playlist: PlaylistStyle,
+    pub accent: RgbColor,
+    pub artwork_palette: Vec<RgbColor>,
+}
+
+/// Remember the colors of a newly applied skin
+pub fn set_skin_colors(skin: &ParsedSkin) {
+    let mut active = ACTIVE_SKIN_COLORS.lock().unwrap();
+    *active = Some(SkinColors {
+        playlist_style: skin.playlist_style.clone(),
+        vis_colors: skin.vis_colors.clone(),
+    });
+}
+
+/// Resolve the active theme
+///
+/// User overrides win over skin colors, which win over the classic defaults.
+/// The accent is the override if set, else the artwork's dominant color, else
+/// the skin's current-track color.
+pub fn resolve_theme(overrides: &ThemeOverrides, artwork_palette: Vec<RgbColor>) -> ActiveTheme {
+    let skin = ACTIVE_SKIN_COLORS.lock().unwrap().clone().unwrap_or_default();
+    merge_theme(&skin, overrides, artwork_palette)
+}
+
+fn merge_theme(skin: &SkinColors, overrides: &ThemeOverrides, artwork_palette: Vec<RgbColor>) -> ActiveTheme {
+    let mut vis_colors = skin
+        .vis_colors
+        .clone()
+        .unwrap_or_else(|| DEFAULT_VIS_COLORS.to_vec());
+    if let Some(custom) = &overrides.visualizer {
+        // Overrides may replace only the leading entries of the palette
+        for (slot, hex) in vis_colors.iter_mut().zip(custom) {
+            if let Some(color) = RgbColor::from_hex(hex) {
+                *slot = color;
+            }
+        }
+    }
+
+    let mut playlist = skin.playlist_style.clone().unwrap_or_default();
+    if let Some(color) = overrides.playlist_text.as_deref().and_then(RgbColor::from_hex) {
+        playlist.normal = color;
+    }
+    if let Some(color) = overrides.playlist_background.as_deref().and_then(RgbColor::from_hex) {
+        playlist.normal_bg = color;
+    }
+
+    let accent = overrides
+        .accent
+        .as_deref()
+        .and_then(RgbColor::from_hex)
+        .or_else(|| artwork_palette.first().copied())
+        .unwrap_or(playlist.current);
+
+    ActiveTheme {
+        vis_colors,
+        playlist,
+        accent,
+        artwork_palette,
+    }
+}
+
+/// Extract the dominant colors of an encoded image, most common first
+///
+/// The image is downsampled and colors are bucketed to 4 bits per channel;
+/// each bucket reports the average of its pixels. Returns an empty palette
+/// if the image cannot be decoded.
+pub fn extract_palette(image_bytes: &[u8], count: usize) -> Vec<RgbColor> {
+    let Ok(img) = image::load_from_memory(image_bytes) else {
+        return Vec::new();
+    };
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let thumb = if width > 64 || height > 64 {
+        img.thumbnail(64, 64).to_rgb8()
+    } else {
+        img.to_rgb8()
+    };
+
+    // bucket -> (pixel count, summed r, g, b)
+    let mut buckets: HashMap<u16, (u32, u32, u32, u32)> = HashMap::new();
+    for pixel in thumb.pixels() {
+        let [r, g, b] = pixel.0;
+        let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+        let entry = buckets.entry(key).or_insert((0, 0, 0, 0));
+        entry.0 += 1;
+        entry.1 += r as u32;
+        entry.2 += g as u32;
+        entry.3 += b as u32;
+    }
+
+    let mut ranked: Vec<_> = buckets.into_iter().collect();
+    // Sort by count, then bucket key so ties are deterministic
+    ranked.sort_by(|a, b| b.1 .0.cmp(&a.1 .0).then(a.0.cmp(&b.0)));
+    ranked
+        .into_iter()
+        .take(count)
+        .map(|(_, (n, r, g, b))| RgbColor::new((r / n) as u8, (g / n) as u8, (b / n) as u8))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb};
+    use std::io::Cursor;
+
+    fn png_bytes(img: ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_defaults_without_skin_or_overrides() {
+        let theme = merge_theme(&SkinColors::default(), &ThemeOverrides::default(), Vec::new());
+        assert_eq!(theme.vis_colors, DEFAULT_VIS_COLORS.to_vec());
+        assert_eq!(theme.playlist, PlaylistStyle::default());
+        assert_eq!(theme.accent, PlaylistStyle::default().current);
+    }
+
+    #[test]
+    fn test_skin_colors_then_overrides() {
+        let skin = SkinColors {
+            playlist_style: None,
+            vis_colors: Some(vec![RgbColor::new(1, 2, 3); VIS_COLOR_COUNT]),
+        };
+        let overrides = ThemeOverrides {
+            accent: Some("#102030".to_string()),
+            visualizer: Some(vec!["#ff0000".to_string(), "bogus".to_string()]),
+            playlist_text: Some("00ff00".to_string()),
+            playlist_background: None,
+        };
+        let artwork = vec![RgbColor::new(9, 9, 9)];
+
+        let theme = merge_theme(&skin, &overrides, artwork);
+        assert_eq!(theme.vis_colors[0], RgbColor::new(255, 0, 0));
+        // Invalid override entries keep the skin color
+        assert_eq!(theme.vis_colors[1], RgbColor::new(1, 2, 3));
+        assert_eq!(theme.playlist.normal, RgbColor::new(0, 255, 0));
+        assert_eq!(theme.accent, RgbColor::new(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn test_artwork_accent_without_override() {
+        let artwork = vec![RgbColor::new(200, 10, 10)];
+        let theme = merge_theme(&SkinColors::default(), &ThemeOverrides::default(), artwork);
+        assert_eq!(theme.accent, RgbColor::new(200, 10, 10));
+    }
+
+    #[test]
+    fn test_extract_palette_dominant_first() {
+        // Three quarters blue, one quarter red
+        let img = ImageBuffer::from_fn(40, 40, |x, _| {
+            if x < 10 { Rgb([250, 0, 0]) } else { Rgb([0, 0, 250]) }
+        });
+        let palette = extract_palette(&png_bytes(img), ARTWORK_PALETTE_SIZE);
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette[0], RgbColor::new(0, 0, 250));
+        assert_eq!(palette[1], RgbColor::new(250, 0, 0));
+    }
+
+    #[test]
+    fn test_extract_palette_invalid_image() {
+        assert!(extract_palette(&[1, 2, 3], ARTWORK_PALETTE_SIZE).is_empty());
+    }
+}
@@ -0,0 +1,522 @@
+// Tempo, key and silence analysis for library tracks
+// Decodes a track to low-rate mono PCM through FFmpeg. Leading and trailing
+// silence are measured at the track's ends, tempo and key over up to two
+// minutes from its middle; long tracks only have those parts decoded. Tempo is the strongest periodicity of the
+// spectral-flux onset envelope (as aubio's tempo tracker does), weighted
+// towards common tempos so half- and double-time readings lose; key is the
+// Krumhansl-Kessler profile that best correlates with the track's chroma.
+// Results are stored per file on disk and stamped like the metadata cache, so
+// unchanged files are analysed once.
+
+use crate::audio_dsp::fft;
+use crate::media_editor::audio_ops;
+use crate::metadata::TrackMetadata;
+use crate::metadata_cache::FileStamp;
+use crate::waveform::{decode_mono, decode_mono_range, WaveformError};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::Path;
+use thiserror::Error;
+
+/// Decode rate; keeps chroma up to the top of the piano's melodic range
+pub const ANALYSIS_SAMPLE_RATE: u32 = 11025;
+
+/// Longest stretch analysed, taken from the middle of the track
+pub const MAX_ANALYSIS_SEC: usize = 120;
+
+/// Seconds decoded at each end of a long track to measure its silence
+const SILENCE_EDGE_SEC: f64 = 60.0;
+
+/// Onset envelope frames: ~93ms windows every ~12ms
+const ONSET_FFT_SIZE: usize = 1024;
+const ONSET_HOP: usize = 128;
+
+/// Chroma frames: long windows for semitone resolution in the bass
+const CHROMA_FFT_SIZE: usize = 4096;
+const CHROMA_HOP: usize = 2048;
+
+const MIN_BPM: f32 = 60.0;
+const MAX_BPM: f32 = 200.0;
+
+/// Tempo the prior is centred on, and its spread in octaves
+const PRIOR_BPM: f32 = 120.0;
+const PRIOR_OCTAVES: f32 = 1.0;
+
+/// Frequencies folded into the chroma, A1 to about B6
+const CHROMA_MIN_HZ: f32 = 55.0;
+const CHROMA_MAX_HZ: f32 = 2000.0;
+
+/// Krumhansl-Kessler key profiles, from the tonic up
+const MAJOR_PROFILE: [f32; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// Windows quieter than this count as silence
+const SILENCE_THRESHOLD_DB: f32 = -50.0;
+
+/// RMS window for silence detection
+const SILENCE_WINDOW_MS: usize = 50;
+
+/// Shorter gaps are left alone, so skipping never clips a fade-in
+const MIN_SILENCE_SEC: f64 = 0.5;
+
+const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+#[derive(Error, Debug)]
+pub enum AnalysisError {
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Failed to decode audio: {0}")]
+    Decode(String),
+    #[error("Analysis database error: {0}")]
+    Database(#[from] sled::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+impl From<WaveformError> for AnalysisError {
+    fn from(err: WaveformError) -> Self {
+        match err {
+            WaveformError::FileNotFound(path) => AnalysisError::FileNotFound(path),
+            other => AnalysisError::Decode(other.to_string()),
+        }
+    }
+}
+
+/// A major or minor key; `tonic` is a pitch class with C = 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MusicalKey {
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl MusicalKey {
+    /// Camelot wheel notation, e.g. "8A" for A minor and "8B" for C major
+    pub fn camelot(&self) -> String {
+        let major_tonic = if self.minor { (self.tonic + 3) % 12 } else { self.tonic };
+        let number = (7 * major_tonic as u32 % 12 + 7) % 12 + 1;
+        format!("{}{}", number, if self.minor { 'A' } else { 'B' })
+    }
+
+    /// Parse a key as tagged: "Am", "A minor", "F#m", "Bb", "Ebmaj" or Camelot "8A"
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(key) = Self::parse_camelot(text) {
+            return Some(key);
+        }
+        let mut chars = text.chars();
+        let mut tonic: i32 = match chars.next()?.to_ascii_uppercase() {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+        let mut rest = chars.as_str();
+        if let Some(after) = rest.strip_prefix(['#', '♯']) {
+            tonic += 1;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix(['b', '♭']) {
+            tonic -= 1;
+            rest = after;
+        }
+        let minor = match rest.trim().to_lowercase().as_str() {
+            "" | "maj" | "major" => false,
+            "m" | "min" | "minor" => true,
+            _ => return None,
+        };
+        Some(MusicalKey { tonic: tonic.rem_euclid(12) as u8, minor })
+    }
+
+    fn parse_camelot(text: &str) -> Option<Self> {
+        let minor = match text.chars().last()?.to_ascii_uppercase() {
+            'A' => true,
+            'B' => false,
+            _ => return None,
+        };
+        let number: i32 = text[..text.len() - 1].parse().ok()?;
+        if !(1..=12).contains(&number) {
+            return None;
+        }
+        // Each step round the wheel is a fifth, and 7 is its own inverse mod 12
+        let major_tonic = (7 * (number - 8).rem_euclid(12)) % 12;
+        let tonic = if minor { (major_tonic + 9) % 12 } else { major_tonic };
+        Some(MusicalKey { tonic: tonic as u8, minor })
+    }
+}
+
+impl fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", PITCH_NAMES[self.tonic as usize], if self.minor { "minor" } else { "major" })
+    }
+}
+
+/// Silence at either end of a track, in seconds; zero when there is none to skip
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Silence {
+    pub leading_sec: f64,
+    pub trailing_sec: f64,
+    pub duration_sec: f64,
+}
+
+/// Analysed tempo, key and silence of one file
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TrackAnalysis {
+    /// Beats per minute, to one decimal
+    pub bpm: Option<f32>,
+    /// Key name, e.g. "A minor"
+    pub key: Option<String>,
+    /// The key in Camelot notation, e.g. "8A"
+    pub camelot: Option<String>,
+    /// Missing from analyses stored before silence detection
+    #[serde(default)]
+    pub silence: Option<Silence>,
+}
+
+/// Analyse the file at `path`
+pub fn analyze(path: &Path) -> Result<TrackAnalysis, AnalysisError> {
+    if !path.exists() {
+        return Err(AnalysisError::FileNotFound(path.to_string_lossy().to_string()));
+    }
+    let duration_sec = audio_ops::probe_audio_metadata(&path.to_string_lossy())
+        .ok()
+        .map(|probe| probe.duration_sec)
+        .filter(|&duration| duration > MAX_ANALYSIS_SEC as f64 + 2.0 * SILENCE_EDGE_SEC);
+    let (silence, samples) = match duration_sec {
+        // Long tracks: decode only the ends and the analysis window
+        Some(duration) => {
+            let range = |start: f64, length: f64| decode_mono_range(path, ANALYSIS_SAMPLE_RATE, start, length).map(to_f32);
+            let head = range(0.0, SILENCE_EDGE_SEC)?;
+            let tail = range(duration - SILENCE_EDGE_SEC, SILENCE_EDGE_SEC)?;
+            let middle = range((duration - MAX_ANALYSIS_SEC as f64) / 2.0, MAX_ANALYSIS_SEC as f64)?;
+            (edge_silence(&head, &tail, duration, ANALYSIS_SAMPLE_RATE), middle)
+        }
+        None => {
+            let samples = to_f32(decode_mono(path, ANALYSIS_SAMPLE_RATE)?);
+            let silence = detect_silence(&samples, ANALYSIS_SAMPLE_RATE);
+            let window = MAX_ANALYSIS_SEC * ANALYSIS_SAMPLE_RATE as usize;
+            let start = samples.len().saturating_sub(window) / 2;
+            (silence, samples[start..(start + window).min(samples.len())].to_vec())
+        }
+    };
+
+    let key = detect_key(&samples, ANALYSIS_SAMPLE_RATE);
+    Ok(TrackAnalysis {
+        bpm: detect_bpm(&samples, ANALYSIS_SAMPLE_RATE).map(|bpm| (bpm * 10.0).round() / 10.0),
+        key: key.map(|k| k.to_string()),
+        camelot: key.map(|k| k.camelot()),
+        silence: Some(silence),
+    })
+}
+
+fn to_f32(samples: Vec<i16>) -> Vec<f32> {
+    samples.into_iter().map(|s| s as f32 / i16::MAX as f32).collect()
+}
+
+/// Silence of a `duration_sec` track from its decoded first and last seconds
+///
+/// An end that is silent throughout counts as silence of its whole length.
+fn edge_silence(head: &[f32], tail: &[f32], duration_sec: f64, sample_rate: u32) -> Silence {
+    let head = detect_silence(head, sample_rate);
+    let tail = detect_silence(tail, sample_rate);
+    let tail_silent = tail.leading_sec >= tail.duration_sec;
+    Silence {
+        leading_sec: head.leading_sec,
+        trailing_sec: if tail_silent { tail.duration_sec } else { tail.trailing_sec },
+        duration_sec,
+    }
+}
+
+/// Leading and trailing silence, measured in RMS windows against `SILENCE_THRESHOLD_DB`
+///
+/// A track that is silent throughout is all leading silence.
+pub fn detect_silence(samples: &[f32], sample_rate: u32) -> Silence {
+    let duration_sec = samples.len() as f64 / sample_rate as f64;
+    let window = (sample_rate as usize * SILENCE_WINDOW_MS / 1000).max(1);
+    let threshold = 10f32.powf(SILENCE_THRESHOLD_DB / 20.0);
+    let loud: Vec<bool> = samples
+        .chunks(window)
+        .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt() >= threshold)
+        .collect();
+    let window_sec = window as f64 / sample_rate as f64;
+    let (Some(first), Some(last)) = (loud.iter().position(|&l| l), loud.iter().rposition(|&l| l)) else {
+        return Silence { leading_sec: duration_sec, trailing_sec: 0.0, duration_sec };
+    };
+    let worth_skipping = |sec: f64| if sec >= MIN_SILENCE_SEC { sec } else { 0.0 };
+    Silence {
+        leading_sec: worth_skipping(first as f64 * window_sec),
+        trailing_sec: worth_skipping((duration_sec - (last + 1) as f64 * window_sec).max(0.0)),
+        duration_sec,
+    }
+}
+
+fn hann(size: usize) -> Vec<f32> {
+    (0..size).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos()).collect()
+}
+
+/// Windowed FFT magnitudes of every full frame, for bins 0..size/2
+fn spectrogram(samples: &[f32], size: usize, hop: usize) -> Vec<Vec<f32>> {
+    let window = hann(size);
+    (0..samples.len().saturating_sub(size) / hop + usize::from(samples.len() >= size))
+        .map(|frame| {
+            let start = frame * hop;
+            let mut re: Vec<f32> = samples[start..start + size].iter().zip(&window).map(|(s, w)| s * w).collect();
+            let mut im = vec![0.0; size];
+            fft(&mut re, &mut im);
+            re.iter().zip(&im).take(size / 2).map(|(r, i)| (r * r + i * i).sqrt()).collect()
+        })
+        .collect()
+}
+
+/// Tempo in BPM, or `None` when there is no steady pulse
+pub fn detect_bpm(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let frames = spectrogram(samples, ONSET_FFT_SIZE, ONSET_HOP);
+    // Spectral flux on log magnitudes, so quiet percussion still registers
+    let mut envelope: Vec<f32> = frames
+        .windows(2)
+        .map(|pair| {
+            pair[1]
+                .iter()
+                .zip(&pair[0])
+                .map(|(now, before)| ((1.0 + 10.0 * now).ln() - (1.0 + 10.0 * before).ln()).max(0.0))
+                .sum()
+        })
+        .collect();
+    let mean = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+    if mean <= f32::EPSILON {
+        return None;
+    }
+    envelope.iter_mut().for_each(|e| *e -= mean);
+
+    let frame_rate = sample_rate as f32 / ONSET_HOP as f32;
+    let min_lag = (frame_rate * 60.0 / MAX_BPM).floor() as usize;
+    let max_lag = (frame_rate * 60.0 / MIN_BPM).ceil() as usize;
+    if envelope.len() <= max_lag + 1 {
+        return None;
+    }
+    let autocorrelation: Vec<f32> = (0..=max_lag + 1)
+        .map(|lag| envelope.iter().zip(&envelope[lag..]).map(|(a, b)| a * b).sum::<f32>() / (envelope.len() - lag) as f32)
+        .collect();
+
+    let prior = |lag: f32| {
+        let octaves = (frame_rate * 60.0 / lag / PRIOR_BPM).log2() / PRIOR_OCTAVES;
+        (-0.5 * octaves * octaves).exp()
+    };
+    let best = (min_lag.max(1)..=max_lag)
+        .filter(|&lag| autocorrelation[lag] > 0.0)
+        .max_by(|&a, &b| (autocorrelation[a] * prior(a as f32)).total_cmp(&(autocorrelation[b] * prior(b as f32))))?;
+
+    // Parabolic interpolation between neighbouring lags for sub-frame precision
+    let (before, peak, after) = (autocorrelation[best - 1], autocorrelation[best], autocorrelation[best + 1]);
+    let curvature = before - 2.0 * peak + after;
+    let offset = if curvature < 0.0 { (0.5 * (before - after) / curvature).clamp(-0.5, 0.5) } else { 0.0 };
+    Some(frame_rate * 60.0 / (best as f32 + offset))
+}
+
+/// Most likely key, or `None` for silence or unpitched audio
+pub fn detect_key(samples: &[f32], sample_rate: u32) -> Option<MusicalKey> {
+    let bin_hz = sample_rate as f32 / CHROMA_FFT_SIZE as f32;
+    let pitch_classes: Vec<Option<usize>> = (0..CHROMA_FFT_SIZE / 2)
+        .map(|bin| {
+            let hz = bin as f32 * bin_hz;
+            (CHROMA_MIN_HZ..=CHROMA_MAX_HZ)
+                .contains(&hz)
+                .then(|| ((12.0 * (hz / 440.0).log2()).round() as i32 + 9).rem_euclid(12) as usize)
+        })
+        .collect();
+
+    let mut chroma = [0.0f32; 12];
+    for frame in spectrogram(samples, CHROMA_FFT_SIZE, CHROMA_HOP) {
+        for (magnitude, pitch_class) in frame.iter().zip(&pitch_classes) {
+            if let Some(pitch_class) = pitch_class {
+                chroma[*pitch_class] += magnitude;
+            }
+        }
+    }
+    if chroma.iter().sum::<f32>() <= f32::EPSILON {
+        return None;
+    }
+
+    (0..12u8)
+        .flat_map(|tonic| [false, true].map(|minor| MusicalKey { tonic, minor }))
+        .map(|key| {
+            let profile = if key.minor { &MINOR_PROFILE } else { &MAJOR_PROFILE };
+            let rotated: Vec<f32> = (0..12).map(|pc| profile[(pc + 12 - key.tonic as usize) % 12]).collect();
+            (key, correlation(&chroma, &rotated))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(key, _)| key)
+}
+
+/// Pearson correlation of two equal-length series
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let mean = |v: &[f32]| v.iter().sum::<f32>() / v.len() as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a) * (x - mean_a);
+        var_b += (y - mean_b) * (y - mean_b);
+    }
+    covariance / (var_a * var_b).sqrt().max(f32::EPSILON)
+}
+
+/// Fill tempo and key from `analysis` where the tags have none
+pub fn fill_metadata(metadata: &mut TrackMetadata, analysis: &TrackAnalysis) {
+    metadata.bpm = metadata.bpm.or(analysis.bpm);
+    metadata.key = metadata.key.take().or_else(|| analysis.key.clone());
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredAnalysis {
+    stamp: FileStamp,
+    analysis: TrackAnalysis,
+}
+
+/// Analysis results on disk, keyed by path and dropped once the file changes
+pub struct AnalysisStore {
+    db: sled::Db,
+}
+
+impl AnalysisStore {
+    /// Open (or create) the store in `dir`
+    pub fn open(dir: &Path) -> Result<Self, AnalysisError> {
+        Ok(Self { db: sled::open(dir)? })
+    }
+
+    /// Stored analysis of `path`, if the file hasn't changed since
+    pub fn get(&self, path: &Path) -> Option<TrackAnalysis> {
+        let stamp = FileStamp::of(path)?;
+        let key = crate::path_codec::encode(path);
+        let stored = self.db.get(key.as_bytes()).ok().flatten()?;
+        serde_json::from_slice::<StoredAnalysis>(&stored)
+            .ok()
+            .filter(|stored| stored.stamp == stamp)
+            .map(|stored| stored.analysis)
+    }
+
+    pub fn put(&self, path: &Path, analysis: &TrackAnalysis) -> Result<(), AnalysisError> {
+        let Some(stamp) = FileStamp::of(path) else {
+            return Err(AnalysisError::FileNotFound(path.to_string_lossy().to_string()));
+        };
+        let stored = StoredAnalysis { stamp, analysis: analysis.clone() };
+        self.db.insert(crate::path_codec::encode(path).as_bytes(), serde_json::to_vec(&stored)?)?;
+        Ok(())
+    }
+}
+
+/// Default location of the analysis store
+pub fn default_store_dir() -> Option<std::path::PathBuf> {
+    dirs::data_local_dir().map(|d| d.join("milk").join("analysis"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = ANALYSIS_SAMPLE_RATE;
+
+    /// Short decaying noise bursts at `bpm` for `seconds`
+    fn clicks(bpm: f32, seconds: usize) -> Vec<f32> {
+        let period = RATE as f32 * 60.0 / bpm;
+        let mut seed = 1u32;
+        let mut samples = vec![0.0; seconds * RATE as usize];
+        let mut beat = 0.0;
+        while (beat as usize) < samples.len() {
+            for (i, sample) in samples[beat as usize..].iter_mut().take(400).enumerate() {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (seed >> 16) as f32 / 32768.0 - 1.0;
+                *sample = noise * (-(i as f32) / 80.0).exp();
+            }
+            beat += period;
+        }
+        samples
+    }
+
+    fn chord(frequencies: &[f32], seconds: usize) -> Vec<f32> {
+        (0..seconds * RATE as usize)
+            .map(|i| {
+                let t = i as f32 / RATE as f32;
+                frequencies.iter().map(|f| (2.0 * PI * f * t).sin()).sum::<f32>() / frequencies.len() as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_bpm() {
+        for bpm in [90.0, 120.0, 128.0, 174.0] {
+            let detected = detect_bpm(&clicks(bpm, 30), RATE).unwrap();
+            assert!((detected - bpm).abs() < 2.0, "expected {} BPM, got {}", bpm, detected);
+        }
+        assert_eq!(detect_bpm(&vec![0.0; 10 * RATE as usize], RATE), None);
+    }
+
+    #[test]
+    fn test_detect_key() {
+        let a_minor = MusicalKey { tonic: 9, minor: true };
+        assert_eq!(detect_key(&chord(&[220.0, 261.63, 329.63], 5), RATE), Some(a_minor));
+        let g_major = MusicalKey { tonic: 7, minor: false };
+        assert_eq!(detect_key(&chord(&[196.0, 246.94, 293.66], 5), RATE), Some(g_major));
+        assert_eq!(detect_key(&vec![0.0; RATE as usize], RATE), None);
+    }
+
+    #[test]
+    fn test_detect_silence() {
+        let rate = RATE as usize;
+        let mut samples = vec![0.0; 2 * rate];
+        samples.extend(chord(&[440.0], 3));
+        samples.extend(vec![0.001; 4 * rate]);
+        let silence = detect_silence(&samples, RATE);
+        assert!((silence.leading_sec - 2.0).abs() < 0.06, "{:?}", silence);
+        assert!((silence.trailing_sec - 4.0).abs() < 0.06, "{:?}", silence);
+        assert!((silence.duration_sec - 9.0).abs() < 1e-9);
+
+        // A short gap before the music starts is kept
+        let mut samples = vec![0.0; rate / 10];
+        samples.extend(chord(&[440.0], 1));
+        assert_eq!(detect_silence(&samples, RATE).leading_sec, 0.0);
+        assert_eq!(detect_silence(&vec![0.0; rate], RATE).leading_sec, 1.0);
+    }
+
+    #[test]
+    fn test_edge_silence() {
+        let rate = RATE as usize;
+        let mut head = vec![0.0; 3 * rate];
+        head.extend(chord(&[440.0], 2));
+        let mut tail = chord(&[440.0], 2);
+        tail.extend(vec![0.0; 2 * rate]);
+        let silence = edge_silence(&head, &tail, 3600.0, RATE);
+        assert!((silence.leading_sec - 3.0).abs() < 0.06, "{:?}", silence);
+        assert!((silence.trailing_sec - 2.0).abs() < 0.06, "{:?}", silence);
+        assert_eq!(silence.duration_sec, 3600.0);
+
+        let silent = vec![0.0; 5 * rate];
+        assert_eq!(edge_silence(&head, &silent, 3600.0, RATE).trailing_sec, 5.0);
+    }
+
+    #[test]
+    fn test_key_notation() {
+        let a_minor = MusicalKey { tonic: 9, minor: true };
+        assert_eq!(a_minor.to_string(), "A minor");
+        assert_eq!(a_minor.camelot(), "8A");
+        for text in ["Am", "A minor", "amin", "8A", "8a"] {
+            assert_eq!(MusicalKey::parse(text), Some(a_minor), "{}", text);
+        }
+        assert_eq!(MusicalKey::parse("Bb").unwrap().to_string(), "Bb major");
+        assert_eq!(MusicalKey::parse("F#m").unwrap().camelot(), "11A");
+        assert_eq!(MusicalKey::parse("Cb"), Some(MusicalKey { tonic: 11, minor: false }));
+        for tonic in 0..12 {
+            for minor in [false, true] {
+                let key = MusicalKey { tonic, minor };
+                assert_eq!(MusicalKey::parse(&key.camelot()), Some(key));
+                assert_eq!(MusicalKey::parse(&key.to_string()), Some(key));
+            }
+        }
+        assert_eq!(MusicalKey::parse("13A"), None);
+        assert_eq!(MusicalKey::parse("H"), None);
+        assert_eq!(MusicalKey::parse("o"), None);
+    }
+}
@@ -0,0 +1,77 @@
+// Track notes
+// Free-text notes on library files, such as cue points or mixing hints. Notes
+// are kept in the KV store by file path, like ratings, so they survive rescans
+// and never touch the files; the library index shows and searches them
+// alongside the files' own comment tags.
+
+use crate::kv_store::{KvError, KvStore};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Key prefix for per-file notes in the KV store
+pub const NOTE_KEY_PREFIX: &str = "note.";
+
+pub const MAX_NOTE_CHARS: usize = 4000;
+
+#[derive(Error, Debug)]
+pub enum NoteError {
+    #[error("Notes are limited to {MAX_NOTE_CHARS} characters, got {0}")]
+    TooLong(usize),
+    #[error("Failed to store note: {0}")]
+    Store(#[from] KvError),
+}
+
+/// KV store key holding the note on a file
+pub fn note_key(file_path: &str) -> String {
+    format!("{}{}", NOTE_KEY_PREFIX, file_path)
+}
+
+pub fn get_note(store: &KvStore, file_path: &str) -> Option<String> {
+    store.get(&note_key(file_path))
+}
+
+/// Every note, by file path
+pub fn all_notes(store: &KvStore) -> HashMap<String, String> {
+    store.entries_with_prefix(NOTE_KEY_PREFIX).into_iter().collect()
+}
+
+/// Store a note, trimmed; a blank or missing note removes the entry
+///
+/// Returns the note as stored.
+pub fn set_note(store: &KvStore, file_path: &str, note: Option<&str>) -> Result<Option<String>, NoteError> {
+    let note = note.map(str::trim).filter(|note| !note.is_empty());
+    let key = note_key(file_path);
+    match note {
+        Some(note) => {
+            let chars = note.chars().count();
+            if chars > MAX_NOTE_CHARS {
+                return Err(NoteError::TooLong(chars));
+            }
+            store.set(&key, &note)?;
+        }
+        None => {
+            store.remove(&key)?;
+        }
+    }
+    Ok(note.map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_clear_note() {
+        let store = KvStore::in_memory();
+        assert_eq!(set_note(&store, "/music/a.mp3", Some("  intro 0:16, drop 1:04\n")).unwrap().as_deref(), Some("intro 0:16, drop 1:04"));
+        assert_eq!(get_note(&store, "/music/a.mp3").as_deref(), Some("intro 0:16, drop 1:04"));
+        assert_eq!(all_notes(&store).len(), 1);
+
+        assert_eq!(set_note(&store, "/music/a.mp3", Some("   ")).unwrap(), None);
+        assert_eq!(get_note(&store, "/music/a.mp3"), None);
+        assert!(all_notes(&store).is_empty());
+
+        let long = "x".repeat(MAX_NOTE_CHARS + 1);
+        assert!(matches!(set_note(&store, "/music/a.mp3", Some(&long)), Err(NoteError::TooLong(_))));
+    }
+}
@@ -0,0 +1,241 @@
+// System tray icon with mini transport controls
+// Menu clicks are forwarded as `media-control` like OS media keys; the
+// tooltip follows the current track. With minimize/close-to-tray the main
+// window hides instead and `tray-window` tells the frontend.
+
+use crate::config::TraySettings;
+use crate::logging::{log_info, log_warn};
+use crate::media_session::{MediaKeyAction, NowPlaying};
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{AppHandle, Emitter, Manager, Runtime, Window, WindowEvent};
+
+const TRAY_ID: &str = "main";
+
+/// Tooltip shown when nothing is playing
+const IDLE_TOOLTIP: &str = "milk";
+
+/// Windows truncates notification area tooltips past this many characters
+const MAX_TOOLTIP_CHARS: usize = 127;
+
+/// Settings in effect, read on every window event so they must stay cheap to reach
+static ACTIVE_SETTINGS: Mutex<Option<TraySettings>> = Mutex::new(None);
+
+/// Entries of the tray context menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayMenuAction {
+    PlayPause,
+    Next,
+    Previous,
+    Show,
+    Quit,
+}
+
+impl TrayMenuAction {
+    const ALL: [TrayMenuAction; 5] = [Self::PlayPause, Self::Next, Self::Previous, Self::Show, Self::Quit];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::PlayPause => "play_pause",
+            Self::Next => "next",
+            Self::Previous => "previous",
+            Self::Show => "show",
+            Self::Quit => "quit",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::PlayPause => "Play/Pause",
+            Self::Next => "Next",
+            Self::Previous => "Previous",
+            Self::Show => "Show milk",
+            Self::Quit => "Quit",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    /// The player action to forward, for the transport entries
+    pub fn media_action(self) -> Option<MediaKeyAction> {
+        match self {
+            Self::PlayPause => Some(MediaKeyAction::Toggle),
+            Self::Next => Some(MediaKeyAction::Next),
+            Self::Previous => Some(MediaKeyAction::Previous),
+            Self::Show | Self::Quit => None,
+        }
+    }
+}
+
+/// Payload of the `tray-window` event
+#[derive(Debug, Clone, Serialize)]
+pub struct TrayWindowEvent {
+    /// False when the window was hidden to the tray
+    pub visible: bool,
+}
+
+/// "Artist - Title", or just the title, cut to what the OS will display
+pub fn tooltip_text(track: Option<&NowPlaying>) -> String {
+    let text = match track {
+        Some(NowPlaying { title, artist: Some(artist), .. }) if !artist.is_empty() => format!("{} - {}", artist, title),
+        Some(track) if !track.title.is_empty() => track.title.clone(),
+        _ => return IDLE_TOOLTIP.to_string(),
+    };
+    if text.chars().count() <= MAX_TOOLTIP_CHARS {
+        return text;
+    }
+    let mut cut: String = text.chars().take(MAX_TOOLTIP_CHARS - 1).collect();
+    cut.push('…');
+    cut
+}
+
+/// Create or remove the tray icon to match `settings`
+pub fn apply<R: Runtime>(app: &AppHandle<R>, settings: &TraySettings) -> tauri::Result<()> {
+    *ACTIVE_SETTINGS.lock().unwrap() = Some(settings.clone());
+    let existing = app.tray_by_id(TRAY_ID);
+    match (settings.enabled, existing) {
+        (true, None) => {
+            build(app)?;
+            log_info("Tray", "Tray icon created");
+        }
+        (false, Some(_)) => {
+            app.remove_tray_by_id(TRAY_ID);
+            log_info("Tray", "Tray icon removed");
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn build<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<TrayIcon<R>> {
+    let items = TrayMenuAction::ALL
+        .into_iter()
+        .map(|action| MenuItem::with_id(app, action.id(), action.label(), true, None::<&str>))
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let transport_separator = PredefinedMenuItem::separator(app)?;
+    let quit_separator = PredefinedMenuItem::separator(app)?;
+    let menu = Menu::with_items(
+        app,
+        &[&items[0], &items[1], &items[2], &transport_separator, &items[3], &quit_separator, &items[4]],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip(IDLE_TOOLTIP)
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(handle_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            // Left click brings the player back, as in most media players
+            if let TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } = event {
+                show_main_window(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)
+}
+
+fn handle_menu_event<R: Runtime>(app: &AppHandle<R>, event: MenuEvent) {
+    let Some(action) = TrayMenuAction::from_id(event.id().as_ref()) else {
+        return;
+    };
+    if let Some(media_action) = action.media_action() {
+        if let Err(e) = app.emit("media-control", media_action) {
+            log_warn("Tray", &format!("Failed to emit media-control event: {}", e));
+        }
+        return;
+    }
+    match action {
+        TrayMenuAction::Show => show_main_window(app),
+        TrayMenuAction::Quit => app.exit(0),
+        _ => {}
+    }
+}
+
+/// Update the tooltip to the current track; a no-op while the tray is disabled
+pub fn set_tooltip<R: Runtime>(app: &AppHandle<R>, track: Option<&NowPlaying>) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        if let Err(e) = tray.set_tooltip(Some(tooltip_text(track))) {
+            log_warn("Tray", &format!("Failed to update tray tooltip: {}", e));
+        }
+    }
+}
+
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show().and_then(|_| window.unminimize()).and_then(|_| window.set_focus());
+        let _ = app.emit("tray-window", TrayWindowEvent { visible: true });
+    }
+}
+
+/// Hide the main window to the tray on minimize or close when configured
+///
+/// Only applies while the tray icon exists, otherwise the window could not
+/// be brought back.
+pub fn handle_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    if window.label() != "main" {
+        return;
+    }
+    let Some(settings) = ACTIVE_SETTINGS.lock().unwrap().clone() else {
+        return;
+    };
+    if !settings.enabled {
+        return;
+    }
+    let hide = match event {
+        WindowEvent::CloseRequested { .. } => settings.close_to_tray,
+        WindowEvent::Resized(_) => settings.minimize_to_tray && window.is_minimized().unwrap_or(false),
+        _ => false,
+    };
+    if !hide || window.app_handle().tray_by_id(TRAY_ID).is_none() {
+        return;
+    }
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        api.prevent_close();
+    }
+    let _ = window.hide();
+    let _ = window.emit("tray-window", TrayWindowEvent { visible: false });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: Option<&str>) -> NowPlaying {
+        NowPlaying {
+            title: title.to_string(),
+            artist: artist.map(str::to_string),
+            album: None,
+            duration_secs: None,
+            file_path: None,
+            artwork_url: None,
+        }
+    }
+
+    #[test]
+    fn test_tooltip_text() {
+        assert_eq!(tooltip_text(None), "milk");
+        assert_eq!(tooltip_text(Some(&track("Song", Some("Artist")))), "Artist - Song");
+        assert_eq!(tooltip_text(Some(&track("Song", Some("")))), "Song");
+        assert_eq!(tooltip_text(Some(&track("", None))), "milk");
+
+        let long = tooltip_text(Some(&track(&"ä".repeat(200), None)));
+        assert_eq!(long.chars().count(), MAX_TOOLTIP_CHARS);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn test_menu_ids_round_trip() {
+        for action in TrayMenuAction::ALL {
+            assert_eq!(TrayMenuAction::from_id(action.id()), Some(action));
+        }
+        assert_eq!(TrayMenuAction::from_id("unknown"), None);
+        assert!(matches!(TrayMenuAction::PlayPause.media_action(), Some(MediaKeyAction::Toggle)));
+        assert!(TrayMenuAction::Quit.media_action().is_none());
+    }
+}
@@ -0,0 +1,514 @@
+// Milkdrop preset support for the visualizer
+// Parses .milk preset files and tracks the preset rotation (next/previous/shuffle)
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum VisualizerError {
+    #[error("Failed to read preset: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Preset not found: {0}")]
+    NotFound(String),
+    #[error("No presets loaded")]
+    NoPresets,
+}
+
+/// A single `target = expression` statement from a preset equation block
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Equation {
+    /// Assigned variable, or None for bare expressions
+    pub target: Option<String>,
+    pub expression: String,
+}
+
+/// A custom waveform (`wavecode_N` / `wave_N_*`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CustomWave {
+    pub index: u32,
+    pub parameters: BTreeMap<String, f64>,
+    pub init: Vec<Equation>,
+    pub per_frame: Vec<Equation>,
+    pub per_point: Vec<Equation>,
+}
+
+/// A custom shape (`shapecode_N` / `shape_N_*`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CustomShape {
+    pub index: u32,
+    pub parameters: BTreeMap<String, f64>,
+    pub init: Vec<Equation>,
+    pub per_frame: Vec<Equation>,
+}
+
+/// A parsed Milkdrop preset
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MilkPreset {
+    pub name: String,
+    pub path: String,
+    /// Numeric base values such as `fDecay` or `zoom`
+    pub parameters: BTreeMap<String, f64>,
+    pub per_frame_init: Vec<Equation>,
+    pub per_frame: Vec<Equation>,
+    pub per_pixel: Vec<Equation>,
+    pub waves: Vec<CustomWave>,
+    pub shapes: Vec<CustomShape>,
+    /// Milkdrop 2 warp shader source, if present
+    pub warp_shader: Option<String>,
+    /// Milkdrop 2 composite shader source, if present
+    pub comp_shader: Option<String>,
+}
+
+/// A preset file found while scanning
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PresetInfo {
+    pub name: String,
+    pub path: String,
+}
+
+/// Numbered code lines collected per block before being joined in order
+#[derive(Default)]
+struct CodeLines(BTreeMap<u32, String>);
+
+impl CodeLines {
+    fn insert(&mut self, index: u32, line: &str) {
+        self.0.insert(index, line.to_string());
+    }
+
+    fn equations(&self) -> Vec<Equation> {
+        let code: Vec<&str> = self.0.values().map(|l| strip_comment(l)).collect();
+        parse_equations(&code.join(" "))
+    }
+
+    /// Shader lines are prefixed with a backtick in the preset file
+    fn shader(&self) -> Option<String> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let lines: Vec<&str> = self.0.values().map(|l| l.strip_prefix('`').unwrap_or(l)).collect();
+        Some(lines.join("\n"))
+    }
+}
+
+#[derive(Default)]
+struct WaveBuilder {
+    parameters: BTreeMap<String, f64>,
+    init: CodeLines,
+    per_frame: CodeLines,
+    per_point: CodeLines,
+}
+
+#[derive(Default)]
+struct ShapeBuilder {
+    parameters: BTreeMap<String, f64>,
+    init: CodeLines,
+    per_frame: CodeLines,
+}
+
+pub struct PresetParser;
+
+impl PresetParser {
+    /// Parse a .milk file from disk
+    pub fn parse_file(path: &Path) -> Result<MilkPreset, VisualizerError> {
+        let bytes = fs::read(path)?;
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let mut preset = Self::parse(&String::from_utf8_lossy(&bytes));
+        preset.name = name;
+        preset.path = path.to_string_lossy().to_string();
+        Ok(preset)
+    }
+
+    /// Parse the text of a .milk preset
+    pub fn parse(text: &str) -> MilkPreset {
+        let mut preset = MilkPreset::default();
+        let mut per_frame_init = CodeLines::default();
+        let mut per_frame = CodeLines::default();
+        let mut per_pixel = CodeLines::default();
+        let mut warp = CodeLines::default();
+        let mut comp = CodeLines::default();
+        let mut waves: BTreeMap<u32, WaveBuilder> = BTreeMap::new();
+        let mut shapes: BTreeMap<u32, ShapeBuilder> = BTreeMap::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('[') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let lower = key.to_lowercase();
+
+            // Order matters: per_frame_init_ shares the per_frame_ prefix
+            if let Some(n) = numbered(&lower, "per_frame_init_") {
+                per_frame_init.insert(n, value);
+            } else if let Some(n) = numbered(&lower, "per_frame_") {
+                per_frame.insert(n, value);
+            } else if let Some(n) = numbered(&lower, "per_pixel_") {
+                per_pixel.insert(n, value);
+            } else if let Some(n) = numbered(&lower, "warp_") {
+                warp.insert(n, value);
+            } else if let Some(n) = numbered(&lower, "comp_") {
+                comp.insert(n, value);
+            } else if let Some((index, param)) = indexed(&lower, "wavecode_") {
+                if let Ok(v) = value.trim().parse::<f64>() {
+                    waves.entry(index).or_default().parameters.insert(param.to_string(), v);
+                }
+            } else if let Some((index, rest)) = indexed(&lower, "wave_") {
+                let wave = waves.entry(index).or_default();
+                if let Some(n) = numbered(rest, "init") {
+                    wave.init.insert(n, value);
+                } else if let Some(n) = numbered(rest, "per_frame") {
+                    wave.per_frame.insert(n, value);
+                } else if let Some(n) = numbered(rest, "per_point") {
+                    wave.per_point.insert(n, value);
+                }
+            } else if let Some((index, param)) = indexed(&lower, "shapecode_") {
+                if let Ok(v) = value.trim().parse::<f64>() {
+                    shapes.entry(index).or_default().parameters.insert(param.to_string(), v);
+                }
+            } else if let Some((index, rest)) = indexed(&lower, "shape_") {
+                let shape = shapes.entry(index).or_default();
+                if let Some(n) = numbered(rest, "init") {
+                    shape.init.insert(n, value);
+                } else if let Some(n) = numbered(rest, "per_frame") {
+                    shape.per_frame.insert(n, value);
+                }
+            } else if let Ok(v) = value.trim().parse::<f64>() {
+                preset.parameters.insert(key.to_string(), v);
+            }
+        }
+
+        preset.per_frame_init = per_frame_init.equations();
+        preset.per_frame = per_frame.equations();
+        preset.per_pixel = per_pixel.equations();
+        preset.warp_shader = warp.shader();
+        preset.comp_shader = comp.shader();
+        preset.waves = waves
+            .into_iter()
+            .map(|(index, w)| CustomWave {
+                index,
+                parameters: w.parameters,
+                init: w.init.equations(),
+                per_frame: w.per_frame.equations(),
+                per_point: w.per_point.equations(),
+            })
+            .collect();
+        preset.shapes = shapes
+            .into_iter()
+            .map(|(index, s)| CustomShape {
+                index,
+                parameters: s.parameters,
+                init: s.init.equations(),
+                per_frame: s.per_frame.equations(),
+            })
+            .collect();
+        preset
+    }
+}
+
+/// Parse `prefix<N>` into N
+fn numbered(key: &str, prefix: &str) -> Option<u32> {
+    key.strip_prefix(prefix)?.parse().ok()
+}
+
+/// Parse `prefix<N>_<rest>` into (N, rest)
+fn indexed<'a>(key: &'a str, prefix: &str) -> Option<(u32, &'a str)> {
+    let (index, rest) = key.strip_prefix(prefix)?.split_once('_')?;
+    Some((index.parse().ok()?, rest))
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split("//").next().unwrap_or("")
+}
+
+/// Split equation code into statements and separate assignment targets
+pub fn parse_equations(code: &str) -> Vec<Equation> {
+    code.split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|statement| match assignment_split(statement) {
+            Some(pos) => Equation {
+                target: Some(statement[..pos].trim().to_string()),
+                expression: statement[pos + 1..].trim().to_string(),
+            },
+            None => Equation {
+                target: None,
+                expression: statement.to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Find the `=` of an assignment, skipping `==`, `<=`, `>=` and `!=`
+fn assignment_split(statement: &str) -> Option<usize> {
+    let bytes = statement.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        if b != b'=' {
+            continue;
+        }
+        let prev = if i > 0 { bytes[i - 1] } else { 0 };
+        let next = bytes.get(i + 1).copied().unwrap_or(0);
+        if next == b'=' || matches!(prev, b'=' | b'<' | b'>' | b'!') {
+            continue;
+        }
+        let target = statement[..i].trim();
+        let is_identifier = !target.is_empty()
+            && target.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        return is_identifier.then_some(i);
+    }
+    None
+}
+
+/// Tracks the scanned presets and the rotation position
+pub struct PresetManager {
+    presets: Vec<PresetInfo>,
+    current: Option<usize>,
+    /// Previously shown presets, most recent last, for `previous` in shuffle mode
+    history: Vec<usize>,
+    shuffle: bool,
+}
+
+impl PresetManager {
+    /// Upper bound on remembered history entries
+    const MAX_HISTORY: usize = 100;
+
+    pub fn new() -> Self {
+        Self {
+            presets: Vec::new(),
+            current: None,
+            history: Vec::new(),
+            shuffle: false,
+        }
+    }
+
+    /// Scan a directory (non-recursively) for .milk files, sorted by name
+    pub fn scan_directory(&mut self, dir: &Path) -> Result<&[PresetInfo], VisualizerError> {
+        let mut presets = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_milk = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| e.eq_ignore_ascii_case("milk"));
+            if path.is_file() && is_milk {
+                presets.push(PresetInfo {
+                    name: path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default(),
+                    path: path.to_string_lossy().to_string(),
+                });
+            }
+        }
+        presets.sort_by(|a, b| crate::sorting::natural_cmp(&a.name, &b.name));
+
+        self.presets = presets;
+        self.current = None;
+        self.history.clear();
+        Ok(&self.presets)
+    }
+
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+    }
+
+    /// Load a preset by name or full path and make it current
+    pub fn load(&mut self, name_or_path: &str) -> Result<MilkPreset, VisualizerError> {
+        let index = self
+            .presets
+            .iter()
+            .position(|p| p.name == name_or_path || p.path == name_or_path)
+            .ok_or_else(|| VisualizerError::NotFound(name_or_path.to_string()))?;
+        self.select(index)
+    }
+
+    /// Advance to the next preset (random when shuffling)
+    pub fn next(&mut self) -> Result<MilkPreset, VisualizerError> {
+        let count = self.presets.len();
+        if count == 0 {
+            return Err(VisualizerError::NoPresets);
+        }
+        let index = if self.shuffle && count > 1 {
+            // Pick any preset other than the current one
+            let mut rng = rand::thread_rng();
+            match self.current {
+                Some(current) => {
+                    let candidate = rng.gen_range(0..count - 1);
+                    if candidate >= current { candidate + 1 } else { candidate }
+                }
+                None => rng.gen_range(0..count),
+            }
+        } else {
+            self.current.map(|current| (current + 1) % count).unwrap_or(0)
+        };
+        self.select(index)
+    }
+
+    /// Go back to the previously shown preset, or step backwards in order
+    pub fn previous(&mut self) -> Result<MilkPreset, VisualizerError> {
+        let count = self.presets.len();
+        if count == 0 {
+            return Err(VisualizerError::NoPresets);
+        }
+        if self.shuffle {
+            if let Some(index) = self.history.pop() {
+                self.current = Some(index);
+                return PresetParser::parse_file(Path::new(&self.presets[index].path));
+            }
+        }
+        let index = match self.current {
+            Some(current) => (current + count - 1) % count,
+            None => count - 1,
+        };
+        self.current = Some(index);
+        PresetParser::parse_file(Path::new(&self.presets[index].path))
+    }
+
+    fn select(&mut self, index: usize) -> Result<MilkPreset, VisualizerError> {
+        let preset = PresetParser::parse_file(Path::new(&self.presets[index].path))?;
+        if let Some(current) = self.current {
+            self.history.push(current);
+            if self.history.len() > Self::MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.current = Some(index);
+        Ok(preset)
+    }
+}
+
+impl Default for PresetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Default preset directory inside the app data folder
+pub fn get_default_presets_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("milk").join("presets"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const SAMPLE: &str = "[preset00]
+fRating=3.000000
+fDecay=0.980000
+zoom=1.010
+bAdditiveWaves=1
+per_frame_init_1=q1 = 0;
+per_frame_1=wave_r = 0.5 + 0.5*sin(time);
+per_frame_2=wave_g = 0.5; // green
+per_frame_10=rot = if(above(bass,1.2), 0.1, 0);
+per_pixel_1=zoom = zoom + 0.02*rad;
+wavecode_0_enabled=1
+wavecode_0_samples=512
+wave_0_per_point1=x = sample; y = value1;
+shapecode_1_sides=4
+shape_1_per_frame1=ang = time*0.3;
+warp_1=`shader_body {
+warp_2=`}
+";
+
+    #[test]
+    fn test_parse_parameters_and_equations() {
+        let preset = PresetParser::parse(SAMPLE);
+        assert_eq!(preset.parameters.get("fDecay"), Some(&0.98));
+        assert_eq!(preset.parameters.get("zoom"), Some(&1.01));
+        assert_eq!(preset.per_frame_init.len(), 1);
+
+        // Lines are ordered numerically, not lexically
+        let targets: Vec<_> = preset.per_frame.iter().map(|e| e.target.clone().unwrap()).collect();
+        assert_eq!(targets, vec!["wave_r", "wave_g", "rot"]);
+        assert_eq!(preset.per_frame[0].expression, "0.5 + 0.5*sin(time)");
+        assert_eq!(preset.per_pixel[0].target.as_deref(), Some("zoom"));
+    }
+
+    #[test]
+    fn test_parse_waves_shapes_and_shaders() {
+        let preset = PresetParser::parse(SAMPLE);
+        assert_eq!(preset.waves.len(), 1);
+        assert_eq!(preset.waves[0].parameters.get("samples"), Some(&512.0));
+        assert_eq!(preset.waves[0].per_point.len(), 2);
+        assert_eq!(preset.shapes[0].index, 1);
+        assert_eq!(preset.shapes[0].per_frame[0].target.as_deref(), Some("ang"));
+        assert_eq!(preset.warp_shader.as_deref(), Some("shader_body {\n}"));
+        assert!(preset.comp_shader.is_none());
+    }
+
+    #[test]
+    fn test_comparisons_are_not_assignments() {
+        let equations = parse_equations("x == 1; y = a >= b; z <= 2");
+        assert_eq!(equations[0].target, None);
+        assert_eq!(equations[1].target.as_deref(), Some("y"));
+        assert_eq!(equations[1].expression, "a >= b");
+        assert_eq!(equations[2].target, None);
+    }
+
+    fn presets_dir(names: &[&str]) -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for name in names {
+            fs::write(temp_dir.path().join(name), SAMPLE).unwrap();
+        }
+        fs::write(temp_dir.path().join("readme.txt"), "not a preset").unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_scan_and_rotate() {
+        let temp_dir = presets_dir(&["b.milk", "a.milk", "c.MILK"]);
+        let mut manager = PresetManager::new();
+        let names: Vec<_> = manager
+            .scan_directory(temp_dir.path())
+            .unwrap()
+            .iter()
+            .map(|p| p.name.clone())
+            .collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+
+        assert_eq!(manager.next().unwrap().name, "a");
+        assert_eq!(manager.next().unwrap().name, "b");
+        assert_eq!(manager.previous().unwrap().name, "a");
+        assert_eq!(manager.previous().unwrap().name, "c");
+        assert_eq!(manager.load("b").unwrap().name, "b");
+        assert!(matches!(manager.load("missing"), Err(VisualizerError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_shuffle_never_repeats_and_previous_uses_history() {
+        let temp_dir = presets_dir(&["a.milk", "b.milk", "c.milk"]);
+        let mut manager = PresetManager::new();
+        manager.scan_directory(temp_dir.path()).unwrap();
+        manager.set_shuffle(true);
+
+        let mut last = manager.next().unwrap().name;
+        let mut shown = vec![last.clone()];
+        for _ in 0..20 {
+            let name = manager.next().unwrap().name;
+            assert_ne!(name, last);
+            shown.push(name.clone());
+            last = name;
+        }
+
+        // Walk back through what was shown
+        shown.pop();
+        assert_eq!(manager.previous().unwrap().name, shown.pop().unwrap());
+        assert_eq!(manager.previous().unwrap().name, shown.pop().unwrap());
+    }
+
+    #[test]
+    fn test_empty_manager() {
+        let mut manager = PresetManager::new();
+        assert!(matches!(manager.next(), Err(VisualizerError::NoPresets)));
+        assert!(matches!(manager.previous(), Err(VisualizerError::NoPresets)));
+    }
+}
@@ -0,0 +1,197 @@
+// Visualizer feed
+// Shared analysis for every audio source the visualizer can follow: mono
+// blocks are run through the spectrum analyzer and the frames handed to the
+// remote API, visualizer plugins and Winamp visualization plugins. System
+// audio capture takes precedence while it runs; otherwise the player taps its
+// own output, so visualization keeps working where loopback capture is
+// unavailable. Taps only queue samples; the analysis and the consumers run on
+// a worker thread, never in an audio callback.
+
+use crate::audio_dsp::{SpectrumAnalyzer, VisualizerFrame, FFT_SIZE};
+use crate::audio_transport::{FrameThrottle, SampleRing};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Samples a tap holds for its worker; about a second of stereo audio at 48kHz
+const TAP_CAPACITY: usize = 1 << 17;
+
+/// How long a worker sleeps when its tap has nothing queued
+const TAP_IDLE: Duration = Duration::from_millis(10);
+
+/// Set while system audio capture is delivering frames
+static CAPTURE_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Record whether system audio capture is running; the player tap yields while it is
+pub fn set_capture_active(active: bool) {
+    CAPTURE_ACTIVE.store(active, Ordering::Relaxed);
+}
+
+pub fn capture_active() -> bool {
+    CAPTURE_ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Analysis state for one source; beats in throttled-away frames carry over
+pub struct VisualizerFeed {
+    analyzer: SpectrumAnalyzer,
+    block: Vec<f32>,
+    throttle: FrameThrottle,
+    pending_beat: bool,
+    pending_onset: bool,
+}
+
+impl VisualizerFeed {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            analyzer: SpectrumAnalyzer::new(sample_rate),
+            block: Vec::with_capacity(FFT_SIZE),
+            throttle: FrameThrottle::default(),
+            pending_beat: false,
+            pending_onset: false,
+        }
+    }
+
+    /// Analyze one full mono block; `None` when throttled by `max_fps`
+    pub fn analyze(&mut self, block: &[f32], max_fps: Option<u32>) -> Option<VisualizerFrame> {
+        let mut frame = self.analyzer.process(block);
+        self.pending_beat |= frame.beat;
+        self.pending_onset |= frame.onset;
+        if !self.throttle.ready(max_fps, Instant::now()) {
+            return None;
+        }
+        frame.beat = std::mem::take(&mut self.pending_beat);
+        frame.onset = std::mem::take(&mut self.pending_onset);
+        Some(frame)
+    }
+
+    /// Mix interleaved samples down to mono, analyzing every block as it fills
+    ///
+    /// `on_frame` gets each frame that passes the throttle with its samples.
+    pub fn push(
+        &mut self,
+        samples: &[f32],
+        channels: usize,
+        max_fps: Option<u32>,
+        mut on_frame: impl FnMut(&VisualizerFrame, &[f32]),
+    ) {
+        for frame in samples.chunks_exact(channels.max(1)) {
+            self.block.push(frame.iter().sum::<f32>() / frame.len() as f32);
+            if self.block.len() < FFT_SIZE {
+                continue;
+            }
+            let block = std::mem::replace(&mut self.block, Vec::with_capacity(FFT_SIZE));
+            if let Some(frame) = self.analyze(&block, max_fps) {
+                on_frame(&frame, &block);
+            }
+        }
+    }
+}
+
+/// Player output queued for analysis on a worker thread
+///
+/// Pushing never blocks or allocates, so it is safe from an audio callback;
+/// samples that do not fit are dropped. The worker stops once every other
+/// handle to the tap is gone.
+pub struct AudioTap {
+    ring: SampleRing,
+    /// Sample rate in the high half, channels in the low half
+    format: AtomicU64,
+}
+
+impl AudioTap {
+    pub fn spawn(sample_rate: u32, channels: usize) -> Arc<Self> {
+        let tap = Arc::new(Self { ring: SampleRing::new(TAP_CAPACITY), format: AtomicU64::new(pack_format(sample_rate, channels)) });
+        let worker = tap.clone();
+        std::thread::Builder::new()
+            .name("visualizer-tap".to_string())
+            .spawn(move || worker.run())
+            .expect("failed to spawn the visualizer tap worker");
+        tap
+    }
+
+    /// Queue interleaved samples in the current format
+    pub fn push(&self, samples: &[f32]) {
+        self.ring.push(samples);
+    }
+
+    /// Samples pushed from now on are in this format
+    pub fn set_format(&self, sample_rate: u32, channels: usize) {
+        self.format.store(pack_format(sample_rate, channels), Ordering::Release);
+    }
+
+    fn run(self: Arc<Self>) {
+        let mut format = self.format.load(Ordering::Acquire);
+        let mut feed = VisualizerFeed::new(unpack_format(format).0);
+        let mut scratch = vec![0.0; FFT_SIZE * 2];
+        while Arc::strong_count(&self) > 1 {
+            let popped = self.ring.pop(&mut scratch);
+            if popped == 0 {
+                std::thread::sleep(TAP_IDLE);
+                continue;
+            }
+            // System capture has the visualizer; drain so the tap never backs up
+            if capture_active() {
+                continue;
+            }
+            let current = self.format.load(Ordering::Acquire);
+            if current != format {
+                format = current;
+                feed = VisualizerFeed::new(unpack_format(format).0);
+            }
+            feed.push(&scratch[..popped], unpack_format(format).1, None, publish);
+        }
+    }
+}
+
+fn pack_format(sample_rate: u32, channels: usize) -> u64 {
+    (sample_rate as u64) << 32 | channels.max(1) as u64 & u32::MAX as u64
+}
+
+fn unpack_format(format: u64) -> (u32, usize) {
+    ((format >> 32) as u32, (format & u32::MAX as u64) as usize)
+}
+
+/// Tap for the windowed player, whose audio is played by the webview
+pub fn webview_tap() -> &'static Arc<AudioTap> {
+    static TAP: OnceLock<Arc<AudioTap>> = OnceLock::new();
+    TAP.get_or_init(|| AudioTap::spawn(48_000, 1))
+}
+
+/// Hand a frame to every in-process consumer
+pub fn publish(frame: &VisualizerFrame, samples: &[f32]) {
+    crate::remote_api::publish_visualizer_frame(frame);
+    crate::plugins::visualizer_frame(frame);
+    crate::winamp_vis::feed(frame, samples);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_analyzes_full_blocks() {
+        let mut feed = VisualizerFeed::new(48_000);
+        let stereo: Vec<f32> = (0..FFT_SIZE * 3).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        let mut blocks = Vec::new();
+        feed.push(&stereo, 2, None, |frame, block| blocks.push((frame.sample_rate, block.to_vec())));
+        // 1.5 blocks of frames: one analyzed, the rest kept for the next push
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, 48_000);
+        assert!(blocks[0].1.iter().all(|s| *s == 0.0));
+        assert_eq!(feed.block.len(), FFT_SIZE / 2);
+    }
+
+    #[test]
+    fn test_format_round_trips() {
+        assert_eq!(unpack_format(pack_format(44_100, 2)), (44_100, 2));
+        assert_eq!(unpack_format(pack_format(192_000, 0)), (192_000, 1));
+    }
+
+    #[test]
+    fn test_throttled_frames_are_dropped() {
+        let mut feed = VisualizerFeed::new(48_000);
+        let block = vec![0.0; FFT_SIZE];
+        assert!(feed.analyze(&block, Some(1)).is_some());
+        assert!(feed.analyze(&block, Some(1)).is_none());
+    }
+}
@@ -0,0 +1,207 @@
+// Waveform peak generation for the seek bar
+// Decodes a track to low-rate mono PCM through FFmpeg and reduces it to
+// min/max peak pairs. Results are cached on disk per file and resolution.
+
+use crate::metadata_cache::FileStamp;
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+
+/// Decode rate; plenty for peaks and keeps long tracks small in memory
+pub const DECODE_SAMPLE_RATE: u32 = 8000;
+
+pub const MAX_RESOLUTION: usize = 8192;
+
+#[derive(Error, Debug)]
+pub enum WaveformError {
+    #[error("Waveform resolution must be between 1 and {MAX_RESOLUTION}, got {0}")]
+    InvalidResolution(usize),
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Failed to decode audio: {0}")]
+    Decode(String),
+    #[error("Waveform cache error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Lowest and highest sample in one bucket, both in -1.0..=1.0
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct WaveformPeak {
+    pub min: f32,
+    pub max: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Waveform {
+    pub duration_sec: f64,
+    /// One entry per bucket, evenly spaced over the track
+    pub peaks: Vec<WaveformPeak>,
+}
+
+/// Reduce samples to `resolution` min/max buckets
+///
+/// Buckets past the end of a very short track stay silent.
+pub fn compute_peaks(samples: &[i16], resolution: usize) -> Vec<WaveformPeak> {
+    (0..resolution)
+        .map(|bucket| {
+            let start = bucket * samples.len() / resolution;
+            let end = ((bucket + 1) * samples.len() / resolution).max(start);
+            let slice = &samples[start..end];
+            match (slice.iter().min(), slice.iter().max()) {
+                (Some(&min), Some(&max)) => WaveformPeak { min: normalize(min), max: normalize(max) },
+                _ => WaveformPeak::default(),
+            }
+        })
+        .collect()
+}
+
+fn normalize(sample: i16) -> f32 {
+    (sample as f32 / i16::MAX as f32).clamp(-1.0, 1.0)
+}
+
+/// Decode a file to mono 16-bit samples at `sample_rate`
+pub fn decode_mono(path: &Path, sample_rate: u32) -> Result<Vec<i16>, WaveformError> {
+    run_decode(path, sample_rate, None)
+}
+
+/// Decode `length_sec` seconds from `start_sec` to mono 16-bit samples at `sample_rate`
+pub fn decode_mono_range(path: &Path, sample_rate: u32, start_sec: f64, length_sec: f64) -> Result<Vec<i16>, WaveformError> {
+    run_decode(path, sample_rate, Some((start_sec, length_sec)))
+}
+
+fn run_decode(path: &Path, sample_rate: u32, range: Option<(f64, f64)>) -> Result<Vec<i16>, WaveformError> {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-v").arg("error");
+    if let Some((start, length)) = range {
+        // Before the input, so FFmpeg seeks instead of decoding up to the start
+        command.args(["-ss", &format!("{:.3}", start.max(0.0)), "-t", &format!("{:.3}", length)]);
+    }
+    let output = command
+        .arg("-i")
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-ar", &sample_rate.to_string(), "-f", "s16le", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| WaveformError::Decode(format!("failed to execute FFmpeg: {}", e)))?;
+    if !output.status.success() {
+        return Err(WaveformError::Decode(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect())
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    stamp: FileStamp,
+    waveform: Waveform,
+}
+
+/// One JSON file per track and resolution, invalidated when the track changes
+pub struct WaveformCache {
+    dir: PathBuf,
+}
+
+impl WaveformCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn entry_path(&self, path: &Path, resolution: usize) -> PathBuf {
+        let digest = Md5::digest(crate::path_codec::encode(path).as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}-{}.json", name, resolution))
+    }
+
+    pub fn get(&self, path: &Path, stamp: FileStamp, resolution: usize) -> Option<Waveform> {
+        let content = fs::read_to_string(self.entry_path(path, resolution)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+        (entry.stamp == stamp).then_some(entry.waveform)
+    }
+
+    pub fn put(&self, path: &Path, stamp: FileStamp, resolution: usize, waveform: &Waveform) -> Result<(), WaveformError> {
+        fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry { stamp, waveform: waveform.clone() };
+        let content = serde_json::to_string(&entry).map_err(std::io::Error::other)?;
+        fs::write(self.entry_path(path, resolution), content)?;
+        Ok(())
+    }
+}
+
+pub fn default_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join("milk").join("waveforms"))
+}
+
+/// Waveform for `path` with `resolution` buckets, from the cache when the file is unchanged
+pub fn generate_waveform(path: &Path, resolution: usize, cache: Option<&WaveformCache>) -> Result<Waveform, WaveformError> {
+    if resolution == 0 || resolution > MAX_RESOLUTION {
+        return Err(WaveformError::InvalidResolution(resolution));
+    }
+    let stamp = FileStamp::of(path).ok_or_else(|| WaveformError::FileNotFound(path.display().to_string()))?;
+    if let Some(waveform) = cache.and_then(|c| c.get(path, stamp, resolution)) {
+        return Ok(waveform);
+    }
+
+    let samples = decode_mono(path, DECODE_SAMPLE_RATE)?;
+    let waveform = Waveform {
+        duration_sec: samples.len() as f64 / DECODE_SAMPLE_RATE as f64,
+        peaks: compute_peaks(&samples, resolution),
+    };
+    if let Some(cache) = cache {
+        // A failed cache write only costs a re-decode next time
+        let _ = cache.put(path, stamp, resolution, &waveform);
+    }
+    Ok(waveform)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compute_peaks() {
+        let samples = [0, i16::MAX, -i16::MAX, 100, -100, 0];
+        let peaks = compute_peaks(&samples, 3);
+        assert_eq!(peaks[0], WaveformPeak { min: 0.0, max: 1.0 });
+        assert_eq!(peaks[1].min, -1.0);
+        assert_eq!(peaks[2].max, 0.0);
+
+        // More buckets than samples leaves the gaps silent
+        let sparse = compute_peaks(&[i16::MAX], 4);
+        assert_eq!(sparse.len(), 4);
+        assert_eq!(sparse.iter().filter(|p| p.max == 1.0).count(), 1);
+        assert!(compute_peaks(&[i16::MIN], 1)[0].min >= -1.0);
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_stamp() {
+        let dir = TempDir::new().unwrap();
+        let cache = WaveformCache::new(dir.path());
+        let track = Path::new("/music/a.mp3");
+        let stamp = FileStamp { mtime_ms: 1, size: 10 };
+        let waveform = Waveform { duration_sec: 1.0, peaks: vec![WaveformPeak { min: -0.5, max: 0.5 }] };
+
+        cache.put(track, stamp, 1, &waveform).unwrap();
+        assert_eq!(cache.get(track, stamp, 1), Some(waveform));
+        assert_eq!(cache.get(track, stamp, 2), None);
+        assert_eq!(cache.get(track, FileStamp { mtime_ms: 2, size: 10 }, 1), None);
+    }
+
+    #[test]
+    fn test_invalid_resolution() {
+        assert!(matches!(
+            generate_waveform(Path::new("/missing.mp3"), 0, None),
+            Err(WaveformError::InvalidResolution(0))
+        ));
+        assert!(matches!(
+            generate_waveform(Path::new("/missing.mp3"), 100, None),
+            Err(WaveformError::FileNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,593 @@
+// Classic Winamp visualization plugins
+// Hosts `vis_*.dll` plugins written against the Winamp 2/5 vis API on Windows.
+// The plugin gets a hidden stand-in for the Winamp main window that answers
+// the few IPC queries vis plugins commonly make, and its spectrum and waveform
+// buffers are filled from system audio capture before every Render call.
+//
+// Plugins run in-process on their own thread, so one that crashes takes the app
+// with it; unlike `plugins` there is no sandbox for native code. Almost all
+// classic plugins are 32-bit builds, which a 64-bit app cannot load; those are
+// listed as incompatible rather than hidden. Plugins that embed themselves into
+// Winamp's own frame windows are not supported.
+
+// Only hosted on Windows; the pure parts are built everywhere for the tests
+#![cfg_attr(not(target_os = "windows"), allow(dead_code))]
+
+use crate::audio_dsp::VisualizerFrame;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+/// Length of each channel's spectrum and waveform buffer in the vis API
+const VIS_SAMPLES: usize = 576;
+
+/// Spectrum values below this level show as 0
+const SPECTRUM_FLOOR_DB: f32 = -60.0;
+
+/// `IMAGE_FILE_HEADER.Machine` values
+const MACHINE_X86: u16 = 0x014c;
+const MACHINE_X64: u16 = 0x8664;
+const MACHINE_ARM64: u16 = 0xaa64;
+
+/// Set while a plugin is running so capture only converts data when needed
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+static LATEST: Mutex<Option<VisData>> = Mutex::new(None);
+
+static ACTIVE: Mutex<Option<ActiveVis>> = Mutex::new(None);
+
+#[derive(Error, Debug)]
+pub enum WinampVisError {
+    #[error("Winamp plugin not found: {0}")]
+    NotFound(String),
+    #[error("{file} can't be loaded: {reason}")]
+    Incompatible { file: String, reason: String },
+    #[error("Winamp plugins are only supported on Windows")]
+    Unsupported,
+    #[error("Failed to load Winamp plugin: {0}")]
+    Load(String),
+    #[error("Winamp plugin failed to start: {0}")]
+    Init(String),
+}
+
+/// Returned by `list_winamp_plugins`
+#[derive(Debug, Clone, Serialize)]
+pub struct WinampPluginInfo {
+    pub file_name: String,
+    pub path: String,
+    /// `x86`, `x64`, `arm64` or `unknown`
+    pub architecture: String,
+    /// Whether this build of the app can load it
+    pub compatible: bool,
+    /// Why it can't be loaded, if it can't
+    pub reason: Option<String>,
+}
+
+/// Returned by `start_winamp_vis`
+#[derive(Debug, Clone, Serialize)]
+pub struct WinampVisInfo {
+    pub path: String,
+    /// Index of the module within the plugin
+    pub module: u32,
+    pub description: String,
+}
+
+/// Buffers in the layout the vis API expects, for one channel
+#[derive(Clone)]
+struct VisData {
+    spectrum: [u8; VIS_SAMPLES],
+    waveform: [u8; VIS_SAMPLES],
+    sample_rate: u32,
+}
+
+struct ActiveVis {
+    info: WinampVisInfo,
+    stop: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+/// Map linear FFT magnitudes spanning 0 Hz to Nyquist onto the vis API's
+/// 576 spectrum values, 0-255 on a decibel scale
+fn spectrum_data(bins: &[f32]) -> [u8; VIS_SAMPLES] {
+    let mut spectrum = [0; VIS_SAMPLES];
+    if bins.is_empty() {
+        return spectrum;
+    }
+    for (i, value) in spectrum.iter_mut().enumerate() {
+        let magnitude = bins[i * bins.len() / VIS_SAMPLES];
+        let db = 20.0 * magnitude.max(f32::MIN_POSITIVE).log10();
+        *value = ((1.0 - db / SPECTRUM_FLOOR_DB).clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    spectrum
+}
+
+/// The last 576 samples as signed 8-bit values, as the vis API stores them
+fn waveform_data(samples: &[f32]) -> [u8; VIS_SAMPLES] {
+    let mut waveform = [0; VIS_SAMPLES];
+    let recent = &samples[samples.len().saturating_sub(VIS_SAMPLES)..];
+    for (value, sample) in waveform.iter_mut().zip(recent) {
+        *value = (sample.clamp(-1.0, 1.0) * 127.0).round() as i8 as u8;
+    }
+    waveform
+}
+
+/// Read the target machine from a DLL's PE header
+fn pe_machine(bytes: &[u8]) -> Option<u16> {
+    if bytes.get(..2)? != b"MZ" {
+        return None;
+    }
+    let pe_offset = u32::from_le_bytes(bytes.get(0x3c..0x40)?.try_into().ok()?) as usize;
+    if bytes.get(pe_offset..pe_offset + 4)? != b"PE\0\0" {
+        return None;
+    }
+    Some(u16::from_le_bytes(bytes.get(pe_offset + 4..pe_offset + 6)?.try_into().ok()?))
+}
+
+fn host_machine() -> Option<u16> {
+    if cfg!(target_arch = "x86_64") {
+        Some(MACHINE_X64)
+    } else if cfg!(target_arch = "x86") {
+        Some(MACHINE_X86)
+    } else if cfg!(target_arch = "aarch64") {
+        Some(MACHINE_ARM64)
+    } else {
+        None
+    }
+}
+
+fn architecture_name(machine: Option<u16>) -> &'static str {
+    match machine {
+        Some(MACHINE_X86) => "x86",
+        Some(MACHINE_X64) => "x64",
+        Some(MACHINE_ARM64) => "arm64",
+        _ => "unknown",
+    }
+}
+
+fn inspect(path: &Path) -> WinampPluginInfo {
+    // The PE headers are within the first few KB
+    let header = fs::File::open(path).and_then(|file| {
+        use std::io::Read;
+        let mut header = Vec::new();
+        file.take(4096).read_to_end(&mut header)?;
+        Ok(header)
+    });
+    let machine = header.ok().and_then(|bytes| pe_machine(&bytes));
+    let reason = match machine {
+        None => Some("not a valid DLL".to_string()),
+        Some(machine) if Some(machine) != host_machine() => Some(format!(
+            "{} plugin; this build of milk is {}",
+            architecture_name(Some(machine)),
+            architecture_name(host_machine())
+        )),
+        Some(_) if !cfg!(target_os = "windows") => Some("Winamp plugins are only supported on Windows".to_string()),
+        Some(_) => None,
+    };
+    WinampPluginInfo {
+        file_name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        architecture: architecture_name(machine).to_string(),
+        compatible: reason.is_none(),
+        reason,
+    }
+}
+
+/// Every `vis_*.dll` in `dirs`, in directory order then by name
+fn discover(dirs: &[PathBuf]) -> Vec<WinampPluginInfo> {
+    let mut plugins = Vec::new();
+    for dir in dirs {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+            .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+            .unwrap_or_default();
+        paths.retain(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+            path.is_file() && name.starts_with("vis_") && name.ends_with(".dll")
+        });
+        paths.sort();
+        plugins.extend(paths.iter().map(|path| inspect(path)));
+    }
+    plugins
+}
+
+/// Where milk's own copies of Winamp plugins go
+pub fn get_plugins_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("milk").join("winamp_plugins"))
+}
+
+/// milk's plugin folder, then the Plugins folder of an installed Winamp
+fn plugin_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = get_plugins_dir().into_iter().collect();
+    for var in ["ProgramFiles(x86)", "ProgramFiles"] {
+        if let Some(program_files) = std::env::var_os(var) {
+            let winamp = PathBuf::from(program_files).join("Winamp").join("Plugins");
+            if !dirs.contains(&winamp) {
+                dirs.push(winamp);
+            }
+        }
+    }
+    dirs
+}
+
+pub fn list() -> Vec<WinampPluginInfo> {
+    discover(&plugin_dirs())
+}
+
+/// Keep the latest capture data for a running plugin
+pub fn feed(frame: &VisualizerFrame, samples: &[f32]) {
+    if !RUNNING.load(Ordering::Relaxed) {
+        return;
+    }
+    *LATEST.lock().unwrap() = Some(VisData {
+        spectrum: spectrum_data(&frame.bins),
+        waveform: waveform_data(samples),
+        sample_rate: frame.sample_rate,
+    });
+}
+
+pub fn active() -> Option<WinampVisInfo> {
+    ACTIVE.lock().unwrap().as_ref().map(|active| active.info.clone())
+}
+
+/// Stop the running plugin, if any; returns whether one was running
+pub fn stop() -> bool {
+    let Some(active) = ACTIVE.lock().unwrap().take() else {
+        return false;
+    };
+    active.stop.store(true, Ordering::Relaxed);
+    let _ = active.thread.join();
+    RUNNING.store(false, Ordering::Relaxed);
+    true
+}
+
+/// Start module `module` of the plugin at `path`, replacing any running plugin
+///
+/// `path` must be one of the plugins from `list`. When the plugin closes on its
+/// own, `on_exit` is called from its thread.
+pub fn start(path: &str, module: u32, on_exit: impl FnOnce(WinampVisInfo) + Send + 'static) -> Result<WinampVisInfo, WinampVisError> {
+    let plugin = list()
+        .into_iter()
+        .find(|plugin| plugin.path == path)
+        .ok_or_else(|| WinampVisError::NotFound(path.to_string()))?;
+    if let Some(reason) = plugin.reason {
+        return Err(WinampVisError::Incompatible { file: plugin.file_name, reason });
+    }
+    stop();
+    start_plugin(PathBuf::from(plugin.path), module, on_exit)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn start_plugin(_path: PathBuf, _module: u32, _on_exit: impl FnOnce(WinampVisInfo) + Send + 'static) -> Result<WinampVisInfo, WinampVisError> {
+    Err(WinampVisError::Unsupported)
+}
+
+#[cfg(target_os = "windows")]
+fn start_plugin(path: PathBuf, module: u32, on_exit: impl FnOnce(WinampVisInfo) + Send + 'static) -> Result<WinampVisInfo, WinampVisError> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+    RUNNING.store(true, Ordering::Relaxed);
+    let thread = {
+        let stop = Arc::clone(&stop);
+        let path = path.clone();
+        std::thread::spawn(move || {
+            let ended_by_plugin = host::run(&path, module, &stop, ready_tx);
+            if ended_by_plugin {
+                RUNNING.store(false, Ordering::Relaxed);
+                if let Some(active) = ACTIVE.lock().unwrap().take() {
+                    on_exit(active.info);
+                }
+            }
+        })
+    };
+    // Init may show a window or read settings, but should never take this long
+    let description = match ready_rx.recv_timeout(std::time::Duration::from_secs(10)) {
+        Ok(Ok(description)) => description,
+        Ok(Err(e)) => {
+            let _ = thread.join();
+            RUNNING.store(false, Ordering::Relaxed);
+            return Err(e);
+        }
+        Err(_) => {
+            stop.store(true, Ordering::Relaxed);
+            RUNNING.store(false, Ordering::Relaxed);
+            return Err(WinampVisError::Init("plugin did not finish starting".to_string()));
+        }
+    };
+    let info = WinampVisInfo { path: path.to_string_lossy().to_string(), module, description };
+    *ACTIVE.lock().unwrap() = Some(ActiveVis { info: info.clone(), stop, thread });
+    Ok(info)
+}
+
+#[cfg(target_os = "windows")]
+mod host {
+    use super::{VisData, WinampVisError, LATEST, VIS_SAMPLES};
+    use crate::logging::log_warn;
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::Sender;
+    use std::sync::OnceLock;
+    use std::time::Duration;
+    use windows::core::{s, w, HSTRING};
+    use windows::Win32::Foundation::{FreeLibrary, HMODULE, HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress, LoadLibraryW};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, PeekMessageW, RegisterClassW, TranslateMessage,
+        MSG, PM_REMOVE, WINDOW_EX_STYLE, WM_USER, WNDCLASSW, WS_POPUP,
+    };
+
+    /// `VIS_HDRVER` of the Winamp 2/5 vis API
+    const VIS_HEADER_VERSION: c_int = 0x101;
+
+    /// `WM_WA_IPC` queries answered by the stand-in window
+    const IPC_GETVERSION: isize = 0;
+    const IPC_ISPLAYING: isize = 104;
+    const IPC_GETINIFILE: isize = 334;
+    const IPC_GETINIDIRECTORY: isize = 335;
+
+    /// Reported as Winamp 5.666, the last classic release
+    const WINAMP_VERSION: isize = 0x5666;
+
+    /// Bounds on the plugin's requested delay between Render calls
+    const MIN_DELAY_MS: u64 = 10;
+    const MAX_DELAY_MS: u64 = 100;
+
+    #[repr(C)]
+    struct VisHeader {
+        version: c_int,
+        description: *const c_char,
+        get_module: Option<unsafe extern "C" fn(c_int) -> *mut VisModule>,
+    }
+
+    /// `winampVisModule`
+    #[repr(C)]
+    struct VisModule {
+        description: *const c_char,
+        hwnd_parent: *mut c_void,
+        dll_instance: *mut c_void,
+        sample_rate: c_int,
+        channels: c_int,
+        latency_ms: c_int,
+        delay_ms: c_int,
+        spectrum_channels: c_int,
+        waveform_channels: c_int,
+        spectrum_data: [[u8; VIS_SAMPLES]; 2],
+        waveform_data: [[u8; VIS_SAMPLES]; 2],
+        config: Option<unsafe extern "C" fn(*mut VisModule)>,
+        init: Option<unsafe extern "C" fn(*mut VisModule) -> c_int>,
+        render: Option<unsafe extern "C" fn(*mut VisModule) -> c_int>,
+        quit: Option<unsafe extern "C" fn(*mut VisModule)>,
+        user_data: *mut c_void,
+    }
+
+    type GetHeader = unsafe extern "C" fn() -> *mut VisHeader;
+
+    /// winamp.ini path and its directory, handed out as C strings that live forever
+    static INI_PATHS: OnceLock<(CString, CString)> = OnceLock::new();
+
+    fn ini_paths() -> &'static (CString, CString) {
+        INI_PATHS.get_or_init(|| {
+            let dir = super::get_plugins_dir().unwrap_or_default();
+            let _ = std::fs::create_dir_all(&dir);
+            let file = dir.join("winamp.ini");
+            let to_c = |path: &Path| CString::new(path.to_string_lossy().as_bytes()).unwrap_or_default();
+            (to_c(&file), to_c(&dir))
+        })
+    }
+
+    unsafe extern "system" fn winamp_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if msg == WM_USER {
+            match lparam.0 {
+                IPC_GETVERSION => return LRESULT(WINAMP_VERSION),
+                IPC_ISPLAYING => return LRESULT(1),
+                IPC_GETINIFILE => return LRESULT(ini_paths().0.as_ptr() as isize),
+                IPC_GETINIDIRECTORY => return LRESULT(ini_paths().1.as_ptr() as isize),
+                _ => {}
+            }
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    /// Hidden window registered under Winamp's class name, for plugins that look for it
+    fn stand_in_window() -> Result<HWND, WinampVisError> {
+        unsafe {
+            let instance = GetModuleHandleW(None).map_err(|e| WinampVisError::Load(e.to_string()))?;
+            let class = WNDCLASSW {
+                lpfnWndProc: Some(winamp_proc),
+                hInstance: instance.into(),
+                lpszClassName: w!("Winamp v1.x"),
+                ..Default::default()
+            };
+            // Fails harmlessly when a previous plugin already registered it
+            RegisterClassW(&class);
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("Winamp v1.x"),
+                w!("milk"),
+                WS_POPUP,
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+                Some(instance.into()),
+                None,
+            )
+            .map_err(|e| WinampVisError::Load(e.to_string()))
+        }
+    }
+
+    /// Dispatch messages for windows the plugin created on this thread
+    fn pump_messages() {
+        let mut msg = MSG::default();
+        unsafe {
+            while PeekMessageW(&mut msg, None, 0, 0, PM_REMOVE).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    fn fill(module: &mut VisModule, data: &VisData) {
+        module.spectrum_data = [data.spectrum; 2];
+        module.waveform_data = [data.waveform; 2];
+    }
+
+    unsafe fn load_module(library: HMODULE, index: u32) -> Result<*mut VisModule, WinampVisError> {
+        let get_header = GetProcAddress(library, s!("winampVisGetHeader"))
+            .ok_or_else(|| WinampVisError::Load("not a Winamp vis plugin (no winampVisGetHeader)".to_string()))?;
+        let get_header: GetHeader = std::mem::transmute(get_header);
+        let header = get_header();
+        if header.is_null() || (*header).version != VIS_HEADER_VERSION {
+            return Err(WinampVisError::Load("unsupported vis plugin version".to_string()));
+        }
+        let get_module = (*header).get_module.ok_or_else(|| WinampVisError::Load("plugin has no modules".to_string()))?;
+        let module = get_module(index as c_int);
+        if module.is_null() {
+            return Err(WinampVisError::NotFound(format!("module {}", index)));
+        }
+        Ok(module)
+    }
+
+    /// Load, run and unload a plugin until `stop` is set or the plugin ends itself
+    ///
+    /// The module description, or the failure to start, is sent on `ready`.
+    /// Returns true if the plugin ended itself.
+    pub fn run(path: &Path, index: u32, stop: &AtomicBool, ready: Sender<Result<String, WinampVisError>>) -> bool {
+        let window = match stand_in_window() {
+            Ok(window) => window,
+            Err(e) => {
+                let _ = ready.send(Err(e));
+                return false;
+            }
+        };
+        let library = match unsafe { LoadLibraryW(&HSTRING::from(path.as_os_str())) } {
+            Ok(library) => library,
+            Err(e) => {
+                let _ = ready.send(Err(WinampVisError::Load(e.to_string())));
+                unsafe {
+                    let _ = DestroyWindow(window);
+                }
+                return false;
+            }
+        };
+
+        let ended_by_plugin = unsafe {
+            match load_module(library, index) {
+                Ok(module) => run_module(&mut *module, window, library, stop, &ready),
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    false
+                }
+            }
+        };
+        pump_messages();
+        unsafe {
+            let _ = DestroyWindow(window);
+            let _ = FreeLibrary(library);
+        }
+        ended_by_plugin
+    }
+
+    unsafe fn run_module(
+        module: &mut VisModule,
+        window: HWND,
+        library: HMODULE,
+        stop: &AtomicBool,
+        ready: &Sender<Result<String, WinampVisError>>,
+    ) -> bool {
+        let (Some(init), Some(render)) = (module.init, module.render) else {
+            let _ = ready.send(Err(WinampVisError::Load("module is missing Init or Render".to_string())));
+            return false;
+        };
+        let description = match module.description.is_null() {
+            true => String::new(),
+            false => CStr::from_ptr(module.description).to_string_lossy().to_string(),
+        };
+        module.hwnd_parent = window.0;
+        module.dll_instance = library.0;
+        module.sample_rate = LATEST.lock().unwrap().as_ref().map_or(44_100, |data| data.sample_rate as c_int);
+        module.channels = 2;
+        if init(module) != 0 {
+            let _ = ready.send(Err(WinampVisError::Init(description)));
+            return false;
+        }
+        let _ = ready.send(Ok(description));
+
+        let delay = Duration::from_millis((module.delay_ms.max(0) as u64).clamp(MIN_DELAY_MS, MAX_DELAY_MS));
+        let mut ended_by_plugin = false;
+        while !stop.load(Ordering::Relaxed) {
+            pump_messages();
+            if let Some(data) = LATEST.lock().unwrap().as_ref() {
+                fill(module, data);
+            }
+            if render(module) != 0 {
+                ended_by_plugin = true;
+                break;
+            }
+            std::thread::sleep(delay);
+        }
+        match module.quit {
+            Some(quit) => quit(module),
+            None => log_warn("WinampVis", "Plugin has no Quit function"),
+        }
+        ended_by_plugin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// The smallest header `pe_machine` accepts
+    fn pe_stub(machine: u16) -> Vec<u8> {
+        let mut bytes = vec![0; 0x80];
+        bytes[..2].copy_from_slice(b"MZ");
+        bytes[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        bytes[0x40..0x44].copy_from_slice(b"PE\0\0");
+        bytes[0x44..0x46].copy_from_slice(&machine.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_discover_reports_architecture() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("vis_old.dll"), pe_stub(MACHINE_X86)).unwrap();
+        fs::write(dir.path().join("vis_new.dll"), pe_stub(MACHINE_X64)).unwrap();
+        fs::write(dir.path().join("vis_broken.dll"), b"not a dll").unwrap();
+        fs::write(dir.path().join("in_mp3.dll"), pe_stub(MACHINE_X64)).unwrap();
+
+        let plugins = discover(&[dir.path().to_path_buf(), dir.path().join("missing")]);
+        let names: Vec<_> = plugins.iter().map(|p| (p.file_name.as_str(), p.architecture.as_str())).collect();
+        assert_eq!(names, [("vis_broken.dll", "unknown"), ("vis_new.dll", "x64"), ("vis_old.dll", "x86")]);
+        assert!(!plugins[0].compatible);
+        if cfg!(all(target_os = "windows", target_arch = "x86_64")) {
+            assert!(plugins[1].compatible);
+        }
+        assert!(plugins[2].reason.as_deref().unwrap().starts_with("x86 plugin"));
+    }
+
+    #[test]
+    fn test_capture_data_conversion() {
+        let mut bins = vec![0.0; 256];
+        bins[0] = 1.0;
+        bins[128] = 0.001;
+        let spectrum = spectrum_data(&bins);
+        assert_eq!(spectrum[0], 255);
+        // -60 dB is the floor
+        assert_eq!(spectrum[VIS_SAMPLES / 2], 0);
+        assert_eq!(spectrum[VIS_SAMPLES - 1], 0);
+
+        let mut samples = vec![0.0; 2048];
+        samples[2048 - VIS_SAMPLES] = 1.0;
+        samples[2047] = -0.5;
+        let waveform = waveform_data(&samples);
+        assert_eq!(waveform[0] as i8, 127);
+        assert_eq!(waveform[VIS_SAMPLES - 1] as i8, -64);
+        assert_eq!(waveform_data(&[0.25])[0] as i8, 32);
+    }
+}
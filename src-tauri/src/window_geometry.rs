@@ -0,0 +1,462 @@
+// Multi-window snapping, docking and window modes
+// Classic Winamp windows (main, equalizer, playlist) snap to each other and to
+// screen edges when dragged close, and windows touching the main window travel
+// with it. Geometry, shade mode and always-on-top are tracked per window label
+// in physical pixels and saved to `Config::windows` a moment after they change.
+
+use crate::config::{FileConfigManager, WindowGeometry};
+use crate::logging::log_warn;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, Runtime, WebviewWindow, Window, WindowEvent};
+use thiserror::Error;
+
+pub const MAIN_WINDOW: &str = "main";
+
+/// Edges closer than this (in logical pixels) snap together, as in Winamp
+pub const SNAP_DISTANCE: i32 = 10;
+
+/// How often moved windows are written back to the config
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+static MANAGER: Mutex<Option<WindowGeometryManager>> = Mutex::new(None);
+
+#[derive(Error, Debug)]
+pub enum WindowGeometryError {
+    #[error("No window named '{0}'")]
+    UnknownWindow(String),
+    #[error("Failed to update window: {0}")]
+    Window(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    /// Edges meet with some overlap along them
+    fn touches(&self, other: &Rect) -> bool {
+        let overlap_x = self.x < other.right() && other.x < self.right();
+        let overlap_y = self.y < other.bottom() && other.y < self.bottom();
+        ((self.right() == other.x || other.right() == self.x) && overlap_y)
+            || ((self.bottom() == other.y || other.bottom() == self.y) && overlap_x)
+    }
+}
+
+impl From<&WindowGeometry> for Rect {
+    fn from(g: &WindowGeometry) -> Self {
+        Rect { x: g.x, y: g.y, width: g.width, height: g.height }
+    }
+}
+
+/// Offset that lines `moving` up with the nearest edge within `distance`, per axis
+///
+/// Windows snap side by side or aligned when they are level with each other;
+/// the screen (if known) is snapped to from the inside.
+pub fn snap_offset(moving: Rect, targets: &[Rect], screen: Option<Rect>, distance: i32) -> (i32, i32) {
+    let mut dx = Vec::new();
+    let mut dy = Vec::new();
+    for t in targets {
+        if moving.y <= t.bottom() + distance && moving.bottom() >= t.y - distance {
+            dx.extend([t.right() - moving.x, t.x - moving.right(), t.x - moving.x, t.right() - moving.right()]);
+        }
+        if moving.x <= t.right() + distance && moving.right() >= t.x - distance {
+            dy.extend([t.bottom() - moving.y, t.y - moving.bottom(), t.y - moving.y, t.bottom() - moving.bottom()]);
+        }
+    }
+    if let Some(s) = screen {
+        dx.extend([s.x - moving.x, s.right() - moving.right()]);
+        dy.extend([s.y - moving.y, s.bottom() - moving.bottom()]);
+    }
+    let nearest = |candidates: Vec<i32>| {
+        candidates
+            .into_iter()
+            .filter(|d| d.abs() <= distance)
+            .min_by_key(|d| d.abs())
+            .unwrap_or(0)
+    };
+    (nearest(dx), nearest(dy))
+}
+
+/// Labels of windows connected to `root` through touching edges
+pub fn docked_group(windows: &BTreeMap<String, WindowGeometry>, root: &str) -> Vec<String> {
+    let mut group = vec![root.to_string()];
+    let mut next = 0;
+    while next < group.len() {
+        let current = Rect::from(&windows[&group[next]]);
+        for (label, geometry) in windows {
+            if !group.contains(label) && Rect::from(geometry).touches(&current) {
+                group.push(label.clone());
+            }
+        }
+        next += 1;
+    }
+    group.remove(0);
+    group
+}
+
+/// Known window placements and the moves in flight between them
+#[derive(Debug, Default)]
+pub struct WindowGeometryManager {
+    windows: BTreeMap<String, WindowGeometry>,
+    /// Positions set by the manager; their move events are not user drags
+    pending: HashMap<String, (i32, i32)>,
+    /// Labels changed since the last save
+    dirty: Vec<String>,
+}
+
+impl WindowGeometryManager {
+    pub fn new(saved: BTreeMap<String, WindowGeometry>) -> Self {
+        Self { windows: saved, ..Default::default() }
+    }
+
+    pub fn get(&self, label: &str) -> Option<WindowGeometry> {
+        self.windows.get(label).copied()
+    }
+
+    fn record(&mut self, label: &str, geometry: WindowGeometry) {
+        if self.windows.get(label) != Some(&geometry) {
+            self.windows.insert(label.to_string(), geometry);
+            if !self.dirty.iter().any(|l| l == label) {
+                self.dirty.push(label.to_string());
+            }
+        }
+    }
+
+    /// Track a window moved to `position`, returning windows to reposition
+    ///
+    /// A dragged window snaps to the others and the screen; dragging the main
+    /// window also carries every window docked to it.
+    pub fn window_moved(
+        &mut self,
+        label: &str,
+        position: (i32, i32),
+        size: (u32, u32),
+        screen: Option<Rect>,
+        distance: i32,
+    ) -> Vec<(String, i32, i32)> {
+        let previous = self.windows.get(label).copied();
+        let mut geometry = WindowGeometry {
+            x: position.0,
+            y: position.1,
+            width: size.0,
+            height: size.1,
+            ..previous.unwrap_or_default()
+        };
+
+        if self.pending.get(label) == Some(&position) {
+            self.pending.remove(label);
+            self.record(label, geometry);
+            return Vec::new();
+        }
+
+        let docked = match previous {
+            Some(_) if label == MAIN_WINDOW => docked_group(&self.windows, label),
+            _ => Vec::new(),
+        };
+        let targets: Vec<Rect> = self
+            .windows
+            .iter()
+            .filter(|(l, _)| l.as_str() != label && !docked.contains(l))
+            .map(|(_, g)| Rect::from(g))
+            .collect();
+        let (dx, dy) = snap_offset(Rect::from(&geometry), &targets, screen, distance);
+
+        let mut moves = Vec::new();
+        if (dx, dy) != (0, 0) {
+            geometry.x += dx;
+            geometry.y += dy;
+            moves.push((label.to_string(), geometry.x, geometry.y));
+        }
+        if let Some(previous) = previous {
+            let (shift_x, shift_y) = (geometry.x - previous.x, geometry.y - previous.y);
+            for other in docked {
+                let mut moved = self.windows[&other];
+                moved.x += shift_x;
+                moved.y += shift_y;
+                moves.push((other.clone(), moved.x, moved.y));
+                self.record(&other, moved);
+            }
+        }
+        for (other, x, y) in &moves {
+            self.pending.insert(other.clone(), (*x, *y));
+        }
+        self.record(label, geometry);
+        moves
+    }
+
+    pub fn window_resized(&mut self, label: &str, size: (u32, u32)) {
+        if let Some(mut geometry) = self.get(label) {
+            geometry.width = size.0;
+            geometry.height = size.1;
+            self.record(label, geometry);
+        }
+    }
+
+    /// Start tracking a window the manager has not seen move yet
+    pub fn track(&mut self, label: &str, geometry: WindowGeometry) {
+        if !self.windows.contains_key(label) {
+            self.record(label, geometry);
+        }
+    }
+
+    pub fn set_always_on_top(&mut self, label: &str, enabled: bool) {
+        if let Some(mut geometry) = self.get(label) {
+            geometry.always_on_top = enabled;
+            self.record(label, geometry);
+        }
+    }
+
+    /// Enter or leave shade mode, returning the size to give the window
+    ///
+    /// `shade` is the shaded size in physical pixels; a `None` width keeps
+    /// the current width. The full height is remembered for unshading.
+    pub fn set_shaded(&mut self, label: &str, shaded: bool, shade: (Option<u32>, u32)) -> Option<(u32, u32)> {
+        let mut geometry = self.get(label)?;
+        if geometry.shaded == shaded {
+            return None;
+        }
+        let size = if shaded {
+            geometry.full_height = Some(geometry.height);
+            (shade.0.unwrap_or(geometry.width), shade.1)
+        } else {
+            (geometry.width, geometry.full_height.take().unwrap_or(geometry.height))
+        };
+        geometry.shaded = shaded;
+        self.record(label, geometry);
+        Some(size)
+    }
+
+    /// Geometry changed since the last call, for saving
+    pub fn take_dirty(&mut self) -> Vec<(String, WindowGeometry)> {
+        std::mem::take(&mut self.dirty)
+            .into_iter()
+            .filter_map(|label| self.get(&label).map(|g| (label, g)))
+            .collect()
+    }
+}
+
+/// Saved geometry of a window, for placing it when the frontend creates it
+pub fn saved_geometry(label: &str) -> Option<WindowGeometry> {
+    MANAGER.lock().unwrap().as_ref().and_then(|m| m.get(label))
+}
+
+/// Restore the main window and start tracking and saving window geometry
+pub fn start<R: Runtime>(app: &AppHandle<R>, saved: BTreeMap<String, WindowGeometry>) {
+    if let (Some(window), Some(geometry)) = (app.get_webview_window(MAIN_WINDOW), saved.get(MAIN_WINDOW)) {
+        let _ = window.set_size(PhysicalSize::new(geometry.width, geometry.height));
+        let _ = window.set_position(PhysicalPosition::new(geometry.x, geometry.y));
+        let _ = window.set_always_on_top(geometry.always_on_top);
+    }
+    *MANAGER.lock().unwrap() = Some(WindowGeometryManager::new(saved));
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(SAVE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let changed = match MANAGER.lock().unwrap().as_mut() {
+                Some(manager) => manager.take_dirty(),
+                None => Vec::new(),
+            };
+            if !changed.is_empty() {
+                save(changed);
+            }
+        }
+    });
+}
+
+fn save(changed: Vec<(String, WindowGeometry)>) {
+    let mut changes = Vec::new();
+    for (label, geometry) in changed {
+        if label == MAIN_WINDOW {
+            // Older builds only know the single main window position
+            changes.push(("window_position".to_string(), json!({ "x": geometry.x, "y": geometry.y })));
+            changes.push(("window_size".to_string(), json!({ "width": geometry.width, "height": geometry.height })));
+        }
+        changes.push((format!("windows.{}", label), json!(geometry)));
+    }
+    if let Err(e) = FileConfigManager.update_settings(changes) {
+        log_warn("Windows", &format!("Failed to save window positions: {}", e));
+    }
+}
+
+/// Manager entry for `label`, tracking the window's current placement if it is new
+fn with_tracked<R: Runtime, T>(
+    app: &AppHandle<R>,
+    label: &str,
+    f: impl FnOnce(&mut WindowGeometryManager, &WebviewWindow<R>) -> T,
+) -> Result<T, WindowGeometryError> {
+    let window = app
+        .get_webview_window(label)
+        .ok_or_else(|| WindowGeometryError::UnknownWindow(label.to_string()))?;
+    let position = window.outer_position().map_err(|e| WindowGeometryError::Window(e.to_string()))?;
+    let size = window.outer_size().map_err(|e| WindowGeometryError::Window(e.to_string()))?;
+    let mut manager = MANAGER.lock().unwrap();
+    let manager = manager.get_or_insert_with(WindowGeometryManager::default);
+    manager.track(label, WindowGeometry { x: position.x, y: position.y, width: size.width, height: size.height, ..Default::default() });
+    Ok(f(manager, &window))
+}
+
+/// Keep a window above others; remembered across restarts
+pub fn set_always_on_top<R: Runtime>(app: &AppHandle<R>, label: &str, enabled: bool) -> Result<(), WindowGeometryError> {
+    let window = with_tracked(app, label, |manager, window| {
+        manager.set_always_on_top(label, enabled);
+        window.clone()
+    })?;
+    window.set_always_on_top(enabled).map_err(|e| WindowGeometryError::Window(e.to_string()))
+}
+
+/// Collapse a window to its title bar or restore it
+///
+/// `shade` is the shaded size in skin pixels, scaled here to the window's DPI.
+pub fn set_shaded<R: Runtime>(app: &AppHandle<R>, label: &str, shaded: bool, shade: (Option<u32>, u32)) -> Result<(), WindowGeometryError> {
+    let (window, size) = with_tracked(app, label, |manager, window| {
+        let scale = window.scale_factor().unwrap_or(1.0);
+        let physical = |v: u32| (v as f64 * scale).round() as u32;
+        let size = manager.set_shaded(label, shaded, (shade.0.map(physical), physical(shade.1)));
+        (window.clone(), size)
+    })?;
+    match size {
+        // Resizing outside the lock; the resize event updates the manager
+        Some((width, height)) => window
+            .set_size(PhysicalSize::new(width, height))
+            .map_err(|e| WindowGeometryError::Window(e.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Feed window moves and resizes to the manager and apply snapping
+pub fn handle_window_event<R: Runtime>(window: &Window<R>, event: &WindowEvent) {
+    let moves = {
+        let mut manager = MANAGER.lock().unwrap();
+        let Some(manager) = manager.as_mut() else {
+            return;
+        };
+        match event {
+            WindowEvent::Moved(position) => {
+                let Ok(size) = window.outer_size() else {
+                    return;
+                };
+                let scale = window.scale_factor().unwrap_or(1.0);
+                let screen = window.current_monitor().ok().flatten().map(|m| Rect {
+                    x: m.position().x,
+                    y: m.position().y,
+                    width: m.size().width,
+                    height: m.size().height,
+                });
+                let distance = (SNAP_DISTANCE as f64 * scale).round() as i32;
+                manager.window_moved(window.label(), (position.x, position.y), (size.width, size.height), screen, distance)
+            }
+            WindowEvent::Resized(size) => {
+                manager.window_resized(window.label(), (size.width, size.height));
+                return;
+            }
+            _ => return,
+        }
+    };
+
+    // Positioned outside the lock since moving a window dispatches further events
+    for (label, x, y) in moves {
+        if let Some(target) = window.app_handle().get_webview_window(&label) {
+            let _ = target.set_position(PhysicalPosition::new(x, y));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(x: i32, y: i32, width: u32, height: u32) -> WindowGeometry {
+        WindowGeometry { x, y, width, height, ..Default::default() }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn test_snap_offset() {
+        let main = rect(100, 100, 275, 116);
+        // Just below the main window and slightly to the right: docks underneath, left-aligned
+        assert_eq!(snap_offset(rect(106, 222, 275, 116), &[main], None, 10), (-6, -6));
+        // Too far away to snap
+        assert_eq!(snap_offset(rect(100, 300, 275, 116), &[main], None, 10), (0, 0));
+        // Screen edges snap from the inside
+        let screen = rect(0, 0, 1920, 1080);
+        assert_eq!(snap_offset(rect(8, 500, 275, 116), &[], Some(screen), 10), (-8, 0));
+    }
+
+    #[test]
+    fn test_docked_group_is_transitive() {
+        let windows = BTreeMap::from([
+            ("main".to_string(), geometry(0, 0, 275, 116)),
+            ("equalizer".to_string(), geometry(0, 116, 275, 116)),
+            ("playlist".to_string(), geometry(0, 232, 275, 200)),
+            ("video".to_string(), geometry(600, 0, 275, 116)),
+        ]);
+        let mut group = docked_group(&windows, "main");
+        group.sort();
+        assert_eq!(group, vec!["equalizer", "playlist"]);
+    }
+
+    #[test]
+    fn test_main_window_carries_docked_windows() {
+        let mut manager = WindowGeometryManager::new(BTreeMap::from([
+            ("main".to_string(), geometry(0, 0, 275, 116)),
+            ("equalizer".to_string(), geometry(0, 116, 275, 116)),
+        ]));
+        let moves = manager.window_moved("main", (50, 40), (275, 116), None, 10);
+        assert_eq!(moves, vec![("equalizer".to_string(), 50, 156)]);
+
+        // The resulting move event is recognized and not treated as a drag
+        assert!(manager.window_moved("equalizer", (50, 156), (275, 116), None, 10).is_empty());
+        let mut saved: Vec<_> = manager.take_dirty().into_iter().map(|(label, _)| label).collect();
+        saved.sort();
+        assert_eq!(saved, vec!["equalizer", "main"]);
+        assert!(manager.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn test_dragged_window_snaps_and_undocks() {
+        let mut manager = WindowGeometryManager::new(BTreeMap::from([
+            ("main".to_string(), geometry(0, 0, 275, 116)),
+            ("playlist".to_string(), geometry(0, 116, 275, 200)),
+        ]));
+        // Dragging the playlist away leaves it behind when main moves
+        assert!(manager.window_moved("playlist", (400, 400), (275, 200), None, 10).is_empty());
+        assert!(manager.window_moved("main", (20, 0), (275, 116), None, 10).is_empty());
+
+        // Dropping it next to main snaps it flush against the right edge
+        let moves = manager.window_moved("playlist", (301, 3), (275, 200), None, 10);
+        assert_eq!(moves, vec![("playlist".to_string(), 295, 0)]);
+    }
+
+    #[test]
+    fn test_shade_remembers_full_height() {
+        let mut manager = WindowGeometryManager::new(BTreeMap::from([("playlist".to_string(), geometry(0, 0, 400, 300))]));
+        assert_eq!(manager.set_shaded("playlist", true, (None, 14)), Some((400, 14)));
+        assert_eq!(manager.set_shaded("playlist", true, (None, 14)), None);
+        manager.window_resized("playlist", (400, 14));
+
+        // Shade state and the full height survive moves
+        manager.window_moved("playlist", (10, 10), (400, 14), None, 10);
+        assert_eq!(manager.get("playlist").unwrap().full_height, Some(300));
+        assert_eq!(manager.set_shaded("playlist", false, (None, 14)), Some((400, 300)));
+        assert!(!manager.get("playlist").unwrap().shaded);
+    }
+}
@@ -1,5 +1,5 @@
 use std::time::{SystemTime, UNIX_EPOCH};
-use reqwest::Client;
+use crate::api_client::{ApiClient, RateBudget};
 use crate::secure_storage::{SecureStorage, PlatformSecureStorage};
 use crate::spotify::{ApiError, Credentials, Token, TrackMetadata, StreamingService};
 
@@ -10,17 +10,37 @@ const REFRESH_TOKEN_KEY: &str = "youtube_refresh_token";
 const TOKEN_EXPIRY_KEY: &str = "youtube_token_expiry";
 const API_KEY_KEY: &str = "youtube_api_key";
 
+/// The Data API meters a daily quota, so keep bursts small
+const RATE_BUDGET: RateBudget = RateBudget::new(5, 1.0);
+
+/// YouTube's "Music" video category
+const MUSIC_CATEGORY_ID: &str = "10";
+
+/// A video found by `search_videos`
+#[derive(Debug, Clone, serde::Serialize, PartialEq)]
+pub struct YouTubeVideo {
+    pub id: String,
+    pub title: String,
+    pub channel: String,
+    pub duration_ms: u64,
+}
+
 /// YouTube API bridge implementation
 pub struct YouTubeBridge {
-    client: Client,
-    storage: PlatformSecureStorage,
+    client: ApiClient,
+    storage: Box<dyn SecureStorage + Send + Sync>,
 }
 
 impl YouTubeBridge {
     pub fn new() -> Self {
+        Self::with_storage(PlatformSecureStorage::new())
+    }
+
+    /// Create a bridge that keeps its tokens in `storage` instead of the OS keyring
+    pub fn with_storage(storage: impl SecureStorage + Send + Sync + 'static) -> Self {
         YouTubeBridge {
-            client: Client::new(),
-            storage: PlatformSecureStorage::new(),
+            client: ApiClient::new("youtube", RATE_BUDGET),
+            storage: Box::new(storage),
         }
     }
 
@@ -43,11 +63,7 @@ impl YouTubeBridge {
         let url = format!("{}/videos?part=snippet&chart=mostPopular&maxResults=1&key={}", 
             YOUTUBE_API_BASE, api_key);
 
-        let response = self.client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+        let response = self.client.send(self.client.get(&url)).await?;
 
         Ok(response.status().is_success())
     }
@@ -188,12 +204,10 @@ impl StreamingService for YouTubeBridge {
             ("client_secret", &credentials.client_secret),
         ];
 
-        let response = self.client
+        let request = self.client
             .post(YOUTUBE_AUTH_URL)
-            .form(&params)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+            .form(&params);
+        let response = self.client.send(request).await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
@@ -240,12 +254,10 @@ impl StreamingService for YouTubeBridge {
             ("client_secret", &credentials.client_secret),
         ];
 
-        let response = self.client
+        let request = self.client
             .post(YOUTUBE_AUTH_URL)
-            .form(&params)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+            .form(&params);
+        let response = self.client.send(request).await?;
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
@@ -270,6 +282,74 @@ impl StreamingService for YouTubeBridge {
 }
 
 impl YouTubeBridge {
+    /// Music videos matching `query`, with their durations
+    ///
+    /// Search results carry no durations, so a second request fetches them;
+    /// videos without one (live streams) are dropped.
+    pub async fn search_videos(&self, query: &str, limit: u32) -> Result<Vec<YouTubeVideo>, ApiError> {
+        let api_key = self.get_api_key()?
+            .ok_or_else(|| ApiError::AuthenticationError("No API key found".to_string()))?;
+        let limit = limit.clamp(1, 50).to_string();
+        let search = self.get_json("search", &[
+            ("part", "snippet"),
+            ("type", "video"),
+            ("videoCategoryId", MUSIC_CATEGORY_ID),
+            ("maxResults", &limit),
+            ("q", query),
+            ("key", &api_key),
+        ]).await?;
+        let ids: Vec<&str> = search.get("items")
+            .and_then(|v| v.as_array())
+            .map(|items| items.iter().filter_map(|item| item["id"]["videoId"].as_str()).collect())
+            .unwrap_or_default();
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let videos = self.get_json("videos", &[
+            ("part", "snippet,contentDetails"),
+            ("id", &ids.join(",")),
+            ("key", &api_key),
+        ]).await?;
+        let items = videos.get("items")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| ApiError::ParseError("Missing 'items' field".to_string()))?;
+        Ok(items
+            .iter()
+            .filter_map(|item| {
+                let duration_ms = item["contentDetails"]["duration"].as_str()
+                    .and_then(|d| self.parse_duration(d).ok())
+                    .filter(|ms| *ms > 0)?;
+                Some(YouTubeVideo {
+                    id: item["id"].as_str()?.to_string(),
+                    title: item["snippet"]["title"].as_str()?.to_string(),
+                    channel: item["snippet"]["channelTitle"].as_str().unwrap_or_default().to_string(),
+                    duration_ms,
+                })
+            })
+            .collect())
+    }
+
+    /// GET a Data API endpoint and parse the JSON body
+    async fn get_json(&self, endpoint: &str, query: &[(&str, &str)]) -> Result<serde_json::Value, ApiError> {
+        let request = self.client.get(format!("{}/{}", YOUTUBE_API_BASE, endpoint)).query(query);
+        let response = self.client.send(request).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            if status == 401 || status == 403 {
+                return Err(ApiError::AuthenticationError(format!("API key invalid: {}", error_text)));
+            }
+            return Err(ApiError::NetworkError(format!("Status {}: {}", status, error_text)));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ApiError::ParseError(e.to_string()))
+    }
+
     /// Get video metadata by video ID (helper method for testing)
     pub async fn get_video_metadata(&self, video_id: &str) -> Result<TrackMetadata, ApiError> {
         let api_key = self.get_api_key()?
@@ -280,11 +360,7 @@ impl YouTubeBridge {
             YOUTUBE_API_BASE, video_id, api_key
         );
 
-        let response = self.client
-            .get(&url)
-            .send()
-            .await
-            .map_err(|e| ApiError::NetworkError(e.to_string()))?;
+        let response = self.client.send(self.client.get(&url)).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -348,6 +424,7 @@ impl YouTubeBridge {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::secure_storage::InMemorySecureStorage;
 
     #[test]
     fn test_youtube_bridge_creation() {
@@ -355,6 +432,16 @@ mod tests {
         assert!(bridge.client.get("https://example.com").build().is_ok());
     }
 
+    #[test]
+    fn test_api_key_with_injected_storage() {
+        let bridge = YouTubeBridge::with_storage(InMemorySecureStorage::new());
+        assert_eq!(bridge.get_api_key().unwrap(), None);
+        assert!(bridge.check_token_expired().unwrap());
+
+        bridge.store_api_key("key-123").unwrap();
+        assert_eq!(bridge.get_api_key().unwrap(), Some("key-123".to_string()));
+    }
+
     #[test]
     fn test_duration_parsing() {
         let bridge = YouTubeBridge::new();
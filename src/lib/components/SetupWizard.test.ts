@@ -5,10 +5,25 @@ import * as tauri from '../tauri';
 
 // Mock Tauri IPC functions
 vi.mock('../tauri', () => ({
-    saveConfig: vi.fn().mockResolvedValue(undefined),
+    completeSetupStep: vi.fn().mockResolvedValue({ completed_steps: [], next_step: null, finished: false }),
+    getSetupState: vi.fn().mockResolvedValue({
+        needs_setup: true,
+        completed_steps: [],
+        next_step: 'library',
+        library_path: null,
+        skin: null,
+        spotify_enabled: false,
+        youtube_enabled: false,
+        indexing: null,
+    }),
+    loadConfig: vi.fn().mockResolvedValue({}),
     validateDirectoryPath: vi.fn().mockResolvedValue(true),
 }));
 
+vi.mock('@tauri-apps/api/event', () => ({
+    listen: vi.fn().mockResolvedValue(() => {}),
+}));
+
 describe('SetupWizard', () => {
     beforeEach(() => {
         vi.clearAllMocks();
@@ -46,6 +61,8 @@ describe('SetupWizard', () => {
         await fireEvent.click(nextButton);
         
         expect(validateSpy).toHaveBeenCalledWith('/test/path');
+        await new Promise(resolve => setTimeout(resolve, 100));
+        expect(tauri.completeSetupStep).toHaveBeenCalledWith('library', { path: '/test/path' });
     });
 
     it('should show error for invalid library path', async () => {
@@ -85,7 +102,7 @@ describe('SetupWizard', () => {
     });
 
     it('should save configuration on completion', async () => {
-        const saveSpy = vi.spyOn(tauri, 'saveConfig');
+        const saveSpy = vi.spyOn(tauri, 'completeSetupStep');
         const onComplete = vi.fn();
         
         render(SetupWizard, { props: { onComplete } });
@@ -103,11 +120,13 @@ describe('SetupWizard', () => {
         // Wait for save
         await new Promise(resolve => setTimeout(resolve, 100));
         
-        expect(saveSpy).toHaveBeenCalled();
+        expect(saveSpy).toHaveBeenCalledWith('library', { path: null });
+        expect(saveSpy).toHaveBeenCalledWith('skin', { skin_path: null });
+        expect(saveSpy).toHaveBeenCalledWith('streaming', expect.anything());
     });
 
     it('should enable streaming services when checkboxes are checked', async () => {
-        const saveSpy = vi.spyOn(tauri, 'saveConfig');
+        const saveSpy = vi.spyOn(tauri, 'completeSetupStep');
         
         render(SetupWizard);
         
@@ -128,11 +147,9 @@ describe('SetupWizard', () => {
         // Wait for save
         await new Promise(resolve => setTimeout(resolve, 100));
         
-        expect(saveSpy).toHaveBeenCalledWith(
-            expect.objectContaining({
-                spotifyEnabled: true,
-                youtubeEnabled: false
-            })
-        );
+        expect(saveSpy).toHaveBeenCalledWith('streaming', {
+            spotify_enabled: true,
+            youtube_enabled: false
+        });
     });
 });
@@ -22,6 +22,9 @@ describe('Visualizer Component', () => {
                             array[i] = Math.floor(Math.random() * 255);
                         }
                     }),
+                    getFloatTimeDomainData: vi.fn((array: Float32Array) => {
+                        array.fill(0);
+                    }),
                     getByteTimeDomainData: vi.fn((array: Uint8Array) => {
                         for (let i = 0; i < array.length; i++) {
                             array[i] = 128 + Math.floor(Math.random() * 50 - 25);
@@ -30,6 +33,22 @@ describe('Visualizer Component', () => {
                 };
             }
 
+            createGain() {
+                return {
+                    gain: { value: 1 },
+                    connect: vi.fn(),
+                    disconnect: vi.fn(),
+                };
+            }
+
+            createChannelSplitter() {
+                return { connect: vi.fn(), disconnect: vi.fn() };
+            }
+
+            createChannelMerger() {
+                return { connect: vi.fn(), disconnect: vi.fn() };
+            }
+
             createMediaElementSource() {
                 return {
                     connect: vi.fn(),
@@ -1,6 +1,6 @@
 // Tauri IPC client wrapper functions
 import { invoke } from '@tauri-apps/api/core';
-import type { Track, Playlist, AppConfig } from '../types';
+import type { Track, Playlist, PlaylistDelta, PlaylistPage, PlaylistSummary, AppConfig, PlayerSession, SessionUpdate, SetupState, SetupStep, SetupProgress, PluginInfo, ScriptInfo, WinampPluginInfo, WinampVisInfo, LibraryRootStatus, SortField, SearchFilters, SearchHit, QuerySort, GroupBy, HitGroup, JobProgress, TrackAnalysis, Silence, Bookmark, ResumePosition, Chapter, EmbeddedArtwork, RenameReport, TagSuggestion, TagApplyReport, UnifiedNowPlaying, OutputMode, OutputDevice, DspChain, EqPreset, EqImportReport, MigrationReport, ItunesImportReport, PlayerKind, PlayerImportReport, SyncOptions, SyncReport, BackupReport, BandcampFan, BandcampPurchasePage, BandcampTrack, BandcampPurchase, BandcampDownloadFormat, BandcampDownload } from '../types';
 import { handleError } from '../utils/errorHandler';
 
 // Configuration commands
@@ -8,14 +8,110 @@ export async function loadConfig(): Promise<AppConfig> {
     return await invoke<AppConfig>('load_config');
 }
 
-export async function saveConfig(config: AppConfig): Promise<void> {
-    await invoke('save_config', { config });
+export async function saveConfig(config: AppConfig, base?: AppConfig): Promise<AppConfig> {
+    return await invoke<AppConfig>('save_config', { config, base: base ?? null });
+}
+
+/**
+ * Output of the headless player; the windowed player always uses the system output
+ */
+export async function setHeadlessOutputMode(mode: OutputMode): Promise<AppConfig> {
+    return await invoke<AppConfig>('set_headless_output_mode', { mode });
+}
+
+export async function setDspChain(chain: DspChain): Promise<AppConfig> {
+    return await invoke<AppConfig>('set_dsp_chain', { chain });
+}
+
+export async function importEqPresets(path: string): Promise<EqImportReport> {
+    return await invoke<EqImportReport>('import_eq_presets', { path });
+}
+
+export async function listEqPresets(): Promise<EqPreset[]> {
+    return await invoke<EqPreset[]>('list_eq_presets');
+}
+
+/** Import a Winamp installation's playlists, media library and EQ presets; runs as a job */
+export async function importFromWinamp(winampDir: string): Promise<MigrationReport> {
+    return await invoke<MigrationReport>('import_from_winamp', { winampDir });
+}
+
+/**
+ * Import an iTunes or Music app library export; runs as a job.
+ * `remapTo` replaces `remapFrom` (by default the export's music folder) in every track location.
+ */
+export async function importItunesLibrary(xmlPath: string, remapTo?: string, remapFrom?: string): Promise<ItunesImportReport> {
+    return await invoke<ItunesImportReport>('import_itunes_library', { xmlPath, remapTo, remapFrom });
+}
+
+/** Import foobar2000 or MusicBee playlists and ratings from a playlist file, playlist folder or profile folder; runs as a job */
+export async function importFromPlayer(kind: PlayerKind, path: string): Promise<PlayerImportReport> {
+    return await invoke<PlayerImportReport>('import_from_player', { kind, path });
+}
+
+/** Sync playlists and tracks to a device or folder, only writing what changed since the last sync; runs as a job */
+export async function syncToDevice(target: string, playlistIds: string[], tracks: string[], options?: SyncOptions): Promise<SyncReport> {
+    return await invoke<SyncReport>('sync_to_device', { target, playlistIds, tracks, options });
+}
+
+/** Back up playlists, ratings and settings, first applying changes backed up from other machines */
+export async function backupNow(): Promise<BackupReport> {
+    return await invoke<BackupReport>('backup_now');
+}
+
+/** Store the WebDAV password or S3 secret key for backups; `null` removes it */
+export async function setBackupSecret(secret: string | null): Promise<void> {
+    await invoke('set_backup_secret', { secret });
+}
+
+/** Sign in to Bandcamp with the `identity` cookie from a signed-in browser session */
+export async function bandcampSignIn(identity: string): Promise<BandcampFan> {
+    return await invoke<BandcampFan>('bandcamp_sign_in', { identity });
+}
+
+export async function bandcampSignOut(): Promise<void> {
+    await invoke('bandcamp_sign_out');
+}
+
+/** A page of the Bandcamp collection, newest first; pass `next_token` back for the next page */
+export async function bandcampListPurchases(token?: string | null, limit?: number): Promise<BandcampPurchasePage> {
+    return await invoke<BandcampPurchasePage>('bandcamp_list_purchases', { token, limit });
+}
+
+export async function bandcampStreamTracks(url: string): Promise<BandcampTrack[]> {
+    return await invoke<BandcampTrack[]>('bandcamp_stream_tracks', { url });
+}
+
+/** Download a purchase into the library folder and rescan; runs as a job */
+export async function bandcampDownload(purchase: BandcampPurchase, format: BandcampDownloadFormat): Promise<BandcampDownload> {
+    return await invoke<BandcampDownload>('bandcamp_download', { purchase, format });
+}
+
+export async function getTrackNote(filePath: string): Promise<string | null> {
+    return await invoke<string | null>('get_track_note', { filePath });
+}
+
+// Resolves to the note as stored; an empty note removes it
+export async function setTrackNote(filePath: string, note: string | null): Promise<string | null> {
+    return await invoke<string | null>('set_track_note', { filePath, note });
+}
+
+export async function listOutputDevices(): Promise<OutputDevice[]> {
+    return await invoke<OutputDevice[]>('list_output_devices');
 }
 
 export async function isFirstRun(): Promise<boolean> {
     return await invoke<boolean>('is_first_run');
 }
 
+export async function getSetupState(): Promise<SetupState> {
+    return await invoke<SetupState>('get_setup_state');
+}
+
+export async function completeSetupStep(step: SetupStep, payload: Record<string, unknown>): Promise<SetupProgress> {
+    return await invoke<SetupProgress>('complete_setup_step', { step, payload });
+}
+
 export async function validateDirectoryPath(path: string): Promise<boolean> {
     return await invoke<boolean>('validate_directory_path', { path });
 }
@@ -25,20 +121,100 @@ export async function scanLibrary(path: string): Promise<Track[]> {
     return await invoke<Track[]>('scan_library', { path });
 }
 
+/**
+ * Sort library tracks with the collation rules of `locale` (e.g. "sv-SE"),
+ * or the system locale when omitted
+ */
+export async function sortTracks(tracks: Track[], key: SortField, locale?: string): Promise<Track[]> {
+    return await invoke<Track[]>('sort_tracks', { tracks, key, locale });
+}
+
+export async function searchLibrary(query: string, filters?: SearchFilters, sort?: QuerySort, path?: string): Promise<SearchHit[]> {
+    return await invoke<SearchHit[]>('search_library', { query, filters, sort, path });
+}
+
+export async function groupLibrary(query: string, groupBy: GroupBy, filters?: SearchFilters, sort?: QuerySort, path?: string): Promise<HitGroup[]> {
+    return await invoke<HitGroup[]>('group_library', { query, groupBy, filters, sort, path });
+}
+
+export async function listJobs(): Promise<JobProgress[]> {
+    return await invoke<JobProgress[]>('list_jobs');
+}
+
+export async function cancelJob(jobId: string): Promise<boolean> {
+    return await invoke<boolean>('cancel_job', { jobId });
+}
+
+export async function getLibraryRoots(): Promise<LibraryRootStatus[]> {
+    return await invoke<LibraryRootStatus[]>('get_library_roots');
+}
+
+export async function listLibraryExclusions(): Promise<string[]> {
+    return await invoke<string[]>('list_library_exclusions');
+}
+
+export async function addLibraryExclusion(pattern: string): Promise<string[]> {
+    return await invoke<string[]>('add_library_exclusion', { pattern });
+}
+
+export async function removeLibraryExclusion(pattern: string): Promise<string[]> {
+    return await invoke<string[]>('remove_library_exclusion', { pattern });
+}
+
 // Metadata commands
 export async function extractMetadata(filePath: string): Promise<Track> {
     return await invoke<Track>('extract_metadata', { filePath });
 }
 
+export async function analyzeTrack(filePath: string): Promise<TrackAnalysis> {
+    return await invoke<TrackAnalysis>('analyze_track', { filePath });
+}
+
+export async function analyzeSilence(filePath: string): Promise<Silence> {
+    return await invoke<Silence>('analyze_silence', { filePath });
+}
+
+export async function analyzeLibrary(path?: string, force?: boolean, jobId?: string): Promise<string> {
+    return await invoke<string>('analyze_library', { path, force, jobId });
+}
+
+export async function getChapters(filePath: string): Promise<Chapter[]> {
+    return await invoke<Chapter[]>('get_chapters', { filePath });
+}
+
+export async function seekToChapter(index: number, filePath?: string): Promise<Chapter> {
+    return await invoke<Chapter>('seek_to_chapter', { index, filePath });
+}
+
 export async function extractArtwork(filePath: string): Promise<string | null> {
     return await invoke<string | null>('extract_artwork', { filePath });
 }
 
+export async function setTrackArtwork(filePath: string, imageBytes: Uint8Array, mime: string): Promise<EmbeddedArtwork> {
+    return await invoke<EmbeddedArtwork>('set_track_artwork', { filePath, imageBytes: Array.from(imageBytes), mime });
+}
+
+export async function renameFilesFromTags(paths: string[], pattern: string, dryRun = true): Promise<RenameReport> {
+    return await invoke<RenameReport>('rename_files_from_tags', { paths, pattern, dryRun });
+}
+
+export async function inferTagsFromFilenames(paths: string[], pattern?: string): Promise<TagSuggestion[]> {
+    return await invoke<TagSuggestion[]>('infer_tags_from_filenames', { paths, pattern });
+}
+
+export async function applyInferredTags(suggestions: TagSuggestion[]): Promise<TagApplyReport> {
+    return await invoke<TagApplyReport>('apply_inferred_tags', { suggestions });
+}
+
 // Playlist commands
 export async function createPlaylist(name: string): Promise<Playlist> {
     return await invoke<Playlist>('create_playlist', { name });
 }
 
+export async function createPlaylistFromFolder(path: string, recursive: boolean): Promise<Playlist> {
+    return await invoke<Playlist>('create_playlist_from_folder', { path, recursive });
+}
+
 export async function listPlaylists(): Promise<Playlist[]> {
     return await invoke<Playlist[]>('list_playlists');
 }
@@ -47,24 +223,56 @@ export async function loadPlaylist(playlistId: string): Promise<Playlist> {
     return await invoke<Playlist>('load_playlist', { playlistId });
 }
 
+// Undo and redo resolve to null when there is nothing left to undo or redo
+export async function undoPlaylistChange(playlistId: string): Promise<PlaylistDelta | null> {
+    return await invoke<PlaylistDelta | null>('undo_playlist_change', { playlistId });
+}
+
+export async function redoPlaylistChange(playlistId: string): Promise<PlaylistDelta | null> {
+    return await invoke<PlaylistDelta | null>('redo_playlist_change', { playlistId });
+}
+
+// Very large playlists should be read a page at a time instead of with loadPlaylist
+export async function getPlaylistPage(playlistId: string, offset: number, limit?: number): Promise<PlaylistPage> {
+    return await invoke<PlaylistPage>('get_playlist_page', { playlistId, offset, limit: limit ?? null });
+}
+
+export async function getPlaylistSummary(playlistId: string): Promise<PlaylistSummary> {
+    return await invoke<PlaylistSummary>('get_playlist_summary', { playlistId });
+}
+
 export async function deletePlaylist(playlistId: string): Promise<void> {
     await invoke('delete_playlist', { playlistId });
 }
 
-export async function addTrackToPlaylist(playlistId: string, track: Track): Promise<Playlist> {
-    return await invoke<Playlist>('add_track_to_playlist', { playlistId, track });
+// Mutations take the `modified_at` (ms) of the playlist the caller last saw and
+// fail with a `playlist_conflict` error if it has changed since
+export async function addTrackToPlaylist(playlistId: string, track: Track, expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('add_track_to_playlist', { playlistId, track, expectedVersion: expectedVersion ?? null });
+}
+
+/**
+ * Append many tracks at once; the delta is also broadcast as `playlist-changed`
+ */
+export async function addTracksToPlaylist(playlistId: string, tracks: Track[], expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('add_tracks_to_playlist', { playlistId, tracks, expectedVersion: expectedVersion ?? null });
+}
+
+export async function removeTrackFromPlaylist(playlistId: string, trackId: string, expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('remove_track_from_playlist', { playlistId, trackId, expectedVersion: expectedVersion ?? null });
 }
 
-export async function removeTrackFromPlaylist(playlistId: string, trackId: string): Promise<Playlist> {
-    return await invoke<Playlist>('remove_track_from_playlist', { playlistId, trackId });
+export async function reorderPlaylistTracks(playlistId: string, trackIds: string[], expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('reorder_playlist_tracks', { playlistId, trackIds, expectedVersion: expectedVersion ?? null });
 }
 
-export async function reorderPlaylistTracks(playlistId: string, trackIds: string[]): Promise<Playlist> {
-    return await invoke<Playlist>('reorder_playlist_tracks', { playlistId, trackIds });
+export async function updatePlaylist(playlistId: string, name?: string, expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('update_playlist', { playlistId, name, expectedVersion: expectedVersion ?? null });
 }
 
-export async function updatePlaylist(playlistId: string, name?: string): Promise<Playlist> {
-    return await invoke<Playlist>('update_playlist', { playlistId, name });
+// An empty description clears it
+export async function setPlaylistDescription(playlistId: string, description: string | null, expectedVersion?: number): Promise<PlaylistDelta> {
+    return await invoke<PlaylistDelta>('set_playlist_description', { playlistId, description, expectedVersion: expectedVersion ?? null });
 }
 
 // Skin commands
@@ -76,6 +284,18 @@ export async function applySkin(skinPath: string): Promise<import('../types').Pa
     return await invoke('apply_skin', { skinPath });
 }
 
+export async function validateSkinDetailed(skinPath: string): Promise<import('../types').SkinValidationReport> {
+    return await invoke('validate_skin_detailed', { skinPath });
+}
+
+export async function packageSkin(directory: string, outputWsz: string): Promise<import('../types').SkinPackageReport> {
+    return await invoke('package_skin', { directory, outputWsz });
+}
+
+export async function getSkinFonts(skinPath: string): Promise<import('../types').SkinFonts> {
+    return await invoke('get_skin_fonts', { skinPath });
+}
+
 export async function getSkinAssets(skinPath: string): Promise<Record<string, number[]>> {
     return await invoke('get_skin_assets', { skinPath });
 }
@@ -104,6 +324,60 @@ export interface SpotifyTrackMetadata {
     progress_ms?: number;
 }
 
+export interface SpotifyAudioFeatures {
+    id: string;
+    tempo: number;
+    energy: number;
+    danceability: number;
+    valence: number;
+    acousticness: number;
+    instrumentalness: number;
+    liveness: number;
+    speechiness: number;
+    loudness: number;
+    key: number;
+    mode: number;
+    time_signature: number;
+    duration_ms: number;
+}
+
+export interface SpotifyTunable {
+    min?: number;
+    max?: number;
+    target?: number;
+}
+
+export interface SpotifyRecommendationParams {
+    limit?: number;
+    seed_artists?: string[];
+    seed_genres?: string[];
+    market?: string;
+    tempo?: SpotifyTunable;
+    energy?: SpotifyTunable;
+    danceability?: SpotifyTunable;
+    valence?: SpotifyTunable;
+    acousticness?: SpotifyTunable;
+    instrumentalness?: SpotifyTunable;
+}
+
+export interface SpotifyRecommendedTrack {
+    id: string;
+    uri: string;
+    title: string;
+    artists: string[];
+    album: string;
+    duration_ms: number;
+    preview_url: string | null;
+}
+
+export interface SpotifyExportReport {
+    spotify_playlist_id: string;
+    url: string;
+    created: boolean;
+    matched: { track_id: string; title: string; artist: string; spotify_uri: string; confidence: number }[];
+    unmatched: { track_id: string; title: string; artist: string; best_confidence: number | null }[];
+}
+
 export async function spotifyAuthenticate(credentials: SpotifyCredentials, authCode: string): Promise<SpotifyToken> {
     return await invoke<SpotifyToken>('spotify_authenticate', { credentials, authCode });
 }
@@ -116,6 +390,48 @@ export async function youtubeGetNowPlaying(): Promise<SpotifyTrackMetadata | nul
     return await invoke<SpotifyTrackMetadata | null>('youtube_get_now_playing');
 }
 
+export async function getUnifiedNowPlaying(): Promise<UnifiedNowPlaying> {
+    return await invoke<UnifiedNowPlaying>('get_unified_now_playing');
+}
+
+export async function spotifyGetAudioFeatures(trackId: string): Promise<SpotifyAudioFeatures> {
+    return await invoke<SpotifyAudioFeatures>('spotify_get_audio_features', { trackId });
+}
+
+export async function spotifyGetRecommendations(seedTracks: string[], params?: SpotifyRecommendationParams): Promise<SpotifyRecommendedTrack[]> {
+    return await invoke<SpotifyRecommendedTrack[]>('spotify_get_recommendations', { seedTracks, params });
+}
+
+export type MatchTargetService = 'local' | 'spotify' | 'youtube';
+
+export interface MatchQuery {
+    title: string;
+    artist: string;
+    album?: string | null;
+    duration_secs?: number | null;
+}
+
+export interface TrackMatch {
+    candidate: {
+        service: MatchTargetService;
+        id: string;
+        title: string;
+        artists: string[];
+        album: string | null;
+        duration_secs: number | null;
+    };
+    confidence: number;
+    confident: boolean;
+}
+
+export async function matchTrack(metadata: MatchQuery, targetService: MatchTargetService): Promise<TrackMatch | null> {
+    return await invoke<TrackMatch | null>('match_track', { metadata, targetService });
+}
+
+export async function exportPlaylistToSpotify(playlistId: string): Promise<SpotifyExportReport> {
+    return await invoke<SpotifyExportReport>('export_playlist_to_spotify', { playlistId });
+}
+
 export async function spotifyRefreshToken(credentials: SpotifyCredentials): Promise<SpotifyToken> {
     return await invoke<SpotifyToken>('spotify_refresh_token', { credentials });
 }
@@ -246,6 +562,36 @@ export async function spotifyGetNowPlayingSafe(): Promise<SpotifyTrackMetadata |
     }
 }
 
+// Session commands
+export async function restoreSession(): Promise<PlayerSession> {
+    return await invoke<PlayerSession>('restore_session');
+}
+
+export async function updateSession(update: SessionUpdate): Promise<void> {
+    await invoke('update_session', { update });
+}
+
+// Bookmark and resume commands
+export async function addBookmark(filePath: string, position: number, label?: string): Promise<Bookmark> {
+    return await invoke<Bookmark>('add_bookmark', { filePath, position, label });
+}
+
+export async function listBookmarks(filePath: string): Promise<Bookmark[]> {
+    return await invoke<Bookmark[]>('list_bookmarks', { filePath });
+}
+
+export async function removeBookmark(filePath: string, bookmarkId: string): Promise<boolean> {
+    return await invoke<boolean>('remove_bookmark', { filePath, bookmarkId });
+}
+
+export async function getResumePosition(filePath: string): Promise<ResumePosition | null> {
+    return await invoke<ResumePosition | null>('get_resume_position', { filePath });
+}
+
+export async function clearResumePosition(filePath: string): Promise<boolean> {
+    return await invoke<boolean>('clear_resume_position', { filePath });
+}
+
 // System audio capture commands
 export async function startSystemAudioCapture(): Promise<void> {
     await invoke('start_system_audio_capture');
@@ -259,6 +605,52 @@ export async function isSystemAudioCaptureActive(): Promise<boolean> {
     return await invoke<boolean>('is_system_audio_capture_active');
 }
 
+// Plugin commands
+export async function listPlugins(): Promise<PluginInfo[]> {
+    return await invoke<PluginInfo[]>('list_plugins');
+}
+
+export async function setPluginEnabled(id: string, enabled: boolean): Promise<PluginInfo> {
+    return await invoke<PluginInfo>('set_plugin_enabled', { id, enabled });
+}
+
+export async function reloadPlugins(): Promise<PluginInfo[]> {
+    return await invoke<PluginInfo[]>('reload_plugins');
+}
+
+// User script commands
+export async function listScripts(): Promise<ScriptInfo[]> {
+    return await invoke<ScriptInfo[]>('list_scripts');
+}
+
+export async function reloadScripts(): Promise<ScriptInfo[]> {
+    return await invoke<ScriptInfo[]>('reload_scripts');
+}
+
+// Winamp vis plugin commands (Windows only)
+export async function listWinampPlugins(): Promise<WinampPluginInfo[]> {
+    return await invoke<WinampPluginInfo[]>('list_winamp_plugins');
+}
+
+export async function startWinampVis(plugin: string, module?: number): Promise<WinampVisInfo> {
+    return await invoke<WinampVisInfo>('start_winamp_vis', { plugin, module: module ?? null });
+}
+
+export async function stopWinampVis(): Promise<boolean> {
+    return await invoke<boolean>('stop_winamp_vis');
+}
+
+export async function getWinampVis(): Promise<WinampVisInfo | null> {
+    return await invoke<WinampVisInfo | null>('get_winamp_vis');
+}
+
+/**
+ * Hand the windowed player's output to the backend visualizer consumers
+ */
+export async function feedPlayerAudio(samples: Float32Array, sampleRate: number): Promise<void> {
+    return await invoke('feed_player_audio', { samples: Array.from(samples), sampleRate });
+}
+
 // Performance monitoring commands
 export interface PerformanceMetrics {
     startup_time_ms: number | null;
@@ -22,6 +22,241 @@ export interface Playlist {
     tracks: Track[];
     createdAt: Date;
     modifiedAt: Date;
+    /** Generated from the library and play history; read-only */
+    system?: boolean;
+    description?: string | null;
+}
+
+export interface PlaylistSummary {
+    id: string;
+    name: string;
+    track_count: number;
+    /** Seconds */
+    total_duration: number;
+    created_at: number;
+    modified_at: number;
+    system: boolean;
+    description: string | null;
+}
+
+export interface PlaylistPage {
+    playlist_id: string;
+    version: number;
+    /** Tracks in the whole playlist */
+    total: number;
+    offset: number;
+    tracks: Track[];
+}
+
+export type PlaylistChange =
+    | { type: 'tracks_added'; index: number; tracks: Track[] }
+    | { type: 'tracks_removed'; track_ids: string[] }
+    /** Undone add: the `count` tracks from `index` are gone */
+    | { type: 'tracks_removed_at'; index: number; count: number }
+    | { type: 'tracks_reordered'; track_ids: string[] }
+    /** Undone removal: `tracks[i]` is back at `indices[i]` */
+    | { type: 'tracks_restored'; indices: number[]; tracks: Track[] }
+    /** Undone reorder: the whole track list */
+    | { type: 'tracks_replaced'; tracks: Track[] }
+    /** Relocated or renamed files: `tracks[i]` is the one at `indices[i]` */
+    | { type: 'tracks_updated'; indices: number[]; tracks: Track[] }
+    | { type: 'renamed'; name: string }
+    | { type: 'description_changed'; description: string | null };
+
+/** Returned by playlist mutations instead of the whole playlist */
+export interface PlaylistDelta {
+    playlist_id: string;
+    /** Pass back as `expectedVersion` for the next edit */
+    version: number;
+    track_count: number;
+    change: PlaylistChange;
+}
+
+export type DroppedKind = 'audio' | 'folder' | 'playlist' | 'skin' | 'unsupported';
+
+/** Payload of the `files-imported` event sent after files are dropped on a window */
+export interface FilesImported {
+    /** Label of the window the files were dropped on */
+    window: string;
+    /** Tracks to enqueue, folders expanded recursively */
+    tracks: Track[];
+    folders_scanned: number;
+    playlists_read: number;
+    /** Paths of skins now in the skin library */
+    skins_installed: string[];
+    skipped: string[];
+    failed: { path: string; kind: DroppedKind; message: string }[];
+}
+
+export type SetupStep = 'library' | 'skin' | 'streaming';
+
+/** Payload of the `setup-index-progress` event */
+export interface IndexProgress {
+    phase: 'scanning' | 'reading_tags' | 'done' | 'failed';
+    completed: number;
+    /** Audio files found; 0 while scanning */
+    total: number;
+    error: string | null;
+}
+
+export interface SetupProgress {
+    completed_steps: SetupStep[];
+    next_step: SetupStep | null;
+    finished: boolean;
+}
+
+export interface SetupState {
+    needs_setup: boolean;
+    completed_steps: SetupStep[];
+    next_step: SetupStep | null;
+    library_path: string | null;
+    skin: string | null;
+    spotify_enabled: boolean;
+    youtube_enabled: boolean;
+    indexing: IndexProgress | null;
+}
+
+export type PluginCapability = 'visualizer' | 'metadata' | 'playback';
+
+export type PluginStatus = 'loaded' | 'disabled' | 'suspended' | 'failed';
+
+export interface PluginInfo {
+    id: string;
+    name: string;
+    version: string;
+    description: string;
+    capabilities: PluginCapability[];
+    enabled: boolean;
+    status: PluginStatus;
+    error: string | null;
+}
+
+export type ScriptStatus = 'running' | 'suspended' | 'failed';
+
+export interface ScriptInfo {
+    name: string;
+    path: string;
+    handlers: string[];
+    status: ScriptStatus;
+    error: string | null;
+}
+
+export interface WinampPluginInfo {
+    file_name: string;
+    path: string;
+    architecture: 'x86' | 'x64' | 'arm64' | 'unknown';
+    compatible: boolean;
+    reason: string | null;
+}
+
+export interface WinampVisInfo {
+    path: string;
+    module: number;
+    description: string;
+}
+
+export type SortField = 'name' | 'artist' | 'album' | 'path' | 'disc_track' | 'created' | 'modified';
+
+export interface LibraryFile {
+    id: string;
+    file_path: string;
+    file_name: string;
+    extension: string;
+}
+
+export interface LibraryTrackMetadata {
+    title: string | null;
+    artist: string | null;
+    album: string | null;
+    year: number | null;
+    genre: string | null;
+    track_number: number | null;
+    disc_number: number | null;
+    duration: number | null;
+    bpm: number | null;
+    key: string | null;
+    /** ID3 COMM / Vorbis COMMENT tag */
+    comment?: string | null;
+}
+
+export interface Silence {
+    leading_sec: number;
+    trailing_sec: number;
+    duration_sec: number;
+}
+
+export interface TrackAnalysis {
+    bpm: number | null;
+    key: string | null;
+    camelot: string | null;
+    silence: Silence | null;
+}
+
+export interface SearchFilters {
+    artist?: string;
+    album?: string;
+    genre?: string;
+    extension?: string;
+    year_from?: number;
+    year_to?: number;
+    bpm_min?: number;
+    bpm_max?: number;
+    key?: string;
+    min_rating?: number;
+    loved?: boolean;
+    fields?: Array<'title' | 'artist' | 'album' | 'genre' | 'comment' | 'path'>;
+    limit?: number;
+}
+
+export interface SearchHit {
+    track: LibraryFile;
+    metadata: LibraryTrackMetadata | null;
+    rating: { stars: number; loved: boolean };
+    score: number;
+    silence?: Silence;
+    /** The user's note, see `setTrackNote` */
+    note?: string;
+}
+
+export interface QuerySort {
+    key: 'relevance' | 'title' | 'artist' | 'album' | 'year' | 'date_added' | 'duration';
+    descending?: boolean;
+    locale?: string;
+}
+
+export type GroupBy = 'album' | 'artist' | 'folder';
+
+export interface HitGroup {
+    key: string;
+    title: string;
+    artist: string | null;
+    hits: SearchHit[];
+}
+
+export interface LibraryRootStatus {
+    path: string;
+    kind: 'local' | 'network' | 'removable';
+    online: boolean;
+}
+
+export type JobKind = 'library_scan' | 'media' | 'artwork' | 'download' | 'analysis';
+
+export type JobState = 'queued' | 'running' | 'completed' | 'failed' | 'cancelled';
+
+export type JobDetail =
+    | { unit: 'items'; completed: number; total: number }
+    | { unit: 'bytes'; received: number; total: number | null };
+
+/** Payload of the `job-progress` event, and an entry of `list_jobs` */
+export interface JobProgress {
+    job_id: string;
+    kind: JobKind;
+    operation: string;
+    state: JobState;
+    percent: number | null;
+    eta_sec: number | null;
+    detail: JobDetail | null;
+    error: string | null;
 }
 
 export interface AppConfig {
@@ -33,6 +268,301 @@ export interface AppConfig {
     youtubeEnabled: boolean;
     windowPosition: { x: number; y: number };
     windowSize: { width: number; height: number };
+    playback?: PlaybackSettings;
+    output?: OutputSettings;
+    skin_assets?: SkinAssetOptions;
+    dsp?: DspChain;
+    backup?: BackupSettings;
+    revision?: number;
+}
+
+export interface PlaybackSettings {
+    skip_silence: boolean;
+    resume_min_duration_secs: number;
+    /** Highest priority first; sources left out are ignored */
+    source_priority: NowPlayingSource[];
+}
+
+/** Where the headless player sends audio */
+export type OutputMode = 'local' | 'snapcast';
+
+/** Audio output of the headless player */
+export interface OutputSettings {
+    mode: OutputMode;
+    /** Device name; the system default when null */
+    device: string | null;
+    /** Devices held exclusively at each file's sample rate, bypassing the OS mixer (Windows, macOS) */
+    bit_perfect_devices: string[];
+    snapcast_host: string;
+    snapcast_port: number;
+}
+
+/** Applied in order; gain in dB up to ±12, balance -1 (left) to 1 (right), width 0 to 2 */
+export type DspEffect =
+    | { type: 'preamp'; gain_db: number }
+    | { type: 'balance'; position: number }
+    | { type: 'mono_downmix' }
+    | { type: 'stereo_expander'; width: number };
+
+/** Effects applied to the player's output, headless or windowed */
+export interface DspChain {
+    effects: DspEffect[];
+}
+
+/** Gains for 60, 170, 310, 600, 1k, 3k, 6k, 12k, 14k and 16k Hz, in dB (±12) */
+export interface EqPreset {
+    name: string;
+    preamp_db: number;
+    bands_db: number[];
+}
+
+export interface EqImportReport {
+    added: number;
+    replaced: number;
+    /** Files that could not be read, with the reason */
+    failed: string[];
+}
+
+export interface MigrationReport {
+    /** Playlists created or added to: Winamp's main playlist, its media library playlists and "Winamp Library" */
+    playlists: PlaylistSummary[];
+    library_tracks: number;
+    /** Library tracks added to the search index */
+    indexed_tracks: number;
+    /** Entries pointing at files that no longer exist */
+    missing_files: number;
+    eq_presets: EqImportReport;
+    /** Parts of the installation that could not be read */
+    warnings: string[];
+}
+
+export interface ItunesImportReport {
+    /** Playlists created: the export's user playlists and "iTunes Library" */
+    playlists: PlaylistSummary[];
+    library_tracks: number;
+    /** Library tracks added to the search index */
+    indexed_tracks: number;
+    /** Ratings set on files that had none in milkline */
+    ratings: number;
+    play_counts: number;
+    dates_added: number;
+    /** Tracks whose file doesn't exist at its (remapped) location */
+    missing_files: number;
+}
+
+export type PlayerKind = 'foobar2000' | 'musicbee';
+
+export interface PlayerImportReport {
+    kind: PlayerKind;
+    /** Playlists created */
+    playlists: PlaylistSummary[];
+    /** Ratings set on files that had none in milkline */
+    ratings: number;
+    play_counts: number;
+    /** Playlist entries pointing at files that no longer exist */
+    missing_files: number;
+    /** Files that could not be read */
+    warnings: string[];
+}
+
+export type AudioFormat = 'mp3' | 'aac' | 'ogg' | 'flac';
+
+export interface TranscodeRule {
+    /** Source extensions the rule covers, e.g. ['flac', 'wav'] */
+    extensions: string[];
+    /** Only files above this bitrate are converted; every matching file when unset */
+    above_kbps?: number | null;
+    format: AudioFormat;
+    bitrate_kbps: number;
+}
+
+export interface SyncOptions {
+    /** Tag pattern for paths on the device */
+    pattern?: string;
+    /** The first matching rule applies; other files are copied as they are */
+    transcode?: TranscodeRule[];
+    /** Remove files an earlier sync wrote that are no longer selected */
+    remove_unselected?: boolean;
+}
+
+export interface SyncReport {
+    target: string;
+    copied: number;
+    transcoded: number;
+    /** Already on the device from an earlier sync */
+    unchanged: number;
+    removed: number;
+    /** Playlist files written */
+    playlists: number;
+    bytes_written: number;
+    /** Files that could not be written, with the reason */
+    failed: string[];
+}
+
+export type BackupProvider = 'none' | 'webdav' | 's3';
+
+/** Cloud backup target; the password or secret key is set with `setBackupSecret` */
+export interface BackupSettings {
+    provider: BackupProvider;
+    /** WebDAV folder URL, or the S3 endpoint */
+    endpoint: string;
+    /** S3 only */
+    bucket: string;
+    region: string;
+    /** Folder (WebDAV) or key prefix (S3) */
+    folder: string;
+    /** WebDAV user name or S3 access key id */
+    username: string | null;
+    /** Hours between automatic backups; 0 only backs up on request */
+    interval_hours: number;
+}
+
+/** Also the payload of the `cloud-backup` event sent after scheduled backups */
+export interface BackupReport {
+    provider: BackupProvider;
+    uploaded: boolean;
+    /** Playlists added, changed or removed here from another machine's backup */
+    playlists_pulled: number;
+    ratings_pulled: number;
+    config_pulled: boolean;
+    /** Items changed on both sides since the last backup */
+    conflicts: number;
+    playlists: number;
+    ratings: number;
+    completed_at: number;
+}
+
+export interface BandcampFan {
+    fan_id: number;
+    username: string;
+}
+
+export type BandcampDownloadFormat = 'mp3-v0' | 'mp3-320' | 'flac' | 'aac-hi' | 'vorbis' | 'alac' | 'wav' | 'aiff-lossless';
+
+/** An album or track in the Bandcamp collection */
+export interface BandcampPurchase {
+    id: number;
+    kind: 'album' | 'track';
+    title: string;
+    artist: string;
+    /** Album or track page */
+    url: string;
+    artwork_url: string | null;
+    /** Null for items that can't be downloaded */
+    download_page: string | null;
+}
+
+export interface BandcampPurchasePage {
+    items: BandcampPurchase[];
+    /** Pass back for the next page; null on the last page */
+    next_token: string | null;
+}
+
+export interface BandcampTrack {
+    title: string;
+    artist: string;
+    album: string | null;
+    track_number: number | null;
+    duration_sec: number;
+    /** Expires; fetch the tracks again before playing later */
+    stream_url: string;
+}
+
+export interface BandcampDownload {
+    folder: string;
+    files: string[];
+    bytes: number;
+}
+
+export interface OutputDevice {
+    name: string;
+    is_default: boolean;
+    sample_rates: number[];
+    /** Exclusive output is available for the device on this platform */
+    bit_perfect: boolean;
+}
+
+export type NowPlayingSource = 'local' | 'spotify' | 'youtube' | 'system_audio';
+
+export interface NowPlayingTrack {
+    title: string;
+    artist: string | null;
+    album: string | null;
+    duration_secs: number | null;
+    file_path: string | null;
+    artwork_url: string | null;
+}
+
+/** Payload of `get_unified_now_playing` and the `now-playing-changed` event */
+export interface UnifiedNowPlaying {
+    source: NowPlayingSource | null;
+    /** Null for system audio */
+    track: NowPlayingTrack | null;
+    status: 'playing' | 'paused' | 'stopped';
+    position_secs: number | null;
+}
+
+export interface Chapter {
+    index: number;
+    title: string;
+    start_secs: number;
+    end_secs: number;
+}
+
+export interface EmbeddedArtwork {
+    mime: string;
+    width: number;
+    height: number;
+    size_bytes: number;
+    converted: boolean;
+}
+
+export type RenameStatus = 'ready' | 'renamed' | 'unchanged' | 'missing_tag' | 'collision' | 'outside_root' | 'failed';
+
+export interface RenameEntry {
+    source: string;
+    target: string | null;
+    status: RenameStatus;
+    detail: string | null;
+}
+
+export interface RenameReport {
+    dry_run: boolean;
+    entries: RenameEntry[];
+    renamed: number;
+    skipped: number;
+}
+
+export interface TagSuggestion {
+    path: string;
+    current: LibraryTrackMetadata;
+    inferred: LibraryTrackMetadata;
+    note: string | null;
+}
+
+export interface TagApplyReport {
+    updated: number;
+    unchanged: number;
+    failed: { path: string; error: string }[];
+}
+
+export type MediaControlAction =
+    | { action: 'play' | 'pause' | 'toggle' | 'next' | 'previous' | 'stop' }
+    | { action: 'seek_by'; offset_secs: number }
+    | { action: 'seek_to'; position_secs: number }
+    | { action: 'set_volume'; volume: number };
+
+export interface Bookmark {
+    id: string;
+    position_secs: number;
+    label: string | null;
+    created_at: number;
+}
+
+export interface ResumePosition {
+    position_secs: number;
+    duration_secs: number;
+    updated_at: number;
 }
 
 export interface PlayerState {
@@ -44,6 +574,22 @@ export interface PlayerState {
     queue: Track[];
 }
 
+export type RepeatMode = 'off' | 'all' | 'one';
+
+/** Player state saved across restarts */
+export interface PlayerSession {
+    queue: Track[];
+    current_index: number | null;
+    position_secs: number;
+    shuffle: boolean;
+    repeat: RepeatMode;
+    /** Labels of the windows that were open */
+    open_windows: string[];
+}
+
+/** Changed parts of the session; omitted fields are kept */
+export type SessionUpdate = Partial<PlayerSession>;
+
 export interface PlaylistState {
     playlists: Playlist[];
     currentPlaylist: Playlist | null;
@@ -70,6 +616,64 @@ export interface ParsedSkin {
     regions: RegionConfig | null;
 }
 
+export type SkinIssueKind = 'unparseable' | 'missing_asset' | 'unreadable_image' | 'oversized_file' | 'unsupported_feature';
+
+export interface SkinIssue {
+    kind: SkinIssueKind;
+    /** Errors make the skin fall back to the default; warnings only affect how it looks */
+    severity: 'error' | 'warning';
+    asset: string | null;
+    message: string;
+}
+
+export interface SkinValidationReport {
+    name: string;
+    falls_back: boolean;
+    asset_count: number;
+    issues: SkinIssue[];
+}
+
+export interface SkinPackageReport {
+    output: string;
+    /** Normalized names written to the archive */
+    files: string[];
+    /** Hidden files and OS clutter left out */
+    skipped: string[];
+    warnings: SkinIssue[];
+}
+
+/** How skin bitmaps are prepared for the webview */
+export interface SkinAssetOptions {
+    /** Re-encode BMP assets as PNG (names keep their .bmp extension) */
+    transcode_bmp: boolean;
+    /** While transcoding, make #FF00FF pixels transparent */
+    magenta_transparency: boolean;
+}
+
+/** A font sheet (PNG bytes) and the top-left corner of each character's glyph */
+export interface BitmapFont {
+    image: number[];
+    glyph_width: number;
+    glyph_height: number;
+    glyphs: Record<string, { x: number; y: number }>;
+}
+
+export interface SkinCursor {
+    /** PNG bytes */
+    image: number[];
+    width: number;
+    height: number;
+    hotspot_x: number;
+    hotspot_y: number;
+}
+
+export interface SkinFonts {
+    text: BitmapFont | null;
+    numbers: BitmapFont | null;
+    /** Keyed by lowercase file stem, e.g. `normal` or `close` */
+    cursors: Record<string, SkinCursor>;
+}
+
 export type FarmerState = 'idle' | 'listening' | 'prompting' | 'celebrating' | 'error';
 
 export interface FarmerExpression {
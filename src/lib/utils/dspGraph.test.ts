@@ -0,0 +1,83 @@
+import { describe, it, expect } from 'vitest';
+import { DspGraph } from './dspGraph';
+
+class MockNode {
+    gain = { value: 1 };
+    channelCount = 2;
+    channelCountMode = 'max';
+    channelInterpretation = 'speakers';
+    targets: MockNode[] = [];
+
+    constructor(public kind: string) {}
+
+    connect(target: MockNode) {
+        this.targets.push(target);
+        return target;
+    }
+
+    disconnect() {
+        this.targets = [];
+    }
+}
+
+class MockContext {
+    created: MockNode[] = [];
+
+    private make(kind: string) {
+        const node = new MockNode(kind);
+        this.created.push(node);
+        return node;
+    }
+
+    createGain() { return this.make('gain'); }
+    createChannelSplitter() { return this.make('splitter'); }
+    createChannelMerger() { return this.make('merger'); }
+}
+
+function setup() {
+    const context = new MockContext();
+    const graph = new DspGraph(context as unknown as BaseAudioContext);
+    return { context, graph, input: graph.input as unknown as MockNode, output: graph.output as unknown as MockNode };
+}
+
+describe('DspGraph', () => {
+    it('passes audio straight through with no effects', () => {
+        const { graph, input, output } = setup();
+        graph.apply({ effects: [] });
+        expect(input.targets).toEqual([output]);
+    });
+
+    it('chains the effects in order', () => {
+        const { context, graph, input, output } = setup();
+        graph.apply({ effects: [{ type: 'preamp', gain_db: -6 }, { type: 'mono_downmix' }] });
+
+        const [preamp] = input.targets;
+        expect(preamp.gain.value).toBeCloseTo(0.501, 3);
+        const [downmix] = preamp.targets;
+        expect(downmix.channelCount).toBe(1);
+        expect(downmix.channelCountMode).toBe('explicit');
+        expect(downmix.targets).toEqual([output]);
+        expect(context.created).toHaveLength(4);
+    });
+
+    it('mixes the stereo effects through gains', () => {
+        const { context, graph } = setup();
+        graph.apply({ effects: [{ type: 'stereo_expander', width: 2 }] });
+        const levels = context.created.filter(node => node.kind === 'gain').slice(3).map(node => node.gain.value);
+        expect(levels).toEqual([1.5, -0.5, -0.5, 1.5]);
+
+        // A hard right balance drops the left channel and leaves the right alone
+        graph.apply({ effects: [{ type: 'balance', position: 1 }] });
+        const balance = context.created.slice(-3).filter(node => node.kind === 'gain');
+        expect(balance.map(node => node.gain.value)).toEqual([1]);
+    });
+
+    it('unhooks the previous chain when a new one is applied', () => {
+        const { context, graph, input, output } = setup();
+        graph.apply({ effects: [{ type: 'preamp', gain_db: 3 }] });
+        const preamp = context.created[2];
+        graph.apply(null);
+        expect(preamp.targets).toEqual([]);
+        expect(input.targets).toEqual([output]);
+    });
+});
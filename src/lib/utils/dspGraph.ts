@@ -0,0 +1,105 @@
+/**
+ * The DSP chain as Web Audio nodes, for the windowed player
+ *
+ * Mirrors the backend's `DspChain` so a chain saved with `set_dsp_chain`
+ * sounds the same in the webview as on the headless player. Audio goes in
+ * at `input` and comes out of `output`; `apply` rebuilds the nodes between
+ * them, so the chain can be swapped while playing.
+ */
+
+import type { DspChain, DspEffect } from '../types';
+
+export class DspGraph {
+    readonly input: GainNode;
+    readonly output: GainNode;
+    private nodes: AudioNode[] = [];
+
+    constructor(private context: BaseAudioContext) {
+        this.input = context.createGain();
+        this.output = context.createGain();
+        this.input.connect(this.output);
+    }
+
+    /**
+     * Replace the effects between `input` and `output`
+     */
+    apply(chain: DspChain | null | undefined) {
+        this.input.disconnect();
+        this.nodes.forEach(node => node.disconnect());
+        this.nodes = [];
+
+        let tail: AudioNode = this.input;
+        for (const effect of chain?.effects ?? []) {
+            const [head, last] = this.build(effect);
+            tail.connect(head);
+            tail = last;
+        }
+        tail.connect(this.output);
+    }
+
+    /**
+     * Nodes for one effect, returned as its first and last node
+     */
+    private build(effect: DspEffect): [AudioNode, AudioNode] {
+        const context = this.context;
+        switch (effect.type) {
+            case 'preamp': {
+                const gain = this.track(context.createGain());
+                gain.gain.value = Math.pow(10, effect.gain_db / 20);
+                return [gain, gain];
+            }
+            case 'balance': {
+                // Attenuate the far side only, like the backend; a panner would
+                // also fold one channel into the other
+                return this.matrix(Math.min(1 - effect.position, 1), 0, 0, Math.min(1 + effect.position, 1));
+            }
+            case 'mono_downmix': {
+                // Forcing one channel downmixes to (L + R) / 2, and the next
+                // node upmixes it back to both speakers
+                const gain = this.track(context.createGain());
+                gain.channelCount = 1;
+                gain.channelCountMode = 'explicit';
+                gain.channelInterpretation = 'speakers';
+                return [gain, gain];
+            }
+            case 'stereo_expander': {
+                // Mid + side and mid - side, with the side scaled by the width
+                const same = (1 + effect.width) / 2;
+                const cross = (1 - effect.width) / 2;
+                return this.matrix(same, cross, cross, same);
+            }
+        }
+    }
+
+    /**
+     * Stereo mix where left out = ll * L + rl * R and right out = lr * L + rr * R
+     */
+    private matrix(ll: number, rl: number, lr: number, rr: number): [AudioNode, AudioNode] {
+        // Mono sources are upmixed to both channels first; the splitter
+        // itself only splits discretely
+        const upmix = this.track(this.context.createGain());
+        upmix.channelCount = 2;
+        upmix.channelCountMode = 'explicit';
+        upmix.channelInterpretation = 'speakers';
+        const splitter = this.track(this.context.createChannelSplitter(2));
+        upmix.connect(splitter);
+        const merger = this.track(this.context.createChannelMerger(2));
+        const route = (from: number, to: number, level: number) => {
+            if (level === 0) return;
+            const gain = this.track(this.context.createGain());
+            gain.gain.value = level;
+            splitter.connect(gain, from);
+            gain.connect(merger, 0, to);
+        };
+        route(0, 0, ll);
+        route(1, 0, rl);
+        route(0, 1, lr);
+        route(1, 1, rr);
+        return [upmix, merger];
+    }
+
+    private track<T extends AudioNode>(node: T): T {
+        this.nodes.push(node);
+        return node;
+    }
+}
@@ -8,7 +8,9 @@ import {
     withRetry,
     isCriticalError,
     isRecoverableError,
-    showSuccess
+    showSuccess,
+    ErrorCategory,
+    type ErrorPayload
 } from './errorHandler';
 import { farmerStore } from '../stores/farmerStore';
 
@@ -68,6 +70,44 @@ describe('errorHandler', () => {
         });
     });
 
+    describe('backend error payloads', () => {
+        const diskFull: ErrorPayload = {
+            code: 'disk_full',
+            category: ErrorCategory.FileSystem,
+            message: "Your disk is full! I couldn't save: config",
+            recoverable: false,
+            critical: true,
+            suggestion: 'Free up some disk space and try again.'
+        };
+
+        it('should use the payload message', () => {
+            expect(extractErrorMessage(diskFull)).toBe(diskFull.message);
+        });
+
+        it('should use the payload flags', () => {
+            expect(isCriticalError(diskFull)).toBe(true);
+            expect(isRecoverableError(diskFull)).toBe(false);
+        });
+
+        it('should use the payload suggestion', async () => {
+            const strategy = await getRecoveryStrategy(diskFull);
+            expect(strategy.canRecover).toBe(false);
+            expect(strategy.suggestion).toBe(diskFull.suggestion);
+        });
+
+        it('should pick the recovery action from the code', async () => {
+            const strategy = await getRecoveryStrategy({
+                ...diskFull,
+                code: 'network_timeout',
+                category: ErrorCategory.Network,
+                recoverable: true,
+                critical: false
+            });
+            expect(strategy.canRecover).toBe(true);
+            expect(strategy.action).toBeDefined();
+        });
+    });
+
     describe('isRecoverableError', () => {
         it('should identify timeout as recoverable', () => {
             expect(isRecoverableError('Connection timeout')).toBe(true);
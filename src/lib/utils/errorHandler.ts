@@ -13,11 +13,40 @@ export enum ErrorCategory {
     Skin = 'Skin',
     Metadata = 'Metadata',
     Playlist = 'Playlist',
+    Visualizer = 'Visualizer',
+    Scheduler = 'Scheduler',
     Storage = 'Storage',
     SystemAudio = 'SystemAudio',
     General = 'General'
 }
 
+/**
+ * Error returned by every backend command
+ */
+export interface ErrorPayload {
+    /** Stable identifier of the backend error, e.g. `disk_full` */
+    code: string;
+    category: ErrorCategory;
+    message: string;
+    recoverable: boolean;
+    critical: boolean;
+    suggestion: string;
+}
+
+/**
+ * Check whether an error is a payload from a backend command
+ */
+export function isErrorPayload(error: unknown): error is ErrorPayload {
+    return (
+        !!error &&
+        typeof error === 'object' &&
+        'code' in error &&
+        'message' in error &&
+        'recoverable' in error &&
+        'critical' in error
+    );
+}
+
 /**
  * Error recovery strategies
  */
@@ -100,11 +129,32 @@ export function extractErrorMessage(error: unknown): string {
     return 'An unexpected error occurred';
 }
 
+/**
+ * Text to match error kinds against; backend payloads match on their code
+ */
+function errorText(error: unknown): string {
+    if (isErrorPayload(error)) {
+        return error.code.replace(/_/g, ' ');
+    }
+    return extractErrorMessage(error).toLowerCase();
+}
+
 /**
  * Get recovery strategy for an error
+ *
+ * Backend payloads already say whether they are recoverable and what to
+ * suggest; only the recovery action is chosen here.
  */
 export async function getRecoveryStrategy(error: unknown): Promise<RecoveryStrategy> {
-    const message = extractErrorMessage(error).toLowerCase();
+    const strategy = matchRecoveryStrategy(error);
+    if (isErrorPayload(error)) {
+        return { ...strategy, canRecover: error.recoverable, suggestion: error.suggestion };
+    }
+    return strategy;
+}
+
+function matchRecoveryStrategy(error: unknown): RecoveryStrategy {
+    const message = errorText(error);
     
     // Network errors - suggest retry
     if (message.includes('timeout') || message.includes('network')) {
@@ -252,6 +302,9 @@ export async function withRetry<T>(
  * Check if an error is critical (requires immediate user attention)
  */
 export function isCriticalError(error: unknown): boolean {
+    if (isErrorPayload(error)) {
+        return error.critical;
+    }
     const message = extractErrorMessage(error).toLowerCase();
     
     return (
@@ -266,6 +319,9 @@ export function isCriticalError(error: unknown): boolean {
  * Check if an error is recoverable (can be handled gracefully)
  */
 export function isRecoverableError(error: unknown): boolean {
+    if (isErrorPayload(error)) {
+        return error.recoverable;
+    }
     const message = extractErrorMessage(error).toLowerCase();
     
     return (
@@ -293,26 +349,35 @@ export function showSuccess(message: string, duration: number = 2000): void {
     farmerStore.celebrate(message, duration);
 }
 
+export type ErrorSeverity = 'critical' | 'error' | 'recoverable';
+
+export interface ErrorRecord {
+    timestamp: string;
+    category: ErrorCategory;
+    severity: ErrorSeverity;
+    message: string;
+}
+
 /**
- * Get error category from backend
+ * Session error statistics from the backend, for the diagnostics UI
  */
-export async function getErrorCategory(errorMessage: string): Promise<ErrorCategory> {
-    try {
-        const category = await invoke<string>('get_error_category', { errorMsg: errorMessage });
-        return category as ErrorCategory;
-    } catch {
-        return ErrorCategory.General;
-    }
+export interface ErrorStats {
+    session_started: string;
+    total: number;
+    by_category: Partial<Record<ErrorCategory, number>>;
+    by_severity: Partial<Record<ErrorSeverity, number>>;
+    /** Newest first */
+    recent_errors: ErrorRecord[];
 }
 
 /**
- * Check if error type is critical via backend
+ * Get error counts and recent errors for this session
  */
-export async function checkIfCritical(errorType: string): Promise<boolean> {
+export async function getErrorStats(): Promise<ErrorStats | null> {
     try {
-        return await invoke<boolean>('is_error_critical', { errorType });
+        return await invoke<ErrorStats>('get_error_stats');
     } catch {
-        return false;
+        return null;
     }
 }
 
@@ -0,0 +1,17 @@
+import { describe, it, expect } from 'vitest';
+import { displayPath, fileUrl, isEscapedPath } from './filePath';
+
+describe('filePath utilities', () => {
+    it('should leave plain paths unchanged', () => {
+        expect(isEscapedPath('/music/Café.mp3')).toBe(false);
+        expect(fileUrl('/music/Café.mp3')).toBe('file:///music/Café.mp3');
+        expect(displayPath('/music/100%25.mp3')).toBe('/music/100%25.mp3');
+    });
+
+    it('should unwrap escaped paths', () => {
+        const escaped = '\0/music/Caf%E9 50%25.mp3';
+        expect(isEscapedPath(escaped)).toBe(true);
+        expect(fileUrl(escaped)).toBe('file:///music/Caf%E9 50%25.mp3');
+        expect(displayPath(escaped)).toBe('/music/Caf� 50%.mp3');
+    });
+});
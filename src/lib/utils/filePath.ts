@@ -0,0 +1,38 @@
+/**
+ * Helpers for track paths coming from the backend
+ *
+ * Paths that are not valid Unicode arrive escaped: a leading NUL, then the
+ * path with each raw byte (or, on Windows, unpaired surrogate) written as
+ * `%XX` / `%uXXXX` and `%` itself as `%25`. Pass them back to commands as-is.
+ */
+
+const ESCAPED = '\0';
+
+/**
+ * Whether a path had to be escaped by the backend
+ */
+export function isEscapedPath(path: string): boolean {
+    return path.startsWith(ESCAPED);
+}
+
+/**
+ * `file://` URL for a track path
+ *
+ * The escapes of an escaped path are already URL percent-encoding, so the
+ * raw bytes reach the file system unchanged.
+ */
+export function fileUrl(path: string): string {
+    return `file://${isEscapedPath(path) ? path.slice(ESCAPED.length) : path}`;
+}
+
+/**
+ * Readable form of a track path for display
+ */
+export function displayPath(path: string): string {
+    if (!isEscapedPath(path)) {
+        return path;
+    }
+    return path
+        .slice(ESCAPED.length)
+        .replace(/%u[0-9A-F]{4}|%[0-9A-F]{2}/g, (escape) => (escape === '%25' ? '%' : '�'));
+}